@@ -0,0 +1,90 @@
+/*
+ * Prints the records published to a shared-memory ring by `iex-publish --ring`, see src/ring.rs for the layout.
+ *
+ *     cc -O2 -o ring_reader examples/ring_reader.c
+ *     ./ring_reader /dev/shm/iex
+ */
+
+#include <fcntl.h>
+#include <inttypes.h>
+#include <sched.h>
+#include <stdatomic.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+typedef struct RingHeader {
+  char magic[8];
+  uint32_t version;
+  uint32_t record_size;
+  uint64_t capacity;
+  _Atomic uint64_t published;
+  uint8_t reserved[32];
+} RingHeader;
+
+typedef struct RingRecord {
+  _Atomic uint64_t sequence;
+  int64_t timestamp;
+  uint8_t kind;
+  uint8_t code;
+  uint8_t reserved[6];
+  char symbol[8];
+  int64_t price;
+  int64_t price2;
+  uint32_t size;
+  uint32_t size2;
+  int64_t id;
+} RingRecord;
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <ring file>\n", argv[0]);
+    return 2;
+  }
+  int fd = open(argv[1], O_RDONLY);
+  struct stat st;
+  if (fd < 0 || fstat(fd, &st) < 0 || st.st_size < (off_t)sizeof(RingHeader)) {
+    perror(argv[1]);
+    return 1;
+  }
+  const RingHeader *header = mmap(NULL, st.st_size, PROT_READ, MAP_SHARED, fd, 0);
+  if (header == MAP_FAILED || memcmp(header->magic, "IEXRING1", 8) != 0 || header->version != 1 ||
+      header->record_size != sizeof(RingRecord) ||
+      st.st_size < (off_t)(sizeof(RingHeader) + header->capacity * sizeof(RingRecord))) {
+    fprintf(stderr, "%s: not a ring\n", argv[1]);
+    return 1;
+  }
+  const RingRecord *records = (const RingRecord *)(header + 1);
+
+  uint64_t next = atomic_load_explicit(&header->published, memory_order_acquire);
+  for (;;) {
+    uint64_t published = atomic_load_explicit(&header->published, memory_order_acquire);
+    if (next >= published) {
+      sched_yield();
+      continue;
+    }
+
+    /* Copy the record, and keep the copy only if the slot wasn't overwritten meanwhile */
+    const RingRecord *slot = &records[next % header->capacity];
+    uint64_t sequence = atomic_load_explicit(&slot->sequence, memory_order_acquire);
+    RingRecord record;
+    memcpy(&record, slot, sizeof(record));
+    atomic_thread_fence(memory_order_acquire);
+    if (sequence != next + 1 || atomic_load_explicit(&slot->sequence, memory_order_relaxed) != sequence) {
+      published = atomic_load_explicit(&header->published, memory_order_acquire);
+      uint64_t oldest = published > header->capacity ? published - header->capacity : 0;
+      uint64_t skipped = (oldest > next + 1 ? oldest : next + 1) - next;
+      fprintf(stderr, "lapped, %" PRIu64 " records missed\n", skipped);
+      next += skipped;
+      continue;
+    }
+    next++;
+
+    printf("%" PRIu64 " %c %" PRId64 " %.8s %" PRId64 ".%04" PRId64 " %" PRIu32 "\n", record.sequence,
+           record.kind, record.timestamp, record.symbol, record.price / 10000, record.price % 10000, record.size);
+    fflush(stdout);
+  }
+}
@@ -0,0 +1,278 @@
+#ifndef IEX_PARSER_H
+#define IEX_PARSER_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+/**
+ * A message was read
+ */
+#define IEX_OK 0
+
+/**
+ * There are no more messages
+ */
+#define IEX_END 1
+
+/**
+ * The function failed, see `iex_last_error`
+ */
+#define IEX_ERROR -1
+
+/**
+ * A reader of the TOPS messages of a HIST file, opaque to C
+ */
+typedef struct IexReader IexReader;
+
+typedef struct IexSystemEvent {
+  uint8_t event_type;
+} IexSystemEvent;
+
+typedef struct IexSecurityDirectory {
+  bool test_security;
+  bool when_issued;
+  bool etp;
+  uint8_t luld_tier;
+  uint32_t round_lot_size;
+  double adjusted_poc_price;
+} IexSecurityDirectory;
+
+typedef struct IexTradingStatus {
+  uint8_t status;
+  /**
+   * The code of the reason, NUL-terminated
+   */
+  char reason[5];
+} IexTradingStatus;
+
+typedef struct IexRetailLiquidityIndicator {
+  uint8_t indicator;
+} IexRetailLiquidityIndicator;
+
+typedef struct IexOperationalHaltStatus {
+  uint8_t status;
+} IexOperationalHaltStatus;
+
+typedef struct IexShortSalePriceTestStatus {
+  bool in_effect;
+  uint8_t detail;
+} IexShortSalePriceTestStatus;
+
+typedef struct IexQuoteUpdate {
+  bool available;
+  bool out_of_hours;
+  uint32_t bid_size;
+  double bid_price;
+  uint32_t ask_size;
+  double ask_price;
+} IexQuoteUpdate;
+
+/**
+ * A trade report or a trade break
+ */
+typedef struct IexTrade {
+  bool intermarket_sweep;
+  bool extended_hours;
+  bool odd_lot;
+  bool trade_through_exempt;
+  bool single_price;
+  uint32_t size;
+  double price;
+  int64_t id;
+} IexTrade;
+
+typedef struct IexOfficialPrice {
+  uint8_t price_type;
+  double price;
+} IexOfficialPrice;
+
+typedef struct IexAuctionInformation {
+  uint8_t auction_type;
+  uint8_t imbalance_side;
+  uint8_t extension_number;
+  uint32_t paired_shares;
+  uint32_t imbalance_shares;
+  double reference_price;
+  double indicative_clearing_price;
+  /**
+   * In nanoseconds since the Unix epoch
+   */
+  int64_t scheduled_auction_time;
+  double auction_book_clearing_price;
+  double collar_reference_price;
+  double lower_auction_collar;
+  double upper_auction_collar;
+} IexAuctionInformation;
+
+/**
+ * The fields specific to each type of message, the one to read being given by [`IexMessage::kind`]
+ */
+typedef union IexMessageBody {
+  IexSystemEvent system_event;
+  IexSecurityDirectory security_directory;
+  IexTradingStatus trading_status;
+  IexRetailLiquidityIndicator retail_liquidity_indicator;
+  IexOperationalHaltStatus operational_halt_status;
+  IexShortSalePriceTestStatus short_sale_price_test_status;
+  IexQuoteUpdate quote_update;
+  /**
+   * The body of trade reports and trade breaks
+   */
+  IexTrade trade;
+  IexOfficialPrice official_price;
+  IexAuctionInformation auction_information;
+} IexMessageBody;
+
+/**
+ * A decoded TOPS message
+ */
+typedef struct IexMessage {
+  /**
+   * The character code of the type of the message, e.g. `'Q'` for quote updates
+   */
+  uint8_t kind;
+  /**
+   * In nanoseconds since the Unix epoch, or 0 if the message has no timestamp
+   */
+  int64_t timestamp;
+  /**
+   * NUL-terminated, and empty for system events
+   */
+  char symbol[9];
+  IexMessageBody body;
+} IexMessage;
+
+/**
+ * The fields of many messages, as arrays of `capacity` values each, filled by `iex_reader_read_columns`: the fields
+ * of a message are at the same index in every array. The arrays left `NULL` aren't filled, so that hosts only pay for
+ * the fields they need.
+ */
+typedef struct IexColumns {
+  /**
+   * The number of values each array can hold
+   */
+  uintptr_t capacity;
+  /**
+   * See [`IexMessage::kind`]
+   */
+  uint8_t *kind;
+  /**
+   * See [`IexMessage::timestamp`]
+   */
+  int64_t *timestamp;
+  /**
+   * See [`IexMessage::symbol`]
+   */
+  char (*symbol)[9];
+  /**
+   * See [`IexMessage::body`]
+   */
+  IexMessageBody *body;
+} IexColumns;
+
+/**
+ * Called by `iex_reader_for_each` with every message and the context it was given. Returning a non-zero value stops
+ * the iteration.
+ */
+typedef int (*IexCallback)(const IexMessage *message, void *context);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Opens an uncompressed HIST file. Returns `NULL` if it can't be opened or isn't a capture.
+ *
+ * # Safety
+ *
+ * `path` must be a NUL-terminated string.
+ */
+IexReader *iex_reader_open(const char *path);
+
+/**
+ * Reads a HIST file held in memory, e.g. by a WebAssembly host, which has no file system. The buffer is copied, so
+ * it can be freed once the reader is open. Returns `NULL` if it isn't a capture.
+ *
+ * # Safety
+ *
+ * `data` must point to `length` readable bytes.
+ */
+IexReader *iex_reader_open_buffer(const uint8_t *data, uintptr_t length);
+
+/**
+ * Allocates a zeroed buffer of `length` bytes, to be freed by `iex_buffer_free`. WebAssembly hosts copy files into
+ * such buffers before opening them with `iex_reader_open_buffer`, and read messages from them.
+ */
+uint8_t *iex_buffer_alloc(uintptr_t length);
+
+/**
+ * Frees a buffer allocated by `iex_buffer_alloc`. Freeing `NULL` does nothing.
+ *
+ * # Safety
+ *
+ * `data` must have been returned by `iex_buffer_alloc` with the same `length`, and not freed yet.
+ */
+void iex_buffer_free(uint8_t *data, uintptr_t length);
+
+/**
+ * The size of an `IexMessage`, for hosts which don't read the header
+ */
+uintptr_t iex_message_size(void);
+
+/**
+ * Reads the next message into `message`. Returns [`IEX_OK`], [`IEX_END`] at the end of the file, or [`IEX_ERROR`]
+ * if the file is invalid (in which case reading can go on with the following segment).
+ *
+ * # Safety
+ *
+ * `reader` must have been returned by `iex_reader_open` and not closed, and `message` must point to an
+ * `IexMessage`.
+ */
+int iex_reader_next(IexReader *reader, IexMessage *message);
+
+/**
+ * Calls `callback` with every remaining message and `context`, until it returns a non-zero value. Returns
+ * [`IEX_END`] if all the messages were read, [`IEX_OK`] if the callback stopped the iteration, or [`IEX_ERROR`] if
+ * the file is invalid.
+ *
+ * # Safety
+ *
+ * `reader` must have been returned by `iex_reader_open` and not closed. The message passed to the callback is only
+ * valid during the call.
+ */
+int iex_reader_for_each(IexReader *reader, IexCallback callback, void *context);
+
+/**
+ * Reads up to `capacity` messages into `columns`. Returns the number of messages read, which is 0 at the end of the
+ * file, or [`IEX_ERROR`] if the file is invalid (in which case reading can go on with the following segment). When the
+ * file turns out to be invalid after some messages of the batch, these messages are returned, and the next call
+ * fails.
+ *
+ * # Safety
+ *
+ * `reader` must have been returned by `iex_reader_open` and not closed, and the arrays of `columns` which aren't
+ * `NULL` must hold `capacity` values.
+ */
+intptr_t iex_reader_read_columns(IexReader *reader, const IexColumns *columns);
+
+/**
+ * Closes a reader and its file. Closing `NULL` does nothing.
+ *
+ * # Safety
+ *
+ * `reader` must have been returned by `iex_reader_open` and not closed yet.
+ */
+void iex_reader_close(IexReader *reader);
+
+/**
+ * Describes the last failure of a function on the calling thread. The string is valid until the next failure.
+ */
+const char *iex_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* IEX_PARSER_H */
@@ -0,0 +1,11 @@
+{
+  "targets": [
+    {
+      "target_name": "iex_parser",
+      "sources": ["src/addon.c"],
+      "include_dirs": ["../include"],
+      "defines": ["NAPI_VERSION=6"],
+      "libraries": ["<(module_root_dir)/../target/release/libiex_parser.a", "-lpthread", "-ldl", "-lm"]
+    }
+  ]
+}
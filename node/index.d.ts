@@ -0,0 +1,22 @@
+export interface ReadOptions {
+  /** The kinds of messages to keep, by name (e.g. `quote_update`) or code (e.g. `Q`) */
+  kinds?: string | string[];
+  /** The symbols to keep, which may be patterns (e.g. `ZIE*`) */
+  symbols?: string | string[];
+  /** Drops the messages before a time (RFC 3339, or nanoseconds since the epoch) */
+  from?: Date | string | bigint;
+  /** Drops the messages from a time */
+  to?: Date | string | bigint;
+}
+
+/** A decoded message, e.g. `{ kind: 'trade_report', timestamp: 1471980632572715948n, symbol: 'ZIEXT', ... }` */
+export interface Message {
+  kind: string;
+  /** In nanoseconds since the Unix epoch */
+  timestamp: bigint;
+  /** Absent from system events */
+  symbol?: string;
+  [field: string]: string | number | bigint | boolean | undefined;
+}
+
+export function readMessages(file: string, options?: ReadOptions): Generator<Message, void, undefined>;
@@ -0,0 +1,81 @@
+'use strict';
+
+// Reads the TOPS messages of HIST files with a Node-API addon (src/addon.c) over the C API of the crate, linked
+// statically: `npm install` builds the library with `cargo rustc --release --lib --features ffi --crate-type
+// staticlib`, and the addon with node-gyp.
+
+const addon = require('./build/Release/iex_parser.node');
+
+const CODES = {
+  S: 'system_event',
+  D: 'security_directory',
+  H: 'trading_status',
+  I: 'retail_liquidity_indicator',
+  O: 'operational_halt_status',
+  P: 'short_sale_price_test_status',
+  Q: 'quote_update',
+  T: 'trade_report',
+  X: 'official_price',
+  B: 'trade_break',
+  A: 'auction_information',
+};
+
+function kindNames(kinds) {
+  return new Set([].concat(kinds).map((kind) => CODES[kind] ?? kind));
+}
+
+// Symbols may be patterns, where `*` matches any characters
+function symbolMatcher(symbols) {
+  const patterns = [].concat(symbols).map((symbol) => {
+    const escaped = symbol.split('*').map((part) => part.replace(/[.*+?^${}()|[\]\\]/g, '\\$&'));
+    return new RegExp(`^${escaped.join('.*')}$`);
+  });
+  return (symbol) => symbol !== undefined && patterns.some((pattern) => pattern.test(symbol));
+}
+
+// In nanoseconds since the Unix epoch, keeping the nanoseconds of RFC 3339 strings
+function nanoseconds(time) {
+  if (typeof time === 'bigint') {
+    return time;
+  }
+  if (time instanceof Date) {
+    return BigInt(time.getTime()) * 1000000n;
+  }
+  const match = /^(.*?)(?:\.(\d{1,9}))?(Z|[+-]\d\d:\d\d)$/i.exec(String(time));
+  const milliseconds = match ? Date.parse(match[1] + match[3]) : NaN;
+  if (Number.isNaN(milliseconds)) {
+    throw new RangeError(`invalid time ${time}`);
+  }
+  return BigInt(milliseconds) * 1000000n + BigInt((match[2] ?? '').padEnd(9, '0'));
+}
+
+/**
+ * Reads the TOPS messages of an uncompressed HIST file (a pcap or pcapng capture) as objects with a `kind` and the
+ * fields of the message, named as in the specification in snake case. Timestamps are BigInts of nanoseconds since the
+ * Unix epoch, codes are one-character strings, and prices are numbers.
+ *
+ * Messages are read lazily, and breaking out of the iteration closes the file. Throws if the file can't be opened,
+ * and when reading an invalid segment.
+ */
+function* readMessages(file, options = {}) {
+  const kinds = options.kinds === undefined ? null : kindNames(options.kinds);
+  const matches = options.symbols === undefined ? null : symbolMatcher(options.symbols);
+  const from = options.from === undefined ? null : nanoseconds(options.from);
+  const to = options.to === undefined ? null : nanoseconds(options.to);
+
+  const reader = addon.open(file);
+  try {
+    for (let message = addon.next(reader); message !== null; message = addon.next(reader)) {
+      if ((kinds === null || kinds.has(message.kind))
+        && (matches === null || matches(message.symbol))
+        && (from === null || message.timestamp >= from)
+        && (to === null || message.timestamp < to)) {
+        yield message;
+      }
+    }
+  } finally {
+    addon.close(reader);
+  }
+}
+
+module.exports = { readMessages };
@@ -0,0 +1,273 @@
+// A Node-API addon over the C API of the crate (include/iex_parser.h), turning its messages into JavaScript objects
+
+#include <stdlib.h>
+
+#include <node_api.h>
+
+#include "iex_parser.h"
+
+#define CHECK(call)                                  \
+  do {                                               \
+    if ((call) != napi_ok) {                         \
+      napi_throw_error(env, NULL, "N-API failure");  \
+      return NULL;                                   \
+    }                                                \
+  } while (0)
+
+// The external value wrapping a reader, which is NULL once closed
+typedef struct Reader {
+  IexReader *reader;
+} Reader;
+
+static const char *kind_name(uint8_t kind) {
+  switch (kind) {
+    case 'S': return "system_event";
+    case 'D': return "security_directory";
+    case 'H': return "trading_status";
+    case 'I': return "retail_liquidity_indicator";
+    case 'O': return "operational_halt_status";
+    case 'P': return "short_sale_price_test_status";
+    case 'Q': return "quote_update";
+    case 'T': return "trade_report";
+    case 'X': return "official_price";
+    case 'B': return "trade_break";
+    case 'A': return "auction_information";
+    default: return NULL;
+  }
+}
+
+static napi_status set_string(napi_env env, napi_value object, const char *name, const char *value) {
+  napi_value string;
+  napi_status status = napi_create_string_utf8(env, value, NAPI_AUTO_LENGTH, &string);
+  return status == napi_ok ? napi_set_named_property(env, object, name, string) : status;
+}
+
+static napi_status set_code(napi_env env, napi_value object, const char *name, uint8_t code) {
+  char string[2] = {(char)code, 0};
+  return set_string(env, object, name, string);
+}
+
+static napi_status set_bool(napi_env env, napi_value object, const char *name, bool value) {
+  napi_value boolean;
+  napi_status status = napi_get_boolean(env, value, &boolean);
+  return status == napi_ok ? napi_set_named_property(env, object, name, boolean) : status;
+}
+
+static napi_status set_number(napi_env env, napi_value object, const char *name, double value) {
+  napi_value number;
+  napi_status status = napi_create_double(env, value, &number);
+  return status == napi_ok ? napi_set_named_property(env, object, name, number) : status;
+}
+
+static napi_status set_bigint(napi_env env, napi_value object, const char *name, int64_t value) {
+  napi_value bigint;
+  napi_status status = napi_create_bigint_int64(env, value, &bigint);
+  return status == napi_ok ? napi_set_named_property(env, object, name, bigint) : status;
+}
+
+static napi_status set_trade(napi_env env, napi_value object, const IexTrade *trade) {
+  napi_status status;
+  if ((status = set_number(env, object, "size", trade->size)) != napi_ok ||
+      (status = set_number(env, object, "price", trade->price)) != napi_ok ||
+      (status = set_bigint(env, object, "id", trade->id)) != napi_ok ||
+      (status = set_bool(env, object, "intermarket_sweep", trade->intermarket_sweep)) != napi_ok ||
+      (status = set_bool(env, object, "extended_hours", trade->extended_hours)) != napi_ok ||
+      (status = set_bool(env, object, "odd_lot", trade->odd_lot)) != napi_ok ||
+      (status = set_bool(env, object, "trade_through_exempt", trade->trade_through_exempt)) != napi_ok) {
+    return status;
+  }
+  return set_bool(env, object, "single_price", trade->single_price);
+}
+
+// Sets the fields of the body of a message, named as in wasm/index.js
+static napi_status set_body(napi_env env, napi_value object, const IexMessage *message) {
+  const IexMessageBody *body = &message->body;
+  napi_status status = napi_ok;
+  switch (message->kind) {
+    case 'S':
+      return set_code(env, object, "event_type", body->system_event.event_type);
+    case 'D': {
+      const IexSecurityDirectory *directory = &body->security_directory;
+      if ((status = set_bool(env, object, "test_security", directory->test_security)) != napi_ok ||
+          (status = set_bool(env, object, "when_issued", directory->when_issued)) != napi_ok ||
+          (status = set_bool(env, object, "etp", directory->etp)) != napi_ok ||
+          (status = set_number(env, object, "round_lot_size", directory->round_lot_size)) != napi_ok ||
+          (status = set_number(env, object, "adjusted_poc_price", directory->adjusted_poc_price)) != napi_ok) {
+        return status;
+      }
+      return set_number(env, object, "luld_tier", directory->luld_tier);
+    }
+    case 'H':
+      if ((status = set_code(env, object, "status", body->trading_status.status)) != napi_ok) {
+        return status;
+      }
+      return set_string(env, object, "reason", body->trading_status.reason);
+    case 'I':
+      return set_code(env, object, "indicator", body->retail_liquidity_indicator.indicator);
+    case 'O':
+      return set_code(env, object, "status", body->operational_halt_status.status);
+    case 'P':
+      if ((status = set_bool(env, object, "in_effect", body->short_sale_price_test_status.in_effect)) != napi_ok) {
+        return status;
+      }
+      return set_code(env, object, "detail", body->short_sale_price_test_status.detail);
+    case 'Q': {
+      const IexQuoteUpdate *quote = &body->quote_update;
+      if ((status = set_bool(env, object, "available", quote->available)) != napi_ok ||
+          (status = set_bool(env, object, "out_of_hours", quote->out_of_hours)) != napi_ok ||
+          (status = set_number(env, object, "bid_size", quote->bid_size)) != napi_ok ||
+          (status = set_number(env, object, "bid_price", quote->bid_price)) != napi_ok ||
+          (status = set_number(env, object, "ask_size", quote->ask_size)) != napi_ok) {
+        return status;
+      }
+      return set_number(env, object, "ask_price", quote->ask_price);
+    }
+    case 'T':
+    case 'B':
+      return set_trade(env, object, &body->trade);
+    case 'X':
+      if ((status = set_code(env, object, "price_type", body->official_price.price_type)) != napi_ok) {
+        return status;
+      }
+      return set_number(env, object, "official_price", body->official_price.price);
+    case 'A': {
+      const IexAuctionInformation *auction = &body->auction_information;
+      if ((status = set_code(env, object, "auction_type", auction->auction_type)) != napi_ok ||
+          (status = set_number(env, object, "paired_shares", auction->paired_shares)) != napi_ok ||
+          (status = set_number(env, object, "reference_price", auction->reference_price)) != napi_ok ||
+          (status = set_number(env, object, "indicative_clearing_price", auction->indicative_clearing_price)) !=
+              napi_ok ||
+          (status = set_number(env, object, "imbalance_shares", auction->imbalance_shares)) != napi_ok ||
+          (status = set_code(env, object, "imbalance_side", auction->imbalance_side)) != napi_ok ||
+          (status = set_number(env, object, "extension_number", auction->extension_number)) != napi_ok ||
+          (status = set_bigint(env, object, "scheduled_auction_time", auction->scheduled_auction_time)) != napi_ok ||
+          (status = set_number(env, object, "auction_book_clearing_price", auction->auction_book_clearing_price)) !=
+              napi_ok ||
+          (status = set_number(env, object, "collar_reference_price", auction->collar_reference_price)) != napi_ok ||
+          (status = set_number(env, object, "lower_auction_collar", auction->lower_auction_collar)) != napi_ok) {
+        return status;
+      }
+      return set_number(env, object, "upper_auction_collar", auction->upper_auction_collar);
+    }
+    default:
+      return status;
+  }
+}
+
+static void finalize(napi_env env, void *data, void *hint) {
+  (void)env;
+  (void)hint;
+  Reader *reader = data;
+  iex_reader_close(reader->reader);
+  free(reader);
+}
+
+// Gets the reader wrapped by the first argument, throwing if it has been closed
+static Reader *get_reader(napi_env env, napi_callback_info info) {
+  size_t argc = 1;
+  napi_value argv[1];
+  void *data = NULL;
+  if (napi_get_cb_info(env, info, &argc, argv, NULL, NULL) != napi_ok || argc < 1 ||
+      napi_get_value_external(env, argv[0], &data) != napi_ok) {
+    napi_throw_type_error(env, NULL, "expected a reader");
+    return NULL;
+  }
+  return data;
+}
+
+// open(path): opens an uncompressed HIST file, returning a reader
+static napi_value open_reader(napi_env env, napi_callback_info info) {
+  size_t argc = 1;
+  napi_value argv[1];
+  CHECK(napi_get_cb_info(env, info, &argc, argv, NULL, NULL));
+  size_t length;
+  if (argc < 1 || napi_get_value_string_utf8(env, argv[0], NULL, 0, &length) != napi_ok) {
+    napi_throw_type_error(env, NULL, "expected a path");
+    return NULL;
+  }
+  char *path = malloc(length + 1);
+  Reader *reader = malloc(sizeof(Reader));
+  if (path == NULL || reader == NULL) {
+    free(path);
+    free(reader);
+    napi_throw_error(env, NULL, "out of memory");
+    return NULL;
+  }
+  napi_get_value_string_utf8(env, argv[0], path, length + 1, &length);
+  reader->reader = iex_reader_open(path);
+  free(path);
+  if (reader->reader == NULL) {
+    free(reader);
+    napi_throw_error(env, NULL, iex_last_error());
+    return NULL;
+  }
+
+  napi_value external;
+  if (napi_create_external(env, reader, finalize, NULL, &external) != napi_ok) {
+    finalize(env, reader, NULL);
+    napi_throw_error(env, NULL, "N-API failure");
+    return NULL;
+  }
+  return external;
+}
+
+// next(reader): reads the next message, returning null at the end of the file
+static napi_value next_message(napi_env env, napi_callback_info info) {
+  Reader *reader = get_reader(env, info);
+  if (reader == NULL) {
+    return NULL;
+  }
+  if (reader->reader == NULL) {
+    napi_throw_error(env, NULL, "the reader is closed");
+    return NULL;
+  }
+
+  IexMessage message;
+  napi_value result;
+  int status = iex_reader_next(reader->reader, &message);
+  if (status == IEX_END) {
+    CHECK(napi_get_null(env, &result));
+    return result;
+  }
+  if (status == IEX_ERROR) {
+    napi_throw_error(env, NULL, iex_last_error());
+    return NULL;
+  }
+
+  const char *kind = kind_name(message.kind);
+  if (kind == NULL) {
+    napi_throw_error(env, NULL, "unknown type of message");
+    return NULL;
+  }
+  CHECK(napi_create_object(env, &result));
+  CHECK(set_string(env, result, "kind", kind));
+  CHECK(set_bigint(env, result, "timestamp", message.timestamp));
+  if (message.kind != 'S') {
+    CHECK(set_string(env, result, "symbol", message.symbol));
+  }
+  CHECK(set_body(env, result, &message));
+  return result;
+}
+
+// close(reader): closes the file of a reader, which is also closed when garbage collected
+static napi_value close_reader(napi_env env, napi_callback_info info) {
+  Reader *reader = get_reader(env, info);
+  if (reader == NULL) {
+    return NULL;
+  }
+  iex_reader_close(reader->reader);
+  reader->reader = NULL;
+  return NULL;
+}
+
+static napi_value init(napi_env env, napi_value exports) {
+  napi_property_descriptor properties[] = {
+      {"open", NULL, open_reader, NULL, NULL, NULL, napi_default, NULL},
+      {"next", NULL, next_message, NULL, NULL, NULL, napi_default, NULL},
+      {"close", NULL, close_reader, NULL, NULL, NULL, napi_default, NULL},
+  };
+  CHECK(napi_define_properties(env, exports, sizeof(properties) / sizeof(properties[0]), properties));
+  return exports;
+}
+
+NAPI_MODULE(NODE_GYP_MODULE_NAME, init)
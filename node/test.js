@@ -0,0 +1,60 @@
+'use strict';
+
+// Run with `npm test` after `npm install` and `cargo build --features cli`
+
+const assert = require('node:assert');
+const { execFileSync } = require('node:child_process');
+const fs = require('node:fs');
+const os = require('node:os');
+const path = require('node:path');
+const { test } = require('node:test');
+
+const { readMessages } = require('./index.js');
+
+const directory = fs.mkdtempSync(path.join(os.tmpdir(), 'iex-parser-node-'));
+const capture = path.join(directory, 'corpus.pcap');
+execFileSync(path.join(__dirname, '..', 'target', 'debug', 'iex-corpus'), [
+  '--size', '200K', '--symbols', '20', '--output', capture,
+], { stdio: 'ignore' });
+
+test('reads every message', () => {
+  const kinds = new Map();
+  let first;
+  let last;
+  for (const message of readMessages(capture)) {
+    first ??= message;
+    last = message;
+    kinds.set(message.kind, (kinds.get(message.kind) ?? 0) + 1);
+  }
+  assert.deepStrictEqual(first, { kind: 'system_event', timestamp: 1492435800000000000n, event_type: 'O' });
+  assert.strictEqual(last.event_type, 'C');
+  assert.ok(kinds.get('quote_update') > kinds.get('trade_report'));
+});
+
+test('filters and stops early', () => {
+  const trades = [];
+  for (const message of readMessages(capture, { kinds: ['T'], symbols: 'A' })) {
+    trades.push(message);
+    if (trades.length === 3) {
+      break;
+    }
+  }
+  assert.strictEqual(trades.length, 3);
+  assert.ok(trades.every((trade) => trade.kind === 'trade_report' && trade.symbol === 'A'));
+  assert.ok(trades.every((trade) => typeof trade.price === 'number' && typeof trade.id === 'bigint'));
+});
+
+test('filters by time', () => {
+  const options = { symbols: ['A*', 'B'], from: '2017-04-17T13:30:00.005Z', to: 1492435800015000000n };
+  const messages = [...readMessages(capture, options)];
+  assert.ok(messages.length > 0);
+  assert.ok(messages.every((message) => ['A', 'B'].includes(message.symbol)));
+  assert.ok(messages.every((message) => message.timestamp >= 1492435800005000000n));
+  assert.ok(messages.every((message) => message.timestamp < 1492435800015000000n));
+});
+
+test('fails on invalid files', () => {
+  assert.throws(() => [...readMessages(path.join(directory, 'missing.pcap'))], /I\/O error/);
+});
+
+test.after(() => fs.rmSync(directory, { recursive: true }));
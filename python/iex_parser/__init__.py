@@ -0,0 +1,465 @@
+"""Python bindings of iex-parser, reading the TOPS messages of HIST files at native speed
+
+The bindings load the C API of the crate (see include/iex_parser.h), built as a shared library with
+`cargo rustc --release --lib --features ffi --crate-type cdylib`. The library is found through the IEX_PARSER_LIB environment variable, next to
+this package, in the target directory of the repository, or in the system paths, in that order.
+
+    from iex_parser import QuoteUpdate, TopsReader
+
+    with TopsReader("20170417_IEXTP1_TOPS1.6.pcap") as reader:
+        for message in reader:
+            if isinstance(message, QuoteUpdate):
+                print(message.symbol, message.bid_price, message.ask_price)
+
+Large files are read much faster in batches of numpy arrays, with `TopsReader.batches`.
+"""
+
+import ctypes
+import ctypes.util
+import os
+from dataclasses import dataclass
+from pathlib import Path
+
+__all__ = [
+    "TopsReader",
+    "Batch",
+    "SystemEvent",
+    "SecurityDirectory",
+    "TradingStatus",
+    "RetailLiquidityIndicator",
+    "OperationalHaltStatus",
+    "ShortSalePriceTestStatus",
+    "QuoteUpdate",
+    "TradeReport",
+    "OfficialPrice",
+    "TradeBreak",
+    "AuctionInformation",
+]
+
+IEX_OK = 0
+IEX_END = 1
+IEX_ERROR = -1
+
+
+class _SystemEvent(ctypes.Structure):
+    _fields_ = [("event_type", ctypes.c_uint8)]
+
+
+class _SecurityDirectory(ctypes.Structure):
+    _fields_ = [
+        ("test_security", ctypes.c_bool),
+        ("when_issued", ctypes.c_bool),
+        ("etp", ctypes.c_bool),
+        ("luld_tier", ctypes.c_uint8),
+        ("round_lot_size", ctypes.c_uint32),
+        ("adjusted_poc_price", ctypes.c_double),
+    ]
+
+
+class _TradingStatus(ctypes.Structure):
+    _fields_ = [("status", ctypes.c_uint8), ("reason", ctypes.c_char * 5)]
+
+
+class _RetailLiquidityIndicator(ctypes.Structure):
+    _fields_ = [("indicator", ctypes.c_uint8)]
+
+
+class _OperationalHaltStatus(ctypes.Structure):
+    _fields_ = [("status", ctypes.c_uint8)]
+
+
+class _ShortSalePriceTestStatus(ctypes.Structure):
+    _fields_ = [("in_effect", ctypes.c_bool), ("detail", ctypes.c_uint8)]
+
+
+class _QuoteUpdate(ctypes.Structure):
+    _fields_ = [
+        ("available", ctypes.c_bool),
+        ("out_of_hours", ctypes.c_bool),
+        ("bid_size", ctypes.c_uint32),
+        ("bid_price", ctypes.c_double),
+        ("ask_size", ctypes.c_uint32),
+        ("ask_price", ctypes.c_double),
+    ]
+
+
+class _Trade(ctypes.Structure):
+    _fields_ = [
+        ("intermarket_sweep", ctypes.c_bool),
+        ("extended_hours", ctypes.c_bool),
+        ("odd_lot", ctypes.c_bool),
+        ("trade_through_exempt", ctypes.c_bool),
+        ("single_price", ctypes.c_bool),
+        ("size", ctypes.c_uint32),
+        ("price", ctypes.c_double),
+        ("id", ctypes.c_int64),
+    ]
+
+
+class _OfficialPrice(ctypes.Structure):
+    _fields_ = [("price_type", ctypes.c_uint8), ("price", ctypes.c_double)]
+
+
+class _AuctionInformation(ctypes.Structure):
+    _fields_ = [
+        ("auction_type", ctypes.c_uint8),
+        ("imbalance_side", ctypes.c_uint8),
+        ("extension_number", ctypes.c_uint8),
+        ("paired_shares", ctypes.c_uint32),
+        ("imbalance_shares", ctypes.c_uint32),
+        ("reference_price", ctypes.c_double),
+        ("indicative_clearing_price", ctypes.c_double),
+        ("scheduled_auction_time", ctypes.c_int64),
+        ("auction_book_clearing_price", ctypes.c_double),
+        ("collar_reference_price", ctypes.c_double),
+        ("lower_auction_collar", ctypes.c_double),
+        ("upper_auction_collar", ctypes.c_double),
+    ]
+
+
+class _MessageBody(ctypes.Union):
+    _fields_ = [
+        ("system_event", _SystemEvent),
+        ("security_directory", _SecurityDirectory),
+        ("trading_status", _TradingStatus),
+        ("retail_liquidity_indicator", _RetailLiquidityIndicator),
+        ("operational_halt_status", _OperationalHaltStatus),
+        ("short_sale_price_test_status", _ShortSalePriceTestStatus),
+        ("quote_update", _QuoteUpdate),
+        ("trade", _Trade),
+        ("official_price", _OfficialPrice),
+        ("auction_information", _AuctionInformation),
+    ]
+
+
+class _Message(ctypes.Structure):
+    _fields_ = [
+        ("kind", ctypes.c_uint8),
+        ("timestamp", ctypes.c_int64),
+        ("symbol", ctypes.c_char * 9),
+        ("body", _MessageBody),
+    ]
+
+
+class _Columns(ctypes.Structure):
+    _fields_ = [
+        ("capacity", ctypes.c_size_t),
+        ("kind", ctypes.c_void_p),
+        ("timestamp", ctypes.c_void_p),
+        ("symbol", ctypes.c_void_p),
+        ("body", ctypes.c_void_p),
+    ]
+
+
+# Timestamps are in nanoseconds since the Unix epoch, and codes are one-character strings, as in the specification
+
+
+@dataclass(frozen=True)
+class SystemEvent:
+    timestamp: int
+    event_type: str
+
+
+@dataclass(frozen=True)
+class SecurityDirectory:
+    timestamp: int
+    symbol: str
+    test_security: bool
+    when_issued: bool
+    etp: bool
+    round_lot_size: int
+    adjusted_poc_price: float
+    luld_tier: int
+
+
+@dataclass(frozen=True)
+class TradingStatus:
+    timestamp: int
+    symbol: str
+    status: str
+    reason: str
+
+
+@dataclass(frozen=True)
+class RetailLiquidityIndicator:
+    timestamp: int
+    symbol: str
+    indicator: str
+
+
+@dataclass(frozen=True)
+class OperationalHaltStatus:
+    timestamp: int
+    symbol: str
+    status: str
+
+
+@dataclass(frozen=True)
+class ShortSalePriceTestStatus:
+    timestamp: int
+    symbol: str
+    in_effect: bool
+    detail: str
+
+
+@dataclass(frozen=True)
+class QuoteUpdate:
+    timestamp: int
+    symbol: str
+    available: bool
+    out_of_hours: bool
+    bid_size: int
+    bid_price: float
+    ask_size: int
+    ask_price: float
+
+
+@dataclass(frozen=True)
+class TradeReport:
+    timestamp: int
+    symbol: str
+    size: int
+    price: float
+    id: int
+    intermarket_sweep: bool
+    extended_hours: bool
+    odd_lot: bool
+    trade_through_exempt: bool
+    single_price: bool
+
+
+@dataclass(frozen=True)
+class OfficialPrice:
+    timestamp: int
+    symbol: str
+    price_type: str
+    official_price: float
+
+
+@dataclass(frozen=True)
+class TradeBreak:
+    timestamp: int
+    symbol: str
+    size: int
+    price: float
+    id: int
+    intermarket_sweep: bool
+    extended_hours: bool
+    odd_lot: bool
+    trade_through_exempt: bool
+    single_price: bool
+
+
+@dataclass(frozen=True)
+class AuctionInformation:
+    timestamp: int
+    symbol: str
+    auction_type: str
+    paired_shares: int
+    reference_price: float
+    indicative_clearing_price: float
+    imbalance_shares: int
+    imbalance_side: str
+    extension_number: int
+    scheduled_auction_time: int
+    auction_book_clearing_price: float
+    collar_reference_price: float
+    lower_auction_collar: float
+    upper_auction_collar: float
+
+
+def _trade(cls, message):
+    trade = message.body.trade
+    return cls(
+        message.timestamp,
+        message.symbol.decode(),
+        trade.size,
+        trade.price,
+        trade.id,
+        trade.intermarket_sweep,
+        trade.extended_hours,
+        trade.odd_lot,
+        trade.trade_through_exempt,
+        trade.single_price,
+    )
+
+
+def _convert(message):
+    kind = chr(message.kind)
+    body = message.body
+    symbol = message.symbol.decode()
+    if kind == "Q":
+        quote = body.quote_update
+        return QuoteUpdate(
+            message.timestamp,
+            symbol,
+            quote.available,
+            quote.out_of_hours,
+            quote.bid_size,
+            quote.bid_price,
+            quote.ask_size,
+            quote.ask_price,
+        )
+    if kind == "T":
+        return _trade(TradeReport, message)
+    if kind == "B":
+        return _trade(TradeBreak, message)
+    if kind == "S":
+        return SystemEvent(message.timestamp, chr(body.system_event.event_type))
+    if kind == "D":
+        directory = body.security_directory
+        return SecurityDirectory(
+            message.timestamp,
+            symbol,
+            directory.test_security,
+            directory.when_issued,
+            directory.etp,
+            directory.round_lot_size,
+            directory.adjusted_poc_price,
+            directory.luld_tier,
+        )
+    if kind == "H":
+        status = body.trading_status
+        return TradingStatus(message.timestamp, symbol, chr(status.status), status.reason.decode())
+    if kind == "I":
+        return RetailLiquidityIndicator(message.timestamp, symbol, chr(body.retail_liquidity_indicator.indicator))
+    if kind == "O":
+        return OperationalHaltStatus(message.timestamp, symbol, chr(body.operational_halt_status.status))
+    if kind == "P":
+        status = body.short_sale_price_test_status
+        return ShortSalePriceTestStatus(message.timestamp, symbol, status.in_effect, chr(status.detail))
+    if kind == "X":
+        price = body.official_price
+        return OfficialPrice(message.timestamp, symbol, chr(price.price_type), price.price)
+    if kind == "A":
+        auction = body.auction_information
+        return AuctionInformation(
+            message.timestamp,
+            symbol,
+            chr(auction.auction_type),
+            auction.paired_shares,
+            auction.reference_price,
+            auction.indicative_clearing_price,
+            auction.imbalance_shares,
+            chr(auction.imbalance_side),
+            auction.extension_number,
+            auction.scheduled_auction_time,
+            auction.auction_book_clearing_price,
+            auction.collar_reference_price,
+            auction.lower_auction_collar,
+            auction.upper_auction_collar,
+        )
+    raise ValueError(f"unknown message type {kind!r}")
+
+
+@dataclass(frozen=True)
+class Batch:
+    """Messages read by `TopsReader.batches`, as numpy arrays of the same length
+
+    The kinds are one-byte strings (e.g. b"Q"), the timestamps datetime64[ns] values and the symbols byte strings
+    (empty for system events). The bodies are a structured array with a field per type of body (e.g. `quote_update`,
+    or `trade` for trade reports and breaks), laid out as in include/iex_parser.h, of which only the one matching the
+    kind of the message is set: `batch.body[batch.kind == b"Q"]["quote_update"]["bid_price"]` are the bid prices of
+    the quotes of the batch.
+    """
+
+    kind: "numpy.ndarray"
+    timestamp: "numpy.ndarray"
+    symbol: "numpy.ndarray"
+    body: "numpy.ndarray"
+
+
+def _find_library():
+    if "IEX_PARSER_LIB" in os.environ:
+        return os.environ["IEX_PARSER_LIB"]
+    package = Path(__file__).resolve().parent
+    for directory in [package, package.parents[1] / "target" / "release", package.parents[1] / "target" / "debug"]:
+        for name in ["libiex_parser.so", "libiex_parser.dylib", "iex_parser.dll"]:
+            if (directory / name).exists():
+                return str(directory / name)
+    return ctypes.util.find_library("iex_parser")
+
+
+def _load():
+    path = _find_library()
+    if path is None:
+        raise ImportError(
+            "the iex_parser library wasn't found, build it with "
+            "`cargo rustc --release --lib --features ffi --crate-type cdylib`"
+        )
+    library = ctypes.CDLL(path)
+    library.iex_reader_open.argtypes = [ctypes.c_char_p]
+    library.iex_reader_open.restype = ctypes.c_void_p
+    library.iex_reader_next.argtypes = [ctypes.c_void_p, ctypes.POINTER(_Message)]
+    library.iex_reader_next.restype = ctypes.c_int
+    library.iex_reader_read_columns.argtypes = [ctypes.c_void_p, ctypes.POINTER(_Columns)]
+    library.iex_reader_read_columns.restype = ctypes.c_ssize_t
+    library.iex_reader_close.argtypes = [ctypes.c_void_p]
+    library.iex_reader_close.restype = None
+    library.iex_last_error.argtypes = []
+    library.iex_last_error.restype = ctypes.c_char_p
+    return library
+
+
+_library = _load()
+
+
+class TopsReader:
+    """Iterates over the TOPS messages of an uncompressed HIST file, as instances of the message classes
+
+    Raises OSError if the file can't be opened, and ValueError when reading an invalid segment.
+    """
+
+    def __init__(self, path):
+        self._reader = _library.iex_reader_open(os.fsencode(path))
+        if not self._reader:
+            raise OSError(_library.iex_last_error().decode())
+        self._message = _Message()
+
+    def __iter__(self):
+        return self
+
+    def __next__(self):
+        if not self._reader:
+            raise StopIteration
+        status = _library.iex_reader_next(self._reader, ctypes.byref(self._message))
+        if status == IEX_END:
+            raise StopIteration
+        if status == IEX_ERROR:
+            raise ValueError(_library.iex_last_error().decode())
+        return _convert(self._message)
+
+    def batches(self, size=65536):
+        """Iterates over the remaining messages in batches of up to `size` messages, as `Batch` instances
+
+        Decoding the messages into numpy arrays rather than Python objects is much faster for large files. Requires
+        numpy.
+        """
+        import numpy
+
+        while self._reader:
+            kind = numpy.empty(size, dtype="S1")
+            timestamp = numpy.empty(size, dtype="datetime64[ns]")
+            symbol = numpy.empty(size, dtype="S9")
+            body = numpy.empty(size, dtype=numpy.dtype(_MessageBody))
+            columns = _Columns(size, kind.ctypes.data, timestamp.ctypes.data, symbol.ctypes.data, body.ctypes.data)
+            count = _library.iex_reader_read_columns(self._reader, ctypes.byref(columns))
+            if count == IEX_ERROR:
+                raise ValueError(_library.iex_last_error().decode())
+            if count == 0:
+                return
+            yield Batch(kind[:count], timestamp[:count], symbol[:count], body[:count])
+
+    def close(self):
+        if self._reader:
+            _library.iex_reader_close(self._reader)
+            self._reader = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __del__(self):
+        self.close()
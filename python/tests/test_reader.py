@@ -0,0 +1,81 @@
+"""Tests of the Python bindings, over a corpus generated by iex-corpus
+
+Run with `PYTHONPATH=python python -m unittest discover python/tests` after `cargo build --features cli` and
+`cargo rustc --release --lib --features ffi --crate-type cdylib`.
+"""
+
+import subprocess
+import tempfile
+import unittest
+from collections import Counter
+from pathlib import Path
+
+from iex_parser import QuoteUpdate, SystemEvent, TopsReader, TradeReport
+
+try:
+    import numpy
+except ImportError:
+    numpy = None
+
+REPOSITORY = Path(__file__).resolve().parents[2]
+
+
+class TopsReaderTest(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.directory = tempfile.TemporaryDirectory()
+        cls.path = Path(cls.directory.name) / "corpus.pcap"
+        subprocess.run(
+            [REPOSITORY / "target" / "debug" / "iex-corpus", "--size", "200K", "--symbols", "20", "--output", cls.path],
+            check=True,
+            capture_output=True,
+        )
+
+    @classmethod
+    def tearDownClass(cls):
+        cls.directory.cleanup()
+
+    def test_messages(self):
+        with TopsReader(self.path) as reader:
+            messages = list(reader)
+
+        self.assertEqual(messages[0], SystemEvent(messages[0].timestamp, "O"))
+        self.assertEqual(messages[-1].event_type, "C")
+        kinds = Counter(type(message) for message in messages)
+        self.assertGreater(kinds[QuoteUpdate], kinds[TradeReport])
+        self.assertGreater(kinds[TradeReport], 0)
+
+        quote = next(message for message in messages if isinstance(message, QuoteUpdate))
+        self.assertTrue(quote.symbol)
+        self.assertLess(quote.bid_price, quote.ask_price)
+        timestamps = [message.timestamp for message in messages]
+        self.assertEqual(timestamps, sorted(timestamps))
+
+    @unittest.skipIf(numpy is None, "numpy isn't installed")
+    def test_batches(self):
+        with TopsReader(self.path) as reader:
+            messages = list(reader)
+        with TopsReader(self.path) as reader:
+            batches = list(reader.batches(1000))
+
+        self.assertTrue(all(len(batch.kind) == 1000 for batch in batches[:-1]))
+        kind = numpy.concatenate([batch.kind for batch in batches])
+        timestamp = numpy.concatenate([batch.timestamp for batch in batches])
+        symbol = numpy.concatenate([batch.symbol for batch in batches])
+        self.assertEqual(len(kind), len(messages))
+        self.assertEqual(timestamp.astype("int64").tolist(), [message.timestamp for message in messages])
+        self.assertEqual(symbol.tolist(), [getattr(message, "symbol", "").encode() for message in messages])
+
+        quotes = numpy.concatenate([batch.body[batch.kind == b"Q"]["quote_update"] for batch in batches])
+        self.assertEqual(
+            quotes["bid_price"].tolist(),
+            [message.bid_price for message in messages if isinstance(message, QuoteUpdate)],
+        )
+
+    def test_missing_file(self):
+        with self.assertRaises(OSError):
+            TopsReader(Path(self.directory.name) / "missing.pcap")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,442 @@
+use std::{
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::{Deep1_0Message, PriceLevelUpdate, SecurityEvent},
+    tops::{
+        AuctionInformation, MarketSession, OfficialPrice, OperationalHaltStatus, QuoteUpdate,
+        RetailLiquidityIndicator, SaleCondition, SecurityDirectory, SecurityDirectoryFlags,
+        ShortSalePriceTestStatus, SystemEvent, Tops1_6Message, TradeBreak, TradeReport,
+        TradingStatus, TradingStatusReason,
+    },
+};
+
+/// A deterministic source of random values (xorshift64*), seeded so that failing cases can be reproduced
+#[derive(Clone, Debug)]
+pub struct Gen {
+    state: u64,
+}
+
+impl Gen {
+    pub fn new(seed: u64) -> Self {
+        // The state must not be zero
+        Self {
+            state: (seed ^ 0x9E3779B97F4A7C15) | 1,
+        }
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// A uniform number below a bound (which must be positive)
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.u64() % bound
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.u64() & 1 == 1
+    }
+
+    /// One of the given values
+    pub fn choose<T: Clone>(&mut self, values: &[T]) -> T {
+        values[self.below(values.len() as u64) as usize].clone()
+    }
+
+    /// A value of a type identified by a byte code (e.g. the enumerations of the specifications)
+    pub fn code<T: TryFrom<u8>>(&mut self) -> T {
+        loop {
+            if let Ok(value) = T::try_from(self.u64() as u8) {
+                return value;
+            }
+        }
+    }
+
+    /// A price as parsed from the wire: a whole number of ten-thousandths, of at most a hundred million dollars
+    pub fn price(&mut self) -> f64 {
+        let ticks = self.below(2_000_000_000_000) as i64 - 1_000_000_000_000;
+        (ticks as f64) * 1e-4
+    }
+
+    /// A symbol of one to eight uppercase letters, as parsed from the wire
+    pub fn symbol(&mut self) -> String {
+        (0..1 + self.below(8))
+            .map(|_| char::from(b'A' + self.below(26) as u8))
+            .collect()
+    }
+
+    /// A byte string of at most some length
+    pub fn bytes(&mut self, max_length: usize) -> Vec<u8> {
+        (0..self.below(max_length as u64 + 1))
+            .map(|_| self.u64() as u8)
+            .collect()
+    }
+}
+
+/// Values which can be generated at random, e.g. to check properties of the parsers over many messages
+///
+/// Generated messages only hold values which the wire format can represent, so they can be compared after a
+/// round-trip through their encoding.
+pub trait Arbitrary: Sized {
+    fn arbitrary(g: &mut Gen) -> Self;
+}
+
+/// Checks a property over values generated from the seeds `0..cases`, panicking with the seed and the value of the
+/// first failing case
+pub fn check<T, F>(cases: u64, mut property: F)
+where
+    T: Arbitrary + Debug,
+    F: FnMut(T),
+{
+    for seed in 0..cases {
+        let value = T::arbitrary(&mut Gen::new(seed));
+        let shown = format!("{value:?}");
+        if let Err(cause) = panic::catch_unwind(AssertUnwindSafe(|| property(value))) {
+            let cause = cause
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| cause.downcast_ref::<&str>().copied())
+                .unwrap_or("");
+            panic!("property failed with seed {seed} for {shown}: {cause}");
+        }
+    }
+}
+
+impl Arbitrary for bool {
+    fn arbitrary(g: &mut Gen) -> Self {
+        g.bool()
+    }
+}
+
+impl Arbitrary for u32 {
+    fn arbitrary(g: &mut Gen) -> Self {
+        g.u64() as u32
+    }
+}
+
+impl Arbitrary for i64 {
+    fn arbitrary(g: &mut Gen) -> Self {
+        g.u64() as i64
+    }
+}
+
+impl Arbitrary for Vec<u8> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        g.bytes(64)
+    }
+}
+
+impl Arbitrary for DateTime<Utc> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        DateTime::from_timestamp_nanos(g.u64() as i64)
+    }
+}
+
+impl Arbitrary for SystemEvent {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            event_type: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+impl Arbitrary for SecurityDirectoryFlags {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            test_security: g.bool(),
+            when_issued: g.bool(),
+            etp: g.bool(),
+        }
+    }
+}
+
+impl Arbitrary for SecurityDirectory<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            flags: Arbitrary::arbitrary(g),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            round_lot_size: Arbitrary::arbitrary(g),
+            adjusted_poc_price: g.price(),
+            luld_tier: g.code(),
+        }
+    }
+}
+
+impl Arbitrary for TradingStatusReason {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let code = g.choose(&[
+            "T1", "IPO1", "IPOD", "MCB3", "NA", "T2", "IPO2", "IPO3", "MCB1", "MCB2", "XYZ",
+        ]);
+        code.parse().expect("the reason codes are valid")
+    }
+}
+
+impl Arbitrary for TradingStatus<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            status: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            reason: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+impl Arbitrary for RetailLiquidityIndicator<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            indicator: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+        }
+    }
+}
+
+impl Arbitrary for OperationalHaltStatus<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            status: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+        }
+    }
+}
+
+impl Arbitrary for ShortSalePriceTestStatus<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            in_effect: g.bool(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            detail: g.code(),
+        }
+    }
+}
+
+impl Arbitrary for QuoteUpdate<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            available: g.bool(),
+            market_session: g.choose(&[MarketSession::Regular, MarketSession::OutOfHours]),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            bid_size: Arbitrary::arbitrary(g),
+            bid_price: g.price(),
+            ask_size: Arbitrary::arbitrary(g),
+            ask_price: g.price(),
+        }
+    }
+}
+
+impl Arbitrary for SaleCondition {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            intermarket_sweep: g.bool(),
+            extended_hours: g.bool(),
+            odd_lot: g.bool(),
+            trade_through_exempt: g.bool(),
+            single_price: g.bool(),
+        }
+    }
+}
+
+impl Arbitrary for TradeReport<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            sale_condition: Arbitrary::arbitrary(g),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            size: Arbitrary::arbitrary(g),
+            price: g.price(),
+            id: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+impl Arbitrary for TradeBreak<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            sale_condition: Arbitrary::arbitrary(g),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            size: Arbitrary::arbitrary(g),
+            price: g.price(),
+            id: Arbitrary::arbitrary(g),
+        }
+    }
+}
+
+impl Arbitrary for OfficialPrice<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            price_type: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            official_price: g.price(),
+        }
+    }
+}
+
+impl Arbitrary for AuctionInformation<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            auction_type: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            paired_shares: Arbitrary::arbitrary(g),
+            reference_price: g.price(),
+            indicative_clearing_price: g.price(),
+            imbalance_shares: Arbitrary::arbitrary(g),
+            imbalance_side: g.code(),
+            extension_number: g.u64() as u8,
+            // Scheduled auction times are whole seconds on the wire
+            scheduled_auction_time: DateTime::from_timestamp(g.u64() as u32 as i64, 0)
+                .expect("the time is in range"),
+            auction_book_clearing_price: g.price(),
+            collar_reference_price: g.price(),
+            lower_auction_collar: g.price(),
+            upper_auction_collar: g.price(),
+        }
+    }
+}
+
+impl Arbitrary for SecurityEvent<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            event_type: g.code(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+        }
+    }
+}
+
+impl Arbitrary for PriceLevelUpdate<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            side: g.code(),
+            event_processing_complete: g.bool(),
+            timestamp: Arbitrary::arbitrary(g),
+            symbol: g.symbol(),
+            size: Arbitrary::arbitrary(g),
+            price: g.price(),
+        }
+    }
+}
+
+impl Arbitrary for Tops1_6Message<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        match g.below(11) {
+            0 => Self::SystemEvent(Arbitrary::arbitrary(g)),
+            1 => Self::SecurityDirectory(Arbitrary::arbitrary(g)),
+            2 => Self::TradingStatus(Arbitrary::arbitrary(g)),
+            3 => Self::RetailLiquidityIndicator(Arbitrary::arbitrary(g)),
+            4 => Self::OperationalHaltStatus(Arbitrary::arbitrary(g)),
+            5 => Self::ShortSalePriceTestStatus(Arbitrary::arbitrary(g)),
+            6 => Self::QuoteUpdate(Arbitrary::arbitrary(g)),
+            7 => Self::TradeReport(Arbitrary::arbitrary(g)),
+            8 => Self::OfficialPrice(Arbitrary::arbitrary(g)),
+            9 => Self::TradeBreak(Arbitrary::arbitrary(g)),
+            _ => Self::AuctionInformation(Arbitrary::arbitrary(g)),
+        }
+    }
+}
+
+impl Arbitrary for Deep1_0Message<String> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        match g.below(11) {
+            0 => Self::SystemEvent(Arbitrary::arbitrary(g)),
+            1 => Self::SecurityDirectory(Arbitrary::arbitrary(g)),
+            2 => Self::TradingStatus(Arbitrary::arbitrary(g)),
+            3 => Self::OperationalHaltStatus(Arbitrary::arbitrary(g)),
+            4 => Self::ShortSalePriceTestStatus(Arbitrary::arbitrary(g)),
+            5 => Self::SecurityEvent(Arbitrary::arbitrary(g)),
+            6 => Self::PriceLevelUpdate(Arbitrary::arbitrary(g)),
+            7 => Self::TradeReport(Arbitrary::arbitrary(g)),
+            8 => Self::OfficialPrice(Arbitrary::arbitrary(g)),
+            9 => Self::TradeBreak(Arbitrary::arbitrary(g)),
+            _ => Self::AuctionInformation(Arbitrary::arbitrary(g)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        deep::deep_1_0_message,
+        filter::MessageKind,
+        iex_tp::{iex_tp_segment, IexTp1Segment, IexTpSegment},
+        tops::tops_1_6_message,
+    };
+
+    use super::*;
+
+    /// A byte string starting with the type of a message, to get past the first check of the parsers
+    #[derive(Debug)]
+    struct MessageLike(Vec<u8>);
+
+    impl Arbitrary for MessageLike {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let kind = g.choose(&MessageKind::ALL);
+            let mut bytes = vec![kind.code()];
+            bytes.extend(g.bytes(80));
+            Self(bytes)
+        }
+    }
+
+    #[test]
+    fn parsers_never_panic() {
+        check(10_000, |MessageLike(bytes)| {
+            let _ = tops_1_6_message::<String>(&bytes);
+            let _ = deep_1_0_message::<String>(&bytes);
+        });
+        check(10_000, |bytes: Vec<u8>| {
+            let _ = iex_tp_segment(&bytes);
+        });
+    }
+
+    #[test]
+    fn segments_round_trip() {
+        check(1_000, |messages: Vec<u8>| {
+            // Split the bytes into messages of various lengths
+            let messages = messages.chunks(7).collect::<Vec<_>>();
+            let segment = IexTp1Segment {
+                message_protocol_id: 0x8003,
+                channel_id: 1,
+                session_id: messages.len() as u32,
+                send_time: DateTime::from_timestamp_nanos(messages.len() as i64 * 1_000_000_007),
+                messages,
+                first_message_sequence_no: 42,
+                stream_offset: 1234,
+            };
+
+            let encoded = segment.encode();
+            assert_eq!(encoded.len(), segment.encoded_len());
+            let Ok((rest, IexTpSegment::V1(decoded))) = iex_tp_segment(&encoded) else {
+                panic!("the segment should decode");
+            };
+            assert!(rest.is_empty());
+            assert_eq!(decoded.session_id, segment.session_id);
+            assert_eq!(decoded.send_time, segment.send_time);
+            assert_eq!(decoded.messages, segment.messages);
+            assert_eq!(decoded.first_message_sequence_no, 42);
+            assert_eq!(decoded.stream_offset, 1234);
+        });
+    }
+
+    #[test]
+    fn failures_report_their_seed() {
+        let failure =
+            panic::catch_unwind(|| check(100, |value: u32| assert!(value % 7 != 3))).unwrap_err();
+        let message = failure.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("property failed with seed "));
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    iex_tp::IexTp1Segment,
+    sequence::{SequenceRange, SequenceTracker, StreamId},
+};
+
+/// One of the redundant feeds of a channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feed {
+    A,
+    B,
+}
+
+impl Feed {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The messages received from a feed, and the ones it lost
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeedReport {
+    pub segments: u64,
+    pub messages: u64,
+    /// The messages missing from the feed, whether the other feed had them or not
+    pub missing: u64,
+    /// The messages of the feed which arrived before their copies from the other feed
+    pub first: u64,
+}
+
+/// What the arbiter delivers, in the order of the sequence numbers of each stream
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Arbitrated {
+    Message {
+        stream: StreamId,
+        sequence_number: i64,
+        /// The feed the message was taken from
+        feed: Feed,
+        /// The send time of the segment carrying the message
+        send_time: DateTime<Utc>,
+        message: Vec<u8>,
+    },
+    /// Messages which neither feed delivered
+    Gap(SequenceRange),
+}
+
+#[derive(Debug)]
+struct PendingMessage {
+    feed: Feed,
+    send_time: DateTime<Utc>,
+    message: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct StreamState {
+    /// The next sequence number to deliver
+    next: i64,
+    /// The sequence number following the last message received from each feed
+    received: [i64; 2],
+    /// The messages received past a gap, waiting for it to be filled
+    pending: BTreeMap<i64, PendingMessage>,
+}
+
+/// Merges the redundant A and B feeds of IEX into a single stream of messages without duplicates
+///
+/// Each message is delivered once, from whichever feed carried it first. When messages are missing from both feeds,
+/// the following ones are held until either feed fills the gap. The gap is given up on once both feeds have moved past
+/// it (so neither will fill it), or once more than `max_pending` messages are held, in which case it's delivered as an
+/// [`Arbitrated::Gap`]. A feed which is down never moves past gaps, so the limit bounds the delay of the other one.
+///
+/// Segments are typically received from two [`FeedReceiver`](crate::live::FeedReceiver)s, one per feed.
+#[derive(Debug)]
+pub struct FeedArbiter {
+    max_pending: usize,
+    streams: HashMap<StreamId, StreamState>,
+    trackers: [SequenceTracker; 2],
+    reports: [FeedReport; 2],
+    output: VecDeque<Arbitrated>,
+    lost: u64,
+}
+
+impl Default for FeedArbiter {
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+
+impl FeedArbiter {
+    /// Creates an arbiter holding at most `max_pending` messages of each stream behind a gap
+    pub fn new(max_pending: usize) -> Self {
+        Self {
+            max_pending,
+            streams: HashMap::new(),
+            trackers: Default::default(),
+            reports: Default::default(),
+            output: VecDeque::new(),
+            lost: 0,
+        }
+    }
+
+    /// Adds a segment received from a feed. Its new messages are then available from [`FeedArbiter::next_message`].
+    pub fn add_segment(&mut self, feed: Feed, segment: &IexTp1Segment<'_>) {
+        let report = &mut self.reports[feed.index()];
+        report.segments += 1;
+        report.messages += segment.messages.len() as u64;
+        self.trackers[feed.index()].update(segment);
+
+        let stream = StreamId::from(segment);
+        let first = segment.first_message_sequence_no;
+        let end = first + segment.messages.len() as i64;
+        let state = self.streams.entry(stream).or_insert(StreamState {
+            next: first,
+            received: [first; 2],
+            pending: BTreeMap::new(),
+        });
+        state.received[feed.index()] = state.received[feed.index()].max(end);
+
+        for (sequence_number, message) in (first..).zip(&segment.messages) {
+            if sequence_number >= state.next {
+                state
+                    .pending
+                    .entry(sequence_number)
+                    .or_insert_with(|| PendingMessage {
+                        feed,
+                        send_time: segment.send_time,
+                        message: message.to_vec(),
+                    });
+            }
+        }
+
+        self.deliver(stream, false);
+    }
+
+    /// Gives up on every gap, delivering the messages held behind them, e.g. once both feeds have ended
+    pub fn flush(&mut self) {
+        let streams = self.streams.keys().copied().collect::<Vec<_>>();
+        for stream in streams {
+            self.deliver(stream, true);
+        }
+    }
+
+    /// The next message (or gap) delivered, if any
+    pub fn next_message(&mut self) -> Option<Arbitrated> {
+        self.output.pop_front()
+    }
+
+    pub fn report(&self, feed: Feed) -> FeedReport {
+        FeedReport {
+            missing: self.trackers[feed.index()].missing(),
+            ..self.reports[feed.index()]
+        }
+    }
+
+    /// The number of messages which neither feed delivered
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    // Delivers the messages of a stream which follow the last one delivered, then the ones past the gaps given up on
+    fn deliver(&mut self, stream: StreamId, flush: bool) {
+        let Some(state) = self.streams.get_mut(&stream) else {
+            return;
+        };
+        loop {
+            while let Some(pending) = state.pending.remove(&state.next) {
+                self.reports[pending.feed.index()].first += 1;
+                self.output.push_back(Arbitrated::Message {
+                    stream,
+                    sequence_number: state.next,
+                    feed: pending.feed,
+                    send_time: pending.send_time,
+                    message: pending.message,
+                });
+                state.next += 1;
+            }
+
+            let Some((&resumption, pending)) = state.pending.first_key_value() else {
+                return;
+            };
+            let passed = state.received.iter().all(|&received| received > state.next);
+            if !(flush || passed || state.pending.len() > self.max_pending) {
+                return;
+            }
+            let count = resumption - state.next;
+            self.lost += count as u64;
+            self.output.push_back(Arbitrated::Gap(SequenceRange {
+                stream,
+                first: state.next,
+                count,
+                send_time: pending.send_time,
+            }));
+            state.next = resumption;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_protocol_ids;
+
+    use super::*;
+
+    fn segment(
+        first_message_sequence_no: i64,
+        messages: &[&'static [u8]],
+    ) -> IexTp1Segment<'static> {
+        IexTp1Segment {
+            message_protocol_id: message_protocol_ids::TOPS,
+            channel_id: 1,
+            session_id: 42,
+            send_time: DateTime::from_timestamp_nanos(first_message_sequence_no),
+            messages: messages.to_vec(),
+            first_message_sequence_no,
+            stream_offset: 0,
+        }
+    }
+
+    fn delivered(arbiter: &mut FeedArbiter) -> Vec<(i64, Option<Feed>)> {
+        std::iter::from_fn(|| arbiter.next_message())
+            .map(|delivered| match delivered {
+                Arbitrated::Message {
+                    sequence_number,
+                    feed,
+                    ..
+                } => (sequence_number, Some(feed)),
+                Arbitrated::Gap(range) => (range.first, None),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arbitration() {
+        let mut arbiter = FeedArbiter::default();
+        arbiter.add_segment(Feed::A, &segment(1, &[b"1", b"2"]));
+        arbiter.add_segment(Feed::B, &segment(1, &[b"1", b"2"]));
+        assert_eq!(
+            delivered(&mut arbiter),
+            [(1, Some(Feed::A)), (2, Some(Feed::A))]
+        );
+
+        // A loses 3 and 4, which B fills
+        arbiter.add_segment(Feed::A, &segment(5, &[b"5"]));
+        assert_eq!(delivered(&mut arbiter), []);
+        arbiter.add_segment(Feed::B, &segment(3, &[b"3", b"4"]));
+        arbiter.add_segment(Feed::B, &segment(5, &[b"5"]));
+        assert_eq!(
+            delivered(&mut arbiter),
+            [(3, Some(Feed::B)), (4, Some(Feed::B)), (5, Some(Feed::A))]
+        );
+
+        // Both lose 6, which is given up on once both have moved past it
+        arbiter.add_segment(Feed::B, &segment(7, &[b"7"]));
+        assert_eq!(delivered(&mut arbiter), []);
+        arbiter.add_segment(Feed::A, &segment(7, &[b"7"]));
+        assert_eq!(delivered(&mut arbiter), [(6, None), (7, Some(Feed::B))]);
+
+        assert_eq!(arbiter.lost(), 1);
+        assert_eq!(
+            arbiter.report(Feed::A),
+            FeedReport {
+                segments: 3,
+                messages: 4,
+                missing: 3,
+                first: 3,
+            }
+        );
+        assert_eq!(arbiter.report(Feed::B).missing, 1);
+        assert_eq!(arbiter.report(Feed::B).first, 3);
+    }
+
+    #[test]
+    fn pending_limit() {
+        let mut arbiter = FeedArbiter::new(2);
+        arbiter.add_segment(Feed::A, &segment(1, &[b"1"]));
+        arbiter.add_segment(Feed::A, &segment(3, &[b"3", b"4"]));
+        assert_eq!(delivered(&mut arbiter), [(1, Some(Feed::A))]);
+        arbiter.add_segment(Feed::A, &segment(5, &[b"5"]));
+        assert_eq!(
+            delivered(&mut arbiter),
+            [
+                (2, None),
+                (3, Some(Feed::A)),
+                (4, Some(Feed::A)),
+                (5, Some(Feed::A))
+            ]
+        );
+
+        arbiter.add_segment(Feed::A, &segment(7, &[b"7"]));
+        arbiter.flush();
+        assert_eq!(delivered(&mut arbiter), [(6, None), (7, Some(Feed::A))]);
+        assert_eq!(arbiter.lost(), 2);
+    }
+}
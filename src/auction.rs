@@ -0,0 +1,274 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+use chrono::{DateTime, Utc};
+
+use crate::tops::{AuctionInformation, AuctionType, ImbalanceSide, Tops1_6Message, TradeReport};
+
+/// The state of an auction as published by a single Auction Information message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AuctionUpdate {
+    pub timestamp: DateTime<Utc>,
+    pub paired_shares: u32,
+    pub indicative_clearing_price: f64,
+    pub imbalance_shares: u32,
+    pub imbalance_side: ImbalanceSide,
+}
+
+impl<S> From<&AuctionInformation<S>> for AuctionUpdate {
+    fn from(information: &AuctionInformation<S>) -> Self {
+        Self {
+            timestamp: information.timestamp,
+            paired_shares: information.paired_shares,
+            indicative_clearing_price: information.indicative_clearing_price,
+            imbalance_shares: information.imbalance_shares,
+            imbalance_side: information.imbalance_side,
+        }
+    }
+}
+
+/// An auction in progress
+#[derive(Clone, Debug)]
+pub struct AuctionState<S> {
+    pub symbol: S,
+    pub auction_type: AuctionType,
+    pub scheduled_auction_time: DateTime<Utc>,
+    /// The latest Auction Information message
+    pub latest: AuctionInformation<S>,
+    /// Every update of the auction so far, oldest first
+    pub updates: Vec<AuctionUpdate>,
+}
+
+/// A completed auction
+#[derive(Clone, Debug)]
+pub struct AuctionSummary<S> {
+    pub symbol: S,
+    pub auction_type: AuctionType,
+    pub scheduled_auction_time: DateTime<Utc>,
+    /// The last Auction Information message before the auction completed
+    pub last_information: AuctionInformation<S>,
+    /// Every update of the auction, oldest first
+    pub updates: Vec<AuctionUpdate>,
+    /// The price of the auction trade
+    pub clearing_price: f64,
+    /// The size of the auction trade
+    pub matched_shares: u32,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl<S> AuctionSummary<S> {
+    /// The update with the largest imbalance (the earliest one in case of a tie)
+    pub fn max_imbalance(&self) -> Option<&AuctionUpdate> {
+        self.updates
+            .iter()
+            .rev()
+            .max_by_key(|update| update.imbalance_shares)
+    }
+
+    /// The indicative clearing prices published before the auction, oldest first. Zero prices (published while the
+    /// auction book couldn't clear) are skipped.
+    pub fn indicative_prices(&self) -> impl Iterator<Item = (DateTime<Utc>, f64)> + '_ {
+        self.updates
+            .iter()
+            .filter(|update| update.indicative_clearing_price != 0.0)
+            .map(|update| (update.timestamp, update.indicative_clearing_price))
+    }
+
+    /// How much the clearing price moved away from the first indicative clearing price
+    pub fn price_drift(&self) -> Option<f64> {
+        let (_, first) = self.indicative_prices().next()?;
+        Some(self.clearing_price - first)
+    }
+
+    /// How many times the auction was extended, e.g. as the clearing price fell outside of the collars
+    pub fn extensions(&self) -> u8 {
+        self.last_information.extension_number
+    }
+}
+
+/// Tracks the auctions of every symbol, emitting an [`AuctionSummary`] when an auction completes
+///
+/// An auction is considered complete when a single-price cross trade is reported for its symbol.
+#[derive(Clone, Debug)]
+pub struct AuctionTracker<S> {
+    auctions: HashMap<S, AuctionState<S>>,
+}
+
+impl<S> Default for AuctionTracker<S> {
+    fn default() -> Self {
+        Self {
+            auctions: HashMap::new(),
+        }
+    }
+}
+
+impl<S> AuctionTracker<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an auction update. An update of a different auction type replaces the auction in progress.
+    pub fn apply_auction_information(&mut self, information: &AuctionInformation<S>) {
+        match self.auctions.get_mut(&information.symbol) {
+            Some(state) if state.auction_type == information.auction_type => {
+                state.scheduled_auction_time = information.scheduled_auction_time;
+                state.latest = information.clone();
+                state.updates.push(information.into());
+            }
+            _ => {
+                self.auctions.insert(
+                    information.symbol.clone(),
+                    AuctionState {
+                        symbol: information.symbol.clone(),
+                        auction_type: information.auction_type,
+                        scheduled_auction_time: information.scheduled_auction_time,
+                        latest: information.clone(),
+                        updates: vec![information.into()],
+                    },
+                );
+            }
+        }
+    }
+
+    /// Completes the auction of the trade's symbol if the trade is a single-price cross
+    pub fn apply_trade(&mut self, trade: &TradeReport<S>) -> Option<AuctionSummary<S>> {
+        if !trade.sale_condition.single_price {
+            return None;
+        }
+
+        let state = self.auctions.remove(&trade.symbol)?;
+        Some(AuctionSummary {
+            symbol: state.symbol,
+            auction_type: state.auction_type,
+            scheduled_auction_time: state.scheduled_auction_time,
+            last_information: state.latest,
+            updates: state.updates,
+            clearing_price: trade.price,
+            matched_shares: trade.size,
+            completed_at: trade.timestamp,
+        })
+    }
+
+    /// Feeds a TOPS message to the tracker, returning the summary of the auction it completes, if any
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Option<AuctionSummary<S>> {
+        match message {
+            Tops1_6Message::AuctionInformation(information) => {
+                self.apply_auction_information(information);
+                None
+            }
+            Tops1_6Message::TradeReport(trade) => self.apply_trade(trade),
+            _ => None,
+        }
+    }
+
+    /// Returns the auction in progress for a symbol
+    pub fn auction<Q>(&self, symbol: &Q) -> Option<&AuctionState<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.auctions.get(symbol)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&S, &AuctionState<S>)> {
+        self.auctions.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::{fixtures, tops::SaleCondition};
+
+    use super::*;
+
+    fn information(
+        auction_type: AuctionType,
+        timestamp: i64,
+        indicative_clearing_price: f64,
+        imbalance_shares: u32,
+    ) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::AuctionInformation(AuctionInformation {
+            auction_type,
+            timestamp: DateTime::from_timestamp_nanos(timestamp),
+            symbol: "ZIEXT",
+            paired_shares: 1000,
+            reference_price: 10.0,
+            indicative_clearing_price,
+            imbalance_shares,
+            imbalance_side: ImbalanceSide::Buy,
+            extension_number: 0,
+            scheduled_auction_time: DateTime::from_timestamp(1599076800, 0).unwrap(),
+            auction_book_clearing_price: indicative_clearing_price,
+            collar_reference_price: 10.0,
+            lower_auction_collar: 9.0,
+            upper_auction_collar: 11.0,
+        })
+    }
+
+    fn trade(timestamp: i64, single_price: bool) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradeReport(TradeReport {
+            sale_condition: SaleCondition {
+                single_price,
+                ..SaleCondition::default()
+            },
+            ..fixtures::trade("ZIEXT", timestamp, 10.02, 1200)
+        })
+    }
+
+    #[test]
+    fn auction_completes_on_cross() {
+        let mut tracker = AuctionTracker::new();
+
+        assert!(tracker
+            .update(&information(AuctionType::Closing, 1, 10.01, 500))
+            .is_none());
+        tracker.update(&information(AuctionType::Closing, 2, 10.02, 200));
+        assert_eq!(tracker.auction("ZIEXT").unwrap().updates.len(), 2);
+
+        assert!(tracker.update(&trade(3, false)).is_none());
+
+        let summary = tracker.update(&trade(4, true)).unwrap();
+        assert_eq!(summary.auction_type, AuctionType::Closing);
+        assert_eq!(summary.clearing_price, 10.02);
+        assert_eq!(summary.matched_shares, 1200);
+        assert_eq!(summary.last_information.imbalance_shares, 200);
+        assert_eq!(
+            summary
+                .updates
+                .iter()
+                .map(|update| update.indicative_clearing_price)
+                .collect::<Vec<_>>(),
+            [10.01, 10.02]
+        );
+        assert!(tracker.auction("ZIEXT").is_none());
+
+        assert_eq!(summary.max_imbalance().unwrap().imbalance_shares, 500);
+        assert_eq!(
+            summary
+                .max_imbalance()
+                .unwrap()
+                .timestamp
+                .timestamp_nanos_opt(),
+            Some(1)
+        );
+        assert_eq!(summary.indicative_prices().count(), 2);
+        assert_float_eq!(summary.price_drift().unwrap(), 0.01, abs <= 1e-9);
+        assert_eq!(summary.extensions(), 0);
+    }
+
+    #[test]
+    fn new_auction_type_replaces_auction() {
+        let mut tracker = AuctionTracker::new();
+
+        tracker.update(&information(AuctionType::Halt, 1, 10.01, 500));
+        tracker.update(&information(AuctionType::Closing, 2, 10.02, 200));
+
+        let auction = tracker.auction("ZIEXT").unwrap();
+        assert_eq!(auction.auction_type, AuctionType::Closing);
+        assert_eq!(auction.updates.len(), 1);
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    export::{write_json_object, Record, ToRecord, Value},
+    iex_tp::IexTp1Segment,
+    parser::Parser,
+};
+
+/// The size from which [`AuditLog`] starts a new file, by default
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 1 << 30;
+
+const FILE_PREFIX: &str = "audit-";
+const FILE_EXTENSION: &str = ".log";
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+/// Updates a CRC-32 (as in gzip or Ethernet) with some bytes
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// How a message was received
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    pub receive_time: DateTime<Utc>,
+    pub message_protocol_id: u16,
+    pub session_id: u32,
+    pub sequence_number: i64,
+}
+
+/// The record number at the start of a line of the log, if it's well-formed
+fn record_number(json: &str) -> Option<u64> {
+    let digits = json.strip_prefix("{\"record\":")?;
+    let end = digits.find(|c: char| !c.is_ascii_digit())?;
+    digits[..end].parse().ok()
+}
+
+/// The numbered files of a log, in order
+fn log_files(directory: &Path) -> io::Result<Vec<(u32, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let index = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(FILE_PREFIX))
+            .and_then(|name| name.strip_suffix(FILE_EXTENSION))
+            .and_then(|index| index.parse::<u32>().ok());
+        if let Some(index) = index {
+            files.push((index, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Checks the checksums of a log file, calling a function with the record number of each line
+fn verify_file(path: &Path, mut on_record: impl FnMut(u64) -> io::Result<()>) -> io::Result<()> {
+    let location = |line: usize| format!("{}, line {}", path.display(), line + 1);
+    let mut checksum = 0;
+    let mut lines = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    for number in 0.. {
+        line.clear();
+        if lines.read_line(&mut line)? == 0 {
+            break;
+        }
+        let Some(content) = line.strip_suffix('\n') else {
+            return Err(invalid_data(format!("{}: truncated", location(number))));
+        };
+        let (expected, json) = content
+            .split_once(' ')
+            .and_then(|(crc, json)| Some((u32::from_str_radix(crc, 16).ok()?, json)))
+            .ok_or_else(|| invalid_data(format!("{}: malformed", location(number))))?;
+        checksum = crc32(checksum, json.as_bytes());
+        if checksum != expected {
+            return Err(invalid_data(format!(
+                "{}: checksum mismatch",
+                location(number)
+            )));
+        }
+        let record = record_number(json)
+            .ok_or_else(|| invalid_data(format!("{}: malformed", location(number))))?;
+        on_record(record)?;
+    }
+    Ok(())
+}
+
+/// Checks a log written by [`AuditLog`]: the checksum of every line, and that no record is missing between the first
+/// one kept and the last one. Returns the number of records.
+pub fn verify(directory: impl AsRef<Path>) -> io::Result<u64> {
+    let mut next = None;
+    let mut records = 0;
+    for (_, path) in log_files(directory.as_ref())? {
+        verify_file(&path, |record| {
+            if next.is_some_and(|next| record != next) {
+                return Err(invalid_data(format!(
+                    "{}: record {record} follows record {}",
+                    path.display(),
+                    next.unwrap_or_default() - 1
+                )));
+            }
+            next = Some(record + 1);
+            records += 1;
+            Ok(())
+        })?;
+    }
+    Ok(records)
+}
+
+/// An append-only log of every message received, as a drop copy for archival: what the feed handler saw, rather than
+/// what IEX published
+///
+/// Each line holds a record: a JSON object (see [`write_json`](crate::export::write_json)) with the number of the
+/// record, the receipt of the message, the message as received (in hexadecimal) and its decoded fields. Lines are
+/// prefixed by a CRC-32 in hexadecimal, chaining the JSON of the line to the previous line of the file, so that altered,
+/// removed or reordered lines are detected by [`verify`]. Records are numbered across files, which detects removed
+/// files.
+///
+/// The log is a directory of numbered files: the log starts a new file once the current one reaches a size, and
+/// whenever it's opened, so that existing files are never modified.
+#[derive(Debug)]
+pub struct AuditLog {
+    directory: PathBuf,
+    max_file_bytes: u64,
+    file: BufWriter<File>,
+    index: u32,
+    file_bytes: u64,
+    records: u64,
+    checksum: u32,
+    line: Vec<u8>,
+}
+
+impl AuditLog {
+    /// Opens the log in a directory, which is created if needed. The records appended follow those of the existing
+    /// files, which are checked with [`verify`] first.
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+
+        let files = log_files(&directory)?;
+        let mut records = 0;
+        for (_, path) in &files {
+            verify_file(path, |record| {
+                records = record + 1;
+                Ok(())
+            })?;
+        }
+        let index = files.last().map_or(0, |(index, _)| index + 1);
+
+        Ok(Self {
+            file: Self::create(&directory, index)?,
+            directory,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            index,
+            file_bytes: 0,
+            records,
+            checksum: 0,
+            line: Vec::new(),
+        })
+    }
+
+    /// Starts a new file once the current one reaches a size, instead of [`DEFAULT_MAX_FILE_BYTES`]
+    pub fn with_max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    fn path(directory: &Path, index: u32) -> PathBuf {
+        directory.join(format!("{FILE_PREFIX}{index:06}{FILE_EXTENSION}"))
+    }
+
+    fn create(directory: &Path, index: u32) -> io::Result<BufWriter<File>> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(Self::path(directory, index))?;
+        Ok(BufWriter::new(file))
+    }
+
+    /// The file being written
+    pub fn current_file(&self) -> PathBuf {
+        Self::path(&self.directory, self.index)
+    }
+
+    /// The number of the next record, i.e. the number of records in the log
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Appends a message, with its decoded fields if it could be decoded
+    pub fn append(
+        &mut self,
+        receipt: &Receipt,
+        message: &[u8],
+        decoded: Option<&Record>,
+    ) -> io::Result<()> {
+        let mut raw = String::with_capacity(2 * message.len());
+        for byte in message {
+            let _ = write!(raw, "{byte:02x}");
+        }
+        let mut record = vec![
+            ("record", Value::Int(self.records as i64)),
+            ("receive_time", Value::Time(receipt.receive_time)),
+            (
+                "message_protocol_id",
+                Value::Int(receipt.message_protocol_id.into()),
+            ),
+            ("session_id", Value::Int(receipt.session_id.into())),
+            ("sequence_number", Value::Int(receipt.sequence_number)),
+            ("raw", Value::Text(raw)),
+        ];
+        record.extend(decoded.into_iter().flatten().cloned());
+
+        self.line.clear();
+        write_json_object(&mut self.line, &record)?;
+        // The checksum in hexadecimal, a space, the JSON and a newline
+        let length = (8 + 1 + self.line.len() + 1) as u64;
+        if self.file_bytes > 0 && self.file_bytes + length > self.max_file_bytes {
+            self.rotate()?;
+        }
+
+        self.checksum = crc32(self.checksum, &self.line);
+        write!(self.file, "{:08x} ", self.checksum)?;
+        self.file.write_all(&self.line)?;
+        self.file.write_all(b"\n")?;
+        self.file_bytes += length;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Appends the messages of a segment received at some time, decoding those of TOPS and DEEP segments
+    pub fn append_segment(
+        &mut self,
+        receive_time: DateTime<Utc>,
+        segment: &IexTp1Segment<'_>,
+    ) -> io::Result<()> {
+        let parser = Parser::for_protocol_id(segment.message_protocol_id);
+        for (sequence_number, message) in
+            (segment.first_message_sequence_no..).zip(&segment.messages)
+        {
+            let receipt = Receipt {
+                receive_time,
+                message_protocol_id: segment.message_protocol_id,
+                session_id: segment.session_id,
+                sequence_number,
+            };
+            let decoded = parser
+                .as_ref()
+                .and_then(|parser| parser.parse::<String>(message).ok())
+                .map(|message| message.to_record());
+            self.append(&receipt, message, decoded.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Closes the current file and starts a new one, e.g. at the start of every trading day
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.file = Self::create(&self.directory, self.index + 1)?;
+        self.index += 1;
+        self.file_bytes = 0;
+        self.checksum = 0;
+        Ok(())
+    }
+
+    /// Writes the buffered records and waits for them to reach the disk
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{
+        hist::tests::segment,
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+    };
+
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("iex-audit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn append_rotate_verify() {
+        let directory = directory("log");
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            5,
+            &[&spec::QUOTE_UPDATE, &spec::TRADE_REPORT, b"garbage"],
+        );
+        let (_, IexTpSegment::V1(decoded)) = iex_tp_segment(&encoded).unwrap();
+        let receive_time = DateTime::from_timestamp_nanos(1492448400000000000);
+
+        let mut log = AuditLog::open(&directory).unwrap().with_max_file_bytes(600);
+        log.append_segment(receive_time, &decoded).unwrap();
+        assert_eq!(log.records(), 3);
+        log.flush().unwrap();
+
+        let first = fs::read_to_string(AuditLog::path(&directory, 0)).unwrap();
+        let line = first.lines().next().unwrap();
+        assert!(line[9..].starts_with(
+            "{\"record\":0,\"receive_time\":\"2017-04-17T17:00:00.000000000Z\",\"message_protocol_id\":32771,\
+             \"session_id\":1116143616,\"sequence_number\":5,\"raw\":\"51"
+        ));
+        assert!(line.contains("\"kind\":\"quote_update\""));
+        drop(log);
+
+        // Reopening continues the numbering in a new file
+        let mut log = AuditLog::open(&directory).unwrap();
+        log.append_segment(receive_time, &decoded).unwrap();
+        assert_eq!(log.records(), 6);
+        let files = log_files(&directory).unwrap();
+        assert!(files.len() >= 3);
+        assert_eq!(log.current_file(), files.last().unwrap().1);
+        drop(log);
+        assert_eq!(verify(&directory).unwrap(), 6);
+
+        // An altered line
+        let path = &files.last().unwrap().1;
+        let altered = fs::read_to_string(path)
+            .unwrap()
+            .replace("\"size\":100", "\"size\":900");
+        fs::write(path, altered).unwrap();
+        let error = verify(&directory).unwrap_err();
+        assert!(error.to_string().ends_with("checksum mismatch"), "{error}");
+
+        // A removed file
+        fs::remove_file(path).unwrap();
+        fs::remove_file(&files[1].1).unwrap();
+        let error = verify(&directory).unwrap_err();
+        assert!(error.to_string().contains("follows record"), "{error}");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::tops::{MarketSession, Tops1_6Message, TradeBreak, TradeReport};
+
+/// How trades executed outside of regular market hours are aggregated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtendedHours {
+    /// Aggregate extended hours trades together with regular hours trades
+    Include,
+    /// Ignore extended hours trades
+    Exclude,
+    /// Aggregate extended hours trades into their own bars
+    Separate,
+}
+
+#[derive(Clone, Debug)]
+pub struct BarConfig {
+    /// The length of each bar. Bars are aligned to multiples of the interval since the POSIX epoch.
+    pub interval: TimeDelta,
+    pub extended_hours: ExtendedHours,
+    pub include_odd_lots: bool,
+    /// Whether to keep the trades of open bars, so that trade breaks can remove them. Trades of bars which already
+    /// closed can't be removed.
+    pub handle_trade_breaks: bool,
+}
+
+impl Default for BarConfig {
+    fn default() -> Self {
+        Self {
+            interval: TimeDelta::minutes(1),
+            extended_hours: ExtendedHours::Exclude,
+            include_odd_lots: true,
+            handle_trade_breaks: false,
+        }
+    }
+}
+
+/// An OHLCV bar of a single symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bar<S> {
+    pub symbol: S,
+    /// `OutOfHours` only for bars of extended hours trades aggregated separately
+    pub session: MarketSession,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    /// The sum of price times size over all trades of the bar
+    pub notional: f64,
+    pub trade_count: u64,
+}
+
+impl<S> Bar<S> {
+    fn new(
+        trade: &TradeReport<S>,
+        session: MarketSession,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Self
+    where
+        S: Clone,
+    {
+        Self {
+            symbol: trade.symbol.clone(),
+            session,
+            start,
+            end,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.size.into(),
+            notional: trade.price * f64::from(trade.size),
+            trade_count: 1,
+        }
+    }
+
+    fn add(&mut self, trade: &TradeReport<S>) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += u64::from(trade.size);
+        self.notional += trade.price * f64::from(trade.size);
+        self.trade_count += 1;
+    }
+
+    /// The volume weighted average price of the bar
+    pub fn vwap(&self) -> f64 {
+        self.notional / self.volume as f64
+    }
+}
+
+// The price, size and ID of a trade
+type BarTrade = (f64, u32, i64);
+
+#[derive(Clone, Debug)]
+struct OpenBar<S> {
+    bar: Bar<S>,
+    // Only kept if trade breaks are handled
+    trades: Vec<BarTrade>,
+}
+
+impl<S> OpenBar<S> {
+    // Removes a trade, recomputing the bar from the remaining ones. Returns whether the trade belonged to the bar.
+    fn remove(&mut self, id: i64) -> bool {
+        let Some(index) = self
+            .trades
+            .iter()
+            .position(|&(_, _, trade_id)| trade_id == id)
+        else {
+            return false;
+        };
+        self.trades.remove(index);
+
+        if let Some(&(first_price, _, _)) = self.trades.first() {
+            let bar = &mut self.bar;
+            bar.open = first_price;
+            bar.high = first_price;
+            bar.low = first_price;
+            bar.volume = 0;
+            bar.notional = 0.0;
+            for &(price, size, _) in &self.trades {
+                bar.high = bar.high.max(price);
+                bar.low = bar.low.min(price);
+                bar.close = price;
+                bar.volume += u64::from(size);
+                bar.notional += price * f64::from(size);
+            }
+            bar.trade_count = self.trades.len() as u64;
+        }
+        true
+    }
+}
+
+/// Aggregates trade reports into time bars, emitting each bar once its interval is over
+///
+/// Trades are expected in chronological order, as they appear in the feed. Since a bar is only known to be complete
+/// once a later message is seen, feeding every message (and not just trades) through [`BarBuilder::update`] closes bars
+/// as early as possible.
+///
+/// The bars closed together are returned in order of symbol, then of session, so that outputs are reproducible.
+#[derive(Clone, Debug)]
+pub struct BarBuilder<S> {
+    config: BarConfig,
+    bucket_start: Option<DateTime<Utc>>,
+    open_bars: BTreeMap<(S, MarketSession), OpenBar<S>>,
+}
+
+impl<S> BarBuilder<S>
+where
+    S: Ord + Clone,
+{
+    pub fn new(config: BarConfig) -> Self {
+        assert!(
+            config.interval > TimeDelta::zero(),
+            "bar interval must be positive"
+        );
+
+        Self {
+            config,
+            bucket_start: None,
+            open_bars: BTreeMap::new(),
+        }
+    }
+
+    fn bucket_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = self.config.interval.num_nanoseconds().unwrap_or(i64::MAX);
+        let nanos = timestamp.timestamp_nanos_opt().unwrap_or_default();
+        DateTime::from_timestamp_nanos(nanos - nanos.rem_euclid(interval))
+    }
+
+    /// Advances the clock of the builder, returning the bars which closed before the given time
+    pub fn advance(&mut self, now: DateTime<Utc>) -> Vec<Bar<S>> {
+        let bucket_start = self.bucket_start(now);
+        match self.bucket_start {
+            Some(current) if current >= bucket_start => Vec::new(),
+            _ => {
+                self.bucket_start = Some(bucket_start);
+                self.close_all()
+            }
+        }
+    }
+
+    /// Adds a trade to the bar of its symbol, returning the bars which closed before it
+    pub fn apply(&mut self, trade: &TradeReport<S>) -> Vec<Bar<S>> {
+        let closed = self.advance(trade.timestamp);
+
+        if trade.sale_condition.odd_lot && !self.config.include_odd_lots {
+            return closed;
+        }
+
+        let session = match (
+            trade.sale_condition.extended_hours,
+            self.config.extended_hours,
+        ) {
+            (false, _) | (true, ExtendedHours::Include) => MarketSession::Regular,
+            (true, ExtendedHours::Exclude) => return closed,
+            (true, ExtendedHours::Separate) => MarketSession::OutOfHours,
+        };
+
+        let start = self.bucket_start(trade.timestamp);
+        let end = start + self.config.interval;
+        let open = self
+            .open_bars
+            .entry((trade.symbol.clone(), session))
+            .and_modify(|open| open.bar.add(trade))
+            .or_insert_with(|| OpenBar {
+                bar: Bar::new(trade, session, start, end),
+                trades: Vec::new(),
+            });
+        if self.config.handle_trade_breaks {
+            open.trades.push((trade.price, trade.size, trade.id));
+        }
+
+        closed
+    }
+
+    /// Removes a broken trade from its bar if it's still open, returning the bars which closed before the break. A bar
+    /// left without any trade is dropped.
+    ///
+    /// Does nothing unless [`BarConfig::handle_trade_breaks`] is set.
+    pub fn apply_trade_break(&mut self, trade_break: &TradeBreak<S>) -> Vec<Bar<S>> {
+        let closed = self.advance(trade_break.timestamp);
+
+        for session in [MarketSession::Regular, MarketSession::OutOfHours] {
+            let key = (trade_break.symbol.clone(), session);
+            if let Some(open) = self.open_bars.get_mut(&key) {
+                if open.remove(trade_break.id) {
+                    if open.trades.is_empty() {
+                        self.open_bars.remove(&key);
+                    }
+                    break;
+                }
+            }
+        }
+
+        closed
+    }
+
+    /// Feeds a TOPS message to the builder, returning the bars which closed before it
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Vec<Bar<S>> {
+        match message {
+            Tops1_6Message::TradeReport(trade) => self.apply(trade),
+            Tops1_6Message::TradeBreak(trade_break) => self.apply_trade_break(trade_break),
+            message => match message.timestamp() {
+                Some(timestamp) => self.advance(timestamp),
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// Closes and returns all the bars which are still open (e.g. at the end of the feed)
+    pub fn finish(&mut self) -> Vec<Bar<S>> {
+        self.close_all()
+    }
+
+    fn close_all(&mut self) -> Vec<Bar<S>> {
+        std::mem::take(&mut self.open_bars)
+            .into_values()
+            .map(|open| open.bar)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::{fixtures, tops::SaleCondition};
+
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn trade(
+        timestamp: i64,
+        price: f64,
+        size: u32,
+        extended_hours: bool,
+        odd_lot: bool,
+    ) -> TradeReport<String> {
+        TradeReport {
+            sale_condition: SaleCondition {
+                extended_hours,
+                odd_lot,
+                ..SaleCondition::default()
+            },
+            ..fixtures::trade("ZIEXT".to_string(), timestamp, price, size)
+        }
+    }
+
+    #[test]
+    fn ohlcv() {
+        let mut builder = BarBuilder::new(BarConfig::default());
+
+        assert!(builder
+            .apply(&trade(60 * SECOND, 10.0, 100, false, false))
+            .is_empty());
+        assert!(builder
+            .apply(&trade(70 * SECOND, 12.0, 100, false, false))
+            .is_empty());
+        assert!(builder
+            .apply(&trade(80 * SECOND, 9.0, 100, false, false))
+            .is_empty());
+        assert!(builder
+            .apply(&trade(90 * SECOND, 11.0, 200, false, false))
+            .is_empty());
+
+        let bars = builder.apply(&trade(120 * SECOND, 11.0, 100, false, false));
+        assert_eq!(bars.len(), 1);
+        let bar = &bars[0];
+        assert_eq!(bar.start, DateTime::from_timestamp_nanos(60 * SECOND));
+        assert_eq!(bar.end, DateTime::from_timestamp_nanos(120 * SECOND));
+        assert_float_eq!(bar.open, 10.0, ulps <= 1);
+        assert_float_eq!(bar.high, 12.0, ulps <= 1);
+        assert_float_eq!(bar.low, 9.0, ulps <= 1);
+        assert_float_eq!(bar.close, 11.0, ulps <= 1);
+        assert_eq!(bar.volume, 500);
+        assert_eq!(bar.trade_count, 4);
+        assert_float_eq!(bar.vwap(), 10.6, ulps <= 5);
+
+        assert_eq!(builder.finish().len(), 1);
+    }
+
+    #[test]
+    fn extended_hours_and_odd_lots() {
+        let mut builder = BarBuilder::new(BarConfig {
+            interval: TimeDelta::seconds(1),
+            extended_hours: ExtendedHours::Separate,
+            include_odd_lots: false,
+            handle_trade_breaks: false,
+        });
+
+        builder.apply(&trade(SECOND, 10.0, 100, false, false));
+        builder.apply(&trade(SECOND, 11.0, 100, true, false));
+        builder.apply(&trade(SECOND, 12.0, 10, false, true));
+
+        let bars = builder.advance(DateTime::from_timestamp_nanos(2 * SECOND));
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].session, MarketSession::Regular);
+        assert_float_eq!(bars[0].high, 10.0, ulps <= 1);
+        assert_eq!(bars[1].session, MarketSession::OutOfHours);
+        assert_float_eq!(bars[1].high, 11.0, ulps <= 1);
+
+        let mut builder = BarBuilder::new(BarConfig::default());
+        builder.apply(&trade(SECOND, 11.0, 100, true, false));
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn trade_breaks() {
+        let mut builder = BarBuilder::new(BarConfig {
+            handle_trade_breaks: true,
+            ..BarConfig::default()
+        });
+
+        let mut first = trade(0, 10.0, 100, false, false);
+        first.id = 1;
+        let mut second = trade(SECOND, 12.0, 300, false, false);
+        second.id = 2;
+        builder.apply(&first);
+        builder.apply(&second);
+
+        let trade_break = |id| TradeBreak {
+            sale_condition: second.sale_condition.clone(),
+            timestamp: DateTime::from_timestamp_nanos(2 * SECOND),
+            symbol: "ZIEXT".to_string(),
+            size: 300,
+            price: 12.0,
+            id,
+        };
+        builder.apply_trade_break(&trade_break(2));
+        // Unknown trades are ignored
+        builder.apply_trade_break(&trade_break(3));
+
+        let bars = builder.finish();
+        assert_eq!(bars.len(), 1);
+        assert_float_eq!(bars[0].high, 10.0, ulps <= 1);
+        assert_float_eq!(bars[0].close, 10.0, ulps <= 1);
+        assert_eq!(bars[0].volume, 100);
+        assert_eq!(bars[0].trade_count, 1);
+
+        builder.apply(&first);
+        builder.apply_trade_break(&trade_break(1));
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn closed_bars_order() {
+        let mut builder = BarBuilder::new(BarConfig {
+            extended_hours: ExtendedHours::Separate,
+            ..BarConfig::default()
+        });
+        for (symbol, extended_hours) in [
+            ("ZVZZT", false),
+            ("ZIEXT", true),
+            ("ZXIET", false),
+            ("ZIEXT", false),
+        ] {
+            let mut trade = trade(0, 10.0, 100, extended_hours, false);
+            trade.symbol = symbol.to_string();
+            builder.apply(&trade);
+        }
+
+        let bars = builder.finish();
+        let keys = bars
+            .iter()
+            .map(|bar| (bar.symbol.as_str(), bar.session))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                ("ZIEXT", MarketSession::Regular),
+                ("ZIEXT", MarketSession::OutOfHours),
+                ("ZVZZT", MarketSession::Regular),
+                ("ZXIET", MarketSession::Regular),
+            ]
+        );
+    }
+}
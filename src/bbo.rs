@@ -0,0 +1,251 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    io::{self, Read, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    checkpoint::{CheckpointReader, CheckpointWriter},
+    tops::{QuoteUpdate, Tops1_6Message},
+};
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"IEXBBO01";
+
+/// The best bid and offer of a symbol. A side without any quote has zero price and size.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bbo {
+    pub bid_price: f64,
+    pub bid_size: u32,
+    pub ask_price: f64,
+    pub ask_size: u32,
+}
+
+impl Bbo {
+    /// Whether both sides are quoted
+    pub fn is_two_sided(&self) -> bool {
+        self.bid_size > 0 && self.ask_size > 0
+    }
+
+    /// The mid price, if both sides are quoted
+    pub fn mid(&self) -> Option<f64> {
+        self.is_two_sided()
+            .then_some((self.bid_price + self.ask_price) / 2.0)
+    }
+
+    /// The quoted spread, if both sides are quoted
+    pub fn spread(&self) -> Option<f64> {
+        self.is_two_sided()
+            .then_some(self.ask_price - self.bid_price)
+    }
+}
+
+impl<S> From<&QuoteUpdate<S>> for Bbo {
+    fn from(quote: &QuoteUpdate<S>) -> Self {
+        Self {
+            bid_price: quote.bid_price,
+            bid_size: quote.bid_size,
+            ask_price: quote.ask_price,
+            ask_size: quote.ask_size,
+        }
+    }
+}
+
+/// Emitted when the best bid or offer of a symbol changes
+#[derive(Clone, Debug)]
+pub struct BboChange<S> {
+    pub symbol: S,
+    pub timestamp: DateTime<Utc>,
+    /// The previous BBO, or `None` for the first quote of the symbol
+    pub previous: Option<Bbo>,
+    pub current: Bbo,
+}
+
+/// Tracks the current best bid and offer of every symbol from TOPS quote updates
+#[derive(Clone, Debug)]
+pub struct BboTracker<S> {
+    quotes: HashMap<S, (Bbo, DateTime<Utc>)>,
+}
+
+impl<S> Default for BboTracker<S> {
+    fn default() -> Self {
+        Self {
+            quotes: HashMap::new(),
+        }
+    }
+}
+
+impl<S> BboTracker<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a quote update, returning a change event only if the BBO of the symbol actually changed
+    pub fn apply(&mut self, quote: &QuoteUpdate<S>) -> Option<BboChange<S>> {
+        let current = Bbo::from(quote);
+
+        let previous = match self.quotes.get_mut(&quote.symbol) {
+            Some((bbo, timestamp)) => {
+                if *bbo == current {
+                    return None;
+                }
+                *timestamp = quote.timestamp;
+                Some(std::mem::replace(bbo, current))
+            }
+            None => {
+                self.quotes
+                    .insert(quote.symbol.clone(), (current, quote.timestamp));
+                None
+            }
+        };
+
+        Some(BboChange {
+            symbol: quote.symbol.clone(),
+            timestamp: quote.timestamp,
+            previous,
+            current,
+        })
+    }
+
+    /// Feeds a TOPS message to the tracker. Messages other than quote updates are ignored.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Option<BboChange<S>> {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => self.apply(quote),
+            _ => None,
+        }
+    }
+
+    pub fn bbo<Q>(&self, symbol: &Q) -> Option<&Bbo>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.quotes.get(symbol).map(|(bbo, _)| bbo)
+    }
+
+    /// The timestamp of the quote which last changed the BBO of the symbol
+    pub fn last_change<Q>(&self, symbol: &Q) -> Option<DateTime<Utc>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.quotes.get(symbol).map(|(_, timestamp)| *timestamp)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&S, &Bbo)> {
+        self.quotes.iter().map(|(symbol, (bbo, _))| (symbol, bbo))
+    }
+
+    /// Writes the BBOs as a checkpoint, from which [`BboTracker::read_from`] restores the tracker
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let mut writer = CheckpointWriter::new(writer, CHECKPOINT_MAGIC)?;
+        let mut quotes = self.quotes.iter().collect::<Vec<_>>();
+        quotes.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        writer.len(quotes.len())?;
+        for (symbol, (bbo, timestamp)) in quotes {
+            writer.symbol(symbol.as_ref())?;
+            writer.timestamp(*timestamp)?;
+            writer.price(bbo.bid_price)?;
+            writer.u32(bbo.bid_size)?;
+            writer.price(bbo.ask_price)?;
+            writer.u32(bbo.ask_size)?;
+        }
+        writer.finish()
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        let mut reader = CheckpointReader::new(reader, CHECKPOINT_MAGIC, "BBO tracker")?;
+        let mut quotes = HashMap::new();
+        for _ in 0..reader.len()? {
+            let symbol = reader.symbol()?;
+            let timestamp = reader.timestamp()?;
+            let bbo = Bbo {
+                bid_price: reader.price()?,
+                bid_size: reader.u32()?,
+                ask_price: reader.price()?,
+                ask_size: reader.u32()?,
+            };
+            quotes.insert(symbol, (bbo, timestamp));
+        }
+        Ok(Self { quotes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::assert_matches;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    fn quote(timestamp: i64, bid_size: u32, bid_price: f64) -> QuoteUpdate<String> {
+        QuoteUpdate {
+            bid_size,
+            ask_size: 1000,
+            ..fixtures::quote("ZIEXT".to_string(), timestamp, bid_price, 99.07)
+        }
+    }
+
+    #[test]
+    fn emits_only_on_change() {
+        let mut tracker = BboTracker::new();
+
+        assert_matches!(
+            tracker.apply(&quote(1, 9700, 99.05)),
+            Some(BboChange { previous: None, .. })
+        );
+        assert_matches!(tracker.apply(&quote(2, 9700, 99.05)), None);
+        assert_eq!(
+            tracker.last_change("ZIEXT"),
+            Some(DateTime::from_timestamp_nanos(1))
+        );
+
+        let change = tracker.apply(&quote(3, 100, 99.05)).unwrap();
+        assert_eq!(change.previous.unwrap().bid_size, 9700);
+        assert_eq!(change.current.bid_size, 100);
+        assert_eq!(tracker.bbo("ZIEXT").unwrap().bid_size, 100);
+    }
+
+    #[test]
+    fn one_sided_quote() {
+        let bbo = Bbo::from(&quote(1, 0, 0.0));
+        assert!(!bbo.is_two_sided());
+        assert_eq!(bbo.mid(), None);
+        assert_eq!(bbo.spread(), None);
+
+        let bbo = Bbo::from(&quote(1, 100, 99.05));
+        assert!((bbo.mid().unwrap() - 99.06).abs() < 1e-9);
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut tracker = BboTracker::new();
+        tracker.apply(&quote(1, 100, 99.05));
+        tracker.apply(&quote(2, 200, 99.06));
+
+        let mut checkpoint = Vec::new();
+        tracker.write_to(&mut checkpoint).unwrap();
+        let mut restored = BboTracker::<String>::read_from(&checkpoint[..]).unwrap();
+        assert_eq!(restored.bbo("ZIEXT").unwrap().bid_size, 200);
+        assert_eq!(
+            restored.last_change("ZIEXT"),
+            Some(DateTime::from_timestamp_nanos(2))
+        );
+
+        // The restored BBO is the previous one of the next change
+        let change = restored.apply(&quote(3, 300, 99.06)).unwrap();
+        assert_eq!(change.previous.unwrap().bid_size, 200);
+    }
+}
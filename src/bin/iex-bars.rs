@@ -0,0 +1,111 @@
+//! Aggregates the trades of a HIST file into OHLCV bars, written as CSV
+
+use std::io::Write;
+
+use iex_parser::{
+    bars::{Bar, BarBuilder, BarConfig, ExtendedHours},
+    cli::{self, Args},
+    export::Value,
+    filter::{MessageKind, MessageKinds},
+    hist,
+    tops::{MarketSession, Tops1_6Message},
+};
+
+const USAGE: &str = "\
+Usage: iex-bars [OPTIONS] <HIST FILE>
+
+Aggregates the trades of a TOPS HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard input)
+into OHLCV bars, written as CSV with the columns symbol, session, start, end, open, high, low, close, volume, vwap and
+trade_count. Bars are aligned to multiples of the interval since the POSIX epoch, and trades broken while their bar is
+still open are removed from it.
+
+Options:
+    --symbols <SYMBOLS>     The symbols aggregated, e.g. AAPL,SPY or 'SP*' [default: all]
+    --interval <DURATION>   The length of the bars, e.g. 5m [default: 1m]
+    --regular-hours         Ignores the trades executed outside of regular market hours
+    --separate-extended     Aggregates the trades executed outside of regular market hours into their own bars
+    --exclude-odd-lots      Ignores odd lot trades
+    --from <TIME>           Ignores the trades before that time (RFC 3339, or nanoseconds since the POSIX epoch)
+    --to <TIME>             Ignores the trades from that time
+    --where <EXPRESSION>    Only aggregates the trades matching an expression, e.g. \"symbol in (SPY, QQQ) and ts >= 14:30\"
+    --output <FILE>         The output file [default: the standard output]";
+
+const COLUMNS: &str = "symbol,session,start,end,open,high,low,close,volume,vwap,trade_count";
+
+fn write_bars(output: &mut impl Write, mut bars: Vec<Bar<String>>) -> std::io::Result<()> {
+    bars.sort_by(|a, b| (a.start, &a.symbol).cmp(&(b.start, &b.symbol)));
+    for bar in bars {
+        let session = match bar.session {
+            MarketSession::Regular => "regular",
+            MarketSession::OutOfHours => "extended",
+        };
+        writeln!(
+            output,
+            "{},{session},{},{},{},{},{},{},{},{},{}",
+            bar.symbol,
+            Value::Time(bar.start),
+            Value::Time(bar.end),
+            Value::Price(bar.open),
+            Value::Price(bar.high),
+            Value::Price(bar.low),
+            Value::Price(bar.close),
+            bar.volume,
+            Value::Price(bar.vwap()),
+            bar.trade_count
+        )?;
+    }
+    Ok(())
+}
+
+fn aggregate(args: &Args, input: &str) -> Result<(), hist::Error> {
+    let extended_hours = match (args.flag("regular-hours"), args.flag("separate-extended")) {
+        (false, false) => ExtendedHours::Include,
+        (true, false) => ExtendedHours::Exclude,
+        (false, true) => ExtendedHours::Separate,
+        (true, true) => cli::fail("--regular-hours and --separate-extended are exclusive"),
+    };
+    let config = BarConfig {
+        interval: args
+            .parsed("interval", cli::parse_duration)
+            .unwrap_or(BarConfig::default().interval),
+        extended_hours,
+        include_odd_lots: !args.flag("exclude-odd-lots"),
+        handle_trade_breaks: true,
+    };
+
+    let filter = args.message_filter().kinds(
+        [MessageKind::TradeReport, MessageKind::TradeBreak]
+            .into_iter()
+            .collect::<MessageKinds>(),
+    );
+    let messages = cli::open_hist(args, input)?
+        .messages::<Tops1_6Message<String>>()
+        .with_filter(filter);
+
+    let mut output = cli::open_output(args.value("output"))?;
+    writeln!(output, "{COLUMNS}")?;
+
+    let mut builder = BarBuilder::new(config);
+    for message in messages {
+        write_bars(&mut output, builder.update(&message?))?;
+    }
+    write_bars(&mut output, builder.finish())?;
+
+    output.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(
+        USAGE,
+        &["regular-hours", "separate-extended", "exclude-odd-lots"],
+    );
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    if let Err(e) = aggregate(&args, input) {
+        cli::fail(e);
+    }
+}
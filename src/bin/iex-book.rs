@@ -0,0 +1,178 @@
+//! Rebuilds the books of a DEEP HIST file, printing snapshots of their top levels
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use iex_parser::{
+    book::{Book, BookBuilder},
+    cli::{self, Args},
+    deep::Deep1_0Message,
+    filter::{MessageKind, MessageKinds},
+    hist::{self, HistReader},
+};
+
+const USAGE: &str = "\
+Usage: iex-book [OPTIONS] <HIST FILE>
+
+Rebuilds the price level books of a DEEP HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard
+input), and writes snapshots of their top levels on every change, or at a regular interval.
+
+Options:
+    --symbols <SYMBOLS>     The symbols of the books, which may be patterns (e.g. SPY,QQQ,ZIE*) [default: all]
+    --depth <LEVELS>        The number of levels of each side [default: 5]
+    --interval <DURATION>   Writes the snapshots of every book at a regular interval (e.g. 1s or 100ms) rather than on
+                            every change
+    --format <text|csv>     The output format [default: text]
+    --from <TIME>           Doesn't write the snapshots before a time (RFC 3339, or nanoseconds since the epoch)
+    --to <TIME>             Stops at a time
+    --output <FILE>         The output file [default: the standard output]";
+
+struct SnapshotWriter {
+    output: Box<dyn Write>,
+    depth: usize,
+    csv: bool,
+}
+
+impl SnapshotWriter {
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.csv {
+            return Ok(());
+        }
+        write!(self.output, "timestamp,symbol")?;
+        for side in ["bid", "ask"] {
+            for level in 1..=self.depth {
+                write!(self.output, ",{side}_price_{level},{side}_size_{level}")?;
+            }
+        }
+        writeln!(self.output)
+    }
+
+    fn write(&mut self, timestamp: DateTime<Utc>, symbol: &str, book: &Book) -> io::Result<()> {
+        let snapshot = book.snapshot(self.depth);
+        let timestamp = timestamp.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true);
+
+        if self.csv {
+            write!(self.output, "{timestamp},{symbol}")?;
+            for levels in [&snapshot.bids, &snapshot.asks] {
+                for level in 0..self.depth {
+                    match levels.get(level) {
+                        Some(level) => write!(self.output, ",{:.4},{}", level.price, level.size)?,
+                        None => write!(self.output, ",,")?,
+                    }
+                }
+            }
+            return writeln!(self.output);
+        }
+
+        write!(self.output, "{timestamp} {symbol:<8} bids:")?;
+        for level in &snapshot.bids {
+            write!(self.output, " {}@{:.4}", level.size, level.price)?;
+        }
+        write!(self.output, " | asks:")?;
+        for level in &snapshot.asks {
+            write!(self.output, " {}@{:.4}", level.size, level.price)?;
+        }
+        writeln!(self.output)
+    }
+
+    fn write_all(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        books: &BookBuilder<String>,
+    ) -> io::Result<()> {
+        let mut books = books.books().collect::<Vec<_>>();
+        books.sort_by_key(|(symbol, _)| *symbol);
+        for (symbol, book) in books {
+            self.write(timestamp, symbol, book)?;
+        }
+        Ok(())
+    }
+}
+
+// The first multiple of the interval (since the POSIX epoch) after a time
+fn next_multiple(timestamp: DateTime<Utc>, interval: TimeDelta) -> DateTime<Utc> {
+    let interval = interval.num_nanoseconds().unwrap_or(i64::MAX);
+    let nanos = timestamp.timestamp_nanos_opt().unwrap_or_default();
+    DateTime::from_timestamp_nanos(nanos - nanos.rem_euclid(interval) + interval)
+}
+
+fn rebuild(args: &Args, input: &str) -> Result<(), hist::Error> {
+    let depth = args
+        .parsed("depth", |depth| {
+            depth.parse::<usize>().map_err(|e| e.to_string())
+        })
+        .unwrap_or(5);
+    let interval = args.parsed("interval", cli::parse_duration);
+    let from = args.parsed("from", cli::parse_time);
+    let to = args.parsed("to", cli::parse_time);
+    let csv = match args.value("format").unwrap_or("text") {
+        "text" => false,
+        "csv" => true,
+        format => cli::fail(format!("unknown format {format:?}")),
+    };
+
+    // The books are rebuilt from the start of the file, whatever the time range
+    let filter = args.symbol_filter().kinds(
+        [
+            MessageKind::PriceLevelUpdateBuy,
+            MessageKind::PriceLevelUpdateSell,
+        ]
+        .into_iter()
+        .collect::<MessageKinds>(),
+    );
+    let messages = HistReader::new(cli::open_input(input)?)?
+        .messages::<Deep1_0Message<String>>()
+        .with_filter(filter);
+
+    let mut writer = SnapshotWriter {
+        output: cli::open_output(args.value("output"))?,
+        depth,
+        csv,
+    };
+    writer.write_header()?;
+
+    let mut books = BookBuilder::new();
+    let mut next_snapshot = None;
+    for message in messages {
+        let Deep1_0Message::PriceLevelUpdate(update) = message? else {
+            continue;
+        };
+
+        if let Some(interval) = interval {
+            let next =
+                next_snapshot.get_or_insert_with(|| next_multiple(update.timestamp, interval));
+            while *next <= update.timestamp && to.is_none_or(|to| *next < to) {
+                if from.is_none_or(|from| *next >= from) {
+                    writer.write_all(*next, &books)?;
+                }
+                *next += interval;
+            }
+        }
+        if to.is_some_and(|to| update.timestamp >= to) {
+            break;
+        }
+
+        let changed = books.apply(&update);
+        if interval.is_none() && changed && from.is_none_or(|from| update.timestamp >= from) {
+            let book = books
+                .book(&update.symbol)
+                .expect("the book was just updated");
+            writer.write(update.timestamp, &update.symbol, book)?;
+        }
+    }
+
+    writer.output.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    if let Err(e) = rebuild(&args, input) {
+        cli::fail(e);
+    }
+}
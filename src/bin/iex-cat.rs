@@ -0,0 +1,102 @@
+//! Merges HIST files into a single one, in chronological order, dropping the messages seen more than once
+
+use std::{cmp::Reverse, collections::BinaryHeap, io::Write};
+
+use chrono::{DateTime, Utc};
+use iex_parser::{
+    cli::{self, Args, Input},
+    hist::{self, HistReader, HistWriter},
+    iex_tp::{iex_tp_segment, IexTpSegment},
+    sequence::{SequenceTracker, Sequencing},
+};
+
+const USAGE: &str = "\
+Usage: iex-cat [OPTIONS] <HIST FILE>...
+
+Merges HIST files (pcap or pcapng captures, optionally gzipped, or - for the standard input), e.g. the TOPS and DEEP
+files of a day or several partial captures of a feed, into a single pcap file. Segments are ordered by capture time
+(or send time, if they weren't captured), and messages already seen with the same stream and sequence number are
+dropped.
+
+Options:
+    --keep-duplicates   Copies every segment, even if its messages were already seen
+    --output <FILE>     The output file [default: the standard output]";
+
+/// The next segment of an input, copied so that the inputs can be read side by side
+struct Pending {
+    /// The capture time, or the send time if the segment wasn't captured
+    time: DateTime<Utc>,
+    payload: Vec<u8>,
+}
+
+fn next_pending(reader: &mut HistReader<Input>) -> Result<Option<Pending>, hist::Error> {
+    Ok(reader.next_segment()?.map(|captured| Pending {
+        time: captured.capture_time.unwrap_or(captured.segment.send_time),
+        payload: captured.payload.to_vec(),
+    }))
+}
+
+fn merge(args: &Args, inputs: &[String]) -> Result<(), hist::Error> {
+    let mut readers = inputs
+        .iter()
+        .map(|path| HistReader::new(cli::open_input(path)?))
+        .collect::<Result<Vec<_>, hist::Error>>()?;
+    let mut pending = readers
+        .iter_mut()
+        .map(next_pending)
+        .collect::<Result<Vec<_>, _>>()?;
+    // The inputs by the time of their next segment, earliest (then first given) first
+    let mut queue = pending
+        .iter()
+        .enumerate()
+        .filter_map(|(index, next)| next.as_ref().map(|next| Reverse((next.time, index))))
+        .collect::<BinaryHeap<_>>();
+
+    let keep_duplicates = args.flag("keep-duplicates");
+    let mut tracker = SequenceTracker::new();
+    let mut writer = HistWriter::new(cli::open_output(args.value("output"))?)?;
+
+    while let Some(Reverse((_, index))) = queue.pop() {
+        let next = pending[index]
+            .take()
+            .expect("queued inputs have a pending segment");
+        let Ok((_, IexTpSegment::V1(segment))) = iex_tp_segment(&next.payload) else {
+            unreachable!("the segment was already decoded")
+        };
+
+        match tracker.update(&segment) {
+            Sequencing::Duplicate(range) if !keep_duplicates => {
+                // Only keep the new messages following the duplicate ones, if any
+                let mut sequence_number = segment.first_message_sequence_no;
+                for run in segment.runs(|_| {
+                    sequence_number += 1;
+                    sequence_number > range.end()
+                }) {
+                    writer.write_segment(next.time, &run)?;
+                }
+            }
+            _ => writer.write_payload(next.time, &next.payload)?,
+        }
+
+        pending[index] = next_pending(&mut readers[index])?;
+        if let Some(next) = &pending[index] {
+            queue.push(Reverse((next.time, index)));
+        }
+    }
+
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["keep-duplicates"]);
+    let inputs = args.positional();
+    if inputs.is_empty() {
+        eprintln!("{USAGE}");
+        cli::fail("expected at least one HIST file");
+    }
+
+    if let Err(e) = merge(&args, inputs) {
+        cli::fail(e);
+    }
+}
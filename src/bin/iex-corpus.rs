@@ -0,0 +1,136 @@
+//! Generates large TOPS HIST files with a configurable mix of messages, as standard workloads for benchmarks
+
+use std::io::Write;
+
+use iex_parser::{
+    cli::{self, Args},
+    corpus::{write_corpus, CorpusConfig, MIXABLE_KINDS},
+    filter::MessageKind,
+    hist::{self, HistWriter},
+    pcap::CaptureFormat,
+};
+
+const USAGE: &str = "\
+Usage: iex-corpus [OPTIONS] --size <SIZE> --output <FILE>
+
+Generates a TOPS HIST file (a pcap capture) of at least some size, for benchmarks: the system events of a trading day,
+the security directory and trading status of each symbol, then messages of every kind, in the proportions of a mix, in
+segments of a few messages. The same options always generate the same file.
+
+Options:
+    --size <SIZE>                The size of the file, in bytes or with a K, M or G suffix (e.g. 4G)
+    --symbols <N>                The number of symbols [default: 8000]
+    --rate <RATE>                The average number of messages per second [default: 100000]
+    --mix <KIND=WEIGHT>          The relative frequency of kinds of messages, by name or code, e.g. Q=90,T=10
+                                 [default: Q=90,T=8,A=1,X=0.3,H=0.2,P=0.2,D=0.2,O=0.05,B=0.05]
+    --segment-messages <N>       The maximum number of messages per segment [default: 4]
+    --seed <N>                   The seed of the random generator [default: 1]
+    --pcapng                     Write a pcapng capture rather than a classic pcap one
+    --output <FILE>              The output file, or - for the standard output";
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (digits, unit) = match s.char_indices().last() {
+        Some((index, 'K' | 'k')) => (&s[..index], 1 << 10),
+        Some((index, 'M' | 'm')) => (&s[..index], 1 << 20),
+        Some((index, 'G' | 'g')) => (&s[..index], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(unit))
+        .ok_or_else(|| format!("invalid size {s:?}"))
+}
+
+fn parse_mix(s: &str) -> Result<(MessageKind, f64), String> {
+    let (name, weight) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KIND=WEIGHT, got {s:?}"))?;
+    let kind =
+        MessageKind::from_name(name).ok_or_else(|| format!("unknown message kind {name:?}"))?;
+    match weight.parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok((kind, weight)),
+        _ => Err(format!("invalid weight {weight:?}")),
+    }
+}
+
+fn generate(args: &Args) -> Result<u64, hist::Error> {
+    let mut config = CorpusConfig::default();
+    let Some(size) = args.parsed("size", parse_size) else {
+        cli::fail("expected --size");
+    };
+    if let Some(symbols) = args.parsed("symbols", |n| {
+        n.parse().map_err(|_| format!("invalid number {n:?}"))
+    }) {
+        config.symbols = symbols;
+    }
+    if let Some(rate) = args.parsed("rate", |rate| {
+        rate.parse::<f64>().map_err(|e| e.to_string())
+    }) {
+        config.message_rate = rate;
+    }
+    if let Some(count) = args.parsed("segment-messages", |n| {
+        n.parse().map_err(|_| format!("invalid number {n:?}"))
+    }) {
+        config.max_segment_messages = count;
+    }
+    if let Some(seed) = args.parsed("seed", |seed| {
+        seed.parse().map_err(|_| format!("invalid seed {seed:?}"))
+    }) {
+        config.seed = seed;
+    }
+    let mix = args.values("mix");
+    if !mix.is_empty() {
+        config.mix = mix
+            .into_iter()
+            .map(|entry| parse_mix(entry).unwrap_or_else(|e| cli::fail(format!("--mix: {e}"))))
+            .collect();
+    }
+
+    if config.symbols == 0 {
+        cli::fail("--symbols: there must be at least one symbol");
+    }
+    if !(config.message_rate.is_finite() && config.message_rate > 0.0) {
+        cli::fail("--rate: the rate must be positive");
+    }
+    if config.max_segment_messages == 0 {
+        cli::fail("--segment-messages: segments must have at least one message");
+    }
+    if let Some((kind, _)) = config
+        .mix
+        .iter()
+        .find(|(kind, _)| !MIXABLE_KINDS.contains(kind))
+    {
+        cli::fail(format!("--mix: {} messages can't be mixed", kind.name()));
+    }
+    if !config.mix.iter().any(|(_, weight)| *weight > 0.0) {
+        cli::fail("--mix: at least one weight must be positive");
+    }
+
+    let format = if args.flag("pcapng") {
+        CaptureFormat::PcapNg
+    } else {
+        CaptureFormat::Pcap
+    };
+    let Some(output) = args.value("output") else {
+        cli::fail("expected --output");
+    };
+
+    let mut writer = HistWriter::with_format(cli::open_output(Some(output))?, format)?;
+    let written = write_corpus(&mut writer, config, size)?;
+    writer.into_inner().flush()?;
+    Ok(written)
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["pcapng"]);
+    if !args.positional().is_empty() {
+        eprintln!("{USAGE}");
+        cli::fail("unexpected arguments");
+    }
+
+    match generate(&args) {
+        Ok(written) => eprintln!("wrote {written} bytes"),
+        Err(e) => cli::fail(e),
+    }
+}
@@ -0,0 +1,95 @@
+//! Compares two captures of the same feed message by message, reporting the messages missing from either one and the
+//! skew between their capture times
+
+use std::{fmt::Display, process};
+
+use chrono::TimeDelta;
+use iex_parser::{
+    cli::{self, Args},
+    compare::{self, Comparison, SkewStats},
+    hist::HistReader,
+    message_protocol_ids,
+    sequence::SequenceRange,
+};
+
+const USAGE: &str = "\
+Usage: iex-diff [OPTIONS] <LEFT HIST FILE> <RIGHT HIST FILE>
+
+Compares two captures of the same feed (pcap or pcapng captures, optionally gzipped), e.g. from two capture hosts,
+message by message: messages are matched by stream and sequence number. Prints the ranges of messages found in a single
+capture or with different contents, and statistics of the capture time skew (right minus left) of the matched
+messages. Exits with status 2 if the captures don't have the same messages.
+
+Options:
+    --max-ranges <N>   The number of ranges listed of each kind [default: 20]";
+
+fn print_ranges(title: &str, ranges: &[SequenceRange], max_ranges: usize) {
+    let messages: i64 = ranges.iter().map(|range| range.count).sum();
+    println!("{title}: {messages} messages in {} ranges", ranges.len());
+    for range in ranges.iter().take(max_ranges) {
+        let protocol = match range.stream.message_protocol_id {
+            message_protocol_ids::TOPS => "TOPS",
+            message_protocol_ids::DEEP_1_0 => "DEEP",
+            _ => "?",
+        };
+        println!(
+            "  {protocol} session {}: {}..{} ({} messages, sent at {})",
+            range.stream.session_id,
+            range.first,
+            range.end(),
+            range.count,
+            range.send_time
+        );
+    }
+    if ranges.len() > max_ranges {
+        println!("  ... and {} more", ranges.len() - max_ranges);
+    }
+}
+
+fn print_skew(skew: &SkewStats) {
+    let show = |delta: Option<TimeDelta>| -> Box<dyn Display> {
+        match delta.and_then(|delta| delta.num_nanoseconds()) {
+            Some(nanos) => Box::new(format!("{:.3}us", nanos as f64 / 1e3)),
+            None => Box::new("-"),
+        }
+    };
+    println!(
+        "Capture time skew: min {}, mean {}, max {} ({} messages)",
+        show(skew.min),
+        show(skew.mean()),
+        show(skew.max),
+        skew.count
+    );
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [left, right] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected two HIST files");
+    };
+    let max_ranges = args
+        .parsed("max-ranges", |n| {
+            n.parse::<usize>().map_err(|e| e.to_string())
+        })
+        .unwrap_or(20);
+
+    let open = |path: &str| {
+        cli::open_input(path)
+            .map_err(Into::into)
+            .and_then(HistReader::new)
+            .unwrap_or_else(|e| cli::fail(format!("{path}: {e}")))
+    };
+    let comparison: Comparison =
+        compare::compare(open(left), open(right)).unwrap_or_else(|e| cli::fail(e));
+
+    println!("Matched: {} messages", comparison.matched);
+    print_ranges("Only in left", &comparison.only_left, max_ranges);
+    print_ranges("Only in right", &comparison.only_right, max_ranges);
+    print_ranges("Different", &comparison.different, max_ranges);
+    print_skew(&comparison.skew);
+
+    if !comparison.is_identical() {
+        process::exit(2);
+    }
+}
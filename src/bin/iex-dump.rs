@@ -0,0 +1,37 @@
+//! Converts a HIST file to CSV or JSON Lines
+
+use iex_parser::cli::{self, Args};
+
+const USAGE: &str = "\
+Usage: iex-dump [OPTIONS] <HIST FILE>
+
+Converts a HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard input) to CSV or JSON Lines.
+The itch format normalizes quotes, price levels and trades into ITCH-like add, cancel, replace, delete and trade
+messages, written as JSON Lines.
+
+Options:
+    --protocol <tops|deep>      The protocol of the feed [default: tops]
+    --format <csv|jsonl|itch>   The output format [default: jsonl]
+    --kinds <KINDS>             The kinds of messages to keep, by name (e.g. quote_update) or code (e.g. Q)
+    --symbols <SYMBOLS>         The symbols to keep, which may be patterns (e.g. SPY,QQQ,ZIE*)
+    --from <TIME>               Drops the messages before a time (RFC 3339, or nanoseconds since the epoch)
+    --to <TIME>                 Drops the messages from a time
+    --where <EXPRESSION>        Only keeps the messages matching an expression, e.g. \"symbol in (SPY, QQQ) and kind = trade and ts >= 14:30\"
+    --metadata <FILE>           Adds the sector, primary exchange and lot size of the symbols, from a CSV file with a
+                                symbol column, to the records
+    --by-day <DIRECTORY>        Writes a file per trading day (Eastern date of its session) into a directory, e.g.
+                                2017-04-17.csv, instead of the output file
+    --output <FILE>             The output file [default: the standard output]";
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    let format = args.value("format").unwrap_or("jsonl");
+    if let Err(e) = cli::write_messages(&args, input, format) {
+        cli::fail(e);
+    }
+}
@@ -0,0 +1,80 @@
+//! Extracts the messages of some symbols, kinds or time window from a HIST file
+
+use std::io::Write;
+
+use iex_parser::{
+    cli::{self, Args},
+    hist::{self, HistWriter},
+    message_protocol_ids,
+};
+
+const USAGE: &str = "\
+Usage: iex-grep [OPTIONS] <HIST FILE>
+
+Extracts the messages of some symbols, kinds or time window from a HIST file (a pcap or pcapng capture, optionally
+gzipped, or - for the standard input), into a smaller HIST file or a normalized CSV or JSON Lines file.
+
+In HIST files, the messages kept keep their sequence numbers: each segment is split into segments of consecutive
+messages, so the messages dropped appear as gaps.
+
+Options:
+    --format <pcap|csv|jsonl>   The output format [default: pcap]
+    --protocol <tops|deep>      Only keeps the messages of a protocol [default: both in pcap, tops otherwise]
+    --kinds <KINDS>             The kinds of messages to keep, by name (e.g. quote_update) or code (e.g. Q)
+    --symbols <SYMBOLS>         The symbols to keep, which may be patterns (e.g. SPY,QQQ,ZIE*)
+    --from <TIME>               Drops the messages before a time (RFC 3339, or nanoseconds since the epoch)
+    --to <TIME>                 Drops the messages from a time
+    --where <EXPRESSION>        Only keeps the messages matching an expression, e.g. \"symbol in (SPY, QQQ) and kind = trade and ts >= 14:30\"
+    --metadata <FILE>           With csv or jsonl, adds the sector, primary exchange and lot size of the symbols, from a
+                                CSV file with a symbol column, to the records
+    --by-day <DIRECTORY>        With csv or jsonl, writes a file per trading day (Eastern date of its session) into a
+                                directory, e.g. 2017-04-17.csv, instead of the output file
+    --output <FILE>             The output file [default: the standard output]";
+
+fn extract(args: &Args, input: &str) -> Result<(), hist::Error> {
+    let protocols = match args.value("protocol") {
+        None => [message_protocol_ids::TOPS, message_protocol_ids::DEEP_1_0].as_slice(),
+        Some("tops") => &[message_protocol_ids::TOPS],
+        Some("deep") => &[message_protocol_ids::DEEP_1_0],
+        Some(protocol) => cli::fail(format!("unknown protocol {protocol:?}")),
+    };
+    let filter = args.message_filter();
+
+    let mut reader = cli::open_hist(args, input)?;
+    let mut writer = HistWriter::new(cli::open_output(args.value("output"))?)?;
+    let (mut kept, mut total) = (0u64, 0u64);
+
+    while let Some(captured) = reader.next_segment()? {
+        let segment = &captured.segment;
+        total += segment.messages.len() as u64;
+        if !protocols.contains(&segment.message_protocol_id) {
+            continue;
+        }
+        let capture_time = captured.capture_time.unwrap_or(segment.send_time);
+
+        for run in segment.runs(|message| filter.accepts(message)) {
+            kept += run.messages.len() as u64;
+            writer.write_segment(capture_time, &run)?;
+        }
+    }
+
+    writer.into_inner().flush()?;
+    eprintln!("kept {kept} of {total} messages");
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    let result = match args.value("format").unwrap_or("pcap") {
+        "pcap" => extract(&args, input),
+        format => cli::write_messages(&args, input, format),
+    };
+    if let Err(e) = result {
+        cli::fail(e);
+    }
+}
@@ -0,0 +1,64 @@
+//! Builds the sidecar index of a HIST file, which lets the other tools start reading it at a time
+
+use std::{fs::File, io::BufWriter};
+
+use iex_parser::{
+    cli::{self, Args},
+    hist::{self, HistReader},
+    index::HistIndex,
+};
+
+const USAGE: &str = "\
+Usage: iex-index [OPTIONS] <HIST FILE>
+
+Builds the sidecar index of an uncompressed HIST file (<HIST FILE>.idx), mapping send times to offsets, and prints its
+statistics. The other tools use the index to start reading the file at the time given by their --from option.
+
+Options:
+    --interval <DURATION>   The interval between the entries of the index (e.g. 1s or 100ms) [default: 1s]
+    --output <FILE>         The index file [default: <HIST FILE>.idx]";
+
+fn build(args: &Args, input: &str) -> Result<(), hist::Error> {
+    let interval = args
+        .parsed("interval", cli::parse_duration)
+        .unwrap_or(chrono::TimeDelta::seconds(1));
+    let index = HistIndex::build(HistReader::new(cli::open_input(input)?)?, interval)?;
+
+    let output = args
+        .value("output")
+        .map(Into::into)
+        .unwrap_or_else(|| HistIndex::sidecar_path(input));
+    index.write_to(BufWriter::new(File::create(&output)?))?;
+
+    let entries = index.entries();
+    println!("Index: {}", output.display());
+    println!("File length: {} bytes", index.file_length());
+    println!("Segments: {}", index.segments());
+    println!("Entries: {} (every {})", entries.len(), index.interval());
+    if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+        println!("First entry: {}", first.send_time);
+        println!("Last entry: {}", last.send_time);
+        println!(
+            "Average distance between entries: {} bytes",
+            index.file_length() / entries.len() as u64
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+    if input == "-" || input.ends_with(".gz") {
+        cli::fail(
+            "only uncompressed files can be indexed, as the others can't be read from an offset",
+        );
+    }
+
+    if let Err(e) = build(&args, input) {
+        cli::fail(e);
+    }
+}
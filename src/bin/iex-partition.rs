@@ -0,0 +1,229 @@
+//! Converts a HIST file to a directory of CSV or Parquet files partitioned by kind of message, and optionally by symbol
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "parquet")]
+use iex_parser::parquet::{self, ParquetWriter};
+use iex_parser::{
+    cli::{self, Args},
+    deep::Deep1_0Message,
+    export::{CsvWriter, Record, ToRecord},
+    filter::MessageKind,
+    hist::{self, Message},
+    tops::Tops1_6Message,
+};
+
+const USAGE: &str = "\
+Usage: iex-partition [OPTIONS] --output <DIRECTORY> <HIST FILE>
+
+Converts a HIST file (a pcap or pcapng capture, optionally gzipped) to CSV or Parquet files partitioned by kind of
+message, and optionally by symbol, in the Hive layout (<DIRECTORY>/kind=trade_report/symbol=ZIEXT/part.csv).
+
+Parquet files are written if built with the parquet feature.
+
+Options:
+    --output <DIRECTORY>     The output directory
+    --format <csv|parquet>   The format of the files [default: csv]
+    --protocol <tops|deep>   The protocol of the feed [default: tops]
+    --by-symbol              Partitions the messages by symbol too
+    --kinds <KINDS>          The kinds of messages to keep, by name (e.g. quote_update) or code (e.g. Q)
+    --symbols <SYMBOLS>      The symbols to keep, which may be patterns (e.g. SPY,QQQ,ZIE*)
+    --from <TIME>            Drops the messages before a time (RFC 3339, or nanoseconds since the epoch)
+    --to <TIME>              Drops the messages from a time
+    --where <EXPRESSION>     Only keeps the messages matching an expression, e.g. \"symbol in (SPY, QQQ) and kind = trade and ts >= 14:30\"
+    --quiet                  Doesn't report the progress";
+
+/// The maximum number of partitions written at once. Partitioning by symbol creates thousands of files, so the least
+/// recently opened ones are closed (and later appended to) past this limit.
+const MAX_OPEN_PARTITIONS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            #[cfg(feature = "parquet")]
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+enum PartitionWriter {
+    Csv(CsvWriter<BufWriter<File>>),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetWriter<BufWriter<File>>),
+}
+
+impl PartitionWriter {
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self {
+            PartitionWriter::Csv(writer) => writer.write(record),
+            #[cfg(feature = "parquet")]
+            PartitionWriter::Parquet(writer) => writer.write(record),
+        }
+    }
+}
+
+struct Partitions {
+    directory: PathBuf,
+    by_symbol: bool,
+    format: Format,
+    open: HashMap<PathBuf, PartitionWriter>,
+    /// The partitions opened in order, for closing the oldest ones first
+    open_order: Vec<PathBuf>,
+    created: HashSet<PathBuf>,
+    /// The Parquet files which were closed, to be appended to and ended later
+    #[cfg(feature = "parquet")]
+    suspended: HashMap<PathBuf, parquet::Suspended>,
+}
+
+impl Partitions {
+    fn path(&self, kind: MessageKind, symbol: Option<&str>) -> PathBuf {
+        let mut path = self.directory.join(format!("kind={}", kind.name()));
+        // Messages without a symbol (system events) aren't partitioned further
+        if let Some(symbol) = symbol.filter(|_| self.by_symbol) {
+            // Symbols are made of letters and a few punctuation marks, but keep them from escaping the directory
+            let symbol = symbol.replace(['/', '\\'], "_");
+            path.push(format!("symbol={symbol}"));
+        }
+        path.join(format!("part.{}", self.format.extension()))
+    }
+
+    fn writer(
+        &mut self,
+        path: &Path,
+        kind: MessageKind,
+    ) -> Result<&mut PartitionWriter, hist::Error> {
+        if !self.open.contains_key(path) {
+            if self.open.len() >= MAX_OPEN_PARTITIONS {
+                let oldest = self.open_order.remove(0);
+                match self.open.remove(&oldest) {
+                    Some(PartitionWriter::Csv(writer)) => writer.into_inner().flush()?,
+                    #[cfg(feature = "parquet")]
+                    Some(PartitionWriter::Parquet(writer)) => {
+                        let (mut file, suspended) = writer.suspend()?;
+                        file.flush()?;
+                        self.suspended.insert(oldest, suspended);
+                    }
+                    None => {}
+                }
+            }
+
+            let writer = if self.created.insert(path.to_path_buf()) {
+                fs::create_dir_all(path.parent().expect("partitions are in a directory"))?;
+                let file = BufWriter::new(File::create(path)?);
+                match self.format {
+                    Format::Csv => PartitionWriter::Csv(CsvWriter::new(file, [kind])?),
+                    #[cfg(feature = "parquet")]
+                    Format::Parquet => PartitionWriter::Parquet(ParquetWriter::new(file, [kind])?),
+                }
+            } else {
+                let file = BufWriter::new(OpenOptions::new().append(true).open(path)?);
+                match self.format {
+                    Format::Csv => PartitionWriter::Csv(CsvWriter::append(file, [kind])),
+                    #[cfg(feature = "parquet")]
+                    Format::Parquet => {
+                        let suspended = self
+                            .suspended
+                            .remove(path)
+                            .expect("closed Parquet files are suspended");
+                        PartitionWriter::Parquet(ParquetWriter::resume(file, suspended))
+                    }
+                }
+            };
+            self.open.insert(path.to_path_buf(), writer);
+            self.open_order.push(path.to_path_buf());
+        }
+        Ok(self
+            .open
+            .get_mut(path)
+            .expect("the partition was just opened"))
+    }
+
+    fn finish(self) -> Result<(), hist::Error> {
+        for (_, writer) in self.open {
+            match writer {
+                PartitionWriter::Csv(writer) => writer.into_inner().flush()?,
+                #[cfg(feature = "parquet")]
+                PartitionWriter::Parquet(writer) => writer.finish()?.flush()?,
+            }
+        }
+        #[cfg(feature = "parquet")]
+        for (path, suspended) in self.suspended {
+            let file = BufWriter::new(OpenOptions::new().append(true).open(path)?);
+            ParquetWriter::resume(file, suspended).finish()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn convert<M>(args: &Args, input: &str, directory: &str, format: Format) -> Result<(), hist::Error>
+where
+    M: Message<Symbol = String> + ToRecord,
+{
+    let mut reader = cli::open_hist(args, input)?;
+    if !args.flag("quiet") {
+        reader = reader.with_progress(cli::progress_reporter(input));
+    }
+    let messages = reader.messages::<M>().with_filter(args.message_filter());
+    let mut partitions = Partitions {
+        directory: PathBuf::from(directory),
+        by_symbol: args.flag("by-symbol"),
+        format,
+        open: HashMap::new(),
+        open_order: Vec::new(),
+        created: HashSet::new(),
+        #[cfg(feature = "parquet")]
+        suspended: HashMap::new(),
+    };
+
+    for message in messages {
+        let message = message?;
+        let path = partitions.path(message.kind(), message.symbol().map(String::as_str));
+        partitions
+            .writer(&path, message.kind())?
+            .write(&message.to_record())?;
+    }
+    partitions.finish()
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["by-symbol", "quiet"]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+    let Some(directory) = args.value("output") else {
+        eprintln!("{USAGE}");
+        cli::fail("expected an output directory");
+    };
+
+    let format = match args.value("format").unwrap_or("csv") {
+        "csv" => Format::Csv,
+        #[cfg(feature = "parquet")]
+        "parquet" => Format::Parquet,
+        #[cfg(not(feature = "parquet"))]
+        "parquet" => cli::fail("Parquet output requires the parquet feature"),
+        format => cli::fail(format!("unknown format {format:?}")),
+    };
+
+    let result = match args.value("protocol").unwrap_or("tops") {
+        "tops" => convert::<Tops1_6Message<String>>(&args, input, directory, format),
+        "deep" => convert::<Deep1_0Message<String>>(&args, input, directory, format),
+        protocol => cli::fail(format!("unknown protocol {protocol:?}")),
+    };
+    if let Err(e) = result {
+        cli::fail(e);
+    }
+}
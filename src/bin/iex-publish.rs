@@ -0,0 +1,141 @@
+//! Publishes the messages of a HIST file to the processes connected to a Unix domain socket, or to a shared-memory ring
+
+use std::{thread, time::Duration};
+
+use iex_parser::{
+    cli::{self, Args},
+    deep::Deep1_0Message,
+    export::ToRecord,
+    hist::{self, Message},
+    ipc::FramePublisher,
+    replay::Pacer,
+    ring::{RingPublisher, RingRecord},
+    tops::Tops1_6Message,
+};
+
+const USAGE: &str = "\
+Usage: iex-publish [OPTIONS] (--socket <PATH> | --ring <PATH>) <HIST FILE>
+
+Publishes the messages of a HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard input) to
+other processes, paced by their timestamps.
+
+With --socket, the messages are sent to the processes connected to a Unix domain socket, as frames: the length of the
+message as a little-endian 32-bit integer, followed by the message as a JSON object, as written by iex-dump.
+
+With --ring, the messages are written as fixed-size records to a shared-memory ring, which any number of processes may
+read without slowing the publisher down (see examples/ring_reader.c).
+
+Options:
+    --socket <PATH>          The socket file to create
+    --subscribers <N>        Waits for N subscribers to the socket before publishing [default: 0]
+    --ring <PATH>            The ring file to create, e.g. /dev/shm/iex
+    --capacity <N>           The number of records of the ring, a power of two [default: 65536]
+    --speed <MULTIPLIER>     The replay speed relative to the original [default: 1]
+    --max-speed              Publishes the messages as fast as possible
+    --protocol <tops|deep>   The protocol of the feed [default: tops]
+    --kinds <KINDS>          The kinds of messages to publish, by name (e.g. quote_update) or code (e.g. Q)
+    --symbols <SYMBOLS>      The symbols to publish, which may be patterns (e.g. SPY,QQQ,ZIE*)
+    --from <TIME>            Drops the messages before a time (RFC 3339, or nanoseconds since the epoch)
+    --to <TIME>              Drops the messages from a time
+    --where <EXPRESSION>     Only publishes the messages matching an expression, e.g. \"symbol in (SPY, QQQ) and kind = trade and ts >= 14:30\"";
+
+enum Publisher {
+    Socket(FramePublisher),
+    Ring(RingPublisher),
+}
+
+fn publish<M>(args: &Args, input: &str, publisher: &mut Publisher) -> Result<u64, hist::Error>
+where
+    M: Message + ToRecord,
+    for<'a> RingRecord: From<&'a M>,
+{
+    let mut pacer = if args.flag("max-speed") {
+        Pacer::unpaced()
+    } else {
+        let speed = args
+            .parsed("speed", |speed| {
+                speed.parse::<f64>().map_err(|e| e.to_string())
+            })
+            .unwrap_or(1.0);
+        if !(speed.is_finite() && speed > 0.0) {
+            cli::fail("--speed: the speed must be positive");
+        }
+        Pacer::new(speed)
+    };
+
+    let mut published = 0;
+    let messages = cli::open_hist(args, input)?
+        .messages::<M>()
+        .with_filter(args.message_filter());
+    for message in messages {
+        let message = message?;
+        if let Some(timestamp) = message.timestamp() {
+            pacer.wait(timestamp);
+        }
+        match publisher {
+            Publisher::Socket(publisher) => publisher.publish(&message)?,
+            Publisher::Ring(publisher) => publisher.publish(&RingRecord::from(&message)),
+        }
+        published += 1;
+    }
+    Ok(published)
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["max-speed"]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    let mut publisher = match (args.value("socket"), args.value("ring")) {
+        (Some(socket), None) => {
+            let subscribers = args
+                .parsed("subscribers", |n| {
+                    n.parse::<usize>().map_err(|e| e.to_string())
+                })
+                .unwrap_or(0);
+            let mut publisher = FramePublisher::bind(socket)
+                .unwrap_or_else(|e| cli::fail(format!("{socket}: {e}")));
+            loop {
+                match publisher.accept() {
+                    Ok(connected) if connected >= subscribers => break,
+                    Ok(_) => thread::sleep(Duration::from_millis(10)),
+                    Err(e) => cli::fail(e),
+                }
+            }
+            Publisher::Socket(publisher)
+        }
+        (None, Some(ring)) => {
+            let capacity = args
+                .parsed("capacity", |n| match n.parse::<usize>() {
+                    Ok(n) if n.is_power_of_two() => Ok(n),
+                    Ok(_) => Err("the capacity must be a power of two".to_string()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .unwrap_or(1 << 16);
+            Publisher::Ring(
+                RingPublisher::create(ring, capacity)
+                    .unwrap_or_else(|e| cli::fail(format!("{ring}: {e}"))),
+            )
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            cli::fail("expected either --socket or --ring");
+        }
+    };
+
+    let published = match args.value("protocol").unwrap_or("tops") {
+        "tops" => publish::<Tops1_6Message<String>>(&args, input, &mut publisher),
+        "deep" => publish::<Deep1_0Message<String>>(&args, input, &mut publisher),
+        protocol => cli::fail(format!("unknown protocol {protocol:?}")),
+    };
+    match (published, &publisher) {
+        (Ok(published), Publisher::Socket(publisher)) => eprintln!(
+            "published {published} messages, {} subscribers left",
+            publisher.subscribers()
+        ),
+        (Ok(published), Publisher::Ring(_)) => eprintln!("published {published} messages"),
+        (Err(e), _) => cli::fail(e),
+    }
+}
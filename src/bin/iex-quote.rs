@@ -0,0 +1,166 @@
+//! Prints the quote, last trade and trading status of a symbol at some point in time
+
+use chrono::{DateTime, Utc};
+use iex_parser::{
+    cli::{self, Args, Input},
+    filter::{raw_symbol, raw_timestamp},
+    hist::{self, HistReader, Message},
+    message_protocol_ids,
+    snapshot::{MarketSnapshot, SymbolSnapshot},
+    tops::Tops1_6Message,
+};
+
+const USAGE: &str = "\
+Usage: iex-quote [OPTIONS] <HIST FILE> <SYMBOL> <TIME>
+
+Prints the prevailing quote, the last trade and the trading status of a symbol at some point in time (RFC 3339, or
+nanoseconds since the POSIX epoch), from a TOPS HIST file. Messages timestamped at that time are included.
+
+If the file has an up-to-date sidecar index (see iex-index), it's searched backwards from that time, stopping once the
+quote, the last trade and the trading status are found. Otherwise it's read from the start.";
+
+/// What is known of a symbol at the queried time, from the messages read so far
+struct Query {
+    /// The symbol, padded with spaces
+    symbol: [u8; 8],
+    found: SymbolSnapshot<String>,
+}
+
+impl Query {
+    fn new(symbol: &str) -> Self {
+        let mut padded = [b' '; 8];
+        padded[..symbol.len()].copy_from_slice(symbol.as_bytes());
+        Self {
+            symbol: padded,
+            found: SymbolSnapshot::default(),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.found.quote.is_some()
+            && self.found.last_trade.is_some()
+            && self.found.trading_status.is_some()
+    }
+
+    /// Reads the messages of a range of the file, up to its end offset or, without one, up to the queried time, and
+    /// keeps what wasn't found in later ranges
+    fn scan(
+        &mut self,
+        reader: &mut HistReader<Input>,
+        time: DateTime<Utc>,
+        end: Option<u64>,
+    ) -> Result<(), hist::Error> {
+        let mut snapshot = MarketSnapshot::<String>::new();
+        'segments: while end.is_none_or(|end| reader.position() < end) {
+            let Some(captured) = reader.next_segment()? else {
+                break;
+            };
+            if captured.segment.message_protocol_id != message_protocol_ids::TOPS {
+                continue;
+            }
+
+            for message in &captured.segment.messages {
+                if raw_timestamp(message).is_some_and(|timestamp| timestamp > time) {
+                    break 'segments;
+                }
+                if raw_symbol(message) != Some(&self.symbol) {
+                    continue;
+                }
+                if let Ok((_, message)) = Tops1_6Message::<String>::parse(message) {
+                    snapshot.update(&message);
+                }
+            }
+        }
+
+        if let Some(found) = snapshot.iter().next().map(|(_, found)| found) {
+            let known = &mut self.found;
+            known.quote = known.quote.take().or_else(|| found.quote.clone());
+            known.last_trade = known.last_trade.take().or_else(|| found.last_trade.clone());
+            known.official_open = known
+                .official_open
+                .take()
+                .or_else(|| found.official_open.clone());
+            known.official_close = known
+                .official_close
+                .take()
+                .or_else(|| found.official_close.clone());
+            known.trading_status = known
+                .trading_status
+                .take()
+                .or_else(|| found.trading_status.clone());
+            known.operational_halt_status = known
+                .operational_halt_status
+                .take()
+                .or_else(|| found.operational_halt_status.clone());
+        }
+        Ok(())
+    }
+}
+
+fn run(
+    path: &str,
+    symbol: &str,
+    time: DateTime<Utc>,
+) -> Result<SymbolSnapshot<String>, hist::Error> {
+    let mut query = Query::new(symbol);
+
+    let mut reader = HistReader::new(cli::open_input(path)?)?;
+    let seekable = path != "-" && !path.ends_with(".gz");
+    let ranges = cli::open_index(path)
+        .filter(|_| seekable)
+        .map(|index| index.ranges_before(time))
+        .unwrap_or_default();
+
+    if ranges.is_empty() {
+        query.scan(&mut reader, time, None)?;
+    }
+    for (start, end) in ranges {
+        reader.seek(start)?;
+        query.scan(&mut reader, time, end)?;
+        if query.is_complete() {
+            break;
+        }
+    }
+    Ok(query.found)
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [path, symbol, time] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a HIST file, a symbol and a time");
+    };
+    if symbol.is_empty() || symbol.len() > 8 {
+        cli::fail(format!("invalid symbol: {symbol}"));
+    }
+    let time = cli::parse_time(time).unwrap_or_else(|e| cli::fail(e));
+
+    let found = run(path, symbol, time).unwrap_or_else(|e| cli::fail(e));
+
+    println!("{symbol} at {time}");
+    match &found.quote {
+        Some(quote) => println!(
+            "Quote:    {} x {:.4} / {:.4} x {} (at {})",
+            quote.bid_size, quote.bid_price, quote.ask_price, quote.ask_size, quote.timestamp
+        ),
+        None => println!("Quote:    -"),
+    }
+    match &found.last_trade {
+        Some(trade) => println!(
+            "Trade:    {} @ {:.4} (at {}, trade {})",
+            trade.size, trade.price, trade.timestamp, trade.id
+        ),
+        None => println!("Trade:    -"),
+    }
+    match &found.trading_status {
+        Some(status) => println!("Status:   {:?} (at {})", status.status, status.timestamp),
+        None => println!("Status:   -"),
+    }
+    if let Some(status) = &found.operational_halt_status {
+        println!("Halt:     {:?} (at {})", status.status, status.timestamp);
+    }
+    println!(
+        "Trading:  {}",
+        if found.is_trading() { "yes" } else { "no" }
+    );
+}
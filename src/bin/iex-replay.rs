@@ -0,0 +1,154 @@
+//! Re-transmits the IEX-TP segments of a HIST file over UDP, with their original timing
+
+use std::net::{SocketAddr, UdpSocket};
+
+use iex_parser::{
+    cli::{self, Args},
+    hist, message_protocol_ids,
+    replay::{MulticastSink, Pacer, SegmentEncoder},
+};
+
+const USAGE: &str = "\
+Usage: iex-replay [OPTIONS] --target <ADDRESS:PORT> <HIST FILE>
+
+Re-transmits the IEX-TP segments of a HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard
+input) as UDP datagrams to a multicast group or a unicast address, paced by their send times.
+
+With --reencode, the messages are instead re-encoded into new segments of a single session, numbered from 1 and paced
+by the timestamps of the messages. Only the messages of some kinds or symbols can then be sent, as a consistent feed.
+
+Options:
+    --target <ADDRESS:PORT>   The destination of the datagrams, e.g. 233.215.21.4:10378
+    --bind <ADDRESS:PORT>     The local address to send from [default: 0.0.0.0:0]
+    --speed <MULTIPLIER>      The replay speed relative to the original [default: 1]
+    --max-speed               Sends the segments as fast as possible
+    --ttl <HOPS>              The time-to-live of multicast datagrams [default: 1]
+    --protocol <tops|deep>    Only sends the segments of a protocol
+    --from <TIME>             Skips the segments sent before a time (RFC 3339, or nanoseconds since the epoch)
+    --to <TIME>               Stops at the first segment sent from a time
+    --reencode                Re-encodes the messages into new segments
+    --kinds <KINDS>           With --reencode, the kinds of messages to send, by name (e.g. quote_update) or code
+    --symbols <SYMBOLS>       With --reencode, the symbols to send, which may be patterns (e.g. SPY,QQQ,ZIE*)
+    --where <EXPRESSION>      With --reencode, only sends the messages matching an expression, e.g. \"kind = trade\"";
+
+fn parse<T>(value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|e: T::Err| e.to_string())
+}
+
+fn replay(args: &Args, input: &str, target: SocketAddr) -> Result<(), hist::Error> {
+    let bind = args
+        .parsed("bind", parse::<SocketAddr>)
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+    let socket = UdpSocket::bind(bind)?;
+    if target.ip().is_multicast() {
+        let ttl = args.parsed("ttl", parse::<u32>).unwrap_or(1);
+        if target.is_ipv4() {
+            socket.set_multicast_ttl_v4(ttl)?;
+        }
+    }
+
+    let protocol = args.value("protocol").map(|protocol| match protocol {
+        "tops" => message_protocol_ids::TOPS,
+        "deep" => message_protocol_ids::DEEP_1_0,
+        protocol => cli::fail(format!("unknown protocol {protocol:?}")),
+    });
+    let from = args.parsed("from", cli::parse_time);
+    let to = args.parsed("to", cli::parse_time);
+    let mut pacer = if args.flag("max-speed") {
+        Pacer::unpaced()
+    } else {
+        let speed = args.parsed("speed", parse::<f64>).unwrap_or(1.0);
+        if !(speed.is_finite() && speed > 0.0) {
+            cli::fail("--speed: the speed must be positive");
+        }
+        Pacer::new(speed)
+    };
+
+    let mut reader = cli::open_hist(args, input)?;
+    if args.flag("reencode") {
+        // The stream of the first segment is re-encoded, and the segments of other protocols skipped
+        let filter = args.message_filter();
+        let mut sink = None;
+        while let Some(captured) = reader.next_segment()? {
+            let segment = captured.segment;
+            if protocol.is_some_and(|protocol| segment.message_protocol_id != protocol)
+                || from.is_some_and(|from| segment.send_time < from)
+            {
+                continue;
+            }
+            if to.is_some_and(|to| segment.send_time >= to) {
+                break;
+            }
+
+            let (sink, message_protocol_id) = match &mut sink {
+                Some(sink) => sink,
+                None => {
+                    let encoder = SegmentEncoder::new(
+                        segment.message_protocol_id,
+                        segment.channel_id,
+                        segment.session_id,
+                    );
+                    let multicast =
+                        MulticastSink::new(socket.try_clone()?, target, pacer.clone(), encoder);
+                    sink.insert((multicast, segment.message_protocol_id))
+                }
+            };
+            if segment.message_protocol_id != *message_protocol_id {
+                continue;
+            }
+            for message in segment.messages {
+                if filter.accepts(message) {
+                    sink.send(message)?;
+                }
+            }
+        }
+
+        let (segments, bytes) = match sink {
+            Some((sink, _)) => sink.finish()?,
+            None => (0, 0),
+        };
+        eprintln!("sent {segments} re-encoded segments ({bytes} bytes) to {target}");
+        return Ok(());
+    }
+
+    let (mut segments, mut bytes) = (0u64, 0u64);
+    while let Some(captured) = reader.next_segment()? {
+        let send_time = captured.segment.send_time;
+        if protocol.is_some_and(|protocol| captured.segment.message_protocol_id != protocol)
+            || from.is_some_and(|from| send_time < from)
+        {
+            continue;
+        }
+        if to.is_some_and(|to| send_time >= to) {
+            break;
+        }
+
+        pacer.wait(send_time);
+        socket.send_to(captured.payload, target)?;
+        segments += 1;
+        bytes += captured.payload.len() as u64;
+    }
+
+    eprintln!("sent {segments} segments ({bytes} bytes) to {target}");
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["max-speed", "reencode"]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+    let Some(target) = args.parsed("target", parse::<SocketAddr>) else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a target address");
+    };
+
+    if let Err(e) = replay(&args, input, target) {
+        cli::fail(e);
+    }
+}
@@ -0,0 +1,111 @@
+//! Generates a synthetic TOPS HIST file, for tests and demos which shouldn't depend on real HIST files
+
+use std::io::Write;
+
+use chrono::{DateTime, TimeDelta};
+use iex_parser::{
+    cli::{self, Args},
+    hist::{self, HistWriter},
+    pcap::CaptureFormat,
+    sim::{Scenario, SimConfig, SymbolConfig},
+};
+
+const USAGE: &str = "\
+Usage: iex-sample [OPTIONS] --output <FILE>
+
+Generates a synthetic TOPS HIST file (a pcap capture): the system events of a trading day, the security directory and
+trading status of each symbol, then random walk quotes and trades arriving at random (Poisson) times. The messages are
+valid per the TOPS 1.6 specification, and the same seed always generates the same file.
+
+Options:
+    --symbols <SYMBOLS>      The symbols [default: ZIEXT,ZVZZT,ZXIET]
+    --start <TIME>           The time of the first message [default: 2017-04-17T13:30:00Z]
+    --duration <DURATION>    The time between the first and last messages [default: 1m]
+    --quote-rate <RATE>      The average number of quote updates per symbol and second [default: 10]
+    --trade-rate <RATE>      The average number of trades per symbol and second [default: 1]
+    --seed <N>               The seed of the random generator [default: 1]
+    --disruptions            Add a trading halt, a trade break, a closing auction, a session restart and a lost segment,
+                             at random times
+    --pcapng                 Write a pcapng capture, as the HIST files distributed by IEX, rather than a classic pcap one
+    --output <FILE>          The output file, or - for the standard output";
+
+fn generate(args: &Args) -> Result<(), hist::Error> {
+    let positive = |name: &str, default: f64| {
+        let rate = args
+            .parsed(name, |rate| rate.parse::<f64>().map_err(|e| e.to_string()))
+            .unwrap_or(default);
+        if !(rate.is_finite() && rate > 0.0) {
+            cli::fail(format!("--{name}: the rate must be positive"));
+        }
+        rate
+    };
+    let quote_rate = positive("quote-rate", 10.0);
+    let trade_rate = positive("trade-rate", 1.0);
+    let start = args
+        .parsed("start", cli::parse_time)
+        .unwrap_or(DateTime::from_timestamp_nanos(1492435800000000000));
+    let duration = args
+        .parsed("duration", cli::parse_duration)
+        .unwrap_or(TimeDelta::minutes(1));
+    let seed = args
+        .parsed("seed", |seed| {
+            seed.parse::<u64>().map_err(|e| e.to_string())
+        })
+        .unwrap_or(1);
+    let format = if args.flag("pcapng") {
+        CaptureFormat::PcapNg
+    } else {
+        CaptureFormat::Pcap
+    };
+    let Some(output) = args.value("output") else {
+        cli::fail("expected --output");
+    };
+
+    let mut names = args.values("symbols");
+    if names.is_empty() {
+        names = vec!["ZIEXT", "ZVZZT", "ZXIET"];
+    }
+    let symbols = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if name.is_empty() || name.len() > 8 {
+                cli::fail(format!("invalid symbol: {name}"));
+            }
+            SymbolConfig {
+                quote_rate,
+                trade_rate,
+                ..SymbolConfig::new(*name, 25.0 * (index + 1) as f64)
+            }
+        })
+        .collect();
+
+    let config = SimConfig {
+        start,
+        duration,
+        symbols,
+        seed,
+    };
+    let scenario = if args.flag("disruptions") {
+        Scenario::random(config)
+    } else {
+        Scenario::new(config)
+    };
+
+    let mut writer = HistWriter::with_format(cli::open_output(Some(output))?, format)?;
+    scenario.write_hist(&mut writer)?;
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["disruptions", "pcapng"]);
+    if !args.positional().is_empty() {
+        eprintln!("{USAGE}");
+        cli::fail("unexpected arguments");
+    }
+
+    if let Err(e) = generate(&args) {
+        cli::fail(e);
+    }
+}
@@ -0,0 +1,148 @@
+//! Splits a HIST file into smaller HIST files, by hour, by symbol shard or by kind of message
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use chrono::Timelike;
+use iex_parser::{
+    cli::{self, Args},
+    filter::{raw_symbol, MessageKind},
+    hist::{self, HistReader, HistWriter},
+};
+
+const USAGE: &str = "\
+Usage: iex-split [OPTIONS] --by <hour|shard|kind> --output <DIRECTORY> <HIST FILE>
+
+Splits a HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard input) into smaller HIST files:
+
+    hour    by the hour (UTC) the segments were sent, e.g. <DIRECTORY>/2017-04-17T13.pcap
+    shard   by a hash of the symbol of the messages, e.g. <DIRECTORY>/shard-03.pcap. The messages without a symbol
+            (system events) are copied to every shard.
+    kind    by kind of message, e.g. <DIRECTORY>/trade_report.pcap
+
+When splitting by hour, the segments are copied as they are. Otherwise they're split into segments of consecutive
+messages, which keep their sequence numbers, so the messages of the other files appear as gaps.
+
+Options:
+    --by <hour|shard|kind>   How to split the file
+    --shards <N>             The number of shards [default: 16]
+    --output <DIRECTORY>     The output directory";
+
+type Output = HistWriter<BufWriter<File>>;
+
+// A hash of a symbol which is stable across builds (FNV-1a), so that shards are reproducible
+fn symbol_hash(symbol: &[u8]) -> u64 {
+    symbol
+        .trim_ascii_end()
+        .iter()
+        .fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
+
+struct Outputs {
+    directory: PathBuf,
+    files: BTreeMap<String, Output>,
+}
+
+impl Outputs {
+    fn get(&mut self, name: String) -> Result<&mut Output, hist::Error> {
+        if !self.files.contains_key(&name) {
+            let path = self.directory.join(format!("{name}.pcap"));
+            let writer = HistWriter::new(BufWriter::new(File::create(path)?))?;
+            self.files.insert(name.clone(), writer);
+        }
+        Ok(self
+            .files
+            .get_mut(&name)
+            .expect("the output was just created"))
+    }
+
+    fn finish(self) -> Result<(), hist::Error> {
+        for (name, writer) in self.files {
+            writer.into_inner().flush()?;
+            println!("{}", self.directory.join(format!("{name}.pcap")).display());
+        }
+        Ok(())
+    }
+}
+
+fn split(args: &Args, input: &str, directory: &str) -> Result<(), hist::Error> {
+    let by = args
+        .value("by")
+        .unwrap_or_else(|| cli::fail("expected --by"));
+    if !matches!(by, "hour" | "shard" | "kind") {
+        cli::fail(format!("can't split by {by:?}"));
+    }
+    let shards = args
+        .parsed("shards", |shards| match shards.parse::<u64>() {
+            Ok(0) => Err("there must be at least one shard".to_string()),
+            shards => shards.map_err(|e| e.to_string()),
+        })
+        .unwrap_or(16);
+
+    fs::create_dir_all(directory)?;
+    let mut outputs = Outputs {
+        directory: PathBuf::from(directory),
+        files: BTreeMap::new(),
+    };
+    let mut reader = HistReader::new(cli::open_input(input)?)?;
+
+    while let Some(captured) = reader.next_segment()? {
+        let segment = &captured.segment;
+        let capture_time = captured.capture_time.unwrap_or(segment.send_time);
+
+        match by {
+            "hour" => {
+                let send_time = segment.send_time;
+                let name = format!("{}T{:02}", send_time.date_naive(), send_time.hour());
+                outputs
+                    .get(name)?
+                    .write_payload(capture_time, captured.payload)?;
+            }
+            "shard" => {
+                for shard in 0..shards {
+                    let in_shard = |message: &[u8]| {
+                        raw_symbol(message)
+                            .is_none_or(|symbol| symbol_hash(symbol) % shards == shard)
+                    };
+                    for run in segment.runs(in_shard) {
+                        let name = format!("shard-{shard:02}");
+                        outputs.get(name)?.write_segment(capture_time, &run)?;
+                    }
+                }
+            }
+            _ => {
+                for kind in MessageKind::ALL {
+                    for run in segment.runs(|message| MessageKind::of(message) == Some(kind)) {
+                        outputs
+                            .get(kind.name().to_string())?
+                            .write_segment(capture_time, &run)?;
+                    }
+                }
+            }
+        }
+    }
+
+    outputs.finish()
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+    let Some(directory) = args.value("output") else {
+        eprintln!("{USAGE}");
+        cli::fail("expected an output directory");
+    };
+
+    if let Err(e) = split(&args, input, directory) {
+        cli::fail(e);
+    }
+}
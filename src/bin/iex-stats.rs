@@ -0,0 +1,295 @@
+//! Prints statistics of a HIST file: message counts, active symbols, session times and sequence gaps
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use iex_parser::{
+    cli::{self, Args},
+    deep::Deep1_0Message,
+    filter::MessageKind,
+    hist::{self, HistReader, Message},
+    latency::{Histogram, LatencyAnalyzer},
+    message_protocol_ids,
+    sequence::{SequenceTracker, Sequencing},
+    stats::Stats,
+    throughput::ThroughputProfile,
+    tops::{SystemEventType, Tops1_6Message},
+};
+
+const USAGE: &str = "\
+Usage: iex-stats [OPTIONS] <HIST FILE>
+
+Prints statistics of a HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard input): message
+counts by kind, trade and quote counts of the most active symbols, the first and last timestamps, the times of the
+system events and the gaps in the sequence numbers.
+
+Options:
+    --top <N>        The number of symbols listed, by number of messages [default: 20]
+    --all-symbols    Lists every symbol
+    --latency        Prints the latencies of each stream, from the timestamps of the messages and the send times of
+                     the segments to the capture times of the packets
+    --throughput     Prints the message rates per second of capture time and the busiest seconds
+    --throughput-csv <FILE>
+                     Writes the segments, messages and bytes of each second as CSV (- for the standard output)";
+
+#[derive(Debug, Default)]
+struct SymbolStats {
+    trades: u64,
+    quotes: u64,
+}
+
+/// What the counts of [`Stats`] don't cover
+#[derive(Debug, Default)]
+struct Summary {
+    symbols: HashMap<String, SymbolStats>,
+    first_timestamp: Option<DateTime<Utc>>,
+    last_timestamp: Option<DateTime<Utc>>,
+    system_events: Vec<(SystemEventType, DateTime<Utc>)>,
+}
+
+impl Summary {
+    fn add<M>(&mut self, message: &M, system_event: Option<SystemEventType>)
+    where
+        M: Message<Symbol = String>,
+    {
+        if let Some(symbol) = message.symbol() {
+            let stats = self.symbols.entry(symbol.clone()).or_default();
+            match message.kind() {
+                MessageKind::TradeReport => stats.trades += 1,
+                MessageKind::QuoteUpdate
+                | MessageKind::PriceLevelUpdateBuy
+                | MessageKind::PriceLevelUpdateSell => stats.quotes += 1,
+                _ => {}
+            }
+        }
+
+        if let Some(timestamp) = message.timestamp() {
+            self.first_timestamp = Some(
+                self.first_timestamp
+                    .map_or(timestamp, |first| first.min(timestamp)),
+            );
+            self.last_timestamp = Some(
+                self.last_timestamp
+                    .map_or(timestamp, |last| last.max(timestamp)),
+            );
+            if let Some(event_type) = system_event {
+                self.system_events.push((event_type, timestamp));
+            }
+        }
+    }
+}
+
+fn tops_system_event(message: &Tops1_6Message<String>) -> Option<SystemEventType> {
+    match message {
+        Tops1_6Message::SystemEvent(event) => Some(event.event_type),
+        _ => None,
+    }
+}
+
+fn deep_system_event(message: &Deep1_0Message<String>) -> Option<SystemEventType> {
+    match message {
+        Deep1_0Message::SystemEvent(event) => Some(event.event_type),
+        _ => None,
+    }
+}
+
+fn decode<M>(
+    stats: &mut Stats,
+    summary: &mut Summary,
+    message: &[u8],
+    system_event: fn(&M) -> Option<SystemEventType>,
+) where
+    M: Message<Symbol = String>,
+{
+    match M::parse(message) {
+        Ok((_, decoded)) => summary.add(&decoded, system_event(&decoded)),
+        Err(_) => stats.invalid_messages += 1,
+    }
+}
+
+fn collect(args: &Args, input: &str) -> Result<(), hist::Error> {
+    let mut reader = HistReader::new(cli::open_input(input)?)?;
+    let mut stats = Stats::new();
+    let mut summary = Summary::default();
+    let mut tracker = SequenceTracker::new();
+    let mut gaps = Vec::new();
+    let mut latency = LatencyAnalyzer::new();
+    let mut throughput = ThroughputProfile::new();
+
+    while let Some(captured) = reader.next_segment()? {
+        latency.add_captured(&captured);
+        throughput.add_captured(&captured);
+        let segment = captured.segment;
+        stats.add_segment(&segment);
+        if let Sequencing::Gap(gap) = tracker.update(&segment) {
+            gaps.push(gap);
+        }
+
+        for message in &segment.messages {
+            match segment.message_protocol_id {
+                message_protocol_ids::TOPS => {
+                    decode(&mut stats, &mut summary, message, tops_system_event)
+                }
+                message_protocol_ids::DEEP_1_0 => {
+                    decode(&mut stats, &mut summary, message, deep_system_event)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    println!("Segments: {}", stats.segments);
+    println!("Bytes: {}", stats.bytes);
+    println!("Messages: {}", stats.messages);
+    println!("Invalid messages: {}", stats.invalid_messages);
+    if let (Some(first), Some(last)) = (summary.first_timestamp, summary.last_timestamp) {
+        println!("First timestamp: {first}");
+        println!("Last timestamp: {last}");
+    }
+
+    println!();
+    println!("Messages by kind:");
+    for kind in MessageKind::ALL {
+        if let Some(count) = stats.kinds.get(&kind) {
+            println!("    {:<28} {count:>12}", kind.name());
+        }
+    }
+
+    println!();
+    println!("System events:");
+    for (event_type, timestamp) in &summary.system_events {
+        println!("    {:<28} {timestamp}", format!("{event_type:?}"));
+    }
+
+    let mut symbols = stats.symbols.iter().collect::<Vec<_>>();
+    symbols.sort_by(|(a, a_messages), (b, b_messages)| b_messages.cmp(a_messages).then(a.cmp(b)));
+    if !args.flag("all-symbols") {
+        let top = args
+            .parsed("top", |top| top.parse::<usize>().map_err(|e| e.to_string()))
+            .unwrap_or(20);
+        symbols.truncate(top);
+    }
+    println!();
+    println!("Symbols: {}", stats.symbols.len());
+    println!(
+        "    {:<12} {:>12} {:>12} {:>12}",
+        "symbol", "messages", "trades", "quotes"
+    );
+    for (symbol, messages) in symbols {
+        let (trades, quotes) = summary
+            .symbols
+            .get(symbol.as_str())
+            .map_or((0, 0), |stats| (stats.trades, stats.quotes));
+        println!("    {symbol:<12} {messages:>12} {trades:>12} {quotes:>12}");
+    }
+
+    // Order the gaps by stream, then sequence number
+    let mut streams = BTreeMap::<_, Vec<_>>::new();
+    for gap in gaps {
+        streams.entry(gap.stream).or_default().push(gap);
+    }
+    println!();
+    println!(
+        "Sequence gaps: {} ({} messages missing)",
+        streams.values().map(Vec::len).sum::<usize>(),
+        tracker.missing()
+    );
+    for (stream, gaps) in streams {
+        for gap in gaps {
+            println!(
+                "    protocol {:#06x}, session {}: {}..{} ({} messages, before {})",
+                stream.message_protocol_id,
+                stream.session_id,
+                gap.first,
+                gap.end(),
+                gap.count,
+                gap.send_time
+            );
+        }
+    }
+
+    if args.flag("latency") {
+        println!();
+        println!("Latencies (µs):");
+        println!(
+            "    {:<40} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "", "count", "min", "p50", "p99", "p99.9", "max"
+        );
+        for (stream, latency) in latency.streams() {
+            let stream = format!(
+                "protocol {:#06x}, session {}",
+                stream.message_protocol_id, stream.session_id
+            );
+            print_latency(&format!("{stream}, capture"), &latency.capture);
+            print_latency(&format!("{stream}, network"), &latency.network);
+        }
+    }
+
+    if args.flag("throughput") {
+        let rate = |quantile| {
+            throughput
+                .messages_quantile(quantile)
+                .map_or("-".to_string(), |messages| messages.to_string())
+        };
+        println!();
+        println!(
+            "Messages per second: p50 {}, p99 {}, p99.9 {}, max {}",
+            rate(0.5),
+            rate(0.99),
+            rate(0.999),
+            rate(1.0)
+        );
+        println!(
+            "    {:<28} {:>12} {:>12} {:>12} {:>16}",
+            "second", "segments", "messages", "bytes", "peak (per ms)"
+        );
+        for second in throughput.busiest(10) {
+            println!(
+                "    {:<28} {:>12} {:>12} {:>12} {:>16}",
+                second.start.to_string(),
+                second.segments,
+                second.messages,
+                second.bytes,
+                second.peak_millisecond
+            );
+        }
+    }
+    if let Some(path) = args.value("throughput-csv") {
+        throughput.write_csv(cli::open_output(Some(path))?)?;
+    }
+
+    Ok(())
+}
+
+fn print_latency(name: &str, histogram: &Histogram) {
+    let microseconds = |nanoseconds: Option<u64>| {
+        nanoseconds.map_or("-".to_string(), |nanoseconds| {
+            format!("{:.1}", nanoseconds as f64 / 1000.0)
+        })
+    };
+    print!(
+        "    {name:<40} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        histogram.count(),
+        microseconds(histogram.min()),
+        microseconds(histogram.quantile(0.5)),
+        microseconds(histogram.quantile(0.99)),
+        microseconds(histogram.quantile(0.999)),
+        microseconds(histogram.max())
+    );
+    if histogram.negative() > 0 {
+        print!(" ({} negative)", histogram.negative());
+    }
+    println!();
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &["all-symbols", "latency", "throughput"]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    if let Err(e) = collect(&args, input) {
+        cli::fail(e);
+    }
+}
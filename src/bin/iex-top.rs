@@ -0,0 +1,259 @@
+//! Shows a continuously updated table of the quotes and trades of some symbols, from a live feed or a replayed file
+
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind, Write},
+    net::{Ipv4Addr, SocketAddrV4},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{SecondsFormat, Utc};
+use iex_parser::{
+    audit::AuditLog,
+    cli::{self, Args},
+    hist::{self, HistReader, Message},
+    iex_tp::IexTp1Segment,
+    live::{FeedReceiver, Received, Watchdog, WatchdogConfig, WatchdogEvent},
+    message_protocol_ids,
+    metrics::{FeedMetrics, MetricsServer},
+    replay::Pacer,
+    snapshot::MarketSnapshot,
+    tops::Tops1_6Message,
+};
+
+const USAGE: &str = "\
+Usage: iex-top [OPTIONS] (--listen <ADDRESS:PORT> | <HIST FILE>)
+
+Shows a continuously updated table of the best quotes, last trades and volumes of some symbols, from a live TOPS feed
+(on a multicast group or a unicast address), or from a HIST file replayed with its original timing.
+
+Options:
+    --listen <ADDRESS:PORT>   Receives a live feed, e.g. 233.215.21.4:10378
+    --interface <ADDRESS>     The address of the interface joining the multicast group [default: any]
+    --metrics <ADDRESS:PORT>  Serves the Prometheus metrics of the live feed over HTTP, e.g. 0.0.0.0:9100
+    --rejoin-after <DURATION> Re-joins the multicast group after a silence of the live feed [default: 5s]
+    --audit <DIRECTORY>       Appends every message of the live feed to a checksummed audit log in a directory
+    --symbols <SYMBOLS>       The symbols shown [default: the most traded ones]
+    --rows <N>                The number of symbols shown, without --symbols [default: 20]
+    --speed <MULTIPLIER>      The replay speed relative to the original [default: 1]
+    --refresh <DURATION>      The interval between refreshes of the table [default: 1s]";
+
+#[derive(Default)]
+struct Viewer {
+    snapshot: MarketSnapshot<String>,
+    volumes: HashMap<String, u64>,
+    messages: u64,
+    gaps: u64,
+    recoveries: u64,
+    next_sequence_number: Option<i64>,
+}
+
+impl Viewer {
+    fn update(&mut self, segment: &IexTp1Segment<'_>) {
+        if segment.message_protocol_id != message_protocol_ids::TOPS {
+            return;
+        }
+        if self
+            .next_sequence_number
+            .is_some_and(|next| segment.first_message_sequence_no > next)
+        {
+            self.gaps += 1;
+        }
+        self.next_sequence_number =
+            Some(segment.first_message_sequence_no + segment.messages.len() as i64);
+
+        for message in &segment.messages {
+            let Ok((_, message)) = Tops1_6Message::<String>::parse(message) else {
+                continue;
+            };
+            self.messages += 1;
+            if let Tops1_6Message::TradeReport(trade) = &message {
+                *self.volumes.entry(trade.symbol.clone()).or_default() += u64::from(trade.size);
+            }
+            self.snapshot.update(&message);
+        }
+    }
+
+    fn render(&self, args: &Args, rows: usize) -> io::Result<()> {
+        let mut symbols = args.values("symbols");
+        if symbols.is_empty() {
+            let mut traded = self.volumes.iter().collect::<Vec<_>>();
+            traded.sort_by(|(a, a_volume), (b, b_volume)| b_volume.cmp(a_volume).then(a.cmp(b)));
+            symbols = traded
+                .into_iter()
+                .take(rows)
+                .map(|(symbol, _)| symbol.as_str())
+                .collect();
+        }
+
+        let mut screen = Vec::new();
+        // Clear the screen and move to its top left corner
+        write!(screen, "\x1b[2J\x1b[H")?;
+        let as_of = self
+            .snapshot
+            .as_of()
+            .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+            .unwrap_or_else(|| "-".to_string());
+        writeln!(
+            screen,
+            "As of {as_of}    {} messages, {} gaps, {} recoveries",
+            self.messages, self.gaps, self.recoveries
+        )?;
+        writeln!(
+            screen,
+            "{:<8} {:>8} {:>10} {:>10} {:>8} {:>10} {:>8} {:>12}  status",
+            "symbol", "bid size", "bid", "ask", "ask size", "last", "size", "volume"
+        )?;
+
+        for symbol in symbols {
+            let snapshot = self.snapshot.get(symbol);
+            let quote = snapshot.and_then(|snapshot| snapshot.quote.as_ref());
+            let trade = snapshot.and_then(|snapshot| snapshot.last_trade.as_ref());
+            let price =
+                |price: Option<f64>| price.map_or("-".to_string(), |price| format!("{price:.4}"));
+            let size = |size: Option<u32>| size.map_or("-".to_string(), |size| size.to_string());
+            let status = match snapshot {
+                Some(snapshot) if snapshot.is_trading() => "trading",
+                Some(_) => "halted",
+                None => "-",
+            };
+
+            writeln!(
+                screen,
+                "{symbol:<8} {:>8} {:>10} {:>10} {:>8} {:>10} {:>8} {:>12}  {status}",
+                size(quote.map(|quote| quote.bid_size)),
+                price(quote.map(|quote| quote.bid_price)),
+                price(quote.map(|quote| quote.ask_price)),
+                size(quote.map(|quote| quote.ask_size)),
+                price(trade.map(|trade| trade.price)),
+                size(trade.map(|trade| trade.size)),
+                self.volumes.get(symbol).copied().unwrap_or_default(),
+            )?;
+        }
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&screen)?;
+        stdout.flush()
+    }
+}
+
+fn run(args: &Args) -> Result<(), hist::Error> {
+    let refresh = args
+        .parsed("refresh", cli::parse_duration)
+        .and_then(|refresh| refresh.to_std().ok())
+        .unwrap_or(Duration::from_secs(1));
+    let rows = args
+        .parsed("rows", |rows| {
+            rows.parse::<usize>().map_err(|e| e.to_string())
+        })
+        .unwrap_or(20);
+    let mut viewer = Viewer::default();
+    let mut last_render = Instant::now();
+
+    if let Some(address) = args.value("listen") {
+        let address = address
+            .parse::<SocketAddrV4>()
+            .unwrap_or_else(|e| cli::fail(format!("--listen: {e}")));
+        let interface = args
+            .parsed("interface", |interface| {
+                interface.parse::<Ipv4Addr>().map_err(|e| e.to_string())
+            })
+            .unwrap_or(Ipv4Addr::UNSPECIFIED);
+        let mut config = WatchdogConfig::default();
+        if let Some(silence) = args
+            .parsed("rejoin-after", cli::parse_duration)
+            .and_then(|silence| silence.to_std().ok())
+        {
+            config.silence = silence;
+        }
+        let mut watchdog = Watchdog::new(FeedReceiver::bind(address, interface)?, config);
+        watchdog.set_timeout(Some(refresh));
+
+        let metrics = match args.value("metrics") {
+            Some(address) => {
+                let metrics = Arc::new(Mutex::new(FeedMetrics::new()));
+                MetricsServer::bind(address)
+                    .unwrap_or_else(|e| cli::fail(format!("--metrics: {e}")))
+                    .spawn(metrics.clone());
+                Some(metrics)
+            }
+            None => None,
+        };
+        let mut audit = args
+            .value("audit")
+            .map(AuditLog::open)
+            .transpose()
+            .unwrap_or_else(|e| cli::fail(format!("--audit: {e}")));
+
+        loop {
+            match watchdog.recv() {
+                Ok(Received::Watchdog(event)) => {
+                    viewer.recoveries += 1;
+                    // Gaps are counted again from the next segment
+                    viewer.next_sequence_number = None;
+                    let reason = match event {
+                        WatchdogEvent::Silence(silence) => format!("{silence:?} of silence"),
+                        WatchdogEvent::Gaps(gaps) => format!("{gaps} gaps"),
+                    };
+                    eprintln!("re-joined {address} after {reason}");
+                }
+                Ok(Received::Segment(segment)) => {
+                    let receive_time = Utc::now();
+                    if let Some(audit) = &mut audit {
+                        audit.append_segment(receive_time, &segment)?;
+                    }
+                    if let Some(metrics) = &metrics {
+                        metrics.lock().unwrap().add_segment(receive_time, &segment);
+                    }
+                    viewer.update(&segment);
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    if let Some(metrics) = &metrics {
+                        metrics.lock().unwrap().add_invalid_segment();
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+            if last_render.elapsed() >= refresh {
+                if let Some(audit) = &mut audit {
+                    audit.flush()?;
+                }
+                viewer.render(args, rows)?;
+                last_render = Instant::now();
+            }
+        }
+    }
+
+    let [input] = args.positional() else {
+        cli::fail("expected a HIST file or --listen");
+    };
+    let speed = args
+        .parsed("speed", |speed| {
+            speed.parse::<f64>().map_err(|e| e.to_string())
+        })
+        .unwrap_or(1.0);
+    if !(speed.is_finite() && speed > 0.0) {
+        cli::fail("--speed: the speed must be positive");
+    }
+    let mut pacer = Pacer::new(speed);
+    let mut reader = HistReader::new(cli::open_input(input)?)?;
+    while let Some(captured) = reader.next_segment()? {
+        pacer.wait(captured.segment.send_time);
+        viewer.update(&captured.segment);
+        if last_render.elapsed() >= refresh {
+            viewer.render(args, rows)?;
+            last_render = Instant::now();
+        }
+    }
+    viewer.render(args, rows)?;
+    Ok(())
+}
+
+fn main() {
+    let args = Args::from_env(USAGE, &[]);
+    if let Err(e) = run(&args) {
+        cli::fail(e);
+    }
+}
@@ -0,0 +1,73 @@
+//! Validates a HIST file, writing a JSON report of the problems found
+
+use std::{io::Write, process};
+
+use iex_parser::{
+    cli::{self, Args},
+    hist::HistReader,
+    quality::{self, QualityConfig},
+    validate,
+};
+
+const USAGE: &str = "\
+Usage: iex-validate [OPTIONS] <HIST FILE>
+
+Validates a HIST file (a pcap or pcapng capture, optionally gzipped, or - for the standard input): decodes every
+message strictly, and looks for gaps and duplicates in the sequence numbers, crossed quotes, trade breaks of unknown
+trades and illegal transitions of the trading statuses (labelled with their likely cause: missed messages, messages out
+of order, or unexplained). Writes a JSON report, and exits with status 2 if any problem was found.
+
+With --quality, writes a data-quality report of the TOPS messages instead, to archive next to the file: quotes with a
+price but no size (or the other way around), symbols without updates after some time, crossed markets, trades far from
+the previous trade of their symbol, and gaps and duplicates in the sequence numbers.
+
+Options:
+    --output <FILE>                The output file [default: the standard output]
+    --quality                      Write a data-quality report
+    --stale-after <TIME>           With --quality, report the symbols without quotes or trades from this time on (in
+                                   nanoseconds since the epoch or RFC 3339)
+    --outlier-threshold <FRACTION> With --quality, report the trades whose price differs from the previous trade of
+                                   their symbol by more than this fraction of it [default: 0.1]";
+
+fn main() {
+    let args = Args::from_env(USAGE, &["quality"]);
+    let [input] = args.positional() else {
+        eprintln!("{USAGE}");
+        cli::fail("expected a single HIST file");
+    };
+
+    let reader = cli::open_input(input)
+        .map_err(Into::into)
+        .and_then(HistReader::new)
+        .unwrap_or_else(|e| cli::fail(e));
+    let mut output = cli::open_output(args.value("output")).unwrap_or_else(|e| cli::fail(e));
+
+    if args.flag("quality") {
+        let config = QualityConfig {
+            stale_after: args.parsed("stale-after", cli::parse_time),
+            outlier_threshold: args
+                .parsed("outlier-threshold", |s| {
+                    s.parse::<f64>()
+                        .ok()
+                        .filter(|threshold| *threshold >= 0.0)
+                        .ok_or_else(|| format!("invalid fraction {s:?}"))
+                })
+                .unwrap_or(QualityConfig::default().outlier_threshold),
+        };
+        quality::analyze(reader, config)
+            .and_then(|report| report.write_json(&mut output))
+            .and_then(|()| output.flush())
+            .unwrap_or_else(|e| cli::fail(e));
+        return;
+    }
+
+    let report = validate::validate(reader).unwrap_or_else(|e| cli::fail(e));
+    report
+        .write_json(&mut output)
+        .and_then(|()| output.flush())
+        .unwrap_or_else(|e| cli::fail(e));
+
+    if !report.is_valid() {
+        process::exit(2);
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    checkpoint::{CheckpointReader, CheckpointWriter},
+    deep::{Deep1_0Message, PriceLevelUpdate, Side},
+    utils::{key_price, price_key},
+};
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"IEXBOOK1";
+
+/// A single aggregated price level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceLevel {
+    pub price: f64,
+    pub size: u32,
+}
+
+/// The top levels of both sides of a book, best level first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BookSnapshot {
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// An aggregated (price level) order book of a single symbol
+///
+/// Updates belonging to an order book event which is still in progress are buffered, and only applied once the
+/// update completing the event arrives, so the book is never observed in a transitional state.
+#[derive(Clone, Debug, Default)]
+pub struct Book {
+    bids: BTreeMap<i64, u32>,
+    asks: BTreeMap<i64, u32>,
+    pending: Vec<(Side, i64, u32)>,
+    last_update: Option<DateTime<Utc>>,
+}
+
+impl Book {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a price level update, returning whether it completed an event (and hence changed the book)
+    pub fn apply<S>(&mut self, update: &PriceLevelUpdate<S>) -> bool {
+        self.pending
+            .push((update.side, price_key(update.price), update.size));
+
+        if !update.event_processing_complete {
+            return false;
+        }
+
+        for (side, price, size) in self.pending.drain(..) {
+            let levels = match side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+
+            if size == 0 {
+                levels.remove(&price);
+            } else {
+                levels.insert(price, size);
+            }
+        }
+        self.last_update = Some(update.timestamp);
+
+        true
+    }
+
+    pub fn best_bid(&self) -> Option<PriceLevel> {
+        self.bids().next()
+    }
+
+    pub fn best_ask(&self) -> Option<PriceLevel> {
+        self.asks().next()
+    }
+
+    /// Iterates over the bid levels, from the highest price down
+    pub fn bids(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.bids.iter().rev().map(|(&price, &size)| PriceLevel {
+            price: key_price(price),
+            size,
+        })
+    }
+
+    /// Iterates over the ask levels, from the lowest price up
+    pub fn asks(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.asks.iter().map(|(&price, &size)| PriceLevel {
+            price: key_price(price),
+            size,
+        })
+    }
+
+    /// Returns (at most) the best `depth` levels of each side
+    pub fn snapshot(&self, depth: usize) -> BookSnapshot {
+        BookSnapshot {
+            bids: self.bids().take(depth).collect(),
+            asks: self.asks().take(depth).collect(),
+        }
+    }
+
+    /// Whether some updates are buffered, waiting for their event to complete
+    pub fn is_in_transition(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The timestamp of the update which last changed the book
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        self.last_update
+    }
+
+    fn write_checkpoint<W: Write>(&self, writer: &mut CheckpointWriter<W>) -> io::Result<()> {
+        writer.optional_timestamp(self.last_update)?;
+        for levels in [&self.bids, &self.asks] {
+            writer.len(levels.len())?;
+            for (&price, &size) in levels {
+                writer.i64(price)?;
+                writer.u32(size)?;
+            }
+        }
+        writer.len(self.pending.len())?;
+        for &(side, price, size) in &self.pending {
+            writer.u8(side.code())?;
+            writer.i64(price)?;
+            writer.u32(size)?;
+        }
+        Ok(())
+    }
+
+    fn read_checkpoint<R: Read>(reader: &mut CheckpointReader<R>) -> io::Result<Self> {
+        let mut book = Self {
+            last_update: reader.optional_timestamp()?,
+            ..Self::default()
+        };
+        for levels in [&mut book.bids, &mut book.asks] {
+            for _ in 0..reader.len()? {
+                let price = reader.i64()?;
+                levels.insert(price, reader.u32()?);
+            }
+        }
+        for _ in 0..reader.len()? {
+            let side = Side::try_from(reader.u8()?)
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid side"))?;
+            let price = reader.i64()?;
+            book.pending.push((side, price, reader.u32()?));
+        }
+        Ok(book)
+    }
+}
+
+/// Maintains the aggregated books of all symbols from DEEP price level updates
+#[derive(Clone, Debug)]
+pub struct BookBuilder<S> {
+    books: HashMap<S, Book>,
+}
+
+impl<S> Default for BookBuilder<S> {
+    fn default() -> Self {
+        Self {
+            books: HashMap::new(),
+        }
+    }
+}
+
+impl<S> BookBuilder<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a price level update to the book of its symbol, returning whether the book changed
+    pub fn apply(&mut self, update: &PriceLevelUpdate<S>) -> bool {
+        match self.books.get_mut(&update.symbol) {
+            Some(book) => book.apply(update),
+            None => self
+                .books
+                .entry(update.symbol.clone())
+                .or_default()
+                .apply(update),
+        }
+    }
+
+    /// Feeds a DEEP message to the builder, returning whether a book changed. Messages other than price level updates
+    /// are ignored.
+    pub fn update(&mut self, message: &Deep1_0Message<S>) -> bool {
+        match message {
+            Deep1_0Message::PriceLevelUpdate(update) => self.apply(update),
+            _ => false,
+        }
+    }
+
+    pub fn book<Q>(&self, symbol: &Q) -> Option<&Book>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.books.get(symbol)
+    }
+
+    pub fn books(&self) -> impl Iterator<Item = (&S, &Book)> {
+        self.books.iter()
+    }
+
+    /// Writes the books (including the updates of the events in progress) as a checkpoint, from which
+    /// [`BookBuilder::read_from`] restores the builder, e.g. to resume a long replay or to reload the end-of-day state
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let mut writer = CheckpointWriter::new(writer, CHECKPOINT_MAGIC)?;
+        let mut books = self.books.iter().collect::<Vec<_>>();
+        books.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        writer.len(books.len())?;
+        for (symbol, book) in books {
+            writer.symbol(symbol.as_ref())?;
+            book.write_checkpoint(&mut writer)?;
+        }
+        writer.finish()
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        let mut reader = CheckpointReader::new(reader, CHECKPOINT_MAGIC, "book builder")?;
+        let mut books = HashMap::new();
+        for _ in 0..reader.len()? {
+            let symbol = reader.symbol()?;
+            books.insert(symbol, Book::read_checkpoint(&mut reader)?);
+        }
+        Ok(Self { books })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+
+    fn update(side: Side, price: f64, size: u32, complete: bool) -> PriceLevelUpdate<String> {
+        PriceLevelUpdate {
+            side,
+            event_processing_complete: complete,
+            timestamp: DateTime::from_timestamp_nanos(1471980632572715948),
+            symbol: "ZIEXT".to_string(),
+            size,
+            price,
+        }
+    }
+
+    #[test]
+    fn levels_are_ordered() {
+        let mut builder = BookBuilder::new();
+        assert!(builder.apply(&update(Side::Buy, 99.05, 100, true)));
+        assert!(builder.apply(&update(Side::Buy, 99.06, 200, true)));
+        assert!(builder.apply(&update(Side::Sell, 99.08, 300, true)));
+        assert!(builder.apply(&update(Side::Sell, 99.07, 400, true)));
+
+        let book = builder.book("ZIEXT").unwrap();
+        let best_bid = book.best_bid().unwrap();
+        assert_float_eq!(best_bid.price, 99.06, ulps <= 5);
+        assert_eq!(best_bid.size, 200);
+        let best_ask = book.best_ask().unwrap();
+        assert_float_eq!(best_ask.price, 99.07, ulps <= 5);
+        assert_eq!(best_ask.size, 400);
+
+        let snapshot = book.snapshot(1);
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.asks.len(), 1);
+        assert_eq!(book.snapshot(5).bids.len(), 2);
+    }
+
+    #[test]
+    fn zero_size_removes_level() {
+        let mut book = Book::new();
+        book.apply(&update(Side::Sell, 99.07, 400, true));
+        book.apply(&update(Side::Sell, 99.07, 0, true));
+
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn events_are_applied_atomically() {
+        let mut book = Book::new();
+        book.apply(&update(Side::Buy, 99.05, 100, true));
+
+        assert!(!book.apply(&update(Side::Buy, 99.05, 0, false)));
+        assert!(book.is_in_transition());
+        assert_eq!(book.best_bid().unwrap().size, 100);
+
+        assert!(book.apply(&update(Side::Buy, 99.04, 300, true)));
+        assert!(!book.is_in_transition());
+        let best_bid = book.best_bid().unwrap();
+        assert_float_eq!(best_bid.price, 99.04, ulps <= 5);
+        assert_eq!(best_bid.size, 300);
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut builder = BookBuilder::new();
+        builder.apply(&update(Side::Buy, 99.05, 100, true));
+        builder.apply(&update(Side::Sell, 99.07, 400, true));
+        builder.apply(&update(Side::Buy, 99.06, 200, false));
+
+        let mut checkpoint = Vec::new();
+        builder.write_to(&mut checkpoint).unwrap();
+        let mut restored = BookBuilder::<String>::read_from(&checkpoint[..]).unwrap();
+        let book = restored.book("ZIEXT").unwrap();
+        assert_eq!(
+            book.snapshot(10),
+            builder.book("ZIEXT").unwrap().snapshot(10)
+        );
+        assert_eq!(
+            book.last_update(),
+            builder.book("ZIEXT").unwrap().last_update()
+        );
+        assert!(book.is_in_transition());
+
+        // The event in progress completes after the restore
+        restored.apply(&update(Side::Sell, 99.08, 300, true));
+        let book = restored.book("ZIEXT").unwrap();
+        assert_float_eq!(book.best_bid().unwrap().price, 99.06, ulps <= 5);
+        assert_eq!(book.asks().count(), 2);
+
+        assert!(BookBuilder::<String>::read_from(&b"IEXBBO01"[..]).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{Read, Seek},
+    ops::Range,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    filter::{raw_timestamp, MessageFilter},
+    hist::{Error, HistReader, Message},
+    index::HistIndex,
+};
+
+/// The size of the cache of a [`BlockCache`], by default
+pub const DEFAULT_CACHE_BYTES: usize = 256 << 20;
+
+#[derive(Clone, Debug)]
+struct CachedMessage {
+    message_protocol_id: u16,
+    sequence_number: i64,
+    bytes: Range<usize>,
+}
+
+/// The messages of the segments between two consecutive entries of the index
+#[derive(Clone, Debug, Default)]
+struct Block {
+    messages: Vec<CachedMessage>,
+    bytes: Vec<u8>,
+}
+
+/// Serves repeated queries of the same HIST file (e.g. of different symbols and time windows, during an interactive
+/// analysis) from memory
+///
+/// The file is read by blocks, the segments between two consecutive entries of its index, which are kept in memory
+/// until the cache is full. The least recently used blocks are then evicted. Messages are selected by their timestamp,
+/// among the blocks sent within the window, and only the messages accepted by the filter of a query are decoded.
+#[derive(Debug)]
+pub struct BlockCache<R> {
+    reader: HistReader<R>,
+    index: HistIndex,
+    max_bytes: usize,
+    blocks: HashMap<usize, Block>,
+    /// The cached blocks, least recently used first
+    recency: VecDeque<usize>,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<R> BlockCache<R>
+where
+    R: Read + Seek,
+{
+    /// Caches the blocks of an uncompressed HIST file and its index, up to [`DEFAULT_CACHE_BYTES`] of messages
+    pub fn new(reader: HistReader<R>, index: HistIndex) -> Self {
+        Self {
+            reader,
+            index,
+            max_bytes: DEFAULT_CACHE_BYTES,
+            blocks: HashMap::new(),
+            recency: VecDeque::new(),
+            bytes: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Caches up to some bytes of messages. The block read last is always kept, whatever its size.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Decodes the messages of a protocol timestamped within a window and accepted by a filter
+    pub fn query<M>(
+        &mut self,
+        window: Range<DateTime<Utc>>,
+        filter: &MessageFilter,
+    ) -> Result<Vec<M>, Error>
+    where
+        M: Message,
+    {
+        let entries = self.index.entries();
+        let first = entries
+            .partition_point(|entry| entry.send_time <= window.start)
+            .saturating_sub(1);
+        let end = entries.partition_point(|entry| entry.send_time < window.end);
+
+        let mut messages = Vec::new();
+        for number in first..end.max(first + 1).min(entries.len()) {
+            let block = self.block(number)?;
+            for message in &block.messages {
+                if message.message_protocol_id != M::MESSAGE_PROTOCOL_ID {
+                    continue;
+                }
+                let bytes = &block.bytes[message.bytes.clone()];
+                if !raw_timestamp(bytes).is_some_and(|timestamp| window.contains(&timestamp))
+                    || !filter.accepts(bytes)
+                {
+                    continue;
+                }
+                match M::parse(bytes) {
+                    Ok((_, decoded)) => messages.push(decoded),
+                    Err(_) => {
+                        return Err(Error::InvalidMessage {
+                            sequence_number: message.sequence_number,
+                            message: bytes.to_vec(),
+                        })
+                    }
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    /// The number of blocks served from memory
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of blocks read from the file
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The bytes of messages cached
+    pub fn cached_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn cached_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Evicts every block
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.recency.clear();
+        self.bytes = 0;
+    }
+
+    fn block(&mut self, number: usize) -> Result<&Block, Error> {
+        if self.blocks.contains_key(&number) {
+            self.hits += 1;
+            self.recency.retain(|&cached| cached != number);
+        } else {
+            self.misses += 1;
+            let block = self.read_block(number)?;
+            self.bytes += block.bytes.len();
+            self.blocks.insert(number, block);
+            while self.bytes > self.max_bytes {
+                let Some(evicted) = self.recency.pop_front() else {
+                    break;
+                };
+                let evicted = self
+                    .blocks
+                    .remove(&evicted)
+                    .expect("cached blocks are listed");
+                self.bytes -= evicted.bytes.len();
+            }
+        }
+        self.recency.push_back(number);
+        Ok(&self.blocks[&number])
+    }
+
+    fn read_block(&mut self, number: usize) -> Result<Block, Error> {
+        let entries = self.index.entries();
+        let end = entries.get(number + 1).map(|entry| entry.offset);
+        self.reader.seek(entries[number].offset)?;
+
+        let mut block = Block::default();
+        while end.is_none_or(|end| self.reader.position() < end) {
+            let Some(captured) = self.reader.next_segment()? else {
+                break;
+            };
+            let segment = captured.segment;
+            for (sequence_number, message) in
+                (segment.first_message_sequence_no..).zip(&segment.messages)
+            {
+                let start = block.bytes.len();
+                block.bytes.extend_from_slice(message);
+                block.messages.push(CachedMessage {
+                    message_protocol_id: segment.message_protocol_id,
+                    sequence_number,
+                    bytes: start..block.bytes.len(),
+                });
+            }
+        }
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::TimeDelta;
+
+    use crate::{
+        fixtures,
+        hist::tests::{capture, segment},
+        message_protocol_ids,
+        tops::{Tops1_6Message, TradeReport},
+    };
+
+    use super::*;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(millis * 1_000_000)
+    }
+
+    #[test]
+    fn cache() {
+        // A trade of ZIEXT and one of ZVZZT in segments sent at 0.4s, 1.1s and 3.5s
+        let payloads = [400, 1100, 3500]
+            .into_iter()
+            .enumerate()
+            .map(|(index, millis)| {
+                let trades = ["ZIEXT", "ZVZZT"].map(|symbol| {
+                    TradeReport {
+                        timestamp: at(millis),
+                        id: index as i64,
+                        ..fixtures::trade(symbol.to_string(), 0, 99.05, 100)
+                    }
+                    .encode()
+                    .unwrap()
+                });
+                let mut payload = segment(
+                    message_protocol_ids::TOPS,
+                    2 * index as i64 + 1,
+                    &[&trades[0], &trades[1]],
+                );
+                payload[32..40].copy_from_slice(&(millis * 1_000_000i64).to_le_bytes());
+                payload
+            })
+            .collect::<Vec<_>>();
+        let capture = capture(&payloads);
+        let index = HistIndex::build(
+            HistReader::new(&capture[..]).unwrap(),
+            TimeDelta::seconds(1),
+        )
+        .unwrap();
+        let mut cache = BlockCache::new(HistReader::new(Cursor::new(&capture)).unwrap(), index);
+
+        let ziext = MessageFilter::new().symbols(["ZIEXT"]);
+        let trades = cache
+            .query::<Tops1_6Message<String>>(at(1100)..at(2000), &ziext)
+            .unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!((cache.hits(), cache.misses()), (0, 1));
+
+        let trades = cache
+            .query::<Tops1_6Message<String>>(at(1100)..at(2000), &MessageFilter::new())
+            .unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        let trades = cache
+            .query::<Tops1_6Message<String>>(at(0)..at(4000), &ziext)
+            .unwrap();
+        assert_eq!(trades.len(), 3);
+        assert_eq!((cache.hits(), cache.misses()), (2, 3));
+        assert_eq!(cache.cached_blocks(), 3);
+
+        // Only the block read last fits
+        let block_bytes = cache.cached_bytes() / 3;
+        let mut cache = cache.with_max_bytes(block_bytes);
+        cache.clear();
+        cache
+            .query::<Tops1_6Message<String>>(at(0)..at(4000), &ziext)
+            .unwrap();
+        assert_eq!(cache.cached_blocks(), 1);
+        assert_eq!(cache.cached_bytes(), block_bytes);
+    }
+}
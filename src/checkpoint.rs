@@ -0,0 +1,188 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use chrono::{DateTime, Utc};
+
+use crate::utils;
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+/// Writes a checkpoint: the state of a stateful component (e.g. a [`BookBuilder`](crate::book::BookBuilder)), saved to
+/// resume a long replay from that point, or to reload the end-of-day state the next morning
+///
+/// A checkpoint starts with a magic number identifying the component and the version of its format. Integers follow
+/// in little-endian, while timestamps, prices, symbols and messages are encoded as they are on the wire.
+pub(crate) struct CheckpointWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> CheckpointWriter<W> {
+    pub(crate) fn new(mut writer: W, magic: &[u8; 8]) -> io::Result<Self> {
+        writer.write_all(magic)?;
+        Ok(Self { writer })
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.writer.write_all(&[value])
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    pub(crate) fn i64(&mut self, value: i64) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    pub(crate) fn len(&mut self, len: usize) -> io::Result<()> {
+        self.u64(len as u64)
+    }
+
+    pub(crate) fn timestamp(&mut self, timestamp: DateTime<Utc>) -> io::Result<()> {
+        utils::write_timestamp(&mut self.writer, timestamp)
+    }
+
+    pub(crate) fn optional_timestamp(
+        &mut self,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> io::Result<()> {
+        self.u8(timestamp.is_some().into())?;
+        match timestamp {
+            Some(timestamp) => self.timestamp(timestamp),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn price(&mut self, price: f64) -> io::Result<()> {
+        utils::write_price(&mut self.writer, price)
+    }
+
+    pub(crate) fn symbol(&mut self, symbol: &str) -> io::Result<()> {
+        utils::write_symbol(&mut self.writer, symbol)
+    }
+
+    /// Writes an encoded message, prefixed by its length as in IEX-TP segments
+    pub(crate) fn message(&mut self, message: &[u8]) -> io::Result<()> {
+        let length = u16::try_from(message.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "message too long"))?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(message)
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads a checkpoint written by a [`CheckpointWriter`]
+pub(crate) struct CheckpointReader<R> {
+    reader: R,
+}
+
+impl<R: Read> CheckpointReader<R> {
+    /// Checks the magic number of the checkpoint, failing if it's the checkpoint of something else
+    pub(crate) fn new(mut reader: R, magic: &[u8; 8], what: &str) -> io::Result<Self> {
+        let mut actual = [0u8; 8];
+        reader.read_exact(&mut actual)?;
+        if &actual != magic {
+            return Err(invalid_data(format!("not a checkpoint of a {what}")));
+        }
+        Ok(Self { reader })
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn len(&mut self) -> io::Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| invalid_data("invalid length"))
+    }
+
+    pub(crate) fn timestamp(&mut self) -> io::Result<DateTime<Utc>> {
+        Ok(DateTime::from_timestamp_nanos(self.i64()?))
+    }
+
+    pub(crate) fn optional_timestamp(&mut self) -> io::Result<Option<DateTime<Utc>>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => self.timestamp().map(Some),
+            _ => Err(invalid_data("invalid optional timestamp")),
+        }
+    }
+
+    pub(crate) fn price(&mut self) -> io::Result<f64> {
+        Ok(utils::key_price(self.i64()?))
+    }
+
+    pub(crate) fn symbol<S>(&mut self) -> io::Result<S>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        let bytes = self.array::<8>()?;
+        let (_, symbol) = utils::symbol::<S>(&bytes).map_err(|_| invalid_data("invalid symbol"))?;
+        Ok(symbol)
+    }
+
+    pub(crate) fn message(&mut self) -> io::Result<Vec<u8>> {
+        let length = u16::from_le_bytes(self.array()?);
+        let mut message = vec![0u8; length.into()];
+        self.reader.read_exact(&mut message)?;
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let timestamp = DateTime::from_timestamp_nanos(1492448400000000000);
+        let mut written = Vec::new();
+        let mut writer = CheckpointWriter::new(&mut written, b"IEXTEST1").unwrap();
+        writer.u32(7).unwrap();
+        writer.optional_timestamp(Some(timestamp)).unwrap();
+        writer.optional_timestamp(None).unwrap();
+        writer.price(99.05).unwrap();
+        writer.symbol("ZIEXT").unwrap();
+        writer.message(b"QUOTE").unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = CheckpointReader::new(&written[..], b"IEXTEST1", "test").unwrap();
+        assert_eq!(reader.u32().unwrap(), 7);
+        assert_eq!(reader.optional_timestamp().unwrap(), Some(timestamp));
+        assert_eq!(reader.optional_timestamp().unwrap(), None);
+        assert_eq!(reader.price().unwrap(), utils::key_price(990500));
+        assert_eq!(reader.symbol::<String>().unwrap(), "ZIEXT");
+        assert_eq!(reader.message().unwrap(), b"QUOTE");
+        assert!(reader.u8().is_err());
+
+        let error = CheckpointReader::new(&written[..], b"IEXTEST2", "test")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "not a checkpoint of a test");
+    }
+}
@@ -0,0 +1,618 @@
+//! Helpers shared by the command-line tools (enabled by the `cli` feature)
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    process::{self, Child, ChildStdout, Command, Stdio},
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::{
+    deep::Deep1_0Message,
+    enrich::{MetadataTable, METADATA_COLUMNS},
+    export::{write_json, CsvWriter, ToRecord},
+    expression::FilterExpression,
+    filter::{MessageFilter, MessageKind, SymbolPattern},
+    hist::{self, HistReader, Message},
+    index::HistIndex,
+    itch::ItchConverter,
+    progress::ProgressReporter,
+    tops::Tops1_6Message,
+    trading_day::{DayPartitioned, TradingDays},
+};
+
+/// Prints an error and exits with a failure status
+pub fn fail(message: impl Display) -> ! {
+    eprintln!("error: {message}");
+    process::exit(1)
+}
+
+/// The command-line arguments of a tool: options (`--name value` or `--name=value`), flags (`--name`) and positional
+/// arguments
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    options: HashMap<String, Vec<String>>,
+    flags: Vec<String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Parses the arguments of the process. `flags` lists the options which don't take a value.
+    pub fn from_env(usage: &str, flags: &[&str]) -> Self {
+        let args = Self::parse(std::env::args().skip(1), flags).unwrap_or_else(|e| {
+            eprintln!("{usage}");
+            fail(e)
+        });
+        if args.flag("help") {
+            println!("{usage}");
+            process::exit(0);
+        }
+        args
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>, flags: &[&str]) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg);
+                continue;
+            };
+
+            if let Some((name, value)) = name.split_once('=') {
+                parsed
+                    .options
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(value.to_string());
+            } else if flags.contains(&name) || name == "help" {
+                parsed.flags.push(name.to_string());
+            } else {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for --{name}"))?;
+                parsed
+                    .options
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(value);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// The last value of an option
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name)?.last().map(String::as_str)
+    }
+
+    /// The values of an option, which may be repeated or comma-separated
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.options
+            .get(name)
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split(','))
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+
+    /// Parses the value of an option, failing with a helpful message if it's invalid
+    pub fn parsed<T>(
+        &self,
+        name: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        self.value(name)
+            .map(|value| parse(value).unwrap_or_else(|e| fail(format!("--{name}: {e}"))))
+    }
+
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    /// Builds a message filter from the `--symbols` option only
+    ///
+    /// Symbols containing `*`, `?` or `[` are treated as patterns.
+    pub fn symbol_filter(&self) -> MessageFilter {
+        let mut filter = MessageFilter::new();
+        let (patterns, symbols): (Vec<_>, Vec<_>) = self
+            .values("symbols")
+            .into_iter()
+            .partition(|symbol| symbol.contains(['*', '?', '[']));
+        if !symbols.is_empty() {
+            filter = filter.symbols(symbols);
+        }
+        if !patterns.is_empty() {
+            filter = filter.symbol_patterns(
+                patterns
+                    .into_iter()
+                    .map(|pattern| pattern.parse::<SymbolPattern>().unwrap_or_else(|e| fail(e))),
+            );
+        }
+        filter
+    }
+
+    /// Builds a message filter from the `--kinds`, `--symbols`, `--from`, `--to` and `--where` options
+    ///
+    /// Symbols containing `*`, `?` or `[` are treated as patterns. `--where` takes a [`FilterExpression`].
+    pub fn message_filter(&self) -> MessageFilter {
+        let mut filter = self.symbol_filter();
+        if let Some(expression) = self.parsed("where", |s| {
+            s.parse::<FilterExpression>().map_err(|e| e.to_string())
+        }) {
+            filter = filter.expression(expression);
+        }
+
+        let kinds = self.values("kinds");
+        if !kinds.is_empty() {
+            filter = filter.kinds(
+                kinds
+                    .into_iter()
+                    .map(|name| {
+                        MessageKind::from_name(name)
+                            .unwrap_or_else(|| fail(format!("unknown message kind {name:?}")))
+                    })
+                    .collect::<crate::filter::MessageKinds>(),
+            );
+        }
+
+        let from = self.parsed("from", parse_time);
+        let to = self.parsed("to", parse_time);
+        filter = match (from, to) {
+            (Some(from), Some(to)) => filter.time_range(from..to),
+            (Some(from), None) => filter.time_range(from..),
+            (None, Some(to)) => filter.time_range(..to),
+            (None, None) => filter,
+        };
+
+        filter
+    }
+
+    /// Reads the symbol metadata of the `--metadata` CSV file, if any, see [`MetadataTable::read_csv`]
+    pub fn metadata(&self) -> Option<MetadataTable> {
+        self.value("metadata").map(|path| {
+            File::open(path)
+                .map(BufReader::new)
+                .and_then(MetadataTable::read_csv)
+                .unwrap_or_else(|e| fail(format!("{path}: {e}")))
+        })
+    }
+}
+
+/// Parses a point in time, either as RFC 3339 (e.g. `2017-04-17T13:30:00Z`) or as nanoseconds since the POSIX epoch
+pub fn parse_time(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(nanos) = s.parse::<i64>() {
+        return Ok(DateTime::from_timestamp_nanos(nanos));
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("invalid time {s:?}: {e}"))
+}
+
+/// Parses a duration made of a number and a unit among `ns`, `us`, `ms`, `s`, `m` and `h` (e.g. `100ms`)
+pub fn parse_duration(s: &str) -> Result<TimeDelta, String> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in duration {s:?}"))?;
+    let (value, unit) = s.split_at(split);
+    let value = value
+        .parse::<i64>()
+        .map_err(|e| format!("invalid duration {s:?}: {e}"))?;
+    let duration = match unit {
+        "ns" => Some(TimeDelta::nanoseconds(value)),
+        "us" => Some(TimeDelta::microseconds(value)),
+        "ms" => TimeDelta::try_milliseconds(value),
+        "s" => TimeDelta::try_seconds(value),
+        "m" => TimeDelta::try_minutes(value),
+        "h" => TimeDelta::try_hours(value),
+        _ => return Err(format!("unknown unit in duration {s:?}")),
+    }
+    .ok_or_else(|| format!("duration {s:?} out of range"))?;
+    if duration <= TimeDelta::zero() {
+        return Err(format!("duration {s:?} isn't positive"));
+    }
+    Ok(duration)
+}
+
+/// An input file, which can seek unless it's a stream (the standard input or a decompressed file)
+pub enum Input {
+    File(BufReader<File>),
+    Stream(Box<dyn Read>),
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buf),
+            Input::Stream(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Input::File(file) => file.seek(pos),
+            Input::Stream(_) => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "can't seek in a stream",
+            )),
+        }
+    }
+}
+
+/// The output of a `gzip` process decompressing a file. The process is waited for at the end of its output, and a
+/// failure of the decompression is an error of the last read.
+struct Gunzip {
+    output: BufReader<ChildStdout>,
+    child: Child,
+}
+
+impl Read for Gunzip {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.output.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("gzip failed ({status})"),
+                ));
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for Gunzip {
+    fn drop(&mut self) {
+        // The process may still be writing, if the input wasn't read to the end
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Opens an input file: `-` for the standard input, and gzipped files (`.gz`) through the `gzip` command, which must be
+/// in the `PATH`
+pub fn open_input(path: &str) -> io::Result<Input> {
+    if path == "-" {
+        return Ok(Input::Stream(Box::new(BufReader::new(io::stdin()))));
+    }
+    if path.ends_with(".gz") {
+        let mut child = Command::new("gzip")
+            .args(["-dc", "--", path])
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let output = child.stdout.take().expect("the output of gzip is piped");
+        return Ok(Input::Stream(Box::new(Gunzip {
+            output: BufReader::new(output),
+            child,
+        })));
+    }
+    Ok(Input::File(BufReader::new(File::open(path)?)))
+}
+
+/// Opens a HIST file. If the `--from` option is set and the file has an up-to-date sidecar index (see `iex-index`),
+/// the reader starts at the last indexed segment sent before that time. Otherwise uncompressed files are bisected for
+/// the first segment sent from that time.
+pub fn open_hist(args: &Args, path: &str) -> Result<HistReader<Input>, hist::Error> {
+    let input = open_input(path)?;
+    let seekable = matches!(input, Input::File(_));
+    let mut reader = HistReader::new(input)?;
+
+    let Some(from) = args.parsed("from", parse_time) else {
+        return Ok(reader);
+    };
+    if !seekable {
+        return Ok(reader);
+    }
+    match open_index(path) {
+        Some(index) => {
+            if let Some(entry) = index.lookup(from) {
+                reader.seek(entry.offset)?;
+            }
+        }
+        None => reader.seek_time(from)?,
+    }
+    Ok(reader)
+}
+
+/// Reads the sidecar index of a HIST file, if it exists and is up to date
+pub fn open_index(path: &str) -> Option<HistIndex> {
+    let index = File::open(HistIndex::sidecar_path(path))
+        .and_then(|file| HistIndex::read_from(BufReader::new(file)))
+        .ok()?;
+    let up_to_date =
+        std::fs::metadata(path).is_ok_and(|metadata| metadata.len() == index.file_length());
+    up_to_date.then_some(index)
+}
+
+/// Opens an output file, or the standard output if no path (or `-`) is given
+pub fn open_output(path: Option<&str>) -> io::Result<Box<dyn Write>> {
+    match path {
+        None | Some("-") => Ok(Box::new(BufWriter::new(io::stdout()))),
+        Some(path) => Ok(Box::new(BufWriter::new(File::create(path)?))),
+    }
+}
+
+/// The size of an input file, if it's known in advance (i.e. the file is neither the standard input nor compressed)
+pub fn input_size(path: &str) -> Option<u64> {
+    if path == "-" || path.ends_with(".gz") {
+        return None;
+    }
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+/// Reports the progress of a tool through its input on the standard error, at most once per second
+pub fn progress_reporter(input: &str) -> ProgressReporter {
+    ProgressReporter::new(input_size(input), Duration::from_secs(1), |progress| {
+        let done = match (progress.fraction(), progress.eta()) {
+            (Some(fraction), Some(eta)) if !progress.finished => {
+                format!(" ({:.1}%, {}s left)", fraction * 100.0, eta.as_secs())
+            }
+            _ => String::new(),
+        };
+        eprintln!(
+            "{} messages, {:.1} MB{done}, {:.0} messages/s, {:.1} MB/s",
+            progress.messages,
+            progress.bytes as f64 / 1e6,
+            progress.messages_per_second(),
+            progress.bytes_per_second() / 1e6,
+        );
+    })
+}
+
+fn write_records<M>(
+    args: &Args,
+    input: &str,
+    format: &str,
+    kinds: Vec<MessageKind>,
+) -> Result<(), hist::Error>
+where
+    M: Message + ToRecord,
+{
+    let reader = open_hist(args, input)?;
+    let messages = reader.messages::<M>().with_filter(args.message_filter());
+    let metadata = args.metadata();
+    let extra_columns = match metadata {
+        Some(_) => &METADATA_COLUMNS[..],
+        None => &[],
+    };
+    let metadata = metadata.unwrap_or_default();
+
+    if let Some(directory) = args.value("by-day") {
+        return write_days(messages, directory, format, kinds, extra_columns, &metadata);
+    }
+
+    let mut output = open_output(args.value("output"))?;
+    match format {
+        "csv" => {
+            let mut writer =
+                CsvWriter::with_extra_columns(&mut output, kinds, extra_columns.iter().copied())?;
+            for message in messages {
+                writer.write(&metadata.enriched(&message?))?;
+            }
+        }
+        "jsonl" => {
+            for message in messages {
+                write_json(&mut output, &metadata.enriched(&message?))?;
+            }
+        }
+        format => fail(format!("unknown format {format:?}")),
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes records into a file per trading day, named after the day (e.g. `2017-04-17.csv`)
+fn write_days<M>(
+    messages: impl Iterator<Item = Result<M, hist::Error>>,
+    directory: &str,
+    format: &str,
+    kinds: Vec<MessageKind>,
+    extra_columns: &[&'static str],
+    metadata: &MetadataTable,
+) -> Result<(), hist::Error>
+where
+    M: Message + ToRecord,
+{
+    fs::create_dir_all(directory)?;
+    let create = |day: NaiveDate| {
+        File::create(Path::new(directory).join(format!("{day}.{format}"))).map(BufWriter::new)
+    };
+    let mut days = TradingDays::new();
+
+    match format {
+        "csv" => {
+            let mut output = DayPartitioned::new(
+                |day| {
+                    CsvWriter::with_extra_columns(
+                        create(day)?,
+                        kinds.iter().copied(),
+                        extra_columns.iter().copied(),
+                    )
+                },
+                |writer: CsvWriter<_>| writer.into_inner().flush(),
+            );
+            for message in messages {
+                let message = message?;
+                if let Some(day) = days.update(&message) {
+                    output.writer(day)?.write(&metadata.enriched(&message))?;
+                }
+            }
+            output.finish()?;
+        }
+        "jsonl" => {
+            let mut output =
+                DayPartitioned::new(create, |mut writer: BufWriter<File>| writer.flush());
+            for message in messages {
+                let message = message?;
+                if let Some(day) = days.update(&message) {
+                    write_json(output.writer(day)?, &metadata.enriched(&message))?;
+                }
+            }
+            output.finish()?;
+        }
+        format => fail(format!("unknown format {format:?}")),
+    }
+    Ok(())
+}
+
+/// Writes the messages of a HIST file, normalized into ITCH-like messages, as JSON Lines
+fn write_itch(args: &Args, input: &str, deep: bool) -> Result<(), hist::Error> {
+    let reader = open_hist(args, input)?;
+    let mut output = open_output(args.value("output"))?;
+    let mut converter = ItchConverter::new();
+    let metadata = args.metadata().unwrap_or_default();
+
+    if deep {
+        let messages = reader.messages::<Deep1_0Message<String>>();
+        for message in messages.with_filter(args.message_filter()) {
+            for converted in converter.convert_deep(&message?) {
+                write_json(&mut output, &metadata.enriched(&converted))?;
+            }
+        }
+    } else {
+        let messages = reader.messages::<Tops1_6Message<String>>();
+        for message in messages.with_filter(args.message_filter()) {
+            for converted in converter.convert_tops(&message?) {
+                write_json(&mut output, &metadata.enriched(&converted))?;
+            }
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the messages of a HIST file as CSV, JSON Lines or normalized ITCH-like JSON Lines (`format`), according to
+/// the `--protocol`, `--output`, `--by-day`, `--metadata` and filter options
+pub fn write_messages(args: &Args, input: &str, format: &str) -> Result<(), hist::Error> {
+    let deep = match args.value("protocol").unwrap_or("tops") {
+        "tops" => false,
+        "deep" => true,
+        protocol => fail(format!("unknown protocol {protocol:?}")),
+    };
+    if format == "itch" {
+        if args.value("by-day").is_some() {
+            fail("--by-day isn't supported with the itch format");
+        }
+        return write_itch(args, input, deep);
+    }
+
+    // The CSV header lists the columns of every kind of message which may be written
+    let mut kinds = args
+        .values("kinds")
+        .into_iter()
+        .filter_map(MessageKind::from_name)
+        .collect::<Vec<_>>();
+    if kinds.is_empty() {
+        kinds = MessageKind::ALL
+            .into_iter()
+            .filter(|kind| match kind {
+                MessageKind::PriceLevelUpdateBuy | MessageKind::PriceLevelUpdateSell => deep,
+                _ => true,
+            })
+            .collect();
+    }
+
+    if deep {
+        write_records::<Deep1_0Message<String>>(args, input, format, kinds)
+    } else {
+        write_records::<Tops1_6Message<String>>(args, input, format, kinds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let args = Args::parse(
+            [
+                "--kinds",
+                "T",
+                "--symbols=ZIEXT,SPY*",
+                "--csv",
+                "input",
+                "--kinds",
+                "Q",
+            ]
+            .map(String::from),
+            &["csv"],
+        )
+        .unwrap();
+
+        assert!(args.flag("csv"));
+        assert_eq!(args.values("kinds"), ["T", "Q"]);
+        assert_eq!(args.value("kinds"), Some("Q"));
+        assert_eq!(args.positional(), ["input"]);
+
+        let filter = args.message_filter();
+        let mut trade = [0u8; 38];
+        trade[0] = b'T';
+        trade[10..18].copy_from_slice(b"SPYG    ");
+        assert!(filter.accepts(&trade));
+        trade[0] = b'B';
+        assert!(!filter.accepts(&trade));
+
+        assert!(Args::parse(["--kinds".to_string()], &[]).is_err());
+        assert_eq!(parse_duration("250ms"), Ok(TimeDelta::milliseconds(250)));
+        assert_eq!(parse_duration("5m"), Ok(TimeDelta::minutes(5)));
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("1d").is_err());
+        assert_eq!(
+            parse_time("2017-04-17T13:30:00Z").unwrap(),
+            parse_time("1492435800000000000").unwrap()
+        );
+    }
+    #[test]
+    fn gzipped_input() {
+        let path = std::env::temp_dir().join(format!("iex-parser-cli-{}.gz", process::id()));
+        let compressed = Command::new("gzip")
+            .arg("-c")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                child.stdin.take().unwrap().write_all(b"HIST")?;
+                child.wait_with_output()
+            })
+            .unwrap()
+            .stdout;
+        let path = path.to_str().unwrap();
+
+        fs::write(path, &compressed).unwrap();
+        let mut decompressed = Vec::new();
+        open_input(path)
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, b"HIST");
+
+        // The status of gzip is checked once its output is read
+        fs::write(path, &compressed[..compressed.len() - 4]).unwrap();
+        let error = open_input(path)
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+use std::{io::Read, ops::Range};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::Deep1_0Message,
+    filter::MessageKind,
+    hist::{self, HistReader},
+    tops::{SystemEvent, SystemEventType, Tops1_6Message},
+};
+
+/// The phase of the trading day
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MarketPhase {
+    /// Outside of the system hours
+    Closed,
+    /// From the start of the system hours to the start of the regular hours
+    PreMarket,
+    Regular,
+    /// From the end of the regular hours to the end of the system hours
+    PostMarket,
+}
+
+impl MarketPhase {
+    /// The phase which a system event starts
+    pub fn after(event_type: SystemEventType) -> Self {
+        match event_type {
+            SystemEventType::StartOfSystemHours => MarketPhase::PreMarket,
+            SystemEventType::StartOfRegularHours => MarketPhase::Regular,
+            SystemEventType::EndOfRegularHours => MarketPhase::PostMarket,
+            SystemEventType::StartOfMessages
+            | SystemEventType::EndOfSystemHours
+            | SystemEventType::EndOfMessages => MarketPhase::Closed,
+        }
+    }
+}
+
+/// The phases of a trading day, as delimited by the system events of a HIST file rather than by the usual schedule
+/// (early closes, for example, end the regular hours before 16:00)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MarketClock {
+    /// The system events seen, in chronological order
+    events: Vec<SystemEvent>,
+}
+
+impl MarketClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the system events of a whole HIST file, of any protocol
+    pub fn from_hist<R: Read>(mut reader: HistReader<R>) -> Result<Self, hist::Error> {
+        let mut clock = Self::new();
+        while let Some(captured) = reader.next_segment()? {
+            for message in &captured.segment.messages {
+                if MessageKind::of(message) == Some(MessageKind::SystemEvent) {
+                    if let Ok(event) = SystemEvent::try_from(*message) {
+                        clock.add_event(&event);
+                    }
+                }
+            }
+        }
+        Ok(clock)
+    }
+
+    /// Records a system event. Events already seen (e.g. from the feed of another protocol) are ignored.
+    pub fn add_event(&mut self, event: &SystemEvent) {
+        if self.events.contains(event) {
+            return;
+        }
+        let index = self
+            .events
+            .partition_point(|seen| seen.timestamp <= event.timestamp);
+        self.events.insert(index, event.clone());
+    }
+
+    /// Feeds a TOPS message to the clock. Messages other than system events are ignored.
+    pub fn update<S>(&mut self, message: &Tops1_6Message<S>) {
+        if let Tops1_6Message::SystemEvent(event) = message {
+            self.add_event(event);
+        }
+    }
+
+    /// Feeds a DEEP message to the clock. Messages other than system events are ignored.
+    pub fn update_deep<S>(&mut self, message: &Deep1_0Message<S>) {
+        if let Deep1_0Message::SystemEvent(event) = message {
+            self.add_event(event);
+        }
+    }
+
+    /// The phase of the market at some time, according to the last system event sent at or before it
+    pub fn phase_at(&self, time: DateTime<Utc>) -> MarketPhase {
+        let following = self.events.partition_point(|event| event.timestamp <= time);
+        following
+            .checked_sub(1)
+            .map_or(MarketPhase::Closed, |index| {
+                MarketPhase::after(self.events[index].event_type)
+            })
+    }
+
+    /// The phase of the market after the last system event seen
+    pub fn phase(&self) -> MarketPhase {
+        self.events.last().map_or(MarketPhase::Closed, |event| {
+            MarketPhase::after(event.event_type)
+        })
+    }
+
+    /// The time of the first system event of a type, e.g. the exact start of the regular hours
+    pub fn transition(&self, event_type: SystemEventType) -> Option<DateTime<Utc>> {
+        self.events
+            .iter()
+            .find(|event| event.event_type == event_type)
+            .map(|event| event.timestamp)
+    }
+
+    /// The system events seen, in chronological order
+    pub fn transitions(&self) -> &[SystemEvent] {
+        &self.events
+    }
+
+    /// The regular hours, if both their start and end were seen
+    pub fn regular_hours(&self) -> Option<Range<DateTime<Utc>>> {
+        let start = self.transition(SystemEventType::StartOfRegularHours)?;
+        let end = self.transition(SystemEventType::EndOfRegularHours)?;
+        Some(start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids,
+    };
+
+    use super::*;
+
+    fn event(event_type: SystemEventType, hours: i64, minutes: i64) -> SystemEvent {
+        // 2017-04-17 00:00 ET
+        let midnight = DateTime::from_timestamp_nanos(1492401600000000000);
+        SystemEvent {
+            event_type,
+            timestamp: midnight + TimeDelta::hours(hours) + TimeDelta::minutes(minutes),
+        }
+    }
+
+    #[test]
+    fn phases() {
+        let events = [
+            event(SystemEventType::StartOfMessages, 7, 45),
+            event(SystemEventType::StartOfSystemHours, 8, 0),
+            event(SystemEventType::StartOfRegularHours, 9, 30),
+            // An early close
+            event(SystemEventType::EndOfRegularHours, 13, 0),
+            event(SystemEventType::EndOfSystemHours, 17, 0),
+            event(SystemEventType::EndOfMessages, 17, 5),
+        ];
+        let encoded = events
+            .iter()
+            .map(|event| event.encode().unwrap())
+            .collect::<Vec<_>>();
+        let messages = encoded.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        // The DEEP feed repeats the events
+        let capture = capture(&[
+            segment(message_protocol_ids::TOPS, 1, &messages),
+            segment(message_protocol_ids::DEEP_1_0, 1, &messages),
+        ]);
+
+        let clock = MarketClock::from_hist(HistReader::new(&capture[..]).unwrap()).unwrap();
+        assert_eq!(clock.transitions(), events);
+        let at = |hours, minutes| {
+            clock.phase_at(event(SystemEventType::EndOfMessages, hours, minutes).timestamp)
+        };
+        assert_eq!(at(7, 0), MarketPhase::Closed);
+        assert_eq!(at(8, 0), MarketPhase::PreMarket);
+        assert_eq!(at(12, 59), MarketPhase::Regular);
+        assert_eq!(at(13, 0), MarketPhase::PostMarket);
+        assert_eq!(at(17, 1), MarketPhase::Closed);
+        assert_eq!(clock.phase(), MarketPhase::Closed);
+        assert_eq!(
+            clock.regular_hours(),
+            Some(events[2].timestamp..events[3].timestamp)
+        );
+
+        let mut partial = MarketClock::new();
+        partial.update(&Tops1_6Message::<String>::SystemEvent(events[2].clone()));
+        partial.update(&Tops1_6Message::<String>::SystemEvent(events[1].clone()));
+        assert_eq!(partial.phase(), MarketPhase::Regular);
+        assert_eq!(partial.regular_hours(), None);
+    }
+}
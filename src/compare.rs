@@ -0,0 +1,277 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    hist::{self, CapturedSegment, HistReader},
+    sequence::{SequenceRange, SequenceTracker, Sequencing, StreamId},
+};
+
+/// One of the two captures compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn index(self) -> usize {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+        }
+    }
+}
+
+/// A message seen in a single capture so far
+#[derive(Clone, Copy, Debug)]
+struct Unmatched {
+    send_time: DateTime<Utc>,
+    capture_time: Option<DateTime<Utc>>,
+    digest: u64,
+}
+
+/// Statistics of the differences between the capture times of the messages of two captures (right minus left)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SkewStats {
+    pub count: u64,
+    pub min: Option<TimeDelta>,
+    pub max: Option<TimeDelta>,
+    sum_nanos: i128,
+}
+
+impl SkewStats {
+    fn add(&mut self, skew: TimeDelta) {
+        self.count += 1;
+        self.min = Some(self.min.map_or(skew, |min| min.min(skew)));
+        self.max = Some(self.max.map_or(skew, |max| max.max(skew)));
+        self.sum_nanos += i128::from(skew.num_nanoseconds().unwrap_or_default());
+    }
+
+    pub fn mean(&self) -> Option<TimeDelta> {
+        (self.count > 0)
+            .then(|| TimeDelta::nanoseconds((self.sum_nanos / i128::from(self.count)) as i64))
+    }
+}
+
+/// The outcome of the comparison of two captures
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comparison {
+    /// The number of messages found in both captures, with the same content
+    pub matched: u64,
+    /// The messages found in the left capture only
+    pub only_left: Vec<SequenceRange>,
+    /// The messages found in the right capture only
+    pub only_right: Vec<SequenceRange>,
+    /// The messages found in both captures, but with different contents
+    pub different: Vec<SequenceRange>,
+    /// The capture time skew of the messages found in both captures
+    pub skew: SkewStats,
+}
+
+impl Comparison {
+    /// Whether both captures have the same messages
+    pub fn is_identical(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.different.is_empty()
+    }
+}
+
+/// Compares two captures of the same feed (e.g. from two capture hosts) message by message, matching them by stream and
+/// sequence number
+///
+/// Segments can be added in any order, but messages are kept until they are seen in the other capture, so the captures
+/// should be read side by side, as [`compare`] does, to keep memory bounded. Messages repeated within a capture are
+/// ignored.
+#[derive(Debug, Default)]
+pub struct CaptureComparer {
+    unmatched: [HashMap<(StreamId, i64), Unmatched>; 2],
+    trackers: [SequenceTracker; 2],
+    matched: u64,
+    different: Vec<(StreamId, i64, DateTime<Utc>)>,
+    skew: SkewStats,
+}
+
+impl CaptureComparer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, side: Side, captured: &CapturedSegment<'_>) {
+        let segment = &captured.segment;
+        let stream = StreamId::from(segment);
+        let first = segment.first_message_sequence_no;
+        let repeated = match self.trackers[side.index()].update(segment) {
+            Sequencing::Duplicate(range) => range.end(),
+            _ => first,
+        };
+
+        for (sequence_number, message) in (first..).zip(&segment.messages) {
+            if sequence_number < repeated {
+                continue;
+            }
+
+            let this = Unmatched {
+                send_time: segment.send_time,
+                capture_time: captured.capture_time,
+                digest: digest(message),
+            };
+            let Some(other) = self.unmatched[1 - side.index()].remove(&(stream, sequence_number))
+            else {
+                self.unmatched[side.index()].insert((stream, sequence_number), this);
+                continue;
+            };
+
+            if this.digest != other.digest {
+                self.different
+                    .push((stream, sequence_number, this.send_time));
+                continue;
+            }
+            self.matched += 1;
+            if let (Some(this_time), Some(other_time)) = (this.capture_time, other.capture_time) {
+                self.skew.add(match side {
+                    Side::Left => other_time - this_time,
+                    Side::Right => this_time - other_time,
+                });
+            }
+        }
+    }
+
+    pub fn finish(self) -> Comparison {
+        let [left, right] = self.unmatched;
+        let unmatched = |messages: HashMap<(StreamId, i64), Unmatched>| {
+            ranges(
+                messages
+                    .into_iter()
+                    .map(|((stream, sequence_number), message)| {
+                        (stream, sequence_number, message.send_time)
+                    }),
+            )
+        };
+
+        Comparison {
+            matched: self.matched,
+            only_left: unmatched(left),
+            only_right: unmatched(right),
+            different: ranges(self.different),
+            skew: self.skew,
+        }
+    }
+}
+
+/// Compares two HIST files, reading them side by side by capture time
+pub fn compare<L: Read, R: Read>(
+    mut left: HistReader<L>,
+    mut right: HistReader<R>,
+) -> Result<Comparison, hist::Error> {
+    let mut comparer = CaptureComparer::new();
+    // The capture time of the last segment read from each side, or `None` once it's exhausted
+    let mut times = [Some(DateTime::<Utc>::MIN_UTC); 2];
+
+    loop {
+        let side = match times {
+            [None, None] => return Ok(comparer.finish()),
+            [Some(left), Some(right)] if right < left => Side::Right,
+            [None, Some(_)] => Side::Right,
+            _ => Side::Left,
+        };
+        let captured = match side {
+            Side::Left => left.next_segment()?,
+            Side::Right => right.next_segment()?,
+        };
+
+        times[side.index()] = captured
+            .as_ref()
+            .map(|captured| captured.capture_time.unwrap_or(captured.segment.send_time));
+        if let Some(captured) = captured {
+            comparer.add(side, &captured);
+        }
+    }
+}
+
+// A hash of the content of a message (FNV-1a), so that unmatched messages aren't kept whole
+fn digest(message: &[u8]) -> u64 {
+    message.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Coalesces messages into ranges of consecutive sequence numbers
+fn ranges(
+    messages: impl IntoIterator<Item = (StreamId, i64, DateTime<Utc>)>,
+) -> Vec<SequenceRange> {
+    let sorted = messages
+        .into_iter()
+        .map(|(stream, sequence_number, send_time)| ((stream, sequence_number), send_time))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut ranges = Vec::<SequenceRange>::new();
+    for ((stream, sequence_number), send_time) in sorted {
+        match ranges.last_mut() {
+            Some(last) if last.stream == stream && last.end() == sequence_number => last.count += 1,
+            _ => ranges.push(SequenceRange {
+                stream,
+                first: sequence_number,
+                count: 1,
+                send_time,
+            }),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids,
+    };
+
+    use super::*;
+
+    #[test]
+    fn comparison() {
+        let messages = (0u8..8).map(|index| vec![0x53, index]).collect::<Vec<_>>();
+        let segment = |first: i64, messages: &[Vec<u8>]| {
+            segment(
+                message_protocol_ids::TOPS,
+                first,
+                &messages.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+            )
+        };
+
+        // The left capture misses messages 3 and 4, the right one misses 7 and 8 and has a different message 6, and
+        // repeats messages 1 and 2
+        let left = capture(&[segment(1, &messages[0..2]), segment(5, &messages[4..8])]);
+        let mut different = messages[5].clone();
+        different[1] = 0xFF;
+        let right = capture(&[
+            segment(1, &messages[0..2]),
+            segment(1, &messages[0..2]),
+            segment(3, &messages[2..5]),
+            segment(6, &[different]),
+        ]);
+
+        let comparison = compare(
+            HistReader::new(&left[..]).unwrap(),
+            HistReader::new(&right[..]).unwrap(),
+        )
+        .unwrap();
+        let summary = |ranges: &[SequenceRange]| {
+            ranges
+                .iter()
+                .map(|range| (range.first, range.count))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(comparison.matched, 3);
+        assert_eq!(summary(&comparison.only_left), [(7, 2)]);
+        assert_eq!(summary(&comparison.only_right), [(3, 2)]);
+        assert_eq!(summary(&comparison.different), [(6, 1)]);
+        assert!(!comparison.is_identical());
+        assert_eq!(comparison.skew.count, 3);
+        assert_eq!(comparison.skew.mean(), Some(TimeDelta::zero()));
+    }
+}
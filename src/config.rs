@@ -0,0 +1,246 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    filter::{MessageFilter, MessageKind, SymbolPattern},
+    hist::Message,
+};
+
+/// What readers do with a message of a known type which can't be decoded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// The message is an error, which readers report in its place before reading the rest of its segment
+    #[default]
+    Strict,
+    /// The message is skipped, and only counted in the statistics of the reader
+    Lenient,
+}
+
+/// What readers do with a message of a type the crate doesn't know, e.g. added to the specifications since
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownMessages {
+    /// The message is handled like any message which can't be decoded, according to the [`Strictness`]
+    #[default]
+    Invalid,
+    /// The message is skipped
+    Skip,
+}
+
+/// How prices, sent as fixed-point numbers with 4 decimal digits, are converted to doubles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PriceRepresentation {
+    /// The fixed-point number is scaled, which doesn't always give the double nearest to its decimal value (e.g.
+    /// 99.05000000000001 rather than 99.05)
+    #[default]
+    Scaled,
+    /// The price is rounded to the double nearest to its decimal value, e.g. to compare it with literals
+    Rounded,
+}
+
+/// How readers treat the timestamps of the messages
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampHandling {
+    /// The timestamps are taken as they are
+    #[default]
+    AsSent,
+    /// The messages timestamped further than this from the send time of their segment are invalid, as a likely sign
+    /// of corruption
+    MaxSkew(TimeDelta),
+}
+
+/// Why a message wasn't decoded, see [`ParserConfig::decode`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// The type of the message is unknown
+    Unknown,
+    /// The message couldn't be decoded
+    Invalid,
+    /// The message was decoded, but its timestamp is too far from the send time of its segment
+    Skewed,
+}
+
+/// The options of the readers of messages (e.g. [`HistReader::with_config`](crate::hist::HistReader::with_config)),
+/// in one place
+///
+/// The default config decodes every message as it is, and fails on the first message which can't be decoded.
+#[derive(Clone, Debug, Default)]
+pub struct ParserConfig {
+    strictness: Strictness,
+    unknown_messages: UnknownMessages,
+    prices: PriceRepresentation,
+    timestamps: TimestampHandling,
+    symbols: MessageFilter,
+}
+
+impl ParserConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    pub fn unknown_messages(mut self, unknown_messages: UnknownMessages) -> Self {
+        self.unknown_messages = unknown_messages;
+        self
+    }
+
+    pub fn prices(mut self, prices: PriceRepresentation) -> Self {
+        self.prices = prices;
+        self
+    }
+
+    pub fn timestamps(mut self, timestamps: TimestampHandling) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Only reads the messages of the given symbols, along with the messages without any symbol (e.g. system events)
+    pub fn symbols<'a>(mut self, symbols: impl IntoIterator<Item = &'a str>) -> Self {
+        self.symbols = self.symbols.symbols(symbols);
+        self
+    }
+
+    /// Also reads the messages whose symbol matches any of the patterns
+    pub fn symbol_patterns(mut self, patterns: impl IntoIterator<Item = SymbolPattern>) -> Self {
+        self.symbols = self.symbols.symbol_patterns(patterns);
+        self
+    }
+
+    /// Whether a raw message is of a symbol to read
+    pub fn accepts(&self, message: &[u8]) -> bool {
+        self.symbols.accepts(message)
+    }
+
+    /// Decodes a message of a segment sent at some time, according to the config. The symbols aren't checked, see
+    /// [`ParserConfig::accepts`].
+    pub fn decode<M: Message>(
+        &self,
+        message: &[u8],
+        send_time: DateTime<Utc>,
+    ) -> Result<M, Rejection> {
+        self.decode_sent::<M>(message, Some(send_time))
+    }
+
+    /// Decodes a message like [`ParserConfig::decode`], without checking its timestamp if its send time is unknown
+    pub(crate) fn decode_sent<M: Message>(
+        &self,
+        message: &[u8],
+        send_time: Option<DateTime<Utc>>,
+    ) -> Result<M, Rejection> {
+        let Ok((_, mut decoded)) = M::parse(message) else {
+            return Err(match MessageKind::of(message) {
+                Some(_) => Rejection::Invalid,
+                None => Rejection::Unknown,
+            });
+        };
+        if let (TimestampHandling::MaxSkew(max_skew), Some(send_time)) =
+            (self.timestamps, send_time)
+        {
+            if decoded
+                .timestamp()
+                .is_some_and(|timestamp| (timestamp - send_time).abs() > max_skew)
+            {
+                return Err(Rejection::Skewed);
+            }
+        }
+        if self.prices == PriceRepresentation::Rounded {
+            decoded.round_prices();
+        }
+        Ok(decoded)
+    }
+
+    /// Whether a message rejected for some reason is skipped rather than reported as an error
+    pub fn tolerates(&self, rejection: Rejection) -> bool {
+        rejection == Rejection::Unknown && self.unknown_messages == UnknownMessages::Skip
+            || self.strictness == Strictness::Lenient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        hist::{
+            tests::{capture, segment},
+            HistReader,
+        },
+        message_protocol_ids, spec,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let (_, Tops1_6Message::TradeReport(trade)) =
+            Tops1_6Message::<String>::parse(&spec::TRADE_REPORT).unwrap()
+        else {
+            panic!("not a trade report")
+        };
+        let near = trade.timestamp + TimeDelta::milliseconds(1);
+
+        let config = ParserConfig::new().prices(PriceRepresentation::Rounded);
+        let Ok(Tops1_6Message::TradeReport(rounded)) =
+            config.decode::<Tops1_6Message<String>>(&spec::TRADE_REPORT, near)
+        else {
+            panic!("not a trade report")
+        };
+        assert_eq!(rounded.price, 99.05);
+
+        let config = config.timestamps(TimestampHandling::MaxSkew(TimeDelta::microseconds(10)));
+        assert_eq!(
+            config
+                .decode::<Tops1_6Message<String>>(&spec::TRADE_REPORT, near)
+                .unwrap_err(),
+            Rejection::Skewed
+        );
+        assert!(!config.tolerates(Rejection::Skewed));
+
+        assert_eq!(
+            config
+                .decode::<Tops1_6Message<String>>(b"Y", near)
+                .unwrap_err(),
+            Rejection::Unknown
+        );
+        let config = config.unknown_messages(UnknownMessages::Skip);
+        assert!(config.tolerates(Rejection::Unknown));
+        assert!(!config.tolerates(Rejection::Invalid));
+        assert!(config
+            .strictness(Strictness::Lenient)
+            .tolerates(Rejection::Invalid));
+
+        let config = ParserConfig::new().symbols(["ZIEXT"]);
+        assert!(config.accepts(&spec::TRADE_REPORT));
+        assert!(!ParserConfig::new()
+            .symbols(["SPY"])
+            .accepts(&spec::TRADE_REPORT));
+    }
+
+    #[test]
+    fn readers() {
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&spec::QUOTE_UPDATE, &b"Y"[..], &spec::TRADE_REPORT],
+        );
+        let capture = capture(&[encoded]);
+        let read = |config: ParserConfig| {
+            HistReader::new(Cursor::new(&capture))
+                .unwrap()
+                .with_config(config)
+                .messages::<Tops1_6Message<String>>()
+                .collect::<Vec<_>>()
+        };
+
+        let strict = read(ParserConfig::new());
+        assert_eq!(strict.len(), 3);
+        assert!(strict[1].is_err());
+
+        let lenient = read(ParserConfig::new().strictness(Strictness::Lenient));
+        assert_eq!(lenient.len(), 2);
+        assert!(lenient.iter().all(Result::is_ok));
+    }
+}
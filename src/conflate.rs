@@ -0,0 +1,132 @@
+use std::{collections::HashMap, hash::Hash};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::tops::{QuoteUpdate, Tops1_6Message};
+
+/// Conflates the quote updates of each symbol, emitting at most one (the latest) per interval
+///
+/// Intervals are aligned to multiples of the interval since the POSIX epoch. The quotes of an interval are emitted (in
+/// chronological order) once a message of a later interval is seen, or when the stream ends. Other messages are
+/// passed through as they come.
+#[derive(Clone, Debug)]
+pub struct Conflator<S> {
+    interval: TimeDelta,
+    /// The end of the interval the pending quotes belong to
+    interval_end: Option<DateTime<Utc>>,
+    pending: HashMap<S, QuoteUpdate<S>>,
+}
+
+impl<S> Conflator<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new(interval: TimeDelta) -> Self {
+        assert!(
+            interval > TimeDelta::zero(),
+            "conflation interval must be positive"
+        );
+
+        Self {
+            interval,
+            interval_end: None,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn interval_end(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = self.interval.num_nanoseconds().unwrap_or(i64::MAX);
+        let nanos = timestamp.timestamp_nanos_opt().unwrap_or_default();
+        DateTime::from_timestamp_nanos(nanos - nanos.rem_euclid(interval) + interval)
+    }
+
+    fn flush(&mut self) -> Vec<Tops1_6Message<S>> {
+        let mut quotes = self
+            .pending
+            .drain()
+            .map(|(_, quote)| quote)
+            .collect::<Vec<_>>();
+        quotes.sort_by_key(|quote| quote.timestamp);
+        self.interval_end = None;
+        quotes
+            .into_iter()
+            .map(Tops1_6Message::QuoteUpdate)
+            .collect()
+    }
+
+    /// Feeds a TOPS message, returning the messages to emit
+    pub fn update(&mut self, message: Tops1_6Message<S>) -> Vec<Tops1_6Message<S>> {
+        let mut emitted = match (message.timestamp(), self.interval_end) {
+            (Some(timestamp), Some(end)) if timestamp >= end => self.flush(),
+            _ => Vec::new(),
+        };
+
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => {
+                if self.interval_end.is_none() {
+                    self.interval_end = Some(self.interval_end(quote.timestamp));
+                }
+                self.pending.insert(quote.symbol.clone(), quote);
+            }
+            message => emitted.push(message),
+        }
+        emitted
+    }
+
+    /// Emits the quotes of the last interval
+    pub fn finish(&mut self) -> Vec<Tops1_6Message<S>> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::assert_matches;
+
+    use crate::{
+        fixtures,
+        tops::{SystemEvent, SystemEventType},
+    };
+
+    use super::*;
+
+    fn quote(timestamp: i64, symbol: &'static str, bid_price: f64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::QuoteUpdate(fixtures::quote(symbol, timestamp, bid_price, 10.05))
+    }
+
+    fn summary(messages: &[Tops1_6Message<&'static str>]) -> Vec<(i64, &'static str)> {
+        messages
+            .iter()
+            .map(|message| {
+                (
+                    message.timestamp().unwrap().timestamp_nanos_opt().unwrap(),
+                    message.symbol().copied().unwrap_or("-"),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn latest_quote_per_interval() {
+        let mut conflator = Conflator::new(TimeDelta::nanoseconds(10));
+
+        assert!(conflator.update(quote(1, "ZIEXT", 10.00)).is_empty());
+        assert!(conflator.update(quote(2, "ZVZZT", 20.00)).is_empty());
+        assert!(conflator.update(quote(5, "ZIEXT", 10.01)).is_empty());
+        let event = Tops1_6Message::SystemEvent(SystemEvent {
+            event_type: SystemEventType::EndOfRegularHours,
+            timestamp: DateTime::from_timestamp_nanos(7),
+        });
+        assert_eq!(summary(&conflator.update(event)), [(7, "-")]);
+
+        let emitted = conflator.update(quote(12, "ZIEXT", 10.02));
+        assert_eq!(summary(&emitted), [(2, "ZVZZT"), (5, "ZIEXT")]);
+        assert_matches!(
+            &emitted[1],
+            Tops1_6Message::QuoteUpdate(QuoteUpdate { bid_price, .. }) if *bid_price == 10.01
+        );
+
+        assert_eq!(summary(&conflator.finish()), [(12, "ZIEXT")]);
+        assert!(conflator.finish().is_empty());
+    }
+}
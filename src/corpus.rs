@@ -0,0 +1,553 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    filter::MessageKind,
+    hist::HistWriter,
+    sim::{dollars, price, Rng, SimulatedSegment},
+    tops::{
+        AuctionInformation, AuctionType, ImbalanceSide, LuldTier, MarketSession, OfficialPrice,
+        OfficialPriceType, OperationalHaltStatus, OperationalHaltStatusType, QuoteUpdate,
+        RetailLiquidityIndicator, RetailLiquidityIndicatorType, SaleCondition, SecurityDirectory,
+        SecurityDirectoryFlags, ShortSalePriceTestDetail, ShortSalePriceTestStatus, SystemEvent,
+        SystemEventType, Tops1_6Message, TradeBreak, TradeReport, TradingStatus,
+        TradingStatusReason, TradingStatusType,
+    },
+};
+
+/// The kinds of messages which can be part of the mix of a corpus: system events are only sent at its start and end
+pub const MIXABLE_KINDS: [MessageKind; 10] = [
+    MessageKind::SecurityDirectory,
+    MessageKind::TradingStatus,
+    MessageKind::RetailLiquidityIndicator,
+    MessageKind::OperationalHaltStatus,
+    MessageKind::ShortSalePriceTestStatus,
+    MessageKind::QuoteUpdate,
+    MessageKind::TradeReport,
+    MessageKind::OfficialPrice,
+    MessageKind::TradeBreak,
+    MessageKind::AuctionInformation,
+];
+
+/// The shape of a benchmark corpus
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorpusConfig {
+    /// The time of the first message
+    pub start: DateTime<Utc>,
+    /// The number of symbols, some of which are much more active than others
+    pub symbols: usize,
+    /// The average number of messages per second
+    pub message_rate: f64,
+    /// The relative frequency of each kind of message, see [`MIXABLE_KINDS`]
+    pub mix: Vec<(MessageKind, f64)>,
+    /// The maximum number of messages per segment
+    pub max_segment_messages: usize,
+    pub seed: u64,
+}
+
+impl Default for CorpusConfig {
+    /// A busy TOPS feed: mostly quote updates, some trades, and a few of every other kind of message
+    fn default() -> Self {
+        Self {
+            // 2017-04-17 09:30 ET
+            start: DateTime::from_timestamp_nanos(1492435800000000000),
+            symbols: 8000,
+            message_rate: 100_000.0,
+            mix: vec![
+                (MessageKind::QuoteUpdate, 90.0),
+                (MessageKind::TradeReport, 8.0),
+                (MessageKind::AuctionInformation, 1.0),
+                (MessageKind::OfficialPrice, 0.3),
+                (MessageKind::TradingStatus, 0.2),
+                (MessageKind::ShortSalePriceTestStatus, 0.2),
+                (MessageKind::SecurityDirectory, 0.2),
+                (MessageKind::OperationalHaltStatus, 0.05),
+                (MessageKind::TradeBreak, 0.05),
+            ],
+            max_segment_messages: 4,
+            seed: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Symbol {
+    name: String,
+    /// The quote, in cents
+    bid: i64,
+    spread: i64,
+    halted: bool,
+    operationally_halted: bool,
+    short_sale_price_test: bool,
+}
+
+/// Generates an endless TOPS feed with a configurable mix of messages, as segments, for benchmarks
+///
+/// The feed starts with the system events of the start of a trading day, and the directory and trading status of every
+/// symbol. Its symbols are named `A` to `Z`, then `AA` and so on, and the first ones are the most active. The content of
+/// the messages is plausible rather than realistic (e.g. quotes follow a random walk and trades are at the quoted
+/// prices), and the same configuration always generates the same feed.
+#[derive(Clone, Debug)]
+pub struct Corpus {
+    rng: Rng,
+    symbols: Vec<Symbol>,
+    /// The cumulative weights of the mix
+    mix: Vec<(MessageKind, f64)>,
+    message_rate: f64,
+    max_segment_messages: usize,
+    time: DateTime<Utc>,
+    pending: VecDeque<Tops1_6Message<String>>,
+    last_trade: Option<TradeReport<String>>,
+    next_trade_id: i64,
+    session_id: u32,
+    next_sequence_number: i64,
+    stream_offset: i64,
+}
+
+impl Corpus {
+    /// # Panics
+    ///
+    /// Panics if the configuration has no symbol, a rate or maximum number of messages per segment which isn't
+    /// positive, or a mix without any positive weight or with a kind which isn't mixable.
+    pub fn new(config: CorpusConfig) -> Self {
+        assert!(config.symbols > 0, "a corpus needs symbols");
+        assert!(
+            config.message_rate > 0.0 && config.max_segment_messages > 0,
+            "message rates and segment sizes must be positive"
+        );
+        let mut total = 0.0;
+        let mut mix = Vec::new();
+        for (kind, weight) in config.mix {
+            assert!(
+                MIXABLE_KINDS.contains(&kind),
+                "{} messages can't be mixed",
+                kind.name()
+            );
+            if weight > 0.0 {
+                total += weight;
+                mix.push((kind, total));
+            }
+        }
+        assert!(total > 0.0, "the mix must have a positive weight");
+
+        let mut rng = Rng::new(config.seed);
+        let symbols = (0..config.symbols)
+            .map(|index| {
+                let bid = 100 + (rng.next_u64() % 50_000) as i64;
+                Symbol {
+                    name: symbol_name(index),
+                    bid,
+                    spread: 1 + (rng.next_u64() % 5) as i64,
+                    halted: false,
+                    operationally_halted: false,
+                    short_sale_price_test: false,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let start = config.start;
+        let mut pending = VecDeque::new();
+        for event_type in [
+            SystemEventType::StartOfMessages,
+            SystemEventType::StartOfSystemHours,
+        ] {
+            pending.push_back(system_event(event_type, start));
+        }
+        for symbol in &symbols {
+            pending.push_back(security_directory(symbol, start));
+            pending.push_back(Tops1_6Message::TradingStatus(TradingStatus {
+                status: TradingStatusType::Trading,
+                timestamp: start,
+                symbol: symbol.name.clone(),
+                reason: TradingStatusReason::Other(*b"    "),
+            }));
+        }
+        pending.push_back(system_event(SystemEventType::StartOfRegularHours, start));
+
+        Self {
+            rng,
+            symbols,
+            mix,
+            message_rate: config.message_rate,
+            max_segment_messages: config.max_segment_messages,
+            time: start,
+            pending,
+            last_trade: None,
+            next_trade_id: 1,
+            session_id: (config.seed as u32).wrapping_add(0x42870000),
+            next_sequence_number: 1,
+            stream_offset: 0,
+        }
+    }
+
+    /// The time of the last segment
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// Generates the next segment
+    pub fn next_segment(&mut self) -> SimulatedSegment {
+        let count = if self.pending.is_empty() {
+            // The messages of a segment are sent at once
+            let count = 1 + (self.rng.next_u64() % self.max_segment_messages as u64) as usize;
+            self.time += self.rng.exponential(self.message_rate / count as f64);
+            for _ in 0..count {
+                let message = self.message();
+                self.pending.push_back(message);
+            }
+            count
+        } else {
+            self.pending.len().min(self.max_segment_messages)
+        };
+        let messages = self.pending.drain(..count).collect();
+        self.segment(messages)
+    }
+
+    /// Ends the feed, returning the segment of the system events of the end of the trading day
+    pub fn finish(mut self) -> SimulatedSegment {
+        let messages = [
+            SystemEventType::EndOfRegularHours,
+            SystemEventType::EndOfSystemHours,
+            SystemEventType::EndOfMessages,
+        ]
+        .map(|event_type| system_event(event_type, self.time))
+        .to_vec();
+        self.segment(messages)
+    }
+
+    fn segment(&mut self, messages: Vec<Tops1_6Message<String>>) -> SimulatedSegment {
+        let segment = SimulatedSegment {
+            session_id: self.session_id,
+            send_time: self.time,
+            first_message_sequence_no: self.next_sequence_number,
+            stream_offset: self.stream_offset,
+            messages,
+        };
+        self.next_sequence_number += segment.messages.len() as i64;
+        self.stream_offset += segment
+            .messages
+            .iter()
+            // Generated messages can all be encoded
+            .map(|message| {
+                message
+                    .encode()
+                    .map_or(0, |encoded| encoded.len() as i64 + 2)
+            })
+            .sum::<i64>();
+        segment
+    }
+
+    // A message of a kind drawn from the mix, about a symbol drawn with a skewed distribution
+    fn message(&mut self) -> Tops1_6Message<String> {
+        let total = self.mix.last().map_or(0.0, |&(_, total)| total);
+        let draw = self.rng.next_f64() * total;
+        let kind = self
+            .mix
+            .iter()
+            .find(|&&(_, cumulative)| draw < cumulative)
+            .or(self.mix.last())
+            .map(|&(kind, _)| kind)
+            .expect("the mix isn't empty");
+
+        // Log-uniform, so that the first symbols are the most active
+        let count = self.symbols.len() as f64;
+        let index =
+            (((count + 1.0).powf(self.rng.next_f64()) - 1.0) as usize).min(self.symbols.len() - 1);
+        let rng = &mut self.rng;
+        let symbol = &mut self.symbols[index];
+        let timestamp = self.time;
+
+        match kind {
+            MessageKind::SecurityDirectory => security_directory(symbol, timestamp),
+            MessageKind::TradingStatus => {
+                symbol.halted = !symbol.halted;
+                let (status, reason) = if symbol.halted {
+                    (
+                        TradingStatusType::Halted,
+                        TradingStatusReason::HaltNewsPending,
+                    )
+                } else {
+                    (
+                        TradingStatusType::Trading,
+                        TradingStatusReason::Other(*b"    "),
+                    )
+                };
+                Tops1_6Message::TradingStatus(TradingStatus {
+                    status,
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                    reason,
+                })
+            }
+            MessageKind::RetailLiquidityIndicator => {
+                Tops1_6Message::RetailLiquidityIndicator(RetailLiquidityIndicator {
+                    indicator: match rng.next_u64() % 4 {
+                        0 => RetailLiquidityIndicatorType::NotApplicable,
+                        1 => RetailLiquidityIndicatorType::BuyInterest,
+                        2 => RetailLiquidityIndicatorType::SellInterest,
+                        _ => RetailLiquidityIndicatorType::BuyAndSellInterest,
+                    },
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                })
+            }
+            MessageKind::OperationalHaltStatus => {
+                symbol.operationally_halted = !symbol.operationally_halted;
+                Tops1_6Message::OperationalHaltStatus(OperationalHaltStatus {
+                    status: if symbol.operationally_halted {
+                        OperationalHaltStatusType::Halted
+                    } else {
+                        OperationalHaltStatusType::NotHalted
+                    },
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                })
+            }
+            MessageKind::ShortSalePriceTestStatus => {
+                symbol.short_sale_price_test = !symbol.short_sale_price_test;
+                Tops1_6Message::ShortSalePriceTestStatus(ShortSalePriceTestStatus {
+                    in_effect: symbol.short_sale_price_test,
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                    detail: if symbol.short_sale_price_test {
+                        ShortSalePriceTestDetail::Activated
+                    } else {
+                        ShortSalePriceTestDetail::Deactivated
+                    },
+                })
+            }
+            MessageKind::QuoteUpdate => {
+                // A step of the random walk, keeping the spread between 1 and 5 cents
+                symbol.bid = (symbol.bid + (rng.next_u64() % 3) as i64 - 1).max(1);
+                symbol.spread = (symbol.spread + (rng.next_u64() % 3) as i64 - 1).clamp(1, 5);
+                let mut size = || 100 * (1 + (rng.next_u64() % 10) as u32);
+                Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                    available: !symbol.halted,
+                    market_session: MarketSession::Regular,
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                    bid_size: size(),
+                    bid_price: dollars(symbol.bid),
+                    ask_size: size(),
+                    ask_price: dollars(symbol.bid + symbol.spread),
+                })
+            }
+            MessageKind::TradeReport => {
+                let odd_lot = rng.next_u64().is_multiple_of(5);
+                let size = if odd_lot {
+                    1 + (rng.next_u64() % 99) as u32
+                } else {
+                    100 * (1 + (rng.next_u64() % 5) as u32)
+                };
+                let cents = symbol.bid + (rng.next_u64() % 2) as i64 * symbol.spread;
+                let trade = TradeReport {
+                    sale_condition: SaleCondition {
+                        intermarket_sweep: rng.next_u64().is_multiple_of(10),
+                        extended_hours: false,
+                        odd_lot,
+                        trade_through_exempt: false,
+                        single_price: false,
+                    },
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                    size,
+                    price: dollars(cents),
+                    id: self.next_trade_id,
+                };
+                self.next_trade_id += 1;
+                self.last_trade = Some(trade.clone());
+                Tops1_6Message::TradeReport(trade)
+            }
+            MessageKind::OfficialPrice => Tops1_6Message::OfficialPrice(OfficialPrice {
+                price_type: if rng.next_u64() & 1 == 0 {
+                    OfficialPriceType::OpeningPrice
+                } else {
+                    OfficialPriceType::ClosingPrice
+                },
+                timestamp,
+                symbol: symbol.name.clone(),
+                official_price: dollars(symbol.bid),
+            }),
+            MessageKind::TradeBreak => match self.last_trade.take() {
+                Some(trade) => Tops1_6Message::TradeBreak(TradeBreak {
+                    sale_condition: trade.sale_condition,
+                    timestamp,
+                    symbol: trade.symbol,
+                    size: trade.size,
+                    price: trade.price,
+                    id: trade.id,
+                }),
+                // Nothing to break yet
+                None => security_directory(symbol, timestamp),
+            },
+            MessageKind::AuctionInformation => {
+                let reference_price = dollars(symbol.bid);
+                let imbalance_shares = 100 * (rng.next_u64() % 50) as u32;
+                Tops1_6Message::AuctionInformation(AuctionInformation {
+                    auction_type: AuctionType::Closing,
+                    timestamp,
+                    symbol: symbol.name.clone(),
+                    paired_shares: 100 * (1 + (rng.next_u64() % 1000) as u32),
+                    reference_price,
+                    indicative_clearing_price: reference_price,
+                    imbalance_shares,
+                    imbalance_side: if imbalance_shares == 0 {
+                        ImbalanceSide::NoImbalance
+                    } else {
+                        ImbalanceSide::Buy
+                    },
+                    extension_number: 0,
+                    scheduled_auction_time: timestamp + TimeDelta::minutes(10),
+                    auction_book_clearing_price: reference_price,
+                    collar_reference_price: reference_price,
+                    lower_auction_collar: price(reference_price * 0.9),
+                    upper_auction_collar: price(reference_price * 1.1),
+                })
+            }
+            _ => unreachable!("the mix only has mixable kinds"),
+        }
+    }
+}
+
+/// Writes a corpus to a HIST file until it's at least some size (in bytes), ending it with the system events of the end
+/// of the trading day, and returns its size
+///
+/// Segments are captured 20 microseconds after being sent. The size excludes the header of the capture, as it depends
+/// on the format of the capture.
+pub fn write_corpus<W: Write>(
+    writer: &mut HistWriter<W>,
+    config: CorpusConfig,
+    size: u64,
+) -> io::Result<u64> {
+    let mut corpus = Corpus::new(config);
+    let mut written = 0;
+    while written < size {
+        written += write_segment(writer, &corpus.next_segment())?;
+    }
+    written += write_segment(writer, &corpus.finish())?;
+    Ok(written)
+}
+
+fn write_segment<W: Write>(
+    writer: &mut HistWriter<W>,
+    segment: &SimulatedSegment,
+) -> io::Result<u64> {
+    let payload = segment.encode()?;
+    writer.write_payload(segment.send_time + TimeDelta::microseconds(20), &payload)?;
+    // The Ethernet, IPv4 and UDP headers, and the header of the pcap record
+    Ok(payload.len() as u64 + 42 + 16)
+}
+
+fn system_event(event_type: SystemEventType, timestamp: DateTime<Utc>) -> Tops1_6Message<String> {
+    Tops1_6Message::SystemEvent(SystemEvent {
+        event_type,
+        timestamp,
+    })
+}
+
+fn security_directory(symbol: &Symbol, timestamp: DateTime<Utc>) -> Tops1_6Message<String> {
+    Tops1_6Message::SecurityDirectory(SecurityDirectory {
+        flags: SecurityDirectoryFlags {
+            test_security: false,
+            when_issued: false,
+            etp: false,
+        },
+        timestamp,
+        symbol: symbol.name.clone(),
+        round_lot_size: 100,
+        adjusted_poc_price: dollars(symbol.bid),
+        luld_tier: if symbol.bid >= 100 {
+            LuldTier::Tier1
+        } else {
+            LuldTier::Tier2
+        },
+    })
+}
+
+// A, B, ..., Z, AA, AB, ...
+fn symbol_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("symbols are ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hist::HistReader,
+        sequence::{SequenceTracker, Sequencing},
+    };
+
+    use super::*;
+
+    #[test]
+    fn corpus() {
+        assert_eq!(
+            [0, 25, 26, 701, 702].map(symbol_name),
+            ["A", "Z", "AA", "ZZ", "AAA"]
+        );
+
+        let config = CorpusConfig {
+            symbols: 100,
+            ..CorpusConfig::default()
+        };
+        let write = || {
+            let mut writer = HistWriter::new(Vec::new()).unwrap();
+            let size = write_corpus(&mut writer, config.clone(), 1_000_000).unwrap();
+            let capture = writer.into_inner();
+            // The size of the capture, without its header
+            assert_eq!(size, capture.len() as u64 - 24);
+            assert!(size >= 1_000_000);
+            capture
+        };
+        let capture = write();
+        assert_eq!(write(), capture);
+
+        let mut reader = HistReader::new(&capture[..]).unwrap();
+        let mut tracker = SequenceTracker::new();
+        while let Some(captured) = reader.next_segment().unwrap() {
+            assert!((1..=4).contains(&captured.segment.messages.len()));
+            assert_eq!(tracker.update(&captured.segment), Sequencing::InOrder);
+        }
+
+        let messages = HistReader::new(&capture[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let count = |kind| {
+            messages
+                .iter()
+                .filter(|message| message.kind() == kind)
+                .count() as f64
+        };
+        let total = messages.len() as f64;
+        assert!((0.88..0.92).contains(&(count(MessageKind::QuoteUpdate) / total)));
+        assert!((0.07..0.09).contains(&(count(MessageKind::TradeReport) / total)));
+        assert!(count(MessageKind::TradeBreak) > 0.0);
+        assert_eq!(count(MessageKind::SystemEvent), 6.0);
+        assert!(messages
+            .windows(2)
+            .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+    }
+
+    #[test]
+    #[should_panic(expected = "system_event messages can't be mixed")]
+    fn unmixable_kinds() {
+        Corpus::new(CorpusConfig {
+            mix: vec![(MessageKind::SystemEvent, 1.0)],
+            ..CorpusConfig::default()
+        });
+    }
+}
@@ -0,0 +1,446 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    combinator::{all_consuming, map, map_res},
+    error::Error,
+    number::complete::{le_u32, u8},
+    IResult, Parser as _,
+};
+
+use crate::{
+    filter::MessageKind,
+    tops::{
+        auction_information, official_price, operational_halt_status, security_directory,
+        short_sale_price_test_status, system_event, trade_break, trade_report, trading_status,
+        AuctionInformation, OfficialPrice, OperationalHaltStatus, SecurityDirectory,
+        ShortSalePriceTestStatus, SystemEvent, TradeBreak, TradeReport, TradingStatus,
+    },
+    utils::{self, char_code_enum, price, WithRaw},
+};
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum SecurityEventType {
+        OpeningProcessComplete = b'O',
+        ClosingProcessComplete = b'C',
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SecurityEvent<S> {
+    pub event_type: SecurityEventType,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+}
+
+fn security_event<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], SecurityEvent<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x45]).parse(input)?;
+    let (input, event_type) = map_res(u8, SecurityEventType::try_from).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+
+    Ok((
+        input,
+        SecurityEvent {
+            event_type,
+            timestamp,
+            symbol,
+        },
+    ))
+}
+
+impl<S> SecurityEvent<S> {
+    /// Parses a Security Event message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        security_event(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> SecurityEvent<T> {
+        SecurityEvent {
+            event_type: self.event_type,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+        }
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for SecurityEvent<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(security_event)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum Side {
+        Buy = b'8',
+        Sell = b'5',
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PriceLevelUpdate<S> {
+    pub side: Side,
+    /// Whether this update completes an order book event. Updates belonging to an event which is still being
+    /// processed leave the book in a transitional state, and should be applied atomically along with the rest of the
+    /// event.
+    pub event_processing_complete: bool,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    /// The aggregated size at the price level, or zero if the price level was removed
+    pub size: u32,
+    pub price: f64,
+}
+
+fn price_level_update<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], PriceLevelUpdate<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, side) = map_res(u8, Side::try_from).parse(input)?;
+    let (input, event_flags) = alt((tag([0x00]), tag([0x01]))).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, size) = le_u32.parse(input)?;
+    let (input, price) = price.parse(input)?;
+
+    Ok((
+        input,
+        PriceLevelUpdate {
+            side,
+            event_processing_complete: event_flags == [0x01],
+            timestamp,
+            symbol,
+            size,
+            price,
+        },
+    ))
+}
+
+impl<S> PriceLevelUpdate<S> {
+    /// Parses a Price Level Update message (of either side), returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        price_level_update(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> PriceLevelUpdate<T> {
+        PriceLevelUpdate {
+            side: self.side,
+            event_processing_complete: self.event_processing_complete,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            size: self.size,
+            price: self.price,
+        }
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for PriceLevelUpdate<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(price_level_update)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Deep1_0Message<S> {
+    SystemEvent(SystemEvent),
+    SecurityDirectory(SecurityDirectory<S>),
+    TradingStatus(TradingStatus<S>),
+    OperationalHaltStatus(OperationalHaltStatus<S>),
+    ShortSalePriceTestStatus(ShortSalePriceTestStatus<S>),
+    SecurityEvent(SecurityEvent<S>),
+    PriceLevelUpdate(PriceLevelUpdate<S>),
+    TradeReport(TradeReport<S>),
+    OfficialPrice(OfficialPrice<S>),
+    TradeBreak(TradeBreak<S>),
+    AuctionInformation(AuctionInformation<S>),
+}
+
+pub fn deep_1_0_message<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], Deep1_0Message<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    alt((
+        map(system_event, Deep1_0Message::SystemEvent),
+        map(security_directory::<S>, Deep1_0Message::SecurityDirectory),
+        map(trading_status::<S>, Deep1_0Message::TradingStatus),
+        map(
+            operational_halt_status::<S>,
+            Deep1_0Message::OperationalHaltStatus,
+        ),
+        map(
+            short_sale_price_test_status::<S>,
+            Deep1_0Message::ShortSalePriceTestStatus,
+        ),
+        map(security_event::<S>, Deep1_0Message::SecurityEvent),
+        map(price_level_update::<S>, Deep1_0Message::PriceLevelUpdate),
+        map(trade_report::<S>, Deep1_0Message::TradeReport),
+        map(official_price::<S>, Deep1_0Message::OfficialPrice),
+        map(trade_break::<S>, Deep1_0Message::TradeBreak),
+        map(auction_information::<S>, Deep1_0Message::AuctionInformation),
+    ))
+    .parse(input)
+}
+
+/// Parses a DEEP 1.0 message like [`deep_1_0_message`], keeping a reference to the bytes it was decoded from
+pub fn deep_1_0_message_with_raw<'a, S>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], WithRaw<'a, Deep1_0Message<S>>>
+where
+    S: TryFrom<&'a str>,
+{
+    utils::with_raw(deep_1_0_message).parse(input)
+}
+
+impl<S> Deep1_0Message<S> {
+    /// The timestamp of the message, always known as every DEEP 1.0 message is decoded
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Deep1_0Message::SystemEvent(message) => Some(message.timestamp),
+            Deep1_0Message::SecurityDirectory(message) => Some(message.timestamp),
+            Deep1_0Message::TradingStatus(message) => Some(message.timestamp),
+            Deep1_0Message::OperationalHaltStatus(message) => Some(message.timestamp),
+            Deep1_0Message::ShortSalePriceTestStatus(message) => Some(message.timestamp),
+            Deep1_0Message::SecurityEvent(message) => Some(message.timestamp),
+            Deep1_0Message::PriceLevelUpdate(message) => Some(message.timestamp),
+            Deep1_0Message::TradeReport(message) => Some(message.timestamp),
+            Deep1_0Message::OfficialPrice(message) => Some(message.timestamp),
+            Deep1_0Message::TradeBreak(message) => Some(message.timestamp),
+            Deep1_0Message::AuctionInformation(message) => Some(message.timestamp),
+        }
+    }
+
+    /// The type of the message
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Deep1_0Message::SystemEvent(_) => MessageKind::SystemEvent,
+            Deep1_0Message::SecurityDirectory(_) => MessageKind::SecurityDirectory,
+            Deep1_0Message::TradingStatus(_) => MessageKind::TradingStatus,
+            Deep1_0Message::OperationalHaltStatus(_) => MessageKind::OperationalHaltStatus,
+            Deep1_0Message::ShortSalePriceTestStatus(_) => MessageKind::ShortSalePriceTestStatus,
+            Deep1_0Message::SecurityEvent(_) => MessageKind::SecurityEvent,
+            Deep1_0Message::PriceLevelUpdate(message) => match message.side {
+                Side::Buy => MessageKind::PriceLevelUpdateBuy,
+                Side::Sell => MessageKind::PriceLevelUpdateSell,
+            },
+            Deep1_0Message::TradeReport(_) => MessageKind::TradeReport,
+            Deep1_0Message::OfficialPrice(_) => MessageKind::OfficialPrice,
+            Deep1_0Message::TradeBreak(_) => MessageKind::TradeBreak,
+            Deep1_0Message::AuctionInformation(_) => MessageKind::AuctionInformation,
+        }
+    }
+
+    /// The symbol of the message, if it carries one and has been decoded
+    pub fn symbol(&self) -> Option<&S> {
+        match self {
+            Deep1_0Message::SecurityDirectory(message) => Some(&message.symbol),
+            Deep1_0Message::TradingStatus(message) => Some(&message.symbol),
+            Deep1_0Message::OperationalHaltStatus(message) => Some(&message.symbol),
+            Deep1_0Message::ShortSalePriceTestStatus(message) => Some(&message.symbol),
+            Deep1_0Message::SecurityEvent(message) => Some(&message.symbol),
+            Deep1_0Message::PriceLevelUpdate(message) => Some(&message.symbol),
+            Deep1_0Message::TradeReport(message) => Some(&message.symbol),
+            Deep1_0Message::OfficialPrice(message) => Some(&message.symbol),
+            Deep1_0Message::TradeBreak(message) => Some(&message.symbol),
+            Deep1_0Message::AuctionInformation(message) => Some(&message.symbol),
+            _ => None,
+        }
+    }
+
+    /// Rounds the prices of the message to the doubles nearest to their decimal values, see
+    /// [`PriceRepresentation`](crate::config::PriceRepresentation)
+    pub fn round_prices(&mut self) {
+        match self {
+            Deep1_0Message::SecurityDirectory(message) => {
+                utils::round_price(&mut message.adjusted_poc_price)
+            }
+            Deep1_0Message::PriceLevelUpdate(message) => utils::round_price(&mut message.price),
+            Deep1_0Message::TradeReport(message) => utils::round_price(&mut message.price),
+            Deep1_0Message::OfficialPrice(message) => {
+                utils::round_price(&mut message.official_price)
+            }
+            Deep1_0Message::TradeBreak(message) => utils::round_price(&mut message.price),
+            Deep1_0Message::AuctionInformation(message) => message.round_prices(),
+            _ => {}
+        }
+    }
+
+    /// Converts the symbol (if the message carries one) to another type
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> Deep1_0Message<T> {
+        match self {
+            Deep1_0Message::SystemEvent(message) => Deep1_0Message::SystemEvent(message),
+            Deep1_0Message::SecurityDirectory(message) => {
+                Deep1_0Message::SecurityDirectory(message.map_symbol(f))
+            }
+            Deep1_0Message::TradingStatus(message) => {
+                Deep1_0Message::TradingStatus(message.map_symbol(f))
+            }
+            Deep1_0Message::OperationalHaltStatus(message) => {
+                Deep1_0Message::OperationalHaltStatus(message.map_symbol(f))
+            }
+            Deep1_0Message::ShortSalePriceTestStatus(message) => {
+                Deep1_0Message::ShortSalePriceTestStatus(message.map_symbol(f))
+            }
+            Deep1_0Message::SecurityEvent(message) => {
+                Deep1_0Message::SecurityEvent(message.map_symbol(f))
+            }
+            Deep1_0Message::PriceLevelUpdate(message) => {
+                Deep1_0Message::PriceLevelUpdate(message.map_symbol(f))
+            }
+            Deep1_0Message::TradeReport(message) => {
+                Deep1_0Message::TradeReport(message.map_symbol(f))
+            }
+            Deep1_0Message::OfficialPrice(message) => {
+                Deep1_0Message::OfficialPrice(message.map_symbol(f))
+            }
+            Deep1_0Message::TradeBreak(message) => {
+                Deep1_0Message::TradeBreak(message.map_symbol(f))
+            }
+            Deep1_0Message::AuctionInformation(message) => {
+                Deep1_0Message::AuctionInformation(message.map_symbol(f))
+            }
+        }
+    }
+}
+
+impl Deep1_0Message<Cow<'_, str>> {
+    /// Detaches the message from the buffer it was parsed from, allocating only if the symbol is still borrowed
+    pub fn into_owned(self) -> Deep1_0Message<Cow<'static, str>> {
+        self.map_symbol(|symbol| Cow::Owned(symbol.into_owned()))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for Deep1_0Message<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(deep_1_0_message)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::assert_matches;
+
+    use float_eq::assert_float_eq;
+
+    use crate::spec;
+
+    use super::*;
+
+    #[test]
+    fn price_level_update_example() {
+        let input = spec::PRICE_LEVEL_UPDATE;
+        let result = deep_1_0_message::<String>(&input).unwrap();
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Deep1_0Message::PriceLevelUpdate(PriceLevelUpdate {
+                    side: Side::Buy,
+                    event_processing_complete: true,
+                    timestamp: _,
+                    symbol: _,
+                    size: 9700,
+                    price: _,
+                })
+            )
+        );
+
+        if let Deep1_0Message::PriceLevelUpdate(inner_result) = result.1 {
+            assert_eq!(inner_result.symbol, "ZIEXT");
+            assert_eq!(
+                inner_result.timestamp,
+                DateTime::from_timestamp_nanos(1471980632572715948)
+            );
+            assert_float_eq!(inner_result.price, 99.05, ulps <= 5);
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn sell_side_in_progress_update() {
+        let input: [u8; 30] = [
+            0x35, 0x00, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00, 0xEC, 0x1D, 0x0F, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ];
+        let result = PriceLevelUpdate::<&str>::try_from(&input[..]).unwrap();
+
+        assert_matches!(
+            result,
+            PriceLevelUpdate {
+                side: Side::Sell,
+                event_processing_complete: false,
+                symbol: "ZIEXT",
+                size: 0,
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn security_event_example() {
+        let input = spec::SECURITY_EVENT;
+        let result = deep_1_0_message::<String>(&input).unwrap();
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Deep1_0Message::SecurityEvent(SecurityEvent {
+                    event_type: SecurityEventType::OpeningProcessComplete,
+                    ..
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn shared_message_types() {
+        let input: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+        let result = Deep1_0Message::<String>::try_from(&input[..]).unwrap();
+
+        assert_matches!(
+            result,
+            Deep1_0Message::SystemEvent(SystemEvent {
+                event_type: crate::tops::SystemEventType::EndOfSystemHours,
+                ..
+            })
+        );
+    }
+}
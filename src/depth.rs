@@ -0,0 +1,472 @@
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    book::{BookBuilder, BookSnapshot, PriceLevel},
+    deep::{Deep1_0Message, Side},
+    utils::{key_price, price_key},
+};
+
+/// The books of all symbols of a depth feed, whether it publishes aggregated price levels (DEEP) or individual orders
+/// (DEEP+), so that code consuming books works with either
+pub trait DepthFeed<S> {
+    /// The messages the books are built from
+    type Message;
+
+    /// Feeds a message, returning whether a book changed. Messages which don't affect books are ignored.
+    fn update(&mut self, message: &Self::Message) -> bool;
+
+    /// The symbols which have a book, in no particular order
+    fn symbols(&self) -> Vec<&S>;
+
+    /// Returns (at most) the best `depth` levels of each side of the book of a symbol, if it has one
+    fn snapshot(&self, symbol: &str, depth: usize) -> Option<BookSnapshot>;
+
+    fn best_bid(&self, symbol: &str) -> Option<PriceLevel> {
+        self.snapshot(symbol, 1)?.bids.first().copied()
+    }
+
+    fn best_ask(&self, symbol: &str) -> Option<PriceLevel> {
+        self.snapshot(symbol, 1)?.asks.first().copied()
+    }
+
+    /// The timestamp of the message which last changed the book of a symbol
+    fn last_update(&self, symbol: &str) -> Option<DateTime<Utc>>;
+}
+
+impl<S> DepthFeed<S> for BookBuilder<S>
+where
+    S: Eq + Hash + Clone + Borrow<str>,
+{
+    type Message = Deep1_0Message<S>;
+
+    fn update(&mut self, message: &Deep1_0Message<S>) -> bool {
+        BookBuilder::update(self, message)
+    }
+
+    fn symbols(&self) -> Vec<&S> {
+        self.books().map(|(symbol, _)| symbol).collect()
+    }
+
+    fn snapshot(&self, symbol: &str, depth: usize) -> Option<BookSnapshot> {
+        Some(self.book(symbol)?.snapshot(depth))
+    }
+
+    fn last_update(&self, symbol: &str) -> Option<DateTime<Utc>> {
+        self.book(symbol)?.last_update()
+    }
+}
+
+/// A change to the orders resting on an order-level (DEEP+) feed
+///
+/// DEEP+ messages aren't decoded by this crate: these are the events a decoder of the feed produces, which
+/// [`OrderBookBuilder`] applies.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderEvent<S> {
+    Add {
+        timestamp: DateTime<Utc>,
+        symbol: S,
+        order_id: u64,
+        side: Side,
+        price: f64,
+        size: u32,
+    },
+    /// A change of the price or size of an order, which loses its priority
+    Modify {
+        timestamp: DateTime<Utc>,
+        order_id: u64,
+        price: f64,
+        size: u32,
+    },
+    Delete {
+        timestamp: DateTime<Utc>,
+        order_id: u64,
+    },
+    /// A (partial) execution of an order
+    Execute {
+        timestamp: DateTime<Utc>,
+        order_id: u64,
+        size: u32,
+    },
+    /// Removes every order of a symbol
+    Clear { timestamp: DateTime<Utc>, symbol: S },
+}
+
+#[derive(Clone, Debug)]
+struct Order<S> {
+    symbol: S,
+    side: Side,
+    price: i64,
+    size: u32,
+}
+
+/// The total size and number of the orders at a price
+#[derive(Clone, Copy, Debug, Default)]
+struct Level {
+    size: u64,
+    orders: u32,
+}
+
+/// An order-level book of a single symbol, aggregated by price
+#[derive(Clone, Debug, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<i64, Level>,
+    asks: BTreeMap<i64, Level>,
+    last_update: Option<DateTime<Utc>>,
+}
+
+impl OrderBook {
+    fn levels(&mut self, side: Side) -> &mut BTreeMap<i64, Level> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn add(&mut self, side: Side, price: i64, size: u32) {
+        let level = self.levels(side).entry(price).or_default();
+        level.size += u64::from(size);
+        level.orders += 1;
+    }
+
+    fn remove(&mut self, side: Side, price: i64, size: u32, order: bool) {
+        let levels = self.levels(side);
+        if let Some(level) = levels.get_mut(&price) {
+            level.size = level.size.saturating_sub(size.into());
+            if order {
+                level.orders = level.orders.saturating_sub(1);
+            }
+            if level.orders == 0 {
+                levels.remove(&price);
+            }
+        }
+    }
+
+    fn level(price: i64, level: &Level) -> PriceLevel {
+        PriceLevel {
+            price: key_price(price),
+            size: level.size.try_into().unwrap_or(u32::MAX),
+        }
+    }
+
+    /// Iterates over the bid levels, from the highest price down
+    pub fn bids(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.bids
+            .iter()
+            .rev()
+            .map(|(&price, level)| Self::level(price, level))
+    }
+
+    /// Iterates over the ask levels, from the lowest price up
+    pub fn asks(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.asks
+            .iter()
+            .map(|(&price, level)| Self::level(price, level))
+    }
+
+    /// The number of orders resting at a price
+    pub fn orders_at(&self, side: Side, price: f64) -> u32 {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels
+            .get(&price_key(price))
+            .map_or(0, |level| level.orders)
+    }
+
+    /// Returns (at most) the best `depth` levels of each side
+    pub fn snapshot(&self, depth: usize) -> BookSnapshot {
+        BookSnapshot {
+            bids: self.bids().take(depth).collect(),
+            asks: self.asks().take(depth).collect(),
+        }
+    }
+
+    /// The timestamp of the event which last changed the book
+    pub fn last_update(&self) -> Option<DateTime<Utc>> {
+        self.last_update
+    }
+}
+
+/// Maintains the books of all symbols from the events of an order-level (DEEP+) feed
+#[derive(Clone, Debug)]
+pub struct OrderBookBuilder<S> {
+    orders: HashMap<u64, Order<S>>,
+    books: HashMap<S, OrderBook>,
+}
+
+impl<S> Default for OrderBookBuilder<S> {
+    fn default() -> Self {
+        Self {
+            orders: HashMap::new(),
+            books: HashMap::new(),
+        }
+    }
+}
+
+impl<S> OrderBookBuilder<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies an event, returning whether a book changed. Events about unknown orders (e.g. added before the feed was
+    /// joined) are ignored.
+    pub fn apply(&mut self, event: &OrderEvent<S>) -> bool {
+        match event {
+            OrderEvent::Add {
+                timestamp,
+                symbol,
+                order_id,
+                side,
+                price,
+                size,
+            } => {
+                let price = price_key(*price);
+                let book = self.books.entry(symbol.clone()).or_default();
+                book.add(*side, price, *size);
+                book.last_update = Some(*timestamp);
+                let order = Order {
+                    symbol: symbol.clone(),
+                    side: *side,
+                    price,
+                    size: *size,
+                };
+                // An order added again replaces the previous one
+                if let Some(previous) = self.orders.insert(*order_id, order) {
+                    if let Some(book) = self.books.get_mut(&previous.symbol) {
+                        book.remove(previous.side, previous.price, previous.size, true);
+                    }
+                }
+                true
+            }
+            OrderEvent::Modify {
+                timestamp,
+                order_id,
+                price,
+                size,
+            } => {
+                let Some(order) = self.orders.get_mut(order_id) else {
+                    return false;
+                };
+                let Some(book) = self.books.get_mut(&order.symbol) else {
+                    return false;
+                };
+                book.remove(order.side, order.price, order.size, true);
+                order.price = price_key(*price);
+                order.size = *size;
+                book.add(order.side, order.price, order.size);
+                book.last_update = Some(*timestamp);
+                true
+            }
+            OrderEvent::Delete {
+                timestamp,
+                order_id,
+            } => {
+                let Some(order) = self.orders.remove(order_id) else {
+                    return false;
+                };
+                let Some(book) = self.books.get_mut(&order.symbol) else {
+                    return false;
+                };
+                book.remove(order.side, order.price, order.size, true);
+                book.last_update = Some(*timestamp);
+                true
+            }
+            OrderEvent::Execute {
+                timestamp,
+                order_id,
+                size,
+            } => {
+                let Some(order) = self.orders.get_mut(order_id) else {
+                    return false;
+                };
+                let Some(book) = self.books.get_mut(&order.symbol) else {
+                    return false;
+                };
+                let executed = (*size).min(order.size);
+                order.size -= executed;
+                let filled = order.size == 0;
+                book.remove(order.side, order.price, executed, filled);
+                book.last_update = Some(*timestamp);
+                if filled {
+                    self.orders.remove(order_id);
+                }
+                true
+            }
+            OrderEvent::Clear { timestamp, symbol } => {
+                self.orders.retain(|_, order| order.symbol != *symbol);
+                let book = self.books.entry(symbol.clone()).or_default();
+                book.bids.clear();
+                book.asks.clear();
+                book.last_update = Some(*timestamp);
+                true
+            }
+        }
+    }
+
+    pub fn book<Q>(&self, symbol: &Q) -> Option<&OrderBook>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.books.get(symbol)
+    }
+
+    pub fn books(&self) -> impl Iterator<Item = (&S, &OrderBook)> {
+        self.books.iter()
+    }
+
+    /// The number of orders resting on all books
+    pub fn orders(&self) -> usize {
+        self.orders.len()
+    }
+}
+
+impl<S> DepthFeed<S> for OrderBookBuilder<S>
+where
+    S: Eq + Hash + Clone + Borrow<str>,
+{
+    type Message = OrderEvent<S>;
+
+    fn update(&mut self, event: &OrderEvent<S>) -> bool {
+        self.apply(event)
+    }
+
+    fn symbols(&self) -> Vec<&S> {
+        self.books.keys().collect()
+    }
+
+    fn snapshot(&self, symbol: &str, depth: usize) -> Option<BookSnapshot> {
+        Some(self.book(symbol)?.snapshot(depth))
+    }
+
+    fn last_update(&self, symbol: &str) -> Option<DateTime<Utc>> {
+        self.book(symbol)?.last_update()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deep::PriceLevelUpdate;
+
+    use super::*;
+
+    fn timestamp() -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(1471980632572715948)
+    }
+
+    fn level(side: Side, price: f64, size: u32) -> Deep1_0Message<String> {
+        Deep1_0Message::PriceLevelUpdate(PriceLevelUpdate {
+            side,
+            event_processing_complete: true,
+            timestamp: timestamp(),
+            symbol: "ZIEXT".to_string(),
+            size,
+            price,
+        })
+    }
+
+    fn add(order_id: u64, side: Side, price: f64, size: u32) -> OrderEvent<String> {
+        OrderEvent::Add {
+            timestamp: timestamp(),
+            symbol: "ZIEXT".to_string(),
+            order_id,
+            side,
+            price,
+            size,
+        }
+    }
+
+    /// Book-consuming code written once for both kinds of feeds
+    fn feed<F: DepthFeed<String>>(mut depth: F, messages: &[F::Message]) -> BookSnapshot {
+        for message in messages {
+            assert!(depth.update(message));
+        }
+        assert_eq!(depth.symbols(), [&"ZIEXT".to_string()]);
+        assert_eq!(depth.last_update("ZIEXT"), Some(timestamp()));
+        assert_eq!(
+            depth.best_bid("ZIEXT"),
+            Some(PriceLevel {
+                price: key_price(990500),
+                size: 300
+            })
+        );
+        depth.snapshot("ZIEXT", 5).unwrap()
+    }
+
+    #[test]
+    fn aggregated_and_order_level() {
+        let aggregated = feed(
+            BookBuilder::new(),
+            &[
+                level(Side::Buy, 99.05, 300),
+                level(Side::Buy, 99.04, 100),
+                level(Side::Sell, 99.07, 50),
+            ],
+        );
+
+        let mut orders = OrderBookBuilder::new();
+        // Fully executed and deleted orders leave the book
+        orders.apply(&add(9, Side::Sell, 99.06, 100));
+        orders.apply(&OrderEvent::Execute {
+            timestamp: timestamp(),
+            order_id: 9,
+            size: 100,
+        });
+        orders.apply(&add(10, Side::Buy, 99.03, 100));
+        orders.apply(&OrderEvent::Delete {
+            timestamp: timestamp(),
+            order_id: 10,
+        });
+        assert_eq!(orders.orders(), 0);
+        let order_level = feed(
+            orders,
+            &[
+                add(1, Side::Buy, 99.05, 100),
+                add(2, Side::Buy, 99.05, 200),
+                add(3, Side::Buy, 99.04, 300),
+                OrderEvent::Execute {
+                    timestamp: timestamp(),
+                    order_id: 3,
+                    size: 200,
+                },
+                add(4, Side::Sell, 99.08, 50),
+                OrderEvent::Modify {
+                    timestamp: timestamp(),
+                    order_id: 4,
+                    price: 99.07,
+                    size: 50,
+                },
+            ],
+        );
+        assert_eq!(order_level, aggregated);
+    }
+
+    #[test]
+    fn clear() {
+        let mut orders = OrderBookBuilder::new();
+        orders.apply(&add(1, Side::Buy, 99.05, 100));
+        orders.apply(&add(2, Side::Buy, 99.05, 200));
+        assert_eq!(orders.book("ZIEXT").unwrap().orders_at(Side::Buy, 99.05), 2);
+
+        assert!(orders.apply(&OrderEvent::Clear {
+            timestamp: timestamp(),
+            symbol: "ZIEXT".to_string(),
+        }));
+        assert_eq!(orders.orders(), 0);
+        assert_eq!(orders.best_bid("ZIEXT"), None);
+        assert!(!orders.apply(&OrderEvent::Delete {
+            timestamp: timestamp(),
+            order_id: 1,
+        }));
+    }
+}
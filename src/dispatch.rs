@@ -0,0 +1,261 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::{filter::MessageKinds, hist::Message};
+
+/// The messages a subscriber is interested in
+#[derive(Clone, Debug)]
+pub struct Subscription<S> {
+    symbols: Option<HashSet<S>>,
+    kinds: Option<MessageKinds>,
+}
+
+impl<S> Default for Subscription<S> {
+    fn default() -> Self {
+        Self {
+            symbols: None,
+            kinds: None,
+        }
+    }
+}
+
+impl<S> Subscription<S>
+where
+    S: Eq + Hash,
+{
+    /// Subscribes to every message
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only the messages of the given symbols, along with the messages without any symbol (e.g. system events)
+    pub fn symbols(mut self, symbols: impl IntoIterator<Item = S>) -> Self {
+        self.symbols
+            .get_or_insert_with(HashSet::new)
+            .extend(symbols);
+        self
+    }
+
+    /// Only the messages of the given types
+    pub fn kinds(mut self, kinds: impl Into<MessageKinds>) -> Self {
+        self.kinds = Some(kinds.into());
+        self
+    }
+}
+
+/// Identifies a subscriber of a [`Dispatcher`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriberId(usize);
+
+type Handler<'a, M> = Box<dyn FnMut(&M) + 'a>;
+
+struct Subscriber<'a, M> {
+    kinds: Option<MessageKinds>,
+    handler: Handler<'a, M>,
+}
+
+/// Routes each message to the subscribers interested in its symbol and type
+///
+/// Subscribers receive the messages in the order they're dispatched, and each message is delivered to its subscribers
+/// in the order they subscribed.
+pub struct Dispatcher<'a, M>
+where
+    M: Message,
+{
+    // Indexed by subscriber ID, with `None` for the unsubscribed ones
+    subscribers: Vec<Option<Subscriber<'a, M>>>,
+    by_symbol: HashMap<M::Symbol, Vec<usize>>,
+    any_symbol: Vec<usize>,
+}
+
+impl<M> Default for Dispatcher<'_, M>
+where
+    M: Message,
+{
+    fn default() -> Self {
+        Self {
+            subscribers: Vec::new(),
+            by_symbol: HashMap::new(),
+            any_symbol: Vec::new(),
+        }
+    }
+}
+
+impl<'a, M> Dispatcher<'a, M>
+where
+    M: Message,
+    M::Symbol: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(
+        &mut self,
+        subscription: Subscription<M::Symbol>,
+        handler: impl FnMut(&M) + 'a,
+    ) -> SubscriberId {
+        let id = self.subscribers.len();
+        self.subscribers.push(Some(Subscriber {
+            kinds: subscription.kinds,
+            handler: Box::new(handler),
+        }));
+
+        match subscription.symbols {
+            Some(symbols) => {
+                for symbol in symbols {
+                    self.by_symbol.entry(symbol).or_default().push(id);
+                }
+            }
+            None => self.any_symbol.push(id),
+        }
+
+        SubscriberId(id)
+    }
+
+    /// Stops delivering messages to a subscriber. Returns whether it was subscribed.
+    pub fn unsubscribe(&mut self, SubscriberId(id): SubscriberId) -> bool {
+        let Some(subscriber) = self.subscribers.get_mut(id) else {
+            return false;
+        };
+        if subscriber.take().is_none() {
+            return false;
+        }
+
+        self.any_symbol.retain(|other| *other != id);
+        self.by_symbol.retain(|_, ids| {
+            ids.retain(|other| *other != id);
+            !ids.is_empty()
+        });
+        true
+    }
+
+    fn deliver(subscriber: &mut Option<Subscriber<'a, M>>, message: &M) -> bool {
+        match subscriber {
+            Some(subscriber)
+                if subscriber
+                    .kinds
+                    .is_none_or(|kinds| kinds.contains(message.kind())) =>
+            {
+                (subscriber.handler)(message);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Delivers a message to the matching subscribers, returning how many received it
+    pub fn dispatch(&mut self, message: &M) -> usize {
+        let Some(symbol) = message.symbol() else {
+            return self
+                .subscribers
+                .iter_mut()
+                .map(|subscriber| Self::deliver(subscriber, message))
+                .filter(|delivered| *delivered)
+                .count();
+        };
+
+        let symbol_subscribers = self.by_symbol.get(symbol).map_or(&[][..], Vec::as_slice);
+        let (mut any, mut specific) = (
+            self.any_symbol.iter().peekable(),
+            symbol_subscribers.iter().peekable(),
+        );
+        let mut delivered = 0;
+        // Both lists are sorted by subscriber ID
+        loop {
+            let next = match (any.peek(), specific.peek()) {
+                (Some(a), Some(b)) if a < b => any.next(),
+                (Some(_), None) => any.next(),
+                _ => specific.next(),
+            };
+            let Some(&id) = next else {
+                break;
+            };
+            if Self::deliver(&mut self.subscribers[id], message) {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use chrono::DateTime;
+
+    use crate::{
+        filter::MessageKind,
+        fixtures,
+        tops::{SystemEvent, SystemEventType, Tops1_6Message},
+    };
+
+    use super::*;
+
+    fn trade(symbol: &str) -> Tops1_6Message<String> {
+        Tops1_6Message::TradeReport(fixtures::trade(symbol.to_string(), 0, 10.0, 100))
+    }
+
+    #[test]
+    fn routing() {
+        let received = RefCell::new(Vec::new());
+        let record = |name| {
+            let received = &received;
+            move |message: &Tops1_6Message<String>| {
+                received.borrow_mut().push(format!(
+                    "{name} {}",
+                    message.symbol().map_or("-", String::as_str)
+                ))
+            }
+        };
+
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.subscribe(
+            Subscription::all().symbols(["ZIEXT".to_string()]),
+            record("ziext"),
+        );
+        let trades = dispatcher.subscribe(
+            Subscription::all().kinds(
+                [MessageKind::TradeReport, MessageKind::SystemEvent]
+                    .into_iter()
+                    .collect::<MessageKinds>(),
+            ),
+            record("trades"),
+        );
+        dispatcher.subscribe(
+            Subscription::all()
+                .symbols(["ZVZZT", "ZIEXT"].map(String::from))
+                .kinds(MessageKind::QuoteUpdate),
+            record("quotes"),
+        );
+
+        assert_eq!(dispatcher.dispatch(&trade("ZIEXT")), 2);
+        assert_eq!(dispatcher.dispatch(&trade("ZVZZT")), 1);
+        assert_eq!(
+            dispatcher.dispatch(&Tops1_6Message::SystemEvent(SystemEvent {
+                event_type: SystemEventType::StartOfMessages,
+                timestamp: DateTime::from_timestamp_nanos(0),
+            })),
+            2
+        );
+        assert!(dispatcher.unsubscribe(trades));
+        assert!(!dispatcher.unsubscribe(trades));
+        assert_eq!(dispatcher.dispatch(&trade("ZIEXT")), 1);
+
+        drop(dispatcher);
+        assert_eq!(
+            received.into_inner(),
+            [
+                "ziext ZIEXT",
+                "trades ZIEXT",
+                "trades ZVZZT",
+                "ziext -",
+                "trades -",
+                "ziext ZIEXT",
+            ]
+        );
+    }
+}
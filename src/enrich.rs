@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+};
+
+use crate::export::{Record, ToRecord, Value};
+
+/// The fields added to the records by [`MetadataTable::enrich`], in order
+pub const METADATA_COLUMNS: [&str; 3] = ["sector", "primary_exchange", "lot_size"];
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {line}: {message}"),
+    )
+}
+
+/// Splits a CSV line into its fields, which may be quoted (with doubled quotes inside)
+fn split_csv(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => break,
+                    c => field.push(c),
+                }
+            }
+            if !matches!(chars.peek(), None | Some(',')) {
+                return None;
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                field.push(c);
+                chars.next();
+            }
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Some(fields);
+        }
+    }
+}
+
+/// The reference data of a symbol provided by the user, e.g. from a vendor or an internal security master
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolMetadata {
+    pub sector: Option<String>,
+    pub primary_exchange: Option<String>,
+    pub lot_size: Option<u32>,
+}
+
+impl SymbolMetadata {
+    /// The known fields, as named in [`METADATA_COLUMNS`]
+    pub fn to_record(&self) -> Record {
+        let mut record = Record::new();
+        if let Some(sector) = &self.sector {
+            record.push(("sector", Value::Text(sector.clone())));
+        }
+        if let Some(primary_exchange) = &self.primary_exchange {
+            record.push(("primary_exchange", Value::Text(primary_exchange.clone())));
+        }
+        if let Some(lot_size) = self.lot_size {
+            record.push(("lot_size", Value::Int(lot_size.into())));
+        }
+        record
+    }
+}
+
+/// Joins the exported records of messages with the metadata of their symbols, so that the exports carry the context
+/// needed to analyze them (e.g. to aggregate by sector)
+///
+/// Unlike [`ReferenceData`](crate::reference::ReferenceData), which is built from the Security Directory messages of
+/// the feed, the metadata comes from outside of it.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataTable {
+    symbols: HashMap<String, SymbolMetadata>,
+}
+
+impl MetadataTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the metadata of a symbol, replacing any previous metadata
+    pub fn insert(&mut self, symbol: impl Into<String>, metadata: SymbolMetadata) {
+        self.symbols.insert(symbol.into(), metadata);
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&SymbolMetadata> {
+        self.symbols.get(symbol)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Reads the metadata from a CSV file with a header, whose `symbol` column is required. The columns named in
+    /// [`METADATA_COLUMNS`] are read, in any order, and empty fields are missing values. Other columns are ignored.
+    pub fn read_csv<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        let header = lines
+            .next()
+            .transpose()?
+            .and_then(|header| split_csv(header.trim_end_matches('\r')))
+            .ok_or_else(|| invalid_data(1, "missing header"))?;
+        let column = |name: &str| header.iter().position(|column| column.trim() == name);
+        let symbol_column = column("symbol").ok_or_else(|| invalid_data(1, "no symbol column"))?;
+        let [sector_column, exchange_column, lot_size_column] = METADATA_COLUMNS.map(column);
+
+        let mut table = Self::new();
+        for (index, line) in lines.enumerate() {
+            let line_number = index + 2;
+            let line = line?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields = split_csv(line)
+                .filter(|fields| fields.len() == header.len())
+                .ok_or_else(|| invalid_data(line_number, "wrong number of fields"))?;
+            let field = |column: Option<usize>| {
+                column
+                    .map(|column| fields[column].trim())
+                    .filter(|field| !field.is_empty())
+            };
+
+            let symbol = field(Some(symbol_column))
+                .ok_or_else(|| invalid_data(line_number, "missing symbol"))?;
+            let metadata = SymbolMetadata {
+                sector: field(sector_column).map(str::to_string),
+                primary_exchange: field(exchange_column).map(str::to_string),
+                lot_size: field(lot_size_column)
+                    .map(|lot_size| {
+                        lot_size
+                            .parse()
+                            .map_err(|_| invalid_data(line_number, "invalid lot size"))
+                    })
+                    .transpose()?,
+            };
+            table.insert(symbol, metadata);
+        }
+        Ok(table)
+    }
+
+    /// Appends the metadata of the symbol of a record to it. Records without a symbol (e.g. of system events), or of a
+    /// symbol without metadata, are left as they are.
+    pub fn enrich(&self, record: &mut Record) {
+        let metadata = record
+            .iter()
+            .find_map(|(name, value)| match (*name, value) {
+                ("symbol", Value::Text(symbol)) => self.get(symbol),
+                _ => None,
+            });
+        if let Some(metadata) = metadata {
+            record.extend(metadata.to_record());
+        }
+    }
+
+    /// The record of a message, enriched with the metadata of its symbol
+    pub fn enriched(&self, message: &impl ToRecord) -> Record {
+        let mut record = message.to_record();
+        self.enrich(&mut record);
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hist::Message, spec, tops::Tops1_6Message};
+
+    use super::*;
+
+    #[test]
+    fn enrich() {
+        let csv = "symbol,name,sector,lot_size\r\n\
+                   ZIEXT,\"IEX Test, Inc.\",\"Test \"\"Securities\"\"\",100\r\n\
+                   ZVZZT,,,\r\n";
+        let table = MetadataTable::read_csv(csv.as_bytes()).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            table.get("ZIEXT"),
+            Some(&SymbolMetadata {
+                sector: Some("Test \"Securities\"".to_string()),
+                primary_exchange: None,
+                lot_size: Some(100),
+            })
+        );
+
+        let (_, trade) = Tops1_6Message::<String>::parse(&spec::TRADE_REPORT).unwrap();
+        let record = table.enriched(&trade);
+        assert_eq!(
+            record[record.len() - 2..],
+            [
+                ("sector", Value::Text("Test \"Securities\"".to_string())),
+                ("lot_size", Value::Int(100)),
+            ]
+        );
+
+        let (_, event) = Tops1_6Message::<String>::parse(&spec::SYSTEM_EVENT).unwrap();
+        assert_eq!(table.enriched(&event), event.to_record());
+
+        assert!(MetadataTable::read_csv("sector\nTech\n".as_bytes()).is_err());
+        assert!(MetadataTable::read_csv("symbol,lot_size\nSPY,lots\n".as_bytes()).is_err());
+        assert!(MetadataTable::read_csv("symbol,sector\nSPY,\"Tech\n".as_bytes()).is_err());
+    }
+}
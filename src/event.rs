@@ -0,0 +1,249 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::{Deep1_0Message, PriceLevelUpdate, SecurityEvent},
+    tops::{
+        AuctionInformation, OfficialPrice, OperationalHaltStatus, QuoteUpdate,
+        RetailLiquidityIndicator, SecurityDirectory, ShortSalePriceTestStatus, SystemEvent,
+        Tops1_6Message, TradeBreak, TradeReport, TradingStatus,
+    },
+};
+
+/// An event of a market data feed, regardless of the feed: engines written against these traits rather than the IEX
+/// messages can be fed by other venues
+pub trait MarketEvent {
+    fn timestamp(&self) -> DateTime<Utc>;
+
+    /// The symbol of the event, unless it concerns the whole market
+    fn symbol(&self) -> Option<&str>;
+
+    /// The event as a quote, if it is one
+    fn as_quote(&self) -> Option<&dyn QuoteEvent> {
+        None
+    }
+
+    /// The event as a trade, if it is one
+    fn as_trade(&self) -> Option<&dyn TradeEvent> {
+        None
+    }
+}
+
+/// An update of the best bid and offer of a symbol
+pub trait QuoteEvent: MarketEvent {
+    /// The best bid price and size, unless there's no bid
+    fn bid(&self) -> Option<(f64, u32)>;
+
+    /// The best ask price and size, unless there's no ask
+    fn ask(&self) -> Option<(f64, u32)>;
+
+    /// The mid price, if both sides are quoted
+    fn mid(&self) -> Option<f64> {
+        let ((bid, _), (ask, _)) = (self.bid()?, self.ask()?);
+        Some((bid + ask) / 2.0)
+    }
+}
+
+/// An execution
+pub trait TradeEvent: MarketEvent {
+    fn price(&self) -> f64;
+
+    fn size(&self) -> u32;
+
+    /// The identifier of the trade, if the feed has any, e.g. to match trade breaks
+    fn trade_id(&self) -> Option<i64> {
+        None
+    }
+
+    /// Whether the trade updates the last sale price, as regular trades during the regular hours do
+    fn is_last_sale_eligible(&self) -> bool {
+        true
+    }
+}
+
+impl MarketEvent for SystemEvent {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        None
+    }
+}
+
+macro_rules! symbol_event {
+    ($($message:ident),* $(,)?) => {
+        $(
+            impl<S: AsRef<str>> MarketEvent for $message<S> {
+                fn timestamp(&self) -> DateTime<Utc> {
+                    self.timestamp
+                }
+
+                fn symbol(&self) -> Option<&str> {
+                    Some(self.symbol.as_ref())
+                }
+            }
+        )*
+    };
+}
+
+symbol_event!(
+    SecurityDirectory,
+    TradingStatus,
+    RetailLiquidityIndicator,
+    OperationalHaltStatus,
+    ShortSalePriceTestStatus,
+    OfficialPrice,
+    TradeBreak,
+    AuctionInformation,
+    SecurityEvent,
+    PriceLevelUpdate,
+);
+
+impl<S: AsRef<str>> MarketEvent for QuoteUpdate<S> {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(self.symbol.as_ref())
+    }
+
+    fn as_quote(&self) -> Option<&dyn QuoteEvent> {
+        Some(self)
+    }
+}
+
+impl<S: AsRef<str>> QuoteEvent for QuoteUpdate<S> {
+    // A side without any quote has zero price and size
+    fn bid(&self) -> Option<(f64, u32)> {
+        (self.bid_size > 0).then_some((self.bid_price, self.bid_size))
+    }
+
+    fn ask(&self) -> Option<(f64, u32)> {
+        (self.ask_size > 0).then_some((self.ask_price, self.ask_size))
+    }
+}
+
+impl<S: AsRef<str>> MarketEvent for TradeReport<S> {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    fn symbol(&self) -> Option<&str> {
+        Some(self.symbol.as_ref())
+    }
+
+    fn as_trade(&self) -> Option<&dyn TradeEvent> {
+        Some(self)
+    }
+}
+
+impl<S: AsRef<str>> TradeEvent for TradeReport<S> {
+    fn price(&self) -> f64 {
+        self.price
+    }
+
+    fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn trade_id(&self) -> Option<i64> {
+        Some(self.id)
+    }
+
+    fn is_last_sale_eligible(&self) -> bool {
+        let condition = &self.sale_condition;
+        !(condition.extended_hours || condition.odd_lot)
+    }
+}
+
+impl<S: AsRef<str>> Tops1_6Message<S> {
+    /// The message as a feed-agnostic event
+    pub fn as_event(&self) -> &dyn MarketEvent {
+        match self {
+            Tops1_6Message::SystemEvent(message) => message,
+            Tops1_6Message::SecurityDirectory(message) => message,
+            Tops1_6Message::TradingStatus(message) => message,
+            Tops1_6Message::RetailLiquidityIndicator(message) => message,
+            Tops1_6Message::OperationalHaltStatus(message) => message,
+            Tops1_6Message::ShortSalePriceTestStatus(message) => message,
+            Tops1_6Message::QuoteUpdate(message) => message,
+            Tops1_6Message::TradeReport(message) => message,
+            Tops1_6Message::OfficialPrice(message) => message,
+            Tops1_6Message::TradeBreak(message) => message,
+            Tops1_6Message::AuctionInformation(message) => message,
+        }
+    }
+}
+
+impl<S: AsRef<str>> Deep1_0Message<S> {
+    /// The message as a feed-agnostic event
+    pub fn as_event(&self) -> &dyn MarketEvent {
+        match self {
+            Deep1_0Message::SystemEvent(message) => message,
+            Deep1_0Message::SecurityDirectory(message) => message,
+            Deep1_0Message::TradingStatus(message) => message,
+            Deep1_0Message::OperationalHaltStatus(message) => message,
+            Deep1_0Message::ShortSalePriceTestStatus(message) => message,
+            Deep1_0Message::SecurityEvent(message) => message,
+            Deep1_0Message::PriceLevelUpdate(message) => message,
+            Deep1_0Message::TradeReport(message) => message,
+            Deep1_0Message::OfficialPrice(message) => message,
+            Deep1_0Message::TradeBreak(message) => message,
+            Deep1_0Message::AuctionInformation(message) => message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hist::Message,
+        spec,
+        tops::{SystemEventType, Tops1_6Message},
+    };
+
+    use super::*;
+
+    /// An engine which only knows the traits
+    #[derive(Default)]
+    struct LastPrices {
+        mid: Option<f64>,
+        last_sale: Option<f64>,
+        events: usize,
+    }
+
+    impl LastPrices {
+        fn on_event(&mut self, event: &dyn MarketEvent) {
+            self.events += 1;
+            if let Some(quote) = event.as_quote() {
+                self.mid = quote.mid().or(self.mid);
+            }
+            if let Some(trade) = event
+                .as_trade()
+                .filter(|trade| trade.is_last_sale_eligible())
+            {
+                self.last_sale = Some(trade.price());
+            }
+        }
+    }
+
+    #[test]
+    fn events() {
+        let mut engine = LastPrices::default();
+        for message in [&spec::QUOTE_UPDATE[..], &spec::TRADE_REPORT[..]] {
+            let (_, message) = Tops1_6Message::<String>::parse(message).unwrap();
+            engine.on_event(message.as_event());
+        }
+        let event = SystemEvent {
+            event_type: SystemEventType::StartOfMessages,
+            timestamp: DateTime::from_timestamp_nanos(0),
+        };
+        engine.on_event(&event);
+        assert_eq!(event.symbol(), None);
+
+        assert_eq!(engine.events, 3);
+        assert!(engine.mid.is_some());
+        assert!(engine.last_sale.is_some());
+    }
+}
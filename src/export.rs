@@ -0,0 +1,755 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{
+    deep::{Deep1_0Message, PriceLevelUpdate, SecurityEvent, Side},
+    filter::MessageKind,
+    tops::{
+        AuctionInformation, MarketSession, OfficialPrice, OperationalHaltStatus, QuoteUpdate,
+        RetailLiquidityIndicator, SecurityDirectory, ShortSalePriceTestStatus, SystemEvent,
+        Tops1_6Message, TradeBreak, TradeReport, TradingStatus,
+    },
+};
+
+/// A field of an exported message
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Price(f64),
+    Time(DateTime<Utc>),
+    Text(String),
+}
+
+/// The type of the values of a column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    Int,
+    Price,
+    Time,
+    Text,
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Bool(_) => ValueType::Bool,
+            Value::Int(_) => ValueType::Int,
+            Value::Price(_) => ValueType::Price,
+            Value::Time(_) => ValueType::Time,
+            Value::Text(_) => ValueType::Text,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Int(value) => write!(f, "{value}"),
+            // Prices have 4 decimal digits on the wire
+            Value::Price(value) => write!(f, "{value:.4}"),
+            Value::Time(value) => {
+                write!(f, "{}", value.to_rfc3339_opts(SecondsFormat::Nanos, true))
+            }
+            Value::Text(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// The fields of a message, in order, starting with its kind, timestamp and symbol
+pub type Record = Vec<(&'static str, Value)>;
+
+/// Converts messages into flat records, for exporting them to other formats
+pub trait ToRecord {
+    fn to_record(&self) -> Record;
+}
+
+fn text(value: impl Display) -> Value {
+    Value::Text(value.to_string())
+}
+
+fn header(kind: MessageKind, timestamp: DateTime<Utc>, symbol: &impl Display) -> Record {
+    vec![
+        ("kind", Value::Text(kind.name().to_string())),
+        ("timestamp", Value::Time(timestamp)),
+        ("symbol", text(symbol)),
+    ]
+}
+
+/// The fields of the records of each kind of message
+pub fn columns(kind: MessageKind) -> &'static [&'static str] {
+    match kind {
+        MessageKind::SystemEvent => &["kind", "timestamp", "event_type"],
+        MessageKind::SecurityDirectory => &[
+            "kind",
+            "timestamp",
+            "symbol",
+            "test_security",
+            "when_issued",
+            "etp",
+            "round_lot_size",
+            "adjusted_poc_price",
+            "luld_tier",
+        ],
+        MessageKind::TradingStatus => &["kind", "timestamp", "symbol", "status", "reason"],
+        MessageKind::RetailLiquidityIndicator => &["kind", "timestamp", "symbol", "indicator"],
+        MessageKind::OperationalHaltStatus => &["kind", "timestamp", "symbol", "status"],
+        MessageKind::ShortSalePriceTestStatus => {
+            &["kind", "timestamp", "symbol", "in_effect", "detail"]
+        }
+        MessageKind::SecurityEvent => &["kind", "timestamp", "symbol", "event_type"],
+        MessageKind::QuoteUpdate => &[
+            "kind",
+            "timestamp",
+            "symbol",
+            "available",
+            "market_session",
+            "bid_size",
+            "bid_price",
+            "ask_size",
+            "ask_price",
+        ],
+        MessageKind::TradeReport | MessageKind::TradeBreak => &[
+            "kind",
+            "timestamp",
+            "symbol",
+            "size",
+            "price",
+            "id",
+            "intermarket_sweep",
+            "extended_hours",
+            "odd_lot",
+            "trade_through_exempt",
+            "single_price",
+        ],
+        MessageKind::OfficialPrice => &[
+            "kind",
+            "timestamp",
+            "symbol",
+            "price_type",
+            "official_price",
+        ],
+        MessageKind::AuctionInformation => &[
+            "kind",
+            "timestamp",
+            "symbol",
+            "auction_type",
+            "paired_shares",
+            "reference_price",
+            "indicative_clearing_price",
+            "imbalance_shares",
+            "imbalance_side",
+            "extension_number",
+            "scheduled_auction_time",
+            "auction_book_clearing_price",
+            "collar_reference_price",
+            "lower_auction_collar",
+            "upper_auction_collar",
+        ],
+        MessageKind::PriceLevelUpdateBuy | MessageKind::PriceLevelUpdateSell => &[
+            "kind",
+            "timestamp",
+            "symbol",
+            "event_processing_complete",
+            "size",
+            "price",
+        ],
+    }
+}
+
+impl ToRecord for SystemEvent {
+    fn to_record(&self) -> Record {
+        vec![
+            (
+                "kind",
+                Value::Text(MessageKind::SystemEvent.name().to_string()),
+            ),
+            ("timestamp", Value::Time(self.timestamp)),
+            ("event_type", text(self.event_type)),
+        ]
+    }
+}
+
+impl<S: Display> ToRecord for SecurityDirectory<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(MessageKind::SecurityDirectory, self.timestamp, &self.symbol);
+        record.extend([
+            ("test_security", Value::Bool(self.flags.test_security)),
+            ("when_issued", Value::Bool(self.flags.when_issued)),
+            ("etp", Value::Bool(self.flags.etp)),
+            ("round_lot_size", Value::Int(self.round_lot_size.into())),
+            ("adjusted_poc_price", Value::Price(self.adjusted_poc_price)),
+            ("luld_tier", Value::Int(u8::from(self.luld_tier).into())),
+        ]);
+        record
+    }
+}
+
+impl<S: Display> ToRecord for TradingStatus<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(MessageKind::TradingStatus, self.timestamp, &self.symbol);
+        record.extend([("status", text(self.status)), ("reason", text(self.reason))]);
+        record
+    }
+}
+
+impl<S: Display> ToRecord for RetailLiquidityIndicator<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(
+            MessageKind::RetailLiquidityIndicator,
+            self.timestamp,
+            &self.symbol,
+        );
+        record.push(("indicator", text(self.indicator)));
+        record
+    }
+}
+
+impl<S: Display> ToRecord for OperationalHaltStatus<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(
+            MessageKind::OperationalHaltStatus,
+            self.timestamp,
+            &self.symbol,
+        );
+        record.push(("status", text(self.status)));
+        record
+    }
+}
+
+impl<S: Display> ToRecord for ShortSalePriceTestStatus<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(
+            MessageKind::ShortSalePriceTestStatus,
+            self.timestamp,
+            &self.symbol,
+        );
+        record.extend([
+            ("in_effect", Value::Bool(self.in_effect)),
+            ("detail", text(self.detail)),
+        ]);
+        record
+    }
+}
+
+impl<S: Display> ToRecord for SecurityEvent<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(MessageKind::SecurityEvent, self.timestamp, &self.symbol);
+        record.push(("event_type", text(self.event_type)));
+        record
+    }
+}
+
+impl<S: Display> ToRecord for QuoteUpdate<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(MessageKind::QuoteUpdate, self.timestamp, &self.symbol);
+        record.extend([
+            ("available", Value::Bool(self.available)),
+            (
+                "market_session",
+                text(match self.market_session {
+                    MarketSession::Regular => "regular",
+                    MarketSession::OutOfHours => "out_of_hours",
+                }),
+            ),
+            ("bid_size", Value::Int(self.bid_size.into())),
+            ("bid_price", Value::Price(self.bid_price)),
+            ("ask_size", Value::Int(self.ask_size.into())),
+            ("ask_price", Value::Price(self.ask_price)),
+        ]);
+        record
+    }
+}
+
+fn trade_record<S: Display>(kind: MessageKind, trade: &TradeReport<S>) -> Record {
+    let mut record = header(kind, trade.timestamp, &trade.symbol);
+    record.extend([
+        ("size", Value::Int(trade.size.into())),
+        ("price", Value::Price(trade.price)),
+        ("id", Value::Int(trade.id)),
+        (
+            "intermarket_sweep",
+            Value::Bool(trade.sale_condition.intermarket_sweep),
+        ),
+        (
+            "extended_hours",
+            Value::Bool(trade.sale_condition.extended_hours),
+        ),
+        ("odd_lot", Value::Bool(trade.sale_condition.odd_lot)),
+        (
+            "trade_through_exempt",
+            Value::Bool(trade.sale_condition.trade_through_exempt),
+        ),
+        (
+            "single_price",
+            Value::Bool(trade.sale_condition.single_price),
+        ),
+    ]);
+    record
+}
+
+impl<S: Display> ToRecord for TradeReport<S> {
+    fn to_record(&self) -> Record {
+        trade_record(MessageKind::TradeReport, self)
+    }
+}
+
+impl<S: Display> ToRecord for TradeBreak<S> {
+    fn to_record(&self) -> Record {
+        trade_record(
+            MessageKind::TradeBreak,
+            &TradeReport {
+                sale_condition: self.sale_condition.clone(),
+                timestamp: self.timestamp,
+                symbol: &self.symbol,
+                size: self.size,
+                price: self.price,
+                id: self.id,
+            },
+        )
+    }
+}
+
+impl<S: Display> ToRecord for OfficialPrice<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(MessageKind::OfficialPrice, self.timestamp, &self.symbol);
+        record.extend([
+            ("price_type", text(self.price_type)),
+            ("official_price", Value::Price(self.official_price)),
+        ]);
+        record
+    }
+}
+
+impl<S: Display> ToRecord for AuctionInformation<S> {
+    fn to_record(&self) -> Record {
+        let mut record = header(
+            MessageKind::AuctionInformation,
+            self.timestamp,
+            &self.symbol,
+        );
+        record.extend([
+            ("auction_type", text(self.auction_type)),
+            ("paired_shares", Value::Int(self.paired_shares.into())),
+            ("reference_price", Value::Price(self.reference_price)),
+            (
+                "indicative_clearing_price",
+                Value::Price(self.indicative_clearing_price),
+            ),
+            ("imbalance_shares", Value::Int(self.imbalance_shares.into())),
+            ("imbalance_side", text(self.imbalance_side)),
+            ("extension_number", Value::Int(self.extension_number.into())),
+            (
+                "scheduled_auction_time",
+                Value::Time(self.scheduled_auction_time),
+            ),
+            (
+                "auction_book_clearing_price",
+                Value::Price(self.auction_book_clearing_price),
+            ),
+            (
+                "collar_reference_price",
+                Value::Price(self.collar_reference_price),
+            ),
+            (
+                "lower_auction_collar",
+                Value::Price(self.lower_auction_collar),
+            ),
+            (
+                "upper_auction_collar",
+                Value::Price(self.upper_auction_collar),
+            ),
+        ]);
+        record
+    }
+}
+
+impl<S: Display> ToRecord for PriceLevelUpdate<S> {
+    fn to_record(&self) -> Record {
+        let kind = match self.side {
+            Side::Buy => MessageKind::PriceLevelUpdateBuy,
+            Side::Sell => MessageKind::PriceLevelUpdateSell,
+        };
+        let mut record = header(kind, self.timestamp, &self.symbol);
+        record.extend([
+            (
+                "event_processing_complete",
+                Value::Bool(self.event_processing_complete),
+            ),
+            ("size", Value::Int(self.size.into())),
+            ("price", Value::Price(self.price)),
+        ]);
+        record
+    }
+}
+
+impl<S: Display> ToRecord for Tops1_6Message<S> {
+    fn to_record(&self) -> Record {
+        match self {
+            Tops1_6Message::SystemEvent(message) => message.to_record(),
+            Tops1_6Message::SecurityDirectory(message) => message.to_record(),
+            Tops1_6Message::TradingStatus(message) => message.to_record(),
+            Tops1_6Message::RetailLiquidityIndicator(message) => message.to_record(),
+            Tops1_6Message::OperationalHaltStatus(message) => message.to_record(),
+            Tops1_6Message::ShortSalePriceTestStatus(message) => message.to_record(),
+            Tops1_6Message::QuoteUpdate(message) => message.to_record(),
+            Tops1_6Message::TradeReport(message) => message.to_record(),
+            Tops1_6Message::OfficialPrice(message) => message.to_record(),
+            Tops1_6Message::TradeBreak(message) => message.to_record(),
+            Tops1_6Message::AuctionInformation(message) => message.to_record(),
+        }
+    }
+}
+
+impl<S: Display> ToRecord for Deep1_0Message<S> {
+    fn to_record(&self) -> Record {
+        match self {
+            Deep1_0Message::SystemEvent(message) => message.to_record(),
+            Deep1_0Message::SecurityDirectory(message) => message.to_record(),
+            Deep1_0Message::TradingStatus(message) => message.to_record(),
+            Deep1_0Message::OperationalHaltStatus(message) => message.to_record(),
+            Deep1_0Message::ShortSalePriceTestStatus(message) => message.to_record(),
+            Deep1_0Message::SecurityEvent(message) => message.to_record(),
+            Deep1_0Message::PriceLevelUpdate(message) => message.to_record(),
+            Deep1_0Message::TradeReport(message) => message.to_record(),
+            Deep1_0Message::OfficialPrice(message) => message.to_record(),
+            Deep1_0Message::TradeBreak(message) => message.to_record(),
+            Deep1_0Message::AuctionInformation(message) => message.to_record(),
+        }
+    }
+}
+
+/// The type of the values of a column, which is the same for all the kinds of messages having it
+fn column_type(column: &str) -> ValueType {
+    match column {
+        "test_security"
+        | "when_issued"
+        | "etp"
+        | "available"
+        | "intermarket_sweep"
+        | "extended_hours"
+        | "odd_lot"
+        | "trade_through_exempt"
+        | "single_price"
+        | "in_effect"
+        | "event_processing_complete" => ValueType::Bool,
+        "round_lot_size" | "luld_tier" | "bid_size" | "ask_size" | "size" | "id"
+        | "paired_shares" | "imbalance_shares" | "extension_number" => ValueType::Int,
+        "adjusted_poc_price"
+        | "bid_price"
+        | "ask_price"
+        | "price"
+        | "official_price"
+        | "reference_price"
+        | "indicative_clearing_price"
+        | "auction_book_clearing_price"
+        | "collar_reference_price"
+        | "lower_auction_collar"
+        | "upper_auction_collar" => ValueType::Price,
+        "timestamp" | "scheduled_auction_time" => ValueType::Time,
+        _ => ValueType::Text,
+    }
+}
+
+/// The columns of several kinds of messages and their types, in order of the kinds and then of the fields of their
+/// records
+pub(crate) fn union_of_columns(
+    kinds: impl IntoIterator<Item = MessageKind>,
+) -> Vec<(&'static str, ValueType)> {
+    let mut union = Vec::new();
+    for kind in kinds {
+        for &column in columns(kind) {
+            if !union.iter().any(|&(name, _)| name == column) {
+                union.push((column, column_type(column)));
+            }
+        }
+    }
+    union
+}
+
+fn write_csv_field<W: Write>(writer: &mut W, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\n', '\r']) {
+        write!(writer, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        write!(writer, "{field}")
+    }
+}
+
+/// Writes records of several kinds of messages as CSV, under the union of their columns
+#[derive(Debug)]
+pub struct CsvWriter<W> {
+    writer: W,
+    columns: Vec<&'static str>,
+}
+
+impl<W> CsvWriter<W>
+where
+    W: Write,
+{
+    /// Writes the header of the CSV, with the columns of the given kinds of messages. Fields of the records which
+    /// aren't among these columns are dropped.
+    pub fn new(writer: W, kinds: impl IntoIterator<Item = MessageKind>) -> io::Result<Self> {
+        Self::with_extra_columns(writer, kinds, [])
+    }
+
+    /// Like [`CsvWriter::new`], with more columns after those of the messages, e.g. for
+    /// [enriched](crate::enrich::MetadataTable::enrich) records
+    pub fn with_extra_columns(
+        writer: W,
+        kinds: impl IntoIterator<Item = MessageKind>,
+        extra_columns: impl IntoIterator<Item = &'static str>,
+    ) -> io::Result<Self> {
+        let mut csv = Self::append(writer, kinds);
+        for column in extra_columns {
+            if !csv.columns.contains(&column) {
+                csv.columns.push(column);
+            }
+        }
+        for (index, column) in csv.columns.iter().enumerate() {
+            if index > 0 {
+                write!(csv.writer, ",")?;
+            }
+            write_csv_field(&mut csv.writer, column)?;
+        }
+        writeln!(csv.writer)?;
+        Ok(csv)
+    }
+
+    /// Like [`CsvWriter::new`], without writing the header, for appending to an existing CSV
+    pub fn append(writer: W, kinds: impl IntoIterator<Item = MessageKind>) -> Self {
+        Self {
+            writer,
+            columns: union_of_columns(kinds)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+        }
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        for (index, column) in self.columns.iter().enumerate() {
+            if index > 0 {
+                write!(self.writer, ",")?;
+            }
+            if let Some((_, value)) = record.iter().find(|(name, _)| name == column) {
+                write_csv_field(&mut self.writer, &value.to_string())?;
+            }
+        }
+        writeln!(self.writer)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn write_json_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    write!(writer, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            c if c.is_control() => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{c}")?,
+        }
+    }
+    write!(writer, "\"")
+}
+
+/// Writes a record as a JSON object, on a single line (JSON Lines)
+pub fn write_json<W: Write>(mut writer: W, record: &Record) -> io::Result<()> {
+    write_json_object(&mut writer, record)?;
+    writeln!(writer)
+}
+
+/// Writes a record as a JSON object, without a trailing newline
+pub(crate) fn write_json_object<W: Write>(writer: &mut W, record: &Record) -> io::Result<()> {
+    write!(writer, "{{")?;
+    for (index, (name, value)) in record.iter().enumerate() {
+        if index > 0 {
+            write!(writer, ",")?;
+        }
+        write_json_string(writer, name)?;
+        write!(writer, ":")?;
+        match value {
+            Value::Bool(_) | Value::Int(_) | Value::Price(_) => write!(writer, "{value}")?,
+            Value::Time(_) | Value::Text(_) => write_json_string(writer, &value.to_string())?,
+        }
+    }
+    write!(writer, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        deep::SecurityEventType,
+        tops::{
+            AuctionType, ImbalanceSide, LuldTier, OfficialPriceType, OperationalHaltStatusType,
+            SaleCondition, SecurityDirectoryFlags, ShortSalePriceTestDetail, SystemEventType,
+            TradingStatusReason, TradingStatusType,
+        },
+    };
+
+    use super::*;
+
+    fn messages() -> Vec<Deep1_0Message<&'static str>> {
+        let timestamp = DateTime::from_timestamp_nanos(1_500_000_000_123_456_789);
+        let symbol = "ZIEXT";
+        let trade = TradeReport {
+            sale_condition: SaleCondition {
+                intermarket_sweep: true,
+                ..SaleCondition::default()
+            },
+            timestamp,
+            symbol,
+            size: 100,
+            price: 99.05,
+            id: 42,
+        };
+        vec![
+            Deep1_0Message::SystemEvent(SystemEvent {
+                event_type: SystemEventType::StartOfMessages,
+                timestamp,
+            }),
+            Deep1_0Message::SecurityDirectory(SecurityDirectory {
+                flags: SecurityDirectoryFlags {
+                    test_security: true,
+                    when_issued: false,
+                    etp: false,
+                },
+                timestamp,
+                symbol,
+                round_lot_size: 100,
+                adjusted_poc_price: 99.05,
+                luld_tier: LuldTier::Tier1,
+            }),
+            Deep1_0Message::TradingStatus(TradingStatus {
+                status: TradingStatusType::Trading,
+                timestamp,
+                symbol,
+                reason: TradingStatusReason::NotApplicable,
+            }),
+            Deep1_0Message::OperationalHaltStatus(OperationalHaltStatus {
+                status: OperationalHaltStatusType::NotHalted,
+                timestamp,
+                symbol,
+            }),
+            Deep1_0Message::ShortSalePriceTestStatus(ShortSalePriceTestStatus {
+                in_effect: false,
+                timestamp,
+                symbol,
+                detail: ShortSalePriceTestDetail::NoPriceTest,
+            }),
+            Deep1_0Message::SecurityEvent(SecurityEvent {
+                event_type: SecurityEventType::OpeningProcessComplete,
+                timestamp,
+                symbol,
+            }),
+            Deep1_0Message::PriceLevelUpdate(PriceLevelUpdate {
+                side: Side::Sell,
+                event_processing_complete: true,
+                timestamp,
+                symbol,
+                size: 100,
+                price: 99.05,
+            }),
+            Deep1_0Message::TradeReport(trade.clone()),
+            Deep1_0Message::OfficialPrice(OfficialPrice {
+                price_type: OfficialPriceType::OpeningPrice,
+                timestamp,
+                symbol,
+                official_price: 99.05,
+            }),
+            Deep1_0Message::TradeBreak(TradeBreak {
+                sale_condition: trade.sale_condition,
+                timestamp,
+                symbol,
+                size: 100,
+                price: 99.05,
+                id: 42,
+            }),
+            Deep1_0Message::AuctionInformation(AuctionInformation {
+                auction_type: AuctionType::Closing,
+                timestamp,
+                symbol,
+                paired_shares: 100,
+                reference_price: 99.05,
+                indicative_clearing_price: 99.05,
+                imbalance_shares: 0,
+                imbalance_side: ImbalanceSide::NoImbalance,
+                extension_number: 0,
+                scheduled_auction_time: timestamp,
+                auction_book_clearing_price: 99.05,
+                collar_reference_price: 99.05,
+                lower_auction_collar: 89.15,
+                upper_auction_collar: 108.95,
+            }),
+        ]
+    }
+
+    #[test]
+    fn columns_match_records() {
+        for message in messages() {
+            let record = message.to_record();
+            let names = record.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            assert_eq!(names, columns(message.kind()));
+            for (name, value) in &record {
+                assert_eq!(value.value_type(), column_type(name), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let mut writer = CsvWriter::new(
+            Vec::new(),
+            [MessageKind::SystemEvent, MessageKind::TradeReport],
+        )
+        .unwrap();
+        for message in messages() {
+            if matches!(
+                message.kind(),
+                MessageKind::SystemEvent | MessageKind::TradeReport
+            ) {
+                writer.write(&message.to_record()).unwrap();
+            }
+        }
+
+        let csv = String::from_utf8(writer.into_inner()).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "kind,timestamp,event_type,symbol,size,price,id,intermarket_sweep,extended_hours,\
+                 odd_lot,trade_through_exempt,single_price",
+                "system_event,2017-07-14T02:40:00.123456789Z,O,,,,,,,,,",
+                "trade_report,2017-07-14T02:40:00.123456789Z,,ZIEXT,100,99.0500,42,true,false,\
+                 false,false,false",
+            ]
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut json = Vec::new();
+        write_json(&mut json, &messages()[6].to_record()).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"kind\":\"price_level_update_sell\",\"timestamp\":\"2017-07-14T02:40:00.123456789Z\",\
+             \"symbol\":\"ZIEXT\",\"event_processing_complete\":true,\"size\":100,\"price\":99.0500}\n"
+        );
+
+        let mut json = Vec::new();
+        write_json(&mut json, &vec![("text", text("a\"b\\c\n"))]).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            "{\"text\":\"a\\\"b\\\\c\\u000a\"}\n"
+        );
+    }
+}
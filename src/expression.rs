@@ -0,0 +1,471 @@
+use std::{error, fmt, str::FromStr};
+
+use chrono::{DateTime, NaiveTime, Utc};
+
+use crate::filter::{raw_symbol, raw_timestamp, MessageKind, MessageKinds, SymbolPattern};
+
+/// Error returned when parsing a malformed filter expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidExpression {
+    /// The offset in the expression where the error was found
+    pub position: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid filter expression at offset {}: {}",
+            self.position, self.reason
+        )
+    }
+}
+
+impl error::Error for InvalidExpression {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    fn holds<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Operator::Equal => left == right,
+            Operator::NotEqual => left != right,
+            Operator::Less => left < right,
+            Operator::LessOrEqual => left <= right,
+            Operator::Greater => left > right,
+            Operator::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TimeValue {
+    Instant(DateTime<Utc>),
+    /// A time of the day, in UTC
+    TimeOfDay(NaiveTime),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+    /// Whether the symbol of the message matches any of the patterns, or none of them if negated. Either requires a
+    /// symbol.
+    Symbol {
+        patterns: Vec<SymbolPattern>,
+        negated: bool,
+    },
+    /// Whether the kind of the message is one of the kinds, or none of them if negated. Either requires a known kind.
+    Kind {
+        kinds: MessageKinds,
+        negated: bool,
+    },
+    Timestamp(Operator, TimeValue),
+}
+
+impl Node {
+    fn accepts(&self, message: &[u8]) -> bool {
+        match self {
+            Node::And(nodes) => nodes.iter().all(|node| node.accepts(message)),
+            Node::Or(nodes) => nodes.iter().any(|node| node.accepts(message)),
+            Node::Not(node) => !node.accepts(message),
+            Node::Symbol { patterns, negated } => raw_symbol(message)
+                .and_then(|symbol| std::str::from_utf8(symbol.trim_ascii_end()).ok())
+                .is_some_and(|symbol| {
+                    patterns.iter().any(|pattern| pattern.matches(symbol)) != *negated
+                }),
+            Node::Kind { kinds, negated } => {
+                MessageKind::of(message).is_some_and(|kind| kinds.contains(kind) != *negated)
+            }
+            Node::Timestamp(operator, value) => {
+                raw_timestamp(message).is_some_and(|timestamp| match value {
+                    TimeValue::Instant(instant) => operator.holds(timestamp, *instant),
+                    TimeValue::TimeOfDay(time) => operator.holds(timestamp.time(), *time),
+                })
+            }
+        }
+    }
+}
+
+/// A filter of raw messages written as an expression, e.g. `symbol in (AAPL, MSFT) and kind = trade and ts >= 14:30`,
+/// to extract messages without writing code (see the `--where` option of the command-line tools)
+///
+/// Comparisons are combined with `and`, `or`, `not` and parentheses, and compare fields to values:
+/// - `symbol = ZIEXT`, `symbol != ZIEXT` and `symbol in (ZIEXT, SPY*)`, where the symbols may be patterns (see
+///   [`SymbolPattern`]). Messages without a symbol (e.g. system events) never match, not even `!=` and `not in`
+///   comparisons, unlike the negation of a comparison with `not`.
+/// - `kind = trade_report`, `kind != Q` and `kind in (...)`, where the kinds are given by name, code or as `trade`,
+///   `quote` and `price_level_update` (of either side).
+/// - `ts` (or `timestamp`) with `=`, `!=`, `<`, `<=`, `>` or `>=`, and either a time of the day in UTC (e.g. `14:30`
+///   or `14:30:00.5`), a point in time in RFC 3339 or nanoseconds since the POSIX epoch.
+///
+/// Keywords and field names are case-insensitive, and `and` binds tighter than `or`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterExpression {
+    root: Node,
+}
+
+impl FilterExpression {
+    /// Whether a raw message is accepted
+    pub fn accepts(&self, message: &[u8]) -> bool {
+        self.root.accepts(message)
+    }
+}
+
+impl FromStr for FilterExpression {
+    type Err = InvalidExpression;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExpressionParser {
+            tokens: tokenize(s)?,
+            next: 0,
+            length: s.len(),
+        };
+        let root = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(Self { root }),
+            Some((position, token)) => Err(InvalidExpression {
+                position: *position,
+                reason: format!("unexpected {token}"),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    Operator(Operator),
+    Word(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "\"(\""),
+            Token::Close => write!(f, "\")\""),
+            Token::Comma => write!(f, "\",\""),
+            Token::Operator(_) => write!(f, "operator"),
+            Token::Word(word) => write!(f, "{word:?}"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, InvalidExpression> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '=' | '!' | '<' | '>' => {
+                let equal = chars.next_if(|&(_, next)| next == '=').is_some();
+                Token::Operator(match (c, equal) {
+                    ('=', _) => Operator::Equal,
+                    ('!', true) => Operator::NotEqual,
+                    ('<', false) => Operator::Less,
+                    ('<', true) => Operator::LessOrEqual,
+                    ('>', false) => Operator::Greater,
+                    ('>', true) => Operator::GreaterOrEqual,
+                    _ => {
+                        return Err(InvalidExpression {
+                            position,
+                            reason: "expected \"!=\"".to_string(),
+                        })
+                    }
+                })
+            }
+            _ => {
+                let mut end = position + c.len_utf8();
+                while let Some((next_position, next)) =
+                    chars.next_if(|&(_, next)| !next.is_whitespace() && !"(),=!<>".contains(next))
+                {
+                    end = next_position + next.len_utf8();
+                }
+                Token::Word(s[position..end].to_string())
+            }
+        };
+        tokens.push((position, token));
+    }
+    Ok(tokens)
+}
+
+struct ExpressionParser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// The length of the expression, the position of errors at its end
+    length: usize,
+}
+
+impl ExpressionParser {
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.length, |(position, _)| *position)
+    }
+
+    fn error<T>(&self, reason: impl Into<String>) -> Result<T, InvalidExpression> {
+        Err(InvalidExpression {
+            position: self.position(),
+            reason: reason.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.next += 1;
+        token
+    }
+
+    /// Consumes the next token if it's a keyword
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), InvalidExpression> {
+        if self.peek() == Some(&expected) {
+            self.next += 1;
+            Ok(())
+        } else {
+            self.error(format!("expected {expected}"))
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, InvalidExpression> {
+        let mut nodes = vec![self.and()?];
+        while self.keyword("or") {
+            nodes.push(self.and()?);
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::Or(nodes)
+        })
+    }
+
+    fn and(&mut self) -> Result<Node, InvalidExpression> {
+        let mut nodes = vec![self.unary()?];
+        while self.keyword("and") {
+            nodes.push(self.unary()?);
+        }
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::And(nodes)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Node, InvalidExpression> {
+        if self.keyword("not") {
+            return Ok(Node::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.next += 1;
+            let node = self.or()?;
+            self.expect(Token::Close)?;
+            return Ok(node);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Node, InvalidExpression> {
+        let field = match self.advance() {
+            Some(Token::Word(field)) => field.to_ascii_lowercase(),
+            _ => {
+                self.next -= 1;
+                return self.error("expected a field (symbol, kind or ts)");
+            }
+        };
+
+        if field == "ts" || field == "timestamp" {
+            let Some(Token::Operator(operator)) = self.advance() else {
+                self.next -= 1;
+                return self.error("expected a comparison operator");
+            };
+            let position = self.position();
+            let value = self.word()?;
+            let value = parse_time(&value).ok_or_else(|| InvalidExpression {
+                position,
+                reason: format!("invalid time {value:?}"),
+            })?;
+            return Ok(Node::Timestamp(operator, value));
+        }
+        if field != "symbol" && field != "kind" {
+            self.next -= 1;
+            return self.error(format!("unknown field {field:?}"));
+        }
+
+        let (negated, list) = if self.keyword("not") {
+            if !self.keyword("in") {
+                return self.error("expected \"in\"");
+            }
+            (true, true)
+        } else if self.keyword("in") {
+            (false, true)
+        } else {
+            match self.advance() {
+                Some(Token::Operator(Operator::Equal)) => (false, false),
+                Some(Token::Operator(Operator::NotEqual)) => (true, false),
+                _ => {
+                    self.next -= 1;
+                    return self.error("expected \"=\", \"!=\" or \"in\"");
+                }
+            }
+        };
+        let values = if list {
+            self.list()?
+        } else {
+            vec![(self.position(), self.word()?)]
+        };
+
+        Ok(if field == "symbol" {
+            let patterns = values
+                .into_iter()
+                .map(|(position, value)| {
+                    value
+                        .parse::<SymbolPattern>()
+                        .map_err(|e| InvalidExpression {
+                            position,
+                            reason: e.to_string(),
+                        })
+                })
+                .collect::<Result<_, _>>()?;
+            Node::Symbol { patterns, negated }
+        } else {
+            let mut kinds = MessageKinds::empty();
+            for (position, value) in values {
+                let found = kinds_named(&value);
+                if found.is_empty() {
+                    return Err(InvalidExpression {
+                        position,
+                        reason: format!("unknown message kind {value:?}"),
+                    });
+                }
+                for kind in found {
+                    kinds.insert(kind);
+                }
+            }
+            Node::Kind { kinds, negated }
+        })
+    }
+
+    /// A parenthesized list of values
+    fn list(&mut self) -> Result<Vec<(usize, String)>, InvalidExpression> {
+        self.expect(Token::Open)?;
+        let mut values = vec![(self.position(), self.word()?)];
+        while self.peek() == Some(&Token::Comma) {
+            self.next += 1;
+            values.push((self.position(), self.word()?));
+        }
+        self.expect(Token::Close)?;
+        Ok(values)
+    }
+
+    fn word(&mut self) -> Result<String, InvalidExpression> {
+        match self.advance() {
+            Some(Token::Word(word)) => Ok(word),
+            _ => {
+                self.next -= 1;
+                self.error("expected a value")
+            }
+        }
+    }
+}
+
+/// The kinds of messages with a name, a code or a shorthand
+fn kinds_named(name: &str) -> Vec<MessageKind> {
+    match name.to_ascii_lowercase().as_str() {
+        "trade" => vec![MessageKind::TradeReport],
+        "quote" => vec![MessageKind::QuoteUpdate],
+        "price_level_update" => vec![
+            MessageKind::PriceLevelUpdateBuy,
+            MessageKind::PriceLevelUpdateSell,
+        ],
+        _ => MessageKind::from_name(name).into_iter().collect(),
+    }
+}
+
+fn parse_time(s: &str) -> Option<TimeValue> {
+    if let Ok(nanos) = s.parse::<i64>() {
+        return Some(TimeValue::Instant(DateTime::from_timestamp_nanos(nanos)));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(TimeValue::Instant(time.with_timezone(&Utc)));
+    }
+    ["%H:%M", "%H:%M:%S%.f"]
+        .into_iter()
+        .find_map(|format| NaiveTime::parse_from_str(s, format).ok())
+        .map(TimeValue::TimeOfDay)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spec;
+
+    use super::*;
+
+    fn accepts(expression: &str, message: &[u8]) -> bool {
+        expression
+            .parse::<FilterExpression>()
+            .unwrap()
+            .accepts(message)
+    }
+
+    #[test]
+    fn expressions() {
+        // The trade of ZIEXT is timestamped at 2016-08-23T19:30:32.572715948Z
+        let trade = &spec::TRADE_REPORT[..];
+        assert!(accepts(
+            "symbol in (AAPL, ZIE*) and kind = trade and ts >= 14:30",
+            trade
+        ));
+        assert!(!accepts("symbol = AAPL or kind = Q", trade));
+        assert!(accepts("not (symbol = AAPL or kind = Q)", trade));
+        assert!(accepts("SYMBOL NOT IN (AAPL) AND kind != quote", trade));
+        assert!(accepts(
+            "ts < 2016-08-24T00:00:00Z and ts > 19:30:32.5",
+            trade
+        ));
+        assert!(!accepts("ts >= 1472000000000000000", trade));
+        assert!(accepts("kind = T and symbol = ZIEXT or ts < 0", trade));
+        assert!(!accepts("symbol = ZIEXT", &spec::SYSTEM_EVENT));
+        assert!(!accepts("symbol != ZIEXT", &spec::SYSTEM_EVENT));
+        assert!(!accepts("symbol not in (ZIEXT)", &spec::SYSTEM_EVENT));
+        assert!(accepts("not symbol = ZIEXT", &spec::SYSTEM_EVENT));
+        assert!(accepts("kind != trade", &spec::SYSTEM_EVENT));
+
+        let error = |expression: &str| expression.parse::<FilterExpression>().unwrap_err();
+        assert_eq!(error("symbol in (AAPL").position, 15);
+        assert_eq!(
+            error("kind = trades").reason,
+            "unknown message kind \"trades\""
+        );
+        assert_eq!(error("price > 10").reason, "unknown field \"price\"");
+        assert_eq!(error("ts >= noon").position, 6);
+        assert_eq!(error("symbol = A B").reason, "unexpected \"B\"");
+        assert!("symbol < A".parse::<FilterExpression>().is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::Read,
+    sync::Arc,
+};
+
+use crate::{
+    config::{ParserConfig, Rejection},
+    hist::{CapturedSegment, Error, HistReader, Message},
+};
+
+type ExtensionParser<E> = Box<dyn Fn(&[u8]) -> Option<E> + Send + Sync>;
+
+/// A message decoded either by the crate, or by a parser registered in [`ExtensionParsers`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Extended<M, E> {
+    Known(M),
+    Extension(E),
+}
+
+impl<M, E> Extended<M, E> {
+    pub fn known(&self) -> Option<&M> {
+        match self {
+            Extended::Known(message) => Some(message),
+            Extended::Extension(_) => None,
+        }
+    }
+
+    pub fn extension(&self) -> Option<&E> {
+        match self {
+            Extended::Known(_) => None,
+            Extended::Extension(payload) => Some(payload),
+        }
+    }
+}
+
+/// Parsers of the message types the crate doesn't decode (yet), by message type byte, e.g. to handle additions to the
+/// specifications before they're supported
+///
+/// A parser is given the whole message, type byte included, and returns its payload, or `None` if it can't decode it.
+/// The crate decodes the types it knows first: a parser registered for one of them only gets the messages the crate
+/// fails to decode.
+pub struct ExtensionParsers<E> {
+    parsers: HashMap<u8, ExtensionParser<E>>,
+}
+
+impl<E> Default for ExtensionParsers<E> {
+    fn default() -> Self {
+        Self {
+            parsers: HashMap::new(),
+        }
+    }
+}
+
+impl<E> fmt::Debug for ExtensionParsers<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut message_types = self.parsers.keys().collect::<Vec<_>>();
+        message_types.sort_unstable();
+        f.debug_struct("ExtensionParsers")
+            .field("message_types", &message_types)
+            .finish()
+    }
+}
+
+impl<E> ExtensionParsers<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the parser of a message type, replacing any parser previously registered for it
+    pub fn register<F>(&mut self, message_type: u8, parser: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> Option<E> + Send + Sync + 'static,
+    {
+        self.parsers.insert(message_type, Box::new(parser));
+        self
+    }
+
+    pub fn is_registered(&self, message_type: u8) -> bool {
+        self.parsers.contains_key(&message_type)
+    }
+
+    /// Decodes a message with the parser registered for its type, if any
+    pub fn parse(&self, message: &[u8]) -> Option<E> {
+        self.parsers.get(message.first()?)?(message)
+    }
+
+    /// Decodes a message of a protocol, with the crate or else with the parser registered for its type
+    pub fn decode<M: Message>(&self, message: &[u8]) -> Option<Extended<M, E>> {
+        match M::parse(message) {
+            Ok((_, decoded)) => Some(Extended::Known(decoded)),
+            Err(_) => self.parse(message).map(Extended::Extension),
+        }
+    }
+
+    /// Converts a reader into an iterator over the messages of a single protocol, decoded according to the config of
+    /// the reader, and with the registered parsers for the messages the crate fails to decode
+    pub fn messages<R, M>(self: Arc<Self>, reader: HistReader<R>) -> ExtendedMessages<R, M, E>
+    where
+        R: Read,
+        M: Message,
+    {
+        ExtendedMessages {
+            config: reader.config().clone(),
+            reader,
+            parsers: self,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// The messages of a HIST file, see [`ExtensionParsers::messages`]
+///
+/// Like [`Messages`](crate::hist::Messages), a message which can't be decoded doesn't drop the rest of its segment: it
+/// is reported as an [`Error::InvalidMessage`], followed by the next messages.
+pub struct ExtendedMessages<R, M, E> {
+    reader: HistReader<R>,
+    config: ParserConfig,
+    parsers: Arc<ExtensionParsers<E>>,
+    pending: VecDeque<Result<Extended<M, E>, Error>>,
+}
+
+impl<R, M, E> ExtendedMessages<R, M, E> {
+    pub fn into_inner(self) -> HistReader<R> {
+        self.reader
+    }
+}
+
+impl<R, M, E> Iterator for ExtendedMessages<R, M, E>
+where
+    R: Read,
+    M: Message,
+{
+    type Item = Result<Extended<M, E>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let segment = match self.reader.next_segment() {
+                Ok(Some(CapturedSegment { segment, .. })) => segment,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            if segment.message_protocol_id != M::MESSAGE_PROTOCOL_ID {
+                continue;
+            }
+
+            for (sequence_number, message) in
+                (segment.first_message_sequence_no..).zip(&segment.messages)
+            {
+                if !self.config.accepts(message) {
+                    continue;
+                }
+                let rejection = match self.config.decode(message, segment.send_time) {
+                    Ok(decoded) => {
+                        self.pending.push_back(Ok(Extended::Known(decoded)));
+                        continue;
+                    }
+                    Err(rejection) => rejection,
+                };
+                if rejection != Rejection::Skewed {
+                    if let Some(payload) = self.parsers.parse(message) {
+                        self.pending.push_back(Ok(Extended::Extension(payload)));
+                        continue;
+                    }
+                }
+                if !self.config.tolerates(rejection) {
+                    self.pending.push_back(Err(Error::InvalidMessage {
+                        sequence_number,
+                        message: message.to_vec(),
+                    }));
+                }
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids, spec,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    /// A message type which isn't in the specification
+    #[derive(Debug, PartialEq)]
+    struct Heartbeat(u8);
+
+    #[test]
+    fn extensions() {
+        let mut parsers = ExtensionParsers::new();
+        parsers.register(b'Z', |message: &[u8]| {
+            (message.len() == 2).then(|| Heartbeat(message[1]))
+        });
+        assert!(parsers.is_registered(b'Z'));
+        assert_eq!(
+            parsers.decode::<Tops1_6Message<String>>(b"Z\x07"),
+            Some(Extended::Extension(Heartbeat(7)))
+        );
+        assert!(parsers
+            .decode::<Tops1_6Message<String>>(&spec::TRADE_REPORT)
+            .is_some_and(|message| message.known().is_some()));
+
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&b"Z\x07"[..], &b"Y"[..], &spec::TRADE_REPORT],
+        );
+        let reader = HistReader::new(Cursor::new(capture(&[encoded]))).unwrap();
+        let messages = Arc::new(parsers)
+            .messages::<_, Tops1_6Message<String>>(reader)
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[0].as_ref().unwrap().extension(),
+            Some(&Heartbeat(7))
+        );
+        assert!(matches!(
+            messages[1],
+            Err(Error::InvalidMessage {
+                sequence_number: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            messages[2],
+            Ok(Extended::Known(Tops1_6Message::TradeReport(_)))
+        ));
+    }
+}
@@ -0,0 +1,748 @@
+//! A C API over the TOPS messages of HIST files, declared by `include/iex_parser.h`
+//!
+//! A reader is opened with `iex_reader_open`, and its messages are read one at a time into an [`IexMessage`] by
+//! `iex_reader_next`, or passed to a callback by `iex_reader_for_each`, or read in batches into the columns of an
+//! [`IexColumns`] by `iex_reader_read_columns`. Failing functions return [`IEX_ERROR`] (or
+//! `NULL`), and `iex_last_error` describes the failure.
+//!
+//! The layout of the structures and the values of the constants are part of the API: fields may only be added at the
+//! end of the structures of the message bodies, within the size of [`IexMessageBody`]. The header is generated by
+//! `cbindgen --config cbindgen.toml --output include/iex_parser.h`.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    fs::File,
+    io::{BufReader, Cursor, Read},
+    mem, ptr, slice,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    hist::{self, HistReader, Messages},
+    tops::{MarketSession, SaleCondition, Tops1_6Message},
+};
+
+/// A message was read
+pub const IEX_OK: c_int = 0;
+/// There are no more messages
+pub const IEX_END: c_int = 1;
+/// The function failed, see `iex_last_error`
+pub const IEX_ERROR: c_int = -1;
+
+/// A reader of the TOPS messages of a HIST file, opaque to C
+pub struct IexReader {
+    messages: Messages<Box<dyn Read>, Tops1_6Message<String>>,
+    /// An error met by `iex_reader_read_columns` after some messages, returned by the next read
+    error: Option<hist::Error>,
+}
+
+impl Iterator for IexReader {
+    type Item = Result<Tops1_6Message<String>, hist::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.error.take().map(Err).or_else(|| self.messages.next())
+    }
+}
+
+fn open_reader<R: Read + 'static>(reader: R) -> *mut IexReader {
+    match HistReader::new(Box::new(reader) as Box<dyn Read>) {
+        Ok(reader) => Box::into_raw(Box::new(IexReader {
+            messages: reader.messages(),
+            error: None,
+        })),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexSystemEvent {
+    pub event_type: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexSecurityDirectory {
+    pub test_security: bool,
+    pub when_issued: bool,
+    pub etp: bool,
+    pub luld_tier: u8,
+    pub round_lot_size: u32,
+    pub adjusted_poc_price: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexTradingStatus {
+    pub status: u8,
+    /// The code of the reason, NUL-terminated
+    pub reason: [c_char; 5],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexRetailLiquidityIndicator {
+    pub indicator: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexOperationalHaltStatus {
+    pub status: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexShortSalePriceTestStatus {
+    pub in_effect: bool,
+    pub detail: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexQuoteUpdate {
+    pub available: bool,
+    pub out_of_hours: bool,
+    pub bid_size: u32,
+    pub bid_price: f64,
+    pub ask_size: u32,
+    pub ask_price: f64,
+}
+
+/// A trade report or a trade break
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexTrade {
+    pub intermarket_sweep: bool,
+    pub extended_hours: bool,
+    pub odd_lot: bool,
+    pub trade_through_exempt: bool,
+    pub single_price: bool,
+    pub size: u32,
+    pub price: f64,
+    pub id: i64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexOfficialPrice {
+    pub price_type: u8,
+    pub price: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IexAuctionInformation {
+    pub auction_type: u8,
+    pub imbalance_side: u8,
+    pub extension_number: u8,
+    pub paired_shares: u32,
+    pub imbalance_shares: u32,
+    pub reference_price: f64,
+    pub indicative_clearing_price: f64,
+    /// In nanoseconds since the Unix epoch
+    pub scheduled_auction_time: i64,
+    pub auction_book_clearing_price: f64,
+    pub collar_reference_price: f64,
+    pub lower_auction_collar: f64,
+    pub upper_auction_collar: f64,
+}
+
+/// The fields specific to each type of message, the one to read being given by [`IexMessage::kind`]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union IexMessageBody {
+    pub system_event: IexSystemEvent,
+    pub security_directory: IexSecurityDirectory,
+    pub trading_status: IexTradingStatus,
+    pub retail_liquidity_indicator: IexRetailLiquidityIndicator,
+    pub operational_halt_status: IexOperationalHaltStatus,
+    pub short_sale_price_test_status: IexShortSalePriceTestStatus,
+    pub quote_update: IexQuoteUpdate,
+    /// The body of trade reports and trade breaks
+    pub trade: IexTrade,
+    pub official_price: IexOfficialPrice,
+    pub auction_information: IexAuctionInformation,
+}
+
+/// A decoded TOPS message
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct IexMessage {
+    /// The character code of the type of the message, e.g. `'Q'` for quote updates
+    pub kind: u8,
+    /// In nanoseconds since the Unix epoch, or 0 if the message has no timestamp
+    pub timestamp: i64,
+    /// NUL-terminated, and empty for system events
+    pub symbol: [c_char; 9],
+    pub body: IexMessageBody,
+}
+
+/// The fields of many messages, as arrays of `capacity` values each, filled by `iex_reader_read_columns`: the fields
+/// of a message are at the same index in every array. The arrays left `NULL` aren't filled, so that hosts only pay for
+/// the fields they need.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct IexColumns {
+    /// The number of values each array can hold
+    pub capacity: usize,
+    /// See [`IexMessage::kind`]
+    pub kind: *mut u8,
+    /// See [`IexMessage::timestamp`]
+    pub timestamp: *mut i64,
+    /// See [`IexMessage::symbol`]
+    pub symbol: *mut [c_char; 9],
+    /// See [`IexMessage::body`]
+    pub body: *mut IexMessageBody,
+}
+
+/// Called by `iex_reader_for_each` with every message and the context it was given. Returning a non-zero value stops
+/// the iteration.
+pub type IexCallback = extern "C" fn(message: *const IexMessage, context: *mut c_void) -> c_int;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(error: impl ToString) {
+    let message = CString::new(error.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+fn nanoseconds(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt().unwrap_or_default()
+}
+
+fn c_string<const N: usize>(s: &str) -> [c_char; N] {
+    let mut buffer = [0; N];
+    for (c, byte) in buffer.iter_mut().zip(s.bytes().take(N - 1)) {
+        *c = byte as c_char;
+    }
+    buffer
+}
+
+fn trade(sale_condition: &SaleCondition, size: u32, price: f64, id: i64) -> IexMessageBody {
+    IexMessageBody {
+        trade: IexTrade {
+            intermarket_sweep: sale_condition.intermarket_sweep,
+            extended_hours: sale_condition.extended_hours,
+            odd_lot: sale_condition.odd_lot,
+            trade_through_exempt: sale_condition.trade_through_exempt,
+            single_price: sale_condition.single_price,
+            size,
+            price,
+            id,
+        },
+    }
+}
+
+impl From<&Tops1_6Message<String>> for IexMessage {
+    fn from(message: &Tops1_6Message<String>) -> Self {
+        let body = match message {
+            Tops1_6Message::SystemEvent(message) => IexMessageBody {
+                system_event: IexSystemEvent {
+                    event_type: message.event_type.code(),
+                },
+            },
+            Tops1_6Message::SecurityDirectory(message) => IexMessageBody {
+                security_directory: IexSecurityDirectory {
+                    test_security: message.flags.test_security,
+                    when_issued: message.flags.when_issued,
+                    etp: message.flags.etp,
+                    luld_tier: message.luld_tier.into(),
+                    round_lot_size: message.round_lot_size,
+                    adjusted_poc_price: message.adjusted_poc_price,
+                },
+            },
+            Tops1_6Message::TradingStatus(message) => IexMessageBody {
+                trading_status: IexTradingStatus {
+                    status: message.status.code(),
+                    reason: c_string(message.reason.code()),
+                },
+            },
+            Tops1_6Message::RetailLiquidityIndicator(message) => IexMessageBody {
+                retail_liquidity_indicator: IexRetailLiquidityIndicator {
+                    indicator: message.indicator.code(),
+                },
+            },
+            Tops1_6Message::OperationalHaltStatus(message) => IexMessageBody {
+                operational_halt_status: IexOperationalHaltStatus {
+                    status: message.status.code(),
+                },
+            },
+            Tops1_6Message::ShortSalePriceTestStatus(message) => IexMessageBody {
+                short_sale_price_test_status: IexShortSalePriceTestStatus {
+                    in_effect: message.in_effect,
+                    detail: message.detail.code(),
+                },
+            },
+            Tops1_6Message::QuoteUpdate(message) => IexMessageBody {
+                quote_update: IexQuoteUpdate {
+                    available: message.available,
+                    out_of_hours: message.market_session == MarketSession::OutOfHours,
+                    bid_size: message.bid_size,
+                    bid_price: message.bid_price,
+                    ask_size: message.ask_size,
+                    ask_price: message.ask_price,
+                },
+            },
+            Tops1_6Message::TradeReport(message) => trade(
+                &message.sale_condition,
+                message.size,
+                message.price,
+                message.id,
+            ),
+            Tops1_6Message::OfficialPrice(message) => IexMessageBody {
+                official_price: IexOfficialPrice {
+                    price_type: message.price_type.code(),
+                    price: message.official_price,
+                },
+            },
+            Tops1_6Message::TradeBreak(message) => trade(
+                &message.sale_condition,
+                message.size,
+                message.price,
+                message.id,
+            ),
+            Tops1_6Message::AuctionInformation(message) => IexMessageBody {
+                auction_information: IexAuctionInformation {
+                    auction_type: message.auction_type.code(),
+                    imbalance_side: message.imbalance_side.code(),
+                    extension_number: message.extension_number,
+                    paired_shares: message.paired_shares,
+                    imbalance_shares: message.imbalance_shares,
+                    reference_price: message.reference_price,
+                    indicative_clearing_price: message.indicative_clearing_price,
+                    scheduled_auction_time: nanoseconds(message.scheduled_auction_time),
+                    auction_book_clearing_price: message.auction_book_clearing_price,
+                    collar_reference_price: message.collar_reference_price,
+                    lower_auction_collar: message.lower_auction_collar,
+                    upper_auction_collar: message.upper_auction_collar,
+                },
+            },
+        };
+
+        IexMessage {
+            kind: message.kind().code(),
+            timestamp: message.timestamp().map_or(0, nanoseconds),
+            symbol: c_string(message.symbol().map_or("", String::as_str)),
+            body,
+        }
+    }
+}
+
+/// Opens an uncompressed HIST file. Returns `NULL` if it can't be opened or isn't a capture.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn iex_reader_open(path: *const c_char) -> *mut IexReader {
+    if path.is_null() {
+        set_last_error("the path is NULL");
+        return ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(_) => {
+            set_last_error("the path isn't valid UTF-8");
+            return ptr::null_mut();
+        }
+    };
+    match File::open(path) {
+        Ok(file) => open_reader(BufReader::new(file)),
+        Err(e) => {
+            set_last_error(hist::Error::Io(e));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Reads a HIST file held in memory, e.g. by a WebAssembly host, which has no file system. The buffer is copied, so
+/// it can be freed once the reader is open. Returns `NULL` if it isn't a capture.
+///
+/// # Safety
+///
+/// `data` must point to `length` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn iex_reader_open_buffer(data: *const u8, length: usize) -> *mut IexReader {
+    if data.is_null() {
+        set_last_error("the buffer is NULL");
+        return ptr::null_mut();
+    }
+    open_reader(Cursor::new(slice::from_raw_parts(data, length).to_vec()))
+}
+
+/// Allocates a zeroed buffer of `length` bytes, to be freed by `iex_buffer_free`. WebAssembly hosts copy files into
+/// such buffers before opening them with `iex_reader_open_buffer`, and read messages from them.
+#[no_mangle]
+pub extern "C" fn iex_buffer_alloc(length: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; length].into_boxed_slice()) as *mut u8
+}
+
+/// Frees a buffer allocated by `iex_buffer_alloc`. Freeing `NULL` does nothing.
+///
+/// # Safety
+///
+/// `data` must have been returned by `iex_buffer_alloc` with the same `length`, and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn iex_buffer_free(data: *mut u8, length: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, length)));
+    }
+}
+
+/// The size of an `IexMessage`, for hosts which don't read the header
+#[no_mangle]
+pub extern "C" fn iex_message_size() -> usize {
+    mem::size_of::<IexMessage>()
+}
+
+/// Reads the next message into `message`. Returns [`IEX_OK`], [`IEX_END`] at the end of the file, or [`IEX_ERROR`]
+/// if the file is invalid (in which case reading can go on with the following segment).
+///
+/// # Safety
+///
+/// `reader` must have been returned by `iex_reader_open` and not closed, and `message` must point to an
+/// `IexMessage`.
+#[no_mangle]
+pub unsafe extern "C" fn iex_reader_next(
+    reader: *mut IexReader,
+    message: *mut IexMessage,
+) -> c_int {
+    let (Some(reader), Some(message)) = (reader.as_mut(), message.as_mut()) else {
+        set_last_error("the reader or the message is NULL");
+        return IEX_ERROR;
+    };
+    match reader.next() {
+        Some(Ok(decoded)) => {
+            *message = IexMessage::from(&decoded);
+            IEX_OK
+        }
+        Some(Err(e)) => {
+            set_last_error(e);
+            IEX_ERROR
+        }
+        None => IEX_END,
+    }
+}
+
+/// Calls `callback` with every remaining message and `context`, until it returns a non-zero value. Returns
+/// [`IEX_END`] if all the messages were read, [`IEX_OK`] if the callback stopped the iteration, or [`IEX_ERROR`] if
+/// the file is invalid.
+///
+/// # Safety
+///
+/// `reader` must have been returned by `iex_reader_open` and not closed. The message passed to the callback is only
+/// valid during the call.
+#[no_mangle]
+pub unsafe extern "C" fn iex_reader_for_each(
+    reader: *mut IexReader,
+    callback: IexCallback,
+    context: *mut c_void,
+) -> c_int {
+    let Some(reader) = reader.as_mut() else {
+        set_last_error("the reader is NULL");
+        return IEX_ERROR;
+    };
+    for decoded in reader {
+        match decoded {
+            Ok(decoded) => {
+                if callback(&IexMessage::from(&decoded), context) != 0 {
+                    return IEX_OK;
+                }
+            }
+            Err(e) => {
+                set_last_error(e);
+                return IEX_ERROR;
+            }
+        }
+    }
+    IEX_END
+}
+
+/// Reads up to `capacity` messages into `columns`. Returns the number of messages read, which is 0 at the end of the
+/// file, or [`IEX_ERROR`] if the file is invalid (in which case reading can go on with the following segment). When the
+/// file turns out to be invalid after some messages of the batch, these messages are returned, and the next call
+/// fails.
+///
+/// # Safety
+///
+/// `reader` must have been returned by `iex_reader_open` and not closed, and the arrays of `columns` which aren't
+/// `NULL` must hold `capacity` values.
+#[no_mangle]
+pub unsafe extern "C" fn iex_reader_read_columns(
+    reader: *mut IexReader,
+    columns: *const IexColumns,
+) -> isize {
+    let (Some(reader), Some(columns)) = (reader.as_mut(), columns.as_ref()) else {
+        set_last_error("the reader or the columns are NULL");
+        return IEX_ERROR as isize;
+    };
+    let mut count = 0;
+    while count < columns.capacity {
+        let message = match reader.next() {
+            Some(Ok(decoded)) => IexMessage::from(&decoded),
+            Some(Err(e)) if count == 0 => {
+                set_last_error(e);
+                return IEX_ERROR as isize;
+            }
+            Some(Err(e)) => {
+                reader.error = Some(e);
+                break;
+            }
+            None => break,
+        };
+        if !columns.kind.is_null() {
+            columns.kind.add(count).write(message.kind);
+        }
+        if !columns.timestamp.is_null() {
+            columns.timestamp.add(count).write(message.timestamp);
+        }
+        if !columns.symbol.is_null() {
+            columns.symbol.add(count).write(message.symbol);
+        }
+        if !columns.body.is_null() {
+            columns.body.add(count).write(message.body);
+        }
+        count += 1;
+    }
+    count as isize
+}
+
+/// Closes a reader and its file. Closing `NULL` does nothing.
+///
+/// # Safety
+///
+/// `reader` must have been returned by `iex_reader_open` and not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn iex_reader_close(reader: *mut IexReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+/// Describes the last failure of a function on the calling thread. The string is valid until the next failure.
+#[no_mangle]
+pub extern "C" fn iex_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, mem::MaybeUninit};
+
+    use float_eq::assert_float_eq;
+
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids, spec,
+    };
+
+    use super::*;
+
+    extern "C" fn count_until_trade(message: *const IexMessage, context: *mut c_void) -> c_int {
+        let count = unsafe { &mut *(context as *mut u32) };
+        *count += 1;
+        c_int::from(unsafe { (*message).kind } == b'T')
+    }
+
+    #[test]
+    fn reader() {
+        let path = env::temp_dir().join(format!("iex-parser-ffi-{}.pcap", std::process::id()));
+        fs::write(
+            &path,
+            capture(&[segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[
+                    &spec::SYSTEM_EVENT,
+                    &spec::QUOTE_UPDATE,
+                    &spec::TRADE_REPORT,
+                    &spec::AUCTION_INFORMATION,
+                ],
+            )]),
+        )
+        .unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let reader = iex_reader_open(c_path.as_ptr());
+            assert!(!reader.is_null());
+
+            let mut message = IexMessage {
+                kind: 0,
+                timestamp: 0,
+                symbol: [0; 9],
+                body: IexMessageBody {
+                    system_event: IexSystemEvent::default(),
+                },
+            };
+            assert_eq!(iex_reader_next(reader, &mut message), IEX_OK);
+            assert_eq!(message.kind, b'S');
+            assert_eq!(message.symbol[0], 0);
+            assert_eq!(message.body.system_event.event_type, b'E');
+
+            assert_eq!(iex_reader_next(reader, &mut message), IEX_OK);
+            assert_eq!(message.kind, b'Q');
+            assert_eq!(message.timestamp, 1471980632572715948);
+            assert_eq!(CStr::from_ptr(message.symbol.as_ptr()), c"ZIEXT");
+            let quote = message.body.quote_update;
+            assert!(quote.available && !quote.out_of_hours);
+            assert_eq!((quote.bid_size, quote.ask_size), (9700, 1000));
+            assert_float_eq!(quote.bid_price, 99.05, ulps <= 1);
+            assert_float_eq!(quote.ask_price, 99.07, ulps <= 1);
+
+            let mut count = 0u32;
+            assert_eq!(
+                iex_reader_for_each(
+                    reader,
+                    count_until_trade,
+                    &mut count as *mut u32 as *mut c_void
+                ),
+                IEX_OK
+            );
+            assert_eq!(count, 1);
+            assert_eq!(
+                iex_reader_for_each(
+                    reader,
+                    count_until_trade,
+                    &mut count as *mut u32 as *mut c_void
+                ),
+                IEX_END
+            );
+            assert_eq!(count, 2);
+            assert_eq!(iex_reader_next(reader, &mut message), IEX_END);
+            iex_reader_close(reader);
+
+            let missing = CString::new(path.with_extension("missing").to_str().unwrap()).unwrap();
+            assert!(iex_reader_open(missing.as_ptr()).is_null());
+            assert!(CStr::from_ptr(iex_last_error())
+                .to_str()
+                .unwrap()
+                .starts_with("I/O error"));
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn buffer() {
+        let capture = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&spec::TRADE_REPORT],
+        )]);
+        let mut message = MaybeUninit::<IexMessage>::uninit();
+
+        unsafe {
+            let buffer = iex_buffer_alloc(capture.len());
+            slice::from_raw_parts_mut(buffer, capture.len()).copy_from_slice(&capture);
+            let reader = iex_reader_open_buffer(buffer, capture.len());
+            iex_buffer_free(buffer, capture.len());
+            assert!(!reader.is_null());
+
+            assert_eq!(iex_reader_next(reader, message.as_mut_ptr()), IEX_OK);
+            let trade = message.assume_init_ref();
+            assert_eq!(trade.kind, b'T');
+            assert_eq!(trade.body.trade.size, 100);
+            assert_eq!(iex_reader_next(reader, message.as_mut_ptr()), IEX_END);
+            iex_reader_close(reader);
+
+            assert!(iex_reader_open_buffer(capture[24..].as_ptr(), capture.len() - 24).is_null());
+        }
+        assert_eq!(iex_message_size(), mem::size_of::<IexMessage>());
+    }
+
+    #[test]
+    fn columns() {
+        let capture = capture(&[
+            segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[
+                    &spec::SYSTEM_EVENT,
+                    &spec::QUOTE_UPDATE,
+                    &spec::TRADE_REPORT,
+                ],
+            ),
+            segment(message_protocol_ids::TOPS, 4, &[&[0xFF, 0xFF]]),
+            segment(message_protocol_ids::TOPS, 5, &[&spec::OFFICIAL_PRICE]),
+        ]);
+        let mut kinds = [0u8; 2];
+        let mut symbols = [[0; 9]; 2];
+        let mut bodies = [IexMessageBody {
+            system_event: IexSystemEvent::default(),
+        }; 2];
+        let columns = IexColumns {
+            capacity: 2,
+            kind: kinds.as_mut_ptr(),
+            timestamp: ptr::null_mut(),
+            symbol: symbols.as_mut_ptr(),
+            body: bodies.as_mut_ptr(),
+        };
+
+        unsafe {
+            let reader = iex_reader_open_buffer(capture.as_ptr(), capture.len());
+            assert_eq!(iex_reader_read_columns(reader, &columns), 2);
+            assert_eq!(kinds, *b"SQ");
+            assert_eq!(CStr::from_ptr(symbols[1].as_ptr()), c"ZIEXT");
+            assert_eq!(bodies[0].system_event.event_type, b'E');
+            assert_eq!(bodies[1].quote_update.bid_size, 9700);
+
+            // The messages before the invalid one are returned first
+            assert_eq!(iex_reader_read_columns(reader, &columns), 1);
+            assert_eq!(kinds[0], b'T');
+            assert_eq!(bodies[0].trade.size, 100);
+            assert_eq!(iex_reader_read_columns(reader, &columns), -1);
+            assert!(CStr::from_ptr(iex_last_error())
+                .to_str()
+                .unwrap()
+                .contains("invalid"));
+
+            assert_eq!(iex_reader_read_columns(reader, &columns), 1);
+            assert_eq!(kinds[0], b'X');
+            assert_eq!(iex_reader_read_columns(reader, &columns), 0);
+            iex_reader_close(reader);
+        }
+    }
+
+    #[test]
+    fn header() {
+        let header = include_str!("../include/iex_parser.h");
+        for declaration in [
+            "IexReader *iex_reader_open(const char *path);",
+            "int iex_reader_next(IexReader *reader, IexMessage *message);",
+            "int iex_reader_for_each(IexReader *reader, IexCallback callback, void *context);",
+            "intptr_t iex_reader_read_columns(IexReader *reader, const IexColumns *columns);",
+            "void iex_reader_close(IexReader *reader);",
+            "IexReader *iex_reader_open_buffer(const uint8_t *data, uintptr_t length);",
+            "uint8_t *iex_buffer_alloc(uintptr_t length);",
+            "void iex_buffer_free(uint8_t *data, uintptr_t length);",
+            "uintptr_t iex_message_size(void);",
+            "const char *iex_last_error(void);",
+            "#define IEX_OK 0",
+            "#define IEX_END 1",
+            "#define IEX_ERROR -1",
+        ] {
+            assert!(header.contains(declaration), "{declaration}");
+        }
+    }
+
+    // As laid out by a C compiler on 64-bit platforms (and in WebAssembly, as read by wasm/index.js), while 32-bit x86
+    // aligns doubles on 4 bytes
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn layout() {
+        assert_eq!(mem::size_of::<IexMessage>(), 104);
+        assert_eq!(mem::offset_of!(IexMessage, body), 32);
+        assert_eq!(
+            mem::offset_of!(IexAuctionInformation, scheduled_auction_time),
+            32
+        );
+    }
+}
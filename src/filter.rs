@@ -0,0 +1,548 @@
+use std::{
+    collections::HashSet,
+    error, fmt,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    expression::FilterExpression,
+    utils::{self, char_code_enum},
+};
+
+/// The offset of the symbol in every TOPS and DEEP message carrying one, i.e. all but system events
+const SYMBOL_OFFSET: usize = 10;
+const SYMBOL_LENGTH: usize = 8;
+/// The offset of the timestamp in every TOPS and DEEP message
+const TIMESTAMP_OFFSET: usize = 2;
+const SYSTEM_EVENT: u8 = 0x53;
+
+/// Pads a symbol into its 8-byte wire representation. Returns `None` if the symbol is too long.
+pub fn padded_symbol(symbol: &str) -> Option<[u8; SYMBOL_LENGTH]> {
+    let bytes = symbol.as_bytes();
+    let mut padded = [b' '; SYMBOL_LENGTH];
+    padded.get_mut(..bytes.len())?.copy_from_slice(bytes);
+    Some(padded)
+}
+
+/// The timestamp of an undecoded message
+pub fn raw_timestamp(message: &[u8]) -> Option<DateTime<Utc>> {
+    let (_, timestamp) = utils::timestamp(message.get(TIMESTAMP_OFFSET..)?).ok()?;
+    Some(timestamp)
+}
+
+/// The raw symbol of an undecoded message (padded with spaces), if it carries one
+pub fn raw_symbol(message: &[u8]) -> Option<&[u8; SYMBOL_LENGTH]> {
+    if message.first() == Some(&SYSTEM_EVENT) {
+        return None;
+    }
+    message
+        .get(SYMBOL_OFFSET..SYMBOL_OFFSET + SYMBOL_LENGTH)?
+        .try_into()
+        .ok()
+}
+
+char_code_enum! {
+    /// The type of a TOPS or DEEP message, identified by its first byte
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum MessageKind {
+        SystemEvent = b'S',
+        SecurityDirectory = b'D',
+        TradingStatus = b'H',
+        RetailLiquidityIndicator = b'I',
+        OperationalHaltStatus = b'O',
+        ShortSalePriceTestStatus = b'P',
+        SecurityEvent = b'E',
+        QuoteUpdate = b'Q',
+        TradeReport = b'T',
+        OfficialPrice = b'X',
+        TradeBreak = b'B',
+        AuctionInformation = b'A',
+        PriceLevelUpdateBuy = b'8',
+        PriceLevelUpdateSell = b'5',
+    }
+}
+
+impl MessageKind {
+    pub const ALL: [MessageKind; 14] = [
+        MessageKind::SystemEvent,
+        MessageKind::SecurityDirectory,
+        MessageKind::TradingStatus,
+        MessageKind::RetailLiquidityIndicator,
+        MessageKind::OperationalHaltStatus,
+        MessageKind::ShortSalePriceTestStatus,
+        MessageKind::SecurityEvent,
+        MessageKind::QuoteUpdate,
+        MessageKind::TradeReport,
+        MessageKind::OfficialPrice,
+        MessageKind::TradeBreak,
+        MessageKind::AuctionInformation,
+        MessageKind::PriceLevelUpdateBuy,
+        MessageKind::PriceLevelUpdateSell,
+    ];
+
+    /// The name of the type, in snake case (e.g. `trade_report`)
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageKind::SystemEvent => "system_event",
+            MessageKind::SecurityDirectory => "security_directory",
+            MessageKind::TradingStatus => "trading_status",
+            MessageKind::RetailLiquidityIndicator => "retail_liquidity_indicator",
+            MessageKind::OperationalHaltStatus => "operational_halt_status",
+            MessageKind::ShortSalePriceTestStatus => "short_sale_price_test_status",
+            MessageKind::SecurityEvent => "security_event",
+            MessageKind::QuoteUpdate => "quote_update",
+            MessageKind::TradeReport => "trade_report",
+            MessageKind::OfficialPrice => "official_price",
+            MessageKind::TradeBreak => "trade_break",
+            MessageKind::AuctionInformation => "auction_information",
+            MessageKind::PriceLevelUpdateBuy => "price_level_update_buy",
+            MessageKind::PriceLevelUpdateSell => "price_level_update_sell",
+        }
+    }
+
+    /// Finds a type by its name or its character code
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .or_else(|| name.parse().ok())
+    }
+
+    /// The type of an undecoded message, if it's a known one
+    pub fn of(message: &[u8]) -> Option<Self> {
+        Self::try_from(*message.first()?).ok()
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// A set of message types, as a bitmask
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MessageKinds(u16);
+
+impl MessageKinds {
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, kind: MessageKind) -> Self {
+        self.insert(kind);
+        self
+    }
+
+    pub fn insert(&mut self, kind: MessageKind) {
+        self.0 |= kind.bit();
+    }
+
+    pub fn contains(&self, kind: MessageKind) -> bool {
+        self.0 & kind.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<MessageKind> for MessageKinds {
+    fn from(kind: MessageKind) -> Self {
+        Self::empty().with(kind)
+    }
+}
+
+impl FromIterator<MessageKind> for MessageKinds {
+    fn from_iter<T: IntoIterator<Item = MessageKind>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::empty(), Self::with)
+    }
+}
+
+/// Error returned when parsing a malformed symbol pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPattern(pub String);
+
+impl fmt::Display for InvalidPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid symbol pattern {:?}", self.0)
+    }
+}
+
+impl error::Error for InvalidPattern {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PatternToken {
+    Byte(u8),
+    AnyByte,
+    AnySequence,
+    Class {
+        negated: bool,
+        ranges: Vec<(u8, u8)>,
+    },
+}
+
+impl PatternToken {
+    fn matches(&self, byte: u8) -> bool {
+        match self {
+            PatternToken::Byte(expected) => byte == *expected,
+            PatternToken::AnyByte | PatternToken::AnySequence => true,
+            PatternToken::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(low, high)| (*low..=*high).contains(&byte))
+                    != *negated
+            }
+        }
+    }
+}
+
+/// A glob-like symbol pattern, e.g. `SPY*`
+///
+/// `*` matches any (possibly empty) sequence of characters, `?` any single character, and `[...]` any character of a
+/// class, such as `[A-C]` or `[!X]` (any character but `X`). Other characters match themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolPattern {
+    tokens: Vec<PatternToken>,
+}
+
+impl SymbolPattern {
+    pub fn matches(&self, symbol: &str) -> bool {
+        self.matches_bytes(symbol.as_bytes())
+    }
+
+    fn matches_bytes(&self, symbol: &[u8]) -> bool {
+        let (mut token, mut byte) = (0, 0);
+        // The position following the last `*` seen, and the symbol byte it's currently matched up to
+        let mut backtrack = None;
+
+        while byte < symbol.len() {
+            match self.tokens.get(token) {
+                Some(PatternToken::AnySequence) => {
+                    token += 1;
+                    backtrack = Some((token, byte));
+                }
+                Some(expected) if expected.matches(symbol[byte]) => {
+                    token += 1;
+                    byte += 1;
+                }
+                _ => match backtrack {
+                    Some((star_token, star_byte)) => {
+                        token = star_token;
+                        byte = star_byte + 1;
+                        backtrack = Some((star_token, byte));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        self.tokens[token..]
+            .iter()
+            .all(|token| *token == PatternToken::AnySequence)
+    }
+}
+
+impl FromStr for SymbolPattern {
+    type Err = InvalidPattern;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidPattern(s.to_string());
+        let mut bytes = s.bytes();
+        let mut tokens = Vec::new();
+
+        while let Some(byte) = bytes.next() {
+            tokens.push(match byte {
+                b'*' => PatternToken::AnySequence,
+                b'?' => PatternToken::AnyByte,
+                b'[' => {
+                    let mut negated = false;
+                    let mut ranges = Vec::new();
+                    loop {
+                        match bytes.next().ok_or_else(invalid)? {
+                            b']' if !ranges.is_empty() => break,
+                            b'!' if ranges.is_empty() && !negated => negated = true,
+                            b'-' if !ranges.is_empty() => {
+                                let high = bytes.next().ok_or_else(invalid)?;
+                                let (low, _) = ranges.pop().expect("a range precedes the dash");
+                                ranges.push((low, high));
+                            }
+                            low => ranges.push((low, low)),
+                        }
+                    }
+                    PatternToken::Class { negated, ranges }
+                }
+                _ => PatternToken::Byte(byte),
+            });
+        }
+
+        Ok(Self { tokens })
+    }
+}
+
+/// Selects messages from their raw bytes, so the others can be skipped without being decoded
+///
+/// The default filter accepts every message.
+#[derive(Clone, Debug, Default)]
+pub struct MessageFilter {
+    kinds: Option<MessageKinds>,
+    start: Option<Bound<DateTime<Utc>>>,
+    end: Option<Bound<DateTime<Utc>>>,
+    symbols: Option<HashSet<[u8; SYMBOL_LENGTH]>>,
+    patterns: Vec<SymbolPattern>,
+    expression: Option<FilterExpression>,
+}
+
+impl MessageFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accepts the messages of the given types. Messages of unknown types are rejected.
+    pub fn kinds(mut self, kinds: impl Into<MessageKinds>) -> Self {
+        self.kinds = Some(kinds.into());
+        self
+    }
+
+    /// Only accepts the messages timestamped within the range
+    ///
+    /// Messages are assumed to be in chronological order, as sent by IEX, so readers may skip the data before the
+    /// range and stop at the first message after it.
+    pub fn time_range(mut self, range: impl RangeBounds<DateTime<Utc>>) -> Self {
+        self.start = Some(range.start_bound().cloned());
+        self.end = Some(range.end_bound().cloned());
+        self
+    }
+
+    /// Whether all the messages of a segment sent at some time precede the time range
+    pub(crate) fn precedes(&self, send_time: DateTime<Utc>) -> bool {
+        match self.start {
+            Some(Bound::Included(start)) => send_time < start,
+            Some(Bound::Excluded(start)) => send_time <= start,
+            Some(Bound::Unbounded) | None => false,
+        }
+    }
+
+    /// Whether a raw message follows the time range
+    pub(crate) fn follows(&self, message: &[u8]) -> bool {
+        let Some(timestamp) = raw_timestamp(message) else {
+            return false;
+        };
+        match self.end {
+            Some(Bound::Included(end)) => timestamp > end,
+            Some(Bound::Excluded(end)) => timestamp >= end,
+            Some(Bound::Unbounded) | None => false,
+        }
+    }
+
+    /// Only accepts the messages of the given symbols, along with the messages without any symbol (e.g. system
+    /// events). Symbols longer than 8 bytes can't match any message.
+    pub fn symbols<'a>(mut self, symbols: impl IntoIterator<Item = &'a str>) -> Self {
+        self.symbols
+            .get_or_insert_with(HashSet::new)
+            .extend(symbols.into_iter().filter_map(padded_symbol));
+        self
+    }
+
+    /// Also accepts the messages whose symbol matches any of the patterns, along with the messages without any symbol
+    pub fn symbol_patterns(mut self, patterns: impl IntoIterator<Item = SymbolPattern>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// Only accepts the messages accepted by an expression, along with the other criteria
+    pub fn expression(mut self, expression: FilterExpression) -> Self {
+        self.expression = Some(expression);
+        self
+    }
+
+    /// Whether a raw message is accepted
+    pub fn accepts(&self, message: &[u8]) -> bool {
+        if let Some(expression) = &self.expression {
+            if !expression.accepts(message) {
+                return false;
+            }
+        }
+
+        if let Some(kinds) = self.kinds {
+            if !MessageKind::of(message).is_some_and(|kind| kinds.contains(kind)) {
+                return false;
+            }
+        }
+
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if !raw_timestamp(message).is_some_and(|timestamp| (start, end).contains(&timestamp)) {
+                return false;
+            }
+        }
+
+        let Some(symbol) = raw_symbol(message) else {
+            return true;
+        };
+        if self.symbols.is_none() && self.patterns.is_empty() {
+            return true;
+        }
+        self.symbols
+            .as_ref()
+            .is_some_and(|symbols| symbols.contains(symbol))
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches_bytes(symbol.trim_ascii_end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hist::{
+            tests::{capture, segment},
+            HistReader,
+        },
+        message_protocol_ids,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    const SYSTEM_EVENT: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+    const ZIEXT_TRADE: [u8; 38] = [
+        0x54, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54,
+        0x20, 0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x96, 0x8F, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn padding() {
+        assert_eq!(padded_symbol("ZIEXT"), Some(*b"ZIEXT   "));
+        assert_eq!(padded_symbol("ABCDEFGH"), Some(*b"ABCDEFGH"));
+        assert_eq!(padded_symbol("ABCDEFGHI"), None);
+    }
+
+    #[test]
+    fn message_kinds() {
+        let kinds = [MessageKind::TradeReport, MessageKind::SystemEvent]
+            .into_iter()
+            .collect::<MessageKinds>();
+        assert!(kinds.contains(MessageKind::TradeReport));
+        assert!(!kinds.contains(MessageKind::QuoteUpdate));
+        assert_eq!(
+            MessageKind::of(&ZIEXT_TRADE),
+            Some(MessageKind::TradeReport)
+        );
+
+        let filter = MessageFilter::new().kinds(kinds);
+        assert!(filter.accepts(&ZIEXT_TRADE));
+        assert!(filter.accepts(&SYSTEM_EVENT));
+        assert!(!filter.accepts(&[0x51; 42]));
+        assert!(!filter.accepts(&[0xFF; 42]));
+        assert!(!filter.accepts(&[]));
+
+        let filter = MessageFilter::new().kinds(MessageKind::QuoteUpdate);
+        assert!(!filter.accepts(&ZIEXT_TRADE));
+    }
+
+    #[test]
+    fn time_range() {
+        let at = |timestamp: i64| {
+            let mut trade = ZIEXT_TRADE;
+            trade[2..10].copy_from_slice(&timestamp.to_le_bytes());
+            trade
+        };
+        let sent_at = |timestamp: i64, messages: &[&[u8]]| {
+            let mut segment = segment(message_protocol_ids::TOPS, 1, messages);
+            segment[32..40].copy_from_slice(&timestamp.to_le_bytes());
+            segment
+        };
+        let invalid = [0x54; 12];
+
+        let filter = MessageFilter::new()
+            .time_range(DateTime::from_timestamp_nanos(20)..DateTime::from_timestamp_nanos(30));
+        assert!(filter.precedes(DateTime::from_timestamp_nanos(19)));
+        assert!(!filter.precedes(DateTime::from_timestamp_nanos(20)));
+        assert!(!filter.accepts(&at(19)));
+        assert!(filter.accepts(&at(29)));
+        assert!(filter.follows(&at(30)));
+
+        // Neither the segments sent before the range nor the messages after it are decoded
+        let capture = capture(&[
+            sent_at(15, &[&invalid, &at(15)]),
+            sent_at(25, &[&at(19), &at(20), &at(25)]),
+            sent_at(35, &[&at(29), &at(30), &invalid]),
+            sent_at(45, &[&invalid]),
+        ]);
+        let messages = HistReader::new(&capture[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .with_filter(filter)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let timestamps = messages
+            .iter()
+            .filter_map(Tops1_6Message::timestamp)
+            .map(|timestamp| timestamp.timestamp_nanos_opt().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, [20, 25, 29]);
+    }
+
+    #[test]
+    fn patterns() {
+        let pattern = |s: &str| s.parse::<SymbolPattern>().unwrap();
+
+        assert!(pattern("SPY*").matches("SPY"));
+        assert!(pattern("SPY*").matches("SPYG"));
+        assert!(!pattern("SPY*").matches("SPX"));
+        assert!(pattern("Z?ZZT").matches("ZVZZT"));
+        assert!(pattern("Z[IV]*T").matches("ZIEXT"));
+        assert!(pattern("Z[IV]*T").matches("ZVZZT"));
+        assert!(!pattern("Z[!IV]*").matches("ZVZZT"));
+        assert!(pattern("[A-C]*").matches("BRK.B"));
+        assert!(pattern("*.B").matches("BRK.B"));
+        assert!(!pattern("*.B").matches("BRK.A"));
+        assert!(pattern("*").matches(""));
+        assert!(!pattern("?").matches(""));
+        assert!("[AB".parse::<SymbolPattern>().is_err());
+        assert!("[]".parse::<SymbolPattern>().is_err());
+
+        let filter = MessageFilter::new()
+            .symbols(["AAPL"])
+            .symbol_patterns([pattern("ZI*")]);
+        let mut aapl_trade = ZIEXT_TRADE;
+        aapl_trade[10..15].copy_from_slice(b"AAPL ");
+        let mut zvzzt_trade = ZIEXT_TRADE;
+        zvzzt_trade[10..15].copy_from_slice(b"ZVZZT");
+        assert!(filter.accepts(&ZIEXT_TRADE));
+        assert!(filter.accepts(&aapl_trade));
+        assert!(!filter.accepts(&zvzzt_trade));
+        assert!(filter.accepts(&SYSTEM_EVENT));
+    }
+
+    #[test]
+    fn symbol_allow_list() {
+        let filter = MessageFilter::new().symbols(["ZIEXT", "ZXZZT"]);
+        assert!(filter.accepts(&ZIEXT_TRADE));
+        assert!(filter.accepts(&SYSTEM_EVENT));
+
+        let mut zvzzt_trade = ZIEXT_TRADE;
+        zvzzt_trade[10..15].copy_from_slice(b"ZVZZT");
+        assert!(!filter.accepts(&zvzzt_trade));
+        assert!(MessageFilter::new().accepts(&zvzzt_trade));
+
+        // The rejected messages aren't even decoded
+        let invalid = &zvzzt_trade[..20];
+        let capture = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[invalid, &zvzzt_trade, &ZIEXT_TRADE, &SYSTEM_EVENT],
+        )]);
+        let messages = HistReader::new(&capture[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .with_filter(filter)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].symbol().map(String::as_str), Some("ZIEXT"));
+    }
+}
@@ -0,0 +1,367 @@
+//! Allocation-free decoding, for consumers which forbid the allocator on their hot path
+//!
+//! Messages decoded with an inline [`Symbol`] (e.g. `tops_1_6_message::<Symbol>`) don't allocate, as the other
+//! fields of every message are inline too. [`SegmentView`] reads the messages of an IEX-TP segment without collecting
+//! them, and [`Price`] keeps prices in their fixed-point wire representation.
+
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    error, fmt,
+    hash::{Hash, Hasher},
+    ops::{Add, Deref, Sub},
+};
+
+use chrono::{DateTime, Utc};
+use nom::{
+    bytes::complete::{tag, take},
+    number::complete::{le_i64, le_u16, le_u32},
+    IResult, Parser as _,
+};
+
+use crate::utils;
+
+/// The length of symbols on the wire
+const SYMBOL_LENGTH: usize = 8;
+const SEGMENT_HEADER_LENGTH: usize = 40;
+
+/// The error of converting a string longer than 8 bytes into a [`Symbol`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolTooLong(pub usize);
+
+impl fmt::Display for SymbolTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "symbol of {} bytes, longer than 8", self.0)
+    }
+}
+
+impl error::Error for SymbolTooLong {}
+
+/// A symbol of up to 8 bytes, stored inline
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Symbol {
+    bytes: [u8; SYMBOL_LENGTH],
+    length: u8,
+}
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        // Symbols are only built from strings, and never split within a character
+        std::str::from_utf8(&self.bytes[..self.length as usize]).unwrap_or_default()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Symbol {
+    type Error = SymbolTooLong;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        let mut bytes = [0; SYMBOL_LENGTH];
+        bytes
+            .get_mut(..s.len())
+            .ok_or(SymbolTooLong(s.len()))?
+            .copy_from_slice(s.as_bytes());
+        Ok(Symbol {
+            bytes,
+            length: s.len() as u8,
+        })
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Symbols hash and compare as their strings, so maps keyed by symbols can be looked up by `&str`
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// A price in its wire representation: a fixed-point number with 4 digits to the right of an implied decimal point
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(pub i64);
+
+impl Price {
+    /// The price of a decoded message, e.g. `Price::from_f64(quote.bid_price)`. The conversion is exact for decoded
+    /// prices, and rounds other ones to the nearest representable price.
+    pub fn from_f64(price: f64) -> Self {
+        Price(utils::price_key(price))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        utils::key_price(self.0)
+    }
+}
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        Price(self.0 + other.0)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        Price(self.0 - other.0)
+    }
+}
+
+/// Formats the price with its 4 decimal digits, e.g. `99.0500`
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let units = self.0.unsigned_abs();
+        write!(f, "{sign}{}.{:04}", units / 10_000, units % 10_000)
+    }
+}
+
+/// Parses an IEX Price into its fixed-point representation, see [`utils::price`]
+pub fn price(input: &[u8]) -> IResult<&[u8], Price> {
+    let (input, price) = le_i64.parse(input)?;
+    Ok((input, Price(price)))
+}
+
+/// An IEX-TP segment, read in place: unlike [`IexTp1Segment`](crate::iex_tp::IexTp1Segment), its messages aren't
+/// collected, but iterated over
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentView<'a> {
+    pub message_protocol_id: u16,
+    pub channel_id: u32,
+    pub session_id: u32,
+    pub send_time: DateTime<Utc>,
+    pub message_count: u16,
+    pub first_message_sequence_no: i64,
+    pub stream_offset: i64,
+    payload: &'a [u8],
+}
+
+impl<'a> SegmentView<'a> {
+    /// Reads a version 1 segment, checking that its payload holds exactly the announced messages
+    pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (input, _) = tag([1u8, 0u8]).parse(input)?;
+        let (input, message_protocol_id) = le_u16.parse(input)?;
+        let (input, channel_id) = le_u32.parse(input)?;
+        let (input, session_id) = le_u32.parse(input)?;
+        let (input, payload_length) = le_u16.parse(input)?;
+        let (input, message_count) = le_u16.parse(input)?;
+        let (input, stream_offset) = le_i64.parse(input)?;
+        let (input, first_message_sequence_no) = le_i64.parse(input)?;
+        let (input, send_time) = utils::timestamp.parse(input)?;
+        let (input, payload) = take(payload_length).parse(input)?;
+
+        let segment = SegmentView {
+            message_protocol_id,
+            channel_id,
+            session_id,
+            send_time,
+            message_count,
+            first_message_sequence_no,
+            stream_offset,
+            payload,
+        };
+        let mut messages = segment.messages();
+        if messages.by_ref().count() != message_count as usize || !messages.rest.is_empty() {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                payload,
+                nom::error::ErrorKind::Count,
+            )));
+        }
+        Ok((input, segment))
+    }
+
+    /// The length of the segment on the wire
+    pub fn encoded_len(&self) -> usize {
+        SEGMENT_HEADER_LENGTH + self.payload.len()
+    }
+
+    pub fn messages(&self) -> SegmentMessages<'a> {
+        SegmentMessages { rest: self.payload }
+    }
+}
+
+/// The messages of a [`SegmentView`]
+#[derive(Clone, Debug)]
+pub struct SegmentMessages<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for SegmentMessages<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let (length, rest) = self.rest.split_first_chunk::<2>()?;
+        let length = u16::from_le_bytes(*length) as usize;
+        let message = rest.get(..length)?;
+        self.rest = &rest[length..];
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        collections::HashSet,
+    };
+
+    use crate::{
+        hist::tests::segment,
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+        tops::{tops_1_6_message, Tops1_6Message},
+    };
+
+    use super::*;
+
+    /// Counts the allocations of each thread, so that tests running in parallel don't disturb each other
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn symbols() {
+        let symbol = Symbol::try_from("ZIEXT").unwrap();
+        assert_eq!(symbol, "ZIEXT");
+        assert_eq!(symbol.len(), 5);
+        assert_eq!(format!("{symbol} {symbol:?}"), "ZIEXT \"ZIEXT\"");
+        assert_eq!(Symbol::try_from("ABCDEFGHI"), Err(SymbolTooLong(9)));
+        assert_eq!(Symbol::default(), "");
+        assert!(Symbol::try_from("ZIEXT").unwrap() > Symbol::try_from("ZIEX").unwrap());
+
+        let symbols = HashSet::from([symbol]);
+        assert!(symbols.contains("ZIEXT"));
+
+        assert_eq!(Price::from_f64(99.05), Price(990500));
+        assert_eq!(Price(990500).to_string(), "99.0500");
+        assert_eq!(Price(-5).to_string(), "-0.0005");
+        assert_eq!(Price(990700) - Price(990500), Price(200));
+        assert_eq!(price(&990500i64.to_le_bytes()).unwrap().1, Price(990500));
+    }
+
+    #[test]
+    fn segment_views() {
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            7,
+            &[&spec::QUOTE_UPDATE, &spec::TRADE_REPORT],
+        );
+        let (_, IexTpSegment::V1(expected)) = iex_tp_segment(&encoded).unwrap();
+        let (rest, view) = SegmentView::parse(&encoded).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(view.encoded_len(), encoded.len());
+        assert_eq!(view.send_time, expected.send_time);
+        assert_eq!(view.first_message_sequence_no, 7);
+        assert!(view.messages().eq(expected.messages));
+
+        // Too many and too few messages for the payload
+        let mut invalid = encoded.clone();
+        invalid[14] = 3;
+        assert!(SegmentView::parse(&invalid).is_err());
+        invalid[14] = 1;
+        assert!(SegmentView::parse(&invalid).is_err());
+    }
+
+    #[test]
+    fn no_allocations() {
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[
+                &spec::SYSTEM_EVENT,
+                &spec::SECURITY_DIRECTORY,
+                &spec::TRADING_STATUS,
+                &spec::QUOTE_UPDATE,
+                &spec::TRADE_REPORT,
+                &spec::AUCTION_INFORMATION,
+            ],
+        );
+
+        let before = ALLOCATIONS.with(Cell::get);
+        let (_, view) = SegmentView::parse(&encoded).unwrap();
+        let mut total = Price(0);
+        for message in view.messages() {
+            match tops_1_6_message::<Symbol>(message).unwrap().1 {
+                Tops1_6Message::QuoteUpdate(quote) => {
+                    total = total + Price::from_f64(quote.bid_price)
+                }
+                Tops1_6Message::TradeReport(trade) => total = total + Price::from_f64(trade.price),
+                _ => {}
+            }
+        }
+        assert_eq!(ALLOCATIONS.with(Cell::get), before);
+        assert_eq!(total, Price(2 * 990500));
+    }
+}
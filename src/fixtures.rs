@@ -0,0 +1,36 @@
+//! Messages for the tests of the crate, whose fields can be overridden with the struct update syntax
+
+use chrono::DateTime;
+
+use crate::tops::{MarketSession, QuoteUpdate, SaleCondition, TradeReport};
+
+/// A quote of the regular session, for 100 shares on each side
+pub(crate) fn quote<S>(
+    symbol: S,
+    timestamp: i64,
+    bid_price: f64,
+    ask_price: f64,
+) -> QuoteUpdate<S> {
+    QuoteUpdate {
+        available: true,
+        market_session: MarketSession::Regular,
+        timestamp: DateTime::from_timestamp_nanos(timestamp),
+        symbol,
+        bid_size: 100,
+        bid_price,
+        ask_size: 100,
+        ask_price,
+    }
+}
+
+/// A trade of the regular session, without any sale condition
+pub(crate) fn trade<S>(symbol: S, timestamp: i64, price: f64, size: u32) -> TradeReport<S> {
+    TradeReport {
+        sale_condition: SaleCondition::default(),
+        timestamp: DateTime::from_timestamp_nanos(timestamp),
+        symbol,
+        size,
+        price,
+        id: 1,
+    }
+}
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, hash::Hash, io::Read};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    hist::{self, HistReader},
+    tops::{
+        OperationalHaltStatus, OperationalHaltStatusType, Tops1_6Message, TradingStatus,
+        TradingStatusReason, TradingStatusType,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HaltKind {
+    /// A trading halt or pause, starting with the given trading status
+    Trading(TradingStatusType),
+    /// An IEX specific operational halt
+    Operational,
+}
+
+/// A period during which a symbol didn't trade on IEX
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HaltInterval<S> {
+    pub symbol: S,
+    pub kind: HaltKind,
+    /// The reason given when the halt started, `None` for operational halts
+    pub reason: Option<TradingStatusReason>,
+    pub start: DateTime<Utc>,
+    /// `None` if the symbol hadn't resumed by the end of the data
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl<S> HaltInterval<S> {
+    /// Whether the halt was in force at some point in time. The interval includes its start but not its end.
+    pub fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        self.start <= timestamp && self.end.is_none_or(|end| timestamp < end)
+    }
+
+    pub fn duration(&self) -> Option<TimeDelta> {
+        Some(self.end? - self.start)
+    }
+}
+
+/// Finds the interval of a symbol's timeline in force at some point in time
+pub fn halt_at<S>(
+    intervals: &[HaltInterval<S>],
+    timestamp: DateTime<Utc>,
+) -> Option<&HaltInterval<S>> {
+    intervals
+        .iter()
+        .take_while(|interval| interval.start <= timestamp)
+        .find(|interval| interval.contains(timestamp))
+}
+
+/// Extracts the halt and resume intervals of every symbol
+///
+/// A trading halt starts when the trading status leaves `Trading` and lasts until it returns to it, even if the status
+/// goes through several non-trading states (e.g. an order acceptance period following a halt). Operational halts are
+/// tracked separately and may overlap trading halts.
+#[derive(Clone, Debug)]
+pub struct HaltTimeline<S> {
+    trading_halts: HashMap<S, HaltInterval<S>>,
+    operational_halts: HashMap<S, HaltInterval<S>>,
+    intervals: HashMap<S, Vec<HaltInterval<S>>>,
+}
+
+impl<S> Default for HaltTimeline<S> {
+    fn default() -> Self {
+        Self {
+            trading_halts: HashMap::new(),
+            operational_halts: HashMap::new(),
+            intervals: HashMap::new(),
+        }
+    }
+}
+
+impl<S> HaltTimeline<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn close(&mut self, mut interval: HaltInterval<S>, end: DateTime<Utc>) -> HaltInterval<S> {
+        interval.end = Some(end);
+        self.intervals
+            .entry(interval.symbol.clone())
+            .or_default()
+            .push(interval.clone());
+        interval
+    }
+
+    /// Returns the interval the status ends, if any
+    pub fn apply_trading_status(&mut self, status: &TradingStatus<S>) -> Option<HaltInterval<S>> {
+        if status.status == TradingStatusType::Trading {
+            let interval = self.trading_halts.remove(&status.symbol)?;
+            Some(self.close(interval, status.timestamp))
+        } else {
+            self.trading_halts
+                .entry(status.symbol.clone())
+                .or_insert_with(|| HaltInterval {
+                    symbol: status.symbol.clone(),
+                    kind: HaltKind::Trading(status.status),
+                    reason: Some(status.reason),
+                    start: status.timestamp,
+                    end: None,
+                });
+            None
+        }
+    }
+
+    /// Returns the interval the status ends, if any
+    pub fn apply_operational_halt_status(
+        &mut self,
+        status: &OperationalHaltStatus<S>,
+    ) -> Option<HaltInterval<S>> {
+        match status.status {
+            OperationalHaltStatusType::NotHalted => {
+                let interval = self.operational_halts.remove(&status.symbol)?;
+                Some(self.close(interval, status.timestamp))
+            }
+            OperationalHaltStatusType::Halted => {
+                self.operational_halts
+                    .entry(status.symbol.clone())
+                    .or_insert_with(|| HaltInterval {
+                        symbol: status.symbol.clone(),
+                        kind: HaltKind::Operational,
+                        reason: None,
+                        start: status.timestamp,
+                        end: None,
+                    });
+                None
+            }
+        }
+    }
+
+    /// Feeds a TOPS message to the timeline, returning the halt interval it ends, if any
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Option<HaltInterval<S>> {
+        match message {
+            Tops1_6Message::TradingStatus(status) => self.apply_trading_status(status),
+            Tops1_6Message::OperationalHaltStatus(status) => {
+                self.apply_operational_halt_status(status)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the intervals of every symbol, sorted by start. Halts still in force are left open.
+    pub fn finish(mut self) -> HashMap<S, Vec<HaltInterval<S>>> {
+        for interval in self
+            .trading_halts
+            .into_values()
+            .chain(self.operational_halts.into_values())
+        {
+            self.intervals
+                .entry(interval.symbol.clone())
+                .or_default()
+                .push(interval);
+        }
+        for intervals in self.intervals.values_mut() {
+            intervals.sort_by_key(|interval| interval.start);
+        }
+        self.intervals
+    }
+}
+
+/// Extracts the halt timeline of a TOPS HIST file in a single pass
+pub fn extract_halts<R, S>(
+    reader: HistReader<R>,
+) -> Result<HashMap<S, Vec<HaltInterval<S>>>, hist::Error>
+where
+    R: Read,
+    S: for<'a> TryFrom<&'a str> + Eq + Hash + Clone,
+{
+    let mut timeline = HaltTimeline::new();
+    for message in reader.messages::<Tops1_6Message<S>>() {
+        timeline.update(&message?);
+    }
+    Ok(timeline.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trading_status(
+        status: TradingStatusType,
+        reason: TradingStatusReason,
+        timestamp: i64,
+    ) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradingStatus(TradingStatus {
+            status,
+            timestamp: DateTime::from_timestamp_nanos(timestamp),
+            symbol: "ZIEXT",
+            reason,
+        })
+    }
+
+    #[test]
+    fn halt_through_order_acceptance_period() {
+        let mut timeline = HaltTimeline::new();
+
+        timeline.update(&trading_status(
+            TradingStatusType::Trading,
+            TradingStatusReason::NotApplicable,
+            10,
+        ));
+        timeline.update(&trading_status(
+            TradingStatusType::Halted,
+            TradingStatusReason::HaltNewsPending,
+            20,
+        ));
+        timeline.update(&trading_status(
+            TradingStatusType::OrderAcceptancePeriod,
+            TradingStatusReason::HaltNewsDisseminated,
+            30,
+        ));
+        let interval = timeline
+            .update(&trading_status(
+                TradingStatusType::Trading,
+                TradingStatusReason::NotApplicable,
+                40,
+            ))
+            .unwrap();
+
+        assert_eq!(interval.kind, HaltKind::Trading(TradingStatusType::Halted));
+        assert_eq!(interval.reason, Some(TradingStatusReason::HaltNewsPending));
+        assert_eq!(interval.duration(), Some(TimeDelta::nanoseconds(20)));
+
+        timeline.update(&Tops1_6Message::OperationalHaltStatus(
+            OperationalHaltStatus {
+                status: OperationalHaltStatusType::Halted,
+                timestamp: DateTime::from_timestamp_nanos(50),
+                symbol: "ZIEXT",
+            },
+        ));
+
+        let intervals = &timeline.finish()["ZIEXT"];
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[1].kind, HaltKind::Operational);
+        assert_eq!(intervals[1].end, None);
+
+        let at = DateTime::from_timestamp_nanos;
+        assert!(halt_at(intervals, at(15)).is_none());
+        assert_eq!(halt_at(intervals, at(25)), Some(&intervals[0]));
+        assert!(halt_at(intervals, at(40)).is_none());
+        assert_eq!(halt_at(intervals, at(60)), Some(&intervals[1]));
+    }
+}
@@ -0,0 +1,901 @@
+use std::{
+    collections::VecDeque,
+    error, fmt,
+    fs::File,
+    io::{self, BufReader, Read, Seek, Write},
+    marker::PhantomData,
+    net::{Ipv4Addr, SocketAddrV4},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use nom::IResult;
+
+use crate::{
+    config::ParserConfig,
+    deep::{deep_1_0_message, Deep1_0Message},
+    filter::{MessageFilter, MessageKind},
+    iex_tp::{iex_tp_segment, IexTp1Segment, IexTpSegment},
+    message_protocol_ids,
+    pcap::{
+        ethernet_udp_frame, CaptureFormat, PcapNgWriter, PcapReader, PcapWriter, LINKTYPE_ETHERNET,
+    },
+    progress::{Progress, ProgressReporter},
+    stats::Stats,
+    tops::{tops_1_6_message, SystemEvent, Tops1_6Message},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// A UDP payload of the capture isn't a valid IEX-TP segment
+    InvalidSegment {
+        /// The offset (in the capture) following the packet
+        position: u64,
+    },
+    /// A message of a segment couldn't be decoded
+    InvalidMessage {
+        sequence_number: i64,
+        message: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::InvalidSegment { position } => {
+                write!(f, "invalid IEX-TP segment before offset {position}")
+            }
+            Error::InvalidMessage {
+                sequence_number,
+                message,
+            } => write!(
+                f,
+                "invalid message (sequence number {sequence_number}, {} bytes)",
+                message.len()
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// An IEX-TP segment along with the time it was captured
+#[derive(Clone, Debug)]
+pub struct CapturedSegment<'a> {
+    pub capture_time: Option<DateTime<Utc>>,
+    pub segment: IexTp1Segment<'a>,
+    /// The UDP payload carrying the segment, as captured
+    pub payload: &'a [u8],
+}
+
+/// The bytes which [`HistReader::seek_time`] reads sequentially once it has narrowed its search down to them
+pub const SEEK_SCAN_LENGTH: u64 = 1 << 16;
+
+/// Reads the IEX-TP segments of a HIST file (a pcap or pcapng capture of the feed)
+///
+/// The reader doesn't decompress its input, so gzipped HIST files should be wrapped in a decompressing reader first.
+#[derive(Debug)]
+pub struct HistReader<R> {
+    pcap: PcapReader<R>,
+    /// The offset of the first record, following the header of the capture
+    start: u64,
+    progress: Option<ProgressReporter>,
+    config: ParserConfig,
+}
+
+impl HistReader<BufReader<File>> {
+    /// Opens an uncompressed HIST file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> HistReader<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let pcap = PcapReader::new(reader)?;
+        Ok(Self {
+            start: pcap.position(),
+            pcap,
+            progress: None,
+            config: ParserConfig::default(),
+        })
+    }
+
+    /// Decodes the messages according to a config, see [`HistReader::messages`]
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Reports the progress of the reader through its input, as segments are read
+    pub fn with_progress(mut self, reporter: ProgressReporter) -> Self {
+        self.progress = Some(reporter);
+        self
+    }
+
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress.as_ref().map(ProgressReporter::progress)
+    }
+
+    /// The number of bytes consumed from the underlying reader so far
+    pub fn position(&self) -> u64 {
+        self.pcap.position()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.pcap.into_inner()
+    }
+
+    /// Reads the next segment, skipping any packets which aren't UDP datagrams. Returns `None` at the end of the file.
+    pub fn next_segment(&mut self) -> Result<Option<CapturedSegment<'_>>, Error> {
+        loop {
+            match self.pcap.next_packet()? {
+                None => {
+                    if let Some(progress) = &mut self.progress {
+                        progress.finish(self.pcap.position());
+                    }
+                    return Ok(None);
+                }
+                Some(packet) if packet.udp_payload().is_some() => break,
+                Some(_) => {}
+            }
+        }
+
+        let packet = self.pcap.last_packet().expect("a packet was just read");
+        let payload = packet.udp_payload().expect("the packet is a UDP datagram");
+        match iex_tp_segment(payload) {
+            Ok((_, IexTpSegment::V1(segment))) => {
+                if let Some(progress) = &mut self.progress {
+                    progress.segment(
+                        self.pcap.position(),
+                        segment.messages.len(),
+                        segment.send_time,
+                    );
+                }
+                Ok(Some(CapturedSegment {
+                    capture_time: packet.timestamp,
+                    segment,
+                    payload,
+                }))
+            }
+            Err(_) => Err(Error::InvalidSegment {
+                position: self.pcap.position(),
+            }),
+        }
+    }
+
+    /// Converts the reader into an iterator over the decoded messages of a single protocol, decoded according to the
+    /// config of the reader
+    pub fn messages<M>(self) -> Messages<R, M>
+    where
+        M: Message,
+    {
+        Messages {
+            config: self.config.clone(),
+            reader: self,
+            pending: VecDeque::new(),
+            filter: MessageFilter::new(),
+            stats: None,
+            ended: false,
+            _message: PhantomData,
+        }
+    }
+
+    /// Calls `f` with every TOPS message of the file, whose symbol borrows from the segment carrying it (see
+    /// [`IexTp1Segment::tops_messages`]), so that no symbol is allocated. Each message only lives for the duration of
+    /// its call. The config of the reader isn't applied.
+    ///
+    /// Stops at the first segment or message which can't be decoded.
+    pub fn for_each_tops_message<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(Tops1_6Message<&str>),
+    {
+        while let Some(CapturedSegment { segment, .. }) = self.next_segment()? {
+            if segment.message_protocol_id != message_protocol_ids::TOPS {
+                continue;
+            }
+            for ((sequence_number, message), decoded) in (segment.first_message_sequence_no..)
+                .zip(&segment.messages)
+                .zip(segment.tops_messages())
+            {
+                match decoded {
+                    Ok(decoded) => f(decoded),
+                    Err(_) => {
+                        return Err(Error::InvalidMessage {
+                            sequence_number,
+                            message: message.to_vec(),
+                        })
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R> HistReader<R>
+where
+    R: Read + Seek,
+{
+    /// Moves to an offset of the file, which must be the start of a packet record (e.g. a past
+    /// [`HistReader::position`], or an entry of a [`HistIndex`](crate::index::HistIndex))
+    pub fn seek(&mut self, offset: u64) -> Result<(), Error> {
+        Ok(self.pcap.seek(offset)?)
+    }
+
+    /// Moves to the first segment sent at or after a time (or to the end of the file), without an index
+    ///
+    /// As the send times of the segments increase through the file, the file is bisected by offset: from each offset
+    /// tried, the reader scans forward to the next packet carrying a valid segment. The last [`SEEK_SCAN_LENGTH`] bytes
+    /// are read sequentially. This takes a few dozen small reads, against a read of the whole file without seeking.
+    pub fn seek_time(&mut self, time: DateTime<Utc>) -> Result<(), Error> {
+        // Reading the first segment also reads the interfaces of pcapng captures, which precede it
+        self.seek(self.start)?;
+        let mut low = match self.next_segment() {
+            Ok(Some(captured)) if captured.segment.send_time < time => self.position(),
+            Ok(Some(_)) | Ok(None) => return self.seek(self.start),
+            Err(Error::InvalidSegment { position }) => position,
+            Err(e) => return Err(e),
+        };
+        let mut high = self.pcap.capture_length()?;
+
+        // `low` is the start of a record preceding the target, and no segment starting from `high` precedes it
+        while high - low > SEEK_SCAN_LENGTH {
+            let middle = low + (high - low) / 2;
+            let mut send_time = None;
+            let found = self.pcap.resync(middle, high - middle, |packet| {
+                send_time = packet
+                    .udp_payload()
+                    .and_then(|payload| iex_tp_segment(payload).ok())
+                    .map(|(_, IexTpSegment::V1(segment))| segment.send_time);
+                send_time.is_some()
+            })?;
+            match (found, send_time) {
+                (Some(offset), Some(send_time)) if send_time < time => low = offset,
+                _ => high = middle,
+            }
+        }
+
+        self.seek(low)?;
+        loop {
+            let position = self.position();
+            match self.next_segment() {
+                Ok(Some(captured)) if captured.segment.send_time < time => {}
+                Ok(Some(_)) => return self.seek(position),
+                Ok(None) => return Ok(()),
+                Err(Error::InvalidSegment { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Writes IEX-TP segments to a pcap or pcapng capture which [`HistReader`] can read, as UDP datagrams in Ethernet frames
+#[derive(Debug)]
+pub struct HistWriter<W> {
+    pcap: CaptureWriter<W>,
+    source: SocketAddrV4,
+    destination: SocketAddrV4,
+}
+
+#[derive(Debug)]
+enum CaptureWriter<W> {
+    Pcap(PcapWriter<W>),
+    PcapNg(PcapNgWriter<W>),
+}
+
+impl<W> HistWriter<W>
+where
+    W: Write,
+{
+    /// Creates a writer of a classic pcap capture, sending the datagrams from 10.0.0.1:10378 to 233.215.21.4:10378
+    pub fn new(writer: W) -> io::Result<Self> {
+        Self::with_format(writer, CaptureFormat::Pcap)
+    }
+
+    /// Creates a writer of a pcapng capture, structured as the HIST files distributed by IEX, sending the datagrams
+    /// from 10.0.0.1:10378 to 233.215.21.4:10378
+    pub fn pcapng(writer: W) -> io::Result<Self> {
+        Self::with_format(writer, CaptureFormat::PcapNg)
+    }
+
+    pub fn with_format(writer: W, format: CaptureFormat) -> io::Result<Self> {
+        Self::with_addresses(
+            writer,
+            format,
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 10378),
+            SocketAddrV4::new(Ipv4Addr::new(233, 215, 21, 4), 10378),
+        )
+    }
+
+    pub fn with_addresses(
+        writer: W,
+        format: CaptureFormat,
+        source: SocketAddrV4,
+        destination: SocketAddrV4,
+    ) -> io::Result<Self> {
+        let pcap = match format {
+            CaptureFormat::Pcap => CaptureWriter::Pcap(PcapWriter::new(writer, LINKTYPE_ETHERNET)?),
+            CaptureFormat::PcapNg => {
+                CaptureWriter::PcapNg(PcapNgWriter::new(writer, LINKTYPE_ETHERNET)?)
+            }
+        };
+        Ok(Self {
+            pcap,
+            source,
+            destination,
+        })
+    }
+
+    /// Writes an encoded segment (a UDP payload) captured at some time
+    pub fn write_payload(&mut self, capture_time: DateTime<Utc>, payload: &[u8]) -> io::Result<()> {
+        let frame = ethernet_udp_frame(self.source, self.destination, payload);
+        match &mut self.pcap {
+            CaptureWriter::Pcap(pcap) => pcap.write_packet(capture_time, &frame),
+            CaptureWriter::PcapNg(pcap) => pcap.write_packet(capture_time, &frame),
+        }
+    }
+
+    pub fn write_segment(
+        &mut self,
+        capture_time: DateTime<Utc>,
+        segment: &IexTp1Segment<'_>,
+    ) -> io::Result<()> {
+        self.write_payload(capture_time, &segment.encode())
+    }
+
+    pub fn into_inner(self) -> W {
+        match self.pcap {
+            CaptureWriter::Pcap(pcap) => pcap.into_inner(),
+            CaptureWriter::PcapNg(pcap) => pcap.into_inner(),
+        }
+    }
+}
+
+/// A message type which can be read from HIST files
+pub trait Message: Sized {
+    /// The message protocol ID of the segments carrying such messages
+    const MESSAGE_PROTOCOL_ID: u16;
+
+    type Symbol;
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Self>;
+
+    fn kind(&self) -> MessageKind;
+
+    /// The timestamp of the message, if it has been decoded
+    fn timestamp(&self) -> Option<DateTime<Utc>>;
+
+    /// The symbol of the message, if it carries one and has been decoded
+    fn symbol(&self) -> Option<&Self::Symbol>;
+
+    /// Rounds the prices of the message to the doubles nearest to their decimal values, see
+    /// [`PriceRepresentation`](crate::config::PriceRepresentation)
+    fn round_prices(&mut self) {}
+
+    /// The message as a system event, if it is one
+    fn system_event(&self) -> Option<&SystemEvent> {
+        None
+    }
+}
+
+impl<S> Message for Tops1_6Message<S>
+where
+    S: for<'a> TryFrom<&'a str>,
+{
+    const MESSAGE_PROTOCOL_ID: u16 = message_protocol_ids::TOPS;
+
+    type Symbol = S;
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        tops_1_6_message(input)
+    }
+
+    fn kind(&self) -> MessageKind {
+        Tops1_6Message::kind(self)
+    }
+
+    fn timestamp(&self) -> Option<DateTime<Utc>> {
+        Tops1_6Message::timestamp(self)
+    }
+
+    fn symbol(&self) -> Option<&S> {
+        Tops1_6Message::symbol(self)
+    }
+
+    fn round_prices(&mut self) {
+        Tops1_6Message::round_prices(self)
+    }
+
+    fn system_event(&self) -> Option<&SystemEvent> {
+        match self {
+            Tops1_6Message::SystemEvent(event) => Some(event),
+            _ => None,
+        }
+    }
+}
+
+impl<S> Message for Deep1_0Message<S>
+where
+    S: for<'a> TryFrom<&'a str>,
+{
+    const MESSAGE_PROTOCOL_ID: u16 = message_protocol_ids::DEEP_1_0;
+
+    type Symbol = S;
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        deep_1_0_message(input)
+    }
+
+    fn kind(&self) -> MessageKind {
+        Deep1_0Message::kind(self)
+    }
+
+    fn timestamp(&self) -> Option<DateTime<Utc>> {
+        Deep1_0Message::timestamp(self)
+    }
+
+    fn symbol(&self) -> Option<&S> {
+        Deep1_0Message::symbol(self)
+    }
+
+    fn round_prices(&mut self) {
+        Deep1_0Message::round_prices(self)
+    }
+
+    fn system_event(&self) -> Option<&SystemEvent> {
+        match self {
+            Deep1_0Message::SystemEvent(event) => Some(event),
+            _ => None,
+        }
+    }
+}
+
+/// Iterates over the decoded messages of a HIST file, skipping segments of other protocols
+///
+/// The messages own their symbols (e.g. as `String`s or [`Symbol`](crate::fixed::Symbol)s), as those can't borrow
+/// from the buffer of the segment, which is reused for the next one: the [`Message`] impls need symbol types
+/// convertible from strings of any lifetime, which `&str` and `Cow<str>` aren't. To decode messages with borrowed
+/// symbols, use [`HistReader::for_each_tops_message`] or the segments of [`HistReader::next_segment`].
+#[derive(Debug)]
+pub struct Messages<R, M> {
+    reader: HistReader<R>,
+    config: ParserConfig,
+    /// The messages decoded from the current segment, and the errors of those which couldn't be, in order
+    pending: VecDeque<Result<M, Error>>,
+    filter: MessageFilter,
+    stats: Option<Stats>,
+    /// Whether a message past the time range of the filter was seen
+    ended: bool,
+    _message: PhantomData<M>,
+}
+
+impl<R, M> Messages<R, M> {
+    /// Skips the messages rejected by the filter without decoding them. If the filter has a time range, the whole
+    /// segments sent before it are skipped, and the iteration ends at the first message past it.
+    pub fn with_filter(mut self, filter: MessageFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Counts the segments of the protocol and the messages accepted by the filter, see [`Messages::stats`]
+    pub fn with_stats(mut self) -> Self {
+        self.stats = Some(Stats::new());
+        self
+    }
+
+    /// The statistics of the messages read so far, if enabled by [`Messages::with_stats`]
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    pub fn get_ref(&self) -> &HistReader<R> {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> HistReader<R> {
+        self.reader
+    }
+}
+
+impl<R, M> Iterator for Messages<R, M>
+where
+    R: Read,
+    M: Message,
+{
+    type Item = Result<M, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.ended {
+                return None;
+            }
+
+            let segment = match self.reader.next_segment() {
+                Ok(Some(CapturedSegment { segment, .. })) => segment,
+                Ok(None) => return None,
+                Err(e) => {
+                    if let Some(stats) = &mut self.stats {
+                        stats.add_error(&e);
+                    }
+                    return Some(Err(e));
+                }
+            };
+
+            if segment.message_protocol_id != M::MESSAGE_PROTOCOL_ID
+                || self.filter.precedes(segment.send_time)
+            {
+                continue;
+            }
+            if let Some(stats) = &mut self.stats {
+                stats.add_segment_header(&segment);
+            }
+
+            for (sequence_number, message) in
+                (segment.first_message_sequence_no..).zip(&segment.messages)
+            {
+                if self.filter.follows(message) {
+                    self.ended = true;
+                    break;
+                }
+                if !self.filter.accepts(message) || !self.config.accepts(message) {
+                    continue;
+                }
+                if let Some(stats) = &mut self.stats {
+                    stats.add_message(message);
+                }
+                match self.config.decode(message, segment.send_time) {
+                    Ok(decoded) => self.pending.push_back(Ok(decoded)),
+                    Err(rejection) => {
+                        if let Some(stats) = &mut self.stats {
+                            stats.invalid_messages += 1;
+                        }
+                        if !self.config.tolerates(rejection) {
+                            self.pending.push_back(Err(Error::InvalidMessage {
+                                sequence_number,
+                                message: message.to_vec(),
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+
+    use chrono::TimeDelta;
+
+    use crate::utils::assert_matches;
+
+    use crate::{
+        sim::{Scenario, SimConfig, Simulator},
+        spec,
+        tops::{SystemEvent, SystemEventType},
+    };
+
+    use super::*;
+
+    // Builds a classic pcap capture of Ethernet frames carrying the given UDP payloads
+    pub(crate) fn capture(payloads: &[Vec<u8>]) -> Vec<u8> {
+        let mut capture = Vec::new();
+        capture.extend_from_slice(&0xa1b23c4du32.to_le_bytes());
+        capture.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0]);
+        capture.extend_from_slice(&1u32.to_le_bytes());
+
+        for payload in payloads {
+            let mut frame = vec![0u8; 12];
+            frame.extend_from_slice(&[0x08, 0x00, 0x45, 0x00]);
+            frame.extend_from_slice(&((28 + payload.len()) as u16).to_be_bytes());
+            frame.extend_from_slice(&[0, 0, 0x40, 0, 0x40, 17, 0, 0, 10, 0, 0, 1, 233, 215, 21, 4]);
+            frame.extend_from_slice(&[0x28, 0x8a, 0x28, 0x8a]);
+            frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+            frame.extend_from_slice(&[0, 0]);
+            frame.extend_from_slice(payload);
+
+            capture.extend_from_slice(&[0; 8]);
+            capture.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            capture.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            capture.extend_from_slice(&frame);
+        }
+
+        capture
+    }
+
+    pub(crate) fn segment(
+        message_protocol_id: u16,
+        first_sequence_no: i64,
+        messages: &[&[u8]],
+    ) -> Vec<u8> {
+        let payload_length: usize = messages.iter().map(|message| message.len() + 2).sum();
+
+        let mut segment = vec![1, 0];
+        segment.extend_from_slice(&message_protocol_id.to_le_bytes());
+        segment.extend_from_slice(&1u32.to_le_bytes());
+        segment.extend_from_slice(&0x42870000u32.to_le_bytes());
+        segment.extend_from_slice(&(payload_length as u16).to_le_bytes());
+        segment.extend_from_slice(&(messages.len() as u16).to_le_bytes());
+        segment.extend_from_slice(&0i64.to_le_bytes());
+        segment.extend_from_slice(&first_sequence_no.to_le_bytes());
+        segment.extend_from_slice(&1492448400000000000i64.to_le_bytes());
+        for message in messages {
+            segment.extend_from_slice(&(message.len() as u16).to_le_bytes());
+            segment.extend_from_slice(message);
+        }
+        segment
+    }
+
+    const SYSTEM_EVENT: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+
+    #[test]
+    fn segments() {
+        let capture = capture(&[
+            segment(message_protocol_ids::TOPS, 1, &[&SYSTEM_EVENT]),
+            segment(message_protocol_ids::TOPS, 2, &[]),
+        ]);
+        let mut reader = HistReader::new(&capture[..]).unwrap();
+
+        let first = reader.next_segment().unwrap().unwrap();
+        assert_eq!(first.segment.first_message_sequence_no, 1);
+        assert_eq!(first.segment.messages, [&SYSTEM_EVENT[..]]);
+        assert_eq!(
+            first.payload,
+            segment(message_protocol_ids::TOPS, 1, &[&SYSTEM_EVENT])
+        );
+
+        let second = reader.next_segment().unwrap().unwrap();
+        assert!(second.segment.messages.is_empty());
+
+        assert!(reader.next_segment().unwrap().is_none());
+    }
+
+    #[test]
+    fn writer() {
+        let capture = capture(&[segment(message_protocol_ids::TOPS, 1, &[&SYSTEM_EVENT])]);
+        let mut reader = HistReader::new(&capture[..]).unwrap();
+        let original = reader.next_segment().unwrap().unwrap();
+
+        for format in [CaptureFormat::Pcap, CaptureFormat::PcapNg] {
+            let mut writer = HistWriter::with_format(Vec::new(), format).unwrap();
+            let capture_time = DateTime::from_timestamp_nanos(1492448400000000001);
+            writer
+                .write_segment(capture_time, &original.segment)
+                .unwrap();
+            let written = writer.into_inner();
+
+            let mut reader = HistReader::new(&written[..]).unwrap();
+            let copy = reader.next_segment().unwrap().unwrap();
+            assert_eq!(copy.capture_time, Some(capture_time));
+            assert_eq!(copy.payload, original.payload);
+            assert!(reader.next_segment().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn simulated_capture() {
+        let simulated = Simulator::new(SimConfig::default()).collect::<Vec<_>>();
+
+        let mut writer = HistWriter::pcapng(Vec::new()).unwrap();
+        let mut stream_offset = 0;
+        for (sequence_number, message) in (1..).zip(&simulated) {
+            let encoded = message.encode().unwrap();
+            let send_time = message.timestamp().unwrap();
+            let segment = IexTp1Segment {
+                message_protocol_id: message_protocol_ids::TOPS,
+                channel_id: 1,
+                session_id: 1,
+                send_time,
+                messages: vec![&encoded],
+                first_message_sequence_no: sequence_number,
+                stream_offset,
+            };
+            writer.write_segment(send_time, &segment).unwrap();
+            stream_offset += encoded.len() as i64 + 2;
+        }
+        let written = writer.into_inner();
+
+        let messages = HistReader::new(&written[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(messages, simulated);
+    }
+
+    #[test]
+    fn seek_time() {
+        let scenario = Scenario::new(SimConfig {
+            duration: TimeDelta::minutes(10),
+            ..Default::default()
+        });
+        for mut writer in [
+            HistWriter::new(Vec::new()).unwrap(),
+            HistWriter::pcapng(Vec::new()).unwrap(),
+        ] {
+            scenario.write_hist(&mut writer).unwrap();
+            let written = writer.into_inner();
+            assert!(written.len() as u64 > 8 * SEEK_SCAN_LENGTH);
+
+            let mut send_times = Vec::new();
+            let mut reader = HistReader::new(Cursor::new(&written)).unwrap();
+            while let Some(captured) = reader.next_segment().unwrap() {
+                send_times.push(captured.segment.send_time);
+            }
+
+            let first = send_times[0];
+            let last = *send_times.last().unwrap();
+            for time in [
+                first - TimeDelta::seconds(1),
+                first,
+                first + TimeDelta::seconds(1),
+                first + (last - first) / 3,
+                send_times[send_times.len() / 2],
+                last,
+            ] {
+                reader.seek_time(time).unwrap();
+                let expected = send_times.iter().find(|&&send_time| send_time >= time);
+                let found = reader.next_segment().unwrap().unwrap();
+                assert_eq!(Some(&found.segment.send_time), expected);
+            }
+            reader.seek_time(last + TimeDelta::seconds(1)).unwrap();
+            assert!(reader.next_segment().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn messages() {
+        let capture = capture(&[
+            segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[&SYSTEM_EVENT, &SYSTEM_EVENT],
+            ),
+            segment(message_protocol_ids::DEEP_1_0, 1, &[&SYSTEM_EVENT]),
+            segment(message_protocol_ids::TOPS, 3, &[&SYSTEM_EVENT]),
+        ]);
+        let messages = HistReader::new(&capture[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_matches!(
+            messages[0],
+            Tops1_6Message::SystemEvent(SystemEvent {
+                event_type: SystemEventType::EndOfSystemHours,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn borrowed_messages() {
+        let capture = capture(&[
+            segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[&spec::QUOTE_UPDATE, &spec::TRADE_REPORT],
+            ),
+            segment(message_protocol_ids::DEEP_1_0, 1, &[&SYSTEM_EVENT]),
+            segment(message_protocol_ids::TOPS, 3, &[&[0xFF, 0xFF]]),
+        ]);
+        let mut symbols = Vec::new();
+        let result = HistReader::new(&capture[..])
+            .unwrap()
+            .for_each_tops_message(|message| symbols.push(message.symbol().map(|s| s.to_string())));
+
+        assert_eq!(
+            symbols,
+            [Some("ZIEXT".to_string()), Some("ZIEXT".to_string())]
+        );
+        assert_matches!(
+            result,
+            Err(Error::InvalidMessage {
+                sequence_number: 3,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_message() {
+        let capture = capture(&[segment(message_protocol_ids::TOPS, 7, &[&[0xFF, 0xFF]])]);
+        let mut messages = HistReader::new(&capture[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>();
+
+        assert_matches!(
+            messages.next(),
+            Some(Err(Error::InvalidMessage {
+                sequence_number: 7,
+                ..
+            }))
+        );
+        assert_matches!(messages.next(), None);
+    }
+
+    #[test]
+    fn invalid_message_within_segment() {
+        let event = |event_type| {
+            SystemEvent {
+                event_type,
+                timestamp: DateTime::from_timestamp_nanos(1_500_000_000_000_000_000),
+            }
+            .encode()
+            .unwrap()
+        };
+        let capture = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[
+                &event(SystemEventType::StartOfMessages),
+                &[0xFF, 0xFF],
+                &event(SystemEventType::StartOfSystemHours),
+            ],
+        )]);
+        let messages = HistReader::new(&capture[..])
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .collect::<Vec<_>>();
+
+        // The messages around the invalid one are still read
+        assert_eq!(messages.len(), 3);
+        assert_matches!(
+            messages[0],
+            Ok(Tops1_6Message::SystemEvent(SystemEvent {
+                event_type: SystemEventType::StartOfMessages,
+                ..
+            }))
+        );
+        assert_matches!(
+            messages[1],
+            Err(Error::InvalidMessage {
+                sequence_number: 2,
+                ..
+            })
+        );
+        assert_matches!(
+            messages[2],
+            Ok(Tops1_6Message::SystemEvent(SystemEvent {
+                event_type: SystemEventType::StartOfSystemHours,
+                ..
+            }))
+        );
+    }
+
+    #[test]
+    fn invalid_segment() {
+        let capture = capture(&[vec![0x02, 0x00]]);
+        let mut reader = HistReader::new(&capture[..]).unwrap();
+
+        assert_matches!(reader.next_segment(), Err(Error::InvalidSegment { .. }));
+    }
+}
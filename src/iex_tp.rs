@@ -1,14 +1,17 @@
+use std::io::{self, Write};
+
 use chrono::{DateTime, Utc};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take},
-    combinator::map,
+    combinator::{all_consuming, map},
+    error::Error,
     multi::count,
     number::complete::{le_i64, le_u16, le_u32},
     IResult, Parser as _,
 };
 
-use crate::utils;
+use crate::{deep::Deep1_0Message, tops::Tops1_6Message, utils};
 
 fn iex_tp_1_message(input: &[u8]) -> IResult<&[u8], &[u8]> {
     let (input, length) = le_u16.parse(input)?;
@@ -23,9 +26,111 @@ pub struct IexTp1Segment<'a> {
     pub send_time: DateTime<Utc>,
     pub messages: Vec<&'a [u8]>,
     pub first_message_sequence_no: i64,
+    /// The offset of the payload within the stream of message bytes of the session
+    pub stream_offset: i64,
 }
 
-fn iex_tp_1_segment(input: &[u8]) -> IResult<&[u8], IexTp1Segment> {
+impl<'a> IexTp1Segment<'a> {
+    /// The length of the segment on the wire
+    pub fn encoded_len(&self) -> usize {
+        40 + self
+            .messages
+            .iter()
+            .map(|message| message.len() + 2)
+            .sum::<usize>()
+    }
+
+    /// Writes the segment as it's sent on the wire
+    ///
+    /// # Panics
+    ///
+    /// Panics if a message or the payload is too long to be encoded (longer than 65535 bytes), or if the send time is
+    /// out of the range of the protocol's timestamps.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let payload_length = u16::try_from(self.encoded_len() - 40).expect("payload too long");
+        let message_count = u16::try_from(self.messages.len()).expect("too many messages");
+        let send_time = self
+            .send_time
+            .timestamp_nanos_opt()
+            .expect("send time out of range");
+
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&self.message_protocol_id.to_le_bytes())?;
+        writer.write_all(&self.channel_id.to_le_bytes())?;
+        writer.write_all(&self.session_id.to_le_bytes())?;
+        writer.write_all(&payload_length.to_le_bytes())?;
+        writer.write_all(&message_count.to_le_bytes())?;
+        writer.write_all(&self.stream_offset.to_le_bytes())?;
+        writer.write_all(&self.first_message_sequence_no.to_le_bytes())?;
+        writer.write_all(&send_time.to_le_bytes())?;
+        for message in &self.messages {
+            let length = u16::try_from(message.len()).expect("message too long");
+            writer.write_all(&length.to_le_bytes())?;
+            writer.write_all(message)?;
+        }
+        Ok(())
+    }
+
+    /// Splits the messages kept by a predicate into segments of consecutive messages, with the sequence numbers and
+    /// stream offsets they had in this segment
+    pub fn runs(&self, mut keep: impl FnMut(&[u8]) -> bool) -> Vec<IexTp1Segment<'a>> {
+        let mut runs = Vec::new();
+        let mut run = None::<IexTp1Segment>;
+        let mut stream_offset = self.stream_offset;
+
+        for (sequence_number, &message) in (self.first_message_sequence_no..).zip(&self.messages) {
+            if keep(message) {
+                run.get_or_insert_with(|| IexTp1Segment {
+                    messages: Vec::new(),
+                    first_message_sequence_no: sequence_number,
+                    stream_offset,
+                    ..self.clone()
+                })
+                .messages
+                .push(message);
+            } else {
+                runs.extend(run.take());
+            }
+            stream_offset += message.len() as i64 + 2;
+        }
+
+        runs.extend(run);
+        runs
+    }
+
+    /// Decodes the messages of the segment as TOPS messages, whose symbols may borrow from the segment (e.g. as
+    /// `Cow<'a, str>` or `&'a str`), unlike those decoded by [`Message::parse`](crate::hist::Message::parse)
+    ///
+    /// The message protocol ID of the segment isn't checked.
+    pub fn tops_messages<S>(
+        &self,
+    ) -> impl Iterator<Item = Result<Tops1_6Message<S>, nom::Err<Error<&'a [u8]>>>> + '_
+    where
+        S: TryFrom<&'a str>,
+    {
+        self.messages.iter().map(|&message| message.try_into())
+    }
+
+    /// Decodes the messages of the segment as DEEP messages, see [`IexTp1Segment::tops_messages`]
+    pub fn deep_messages<S>(
+        &self,
+    ) -> impl Iterator<Item = Result<Deep1_0Message<S>, nom::Err<Error<&'a [u8]>>>> + '_
+    where
+        S: TryFrom<&'a str>,
+    {
+        self.messages.iter().map(|&message| message.try_into())
+    }
+
+    /// Encodes the segment as it's sent on the wire, see [`IexTp1Segment::write_to`]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(self.encoded_len());
+        self.write_to(&mut encoded)
+            .expect("writing to a vector can't fail");
+        encoded
+    }
+}
+
+fn iex_tp_1_segment(input: &[u8]) -> IResult<&[u8], IexTp1Segment<'_>> {
     // Parse the version (0x01) and the reserved byte
     let (input, _) = tag([1u8, 0u8]).parse(input)?;
     let (input, message_protocol_id) = le_u16.parse(input)?;
@@ -33,13 +138,14 @@ fn iex_tp_1_segment(input: &[u8]) -> IResult<&[u8], IexTp1Segment> {
     let (input, session_id) = le_u32.parse(input)?;
     let (input, payload_length) = le_u16.parse(input)?;
     let (input, message_count) = le_u16.parse(input)?;
-    let (input, _stream_offset) = le_i64.parse(input)?; // We don't verify correctness using stream bytes
+    let (input, stream_offset) = le_i64.parse(input)?;
     let (input, first_message_sequence_no) = le_i64.parse(input)?;
     let (input, send_time) = utils::timestamp.parse(input)?;
 
     let (input, payload) = take(payload_length)(input)?;
-    let (litter, messages) = count(iex_tp_1_message, message_count.into()).parse(payload)?;
-    assert!(litter.is_empty());
+    // The payload must hold exactly the announced messages
+    let (_, messages) =
+        all_consuming(count(iex_tp_1_message, message_count.into())).parse(payload)?;
 
     Ok((
         input,
@@ -50,6 +156,7 @@ fn iex_tp_1_segment(input: &[u8]) -> IResult<&[u8], IexTp1Segment> {
             send_time,
             messages,
             first_message_sequence_no,
+            stream_offset,
         },
     ))
 }
@@ -60,7 +167,7 @@ pub enum IexTpSegment<'a> {
 }
 
 // Parse an outbound IEX-TP segment
-pub fn iex_tp_segment(input: &[u8]) -> IResult<&[u8], IexTpSegment> {
+pub fn iex_tp_segment(input: &[u8]) -> IResult<&[u8], IexTpSegment<'_>> {
     alt((map(iex_tp_1_segment, IexTpSegment::V1),)).parse(input)
     // todo!();
     // Ok((input, IexTpSegment { ??? }))
@@ -68,9 +175,14 @@ pub fn iex_tp_segment(input: &[u8]) -> IResult<&[u8], IexTpSegment> {
 
 #[cfg(test)]
 mod tests {
-    use std::assert_matches::assert_matches;
+    use crate::utils::assert_matches;
+
+    use std::borrow::Cow;
 
-    use crate::message_protocol_ids;
+    use crate::{
+        message_protocol_ids, spec,
+        tops::{QuoteUpdate, TradeReport},
+    };
 
     use super::*;
 
@@ -99,11 +211,13 @@ mod tests {
                     send_time: _,
                     messages: _,
                     first_message_sequence_no: 50122,
+                    stream_offset: 0x21A68C,
                 })
             )
         );
 
         let IexTpSegment::V1(inner_result) = result.1;
+        assert_eq!(inner_result.encode(), input);
         assert_eq!(
             inner_result.send_time,
             DateTime::from_timestamp_nanos(1471980632572839404) // 2016-08-23 15:30:32.572839404
@@ -127,4 +241,76 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn trailing_payload() {
+        let mut input = crate::hist::tests::segment(message_protocol_ids::TOPS, 1, &[&[0x53; 10]]);
+        // Announce no messages, leaving the payload unconsumed
+        input[14] = 0;
+        assert!(iex_tp_segment(&input).is_err());
+    }
+
+    #[test]
+    fn runs() {
+        let segment = IexTp1Segment {
+            message_protocol_id: message_protocol_ids::TOPS,
+            channel_id: 1,
+            session_id: 42,
+            send_time: DateTime::from_timestamp_nanos(0),
+            messages: vec![&[1], &[2, 2], &[3], &[4]],
+            first_message_sequence_no: 10,
+            stream_offset: 100,
+        };
+
+        let runs = segment.runs(|message| message[0] != 3);
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].messages, [&[1][..], &[2, 2]]);
+        assert_eq!(
+            (runs[0].first_message_sequence_no, runs[0].stream_offset),
+            (10, 100)
+        );
+        assert_eq!(runs[1].messages, [&[4][..]]);
+        assert_eq!(
+            (runs[1].first_message_sequence_no, runs[1].stream_offset),
+            (13, 110)
+        );
+        assert!(segment.runs(|_| false).is_empty());
+    }
+
+    #[test]
+    fn borrowed_messages() {
+        let payload = crate::hist::tests::segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&spec::QUOTE_UPDATE, &spec::TRADE_REPORT, &[0xFF, 0xFF]],
+        );
+        let (_, IexTpSegment::V1(segment)) = iex_tp_segment(&payload).unwrap();
+
+        let messages = segment.tops_messages::<Cow<str>>().collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3);
+        assert_matches!(
+            messages[0],
+            Ok(Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                symbol: Cow::Borrowed("ZIEXT"),
+                ..
+            }))
+        );
+        assert_matches!(
+            messages[1],
+            Ok(Tops1_6Message::TradeReport(TradeReport {
+                symbol: Cow::Borrowed("ZIEXT"),
+                ..
+            }))
+        );
+        assert!(messages[2].is_err());
+
+        let messages = segment.deep_messages::<&str>().collect::<Vec<_>>();
+        assert_matches!(
+            messages[1],
+            Ok(Deep1_0Message::TradeReport(TradeReport {
+                symbol: "ZIEXT",
+                ..
+            }))
+        );
+    }
 }
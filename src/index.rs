@@ -0,0 +1,252 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::hist::{self, HistReader};
+
+const MAGIC: &[u8; 8] = b"IEXIDX01";
+
+/// The offset of the first segment sent from some time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub send_time: DateTime<Utc>,
+    /// The offset of the packet record carrying the segment, in the uncompressed file
+    pub offset: u64,
+}
+
+/// A sparse index of a HIST file, mapping send times to offsets, to start reading the file at some time without
+/// scanning it from the start
+///
+/// An entry is recorded for the first segment of the file, then for the first segment of every interval (aligned to
+/// multiples of the interval since the POSIX epoch) which has any. The index is kept in a sidecar file next to the HIST
+/// file, see [`HistIndex::sidecar_path`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistIndex {
+    interval: TimeDelta,
+    entries: Vec<IndexEntry>,
+    segments: u64,
+    /// The length of the indexed file, to detect stale indices
+    file_length: u64,
+}
+
+impl HistIndex {
+    /// Indexes a whole (uncompressed) HIST file
+    pub fn build<R: Read>(
+        mut reader: HistReader<R>,
+        interval: TimeDelta,
+    ) -> Result<Self, hist::Error> {
+        assert!(
+            interval > TimeDelta::zero(),
+            "index interval must be positive"
+        );
+        let interval_nanos = interval.num_nanoseconds().unwrap_or(i64::MAX);
+
+        let mut index = Self {
+            interval,
+            entries: Vec::new(),
+            segments: 0,
+            file_length: 0,
+        };
+        let mut next_entry = None;
+        loop {
+            let offset = reader.position();
+            let Some(captured) = reader.next_segment()? else {
+                break;
+            };
+
+            let send_time = captured.segment.send_time;
+            index.segments += 1;
+            if next_entry.is_none_or(|next| send_time >= next) {
+                index.entries.push(IndexEntry { send_time, offset });
+                let nanos = send_time.timestamp_nanos_opt().unwrap_or_default();
+                next_entry = Some(DateTime::from_timestamp_nanos(
+                    nanos - nanos.rem_euclid(interval_nanos) + interval_nanos,
+                ));
+            }
+        }
+
+        index.file_length = reader.position();
+        Ok(index)
+    }
+
+    pub fn interval(&self) -> TimeDelta {
+        self.interval
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// The number of segments of the indexed file
+    pub fn segments(&self) -> u64 {
+        self.segments
+    }
+
+    /// The length of the indexed file
+    pub fn file_length(&self) -> u64 {
+        self.file_length
+    }
+
+    /// The entry to start reading from to see every segment sent from some time, i.e. the last one sent before it
+    pub fn lookup(&self, time: DateTime<Utc>) -> Option<&IndexEntry> {
+        let following = self.entries.partition_point(|entry| entry.send_time < time);
+        following.checked_sub(1).map(|index| &self.entries[index])
+    }
+
+    /// The ranges of offsets to read to search the file backwards from some time, latest first, as (start, end)
+    /// offsets. The first range starts at [`HistIndex::lookup`]'s entry (or the first one) and has no end, as it should
+    /// be read until the messages are past the time; the others each end where the previous one starts.
+    pub fn ranges_before(&self, time: DateTime<Utc>) -> Vec<(u64, Option<u64>)> {
+        let last = self
+            .entries
+            .partition_point(|entry| entry.send_time < time)
+            .max(1);
+        let starts = self.entries.iter().take(last).map(|entry| entry.offset);
+        let ends = self
+            .entries
+            .iter()
+            .skip(1)
+            .map(|entry| Some(entry.offset))
+            .take(last - 1)
+            .chain([None]);
+        let mut ranges = starts.zip(ends).collect::<Vec<_>>();
+        ranges.reverse();
+        ranges
+    }
+
+    /// The path of the sidecar index of a HIST file: the path of the file followed by `.idx`
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut path = path.as_ref().as_os_str().to_owned();
+        path.push(".idx");
+        PathBuf::from(path)
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(
+            &self
+                .interval
+                .num_nanoseconds()
+                .unwrap_or(i64::MAX)
+                .to_le_bytes(),
+        )?;
+        writer.write_all(&self.segments.to_le_bytes())?;
+        writer.write_all(&self.file_length.to_le_bytes())?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in &self.entries {
+            let send_time = entry.send_time.timestamp_nanos_opt().unwrap_or_default();
+            writer.write_all(&send_time.to_le_bytes())?;
+            writer.write_all(&entry.offset.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut read_u64 = || -> io::Result<u64> {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+
+        let magic = read_u64()?.to_le_bytes();
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not an index of a HIST file",
+            ));
+        }
+        let interval = TimeDelta::nanoseconds(read_u64()? as i64);
+        let segments = read_u64()?;
+        let file_length = read_u64()?;
+        let count = read_u64()?;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let send_time = DateTime::from_timestamp_nanos(read_u64()? as i64);
+            let offset = read_u64()?;
+            entries.push(IndexEntry { send_time, offset });
+        }
+
+        Ok(Self {
+            interval,
+            entries,
+            segments,
+            file_length,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids,
+    };
+
+    use super::*;
+
+    #[test]
+    fn index() {
+        // Segments sent at 0.4s, 0.9s, 1.1s and 3.5s
+        let payloads = [400, 900, 1100, 3500]
+            .into_iter()
+            .enumerate()
+            .map(|(index, millis)| {
+                let mut payload = segment(message_protocol_ids::TOPS, index as i64 + 1, &[]);
+                payload[32..40].copy_from_slice(&(millis * 1_000_000i64).to_le_bytes());
+                payload
+            })
+            .collect::<Vec<_>>();
+        let capture = capture(&payloads);
+
+        let index = HistIndex::build(
+            HistReader::new(&capture[..]).unwrap(),
+            TimeDelta::seconds(1),
+        )
+        .unwrap();
+        assert_eq!(index.segments(), 4);
+        assert_eq!(index.file_length(), capture.len() as u64);
+        let times = index
+            .entries()
+            .iter()
+            .map(|entry| entry.send_time.timestamp_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(times, [400, 1100, 3500]);
+
+        let at = |millis: i64| DateTime::from_timestamp_nanos(millis * 1_000_000);
+        assert_eq!(index.lookup(at(400)), None);
+        assert_eq!(index.lookup(at(1100)), Some(&index.entries()[0]));
+        assert_eq!(index.lookup(at(2000)), Some(&index.entries()[1]));
+        let offsets = index
+            .entries()
+            .iter()
+            .map(|entry| entry.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            index.ranges_before(at(2000)),
+            [(offsets[1], None), (offsets[0], Some(offsets[1]))]
+        );
+        assert_eq!(index.ranges_before(at(0)), [(offsets[0], None)]);
+
+        // Reading from an entry starts at its segment
+        let mut reader = HistReader::new(Cursor::new(&capture)).unwrap();
+        reader.seek(index.entries()[1].offset).unwrap();
+        let segment = reader.next_segment().unwrap().unwrap();
+        assert_eq!(segment.segment.first_message_sequence_no, 3);
+
+        let mut file = Vec::new();
+        index.write_to(&mut file).unwrap();
+        assert_eq!(HistIndex::read_from(&file[..]).unwrap(), index);
+        assert!(HistIndex::read_from(&file[1..]).is_err());
+
+        assert_eq!(
+            HistIndex::sidecar_path("TOPS.pcap"),
+            PathBuf::from("TOPS.pcap.idx")
+        );
+    }
+}
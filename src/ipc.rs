@@ -0,0 +1,172 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+};
+
+use crate::export::{write_json_object, Record, ToRecord};
+
+/// The largest frame read by [`read_frame`], far above the size of any message
+pub const MAX_FRAME_LENGTH: usize = 1 << 16;
+
+/// Writes a record as a frame: its length in bytes, as a little-endian `u32`, followed by the record as a JSON object
+/// (see [`write_json`](crate::export::write_json)), without a trailing newline
+pub fn write_frame<W: Write>(writer: &mut W, record: &Record) -> io::Result<()> {
+    let mut frame = vec![0; 4];
+    write_json_object(&mut frame, record)?;
+    let length = (frame.len() - 4) as u32;
+    frame[..4].copy_from_slice(&length.to_le_bytes());
+    writer.write_all(&frame)
+}
+
+/// Reads the JSON object of a frame written by [`write_frame`]. Returns `None` at the end of the stream, if it ends
+/// between frames.
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FRAME_LENGTH {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("frame of {length} bytes"),
+        ));
+    }
+    let mut frame = vec![0; length];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+/// Publishes messages to the processes connected to a Unix domain socket, as frames (see [`write_frame`])
+///
+/// Subscribers connect at any time and receive the messages published from then on. Writes block until every
+/// subscriber has room for the frame, so a slow subscriber slows the publisher down rather than missing messages, and
+/// subscribers which disconnect are dropped. The socket file is removed when the publisher is dropped.
+#[derive(Debug)]
+pub struct FramePublisher {
+    listener: UnixListener,
+    path: PathBuf,
+    subscribers: Vec<UnixStream>,
+    frame: Vec<u8>,
+}
+
+impl FramePublisher {
+    /// Listens on a socket file, which mustn't exist
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path: path.as_ref().to_path_buf(),
+            subscribers: Vec::new(),
+            frame: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of connected subscribers, as of the last publication
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// Accepts the pending connections, without waiting for new ones. Returns the number of subscribers.
+    pub fn accept(&mut self) -> io::Result<usize> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    self.subscribers.push(stream);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(self.subscribers.len()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends a message to every subscriber, after accepting the pending connections
+    pub fn publish<M: ToRecord>(&mut self, message: &M) -> io::Result<()> {
+        self.publish_record(&message.to_record())
+    }
+
+    pub fn publish_record(&mut self, record: &Record) -> io::Result<()> {
+        self.accept()?;
+        self.frame.clear();
+        write_frame(&mut self.frame, record)?;
+        let frame = &self.frame;
+        self.subscribers
+            .retain_mut(|subscriber| subscriber.write_all(frame).is_ok());
+        Ok(())
+    }
+}
+
+impl Drop for FramePublisher {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, io::BufReader};
+
+    use crate::{spec, tops::tops_1_6_message};
+
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let (_, trade) = tops_1_6_message::<String>(&spec::TRADE_REPORT).unwrap();
+        let mut frames = Vec::new();
+        write_frame(&mut frames, &trade.to_record()).unwrap();
+        write_frame(&mut frames, &trade.to_record()).unwrap();
+
+        let mut reader = &frames[..];
+        let frame = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(&frames[..4], (frame.len() as u32).to_le_bytes());
+        assert!(frame.starts_with(b"{\"kind\":\"trade_report\""));
+        assert!(read_frame(&mut reader).unwrap().is_some());
+        assert!(read_frame(&mut reader).unwrap().is_none());
+
+        let mut truncated = &frames[..10];
+        assert!(read_frame(&mut truncated).is_err());
+    }
+
+    #[test]
+    fn publisher() {
+        let path = env::temp_dir().join(format!("iex-parser-ipc-{}.sock", std::process::id()));
+        let mut publisher = FramePublisher::bind(&path).unwrap();
+        let (_, quote) = tops_1_6_message::<String>(&spec::QUOTE_UPDATE).unwrap();
+        let (_, trade) = tops_1_6_message::<String>(&spec::TRADE_REPORT).unwrap();
+
+        // Messages published before a subscriber connects aren't received
+        publisher.publish(&quote).unwrap();
+        let mut first = BufReader::new(UnixStream::connect(&path).unwrap());
+        let second = UnixStream::connect(&path).unwrap();
+        publisher.publish(&trade).unwrap();
+        assert_eq!(publisher.subscribers(), 2);
+
+        drop(second);
+        publisher.publish(&quote).unwrap();
+        publisher.publish(&quote).unwrap();
+        assert_eq!(publisher.subscribers(), 1);
+
+        let frame = read_frame(&mut first).unwrap().unwrap();
+        assert!(frame.starts_with(b"{\"kind\":\"trade_report\""));
+        for _ in 0..2 {
+            let frame = read_frame(&mut first).unwrap().unwrap();
+            assert!(frame.starts_with(b"{\"kind\":\"quote_update\""));
+        }
+
+        drop(publisher);
+        assert!(read_frame(&mut first).unwrap().is_none());
+        assert!(!path.exists());
+    }
+}
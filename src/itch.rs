@@ -0,0 +1,691 @@
+use std::{collections::HashMap, fmt::Display, hash::Hash};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::{Deep1_0Message, PriceLevelUpdate, Side},
+    depth::OrderEvent,
+    export::{Record, ToRecord, Value},
+    tops::{QuoteUpdate, Tops1_6Message, TradeBreak, TradeReport},
+    utils::{key_price, price_key},
+};
+
+/// A message of a normalized message set modeled after Nasdaq TotalView-ITCH, for tools which only understand its
+/// order-level semantics
+///
+/// Every order is identified by a reference number, and an order which grows or moves is replaced by a new one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ItchMessage<S> {
+    /// A new order resting on the book (`A`)
+    AddOrder {
+        timestamp: DateTime<Utc>,
+        reference: u64,
+        side: Side,
+        shares: u32,
+        symbol: S,
+        price: f64,
+    },
+    /// A (partial) execution of a resting order (`E`). An order executed in full leaves the book.
+    OrderExecuted {
+        timestamp: DateTime<Utc>,
+        reference: u64,
+        executed_shares: u32,
+        match_number: u64,
+    },
+    /// A partial cancellation of a resting order (`X`)
+    OrderCancel {
+        timestamp: DateTime<Utc>,
+        reference: u64,
+        cancelled_shares: u32,
+    },
+    /// The removal of what's left of an order (`D`)
+    OrderDelete {
+        timestamp: DateTime<Utc>,
+        reference: u64,
+    },
+    /// The replacement of an order by a new one, at the back of the queue (`U`)
+    OrderReplace {
+        timestamp: DateTime<Utc>,
+        original_reference: u64,
+        new_reference: u64,
+        shares: u32,
+        price: f64,
+    },
+    /// An execution which doesn't concern any order of the book, e.g. against hidden liquidity (`P`)
+    Trade {
+        timestamp: DateTime<Utc>,
+        symbol: S,
+        shares: u32,
+        price: f64,
+        match_number: u64,
+    },
+    /// The execution of an auction (`Q`)
+    CrossTrade {
+        timestamp: DateTime<Utc>,
+        symbol: S,
+        shares: u32,
+        cross_price: f64,
+        match_number: u64,
+    },
+    /// The cancellation of a trade (`B`)
+    BrokenTrade {
+        timestamp: DateTime<Utc>,
+        match_number: u64,
+    },
+}
+
+impl<S> ItchMessage<S> {
+    /// The ITCH message type
+    pub fn code(&self) -> u8 {
+        match self {
+            ItchMessage::AddOrder { .. } => b'A',
+            ItchMessage::OrderExecuted { .. } => b'E',
+            ItchMessage::OrderCancel { .. } => b'X',
+            ItchMessage::OrderDelete { .. } => b'D',
+            ItchMessage::OrderReplace { .. } => b'U',
+            ItchMessage::Trade { .. } => b'P',
+            ItchMessage::CrossTrade { .. } => b'Q',
+            ItchMessage::BrokenTrade { .. } => b'B',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItchMessage::AddOrder { .. } => "add_order",
+            ItchMessage::OrderExecuted { .. } => "order_executed",
+            ItchMessage::OrderCancel { .. } => "order_cancel",
+            ItchMessage::OrderDelete { .. } => "order_delete",
+            ItchMessage::OrderReplace { .. } => "order_replace",
+            ItchMessage::Trade { .. } => "trade",
+            ItchMessage::CrossTrade { .. } => "cross_trade",
+            ItchMessage::BrokenTrade { .. } => "broken_trade",
+        }
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            ItchMessage::AddOrder { timestamp, .. }
+            | ItchMessage::OrderExecuted { timestamp, .. }
+            | ItchMessage::OrderCancel { timestamp, .. }
+            | ItchMessage::OrderDelete { timestamp, .. }
+            | ItchMessage::OrderReplace { timestamp, .. }
+            | ItchMessage::Trade { timestamp, .. }
+            | ItchMessage::CrossTrade { timestamp, .. }
+            | ItchMessage::BrokenTrade { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+impl<S: Display> ToRecord for ItchMessage<S> {
+    fn to_record(&self) -> Record {
+        let mut record = vec![
+            ("kind", Value::Text(self.name().to_string())),
+            ("timestamp", Value::Time(self.timestamp())),
+        ];
+        let int = |value: u64| Value::Int(value as i64);
+        match self {
+            ItchMessage::AddOrder {
+                reference,
+                side,
+                shares,
+                symbol,
+                price,
+                ..
+            } => record.extend([
+                ("symbol", Value::Text(symbol.to_string())),
+                ("reference", int(*reference)),
+                ("side", Value::Text(side_indicator(*side).to_string())),
+                ("shares", Value::Int((*shares).into())),
+                ("price", Value::Price(*price)),
+            ]),
+            ItchMessage::OrderExecuted {
+                reference,
+                executed_shares,
+                match_number,
+                ..
+            } => record.extend([
+                ("reference", int(*reference)),
+                ("executed_shares", Value::Int((*executed_shares).into())),
+                ("match_number", int(*match_number)),
+            ]),
+            ItchMessage::OrderCancel {
+                reference,
+                cancelled_shares,
+                ..
+            } => record.extend([
+                ("reference", int(*reference)),
+                ("cancelled_shares", Value::Int((*cancelled_shares).into())),
+            ]),
+            ItchMessage::OrderDelete { reference, .. } => {
+                record.push(("reference", int(*reference)));
+            }
+            ItchMessage::OrderReplace {
+                original_reference,
+                new_reference,
+                shares,
+                price,
+                ..
+            } => record.extend([
+                ("original_reference", int(*original_reference)),
+                ("new_reference", int(*new_reference)),
+                ("shares", Value::Int((*shares).into())),
+                ("price", Value::Price(*price)),
+            ]),
+            ItchMessage::Trade {
+                symbol,
+                shares,
+                price,
+                match_number,
+                ..
+            }
+            | ItchMessage::CrossTrade {
+                symbol,
+                shares,
+                cross_price: price,
+                match_number,
+                ..
+            } => record.extend([
+                ("symbol", Value::Text(symbol.to_string())),
+                ("shares", Value::Int((*shares).into())),
+                ("price", Value::Price(*price)),
+                ("match_number", int(*match_number)),
+            ]),
+            ItchMessage::BrokenTrade { match_number, .. } => {
+                record.push(("match_number", int(*match_number)));
+            }
+        }
+        record
+    }
+}
+
+fn side_indicator(side: Side) -> char {
+    match side {
+        Side::Buy => 'B',
+        Side::Sell => 'S',
+    }
+}
+
+/// An order of the normalized book
+#[derive(Clone, Debug)]
+struct Resting<S> {
+    symbol: S,
+    shares: u32,
+}
+
+/// Converts the messages of a feed into [`ItchMessage`]s
+///
+/// The orders of DEEP+ are given references in the order they're added. Feeds without orders are given synthetic
+/// ones: each price level of DEEP, and each side of the best quote of TOPS, is a single order, cancelled when the
+/// level shrinks and replaced when it grows. These feeds don't tie trades to the levels they execute against, so their trades are [`Trade`]s
+/// (or [`CrossTrade`]s for auctions) and the levels they deplete are cancelled.
+///
+/// A converter should be fed a single feed, as the books of different feeds would collide.
+///
+/// [`Trade`]: ItchMessage::Trade
+/// [`CrossTrade`]: ItchMessage::CrossTrade
+#[derive(Clone, Debug)]
+pub struct ItchConverter<S> {
+    /// The resting orders, by reference
+    orders: HashMap<u64, Resting<S>>,
+    /// The references of the DEEP+ orders, by identifier
+    order_ids: HashMap<u64, u64>,
+    /// The synthetic orders of the aggregated levels, by symbol, side and price
+    levels: HashMap<(S, Side, i64), u64>,
+    /// The price of the best quote on each side (bid, then ask) of every symbol
+    quotes: HashMap<S, [Option<i64>; 2]>,
+    next_reference: u64,
+}
+
+impl<S> Default for ItchConverter<S> {
+    fn default() -> Self {
+        Self {
+            orders: HashMap::new(),
+            order_ids: HashMap::new(),
+            levels: HashMap::new(),
+            quotes: HashMap::new(),
+            next_reference: 1,
+        }
+    }
+}
+
+impl<S> ItchConverter<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of orders resting on the normalized book
+    pub fn resting_orders(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// Converts a TOPS message. Only quotes, trades and trade breaks have an equivalent.
+    pub fn convert_tops(&mut self, message: &Tops1_6Message<S>) -> Vec<ItchMessage<S>> {
+        let mut converted = Vec::new();
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => self.quote(quote, &mut converted),
+            Tops1_6Message::TradeReport(trade) => converted.push(Self::trade(trade)),
+            Tops1_6Message::TradeBreak(trade) => converted.push(Self::trade_break(trade)),
+            _ => {}
+        }
+        converted
+    }
+
+    /// Converts a DEEP message. Only price level updates, trades and trade breaks have an equivalent.
+    pub fn convert_deep(&mut self, message: &Deep1_0Message<S>) -> Vec<ItchMessage<S>> {
+        let mut converted = Vec::new();
+        match message {
+            Deep1_0Message::PriceLevelUpdate(update) => self.price_level(update, &mut converted),
+            Deep1_0Message::TradeReport(trade) => converted.push(Self::trade(trade)),
+            Deep1_0Message::TradeBreak(trade) => converted.push(Self::trade_break(trade)),
+            _ => {}
+        }
+        converted
+    }
+
+    /// Converts a DEEP+ order event. Executions have no match number, as the events don't carry the trade IDs.
+    pub fn convert_order_event(&mut self, event: &OrderEvent<S>) -> Vec<ItchMessage<S>> {
+        let mut converted = Vec::new();
+        match event {
+            OrderEvent::Add {
+                timestamp,
+                symbol,
+                order_id,
+                side,
+                price,
+                size,
+            } => {
+                let reference = self.allocate_reference();
+                if let Some(previous) = self.order_ids.insert(*order_id, reference) {
+                    self.orders.remove(&previous);
+                }
+                self.orders.insert(
+                    reference,
+                    Resting {
+                        symbol: symbol.clone(),
+                        shares: *size,
+                    },
+                );
+                converted.push(ItchMessage::AddOrder {
+                    timestamp: *timestamp,
+                    reference,
+                    side: *side,
+                    shares: *size,
+                    symbol: symbol.clone(),
+                    price: *price,
+                });
+            }
+            OrderEvent::Modify {
+                timestamp,
+                order_id,
+                price,
+                size,
+            } => {
+                let Some(reference) = self.order_ids.get_mut(order_id) else {
+                    return converted;
+                };
+                let original_reference = *reference;
+                let new_reference = self.next_reference;
+                self.next_reference += 1;
+                *reference = new_reference;
+
+                let mut order = self
+                    .orders
+                    .remove(&original_reference)
+                    .expect("orders have a resting order");
+                order.shares = *size;
+                self.orders.insert(new_reference, order);
+                converted.push(ItchMessage::OrderReplace {
+                    timestamp: *timestamp,
+                    original_reference,
+                    new_reference,
+                    shares: *size,
+                    price: *price,
+                });
+            }
+            OrderEvent::Delete {
+                timestamp,
+                order_id,
+            } => {
+                if let Some(reference) = self.order_ids.remove(order_id) {
+                    self.orders.remove(&reference);
+                    converted.push(ItchMessage::OrderDelete {
+                        timestamp: *timestamp,
+                        reference,
+                    });
+                }
+            }
+            OrderEvent::Execute {
+                timestamp,
+                order_id,
+                size,
+            } => {
+                let Some(&reference) = self.order_ids.get(order_id) else {
+                    return converted;
+                };
+                let order = self
+                    .orders
+                    .get_mut(&reference)
+                    .expect("orders have a resting order");
+                let executed_shares = (*size).min(order.shares);
+                order.shares -= executed_shares;
+                if order.shares == 0 {
+                    self.orders.remove(&reference);
+                    self.order_ids.remove(order_id);
+                }
+                converted.push(ItchMessage::OrderExecuted {
+                    timestamp: *timestamp,
+                    reference,
+                    executed_shares,
+                    match_number: 0,
+                });
+            }
+            OrderEvent::Clear { timestamp, symbol } => {
+                let mut cleared = self
+                    .order_ids
+                    .iter()
+                    .filter(|(_, reference)| self.orders[reference].symbol == *symbol)
+                    .map(|(&order_id, &reference)| (reference, order_id))
+                    .collect::<Vec<_>>();
+                cleared.sort_unstable();
+                for (reference, order_id) in cleared {
+                    self.orders.remove(&reference);
+                    self.order_ids.remove(&order_id);
+                    converted.push(ItchMessage::OrderDelete {
+                        timestamp: *timestamp,
+                        reference,
+                    });
+                }
+            }
+        }
+        converted
+    }
+
+    fn quote(&mut self, quote: &QuoteUpdate<S>, converted: &mut Vec<ItchMessage<S>>) {
+        let sides = [
+            (Side::Buy, quote.bid_price, quote.bid_size),
+            (Side::Sell, quote.ask_price, quote.ask_size),
+        ];
+        let mut previous = self.quotes.remove(&quote.symbol).unwrap_or_default();
+        let mut current = [None; 2];
+        for (index, (side, price, size)) in sides.into_iter().enumerate() {
+            let price = price_key(price);
+            // The previous best level is gone, unless it's still the best one
+            if let Some(previous) = previous[index]
+                .take()
+                .filter(|&key| key != price || size == 0)
+            {
+                self.set_level(quote.timestamp, &quote.symbol, side, previous, 0, converted);
+            }
+            if size > 0 {
+                self.set_level(quote.timestamp, &quote.symbol, side, price, size, converted);
+                current[index] = Some(price);
+            }
+        }
+        self.quotes.insert(quote.symbol.clone(), current);
+    }
+
+    fn price_level(&mut self, update: &PriceLevelUpdate<S>, converted: &mut Vec<ItchMessage<S>>) {
+        self.set_level(
+            update.timestamp,
+            &update.symbol,
+            update.side,
+            price_key(update.price),
+            update.size,
+            converted,
+        );
+    }
+
+    /// Brings the synthetic order of a price level to a new size
+    fn set_level(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        symbol: &S,
+        side: Side,
+        price: i64,
+        shares: u32,
+        converted: &mut Vec<ItchMessage<S>>,
+    ) {
+        let key = (symbol.clone(), side, price);
+        let Some(&reference) = self.levels.get(&key) else {
+            if shares > 0 {
+                let reference = self.allocate_reference();
+                self.levels.insert(key, reference);
+                self.orders.insert(
+                    reference,
+                    Resting {
+                        symbol: symbol.clone(),
+                        shares,
+                    },
+                );
+                converted.push(ItchMessage::AddOrder {
+                    timestamp,
+                    reference,
+                    side,
+                    shares,
+                    symbol: symbol.clone(),
+                    price: key_price(price),
+                });
+            }
+            return;
+        };
+
+        let order = self
+            .orders
+            .get_mut(&reference)
+            .expect("levels have a resting order");
+        if shares == 0 {
+            self.orders.remove(&reference);
+            self.levels.remove(&key);
+            converted.push(ItchMessage::OrderDelete {
+                timestamp,
+                reference,
+            });
+        } else if shares < order.shares {
+            converted.push(ItchMessage::OrderCancel {
+                timestamp,
+                reference,
+                cancelled_shares: order.shares - shares,
+            });
+            order.shares = shares;
+        } else if shares > order.shares {
+            // Orders can't grow: the level is replaced by a larger order
+            let mut order = self.orders.remove(&reference).expect("the order exists");
+            order.shares = shares;
+            let new_reference = self.allocate_reference();
+            self.orders.insert(new_reference, order);
+            self.levels.insert(key, new_reference);
+            converted.push(ItchMessage::OrderReplace {
+                timestamp,
+                original_reference: reference,
+                new_reference,
+                shares,
+                price: key_price(price),
+            });
+        }
+    }
+
+    fn allocate_reference(&mut self) -> u64 {
+        let reference = self.next_reference;
+        self.next_reference += 1;
+        reference
+    }
+
+    fn trade(trade: &TradeReport<S>) -> ItchMessage<S> {
+        if trade.sale_condition.single_price {
+            ItchMessage::CrossTrade {
+                timestamp: trade.timestamp,
+                symbol: trade.symbol.clone(),
+                shares: trade.size,
+                cross_price: trade.price,
+                match_number: trade.id as u64,
+            }
+        } else {
+            ItchMessage::Trade {
+                timestamp: trade.timestamp,
+                symbol: trade.symbol.clone(),
+                shares: trade.size,
+                price: trade.price,
+                match_number: trade.id as u64,
+            }
+        }
+    }
+
+    fn trade_break(trade: &TradeBreak<S>) -> ItchMessage<S> {
+        ItchMessage::BrokenTrade {
+            timestamp: trade.timestamp,
+            match_number: trade.id as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fixtures, tops::SaleCondition};
+
+    use super::*;
+
+    fn level(side: Side, price: f64, size: u32) -> Deep1_0Message<String> {
+        Deep1_0Message::PriceLevelUpdate(PriceLevelUpdate {
+            side,
+            event_processing_complete: true,
+            timestamp: DateTime::from_timestamp_nanos(0),
+            symbol: "ZIEXT".to_string(),
+            size,
+            price,
+        })
+    }
+
+    #[test]
+    fn price_levels() {
+        let mut converter = ItchConverter::new();
+        let timestamp = DateTime::from_timestamp_nanos(0);
+        let codes = |messages: Vec<ItchMessage<String>>| {
+            messages
+                .iter()
+                .map(|message| message.code() as char)
+                .collect::<String>()
+        };
+
+        assert_eq!(
+            converter.convert_deep(&level(Side::Buy, 99.05, 300)),
+            [ItchMessage::AddOrder {
+                timestamp,
+                reference: 1,
+                side: Side::Buy,
+                shares: 300,
+                symbol: "ZIEXT".to_string(),
+                price: key_price(990500),
+            }]
+        );
+        assert_eq!(
+            converter.convert_deep(&level(Side::Buy, 99.05, 100)),
+            [ItchMessage::OrderCancel {
+                timestamp,
+                reference: 1,
+                cancelled_shares: 200,
+            }]
+        );
+        assert_eq!(
+            converter.convert_deep(&level(Side::Buy, 99.05, 500)),
+            [ItchMessage::OrderReplace {
+                timestamp,
+                original_reference: 1,
+                new_reference: 2,
+                shares: 500,
+                price: key_price(990500),
+            }]
+        );
+        assert_eq!(
+            codes(converter.convert_deep(&level(Side::Buy, 99.05, 0))),
+            "D"
+        );
+        assert_eq!(converter.resting_orders(), 0);
+
+        let trade = TradeReport {
+            sale_condition: SaleCondition {
+                single_price: true,
+                ..SaleCondition::default()
+            },
+            timestamp,
+            id: 42,
+            ..fixtures::trade("ZIEXT".to_string(), 0, 99.05, 100)
+        };
+        let converted = converter.convert_deep(&Deep1_0Message::TradeReport(trade));
+        assert_eq!(codes(converted.clone()), "Q");
+        assert_eq!(
+            converted[0].to_record()[0].1,
+            Value::Text("cross_trade".to_string())
+        );
+
+        // A quote moving up on the bid side and disappearing on the ask side
+        let quote = |bid_price, ask_size| {
+            Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                timestamp,
+                ask_size,
+                ..fixtures::quote("ZIEXT".to_string(), 0, bid_price, 99.10)
+            })
+        };
+        let mut converter = ItchConverter::new();
+        assert_eq!(codes(converter.convert_tops(&quote(99.05, 200))), "AA");
+        assert_eq!(codes(converter.convert_tops(&quote(99.06, 0))), "DAD");
+        assert_eq!(converter.resting_orders(), 1);
+    }
+
+    #[test]
+    fn order_events() {
+        let timestamp = DateTime::from_timestamp_nanos(0);
+        let mut converter = ItchConverter::new();
+        for order_id in [7, 8] {
+            converter.convert_order_event(&OrderEvent::Add {
+                timestamp,
+                symbol: "ZIEXT".to_string(),
+                order_id,
+                side: Side::Sell,
+                price: 99.1,
+                size: 100,
+            });
+        }
+        assert_eq!(
+            converter.convert_order_event(&OrderEvent::Execute {
+                timestamp,
+                order_id: 7,
+                size: 100,
+            }),
+            [ItchMessage::OrderExecuted {
+                timestamp,
+                reference: 1,
+                executed_shares: 100,
+                match_number: 0,
+            }]
+        );
+        assert_eq!(
+            converter.convert_order_event(&OrderEvent::Modify {
+                timestamp,
+                order_id: 8,
+                price: 99.2,
+                size: 200,
+            }),
+            [ItchMessage::OrderReplace {
+                timestamp,
+                original_reference: 2,
+                new_reference: 3,
+                shares: 200,
+                price: 99.2,
+            }]
+        );
+        assert_eq!(
+            converter.convert_order_event(&OrderEvent::Clear {
+                timestamp,
+                symbol: "ZIEXT".to_string(),
+            }),
+            [ItchMessage::OrderDelete {
+                timestamp,
+                reference: 3,
+            }]
+        );
+        assert_eq!(converter.resting_orders(), 0);
+    }
+}
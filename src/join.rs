@@ -0,0 +1,121 @@
+use std::{borrow::Borrow, hash::Hash};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    bbo::{Bbo, BboTracker},
+    tops::{QuoteUpdate, Tops1_6Message, TradeReport},
+};
+
+/// The quote in force for a symbol at some point in time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrevailingQuote {
+    pub bbo: Bbo,
+    /// When the quote came into force
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A trade annotated with the quote in force immediately before it
+#[derive(Clone, Debug)]
+pub struct AlignedTrade<S> {
+    pub trade: TradeReport<S>,
+    /// `None` if the symbol wasn't quoted before the trade
+    pub quote: Option<PrevailingQuote>,
+}
+
+/// Annotates each trade report with the prevailing quote of its symbol
+///
+/// Messages are expected in feed order: a quote update preceding a trade in the feed prevails at the time of the trade,
+/// even if both share the same timestamp.
+#[derive(Clone, Debug)]
+pub struct TradeQuoteJoiner<S> {
+    quotes: BboTracker<S>,
+}
+
+impl<S> Default for TradeQuoteJoiner<S> {
+    fn default() -> Self {
+        Self {
+            quotes: BboTracker::default(),
+        }
+    }
+}
+
+impl<S> TradeQuoteJoiner<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply_quote(&mut self, quote: &QuoteUpdate<S>) {
+        self.quotes.apply(quote);
+    }
+
+    /// Returns the quote currently in force for a symbol
+    pub fn prevailing_quote<Q>(&self, symbol: &Q) -> Option<PrevailingQuote>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(PrevailingQuote {
+            bbo: *self.quotes.bbo(symbol)?,
+            timestamp: self.quotes.last_change(symbol)?,
+        })
+    }
+
+    pub fn apply_trade(&self, trade: &TradeReport<S>) -> AlignedTrade<S> {
+        AlignedTrade {
+            trade: trade.clone(),
+            quote: self.prevailing_quote(&trade.symbol),
+        }
+    }
+
+    /// Feeds a TOPS message to the joiner, returning the aligned trade if the message is a trade report
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Option<AlignedTrade<S>> {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => {
+                self.apply_quote(quote);
+                None
+            }
+            Tops1_6Message::TradeReport(trade) => Some(self.apply_trade(trade)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::*;
+
+    fn quote(symbol: &'static str, timestamp: i64, bid_price: f64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::QuoteUpdate(fixtures::quote(
+            symbol,
+            timestamp,
+            bid_price,
+            bid_price + 0.02,
+        ))
+    }
+
+    fn trade(symbol: &'static str, timestamp: i64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradeReport(fixtures::trade(symbol, timestamp, 10.01, 100))
+    }
+
+    #[test]
+    fn trades_see_the_latest_quote_of_their_symbol() {
+        let mut joiner = TradeQuoteJoiner::new();
+
+        assert!(joiner.update(&trade("ZIEXT", 1)).unwrap().quote.is_none());
+
+        assert!(joiner.update(&quote("ZIEXT", 2, 10.00)).is_none());
+        joiner.update(&quote("ZVZZT", 3, 20.00));
+        joiner.update(&quote("ZIEXT", 4, 10.01));
+
+        let aligned = joiner.update(&trade("ZIEXT", 4)).unwrap();
+        let quote = aligned.quote.unwrap();
+        assert_eq!(quote.bbo.bid_price, 10.01);
+        assert_eq!(quote.timestamp, DateTime::from_timestamp_nanos(4));
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    filter::raw_timestamp, hist::CapturedSegment, iex_tp::IexTp1Segment, sequence::StreamId,
+};
+
+/// The values below `1 << SUB_BUCKET_BITS` are recorded exactly, and larger ones within one part in
+/// `1 << SUB_BUCKET_BITS` (less than 1%)
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// A histogram of durations in nanoseconds, with buckets of a constant relative width (as HDR histograms)
+///
+/// Values are recorded within 1% of their magnitude, from a nanosecond to centuries, in a few kilobytes. Negative
+/// durations (e.g. between unsynchronized clocks) can't be recorded, and are only counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    negative: u64,
+    min: u64,
+    max: u64,
+    sum: u128,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        let shift = u64::BITS - 1 - value.leading_zeros() - SUB_BUCKET_BITS;
+        let sub_bucket = (value >> shift) as usize - SUB_BUCKETS;
+        ((shift as usize + 1) << SUB_BUCKET_BITS) + sub_bucket
+    }
+
+    /// The highest value of a bucket
+    fn highest(index: usize) -> u64 {
+        if index < SUB_BUCKETS {
+            return index as u64;
+        }
+        let shift = (index >> SUB_BUCKET_BITS) - 1;
+        let sub_bucket = (index % SUB_BUCKETS + SUB_BUCKETS) as u64;
+        ((sub_bucket + 1) << shift) - 1
+    }
+
+    /// Records a duration in nanoseconds
+    pub fn record(&mut self, nanoseconds: u64) {
+        let index = Self::index(nanoseconds);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.min = if self.count == 0 {
+            nanoseconds
+        } else {
+            self.min.min(nanoseconds)
+        };
+        self.max = self.max.max(nanoseconds);
+        self.sum += u128::from(nanoseconds);
+        self.count += 1;
+    }
+
+    /// Records a duration, or counts it if it's negative
+    pub fn record_delta(&mut self, delta: TimeDelta) {
+        match delta.num_nanoseconds() {
+            Some(nanoseconds) if nanoseconds >= 0 => self.record(nanoseconds as u64),
+            Some(_) => self.negative += 1,
+            None if delta < TimeDelta::zero() => self.negative += 1,
+            None => self.record(u64::MAX),
+        }
+    }
+
+    /// The number of durations recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The number of negative durations, which weren't recorded
+    pub fn negative(&self) -> u64 {
+        self.negative
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The sum of the durations recorded, in nanoseconds
+    pub fn sum(&self) -> u128 {
+        self.sum
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// The duration below which a share of the durations fall (e.g. 0.99 for the 99th percentile), rounded up to the
+    /// precision of the histogram
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::highest(index).clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Adds the durations of another histogram
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        if other.count > 0 {
+            self.min = if self.count == 0 {
+                other.min
+            } else {
+                self.min.min(other.min)
+            };
+            self.max = self.max.max(other.max);
+        }
+        self.count += other.count;
+        self.negative += other.negative;
+        self.sum += other.sum;
+    }
+}
+
+/// The latencies of a stream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamLatency {
+    /// From the timestamp of each message (the time of the event it reports) to the capture of its segment
+    pub capture: Histogram,
+    /// From the send time of each segment to its capture, i.e. through the network
+    pub network: Histogram,
+}
+
+/// Measures the latencies of the segments of each stream, from the timestamps of the data itself
+///
+/// Segments are recorded with their capture time: the timestamp of their packet in a HIST file, or the time they were
+/// received from a [`FeedReceiver`](crate::live::FeedReceiver).
+#[derive(Clone, Debug, Default)]
+pub struct LatencyAnalyzer {
+    streams: BTreeMap<StreamId, StreamLatency>,
+}
+
+impl LatencyAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_segment(&mut self, capture_time: DateTime<Utc>, segment: &IexTp1Segment<'_>) {
+        let latency = self.streams.entry(StreamId::from(segment)).or_default();
+        latency
+            .network
+            .record_delta(capture_time - segment.send_time);
+        for message in &segment.messages {
+            if let Some(timestamp) = raw_timestamp(message) {
+                latency.capture.record_delta(capture_time - timestamp);
+            }
+        }
+    }
+
+    /// Records a segment of a HIST file, if its packet has a timestamp
+    pub fn add_captured(&mut self, captured: &CapturedSegment<'_>) {
+        if let Some(capture_time) = captured.capture_time {
+            self.add_segment(capture_time, &captured.segment);
+        }
+    }
+
+    pub fn stream(&self, stream: &StreamId) -> Option<&StreamLatency> {
+        self.streams.get(stream)
+    }
+
+    /// The latencies of every stream, in the order of their IDs
+    pub fn streams(&self) -> impl Iterator<Item = (&StreamId, &StreamLatency)> {
+        self.streams.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hist::tests::segment,
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+    };
+
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.quantile(0.5), None);
+        for value in 1..=100_000 {
+            histogram.record(value);
+        }
+        histogram.record_delta(TimeDelta::nanoseconds(-5));
+        assert_eq!(histogram.count(), 100_000);
+        assert_eq!(histogram.negative(), 1);
+        assert_eq!((histogram.min(), histogram.max()), (Some(1), Some(100_000)));
+        assert_eq!(histogram.mean(), Some(50_000.5));
+        assert_eq!(histogram.quantile(0.0001), Some(10));
+        assert_eq!(histogram.quantile(1.0), Some(100_000));
+        for quantile in [0.5, 0.9, 0.99] {
+            let value = histogram.quantile(quantile).unwrap() as f64;
+            let exact = quantile * 100_000.0;
+            assert!(
+                value >= exact && value <= exact * 1.01,
+                "{quantile}: {value}"
+            );
+        }
+
+        // Buckets are contiguous
+        for index in 1..Histogram::index(u64::MAX) {
+            assert_eq!(Histogram::index(Histogram::highest(index - 1) + 1), index);
+        }
+
+        let mut merged = Histogram::new();
+        merged.merge(&histogram);
+        merged.merge(&histogram);
+        assert_eq!(merged.count(), 200_000);
+        assert_eq!(merged.quantile(0.5), histogram.quantile(0.5));
+        assert_eq!(merged.min(), Some(1));
+    }
+
+    #[test]
+    fn analyzer() {
+        // Captured 250µs after the segment was sent
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&spec::QUOTE_UPDATE, &spec::TRADE_REPORT],
+        );
+        let (_, IexTpSegment::V1(decoded)) = iex_tp_segment(&encoded).unwrap();
+        let timestamp = raw_timestamp(&spec::QUOTE_UPDATE).unwrap();
+        let send_time = decoded.send_time;
+
+        let mut analyzer = LatencyAnalyzer::new();
+        analyzer.add_segment(send_time + TimeDelta::microseconds(250), &decoded);
+        let (stream, latency) = analyzer.streams().next().unwrap();
+        assert_eq!(stream.message_protocol_id, message_protocol_ids::TOPS);
+        assert_eq!(latency.network.count(), 1);
+        assert_eq!(latency.network.max(), Some(250_000));
+        assert_eq!(latency.capture.count(), 2);
+        assert_eq!(
+            latency.capture.max(),
+            ((send_time - timestamp) + TimeDelta::microseconds(250))
+                .num_nanoseconds()
+                .map(|n| n as u64)
+        );
+    }
+}
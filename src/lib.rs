@@ -1,7 +1,81 @@
-#![feature(assert_matches)]
-
+#[cfg(any(test, feature = "arbitrary"))]
+pub mod arbitrary;
+pub mod arbitration;
+pub mod auction;
+pub mod audit;
+pub mod bars;
+pub mod bbo;
+pub mod book;
+pub mod cache;
+mod checkpoint;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod clock;
+pub mod compare;
+pub mod config;
+pub mod conflate;
+pub mod corpus;
+pub mod deep;
+pub mod depth;
+pub mod dispatch;
+pub mod enrich;
+pub mod event;
+pub mod export;
+pub mod expression;
+pub mod extension;
+#[cfg(any(test, feature = "ffi"))]
+pub mod ffi;
+pub mod filter;
+pub mod fixed;
+#[cfg(test)]
+mod fixtures;
+pub mod halts;
+pub mod hist;
 pub mod iex_tp;
+pub mod index;
+#[cfg(unix)]
+pub mod ipc;
+pub mod itch;
+pub mod join;
+pub mod latency;
+pub mod liquidity;
+pub mod live;
+pub mod locked_crossed;
 pub mod message_protocol_ids;
+pub mod metrics;
+pub mod official;
+#[cfg(any(test, feature = "parquet"))]
+pub mod parquet;
+pub mod parser;
+pub mod partition;
+pub mod pcap;
+pub mod point_in_time;
+pub mod progress;
+pub mod quality;
+pub mod quote_filter;
+pub mod reference;
+#[cfg(any(test, feature = "render"))]
+pub mod render;
+pub mod replay;
+#[cfg(unix)]
+pub mod ring;
+pub mod sequence;
+pub mod series;
+pub mod session;
+pub mod signing;
+pub mod sim;
+pub mod snapshot;
+pub mod spec;
+pub mod ssr;
+pub mod stats;
+pub mod store;
+pub mod summary;
+pub mod testing;
+pub mod throughput;
 pub mod tops;
-
-pub(crate) mod utils;
+pub mod trading_day;
+pub mod trading_state;
+pub mod utils;
+pub mod validate;
+pub mod volatility;
+pub mod volume_profile;
@@ -0,0 +1,250 @@
+use std::{collections::HashMap, hash::Hash};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    bbo::Bbo,
+    tops::{QuoteUpdate, Tops1_6Message},
+};
+
+/// Time-weighted liquidity statistics of a single symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiquidityStats<S> {
+    pub symbol: S,
+    /// The total time during which the symbol was quoted on both sides
+    pub quoted_time: TimeDelta,
+    /// The integral of the quoted spread over the quoted time, in dollar seconds
+    pub spread_integral: f64,
+    /// The integral of the spread relative to the mid price over the quoted time, in seconds
+    pub relative_spread_integral: f64,
+    /// The integral of the quoted depth (bid size plus ask size) over the quoted time, in share seconds
+    pub depth_integral: f64,
+    /// The part of the quoted time during which an NBBO was known
+    pub nbbo_time: TimeDelta,
+    /// The part of the NBBO time during which the IEX bid was at (or better than) the national best bid
+    pub bid_at_nbbo_time: TimeDelta,
+    /// The part of the NBBO time during which the IEX ask was at (or better than) the national best offer
+    pub ask_at_nbbo_time: TimeDelta,
+}
+
+fn seconds(duration: TimeDelta) -> f64 {
+    duration.num_nanoseconds().unwrap_or(i64::MAX) as f64 * 1e-9
+}
+
+impl<S> LiquidityStats<S> {
+    fn new(symbol: S) -> Self {
+        Self {
+            symbol,
+            quoted_time: TimeDelta::zero(),
+            spread_integral: 0.0,
+            relative_spread_integral: 0.0,
+            depth_integral: 0.0,
+            nbbo_time: TimeDelta::zero(),
+            bid_at_nbbo_time: TimeDelta::zero(),
+            ask_at_nbbo_time: TimeDelta::zero(),
+        }
+    }
+
+    /// The time-weighted quoted spread, if the symbol was ever quoted on both sides
+    pub fn time_weighted_spread(&self) -> Option<f64> {
+        (self.quoted_time > TimeDelta::zero())
+            .then(|| self.spread_integral / seconds(self.quoted_time))
+    }
+
+    /// The time-weighted spread relative to the mid price, if the symbol was ever quoted on both sides
+    pub fn time_weighted_relative_spread(&self) -> Option<f64> {
+        (self.quoted_time > TimeDelta::zero())
+            .then(|| self.relative_spread_integral / seconds(self.quoted_time))
+    }
+
+    /// The time-weighted quoted depth in shares, if the symbol was ever quoted on both sides
+    pub fn time_weighted_depth(&self) -> Option<f64> {
+        (self.quoted_time > TimeDelta::zero())
+            .then(|| self.depth_integral / seconds(self.quoted_time))
+    }
+
+    /// The fractions of the NBBO time during which the IEX bid and ask respectively were at the NBBO
+    pub fn fraction_at_nbbo(&self) -> Option<(f64, f64)> {
+        (self.nbbo_time > TimeDelta::zero()).then(|| {
+            let nbbo_time = seconds(self.nbbo_time);
+            (
+                seconds(self.bid_at_nbbo_time) / nbbo_time,
+                seconds(self.ask_at_nbbo_time) / nbbo_time,
+            )
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SymbolState<S> {
+    stats: LiquidityStats<S>,
+    bbo: Bbo,
+    nbbo: Option<Bbo>,
+    // When the current BBO and NBBO came into force
+    since: Option<DateTime<Utc>>,
+}
+
+impl<S> SymbolState<S> {
+    // Accounts the state in force since the last change up to some point in time
+    fn accumulate(&mut self, until: DateTime<Utc>) {
+        let Some(since) = self.since else {
+            return;
+        };
+        let (Some(spread), Some(mid)) = (self.bbo.spread(), self.bbo.mid()) else {
+            return;
+        };
+
+        let duration = until - since;
+        let stats = &mut self.stats;
+        stats.quoted_time += duration;
+        stats.spread_integral += spread * seconds(duration);
+        stats.relative_spread_integral += spread / mid * seconds(duration);
+        stats.depth_integral +=
+            (f64::from(self.bbo.bid_size) + f64::from(self.bbo.ask_size)) * seconds(duration);
+
+        if let Some(nbbo) = self.nbbo {
+            stats.nbbo_time += duration;
+            if self.bbo.bid_price >= nbbo.bid_price {
+                stats.bid_at_nbbo_time += duration;
+            }
+            if self.bbo.ask_price <= nbbo.ask_price {
+                stats.ask_at_nbbo_time += duration;
+            }
+        }
+    }
+}
+
+/// Computes time-weighted spread and depth statistics from TOPS quote updates
+///
+/// TOPS only carries the IEX BBO, so time at the NBBO can only be computed if the national best bid and offer is fed
+/// from another source (e.g. the SIP) through [`LiquidityAnalyzer::apply_nbbo`].
+#[derive(Clone, Debug)]
+pub struct LiquidityAnalyzer<S> {
+    symbols: HashMap<S, SymbolState<S>>,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl<S> Default for LiquidityAnalyzer<S> {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+            last_timestamp: None,
+        }
+    }
+}
+
+impl<S> LiquidityAnalyzer<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&mut self, symbol: &S) -> &mut SymbolState<S> {
+        self.symbols
+            .entry(symbol.clone())
+            .or_insert_with(|| SymbolState {
+                stats: LiquidityStats::new(symbol.clone()),
+                bbo: Bbo::default(),
+                nbbo: None,
+                since: None,
+            })
+    }
+
+    pub fn apply_quote(&mut self, quote: &QuoteUpdate<S>) {
+        let state = self.state(&quote.symbol);
+        state.accumulate(quote.timestamp);
+        state.bbo = Bbo::from(quote);
+        state.since = Some(quote.timestamp);
+        self.last_timestamp = Some(quote.timestamp);
+    }
+
+    /// Sets the national best bid and offer of a symbol from an external source
+    pub fn apply_nbbo(&mut self, symbol: &S, timestamp: DateTime<Utc>, nbbo: Bbo) {
+        let state = self.state(symbol);
+        state.accumulate(timestamp);
+        state.nbbo = nbbo.is_two_sided().then_some(nbbo);
+        state.since = Some(timestamp);
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        if let Tops1_6Message::QuoteUpdate(quote) = message {
+            self.apply_quote(quote);
+        }
+        if let Some(timestamp) = message.timestamp() {
+            self.last_timestamp = Some(timestamp);
+        }
+    }
+
+    /// Returns the statistics of all symbols seen. Quotes still in force are accounted up to the last message seen.
+    pub fn finish(self) -> Vec<LiquidityStats<S>> {
+        let end = self.last_timestamp;
+        self.symbols
+            .into_values()
+            .map(|mut state| {
+                if let Some(end) = end {
+                    state.accumulate(end);
+                }
+                state.stats
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    fn quote(
+        timestamp: i64,
+        bid_price: f64,
+        ask_price: f64,
+        size: u32,
+    ) -> QuoteUpdate<&'static str> {
+        QuoteUpdate {
+            bid_size: size,
+            ask_size: size,
+            ..fixtures::quote("ZIEXT", timestamp * 1_000_000_000, bid_price, ask_price)
+        }
+    }
+
+    #[test]
+    fn time_weighted_statistics() {
+        let mut analyzer = LiquidityAnalyzer::new();
+
+        analyzer.apply_quote(&quote(0, 10.00, 10.02, 100));
+        analyzer.apply_quote(&quote(10, 10.00, 10.06, 300));
+        analyzer.apply_nbbo(
+            &"ZIEXT",
+            DateTime::from_timestamp(20, 0).unwrap(),
+            Bbo {
+                bid_price: 10.01,
+                bid_size: 100,
+                ask_price: 10.06,
+                ask_size: 100,
+            },
+        );
+        analyzer.apply_quote(&quote(30, 0.0, 0.0, 0));
+        analyzer.update(&Tops1_6Message::QuoteUpdate(quote(40, 10.00, 10.02, 100)));
+
+        let stats = &analyzer.finish()[0];
+        assert_eq!(stats.quoted_time, TimeDelta::seconds(30));
+        assert_float_eq!(
+            stats.time_weighted_spread().unwrap(),
+            0.14 / 3.0,
+            abs <= 1e-9
+        );
+        assert_float_eq!(
+            stats.time_weighted_depth().unwrap(),
+            14000.0 / 30.0,
+            abs <= 1e-9
+        );
+        assert_eq!(stats.nbbo_time, TimeDelta::seconds(10));
+        assert_eq!(stats.fraction_at_nbbo(), Some((0.0, 1.0)));
+    }
+}
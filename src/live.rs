@@ -0,0 +1,330 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    iex_tp::{iex_tp_segment, IexTp1Segment, IexTpSegment},
+    sequence::{SequenceTracker, Sequencing},
+};
+
+/// The largest UDP payload
+const MAX_DATAGRAM_LENGTH: usize = 65535;
+
+/// Receives the IEX-TP segments of a live feed, from a multicast group or a unicast address
+///
+/// Gaps aren't filled: segments lost by the network are just missing, as the recovery services of IEX aren't
+/// supported. A [`SequenceTracker`](crate::sequence::SequenceTracker) can detect them.
+#[derive(Debug)]
+pub struct FeedReceiver {
+    socket: UdpSocket,
+    address: SocketAddrV4,
+    interface: Ipv4Addr,
+    buffer: Vec<u8>,
+}
+
+impl FeedReceiver {
+    /// Listens on an address. If it's a multicast group, it's joined on the given interface (`0.0.0.0` for the
+    /// default one).
+    pub fn bind(address: SocketAddrV4, interface: Ipv4Addr) -> io::Result<Self> {
+        let socket = if address.ip().is_multicast() {
+            let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, address.port()))?;
+            socket.join_multicast_v4(address.ip(), &interface)?;
+            socket
+        } else {
+            UdpSocket::bind(address)?
+        };
+
+        Ok(Self {
+            socket,
+            address,
+            interface,
+            buffer: vec![0; MAX_DATAGRAM_LENGTH],
+        })
+    }
+
+    /// Leaves and joins the multicast group again, which makes the network re-establish a multicast route lost e.g. to
+    /// a switch failover. Does nothing for a unicast address.
+    pub fn rejoin(&self) -> io::Result<()> {
+        if self.address.ip().is_multicast() {
+            // Leaving fails if the membership was already lost
+            let _ = self
+                .socket
+                .leave_multicast_v4(self.address.ip(), &self.interface);
+            self.socket
+                .join_multicast_v4(self.address.ip(), &self.interface)?;
+        }
+        Ok(())
+    }
+
+    /// The address the receiver is bound to
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Makes [`FeedReceiver::recv_segment`] fail with [`ErrorKind::WouldBlock`] or [`ErrorKind::TimedOut`] if no
+    /// datagram arrives in time, rather than blocking forever
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Waits for the next segment. Datagrams which aren't IEX-TP segments fail with [`ErrorKind::InvalidData`].
+    pub fn recv_segment(&mut self) -> io::Result<IexTp1Segment<'_>> {
+        let length = self.socket.recv(&mut self.buffer)?;
+        self.segment(length)
+    }
+
+    /// Decodes the datagram of some length last received
+    fn segment(&self, length: usize) -> io::Result<IexTp1Segment<'_>> {
+        match iex_tp_segment(&self.buffer[..length]) {
+            Ok((_, IexTpSegment::V1(segment))) => Ok(segment),
+            Err(_) => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "invalid IEX-TP segment",
+            )),
+        }
+    }
+}
+
+/// When a [`Watchdog`] considers the feed broken
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    /// How long without any segment before re-joining, e.g. as the multicast route was lost. IEX sends heartbeats
+    /// every second, even outside of the trading hours.
+    pub silence: Duration,
+    /// How many gaps in the sequence numbers within [`WatchdogConfig::gap_window`] before re-joining
+    pub max_gaps: usize,
+    pub gap_window: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            silence: Duration::from_secs(5),
+            max_gaps: 3,
+            gap_window: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A recovery made by a [`Watchdog`]. Once the group is re-joined, gaps are tracked from the next segment received, so
+/// state derived from the feed (e.g. books) should be reset or re-synchronized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// No segment arrived for some time, and the group was re-joined
+    Silence(Duration),
+    /// Some gaps were found within the window, and the group was re-joined
+    Gaps(usize),
+}
+
+/// What a [`Watchdog`] received
+#[derive(Debug)]
+pub enum Received<'a> {
+    Segment(IexTp1Segment<'a>),
+    Watchdog(WatchdogEvent),
+}
+
+/// Watches a live feed, re-joining its multicast group when it falls silent or keeps losing segments
+///
+/// Segments are returned as they arrive, except that the segment revealing too many gaps is returned right after the
+/// [`WatchdogEvent::Gaps`] event, as the first segment following the recovery.
+#[derive(Debug)]
+pub struct Watchdog {
+    receiver: FeedReceiver,
+    config: WatchdogConfig,
+    tracker: SequenceTracker,
+    last_segment: Instant,
+    /// The times of the recent gaps
+    gaps: VecDeque<Instant>,
+    /// The length of a segment received but not returned yet
+    pending: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+impl Watchdog {
+    pub fn new(receiver: FeedReceiver, config: WatchdogConfig) -> Self {
+        Self {
+            receiver,
+            config,
+            tracker: SequenceTracker::new(),
+            last_segment: Instant::now(),
+            gaps: VecDeque::new(),
+            pending: None,
+            timeout: None,
+        }
+    }
+
+    /// Makes [`Watchdog::recv`] fail with [`ErrorKind::WouldBlock`] or [`ErrorKind::TimedOut`] if no datagram arrives
+    /// in time and the feed isn't silent for long enough to re-join yet, rather than blocking until then
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn receiver(&self) -> &FeedReceiver {
+        &self.receiver
+    }
+
+    /// The sequence numbers of the segments received since the last recovery
+    pub fn tracker(&self) -> &SequenceTracker {
+        &self.tracker
+    }
+
+    fn recover(&mut self, event: WatchdogEvent) -> io::Result<Received<'_>> {
+        self.receiver.rejoin()?;
+        self.tracker = SequenceTracker::new();
+        self.gaps.clear();
+        self.last_segment = Instant::now();
+        Ok(Received::Watchdog(event))
+    }
+
+    /// Waits for the next segment, or for the feed to fall silent. Datagrams which aren't IEX-TP segments fail with
+    /// [`ErrorKind::InvalidData`]. Failures to re-join the group are returned as they are, and the next call tries
+    /// again.
+    pub fn recv(&mut self) -> io::Result<Received<'_>> {
+        if let Some(length) = self.pending.take() {
+            let segment = self.receiver.segment(length)?;
+            self.tracker.update(&segment);
+            return Ok(Received::Segment(segment));
+        }
+
+        let silent = self.last_segment.elapsed();
+        let Some(remaining) = self
+            .config
+            .silence
+            .checked_sub(silent)
+            .filter(|d| !d.is_zero())
+        else {
+            return self.recover(WatchdogEvent::Silence(silent));
+        };
+        let wait = self
+            .timeout
+            .map_or(remaining, |timeout| timeout.min(remaining));
+        self.receiver.set_timeout(Some(wait))?;
+        let length = match self.receiver.socket.recv(&mut self.receiver.buffer) {
+            Ok(length) => length,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                let silent = self.last_segment.elapsed();
+                if silent < self.config.silence {
+                    return Err(e);
+                }
+                return self.recover(WatchdogEvent::Silence(silent));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let now = Instant::now();
+        self.last_segment = now;
+        let sequencing = self.tracker.update(&self.receiver.segment(length)?);
+        if let Sequencing::Gap(_) = sequencing {
+            self.gaps.push_back(now);
+            while self
+                .gaps
+                .front()
+                .is_some_and(|&gap| now.duration_since(gap) > self.config.gap_window)
+            {
+                self.gaps.pop_front();
+            }
+            if self.gaps.len() >= self.config.max_gaps {
+                let gaps = self.gaps.len();
+                self.pending = Some(length);
+                return self.recover(WatchdogEvent::Gaps(gaps));
+            }
+        }
+        Ok(Received::Segment(self.receiver.segment(length)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hist::tests::segment, message_protocol_ids};
+
+    use super::*;
+
+    #[test]
+    fn receive() {
+        let mut receiver = FeedReceiver::bind(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0),
+            Ipv4Addr::UNSPECIFIED,
+        )
+        .unwrap();
+        receiver.set_timeout(Some(Duration::from_secs(5))).unwrap();
+        let address = receiver.local_addr().unwrap();
+
+        let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let system_event = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+        sender
+            .send_to(
+                &segment(message_protocol_ids::TOPS, 7, &[&system_event]),
+                address,
+            )
+            .unwrap();
+        sender.send_to(b"garbage", address).unwrap();
+
+        let received = receiver.recv_segment().unwrap();
+        assert_eq!(received.first_message_sequence_no, 7);
+        assert_eq!(received.messages, [&system_event[..]]);
+        assert_eq!(
+            receiver.recv_segment().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn watchdog() {
+        let receiver = FeedReceiver::bind(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0),
+            Ipv4Addr::UNSPECIFIED,
+        )
+        .unwrap();
+        let address = receiver.local_addr().unwrap();
+        let mut watchdog = Watchdog::new(
+            receiver,
+            WatchdogConfig {
+                silence: Duration::from_millis(200),
+                max_gaps: 2,
+                gap_window: Duration::from_secs(60),
+            },
+        );
+
+        let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let system_event = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+        for sequence_number in [1, 2, 4, 6, 7] {
+            sender
+                .send_to(
+                    &segment(
+                        message_protocol_ids::TOPS,
+                        sequence_number,
+                        &[&system_event],
+                    ),
+                    address,
+                )
+                .unwrap();
+        }
+
+        let mut received = Vec::new();
+        while received.len() < 7 {
+            received.push(match watchdog.recv().unwrap() {
+                Received::Segment(segment) => Ok(segment.first_message_sequence_no),
+                Received::Watchdog(event) => Err(event),
+            });
+        }
+        assert_eq!(
+            received[..6],
+            [
+                Ok(1),
+                Ok(2),
+                Ok(4),
+                Err(WatchdogEvent::Gaps(2)),
+                Ok(6),
+                Ok(7)
+            ]
+        );
+        // The gaps are tracked again from the segment following the recovery
+        assert_eq!(watchdog.tracker().missing(), 0);
+        assert!(
+            matches!(received[6], Err(WatchdogEvent::Silence(silence)) if silence >= Duration::from_millis(200))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    bbo::Bbo,
+    tops::{QuoteUpdate, Tops1_6Message},
+};
+
+/// The state of a quote relative to itself
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum QuoteCondition {
+    /// The bid is below the ask, or a side isn't quoted
+    #[default]
+    Normal,
+    /// The bid equals the ask
+    Locked,
+    /// The bid is above the ask
+    Crossed,
+}
+
+impl From<&Bbo> for QuoteCondition {
+    fn from(bbo: &Bbo) -> Self {
+        if !bbo.is_two_sided() || bbo.bid_price < bbo.ask_price {
+            QuoteCondition::Normal
+        } else if bbo.bid_price == bbo.ask_price {
+            QuoteCondition::Locked
+        } else {
+            QuoteCondition::Crossed
+        }
+    }
+}
+
+/// How long and how often a symbol was locked or crossed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedCrossedStats<S> {
+    pub symbol: S,
+    pub locked_time: TimeDelta,
+    pub crossed_time: TimeDelta,
+    /// The number of times the quote became locked
+    pub locked_count: u64,
+    /// The number of times the quote became crossed
+    pub crossed_count: u64,
+}
+
+#[derive(Clone, Debug)]
+struct SymbolState<S> {
+    stats: LockedCrossedStats<S>,
+    condition: QuoteCondition,
+    since: DateTime<Utc>,
+}
+
+impl<S> SymbolState<S> {
+    fn accumulate(&mut self, until: DateTime<Utc>) {
+        match self.condition {
+            QuoteCondition::Normal => {}
+            QuoteCondition::Locked => self.stats.locked_time += until - self.since,
+            QuoteCondition::Crossed => self.stats.crossed_time += until - self.since,
+        }
+        self.since = until;
+    }
+}
+
+/// Measures how long each symbol spent locked or crossed, from TOPS quote updates
+#[derive(Clone, Debug)]
+pub struct LockedCrossedAnalyzer<S> {
+    symbols: HashMap<S, SymbolState<S>>,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl<S> Default for LockedCrossedAnalyzer<S> {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+            last_timestamp: None,
+        }
+    }
+}
+
+impl<S> LockedCrossedAnalyzer<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply_quote(&mut self, quote: &QuoteUpdate<S>) {
+        let condition = QuoteCondition::from(&Bbo::from(quote));
+        let state = self
+            .symbols
+            .entry(quote.symbol.clone())
+            .or_insert_with(|| SymbolState {
+                stats: LockedCrossedStats {
+                    symbol: quote.symbol.clone(),
+                    locked_time: TimeDelta::zero(),
+                    crossed_time: TimeDelta::zero(),
+                    locked_count: 0,
+                    crossed_count: 0,
+                },
+                condition: QuoteCondition::Normal,
+                since: quote.timestamp,
+            });
+
+        state.accumulate(quote.timestamp);
+        if condition != state.condition {
+            match condition {
+                QuoteCondition::Normal => {}
+                QuoteCondition::Locked => state.stats.locked_count += 1,
+                QuoteCondition::Crossed => state.stats.crossed_count += 1,
+            }
+            state.condition = condition;
+        }
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        if let Tops1_6Message::QuoteUpdate(quote) = message {
+            self.apply_quote(quote);
+        }
+        if let Some(timestamp) = message.timestamp() {
+            self.last_timestamp = Some(timestamp);
+        }
+    }
+
+    /// The current condition of a symbol's quote
+    pub fn condition<Q>(&self, symbol: &Q) -> QuoteCondition
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.symbols
+            .get(symbol)
+            .map_or(QuoteCondition::Normal, |state| state.condition)
+    }
+
+    /// Returns the statistics of all symbols seen. Conditions still in force are accounted up to the last message
+    /// seen.
+    pub fn finish(self) -> Vec<LockedCrossedStats<S>> {
+        let end = self.last_timestamp;
+        self.symbols
+            .into_values()
+            .map(|mut state| {
+                if let Some(end) = end {
+                    state.accumulate(end);
+                }
+                state.stats
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::*;
+
+    fn quote(timestamp: i64, bid_price: f64, ask_price: f64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::QuoteUpdate(fixtures::quote("ZIEXT", timestamp, bid_price, ask_price))
+    }
+
+    #[test]
+    fn locked_and_crossed_time() {
+        let mut analyzer = LockedCrossedAnalyzer::new();
+
+        analyzer.update(&quote(0, 10.00, 10.01));
+        analyzer.update(&quote(10, 10.01, 10.01));
+        assert_eq!(analyzer.condition("ZIEXT"), QuoteCondition::Locked);
+        analyzer.update(&quote(15, 10.01, 10.01));
+        analyzer.update(&quote(20, 10.02, 10.01));
+        analyzer.update(&quote(25, 10.00, 10.01));
+        analyzer.update(&quote(30, 10.01, 10.01));
+        analyzer.update(&quote(32, 10.00, 10.01));
+
+        let stats = &analyzer.finish()[0];
+        assert_eq!(stats.locked_time, TimeDelta::nanoseconds(12));
+        assert_eq!(stats.crossed_time, TimeDelta::nanoseconds(5));
+        assert_eq!(stats.locked_count, 2);
+        assert_eq!(stats.crossed_count, 1);
+    }
+}
@@ -1,2 +1,39 @@
 pub const TOPS: u16 = 0x8003;
 pub const DEEP_1_0: u16 = 0x8004;
+
+/// A message protocol carried by IEX-TP segments, known at run time (e.g. from the segments of a file)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProtocolId {
+    Tops,
+    Deep1_0,
+}
+
+impl ProtocolId {
+    pub const ALL: [ProtocolId; 2] = [ProtocolId::Tops, ProtocolId::Deep1_0];
+
+    /// The protocol with a message protocol ID, as found in the segment headers
+    pub fn from_id(id: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|protocol| protocol.id() == id)
+    }
+
+    pub fn id(self) -> u16 {
+        match self {
+            ProtocolId::Tops => TOPS,
+            ProtocolId::Deep1_0 => DEEP_1_0,
+        }
+    }
+
+    /// The name of the protocol, as given to the command-line tools
+    pub fn name(self) -> &'static str {
+        match self {
+            ProtocolId::Tops => "tops",
+            ProtocolId::Deep1_0 => "deep",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|protocol| protocol.name().eq_ignore_ascii_case(name))
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    filter::MessageKind,
+    iex_tp::IexTp1Segment,
+    latency::{Histogram, LatencyAnalyzer},
+    sequence::{SequenceTracker, Sequencing, StreamId},
+    stats::Stats,
+};
+
+/// The quantiles of the latencies exposed
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+/// How long the server waits for a request, so that a silent client doesn't block the scrapes
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The size of the longest request line and headers read
+const MAX_REQUEST_LENGTH: u64 = 8192;
+
+/// The health metrics of a live feed, written in the Prometheus text format
+///
+/// Counters are totals since the start, so rates (e.g. of messages by kind) are computed by Prometheus.
+#[derive(Clone, Debug, Default)]
+pub struct FeedMetrics {
+    stats: Stats,
+    tracker: SequenceTracker,
+    gaps: u64,
+    latency: LatencyAnalyzer,
+}
+
+impl FeedMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a segment, received at some time
+    pub fn add_segment(&mut self, receive_time: DateTime<Utc>, segment: &IexTp1Segment<'_>) {
+        self.stats.add_segment(segment);
+        if let Sequencing::Gap(_) = self.tracker.update(segment) {
+            self.gaps += 1;
+        }
+        self.latency.add_segment(receive_time, segment);
+    }
+
+    /// Records a datagram which isn't a valid IEX-TP segment
+    pub fn add_invalid_segment(&mut self) {
+        self.stats.invalid_segments += 1;
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Writes the metrics in the Prometheus text exposition format (version 0.0.4)
+    pub fn write_prometheus<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let counters = [
+            (
+                "iex_segments_total",
+                "Segments received",
+                self.stats.segments,
+            ),
+            (
+                "iex_bytes_total",
+                "Bytes of the segments received",
+                self.stats.bytes,
+            ),
+            (
+                "iex_invalid_segments_total",
+                "Datagrams which weren't IEX-TP segments",
+                self.stats.invalid_segments,
+            ),
+            ("iex_gaps_total", "Gaps in the sequence numbers", self.gaps),
+            (
+                "iex_missing_messages_total",
+                "Messages missing in the gaps",
+                self.tracker.missing(),
+            ),
+            (
+                "iex_duplicate_messages_total",
+                "Messages received more than once",
+                self.tracker.duplicates(),
+            ),
+        ];
+        for (name, help, value) in counters {
+            writeln!(writer, "# HELP {name} {help}")?;
+            writeln!(writer, "# TYPE {name} counter")?;
+            writeln!(writer, "{name} {value}")?;
+        }
+
+        writeln!(
+            writer,
+            "# HELP iex_messages_total Messages received, by kind"
+        )?;
+        writeln!(writer, "# TYPE iex_messages_total counter")?;
+        for kind in MessageKind::ALL {
+            let count = self.stats.kind(kind);
+            if count > 0 {
+                writeln!(
+                    writer,
+                    "iex_messages_total{{kind=\"{}\"}} {count}",
+                    kind.name()
+                )?;
+            }
+        }
+
+        let mut streams = self.tracker.streams().collect::<Vec<_>>();
+        streams.sort();
+        writeln!(
+            writer,
+            "# HELP iex_last_sequence_number The sequence number of the last message of each stream"
+        )?;
+        writeln!(writer, "# TYPE iex_last_sequence_number gauge")?;
+        for stream in streams {
+            if let Some(next) = self.tracker.next_sequence_number(stream) {
+                writeln!(
+                    writer,
+                    "iex_last_sequence_number{{{}}} {}",
+                    labels(stream),
+                    next - 1
+                )?;
+            }
+        }
+
+        for (name, help, network) in [
+            (
+                "iex_capture_latency_seconds",
+                "From the timestamps of the messages to their reception",
+                false,
+            ),
+            (
+                "iex_network_latency_seconds",
+                "From the send times of the segments to their reception",
+                true,
+            ),
+        ] {
+            writeln!(writer, "# HELP {name} {help}")?;
+            writeln!(writer, "# TYPE {name} summary")?;
+            for (stream, latency) in self.latency.streams() {
+                let histogram = if network {
+                    &latency.network
+                } else {
+                    &latency.capture
+                };
+                write_summary(&mut writer, name, &labels(stream), histogram)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn labels(stream: &StreamId) -> String {
+    format!(
+        "protocol=\"{:#06x}\",channel=\"{}\",session=\"{}\"",
+        stream.message_protocol_id, stream.channel_id, stream.session_id
+    )
+}
+
+fn write_summary<W: Write>(
+    writer: &mut W,
+    name: &str,
+    labels: &str,
+    histogram: &Histogram,
+) -> io::Result<()> {
+    let seconds = |nanoseconds: u64| nanoseconds as f64 / 1e9;
+    for quantile in QUANTILES {
+        if let Some(value) = histogram.quantile(quantile) {
+            writeln!(
+                writer,
+                "{name}{{{labels},quantile=\"{quantile}\"}} {}",
+                seconds(value)
+            )?;
+        }
+    }
+    writeln!(
+        writer,
+        "{name}_sum{{{labels}}} {}",
+        histogram.sum() as f64 / 1e9
+    )?;
+    writeln!(writer, "{name}_count{{{labels}}} {}", histogram.count())
+}
+
+/// Serves the metrics of a feed over HTTP, to be scraped by Prometheus
+///
+/// Requests for `/metrics` get the current metrics, and other paths are not found. Requests are read for up to 5
+/// seconds and 8 KiB, and connections are closed after each response. The server runs on its own thread, while the
+/// feed is received on another one.
+#[derive(Debug)]
+pub struct MetricsServer {
+    listener: TcpListener,
+}
+
+impl MetricsServer {
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves the metrics until the process exits
+    pub fn spawn(self, metrics: Arc<Mutex<FeedMetrics>>) -> JoinHandle<()> {
+        thread::spawn(move || {
+            for stream in self.listener.incoming().flatten() {
+                // A failed response only affects its client
+                let _ = respond(stream, &metrics);
+            }
+        })
+    }
+}
+
+fn respond(stream: TcpStream, metrics: &Mutex<FeedMetrics>) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    // Read the request up to its blank line, only keeping the path of its request line
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_LENGTH));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut line = String::new();
+    let complete = loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+            break false;
+        }
+        if line == "\r\n" || line == "\n" {
+            break true;
+        }
+    };
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .map(|target| target.split('?').next().unwrap_or(target));
+
+    let mut writer = &stream;
+    match path {
+        Some("/metrics") if complete => {
+            let mut body = Vec::new();
+            metrics
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .write_prometheus(&mut body)?;
+            write_response(&mut writer, "200 OK", "text/plain; version=0.0.4", &body)
+        }
+        Some(_) if complete => {
+            write_response(&mut writer, "404 Not Found", "text/plain", b"Not Found\n")
+        }
+        _ => write_response(
+            &mut writer,
+            "400 Bad Request",
+            "text/plain",
+            b"Bad Request\n",
+        ),
+    }
+}
+
+fn write_response<W: Write>(
+    writer: &mut W,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    writer.write_all(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Shutdown;
+
+    use chrono::TimeDelta;
+
+    use crate::{
+        hist::tests::segment,
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+    };
+
+    use super::*;
+
+    fn metrics() -> FeedMetrics {
+        let mut metrics = FeedMetrics::new();
+        let first = segment(message_protocol_ids::TOPS, 1, &[&spec::QUOTE_UPDATE]);
+        let second = segment(
+            message_protocol_ids::TOPS,
+            5,
+            &[&spec::TRADE_REPORT, &spec::QUOTE_UPDATE],
+        );
+        for encoded in [first, second] {
+            let (_, IexTpSegment::V1(decoded)) = iex_tp_segment(&encoded).unwrap();
+            metrics.add_segment(decoded.send_time + TimeDelta::milliseconds(2), &decoded);
+        }
+        metrics.add_invalid_segment();
+        metrics
+    }
+
+    #[test]
+    fn prometheus() {
+        let mut text = Vec::new();
+        metrics().write_prometheus(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        for expected in [
+            "iex_segments_total 2",
+            "iex_invalid_segments_total 1",
+            "iex_gaps_total 1",
+            "iex_missing_messages_total 3",
+            "iex_messages_total{kind=\"quote_update\"} 2",
+            "iex_messages_total{kind=\"trade_report\"} 1",
+            "iex_last_sequence_number{protocol=\"0x8003\",channel=\"1\",session=\"1116143616\"} 6",
+            "# TYPE iex_network_latency_seconds summary",
+            "iex_network_latency_seconds{protocol=\"0x8003\",channel=\"1\",session=\"1116143616\",quantile=\"0.5\"} 0.002",
+            "iex_network_latency_seconds_count{protocol=\"0x8003\",channel=\"1\",session=\"1116143616\"} 2",
+        ] {
+            assert!(lines.contains(&expected), "{expected} not in\n{text}");
+        }
+        // Every line is a comment or a sample
+        assert!(lines
+            .iter()
+            .filter(|line| !line.starts_with('#'))
+            .all(|line| line.starts_with("iex_")));
+    }
+
+    #[test]
+    fn server() {
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        server.spawn(Arc::new(Mutex::new(metrics())));
+        let request = |request: &[u8]| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = request(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\r\n\r\n# HELP iex_segments_total"));
+        assert!(
+            request(b"GET /metrics?name=iex_gaps_total HTTP/1.1\r\n\r\n")
+                .starts_with("HTTP/1.1 200 OK\r\n")
+        );
+
+        assert!(request(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .starts_with("HTTP/1.1 404 Not Found\r\n"));
+        // Requests cut before their blank line, by the client or the limit of their length
+        assert!(request(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n")
+            .starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    io::{self, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::Deep1_0Message,
+    tops::{OfficialPrice, OfficialPriceType, Tops1_6Message},
+};
+
+const CSV_HEADER: &str = "symbol,opening_timestamp,opening_price,closing_timestamp,closing_price";
+
+/// An official price, and when it was published
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mark {
+    pub timestamp: DateTime<Utc>,
+    pub price: f64,
+}
+
+/// The official opening and closing prices of a symbol, as far as they were published
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OfficialMarks {
+    pub opening: Option<Mark>,
+    pub closing: Option<Mark>,
+}
+
+/// Stores the official opening and closing prices of every symbol, e.g. to validate opens and closes computed from the
+/// trades
+///
+/// IEX only publishes official prices for the securities it lists. A price published again replaces the previous one.
+#[derive(Clone, Debug)]
+pub struct OfficialPrices<S> {
+    marks: HashMap<S, OfficialMarks>,
+}
+
+impl<S> Default for OfficialPrices<S> {
+    fn default() -> Self {
+        Self {
+            marks: HashMap::new(),
+        }
+    }
+}
+
+impl<S> OfficialPrices<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, price: &OfficialPrice<S>) {
+        let marks = self.marks.entry(price.symbol.clone()).or_default();
+        let mark = Some(Mark {
+            timestamp: price.timestamp,
+            price: price.official_price,
+        });
+        match price.price_type {
+            OfficialPriceType::OpeningPrice => marks.opening = mark,
+            OfficialPriceType::ClosingPrice => marks.closing = mark,
+        }
+    }
+
+    /// Feeds a TOPS message to the store. Messages other than official prices are ignored.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        if let Tops1_6Message::OfficialPrice(price) = message {
+            self.apply(price);
+        }
+    }
+
+    /// Feeds a DEEP message to the store. Messages other than official prices are ignored.
+    pub fn update_deep(&mut self, message: &Deep1_0Message<S>) {
+        if let Deep1_0Message::OfficialPrice(price) = message {
+            self.apply(price);
+        }
+    }
+
+    pub fn get<Q>(&self, symbol: &Q) -> Option<&OfficialMarks>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.marks.get(symbol)
+    }
+
+    pub fn opening_price<Q>(&self, symbol: &Q) -> Option<f64>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.get(symbol)?.opening?.price)
+    }
+
+    pub fn closing_price<Q>(&self, symbol: &Q) -> Option<f64>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.get(symbol)?.closing?.price)
+    }
+
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&S, &OfficialMarks)> {
+        self.marks.iter()
+    }
+
+    /// Writes the prices as CSV, sorted by symbol. Timestamps are in nanoseconds since the epoch, and prices which
+    /// weren't published are left empty.
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+        S: Display,
+    {
+        let mut rows = self
+            .marks
+            .iter()
+            .map(|(symbol, marks)| (symbol.to_string(), marks))
+            .collect::<Vec<_>>();
+        rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mark = |mark: Option<Mark>| match mark {
+            Some(mark) => format!(
+                "{},{}",
+                mark.timestamp.timestamp_nanos_opt().unwrap_or_default(),
+                mark.price
+            ),
+            None => ",".to_string(),
+        };
+        writeln!(writer, "{CSV_HEADER}")?;
+        for (symbol, marks) in rows {
+            writeln!(
+                writer,
+                "{symbol},{},{}",
+                mark(marks.opening),
+                mark(marks.closing)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(
+        symbol: &str,
+        price_type: OfficialPriceType,
+        official_price: f64,
+    ) -> OfficialPrice<String> {
+        OfficialPrice {
+            price_type,
+            timestamp: DateTime::from_timestamp_nanos(1_500_000_000_000_000_000),
+            symbol: symbol.to_string(),
+            official_price,
+        }
+    }
+
+    #[test]
+    fn store_and_export() {
+        let mut prices = OfficialPrices::new();
+        prices.update(&Tops1_6Message::OfficialPrice(price(
+            "ZIEXT",
+            OfficialPriceType::OpeningPrice,
+            99.0,
+        )));
+        prices.update_deep(&Deep1_0Message::OfficialPrice(price(
+            "ZIEXT",
+            OfficialPriceType::ClosingPrice,
+            99.05,
+        )));
+        prices.apply(&price("ZVZZT", OfficialPriceType::OpeningPrice, 10.0));
+        prices.apply(&price("ZVZZT", OfficialPriceType::OpeningPrice, 10.5));
+
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices.opening_price("ZIEXT"), Some(99.0));
+        assert_eq!(prices.closing_price("ZIEXT"), Some(99.05));
+        assert_eq!(prices.opening_price("ZVZZT"), Some(10.5));
+        assert_eq!(prices.closing_price("ZVZZT"), None);
+        assert_eq!(prices.get("ZXIET"), None);
+
+        let mut csv = Vec::new();
+        prices.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            format!(
+                "{CSV_HEADER}\n\
+                 ZIEXT,1500000000000000000,99,1500000000000000000,99.05\n\
+                 ZVZZT,1500000000000000000,10.5,,\n"
+            )
+        );
+    }
+}
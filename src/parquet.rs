@@ -0,0 +1,649 @@
+use std::io::{self, ErrorKind, Write};
+
+use crate::{
+    export::{self, Record, Value, ValueType},
+    filter::MessageKind,
+};
+
+/// The number of rows a [`ParquetWriter`] buffers before writing them as a row group, unless set otherwise
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 1 << 16;
+
+const MAGIC: &[u8; 4] = b"PAR1";
+
+/// The types of the Thrift compact protocol, in which Parquet encodes its metadata
+mod thrift_type {
+    pub const BOOL_TRUE: u8 = 1;
+    pub const BOOL_FALSE: u8 = 2;
+    pub const I32: u8 = 5;
+    pub const I64: u8 = 6;
+    pub const BINARY: u8 = 8;
+    pub const LIST: u8 = 9;
+    pub const STRUCT: u8 = 12;
+}
+
+/// The Parquet enumerations used by the writer
+mod format {
+    pub const DATA_PAGE: i32 = 0;
+    pub const PLAIN: i32 = 0;
+    pub const RLE: i32 = 3;
+    pub const UNCOMPRESSED: i32 = 0;
+    pub const OPTIONAL: i32 = 1;
+    pub const UTF8: i32 = 0;
+}
+
+/// An encoder of Thrift structs in the compact protocol
+#[derive(Debug, Default)]
+struct Thrift {
+    bytes: Vec<u8>,
+    /// The ID of the last field written, in each of the structs being written
+    last_fields: Vec<i16>,
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl Thrift {
+    fn varint(&mut self, value: u64) {
+        write_varint(&mut self.bytes, value);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, field_type: u8) {
+        let last = self
+            .last_fields
+            .last_mut()
+            .expect("fields are written in structs");
+        let delta = id - *last;
+        *last = id;
+        if (1..=15).contains(&delta) {
+            self.bytes.push(((delta as u8) << 4) | field_type);
+        } else {
+            self.bytes.push(field_type);
+            self.zigzag(id.into());
+        }
+    }
+
+    /// Starts a struct, at the top level or as an element of a list
+    fn begin(&mut self) {
+        self.last_fields.push(0);
+    }
+
+    fn end(&mut self) {
+        self.bytes.push(0);
+        self.last_fields.pop();
+    }
+
+    fn struct_field(&mut self, id: i16) {
+        self.field(id, thrift_type::STRUCT);
+        self.begin();
+    }
+
+    fn bool(&mut self, id: i16, value: bool) {
+        let field_type = if value {
+            thrift_type::BOOL_TRUE
+        } else {
+            thrift_type::BOOL_FALSE
+        };
+        self.field(id, field_type);
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, thrift_type::I32);
+        self.zigzag(value.into());
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, thrift_type::I64);
+        self.zigzag(value);
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, thrift_type::BINARY);
+        self.binary_element(value);
+    }
+
+    /// Starts a list, whose elements are written next
+    fn list(&mut self, id: i16, element_type: u8, len: usize) {
+        self.field(id, thrift_type::LIST);
+        if len < 15 {
+            self.bytes.push(((len as u8) << 4) | element_type);
+        } else {
+            self.bytes.push(0xF0 | element_type);
+            self.varint(len as u64);
+        }
+    }
+
+    fn i32_element(&mut self, value: i32) {
+        self.zigzag(value.into());
+    }
+
+    fn binary_element(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+}
+
+/// The physical type in Parquet of a column of values of some type. Times are nanoseconds since the POSIX epoch, in
+/// UTC.
+fn physical_type(value_type: ValueType) -> i32 {
+    match value_type {
+        ValueType::Bool => 0,
+        ValueType::Int | ValueType::Time => 2,
+        ValueType::Price => 5,
+        ValueType::Text => 6,
+    }
+}
+
+/// Writes the fields of the schema element of a column annotating its physical type
+fn annotate(value_type: ValueType, thrift: &mut Thrift) {
+    match value_type {
+        ValueType::Text => {
+            thrift.i32(6, format::UTF8);
+            // A STRING logical type
+            thrift.struct_field(10);
+            thrift.struct_field(1);
+            thrift.end();
+            thrift.end();
+        }
+        ValueType::Time => {
+            // A TIMESTAMP logical type, adjusted to UTC, in nanoseconds
+            thrift.struct_field(10);
+            thrift.struct_field(8);
+            thrift.bool(1, true);
+            thrift.struct_field(2);
+            thrift.struct_field(3);
+            thrift.end();
+            thrift.end();
+            thrift.end();
+            thrift.end();
+        }
+        ValueType::Bool | ValueType::Int | ValueType::Price => {}
+    }
+}
+
+/// A column of a file, with its rows which are yet to be written
+#[derive(Clone, Debug)]
+struct Column {
+    name: &'static str,
+    value_type: ValueType,
+    /// Whether each buffered row has a value
+    defined: Vec<bool>,
+    /// The PLAIN encoding of the buffered values, but booleans
+    values: Vec<u8>,
+    booleans: Vec<bool>,
+}
+
+impl Column {
+    fn push(&mut self, value: Option<&Value>) -> io::Result<()> {
+        let Some(value) = value else {
+            self.defined.push(false);
+            return Ok(());
+        };
+
+        match (self.value_type, value) {
+            (ValueType::Bool, Value::Bool(value)) => self.booleans.push(*value),
+            (ValueType::Int, Value::Int(value)) => self.values.extend(value.to_le_bytes()),
+            (ValueType::Price, Value::Price(value)) => self.values.extend(value.to_le_bytes()),
+            (ValueType::Time, Value::Time(value)) => {
+                let nanos = value.timestamp_nanos_opt().ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "timestamp out of range")
+                })?;
+                self.values.extend(nanos.to_le_bytes());
+            }
+            (ValueType::Text, Value::Text(text)) => {
+                let len = u32::try_from(text.len())
+                    .map_err(|_| io::Error::new(ErrorKind::InvalidData, "text too long"))?;
+                self.values.extend(len.to_le_bytes());
+                self.values.extend(text.as_bytes());
+            }
+            (value_type, value) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "column {} of type {value_type:?} can't hold {value:?}",
+                        self.name
+                    ),
+                ))
+            }
+        }
+        self.defined.push(true);
+        Ok(())
+    }
+
+    /// The data page of the buffered rows, emptying the buffers
+    fn take_page(&mut self) -> Vec<u8> {
+        // Definition levels, in runs of the RLE/bit-packing hybrid encoding
+        let mut levels = Vec::new();
+        let mut rest = &self.defined[..];
+        while let Some(&defined) = rest.first() {
+            let run = rest.iter().take_while(|&&other| other == defined).count();
+            write_varint(&mut levels, (run as u64) << 1);
+            levels.push(defined.into());
+            rest = &rest[run..];
+        }
+
+        let mut body = Vec::new();
+        body.extend((levels.len() as u32).to_le_bytes());
+        body.extend(levels);
+        body.append(&mut self.values);
+        let mut packed = vec![0u8; self.booleans.len().div_ceil(8)];
+        for (index, _) in self.booleans.iter().enumerate().filter(|(_, &value)| value) {
+            packed[index / 8] |= 1 << (index % 8);
+        }
+        body.extend(packed);
+
+        let mut header = Thrift::default();
+        header.begin();
+        header.i32(1, format::DATA_PAGE);
+        header.i32(2, body.len() as i32);
+        header.i32(3, body.len() as i32);
+        header.struct_field(5);
+        header.i32(1, self.defined.len() as i32);
+        header.i32(2, format::PLAIN);
+        header.i32(3, format::RLE);
+        header.i32(4, format::RLE);
+        header.end();
+        header.end();
+
+        self.defined.clear();
+        self.booleans.clear();
+        let mut page = header.bytes;
+        page.extend(body);
+        page
+    }
+}
+
+/// Where a column chunk was written
+#[derive(Clone, Copy, Debug)]
+struct Chunk {
+    offset: u64,
+    len: u64,
+}
+
+#[derive(Clone, Debug)]
+struct RowGroup {
+    rows: usize,
+    chunks: Vec<Chunk>,
+}
+
+/// The state of a Parquet file whose writer was set aside, see [`ParquetWriter::suspend`]
+#[derive(Clone, Debug)]
+pub struct Suspended {
+    columns: Vec<Column>,
+    row_groups: Vec<RowGroup>,
+    row_group_size: usize,
+    offset: u64,
+}
+
+/// Writes records of several kinds of messages as a Parquet file, under the union of their columns
+///
+/// Every column is optional, and has the type of its values in the records of the messages (see
+/// [`ValueType`]): booleans, 64 bits integers, doubles, timestamps in nanoseconds, or strings. Rows are buffered and
+/// written in row groups of a single uncompressed page per column.
+#[derive(Debug)]
+pub struct ParquetWriter<W> {
+    writer: W,
+    state: Suspended,
+}
+
+impl<W> ParquetWriter<W>
+where
+    W: Write,
+{
+    /// Starts a file with the columns of the given kinds of messages. Fields of the records which aren't among these
+    /// columns are dropped.
+    pub fn new(mut writer: W, kinds: impl IntoIterator<Item = MessageKind>) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        let columns = export::union_of_columns(kinds)
+            .into_iter()
+            .map(|(name, value_type)| Column {
+                name,
+                value_type,
+                defined: Vec::new(),
+                values: Vec::new(),
+                booleans: Vec::new(),
+            })
+            .collect();
+        Ok(Self {
+            writer,
+            state: Suspended {
+                columns,
+                row_groups: Vec::new(),
+                row_group_size: DEFAULT_ROW_GROUP_SIZE,
+                offset: MAGIC.len() as u64,
+            },
+        })
+    }
+
+    /// Sets the number of rows of the row groups
+    pub fn with_row_group_size(mut self, rows: usize) -> Self {
+        self.state.row_group_size = rows.max(1);
+        self
+    }
+
+    /// Continues a file set aside by [`ParquetWriter::suspend`], given a writer appending to it
+    pub fn resume(writer: W, suspended: Suspended) -> Self {
+        Self {
+            writer,
+            state: suspended,
+        }
+    }
+
+    /// Buffers a record as a row, and writes the buffered rows as a row group once there are enough of them. Fails
+    /// if a field of the record doesn't have the type of its column.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        for column in &mut self.state.columns {
+            let value = record
+                .iter()
+                .find(|(name, _)| *name == column.name)
+                .map(|(_, value)| value);
+            column.push(value)?;
+        }
+        if self.buffered_rows() >= self.state.row_group_size {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn buffered_rows(&self) -> usize {
+        self.state
+            .columns
+            .first()
+            .map_or(0, |column| column.defined.len())
+    }
+
+    fn write_row_group(&mut self) -> io::Result<()> {
+        let rows = self.buffered_rows();
+        if rows == 0 {
+            return Ok(());
+        }
+
+        let mut chunks = Vec::with_capacity(self.state.columns.len());
+        for column in &mut self.state.columns {
+            let page = column.take_page();
+            self.writer.write_all(&page)?;
+            chunks.push(Chunk {
+                offset: self.state.offset,
+                len: page.len() as u64,
+            });
+            self.state.offset += page.len() as u64;
+        }
+        self.state.row_groups.push(RowGroup { rows, chunks });
+        Ok(())
+    }
+
+    /// Writes the buffered rows, and returns the writer along with the state of the file, so that the file can be
+    /// closed and appended to later (e.g. to bound the number of files open at once)
+    pub fn suspend(mut self) -> io::Result<(W, Suspended)> {
+        self.write_row_group()?;
+        Ok((self.writer, self.state))
+    }
+
+    /// Writes the buffered rows and the metadata ending the file, returning the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_row_group()?;
+        let Suspended {
+            columns,
+            row_groups,
+            ..
+        } = &self.state;
+
+        let mut metadata = Thrift::default();
+        metadata.begin();
+        metadata.i32(1, 1);
+        metadata.list(2, thrift_type::STRUCT, columns.len() + 1);
+        metadata.begin();
+        metadata.binary(4, b"schema");
+        metadata.i32(5, columns.len() as i32);
+        metadata.end();
+        for column in columns {
+            metadata.begin();
+            metadata.i32(1, physical_type(column.value_type));
+            metadata.i32(3, format::OPTIONAL);
+            metadata.binary(4, column.name.as_bytes());
+            annotate(column.value_type, &mut metadata);
+            metadata.end();
+        }
+        metadata.i64(3, row_groups.iter().map(|group| group.rows as i64).sum());
+
+        metadata.list(4, thrift_type::STRUCT, row_groups.len());
+        for group in row_groups {
+            metadata.begin();
+            metadata.list(1, thrift_type::STRUCT, columns.len());
+            for (column, chunk) in columns.iter().zip(&group.chunks) {
+                metadata.begin();
+                metadata.i64(2, chunk.offset as i64);
+                metadata.struct_field(3);
+                metadata.i32(1, physical_type(column.value_type));
+                metadata.list(2, thrift_type::I32, 2);
+                metadata.i32_element(format::PLAIN);
+                metadata.i32_element(format::RLE);
+                metadata.list(3, thrift_type::BINARY, 1);
+                metadata.binary_element(column.name.as_bytes());
+                metadata.i32(4, format::UNCOMPRESSED);
+                metadata.i64(5, group.rows as i64);
+                metadata.i64(6, chunk.len as i64);
+                metadata.i64(7, chunk.len as i64);
+                metadata.i64(9, chunk.offset as i64);
+                metadata.end();
+                metadata.end();
+            }
+            metadata.i64(2, group.chunks.iter().map(|chunk| chunk.len as i64).sum());
+            metadata.i64(3, group.rows as i64);
+            metadata.end();
+        }
+        metadata.binary(
+            6,
+            concat!("iex-parser version ", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        metadata.end();
+
+        self.writer.write_all(&metadata.bytes)?;
+        self.writer
+            .write_all(&(metadata.bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(MAGIC)?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{export::ToRecord, fixtures, hist::Message, spec, tops::Tops1_6Message};
+
+    use super::*;
+
+    /// A value of the Thrift compact protocol
+    #[derive(Clone, Debug, PartialEq)]
+    enum Decoded {
+        Bool(bool),
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Decoded>),
+        Struct(Vec<(i16, Decoded)>),
+    }
+
+    impl Decoded {
+        fn field(&self, id: i16) -> &Decoded {
+            let Decoded::Struct(fields) = self else {
+                panic!("not a struct: {self:?}");
+            };
+            &fields.iter().find(|(field, _)| *field == id).unwrap().1
+        }
+
+        fn int(&self) -> i64 {
+            match self {
+                Decoded::Int(value) => *value,
+                _ => panic!("not an integer: {self:?}"),
+            }
+        }
+
+        fn list(&self) -> &[Decoded] {
+            match self {
+                Decoded::List(elements) => elements,
+                _ => panic!("not a list: {self:?}"),
+            }
+        }
+    }
+
+    fn varint(input: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        for shift in (0..).step_by(7) {
+            let byte = input[0];
+            *input = &input[1..];
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        value
+    }
+
+    fn zigzag(input: &mut &[u8]) -> i64 {
+        let value = varint(input);
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+
+    fn decode(input: &mut &[u8], value_type: u8) -> Decoded {
+        match value_type {
+            thrift_type::BOOL_TRUE | thrift_type::BOOL_FALSE => {
+                Decoded::Bool(value_type == thrift_type::BOOL_TRUE)
+            }
+            thrift_type::I32 | thrift_type::I64 => Decoded::Int(zigzag(input)),
+            thrift_type::BINARY => {
+                let len = varint(input) as usize;
+                let (value, rest) = input.split_at(len);
+                *input = rest;
+                Decoded::Binary(value.to_vec())
+            }
+            thrift_type::LIST => {
+                let header = input[0];
+                *input = &input[1..];
+                let len = match header >> 4 {
+                    15 => varint(input) as usize,
+                    len => len.into(),
+                };
+                Decoded::List((0..len).map(|_| decode(input, header & 0x0F)).collect())
+            }
+            thrift_type::STRUCT => {
+                let mut fields = Vec::new();
+                let mut last = 0;
+                loop {
+                    let header = input[0];
+                    *input = &input[1..];
+                    if header == 0 {
+                        return Decoded::Struct(fields);
+                    }
+                    last = match header >> 4 {
+                        0 => zigzag(input) as i16,
+                        delta => last + i16::from(delta),
+                    };
+                    fields.push((last, decode(input, header & 0x0F)));
+                }
+            }
+            _ => panic!("unexpected type {value_type}"),
+        }
+    }
+
+    /// The metadata at the end of a file
+    fn metadata(file: &[u8]) -> Decoded {
+        assert_eq!(file[..4], *MAGIC);
+        assert_eq!(file[file.len() - 4..], *MAGIC);
+        let metadata_len =
+            u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+        let mut input = &file[file.len() - 8 - metadata_len..file.len() - 8];
+        let metadata = decode(&mut input, thrift_type::STRUCT);
+        assert!(input.is_empty());
+        metadata
+    }
+
+    #[test]
+    fn parquet() {
+        let (_, event) = Tops1_6Message::<String>::parse(&spec::SYSTEM_EVENT).unwrap();
+        let trade = Tops1_6Message::TradeReport(fixtures::trade("ZIEXT".to_string(), 1, 10.5, 100));
+        let kinds = [MessageKind::SystemEvent, MessageKind::TradeReport];
+
+        let mut writer = ParquetWriter::new(Vec::new(), kinds)
+            .unwrap()
+            .with_row_group_size(2);
+        writer.write(&event.to_record()).unwrap();
+        writer.write(&trade.to_record()).unwrap();
+        // Set aside between row groups
+        let (file, suspended) = writer.suspend().unwrap();
+        let mut writer = ParquetWriter::resume(file, suspended);
+        writer.write(&trade.to_record()).unwrap();
+        let file = writer.finish().unwrap();
+
+        let metadata = metadata(&file);
+        assert_eq!(metadata.field(3).int(), 3);
+        let columns = export::union_of_columns(kinds);
+        let schema = metadata.field(2).list();
+        assert_eq!(schema.len(), columns.len() + 1);
+        let types = columns
+            .iter()
+            .zip(&schema[1..])
+            .map(|((name, _), element)| {
+                assert_eq!(*element.field(4), Decoded::Binary(name.as_bytes().to_vec()));
+                (*name, element.field(1).int())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types[..3],
+            [("kind", 6), ("timestamp", 2), ("event_type", 6)]
+        );
+        assert!(types.contains(&("price", 5)));
+
+        let row_groups = metadata.field(4).list();
+        assert_eq!(row_groups.len(), 2);
+        assert_eq!(row_groups[0].field(3).int(), 2);
+        assert_eq!(row_groups[1].field(3).int(), 1);
+
+        // The symbol of the trade, after that of the system event, which has none
+        let symbol = columns
+            .iter()
+            .position(|(name, _)| *name == "symbol")
+            .unwrap();
+        let chunk = row_groups[0].field(1).list()[symbol].field(3);
+        let mut page = &file[chunk.field(9).int() as usize..][..chunk.field(7).int() as usize];
+        let header = decode(&mut page, thrift_type::STRUCT);
+        assert_eq!(header.field(5).field(1).int(), 2);
+        assert_eq!(header.field(2).int(), page.len() as i64);
+        assert_eq!(
+            page,
+            b"\x04\x00\x00\x00\x02\x00\x02\x01\x05\x00\x00\x00ZIEXT"
+        );
+    }
+    #[test]
+    fn column_types() {
+        let (_, event) = Tops1_6Message::<String>::parse(&spec::SYSTEM_EVENT).unwrap();
+
+        // The columns of the trades have their types, though no trade was written
+        let mut writer = ParquetWriter::new(
+            Vec::new(),
+            [MessageKind::SystemEvent, MessageKind::TradeReport],
+        )
+        .unwrap();
+        writer.write(&event.to_record()).unwrap();
+        let metadata = metadata(&writer.finish().unwrap());
+        let price = metadata
+            .field(2)
+            .list()
+            .iter()
+            .find(|element| *element.field(4) == Decoded::Binary(b"price".to_vec()))
+            .unwrap();
+        assert_eq!(price.field(1).int(), 5);
+
+        let mut writer = ParquetWriter::new(Vec::new(), [MessageKind::TradeReport]).unwrap();
+        let error = writer
+            .write(&vec![("price", Value::Text("10.5".to_string()))])
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
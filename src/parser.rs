@@ -0,0 +1,190 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::{ParserConfig, Rejection},
+    deep::Deep1_0Message,
+    event::MarketEvent,
+    export::{Record, ToRecord},
+    filter::MessageKind,
+    message_protocol_ids::ProtocolId,
+    tops::Tops1_6Message,
+};
+
+/// A message of any protocol, as decoded by a [`Parser`]
+#[derive(Clone, Debug)]
+pub enum IexMessage<S> {
+    Tops(Tops1_6Message<S>),
+    Deep(Deep1_0Message<S>),
+}
+
+impl<S> IexMessage<S> {
+    pub fn protocol(&self) -> ProtocolId {
+        match self {
+            IexMessage::Tops(_) => ProtocolId::Tops,
+            IexMessage::Deep(_) => ProtocolId::Deep1_0,
+        }
+    }
+
+    /// The type of the message
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            IexMessage::Tops(message) => message.kind(),
+            IexMessage::Deep(message) => message.kind(),
+        }
+    }
+
+    /// The timestamp of the message, if it has been decoded
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            IexMessage::Tops(message) => message.timestamp(),
+            IexMessage::Deep(message) => message.timestamp(),
+        }
+    }
+
+    /// The symbol of the message, if it carries one and has been decoded
+    pub fn symbol(&self) -> Option<&S> {
+        match self {
+            IexMessage::Tops(message) => message.symbol(),
+            IexMessage::Deep(message) => message.symbol(),
+        }
+    }
+}
+
+impl<S: AsRef<str>> IexMessage<S> {
+    /// The message as a feed-agnostic event
+    pub fn as_event(&self) -> &dyn MarketEvent {
+        match self {
+            IexMessage::Tops(message) => message.as_event(),
+            IexMessage::Deep(message) => message.as_event(),
+        }
+    }
+}
+
+impl<S: Display> ToRecord for IexMessage<S> {
+    fn to_record(&self) -> Record {
+        match self {
+            IexMessage::Tops(message) => message.to_record(),
+            IexMessage::Deep(message) => message.to_record(),
+        }
+    }
+}
+
+/// Decodes the messages of a protocol chosen at run time, e.g. from the segments of whatever file a tool is given
+///
+/// # Example
+///
+/// ```
+/// use iex_parser::{message_protocol_ids, parser::{IexMessage, Parser}, spec};
+///
+/// let parser = Parser::for_protocol_id(message_protocol_ids::TOPS).unwrap();
+/// let message = parser.parse::<String>(&spec::TRADE_REPORT).unwrap();
+/// assert!(matches!(message, IexMessage::Tops(_)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Parser {
+    protocol: ProtocolId,
+    config: ParserConfig,
+}
+
+impl Parser {
+    pub fn new(protocol: ProtocolId) -> Self {
+        Self {
+            protocol,
+            config: ParserConfig::default(),
+        }
+    }
+
+    /// The parser of the protocol with a message protocol ID, as found in the segment headers, if it's supported
+    pub fn for_protocol_id(id: u16) -> Option<Self> {
+        ProtocolId::from_id(id).map(Self::new)
+    }
+
+    /// Decodes the messages according to a config. The symbols of the config aren't checked by the parser, see
+    /// [`ParserConfig::accepts`].
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn protocol(&self) -> ProtocolId {
+        self.protocol
+    }
+
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    /// Decodes a message. Its timestamp isn't checked, as the send time of its segment is unknown.
+    pub fn parse<S>(&self, message: &[u8]) -> Result<IexMessage<S>, Rejection>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        self.decode(message, None)
+    }
+
+    /// Decodes a message of a segment sent at some time, see [`ParserConfig::decode`]
+    pub fn parse_sent<S>(
+        &self,
+        message: &[u8],
+        send_time: DateTime<Utc>,
+    ) -> Result<IexMessage<S>, Rejection>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        self.decode(message, Some(send_time))
+    }
+
+    fn decode<S>(
+        &self,
+        message: &[u8],
+        send_time: Option<DateTime<Utc>>,
+    ) -> Result<IexMessage<S>, Rejection>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        match self.protocol {
+            ProtocolId::Tops => self
+                .config
+                .decode_sent(message, send_time)
+                .map(IexMessage::Tops),
+            ProtocolId::Deep1_0 => self
+                .config
+                .decode_sent(message, send_time)
+                .map(IexMessage::Deep),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::PriceRepresentation, message_protocol_ids, spec};
+
+    use super::*;
+
+    #[test]
+    fn runtime_protocols() {
+        assert!(Parser::for_protocol_id(0x8005).is_none());
+        assert_eq!(ProtocolId::from_name("DEEP"), Some(ProtocolId::Deep1_0));
+
+        let config = ParserConfig::new().prices(PriceRepresentation::Rounded);
+        for protocol in ProtocolId::ALL {
+            let parser = Parser::new(protocol).with_config(config.clone());
+            let message = parser.parse::<String>(&spec::TRADE_REPORT).unwrap();
+            assert_eq!(message.protocol(), protocol);
+            assert_eq!(message.kind(), MessageKind::TradeReport);
+            assert_eq!(message.symbol().map(String::as_str), Some("ZIEXT"));
+            assert_eq!(
+                message.as_event().as_trade().map(|trade| trade.price()),
+                Some(99.05)
+            );
+        }
+
+        let deep = Parser::for_protocol_id(message_protocol_ids::DEEP_1_0).unwrap();
+        assert_eq!(
+            deep.parse::<String>(&spec::QUOTE_UPDATE).unwrap_err(),
+            Rejection::Invalid
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash},
+    panic,
+    sync::mpsc::{self, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+use crate::hist::Message;
+
+/// The number of messages which may be queued for each worker before [`PartitionedRunner::send`] blocks
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Runs per-symbol processing on a pool of worker threads
+///
+/// Symbols are hashed onto the workers, so all the messages of a symbol are handled by the same worker, in the order
+/// they were sent. Each worker owns its state (e.g. the books or bars of its symbols), so no locking is needed.
+/// Messages without any symbol (e.g. system events) are sent to every worker.
+pub struct PartitionedRunner<M, W>
+where
+    M: Message,
+{
+    senders: Vec<SyncSender<M>>,
+    workers: Vec<JoinHandle<W>>,
+    // The default hasher isn't randomly seeded, so symbols are assigned to the same workers across runs
+    hasher: BuildHasherDefault<DefaultHasher>,
+}
+
+impl<M, W> PartitionedRunner<M, W>
+where
+    M: Message + Clone + Send + 'static,
+    M::Symbol: Hash,
+    W: Send + 'static,
+{
+    /// Starts `workers` threads, the state of each being created by `init` (given the index of the worker) and updated
+    /// with every message routed to it by `handle`
+    pub fn new<H>(workers: usize, mut init: impl FnMut(usize) -> W, handle: H) -> Self
+    where
+        H: Fn(&mut W, M) + Clone + Send + 'static,
+    {
+        assert!(workers > 0, "at least one worker is needed");
+
+        let (senders, workers) = (0..workers)
+            .map(|index| {
+                let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+                let mut state = init(index);
+                let handle = handle.clone();
+                let worker = thread::spawn(move || {
+                    for message in receiver {
+                        handle(&mut state, message);
+                    }
+                    state
+                });
+                (sender, worker)
+            })
+            .unzip();
+
+        Self {
+            senders,
+            workers,
+            hasher: BuildHasherDefault::default(),
+        }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// The index of the worker handling a symbol
+    pub fn worker_of(&self, symbol: &M::Symbol) -> usize {
+        (self.hasher.hash_one(symbol) % self.senders.len() as u64) as usize
+    }
+
+    /// Queues a message for its worker, blocking while the queue of the worker is full
+    ///
+    /// Messages sent to a worker which panicked are dropped; the panic is propagated by [`PartitionedRunner::finish`].
+    pub fn send(&self, message: M) {
+        match message.symbol() {
+            Some(symbol) => {
+                let _ = self.senders[self.worker_of(symbol)].send(message);
+            }
+            None => {
+                for sender in &self.senders {
+                    let _ = sender.send(message.clone());
+                }
+            }
+        }
+    }
+
+    /// Waits for the workers to handle every queued message, returning their states in order
+    pub fn finish(self) -> Vec<W> {
+        drop(self.senders);
+        self.workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::{
+        fixtures,
+        tops::{SystemEvent, SystemEventType, Tops1_6Message, TradeReport},
+    };
+
+    use super::*;
+
+    fn trade(symbol: &str, id: i64) -> Tops1_6Message<String> {
+        Tops1_6Message::TradeReport(TradeReport {
+            id,
+            ..fixtures::trade(symbol.to_string(), id, 10.0, 100)
+        })
+    }
+
+    #[test]
+    fn partitioning() {
+        let symbols = ["ZIEXT", "ZVZZT", "ZXZZT", "ZWZZT", "ZJZZT"];
+        let runner = PartitionedRunner::new(
+            3,
+            |index| (index, Vec::new(), 0),
+            |(_, trades, events): &mut (usize, Vec<(String, i64)>, u32), message| match message {
+                Tops1_6Message::TradeReport(trade) => trades.push((trade.symbol, trade.id)),
+                _ => *events += 1,
+            },
+        );
+        assert_eq!(runner.worker_count(), 3);
+
+        runner.send(Tops1_6Message::SystemEvent(SystemEvent {
+            event_type: SystemEventType::StartOfMessages,
+            timestamp: DateTime::from_timestamp_nanos(0),
+        }));
+        for id in 0..100 {
+            runner.send(trade(symbols[id as usize % symbols.len()], id));
+        }
+        let assignments = symbols.map(|symbol| runner.worker_of(&symbol.to_string()));
+
+        let states = runner.finish();
+        assert_eq!(states.len(), 3);
+        for (index, (worker, trades, events)) in states.into_iter().enumerate() {
+            assert_eq!(worker, index);
+            assert_eq!(events, 1);
+            for symbol_index in 0..symbols.len() {
+                let ids = trades
+                    .iter()
+                    .filter(|(symbol, _)| symbol == symbols[symbol_index])
+                    .map(|(_, id)| *id)
+                    .collect::<Vec<_>>();
+                if assignments[symbol_index] == index {
+                    assert_eq!(ids.len(), 20);
+                    assert!(ids.is_sorted());
+                } else {
+                    assert!(ids.is_empty());
+                }
+            }
+        }
+    }
+}
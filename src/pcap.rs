@@ -0,0 +1,894 @@
+use std::{
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    net::SocketAddrV4,
+    ops::Range,
+};
+
+use chrono::{DateTime, Utc};
+
+const PCAP_MICROSECONDS_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_NANOSECONDS_MAGIC: u32 = 0xa1b23c4d;
+const PCAPNG_SECTION_HEADER_BLOCK: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const PCAPNG_SIMPLE_PACKET_BLOCK: u32 = 0x00000003;
+const PCAPNG_ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const PCAPNG_IF_TSRESOL_OPTION: u16 = 9;
+
+/// The largest record which [`PcapReader::resync`] recognizes, far larger than any frame of the IEX feeds
+const MAX_RECORD_LENGTH: usize = 1 << 16;
+
+pub const LINKTYPE_ETHERNET: u16 = 1;
+pub const LINKTYPE_RAW: u16 = 101;
+pub const LINKTYPE_LINUX_SLL: u16 = 113;
+pub const LINKTYPE_IPV4: u16 = 228;
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Endianness {
+    big_endian: bool,
+}
+
+impl Endianness {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Interface {
+    link_type: u16,
+    // Number of timestamp units per second
+    timestamp_resolution: u64,
+}
+
+#[derive(Clone, Debug)]
+enum Format {
+    Pcap {
+        endianness: Endianness,
+        nanosecond_resolution: bool,
+        link_type: u16,
+    },
+    PcapNg {
+        endianness: Endianness,
+        interfaces: Vec<Interface>,
+    },
+}
+
+// The location of the current packet within the buffer of the reader
+#[derive(Clone, Debug)]
+struct PacketInfo {
+    timestamp: Option<DateTime<Utc>>,
+    link_type: u16,
+    data: Range<usize>,
+}
+
+/// A captured link-layer frame
+#[derive(Clone, Debug)]
+pub struct Packet<'a> {
+    /// The capture time, if the capture format records one
+    pub timestamp: Option<DateTime<Utc>>,
+    pub link_type: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> Packet<'a> {
+    /// Extracts the UDP payload of the frame, see [`udp_payload`]
+    pub fn udp_payload(&self) -> Option<&'a [u8]> {
+        udp_payload(self.link_type, self.data)
+    }
+}
+
+/// Reads packets from a capture in either the classic pcap or the pcapng format
+///
+/// The reader doesn't decompress its input, so gzipped captures (such as the HIST files distributed by IEX) should be
+/// wrapped in a decompressing reader first.
+#[derive(Debug)]
+pub struct PcapReader<R> {
+    reader: R,
+    format: Format,
+    buffer: Vec<u8>,
+    position: u64,
+    last: Option<PacketInfo>,
+}
+
+impl<R> PcapReader<R>
+where
+    R: Read,
+{
+    /// Creates a reader, detecting the capture format from its header
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let mut position = 4;
+        let format = if u32::from_le_bytes(magic) == PCAPNG_SECTION_HEADER_BLOCK {
+            // The rest of the section header block is read once the byte order is known
+            Format::PcapNg {
+                endianness: Endianness { big_endian: false },
+                interfaces: Vec::new(),
+            }
+        } else {
+            let (endianness, nanosecond_resolution) =
+                match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                    (PCAP_MICROSECONDS_MAGIC, _) => (Endianness { big_endian: false }, false),
+                    (PCAP_NANOSECONDS_MAGIC, _) => (Endianness { big_endian: false }, true),
+                    (_, PCAP_MICROSECONDS_MAGIC) => (Endianness { big_endian: true }, false),
+                    (_, PCAP_NANOSECONDS_MAGIC) => (Endianness { big_endian: true }, true),
+                    _ => return Err(invalid_data("not a pcap or pcapng capture")),
+                };
+
+            let mut header = [0u8; 20];
+            reader.read_exact(&mut header)?;
+            position += 20;
+
+            Format::Pcap {
+                endianness,
+                nanosecond_resolution,
+                link_type: endianness.u32(&header[16..20]) as u16,
+            }
+        };
+
+        let mut pcap_reader = Self {
+            reader,
+            format,
+            buffer: Vec::new(),
+            position,
+            last: None,
+        };
+
+        if let Format::PcapNg { .. } = pcap_reader.format {
+            let mut start = [0u8; 8];
+            start[..4].copy_from_slice(&magic);
+            pcap_reader.reader.read_exact(&mut start[4..])?;
+            pcap_reader.position += 4;
+            pcap_reader.read_section_header(start)?;
+        }
+
+        Ok(pcap_reader)
+    }
+
+    /// The number of bytes consumed from the underlying reader so far
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn fill_buffer(&mut self, length: usize) -> io::Result<()> {
+        self.buffer.resize(length, 0);
+        self.reader.read_exact(&mut self.buffer)?;
+        self.position += length as u64;
+        Ok(())
+    }
+
+    // Reads the 8 bytes which start every record/block, returning `None` at a clean end of file
+    fn read_record_start(&mut self) -> io::Result<Option<[u8; 8]>> {
+        let mut start = [0u8; 8];
+        let mut filled = 0;
+        while filled < start.len() {
+            match self.reader.read(&mut start[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.position += 8;
+        Ok(Some(start))
+    }
+
+    // Reads the rest of a section header block, given its first 8 bytes (the block type and length)
+    fn read_section_header(&mut self, start: [u8; 8]) -> io::Result<()> {
+        let mut magic = [0u8; 4];
+        self.reader.read_exact(&mut magic)?;
+        self.position += 4;
+
+        let big_endian = match u32::from_le_bytes(magic) {
+            PCAPNG_BYTE_ORDER_MAGIC => false,
+            magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+            _ => return Err(invalid_data("invalid pcapng byte order magic")),
+        };
+        let endianness = Endianness { big_endian };
+
+        let total_length = endianness.u32(&start[4..8]) as usize;
+        if total_length < 28 || !total_length.is_multiple_of(4) {
+            return Err(invalid_data("invalid pcapng section header block length"));
+        }
+        self.fill_buffer(total_length - 12)?;
+
+        // Interfaces are scoped to their section
+        self.format = Format::PcapNg {
+            endianness,
+            interfaces: Vec::new(),
+        };
+
+        Ok(())
+    }
+
+    /// Reads the next packet, returning `None` at the end of the capture
+    pub fn next_packet(&mut self) -> io::Result<Option<Packet<'_>>> {
+        self.last = match self.format {
+            Format::Pcap { .. } => self.read_pcap_record()?,
+            Format::PcapNg { .. } => self.read_pcapng_packet()?,
+        };
+        Ok(self.last_packet())
+    }
+
+    /// The packet most recently returned by [`PcapReader::next_packet`]
+    pub fn last_packet(&self) -> Option<Packet<'_>> {
+        self.last.as_ref().map(|info| Packet {
+            timestamp: info.timestamp,
+            link_type: info.link_type,
+            data: &self.buffer[info.data.clone()],
+        })
+    }
+
+    fn read_pcap_record(&mut self) -> io::Result<Option<PacketInfo>> {
+        let Format::Pcap {
+            endianness,
+            nanosecond_resolution,
+            link_type,
+        } = self.format
+        else {
+            unreachable!()
+        };
+
+        let Some(start) = self.read_record_start()? else {
+            return Ok(None);
+        };
+        let mut rest = [0u8; 8];
+        self.reader.read_exact(&mut rest)?;
+        self.position += 8;
+
+        let seconds = i64::from(endianness.u32(&start[0..4]));
+        let fraction = i64::from(endianness.u32(&start[4..8]));
+        let captured_length = endianness.u32(&rest[0..4]) as usize;
+        self.fill_buffer(captured_length)?;
+
+        let nanoseconds = if nanosecond_resolution {
+            fraction
+        } else {
+            fraction * 1_000
+        };
+
+        Ok(Some(PacketInfo {
+            timestamp: Some(DateTime::from_timestamp_nanos(
+                seconds * 1_000_000_000 + nanoseconds,
+            )),
+            link_type,
+            data: 0..captured_length,
+        }))
+    }
+
+    fn read_pcapng_packet(&mut self) -> io::Result<Option<PacketInfo>> {
+        loop {
+            let Some(start) = self.read_record_start()? else {
+                return Ok(None);
+            };
+
+            let block_type = u32::from_le_bytes([start[0], start[1], start[2], start[3]]);
+            if block_type == PCAPNG_SECTION_HEADER_BLOCK {
+                // The block type of a section header is a palindrome, so it's recognizable before the byte order is
+                self.read_section_header(start)?;
+                continue;
+            }
+
+            let Format::PcapNg {
+                endianness,
+                ref mut interfaces,
+            } = self.format
+            else {
+                unreachable!()
+            };
+
+            let block_type = endianness.u32(&start[0..4]);
+            let total_length = endianness.u32(&start[4..8]) as usize;
+            if total_length < 12 || !total_length.is_multiple_of(4) {
+                return Err(invalid_data("invalid pcapng block length"));
+            }
+            self.buffer.resize(total_length - 8, 0);
+            self.reader.read_exact(&mut self.buffer)?;
+            self.position += (total_length - 8) as u64;
+            let body = &self.buffer[..total_length - 12];
+
+            match block_type {
+                PCAPNG_INTERFACE_DESCRIPTION_BLOCK => {
+                    if body.len() < 8 {
+                        return Err(invalid_data("truncated pcapng interface description"));
+                    }
+                    interfaces.push(Interface {
+                        link_type: endianness.u16(&body[0..2]),
+                        timestamp_resolution: timestamp_resolution(endianness, &body[8..]),
+                    });
+                }
+                PCAPNG_ENHANCED_PACKET_BLOCK => {
+                    if body.len() < 20 {
+                        return Err(invalid_data("truncated pcapng enhanced packet block"));
+                    }
+                    let interface_id = endianness.u32(&body[0..4]) as usize;
+                    let timestamp = (u64::from(endianness.u32(&body[4..8])) << 32)
+                        | u64::from(endianness.u32(&body[8..12]));
+                    let captured_length = endianness.u32(&body[12..16]) as usize;
+                    if body.len() < 20 + captured_length {
+                        return Err(invalid_data("truncated pcapng packet data"));
+                    }
+
+                    let interface = interfaces
+                        .get(interface_id)
+                        .ok_or_else(|| invalid_data("packet of an undescribed pcapng interface"))?;
+
+                    let resolution = interface.timestamp_resolution;
+                    let nanoseconds = (timestamp / resolution) as i128 * 1_000_000_000
+                        + (timestamp % resolution) as i128 * 1_000_000_000 / resolution as i128;
+
+                    return Ok(Some(PacketInfo {
+                        timestamp: i64::try_from(nanoseconds)
+                            .ok()
+                            .map(DateTime::from_timestamp_nanos),
+                        link_type: interface.link_type,
+                        data: 20..20 + captured_length,
+                    }));
+                }
+                PCAPNG_SIMPLE_PACKET_BLOCK => {
+                    if body.len() < 4 {
+                        return Err(invalid_data("truncated pcapng simple packet block"));
+                    }
+                    let interface = interfaces
+                        .first()
+                        .ok_or_else(|| invalid_data("packet of an undescribed pcapng interface"))?;
+                    let original_length = endianness.u32(&body[0..4]) as usize;
+                    let captured_length = original_length.min(body.len() - 4);
+
+                    return Ok(Some(PacketInfo {
+                        timestamp: None,
+                        link_type: interface.link_type,
+                        data: 4..4 + captured_length,
+                    }));
+                }
+                // Statistics, name resolution and custom blocks are irrelevant
+                _ => {}
+            }
+        }
+    }
+}
+
+// Finds the if_tsresol option of an interface description block, defaulting to microseconds
+fn timestamp_resolution(endianness: Endianness, mut options: &[u8]) -> u64 {
+    while options.len() >= 4 {
+        let code = endianness.u16(&options[0..2]);
+        let length = endianness.u16(&options[2..4]) as usize;
+        let padded_length = length.next_multiple_of(4);
+        if code == 0 || options.len() < 4 + length {
+            break;
+        }
+
+        if code == PCAPNG_IF_TSRESOL_OPTION && length == 1 {
+            let value = options[4];
+            let exponent = u32::from(value & 0x7f);
+            return if value & 0x80 == 0 {
+                10u64.checked_pow(exponent).unwrap_or(1_000_000)
+            } else {
+                2u64.checked_pow(exponent).unwrap_or(1_000_000)
+            };
+        }
+
+        options = options.get(4 + padded_length..).unwrap_or_default();
+    }
+
+    1_000_000
+}
+
+/// Extracts the payload of a UDP datagram carried in a link-layer frame, returning `None` for any other kind of frame
+///
+/// Ethernet (optionally VLAN tagged), Linux cooked and raw IP frames are supported, with IPv4 only. Fragmented datagrams
+/// aren't reassembled.
+pub fn udp_payload(link_type: u16, frame: &[u8]) -> Option<&[u8]> {
+    let ip_packet = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ether_type = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+            let mut offset = 14;
+            // 802.1Q and 802.1ad tags
+            while ether_type == 0x8100 || ether_type == 0x88a8 {
+                ether_type = u16::from_be_bytes([*frame.get(offset + 2)?, *frame.get(offset + 3)?]);
+                offset += 4;
+            }
+            if ether_type != 0x0800 {
+                return None;
+            }
+            frame.get(offset..)?
+        }
+        LINKTYPE_LINUX_SLL => {
+            if u16::from_be_bytes([*frame.get(14)?, *frame.get(15)?]) != 0x0800 {
+                return None;
+            }
+            frame.get(16..)?
+        }
+        LINKTYPE_RAW | LINKTYPE_IPV4 => frame,
+        _ => return None,
+    };
+
+    let version_and_header_length = *ip_packet.first()?;
+    if version_and_header_length >> 4 != 4 || *ip_packet.get(9)? != 17 {
+        return None;
+    }
+    let header_length = usize::from(version_and_header_length & 0x0f) * 4;
+    let total_length = usize::from(u16::from_be_bytes([*ip_packet.get(2)?, *ip_packet.get(3)?]));
+    let udp_datagram = ip_packet.get(header_length..total_length.min(ip_packet.len()))?;
+
+    let udp_length = usize::from(u16::from_be_bytes([
+        *udp_datagram.get(4)?,
+        *udp_datagram.get(5)?,
+    ]));
+    udp_datagram.get(8..udp_length.min(udp_datagram.len()))
+}
+
+impl<R> PcapReader<R>
+where
+    R: Read + Seek,
+{
+    /// Moves to an offset of the capture, which must be the start of a record (e.g. a past [`PcapReader::position`])
+    ///
+    /// The header of the capture isn't read again, so pcapng captures made of several sections can only be read from
+    /// an offset within their current section.
+    pub fn seek(&mut self, offset: u64) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.position = offset;
+        self.last = None;
+        Ok(())
+    }
+
+    /// The length of the whole capture
+    pub fn capture_length(&mut self) -> io::Result<u64> {
+        let length = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(self.position))?;
+        Ok(length)
+    }
+
+    /// Finds the first record starting within `length` bytes of an offset whose packet is accepted by a predicate,
+    /// returning its offset. The reader is then positioned at that record, or after the bytes scanned if none is found.
+    ///
+    /// The offset doesn't need to be the start of a record: any position whose bytes look like the header of a whole
+    /// record is a candidate, which the predicate should check further (e.g. that the packet carries a valid segment).
+    /// Only packet records are found (enhanced packet blocks in pcapng captures, whose interfaces must have been read).
+    pub fn resync(
+        &mut self,
+        offset: u64,
+        length: u64,
+        mut accept: impl FnMut(&Packet<'_>) -> bool,
+    ) -> io::Result<Option<u64>> {
+        // Records starting near the end of the range are read whole
+        let mut window = Vec::new();
+        self.reader.seek(SeekFrom::Start(offset))?;
+        (&mut self.reader)
+            .take(length + MAX_RECORD_LENGTH as u64)
+            .read_to_end(&mut window)?;
+
+        let scanned = (length as usize).min(window.len());
+        let found = (0..scanned).find(|&start| {
+            self.packet_at(&window[start..])
+                .is_some_and(|packet| accept(&packet))
+        });
+        let position = offset + found.unwrap_or(scanned) as u64;
+        self.seek(position)?;
+        Ok(found.map(|_| position))
+    }
+
+    // Decodes the packet record at the start of some bytes, if they look like one
+    fn packet_at<'a>(&self, bytes: &'a [u8]) -> Option<Packet<'a>> {
+        match &self.format {
+            Format::Pcap {
+                endianness,
+                nanosecond_resolution,
+                link_type,
+            } => {
+                let header = bytes.get(..16)?;
+                let seconds = i64::from(endianness.u32(&header[0..4]));
+                let fraction = i64::from(endianness.u32(&header[4..8]));
+                let captured_length = endianness.u32(&header[8..12]) as usize;
+                let original_length = endianness.u32(&header[12..16]) as usize;
+                let nanoseconds = if *nanosecond_resolution {
+                    fraction
+                } else {
+                    fraction * 1_000
+                };
+                if nanoseconds >= 1_000_000_000
+                    || captured_length > original_length
+                    || 16 + captured_length > MAX_RECORD_LENGTH
+                {
+                    return None;
+                }
+
+                Some(Packet {
+                    timestamp: Some(DateTime::from_timestamp_nanos(
+                        seconds * 1_000_000_000 + nanoseconds,
+                    )),
+                    link_type: *link_type,
+                    data: bytes.get(16..16 + captured_length)?,
+                })
+            }
+            Format::PcapNg {
+                endianness,
+                interfaces,
+            } => {
+                let header = bytes.get(..28)?;
+                let total_length = endianness.u32(&header[4..8]) as usize;
+                if endianness.u32(&header[0..4]) != PCAPNG_ENHANCED_PACKET_BLOCK
+                    || total_length < 32
+                    || !total_length.is_multiple_of(4)
+                    || total_length > MAX_RECORD_LENGTH
+                {
+                    return None;
+                }
+                // The length of a block is repeated at its end
+                let block = bytes.get(..total_length)?;
+                if endianness.u32(&block[total_length - 4..]) as usize != total_length {
+                    return None;
+                }
+
+                let interface = interfaces.get(endianness.u32(&header[8..12]) as usize)?;
+                let captured_length = endianness.u32(&header[20..24]) as usize;
+                if 32 + captured_length > total_length {
+                    return None;
+                }
+                Some(Packet {
+                    // Timestamps aren't needed to recognize records
+                    timestamp: None,
+                    link_type: interface.link_type,
+                    data: &block[28..28 + captured_length],
+                })
+            }
+        }
+    }
+}
+
+/// Writes packets to a classic pcap capture, with nanosecond timestamps
+#[derive(Debug)]
+pub struct PcapWriter<W> {
+    writer: W,
+}
+
+impl<W> PcapWriter<W>
+where
+    W: Write,
+{
+    /// Creates a writer, writing the header of the capture
+    pub fn new(mut writer: W, link_type: u16) -> io::Result<Self> {
+        writer.write_all(&PCAP_NANOSECONDS_MAGIC.to_le_bytes())?;
+        // Version 2.4, no time zone offset or timestamp accuracy
+        writer.write_all(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0])?;
+        writer.write_all(&u32::from(u16::MAX).to_le_bytes())?;
+        writer.write_all(&u32::from(link_type).to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a packet captured at some time
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timestamp precedes the POSIX epoch or is past 2106, which the format can't represent.
+    pub fn write_packet(&mut self, timestamp: DateTime<Utc>, data: &[u8]) -> io::Result<()> {
+        let seconds = u32::try_from(timestamp.timestamp()).expect("timestamp out of range");
+        let length = u32::try_from(data.len()).expect("packet too long");
+
+        self.writer.write_all(&seconds.to_le_bytes())?;
+        self.writer
+            .write_all(&timestamp.timestamp_subsec_nanos().to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(data)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The formats of the captures written by [`PcapNgWriter`] and [`PcapWriter`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// The classic pcap format, with nanosecond timestamps
+    #[default]
+    Pcap,
+    /// The pcapng format, with a single interface with nanosecond timestamps, as the HIST files distributed by IEX
+    PcapNg,
+}
+
+/// Writes packets to a pcapng capture made of a single section, with a single interface with nanosecond timestamps
+#[derive(Debug)]
+pub struct PcapNgWriter<W> {
+    writer: W,
+}
+
+impl<W> PcapNgWriter<W>
+where
+    W: Write,
+{
+    /// Creates a writer, writing the section header and the interface description of the capture
+    pub fn new(mut writer: W, link_type: u16) -> io::Result<Self> {
+        // Version 1.0, with an unspecified section length
+        writer.write_all(&PCAPNG_SECTION_HEADER_BLOCK.to_le_bytes())?;
+        writer.write_all(&28u32.to_le_bytes())?;
+        writer.write_all(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes())?;
+        writer.write_all(&[1, 0, 0, 0])?;
+        writer.write_all(&u64::MAX.to_le_bytes())?;
+        writer.write_all(&28u32.to_le_bytes())?;
+
+        // No snapshot length, an if_tsresol option of 10^-9 and the end of the options
+        writer.write_all(&PCAPNG_INTERFACE_DESCRIPTION_BLOCK.to_le_bytes())?;
+        writer.write_all(&32u32.to_le_bytes())?;
+        writer.write_all(&link_type.to_le_bytes())?;
+        writer.write_all(&[0; 6])?;
+        writer.write_all(&PCAPNG_IF_TSRESOL_OPTION.to_le_bytes())?;
+        writer.write_all(&[1, 0, 9, 0, 0, 0, 0, 0, 0, 0])?;
+        writer.write_all(&32u32.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a packet captured at some time, as an enhanced packet block
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timestamp precedes the POSIX epoch.
+    pub fn write_packet(&mut self, timestamp: DateTime<Utc>, data: &[u8]) -> io::Result<()> {
+        let nanoseconds = timestamp
+            .timestamp_nanos_opt()
+            .and_then(|nanoseconds| u64::try_from(nanoseconds).ok())
+            .expect("timestamp out of range");
+        let length = u32::try_from(data.len()).expect("packet too long");
+        let padding = data.len().next_multiple_of(4) - data.len();
+        let block_length = u32::try_from(32 + data.len() + padding).expect("packet too long");
+
+        self.writer
+            .write_all(&PCAPNG_ENHANCED_PACKET_BLOCK.to_le_bytes())?;
+        self.writer.write_all(&block_length.to_le_bytes())?;
+        // The only interface
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.writer
+            .write_all(&((nanoseconds >> 32) as u32).to_le_bytes())?;
+        self.writer.write_all(&(nanoseconds as u32).to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(&length.to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&[0; 3][..padding])?;
+        self.writer.write_all(&block_length.to_le_bytes())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Builds an Ethernet frame carrying a UDP datagram over IPv4, as [`udp_payload`] reads them
+///
+/// The source MAC address is zero, and the destination one is derived from the destination IP address if it's a
+/// multicast group.
+pub fn ethernet_udp_frame(
+    source: SocketAddrV4,
+    destination: SocketAddrV4,
+    payload: &[u8],
+) -> Vec<u8> {
+    let udp_length = u16::try_from(8 + payload.len()).expect("datagram too long");
+    let total_length = udp_length.checked_add(20).expect("datagram too long");
+
+    let mut frame = Vec::with_capacity(14 + usize::from(total_length));
+    let group = destination.ip().octets();
+    if destination.ip().is_multicast() {
+        frame.extend_from_slice(&[0x01, 0x00, 0x5e, group[1] & 0x7f, group[2], group[3]]);
+    } else {
+        frame.extend_from_slice(&[0; 6]);
+    }
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&[0x08, 0x00]);
+
+    let mut ip_header = [0u8; 20];
+    ip_header[0] = 0x45;
+    ip_header[2..4].copy_from_slice(&total_length.to_be_bytes());
+    // Don't fragment, a TTL of 64, UDP
+    ip_header[6] = 0x40;
+    ip_header[8] = 64;
+    ip_header[9] = 17;
+    ip_header[12..16].copy_from_slice(&source.ip().octets());
+    ip_header[16..20].copy_from_slice(&group);
+    let sum = ip_header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum::<u32>();
+    let checksum = !((sum & 0xffff) + (sum >> 16)) as u16;
+    ip_header[10..12].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&ip_header);
+
+    // The UDP checksum is optional over IPv4
+    frame.extend_from_slice(&source.port().to_be_bytes());
+    frame.extend_from_slice(&destination.port().to_be_bytes());
+    frame.extend_from_slice(&udp_length.to_be_bytes());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ethernet_frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&[0x08, 0x00]);
+        let total_length = (20 + 8 + payload.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0x00]);
+        frame.extend_from_slice(&total_length.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 0x40, 17, 0, 0, 10, 0, 0, 1, 233, 215, 21, 4]);
+        frame.extend_from_slice(&10378u16.to_be_bytes());
+        frame.extend_from_slice(&10378u16.to_be_bytes());
+        frame.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn classic_pcap() {
+        let frame = ethernet_frame(b"hello");
+
+        let mut capture = Vec::new();
+        capture.extend_from_slice(&PCAP_NANOSECONDS_MAGIC.to_le_bytes());
+        capture.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0]);
+        capture.extend_from_slice(&1u32.to_le_bytes());
+        capture.extend_from_slice(&1_471_980_632u32.to_le_bytes());
+        capture.extend_from_slice(&572_839_404u32.to_le_bytes());
+        capture.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        capture.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        capture.extend_from_slice(&frame);
+
+        let mut reader = PcapReader::new(&capture[..]).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(
+            packet.timestamp,
+            Some(DateTime::from_timestamp_nanos(1471980632572839404))
+        );
+        assert_eq!(packet.udp_payload(), Some(&b"hello"[..]));
+        assert!(reader.next_packet().unwrap().is_none());
+        assert_eq!(reader.position(), capture.len() as u64);
+    }
+
+    #[test]
+    fn writer() {
+        let timestamp = DateTime::from_timestamp_nanos(1471980632572839404);
+        let frame = ethernet_udp_frame(
+            "10.0.0.1:10378".parse().unwrap(),
+            "233.215.21.4:10378".parse().unwrap(),
+            b"hello",
+        );
+        assert_eq!(frame[..6], [0x01, 0x00, 0x5e, 0x57, 0x15, 0x04]);
+        // The checksum of a header including its checksum is zero
+        let sum = frame[14..34]
+            .chunks(2)
+            .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+            .sum::<u32>();
+        assert_eq!((sum & 0xffff) + (sum >> 16), 0xffff);
+
+        let mut writer = PcapWriter::new(Vec::new(), LINKTYPE_ETHERNET).unwrap();
+        writer.write_packet(timestamp, &frame).unwrap();
+        let capture = writer.into_inner();
+
+        let mut reader = PcapReader::new(&capture[..]).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.timestamp, Some(timestamp));
+        assert_eq!(packet.data, frame);
+        assert_eq!(packet.udp_payload(), Some(&b"hello"[..]));
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn pcapng() {
+        let frame = ethernet_frame(b"hello!");
+        let padding = frame.len().next_multiple_of(4) - frame.len();
+
+        let mut capture = Vec::new();
+        // Section header block
+        capture.extend_from_slice(&PCAPNG_SECTION_HEADER_BLOCK.to_le_bytes());
+        capture.extend_from_slice(&28u32.to_le_bytes());
+        capture.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        capture.extend_from_slice(&[1, 0, 0, 0]);
+        capture.extend_from_slice(&u64::MAX.to_le_bytes());
+        capture.extend_from_slice(&28u32.to_le_bytes());
+        // Interface description block, with nanosecond resolution
+        capture.extend_from_slice(&PCAPNG_INTERFACE_DESCRIPTION_BLOCK.to_le_bytes());
+        capture.extend_from_slice(&32u32.to_le_bytes());
+        capture.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        capture.extend_from_slice(&[9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0]);
+        capture.extend_from_slice(&32u32.to_le_bytes());
+        // Enhanced packet block
+        let block_length = (32 + frame.len() + padding) as u32;
+        let timestamp = 1471980632572839404u64;
+        capture.extend_from_slice(&PCAPNG_ENHANCED_PACKET_BLOCK.to_le_bytes());
+        capture.extend_from_slice(&block_length.to_le_bytes());
+        capture.extend_from_slice(&0u32.to_le_bytes());
+        capture.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        capture.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        capture.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        capture.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        capture.extend_from_slice(&frame);
+        capture.extend(std::iter::repeat_n(0, padding));
+        capture.extend_from_slice(&block_length.to_le_bytes());
+
+        let mut reader = PcapReader::new(&capture[..]).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(
+            packet.timestamp,
+            Some(DateTime::from_timestamp_nanos(1471980632572839404))
+        );
+        assert_eq!(packet.udp_payload(), Some(&b"hello!"[..]));
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn pcapng_writer() {
+        let timestamp = DateTime::from_timestamp_nanos(1471980632572839404);
+        let mut writer = PcapNgWriter::new(Vec::new(), LINKTYPE_ETHERNET).unwrap();
+        for payload in [&b"hello"[..], b"hello!!!"] {
+            writer
+                .write_packet(timestamp, &ethernet_frame(payload))
+                .unwrap();
+        }
+        let capture = writer.into_inner();
+        assert!(capture.len().is_multiple_of(4));
+
+        let mut reader = PcapReader::new(&capture[..]).unwrap();
+        for payload in [&b"hello"[..], b"hello!!!"] {
+            let packet = reader.next_packet().unwrap().unwrap();
+            assert_eq!(packet.timestamp, Some(timestamp));
+            assert_eq!(packet.udp_payload(), Some(payload));
+        }
+        assert!(reader.next_packet().unwrap().is_none());
+        assert_eq!(reader.position(), capture.len() as u64);
+    }
+
+    #[test]
+    fn not_a_capture() {
+        assert!(PcapReader::new(&b"GARBAGE GARBAGE GARBAGE GARBAGE"[..]).is_err());
+    }
+
+    #[test]
+    fn non_udp_frames() {
+        let mut frame = ethernet_frame(b"hello");
+        frame[23] = 6; // TCP
+        assert_eq!(udp_payload(LINKTYPE_ETHERNET, &frame), None);
+
+        frame[12] = 0x86; // IPv6
+        frame[13] = 0xdd;
+        assert_eq!(udp_payload(LINKTYPE_ETHERNET, &frame), None);
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    bbo::{Bbo, BboTracker},
+    book::{BookBuilder, BookSnapshot},
+    deep::Deep1_0Message,
+    tops::Tops1_6Message,
+};
+
+/// The points in time at which snapshots should be taken during a replay
+///
+/// A snapshot as of some time reflects every message timestamped at or before it, so it's only taken once a later
+/// message is seen (or the replay ends).
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotSchedule {
+    requested: BTreeSet<DateTime<Utc>>,
+    interval: Option<TimeDelta>,
+    next_periodic: Option<DateTime<Utc>>,
+}
+
+impl SnapshotSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes snapshots periodically, at multiples of the interval since the POSIX epoch
+    pub fn every(interval: TimeDelta) -> Self {
+        assert!(
+            interval > TimeDelta::zero(),
+            "snapshot interval must be positive"
+        );
+
+        Self {
+            interval: Some(interval),
+            ..Self::default()
+        }
+    }
+
+    /// Requests a snapshot as of some point in time
+    pub fn at(&mut self, timestamp: DateTime<Utc>) -> &mut Self {
+        self.requested.insert(timestamp);
+        self
+    }
+
+    fn first_periodic(interval: TimeDelta, now: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = interval.num_nanoseconds().unwrap_or(i64::MAX);
+        let nanos = now.timestamp_nanos_opt().unwrap_or_default();
+        let remainder = nanos.rem_euclid(interval);
+        if remainder == 0 {
+            now
+        } else {
+            DateTime::from_timestamp_nanos(nanos - remainder + interval)
+        }
+    }
+
+    /// Advances the clock of the replay, returning the snapshot times which are now in the past, in order
+    pub fn due(&mut self, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut due = BTreeSet::new();
+
+        let later = self.requested.split_off(&now);
+        due.append(&mut self.requested);
+        self.requested = later;
+
+        if let Some(interval) = self.interval {
+            let mut next = *self
+                .next_periodic
+                .get_or_insert_with(|| Self::first_periodic(interval, now));
+            while next < now {
+                due.insert(next);
+                next += interval;
+            }
+            self.next_periodic = Some(next);
+        }
+
+        due.into_iter().collect()
+    }
+
+    /// Ends the replay, returning the requested snapshot times which haven't been passed yet. Periodic snapshots end
+    /// with the data.
+    pub fn finish(&mut self) -> Vec<DateTime<Utc>> {
+        std::mem::take(&mut self.requested).into_iter().collect()
+    }
+}
+
+/// The IEX BBO of every quoted symbol at some point in time
+#[derive(Clone, Debug)]
+pub struct QuoteSnapshot<S> {
+    pub as_of: DateTime<Utc>,
+    pub quotes: HashMap<S, Bbo>,
+}
+
+/// Replays TOPS messages through a [`BboTracker`], taking snapshots of the quotes on schedule
+#[derive(Clone, Debug)]
+pub struct QuoteReplay<S> {
+    tracker: BboTracker<S>,
+    schedule: SnapshotSchedule,
+}
+
+impl<S> QuoteReplay<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new(schedule: SnapshotSchedule) -> Self {
+        Self {
+            tracker: BboTracker::new(),
+            schedule,
+        }
+    }
+
+    pub fn tracker(&self) -> &BboTracker<S> {
+        &self.tracker
+    }
+
+    pub fn schedule_mut(&mut self) -> &mut SnapshotSchedule {
+        &mut self.schedule
+    }
+
+    fn snapshots(&self, times: Vec<DateTime<Utc>>) -> Vec<QuoteSnapshot<S>> {
+        times
+            .into_iter()
+            .map(|as_of| QuoteSnapshot {
+                as_of,
+                quotes: self
+                    .tracker
+                    .iter()
+                    .map(|(symbol, bbo)| (symbol.clone(), *bbo))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Feeds a TOPS message, returning the snapshots which became due before it
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Vec<QuoteSnapshot<S>> {
+        let snapshots = match message.timestamp() {
+            Some(timestamp) => {
+                let due = self.schedule.due(timestamp);
+                self.snapshots(due)
+            }
+            None => Vec::new(),
+        };
+        self.tracker.update(message);
+        snapshots
+    }
+
+    /// Takes the requested snapshots which are still pending, as of the end of the data
+    pub fn finish(&mut self) -> Vec<QuoteSnapshot<S>> {
+        let due = self.schedule.finish();
+        self.snapshots(due)
+    }
+}
+
+/// The books of every symbol at some point in time
+#[derive(Clone, Debug)]
+pub struct BookSnapshots<S> {
+    pub as_of: DateTime<Utc>,
+    pub books: HashMap<S, BookSnapshot>,
+}
+
+/// Replays DEEP messages through a [`BookBuilder`], taking snapshots of the books on schedule
+#[derive(Clone, Debug)]
+pub struct BookReplay<S> {
+    builder: BookBuilder<S>,
+    schedule: SnapshotSchedule,
+    depth: usize,
+}
+
+impl<S> BookReplay<S>
+where
+    S: Eq + Hash + Clone,
+{
+    /// Creates a replay keeping (at most) the best `depth` levels of each side in the snapshots
+    pub fn new(schedule: SnapshotSchedule, depth: usize) -> Self {
+        Self {
+            builder: BookBuilder::new(),
+            schedule,
+            depth,
+        }
+    }
+
+    pub fn builder(&self) -> &BookBuilder<S> {
+        &self.builder
+    }
+
+    pub fn schedule_mut(&mut self) -> &mut SnapshotSchedule {
+        &mut self.schedule
+    }
+
+    fn snapshots(&self, times: Vec<DateTime<Utc>>) -> Vec<BookSnapshots<S>> {
+        times
+            .into_iter()
+            .map(|as_of| BookSnapshots {
+                as_of,
+                books: self
+                    .builder
+                    .books()
+                    .map(|(symbol, book)| (symbol.clone(), book.snapshot(self.depth)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Feeds a DEEP message, returning the snapshots which became due before it
+    pub fn update(&mut self, message: &Deep1_0Message<S>) -> Vec<BookSnapshots<S>> {
+        let snapshots = match message.timestamp() {
+            Some(timestamp) => {
+                let due = self.schedule.due(timestamp);
+                self.snapshots(due)
+            }
+            None => Vec::new(),
+        };
+        self.builder.update(message);
+        snapshots
+    }
+
+    /// Takes the requested snapshots which are still pending, as of the end of the data
+    pub fn finish(&mut self) -> Vec<BookSnapshots<S>> {
+        let due = self.schedule.finish();
+        self.snapshots(due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        deep::{PriceLevelUpdate, Side},
+        fixtures,
+    };
+
+    use super::*;
+
+    #[test]
+    fn schedule() {
+        let at = DateTime::from_timestamp_nanos;
+        let mut schedule = SnapshotSchedule::every(TimeDelta::nanoseconds(10));
+        schedule.at(at(15)).at(at(42));
+
+        assert!(schedule.due(at(7)).is_empty());
+        assert!(schedule.due(at(10)).is_empty());
+        assert_eq!(schedule.due(at(11)), [at(10)]);
+        assert_eq!(schedule.due(at(35)), [at(15), at(20), at(30)]);
+        assert!(schedule.due(at(35)).is_empty());
+        assert_eq!(schedule.finish(), [at(42)]);
+    }
+
+    #[test]
+    fn quotes_as_of() {
+        let quote = |timestamp, bid_price| {
+            Tops1_6Message::QuoteUpdate(fixtures::quote("ZIEXT", timestamp, bid_price, 10.05))
+        };
+
+        let mut schedule = SnapshotSchedule::new();
+        schedule.at(DateTime::from_timestamp_nanos(20));
+        let mut replay = QuoteReplay::new(schedule);
+
+        assert!(replay.update(&quote(10, 10.00)).is_empty());
+        assert!(replay.update(&quote(20, 10.01)).is_empty());
+        let snapshots = replay.update(&quote(30, 10.02));
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].quotes["ZIEXT"].bid_price, 10.01);
+        assert!(replay.finish().is_empty());
+    }
+
+    #[test]
+    fn books_as_of() {
+        let update = |timestamp, size| {
+            Deep1_0Message::PriceLevelUpdate(PriceLevelUpdate {
+                side: Side::Buy,
+                event_processing_complete: true,
+                timestamp: DateTime::from_timestamp_nanos(timestamp),
+                symbol: "ZIEXT",
+                size,
+                price: 10.0,
+            })
+        };
+
+        let mut schedule = SnapshotSchedule::new();
+        schedule.at(DateTime::from_timestamp_nanos(15));
+        let mut replay = BookReplay::new(schedule, 5);
+
+        replay.update(&update(10, 100));
+        let snapshots = replay.update(&update(20, 200));
+        assert_eq!(snapshots[0].books["ZIEXT"].bids[0].size, 100);
+    }
+}
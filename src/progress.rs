@@ -0,0 +1,208 @@
+use std::{
+    fmt,
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+/// How far a reader is through its input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// The bytes of the input consumed so far
+    pub bytes: u64,
+    /// The size of the input, if it's known in advance
+    pub total_bytes: Option<u64>,
+    pub segments: u64,
+    pub messages: u64,
+    /// The send time of the last segment read, i.e. how far the reader is through the trading day
+    pub send_time: Option<DateTime<Utc>>,
+    pub elapsed: Duration,
+    /// Whether the end of the input was reached
+    pub finished: bool,
+}
+
+impl Progress {
+    /// The share of the input consumed, from 0 to 1
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total_bytes {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.bytes as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+
+    pub fn messages_per_second(&self) -> f64 {
+        self.messages as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+
+    /// The estimated time left, at the average throughput so far
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total_bytes?.saturating_sub(self.bytes);
+        if remaining == 0 {
+            return Some(Duration::ZERO);
+        }
+        let rate = self.bytes_per_second();
+        (self.bytes > 0).then(|| Duration::from_secs_f64(remaining as f64 / rate))
+    }
+}
+
+/// Reports the progress of a [`HistReader`](crate::hist::HistReader) to a callback, at most once per interval and
+/// once more at the end of the input, see [`HistReader::with_progress`](crate::hist::HistReader::with_progress)
+pub struct ProgressReporter {
+    progress: Progress,
+    start: Instant,
+    last_report: Instant,
+    interval: Duration,
+    callback: Box<dyn FnMut(&Progress) + Send>,
+}
+
+impl ProgressReporter {
+    pub fn new(
+        total_bytes: Option<u64>,
+        interval: Duration,
+        callback: impl FnMut(&Progress) + Send + 'static,
+    ) -> Self {
+        Self {
+            progress: Progress {
+                bytes: 0,
+                total_bytes,
+                segments: 0,
+                messages: 0,
+                send_time: None,
+                elapsed: Duration::ZERO,
+                finished: false,
+            },
+            start: Instant::now(),
+            last_report: Instant::now(),
+            interval,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Sends the progress to a channel, e.g. to the thread of a user interface. Reporting stops if the receiver is
+    /// dropped.
+    pub fn channel(total_bytes: Option<u64>, interval: Duration, sender: Sender<Progress>) -> Self {
+        Self::new(total_bytes, interval, move |progress| {
+            let _ = sender.send(*progress);
+        })
+    }
+
+    /// The progress as of the last segment read
+    pub fn progress(&self) -> Progress {
+        Progress {
+            elapsed: self.start.elapsed(),
+            ..self.progress
+        }
+    }
+
+    /// Counts a segment of some messages, given the bytes of the input consumed so far
+    pub(crate) fn segment(&mut self, position: u64, messages: usize, send_time: DateTime<Utc>) {
+        self.progress.bytes = position;
+        self.progress.segments += 1;
+        self.progress.messages += messages as u64;
+        self.progress.send_time = Some(send_time);
+        if self.last_report.elapsed() >= self.interval {
+            self.report();
+        }
+    }
+
+    /// Reports the end of the input, once
+    pub(crate) fn finish(&mut self, position: u64) {
+        if !self.progress.finished {
+            self.progress.bytes = position;
+            self.progress.finished = true;
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.last_report = Instant::now();
+        let progress = self.progress();
+        (self.callback)(&progress);
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("progress", &self.progress)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::mpsc};
+
+    use crate::{
+        hist::{
+            tests::{capture, segment},
+            HistReader,
+        },
+        message_protocol_ids, spec,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    #[test]
+    fn estimates() {
+        let progress = Progress {
+            bytes: 250,
+            total_bytes: Some(1000),
+            segments: 1,
+            messages: 10,
+            send_time: None,
+            elapsed: Duration::from_secs(1),
+            finished: false,
+        };
+        assert_eq!(progress.fraction(), Some(0.25));
+        assert_eq!(progress.messages_per_second(), 10.0);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(3)));
+        assert_eq!(
+            Progress {
+                total_bytes: None,
+                ..progress
+            }
+            .eta(),
+            None
+        );
+    }
+
+    #[test]
+    fn reports() {
+        let file = capture(&[
+            segment(message_protocol_ids::TOPS, 1, &[&spec::QUOTE_UPDATE]),
+            segment(
+                message_protocol_ids::TOPS,
+                2,
+                &[&spec::TRADE_REPORT, &spec::QUOTE_UPDATE],
+            ),
+        ]);
+        let size = file.len() as u64;
+
+        // Every segment is reported with a zero interval, and the end once
+        let (sender, receiver) = mpsc::channel();
+        let reporter = ProgressReporter::channel(Some(size), Duration::ZERO, sender);
+        let messages = HistReader::new(Cursor::new(file))
+            .unwrap()
+            .with_progress(reporter)
+            .messages::<Tops1_6Message<String>>();
+        assert_eq!(messages.count(), 3);
+
+        let reports = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].messages, 1);
+        assert!(reports[0].fraction().unwrap() < 0.6);
+        let last = reports[2];
+        assert!(last.finished);
+        assert_eq!((last.segments, last.messages, last.bytes), (2, 3, size));
+        assert_eq!(last.eta(), Some(Duration::ZERO));
+    }
+}
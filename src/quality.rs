@@ -0,0 +1,390 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    bbo::Bbo,
+    export::{write_json_object, Record, Value},
+    hist::{self, HistReader},
+    iex_tp::IexTp1Segment,
+    locked_crossed::QuoteCondition,
+    message_protocol_ids,
+    sequence::{SequenceRange, SequenceTracker, Sequencing},
+    tops::{tops_1_6_message, QuoteUpdate, Tops1_6Message},
+};
+
+/// The thresholds of a [`QualityAnalyzer`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QualityConfig {
+    /// Symbols without quotes or trades from this time on are reported as stale
+    pub stale_after: Option<DateTime<Utc>>,
+    /// Trades whose price differs from the previous trade of their symbol by more than this fraction of it are
+    /// reported as outliers
+    pub outlier_threshold: f64,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            stale_after: None,
+            outlier_threshold: 0.1,
+        }
+    }
+}
+
+/// A trade far from the previous trade of its symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriceOutlier {
+    pub timestamp: DateTime<Utc>,
+    pub symbol: String,
+    pub price: f64,
+    /// The price of the previous trade
+    pub reference_price: f64,
+}
+
+impl PriceOutlier {
+    pub fn to_record(&self) -> Record {
+        vec![
+            ("timestamp", Value::Time(self.timestamp)),
+            ("symbol", Value::Text(self.symbol.clone())),
+            ("price", Value::Price(self.price)),
+            ("reference_price", Value::Price(self.reference_price)),
+        ]
+    }
+}
+
+/// The data-quality findings of a day of TOPS messages
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QualityReport {
+    pub segments: u64,
+    pub messages: u64,
+    /// The messages which couldn't be decoded
+    pub invalid_messages: u64,
+    /// The quote updates of each symbol with a side which has a price but no size, or a size but no price
+    pub zero_size_quotes: BTreeMap<String, u64>,
+    /// The symbols without quotes or trades from [`QualityConfig::stale_after`] on, with the time of their last one (if
+    /// any)
+    pub stale_symbols: BTreeMap<String, Option<DateTime<Utc>>>,
+    /// The number of times the quote of each symbol became crossed
+    pub crossed_markets: BTreeMap<String, u64>,
+    pub price_outliers: Vec<PriceOutlier>,
+    pub gaps: Vec<SequenceRange>,
+    /// The sequence numbers seen more than once
+    pub duplicates: Vec<SequenceRange>,
+}
+
+fn range_record(range: &SequenceRange) -> Record {
+    vec![
+        (
+            "message_protocol_id",
+            Value::Int(range.stream.message_protocol_id.into()),
+        ),
+        ("session_id", Value::Int(range.stream.session_id.into())),
+        ("first", Value::Int(range.first)),
+        ("count", Value::Int(range.count)),
+        ("send_time", Value::Time(range.send_time)),
+    ]
+}
+
+impl QualityReport {
+    /// Writes the report as a JSON object, with the findings by symbol as nested objects
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(
+            writer,
+            "{{\"segments\":{},\"messages\":{},\"invalid_messages\":{}",
+            self.segments, self.messages, self.invalid_messages
+        )?;
+        for (name, counts) in [
+            ("zero_size_quotes", &self.zero_size_quotes),
+            ("crossed_markets", &self.crossed_markets),
+        ] {
+            let counts = counts
+                .iter()
+                .map(|(symbol, count)| (symbol.as_str(), Value::Int(*count as i64)));
+            write!(writer, ",\"{name}\":")?;
+            write_symbol_object(&mut writer, counts)?;
+        }
+
+        // Symbols never quoted nor traded have a null time
+        write!(writer, ",\"stale_symbols\":{{")?;
+        for (index, (symbol, last_update)) in self.stale_symbols.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{symbol:?}:")?;
+            match last_update {
+                Some(time) => write!(writer, "\"{}\"", Value::Time(*time))?,
+                None => write!(writer, "null")?,
+            }
+        }
+        write!(writer, "}}")?;
+
+        let lists = [
+            (
+                "price_outliers",
+                self.price_outliers
+                    .iter()
+                    .map(PriceOutlier::to_record)
+                    .collect::<Vec<_>>(),
+            ),
+            ("gaps", self.gaps.iter().map(range_record).collect()),
+            (
+                "duplicates",
+                self.duplicates.iter().map(range_record).collect(),
+            ),
+        ];
+        for (name, records) in lists {
+            write!(writer, ",\"{name}\":[")?;
+            for (index, record) in records.iter().enumerate() {
+                if index > 0 {
+                    write!(writer, ",")?;
+                }
+                write_json_object(&mut writer, record)?;
+            }
+            write!(writer, "]")?;
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+// Symbols are printable ASCII, but for quotes and backslashes, which Debug escapes as JSON does
+fn write_symbol_object<'a, W: Write>(
+    writer: &mut W,
+    values: impl Iterator<Item = (&'a str, Value)>,
+) -> io::Result<()> {
+    write!(writer, "{{")?;
+    for (index, (symbol, value)) in values.enumerate() {
+        if index > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, "{symbol:?}:{value}")?;
+    }
+    write!(writer, "}}")
+}
+
+#[derive(Debug, Default)]
+struct SymbolState {
+    last_update: Option<DateTime<Utc>>,
+    condition: QuoteCondition,
+    last_trade_price: Option<f64>,
+}
+
+/// Builds a [`QualityReport`] in one pass over the segments of a HIST file
+///
+/// Only TOPS messages are analyzed, but the sequence numbers of every protocol are checked.
+#[derive(Debug, Default)]
+pub struct QualityAnalyzer {
+    config: QualityConfig,
+    report: QualityReport,
+    tracker: SequenceTracker,
+    symbols: HashMap<String, SymbolState>,
+}
+
+impl QualityAnalyzer {
+    pub fn new(config: QualityConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_segment(&mut self, segment: &IexTp1Segment<'_>) {
+        self.report.segments += 1;
+        self.report.messages += segment.messages.len() as u64;
+        match self.tracker.update(segment) {
+            Sequencing::InOrder => {}
+            Sequencing::Gap(range) => self.report.gaps.push(range),
+            Sequencing::Duplicate(range) => self.report.duplicates.push(range),
+        }
+
+        if segment.message_protocol_id != message_protocol_ids::TOPS {
+            return;
+        }
+        for message in &segment.messages {
+            match tops_1_6_message::<String>(message) {
+                Ok((_, message)) => self.add_message(message),
+                Err(_) => self.report.invalid_messages += 1,
+            }
+        }
+    }
+
+    fn symbol(&mut self, symbol: &str) -> &mut SymbolState {
+        if !self.symbols.contains_key(symbol) {
+            self.symbols
+                .insert(symbol.to_string(), SymbolState::default());
+        }
+        self.symbols
+            .get_mut(symbol)
+            .expect("the symbol was inserted")
+    }
+
+    fn add_message(&mut self, message: Tops1_6Message<String>) {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => self.add_quote(quote),
+            Tops1_6Message::TradeReport(trade) => {
+                let threshold = self.config.outlier_threshold;
+                let state = self.symbol(&trade.symbol);
+                state.last_update = Some(trade.timestamp);
+                let reference_price = state.last_trade_price.replace(trade.price);
+                if let Some(reference_price) = reference_price {
+                    if (trade.price - reference_price).abs() > threshold * reference_price {
+                        self.report.price_outliers.push(PriceOutlier {
+                            timestamp: trade.timestamp,
+                            symbol: trade.symbol,
+                            price: trade.price,
+                            reference_price,
+                        });
+                    }
+                }
+            }
+            message => {
+                if let Some(symbol) = message.symbol() {
+                    self.symbol(symbol);
+                }
+            }
+        }
+    }
+
+    fn add_quote(&mut self, quote: QuoteUpdate<String>) {
+        let state = self.symbol(&quote.symbol);
+        state.last_update = Some(quote.timestamp);
+        let condition = QuoteCondition::from(&Bbo::from(&quote));
+        let crossed =
+            condition == QuoteCondition::Crossed && state.condition != QuoteCondition::Crossed;
+        state.condition = condition;
+
+        let inconsistent = |size: u32, price: f64| (size == 0) != (price == 0.0);
+        if inconsistent(quote.bid_size, quote.bid_price)
+            || inconsistent(quote.ask_size, quote.ask_price)
+        {
+            *self
+                .report
+                .zero_size_quotes
+                .entry(quote.symbol.clone())
+                .or_default() += 1;
+        }
+        if crossed {
+            *self.report.crossed_markets.entry(quote.symbol).or_default() += 1;
+        }
+    }
+
+    pub fn finish(mut self) -> QualityReport {
+        if let Some(stale_after) = self.config.stale_after {
+            self.report.stale_symbols = self
+                .symbols
+                .into_iter()
+                .filter(|(_, state)| state.last_update.is_none_or(|last| last < stale_after))
+                .map(|(symbol, state)| (symbol, state.last_update))
+                .collect();
+        }
+        self.report
+    }
+}
+
+/// Analyzes a whole HIST file. Only I/O errors interrupt the analysis, invalid segments being skipped.
+pub fn analyze<R: Read>(
+    mut reader: HistReader<R>,
+    config: QualityConfig,
+) -> io::Result<QualityReport> {
+    let mut analyzer = QualityAnalyzer::new(config);
+    loop {
+        match reader.next_segment() {
+            Ok(Some(captured)) => analyzer.add_segment(&captured.segment),
+            Ok(None) => return Ok(analyzer.finish()),
+            Err(hist::Error::Io(e)) => return Err(e),
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::hist::tests::{capture, segment};
+
+    use super::*;
+
+    const TIMESTAMP: i64 = 1492448400000000000;
+
+    fn quote(symbol: &[u8; 8], bid_size: u32, bid_price: i64, ask_price: i64) -> Vec<u8> {
+        let mut quote = vec![0x51, 0x00];
+        quote.extend_from_slice(&TIMESTAMP.to_le_bytes());
+        quote.extend_from_slice(symbol);
+        quote.extend_from_slice(&bid_size.to_le_bytes());
+        quote.extend_from_slice(&(bid_price * 10000).to_le_bytes());
+        quote.extend_from_slice(&(ask_price * 10000).to_le_bytes());
+        quote.extend_from_slice(&100u32.to_le_bytes());
+        quote
+    }
+
+    fn trade(price: i64) -> Vec<u8> {
+        let mut trade = vec![0x54, 0x00];
+        trade.extend_from_slice(&TIMESTAMP.to_le_bytes());
+        trade.extend_from_slice(b"ZIEXT   ");
+        trade.extend_from_slice(&100u32.to_le_bytes());
+        trade.extend_from_slice(&(price * 10000).to_le_bytes());
+        trade.extend_from_slice(&1i64.to_le_bytes());
+        trade
+    }
+
+    #[test]
+    fn report() {
+        let capture = capture(&[
+            segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[
+                    &quote(b"ZIEXT   ", 100, 10, 11),
+                    &quote(b"ZIEXT   ", 0, 10, 11),
+                    &quote(b"ZIEXT   ", 100, 12, 11),
+                    &quote(b"ZIEXT   ", 100, 13, 11),
+                    &trade(100),
+                    &trade(105),
+                    &trade(150),
+                ],
+            ),
+            segment(
+                message_protocol_ids::TOPS,
+                5,
+                &[&quote(b"ZXIET   ", 100, 10, 11)],
+            ),
+        ]);
+
+        let config = QualityConfig {
+            stale_after: Some(DateTime::from_timestamp_nanos(TIMESTAMP) + TimeDelta::seconds(1)),
+            ..Default::default()
+        };
+        let report = analyze(HistReader::new(&capture[..]).unwrap(), config).unwrap();
+        assert_eq!((report.segments, report.messages), (2, 8));
+        assert_eq!(
+            report.zero_size_quotes,
+            BTreeMap::from([("ZIEXT".to_string(), 1)])
+        );
+        assert_eq!(
+            report.crossed_markets,
+            BTreeMap::from([("ZIEXT".to_string(), 1)])
+        );
+        assert_eq!(report.price_outliers.len(), 1);
+        assert_eq!(report.price_outliers[0].price, 150.0);
+        assert_eq!(report.price_outliers[0].reference_price, 105.0);
+        assert_eq!(report.stale_symbols.len(), 2);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].first, 5);
+
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(
+            "{\"segments\":2,\"messages\":8,\"invalid_messages\":0,\"zero_size_quotes\":{\"ZIEXT\":1},\
+             \"crossed_markets\":{\"ZIEXT\":1},\"stale_symbols\":{\"ZIEXT\":\"2017-04-17T17:00:00.000000000Z\","
+        ));
+        assert!(json.contains(
+            "\"price_outliers\":[{\"timestamp\":\"2017-04-17T17:00:00.000000000Z\",\"symbol\":\"ZIEXT\",\
+             \"price\":150.0000,\"reference_price\":105.0000}]"
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{
+    bbo::Bbo,
+    tops::{MarketSession, QuoteUpdate, Tops1_6Message},
+    utils::price_key,
+};
+
+/// The minimum price increment of a quote at some price, per Regulation NMS: a cent from $1.00, a hundredth of a cent
+/// below
+pub fn tick_size(price: f64) -> f64 {
+    if price >= 1.0 {
+        0.01
+    } else {
+        0.0001
+    }
+}
+
+// The quote attributes compared by the filters
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct QuoteState {
+    available: bool,
+    market_session: MarketSession,
+    bbo: Bbo,
+}
+
+impl<S> From<&QuoteUpdate<S>> for QuoteState {
+    fn from(quote: &QuoteUpdate<S>) -> Self {
+        Self {
+            available: quote.available,
+            market_session: quote.market_session,
+            bbo: Bbo::from(quote),
+        }
+    }
+}
+
+/// Suppresses the quote updates which don't change the quote of their symbol significantly
+///
+/// A quote update is kept if, compared to the last update kept for the symbol, a side's price moved by at least
+/// `min_ticks` ticks or its size changed by at least `min_size_change` (relative, e.g. 0.5 for 50%). Updates which
+/// (un)quote a side or change the availability or session of the symbol are always kept.
+#[derive(Clone, Debug)]
+pub struct QuoteThresholdFilter<S> {
+    min_ticks: u32,
+    min_size_change: f64,
+    last: HashMap<S, QuoteState>,
+}
+
+impl<S> QuoteThresholdFilter<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new(min_ticks: u32, min_size_change: f64) -> Self {
+        Self {
+            min_ticks,
+            min_size_change,
+            last: HashMap::new(),
+        }
+    }
+
+    fn side_changed(&self, (old_price, old_size): (f64, u32), (price, size): (f64, u32)) -> bool {
+        if (old_size == 0) != (size == 0) {
+            return true;
+        }
+        if size == 0 {
+            return false;
+        }
+
+        let ticks =
+            (price_key(price) - price_key(old_price)).abs() / price_key(tick_size(old_price));
+        let size_change = (f64::from(size) - f64::from(old_size)).abs() / f64::from(old_size);
+        ticks >= i64::from(self.min_ticks) || size_change >= self.min_size_change
+    }
+
+    /// Whether a quote update is kept
+    pub fn accept(&mut self, quote: &QuoteUpdate<S>) -> bool {
+        let current = QuoteState::from(quote);
+        let accepted = self.last.get(&quote.symbol).is_none_or(|last| {
+            last.available != current.available
+                || last.market_session != current.market_session
+                || self.side_changed(
+                    (last.bbo.bid_price, last.bbo.bid_size),
+                    (current.bbo.bid_price, current.bbo.bid_size),
+                )
+                || self.side_changed(
+                    (last.bbo.ask_price, last.bbo.ask_size),
+                    (current.bbo.ask_price, current.bbo.ask_size),
+                )
+        });
+
+        if accepted {
+            self.last.insert(quote.symbol.clone(), current);
+        }
+        accepted
+    }
+
+    /// Whether a TOPS message is kept. Messages other than quote updates always are.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> bool {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => self.accept(quote),
+            _ => true,
+        }
+    }
+}
+
+/// Drops the quote updates identical (in prices, sizes and flags) to the previous update of their symbol
+#[derive(Clone, Debug)]
+pub struct QuoteDeduplicator<S> {
+    last: HashMap<S, QuoteState>,
+}
+
+impl<S> Default for QuoteDeduplicator<S> {
+    fn default() -> Self {
+        Self {
+            last: HashMap::new(),
+        }
+    }
+}
+
+impl<S> QuoteDeduplicator<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a quote update is kept
+    pub fn accept(&mut self, quote: &QuoteUpdate<S>) -> bool {
+        let current = QuoteState::from(quote);
+        match self.last.get_mut(&quote.symbol) {
+            Some(last) if *last == current => false,
+            Some(last) => {
+                *last = current;
+                true
+            }
+            None => {
+                self.last.insert(quote.symbol.clone(), current);
+                true
+            }
+        }
+    }
+
+    /// Whether a TOPS message is kept. Messages other than quote updates always are.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> bool {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => self.accept(quote),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    fn quote(bid_price: f64, bid_size: u32, ask_size: u32) -> QuoteUpdate<&'static str> {
+        QuoteUpdate {
+            bid_size,
+            ask_size,
+            ..fixtures::quote("ZIEXT", 0, bid_price, 10.50)
+        }
+    }
+
+    #[test]
+    fn thresholds() {
+        let mut filter = QuoteThresholdFilter::new(2, 0.5);
+
+        assert!(filter.accept(&quote(10.00, 100, 100)));
+        assert!(!filter.accept(&quote(10.01, 100, 100)));
+        assert!(filter.accept(&quote(10.02, 100, 100)));
+        assert!(!filter.accept(&quote(10.02, 140, 100)));
+        assert!(filter.accept(&quote(10.02, 150, 100)));
+        assert!(!filter.accept(&quote(10.02, 150, 60)));
+        assert!(filter.accept(&quote(10.02, 150, 0)));
+        assert!(!filter.accept(&quote(10.02, 150, 0)));
+
+        let mut halted = quote(10.02, 150, 0);
+        halted.available = false;
+        assert!(filter.accept(&halted));
+
+        assert_eq!(tick_size(0.5), 0.0001);
+        assert!(filter.accept(&quote(0.5, 100, 0)));
+        assert!(filter.accept(&quote(0.5002, 100, 0)));
+        assert!(!filter.accept(&quote(0.5003, 100, 0)));
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut deduplicator = QuoteDeduplicator::new();
+
+        assert!(deduplicator.accept(&quote(10.00, 100, 100)));
+        assert!(!deduplicator.accept(&quote(10.00, 100, 100)));
+        assert!(deduplicator.accept(&quote(10.00, 100, 200)));
+
+        let mut other = quote(10.00, 100, 200);
+        other.symbol = "ZVZZT";
+        assert!(deduplicator.update(&Tops1_6Message::QuoteUpdate(other.clone())));
+        assert!(!deduplicator.update(&Tops1_6Message::QuoteUpdate(other.clone())));
+
+        other.market_session = MarketSession::OutOfHours;
+        assert!(deduplicator.accept(&other));
+        other.timestamp = DateTime::from_timestamp_nanos(1);
+        assert!(!deduplicator.accept(&other));
+    }
+}
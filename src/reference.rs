@@ -0,0 +1,344 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    io::{self, BufRead, Write},
+};
+
+use chrono::DateTime;
+
+use crate::tops::{LuldTier, SecurityDirectory, SecurityDirectoryFlags, Tops1_6Message};
+
+const CSV_HEADER: &str =
+    "symbol,timestamp,round_lot_size,adjusted_poc_price,luld_tier,test_security,when_issued,etp";
+
+fn invalid_data(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {line}: {message}"),
+    )
+}
+
+/// A difference between the security directories of two days
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListingChange<S> {
+    /// A symbol only in the directory of the later day
+    Listed(SecurityDirectory<S>),
+    /// A symbol only in the directory of the earlier day
+    Delisted(SecurityDirectory<S>),
+    /// A symbol whose attributes changed. The adjusted previous official closing price, which changes every day, isn't
+    /// compared.
+    Changed {
+        previous: SecurityDirectory<S>,
+        current: SecurityDirectory<S>,
+    },
+}
+
+impl<S> ListingChange<S> {
+    pub fn symbol(&self) -> &S {
+        match self {
+            ListingChange::Listed(directory) | ListingChange::Delisted(directory) => {
+                &directory.symbol
+            }
+            ListingChange::Changed { current, .. } => &current.symbol,
+        }
+    }
+
+    /// The names of the attributes which changed (as in the CSV of [`ReferenceData`]), or none for listings and
+    /// delistings
+    pub fn changed_attributes(&self) -> Vec<&'static str> {
+        let ListingChange::Changed { previous, current } = self else {
+            return Vec::new();
+        };
+        [
+            (
+                "round_lot_size",
+                previous.round_lot_size != current.round_lot_size,
+            ),
+            ("luld_tier", previous.luld_tier != current.luld_tier),
+            (
+                "test_security",
+                previous.flags.test_security != current.flags.test_security,
+            ),
+            (
+                "when_issued",
+                previous.flags.when_issued != current.flags.when_issued,
+            ),
+            ("etp", previous.flags.etp != current.flags.etp),
+        ]
+        .into_iter()
+        .filter_map(|(name, changed)| changed.then_some(name))
+        .collect()
+    }
+}
+
+/// Stores the latest Security Directory attributes of every symbol
+#[derive(Clone, Debug)]
+pub struct ReferenceData<S> {
+    securities: HashMap<S, SecurityDirectory<S>>,
+}
+
+impl<S> Default for ReferenceData<S> {
+    fn default() -> Self {
+        Self {
+            securities: HashMap::new(),
+        }
+    }
+}
+
+impl<S> ReferenceData<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, directory: &SecurityDirectory<S>) {
+        self.securities
+            .insert(directory.symbol.clone(), directory.clone());
+    }
+
+    /// Feeds a TOPS message to the store. Messages other than security directories are ignored.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        if let Tops1_6Message::SecurityDirectory(directory) = message {
+            self.apply(directory);
+        }
+    }
+
+    pub fn get<Q>(&self, symbol: &Q) -> Option<&SecurityDirectory<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.securities.get(symbol)
+    }
+
+    pub fn round_lot_size<Q>(&self, symbol: &Q) -> Option<u32>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.get(symbol)?.round_lot_size)
+    }
+
+    pub fn luld_tier<Q>(&self, symbol: &Q) -> Option<LuldTier>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Some(self.get(symbol)?.luld_tier)
+    }
+
+    pub fn len(&self) -> usize {
+        self.securities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.securities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&S, &SecurityDirectory<S>)> {
+        self.securities.iter()
+    }
+
+    /// The listing changes from this directory (e.g. of the previous day) to a later one, sorted by symbol
+    pub fn diff(&self, later: &ReferenceData<S>) -> Vec<ListingChange<S>>
+    where
+        S: Ord,
+    {
+        let mut changes = Vec::new();
+        for (symbol, current) in &later.securities {
+            match self.securities.get(symbol) {
+                None => changes.push(ListingChange::Listed(current.clone())),
+                Some(previous) => {
+                    let change = ListingChange::Changed {
+                        previous: previous.clone(),
+                        current: current.clone(),
+                    };
+                    if !change.changed_attributes().is_empty() {
+                        changes.push(change);
+                    }
+                }
+            }
+        }
+        changes.extend(
+            self.securities
+                .iter()
+                .filter(|(symbol, _)| !later.securities.contains_key(*symbol))
+                .map(|(_, previous)| ListingChange::Delisted(previous.clone())),
+        );
+        changes.sort_by(|a, b| a.symbol().cmp(b.symbol()));
+        changes
+    }
+
+    /// Writes a snapshot of the store as CSV, sorted by symbol
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+        S: Display,
+    {
+        let mut rows = self
+            .securities
+            .values()
+            .map(|directory| (directory.symbol.to_string(), directory))
+            .collect::<Vec<_>>();
+        rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        writeln!(writer, "{CSV_HEADER}")?;
+        for (symbol, directory) in rows {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                symbol,
+                directory
+                    .timestamp
+                    .timestamp_nanos_opt()
+                    .unwrap_or_default(),
+                directory.round_lot_size,
+                directory.adjusted_poc_price,
+                u8::from(directory.luld_tier),
+                u8::from(directory.flags.test_security),
+                u8::from(directory.flags.when_issued),
+                u8::from(directory.flags.etp),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot written by [`ReferenceData::write_csv`]
+    pub fn read_csv<R>(reader: R) -> io::Result<Self>
+    where
+        R: BufRead,
+        S: for<'a> TryFrom<&'a str>,
+    {
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(CSV_HEADER) {
+            return Err(invalid_data(1, "missing header"));
+        }
+
+        let mut store = Self::new();
+        for (index, line) in lines.enumerate() {
+            let line_number = index + 2;
+            let line = line?;
+            let invalid = |message| invalid_data(line_number, message);
+
+            let fields: [&str; 8] = line
+                .split(',')
+                .collect::<Vec<_>>()
+                .try_into()
+                .map_err(|_| invalid("wrong number of fields"))?;
+            let [symbol, timestamp, round_lot, poc_price, luld_tier, test, when_issued, etp] =
+                fields;
+            let flag = |field: &str| match field {
+                "0" => Ok(false),
+                "1" => Ok(true),
+                _ => Err(invalid("invalid flag")),
+            };
+
+            let directory = SecurityDirectory {
+                flags: SecurityDirectoryFlags {
+                    test_security: flag(test)?,
+                    when_issued: flag(when_issued)?,
+                    etp: flag(etp)?,
+                },
+                timestamp: DateTime::from_timestamp_nanos(
+                    timestamp
+                        .parse()
+                        .map_err(|_| invalid("invalid timestamp"))?,
+                ),
+                symbol: S::try_from(symbol).map_err(|_| invalid("invalid symbol"))?,
+                round_lot_size: round_lot
+                    .parse()
+                    .map_err(|_| invalid("invalid round lot size"))?,
+                adjusted_poc_price: poc_price.parse().map_err(|_| invalid("invalid price"))?,
+                luld_tier: luld_tier
+                    .parse::<u8>()
+                    .ok()
+                    .and_then(|tier| LuldTier::try_from(tier).ok())
+                    .ok_or_else(|| invalid("invalid LULD tier"))?,
+            };
+            store.apply(&directory);
+        }
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(symbol: &str, round_lot_size: u32, etp: bool) -> SecurityDirectory<String> {
+        SecurityDirectory {
+            flags: SecurityDirectoryFlags {
+                test_security: true,
+                when_issued: false,
+                etp,
+            },
+            timestamp: DateTime::from_timestamp_nanos(1_500_000_000_000_000_000),
+            symbol: symbol.to_string(),
+            round_lot_size,
+            adjusted_poc_price: 99.05,
+            luld_tier: LuldTier::Tier1,
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut store = ReferenceData::new();
+        store.update(&Tops1_6Message::SecurityDirectory(directory(
+            "ZVZZT", 100, false,
+        )));
+        store.apply(&directory("ZIEXT", 10, true));
+        store.apply(&directory("ZIEXT", 100, true));
+
+        let mut csv = Vec::new();
+        store.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(1).unwrap().starts_with("ZIEXT,"));
+
+        let restored = ReferenceData::<String>::read_csv(csv.as_bytes()).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.round_lot_size("ZIEXT"), Some(100));
+        assert_eq!(restored.luld_tier("ZVZZT"), Some(LuldTier::Tier1));
+        let ziext = restored.get("ZIEXT").unwrap();
+        assert!(ziext.flags.etp);
+        assert_eq!(ziext.adjusted_poc_price, 99.05);
+        assert_eq!(ziext.timestamp, store.get("ZIEXT").unwrap().timestamp);
+
+        assert!(ReferenceData::<String>::read_csv(&b"symbol\n"[..]).is_err());
+    }
+
+    #[test]
+    fn listing_changes() {
+        let mut monday = ReferenceData::new();
+        monday.apply(&directory("ZIEXT", 100, false));
+        monday.apply(&directory("ZVZZT", 100, false));
+        monday.apply(&directory("ZXIET", 100, false));
+
+        let mut tuesday = ReferenceData::new();
+        let mut ziext = directory("ZIEXT", 100, false);
+        ziext.adjusted_poc_price = 99.1;
+        tuesday.apply(&ziext);
+        tuesday.apply(&directory("ZVZZT", 10, true));
+        tuesday.apply(&directory("ZWZZT", 100, false));
+
+        let changes = monday.diff(&tuesday);
+        assert_eq!(
+            changes
+                .iter()
+                .map(ListingChange::symbol)
+                .collect::<Vec<_>>(),
+            ["ZVZZT", "ZWZZT", "ZXIET"]
+        );
+        assert_eq!(changes[0].changed_attributes(), ["round_lot_size", "etp"]);
+        assert_eq!(
+            changes[1],
+            ListingChange::Listed(directory("ZWZZT", 100, false))
+        );
+        assert!(matches!(changes[2], ListingChange::Delisted(_)));
+        assert!(tuesday.diff(&tuesday).is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    export::{Record, ToRecord, Value},
+    hist::{self, HistReader, Message},
+};
+
+/// Renders a message on a single line, as the fields of its record (see [`ToRecord`]) separated by spaces, e.g.
+/// `kind=system_event timestamp=2017-04-17T17:00:00.000000000Z event_type=E`
+///
+/// The rendering only depends on the content of the message: prices have exactly 4 decimal digits, timestamps are in
+/// UTC with nanoseconds, and texts are quoted if they are empty or contain spaces, so that renderings can be compared
+/// across versions and platforms, e.g. by snapshot tests.
+pub fn render<M: ToRecord>(message: &M) -> String {
+    render_record(&message.to_record())
+}
+
+/// Renders a record, see [`render`]
+pub fn render_record(record: &Record) -> String {
+    let mut line = String::new();
+    for (index, (name, value)) in record.iter().enumerate() {
+        if index > 0 {
+            line.push(' ');
+        }
+        match value {
+            Value::Text(text) if text.is_empty() || text.contains(char::is_whitespace) => {
+                write!(line, "{name}={text:?}")
+            }
+            value => write!(line, "{name}={value}"),
+        }
+        .expect("writing to a String can't fail");
+    }
+    line
+}
+
+/// Renders the segments of a HIST file carrying a kind of message, and their messages, one per line
+///
+/// Every segment is rendered as its stream, sequence numbers and send time, followed by its messages, indented.
+/// Messages which can't be decoded are rendered as hexadecimal bytes, so that a rendering shows any change in the
+/// behavior of the parsers.
+pub fn render_capture<M, R>(mut reader: HistReader<R>) -> Result<String, hist::Error>
+where
+    M: Message + ToRecord,
+    R: Read,
+{
+    let mut rendering = String::new();
+    while let Some(captured) = reader.next_segment()? {
+        let segment = &captured.segment;
+        if segment.message_protocol_id != M::MESSAGE_PROTOCOL_ID {
+            continue;
+        }
+
+        let _ = writeln!(
+            rendering,
+            "segment session={} channel={} sequence={}..{} send_time={}",
+            segment.session_id,
+            segment.channel_id,
+            segment.first_message_sequence_no,
+            segment.first_message_sequence_no + segment.messages.len() as i64,
+            Value::Time(segment.send_time),
+        );
+        for message in &segment.messages {
+            let line = match M::parse(message) {
+                Ok(([], decoded)) => render(&decoded),
+                _ => message
+                    .iter()
+                    .fold("undecodable=".to_string(), |mut line, byte| {
+                        let _ = write!(line, "{byte:02X}");
+                        line
+                    }),
+            };
+            let _ = writeln!(rendering, "    {line}");
+        }
+    }
+    Ok(rendering)
+}
+
+/// Checks a rendering against the snapshot of the given name, in the `snapshots` directory of the crate under test
+///
+/// Missing snapshots are created, and outdated ones are overwritten if the `UPDATE_SNAPSHOTS` environment variable is
+/// set, so that changes of renderings can be reviewed as changes of files.
+///
+/// # Panics
+///
+/// Panics if the rendering differs from the snapshot, showing the first different line.
+pub fn assert_snapshot(name: &str, rendering: &str) {
+    let directory = env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join("snapshots");
+    assert_snapshot_in(&directory, name, rendering);
+}
+
+fn assert_snapshot_in(directory: &Path, name: &str, rendering: &str) {
+    let path = directory.join(format!("{name}.snap"));
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    match fs::read_to_string(&path) {
+        Ok(snapshot) if snapshot == rendering => {}
+        Ok(snapshot) if !update => {
+            let (line, expected, actual) = snapshot
+                .lines()
+                .map(Some)
+                .chain(std::iter::repeat(None))
+                .zip(rendering.lines().map(Some).chain(std::iter::repeat(None)))
+                .enumerate()
+                .map(|(index, (expected, actual))| (index + 1, expected, actual))
+                .find(|(_, expected, actual)| expected != actual)
+                .expect("different renderings have a different line");
+            panic!(
+                "rendering differs from snapshot {} at line {line}:\n  expected: {}\n    actual: {}\n\
+                 (set UPDATE_SNAPSHOTS to update it)",
+                path.display(),
+                expected.unwrap_or("<end>"),
+                actual.unwrap_or("<end>"),
+            );
+        }
+        _ => {
+            fs::create_dir_all(directory).expect("failed to create the snapshot directory");
+            fs::write(&path, rendering).expect("failed to write the snapshot");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids, spec,
+        tops::{tops_1_6_message, Tops1_6Message},
+    };
+
+    use super::*;
+
+    #[test]
+    fn renderings() {
+        let (_, quote) = tops_1_6_message::<String>(&spec::QUOTE_UPDATE).unwrap();
+        assert_eq!(
+            render(&quote),
+            "kind=quote_update timestamp=2016-08-23T19:30:32.572715948Z symbol=ZIEXT available=true \
+             market_session=regular bid_size=9700 bid_price=99.0500 ask_size=1000 ask_price=99.0700"
+        );
+
+        let capture = capture(&[segment(
+            message_protocol_ids::TOPS,
+            7,
+            &[&spec::SYSTEM_EVENT, &[0x53, 0x00]],
+        )]);
+        let rendering =
+            render_capture::<Tops1_6Message<String>, _>(HistReader::new(&capture[..]).unwrap())
+                .unwrap();
+        assert_eq!(
+            rendering,
+            "segment session=1116143616 channel=1 sequence=7..9 send_time=2017-04-17T17:00:00.000000000Z\n\
+             \x20   kind=system_event timestamp=2017-04-17T17:00:00.000000000Z event_type=E\n\
+             \x20   undecodable=5300\n"
+        );
+    }
+
+    #[test]
+    fn snapshots() {
+        let directory =
+            env::temp_dir().join(format!("iex-parser-snapshots-{}", std::process::id()));
+        assert_snapshot_in(&directory, "example", "a\nb\n");
+        assert_snapshot_in(&directory, "example", "a\nb\n");
+
+        let failure = panic::catch_unwind(|| assert_snapshot_in(&directory, "example", "a\nc\n"))
+            .unwrap_err();
+        let message = failure.downcast_ref::<String>().unwrap();
+        assert!(
+            message.contains("at line 2:\n  expected: b\n    actual: c"),
+            "{message}"
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    filter::raw_timestamp,
+    hist::{Error, Message},
+    iex_tp::IexTp1Segment,
+    tops::Tops1_6Message,
+};
+
+/// The largest payload of the segments built by a [`SegmentEncoder`] by default, so that they fit in Ethernet frames
+/// of 1500 bytes with their IP, UDP and IEX-TP headers
+pub const DEFAULT_MAX_PAYLOAD: usize = 1500 - 20 - 8 - 40;
+
+/// Paces events (e.g. messages or segments) by their timestamps, to replay them with their original timing
+///
+/// The first event sets the origin: later ones are due after the time elapsed between their timestamp and the first
+/// one's, divided by the speed. Events whose timestamp goes back in time are due immediately.
+#[derive(Clone, Debug)]
+pub struct Pacer {
+    /// The speed multiplier, or `None` to replay as fast as possible
+    speed: Option<f64>,
+    origin: Option<(DateTime<Utc>, Instant)>,
+    /// The timestamp of the last event
+    last: Option<DateTime<Utc>>,
+}
+
+impl Pacer {
+    /// Creates a pacer replaying at a multiple of the original speed (e.g. 2.0 for twice as fast)
+    pub fn new(speed: f64) -> Self {
+        assert!(
+            speed.is_finite() && speed > 0.0,
+            "replay speed must be positive"
+        );
+
+        Self {
+            speed: Some(speed),
+            origin: None,
+            last: None,
+        }
+    }
+
+    /// Creates a pacer which never waits
+    pub fn unpaced() -> Self {
+        Self {
+            speed: None,
+            origin: None,
+            last: None,
+        }
+    }
+
+    /// The speed multiplier, or `None` if replaying as fast as possible
+    pub fn speed(&self) -> Option<f64> {
+        self.speed
+    }
+
+    /// Changes the speed of the replay from the last event on, or replays as fast as possible with `None`
+    pub fn set_speed(&mut self, speed: Option<f64>) {
+        if let Some(speed) = speed {
+            assert!(
+                speed.is_finite() && speed > 0.0,
+                "replay speed must be positive"
+            );
+        }
+        self.speed = speed;
+        // The following events are due relative to the last one, from now
+        self.origin = self.last.map(|last| (last, Instant::now()));
+    }
+
+    /// How long to wait before emitting an event with the given timestamp
+    pub fn delay(&mut self, timestamp: DateTime<Utc>) -> Duration {
+        self.last = Some(timestamp);
+        let Some(speed) = self.speed else {
+            return Duration::ZERO;
+        };
+        let (origin, start) = *self.origin.get_or_insert((timestamp, Instant::now()));
+
+        let offset = (timestamp - origin).to_std().unwrap_or_default();
+        (start + offset.div_f64(speed)).saturating_duration_since(Instant::now())
+    }
+
+    /// Waits until an event with the given timestamp is due
+    pub fn wait(&mut self, timestamp: DateTime<Utc>) {
+        let delay = self.delay(timestamp);
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Replays decoded messages with their original timing, e.g. to run a strategy simulation against realistic timing
+///
+/// Each message is yielded once it's due according to its timestamp, see [`Pacer`]. Messages without a timestamp and
+/// errors are yielded immediately.
+#[derive(Debug)]
+pub struct Replay<I> {
+    messages: I,
+    pacer: Pacer,
+}
+
+impl<I> Replay<I> {
+    /// Replays the messages of an iterator, typically [`hist::Messages`](crate::hist::Messages)
+    pub fn new(messages: I, pacer: Pacer) -> Self {
+        Self { messages, pacer }
+    }
+
+    pub fn pacer(&self) -> &Pacer {
+        &self.pacer
+    }
+
+    /// The pacer of the replay, e.g. to change its speed while replaying
+    pub fn pacer_mut(&mut self) -> &mut Pacer {
+        &mut self.pacer
+    }
+
+    pub fn into_inner(self) -> I {
+        self.messages
+    }
+}
+
+impl<I, M> Iterator for Replay<I>
+where
+    I: Iterator<Item = Result<M, Error>>,
+    M: Message,
+{
+    type Item = Result<M, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let message = self.messages.next()?;
+        if let Some(timestamp) = message.as_ref().ok().and_then(Message::timestamp) {
+            self.pacer.wait(timestamp);
+        }
+        Some(message)
+    }
+}
+
+/// A segment built by a [`SegmentEncoder`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedSegment {
+    pub send_time: DateTime<Utc>,
+    pub message_count: usize,
+    /// The segment as it's sent on the wire
+    pub payload: Vec<u8>,
+}
+
+/// Packs messages into the IEX-TP segments of a stream, numbering them from 1
+///
+/// Consecutive messages with the same timestamp are sent in the same segment (as the exchange does), sent at that
+/// timestamp, unless it would exceed the maximum payload. Messages without a timestamp join the current segment.
+#[derive(Clone, Debug)]
+pub struct SegmentEncoder {
+    message_protocol_id: u16,
+    channel_id: u32,
+    session_id: u32,
+    max_payload: usize,
+    next_sequence_number: i64,
+    stream_offset: i64,
+    send_time: Option<DateTime<Utc>>,
+    messages: Vec<Vec<u8>>,
+    payload_len: usize,
+}
+
+impl SegmentEncoder {
+    pub fn new(message_protocol_id: u16, channel_id: u32, session_id: u32) -> Self {
+        Self {
+            message_protocol_id,
+            channel_id,
+            session_id,
+            max_payload: DEFAULT_MAX_PAYLOAD,
+            next_sequence_number: 1,
+            stream_offset: 0,
+            send_time: None,
+            messages: Vec::new(),
+            payload_len: 0,
+        }
+    }
+
+    /// Limits the payload of the segments, though a single message longer than the limit still gets its own segment
+    pub fn with_max_payload(mut self, max_payload: usize) -> Self {
+        self.max_payload = max_payload;
+        self
+    }
+
+    /// Adds an encoded message, returning the previous segment if the message doesn't belong to it
+    pub fn push(&mut self, message: &[u8]) -> Option<EncodedSegment> {
+        let timestamp = raw_timestamp(message);
+        let full = self.payload_len + message.len() + 2 > self.max_payload;
+        let segment =
+            if full || timestamp.is_some_and(|timestamp| Some(timestamp) != self.send_time) {
+                self.flush()
+            } else {
+                None
+            };
+
+        // A message without a timestamp is sent at the time of the previous one
+        if let Some(timestamp) = timestamp {
+            self.send_time = Some(timestamp);
+        }
+        self.messages.push(message.to_vec());
+        self.payload_len += message.len() + 2;
+        segment
+    }
+
+    /// Returns the current segment, if it has any message
+    pub fn flush(&mut self) -> Option<EncodedSegment> {
+        if self.messages.is_empty() {
+            return None;
+        }
+        let send_time = self.send_time.unwrap_or_default();
+        let segment = IexTp1Segment {
+            message_protocol_id: self.message_protocol_id,
+            channel_id: self.channel_id,
+            session_id: self.session_id,
+            send_time,
+            messages: self.messages.iter().map(Vec::as_slice).collect(),
+            first_message_sequence_no: self.next_sequence_number,
+            stream_offset: self.stream_offset,
+        };
+        let encoded = EncodedSegment {
+            send_time,
+            message_count: self.messages.len(),
+            payload: segment.encode(),
+        };
+
+        self.next_sequence_number += self.messages.len() as i64;
+        self.stream_offset += self.payload_len as i64;
+        self.messages.clear();
+        self.payload_len = 0;
+        Some(encoded)
+    }
+}
+
+/// Re-encodes messages into IEX-TP segments and sends them as UDP datagrams (typically to a multicast group) with
+/// their original timing, to exercise the whole path of a live feed from a historical file
+///
+/// Each segment is sent once the next message shows it's complete, so [`MulticastSink::finish`] must be called after
+/// the last message.
+#[derive(Debug)]
+pub struct MulticastSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    pacer: Pacer,
+    encoder: SegmentEncoder,
+    segments: u64,
+    bytes: u64,
+}
+
+impl MulticastSink {
+    /// Creates a sink sending from a socket (with its multicast TTL already set) to a target address
+    pub fn new(
+        socket: UdpSocket,
+        target: SocketAddr,
+        pacer: Pacer,
+        encoder: SegmentEncoder,
+    ) -> Self {
+        Self {
+            socket,
+            target,
+            pacer,
+            encoder,
+            segments: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Sends a message as it's encoded on the wire
+    pub fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self.encoder.push(message) {
+            Some(segment) => self.send_segment(segment),
+            None => Ok(()),
+        }
+    }
+
+    /// Encodes and sends a TOPS message
+    pub fn send_tops<S: AsRef<str>>(&mut self, message: &Tops1_6Message<S>) -> io::Result<()> {
+        self.send(&message.encode()?)
+    }
+
+    /// Sends the last segment, returning the number of segments and bytes sent
+    pub fn finish(mut self) -> io::Result<(u64, u64)> {
+        if let Some(segment) = self.encoder.flush() {
+            self.send_segment(segment)?;
+        }
+        Ok((self.segments, self.bytes))
+    }
+
+    fn send_segment(&mut self, segment: EncodedSegment) -> io::Result<()> {
+        self.pacer.wait(segment.send_time);
+        self.socket.send_to(&segment.payload, self.target)?;
+        self.segments += 1;
+        self.bytes += segment.payload.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::{
+        hist::{
+            tests::{capture, segment},
+            HistReader,
+        },
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+    };
+
+    use super::*;
+
+    #[test]
+    fn pacing() {
+        let origin = DateTime::from_timestamp_nanos(1_000_000_000);
+        let mut pacer = Pacer::new(2.0);
+
+        assert_eq!(pacer.delay(origin), Duration::ZERO);
+        let delay = pacer.delay(origin + TimeDelta::seconds(10));
+        assert!(delay > Duration::from_secs(4) && delay <= Duration::from_secs(5));
+        assert_eq!(pacer.delay(origin - TimeDelta::seconds(1)), Duration::ZERO);
+
+        let mut unpaced = Pacer::unpaced();
+        assert_eq!(unpaced.delay(origin), Duration::ZERO);
+        assert_eq!(
+            unpaced.delay(origin + TimeDelta::seconds(10)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn speed_change() {
+        let origin = DateTime::from_timestamp_nanos(1_000_000_000);
+        let mut pacer = Pacer::new(1.0);
+        pacer.delay(origin);
+        pacer.delay(origin + TimeDelta::seconds(10));
+
+        // Later events are due relative to the last one
+        pacer.set_speed(Some(10.0));
+        assert_eq!(pacer.speed(), Some(10.0));
+        let delay = pacer.delay(origin + TimeDelta::seconds(20));
+        assert!(delay > Duration::from_millis(900) && delay <= Duration::from_secs(1));
+
+        pacer.set_speed(None);
+        assert_eq!(pacer.delay(origin + TimeDelta::seconds(30)), Duration::ZERO);
+    }
+
+    // A trade report with the given timestamp
+    fn trade(timestamp: DateTime<Utc>) -> Vec<u8> {
+        let mut trade = vec![0x54, 0x00];
+        trade.extend_from_slice(&timestamp.timestamp_nanos_opt().unwrap().to_le_bytes());
+        trade.extend_from_slice(b"ZIEXT   ");
+        trade.extend_from_slice(&100u32.to_le_bytes());
+        trade.extend_from_slice(&100_0000i64.to_le_bytes());
+        trade.extend_from_slice(&1i64.to_le_bytes());
+        trade
+    }
+
+    #[test]
+    fn replay() {
+        let origin = DateTime::from_timestamp_nanos(1492448400000000000);
+        let capture = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[
+                &trade(origin),
+                &trade(origin + TimeDelta::milliseconds(40)),
+                &trade(origin + TimeDelta::milliseconds(80)),
+            ],
+        )]);
+        let messages = || {
+            HistReader::new(&capture[..])
+                .unwrap()
+                .messages::<Tops1_6Message<String>>()
+        };
+
+        let start = Instant::now();
+        let replay = Replay::new(messages(), Pacer::new(2.0));
+        assert_eq!(replay.map(Result::unwrap).count(), 3);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        let start = Instant::now();
+        let replay = Replay::new(messages(), Pacer::unpaced());
+        assert_eq!(replay.count(), 3);
+        assert!(start.elapsed() < Duration::from_millis(40));
+    }
+
+    #[test]
+    fn segments() {
+        let mut encoder =
+            SegmentEncoder::new(message_protocol_ids::TOPS, 1, 42).with_max_payload(100);
+        let origin = DateTime::from_timestamp_nanos(1492448400000000000);
+        let later = origin + TimeDelta::milliseconds(1);
+
+        assert_eq!(encoder.push(&trade(origin)), None);
+        assert_eq!(encoder.push(&trade(origin)), None);
+        // A third trade exceeds the payload
+        let first = encoder.push(&trade(origin)).unwrap();
+        let second = encoder.push(&trade(later)).unwrap();
+        let third = encoder.flush().unwrap();
+        assert_eq!(encoder.flush(), None);
+
+        let decode = |encoded: &EncodedSegment| {
+            let (_, IexTpSegment::V1(segment)) = iex_tp_segment(&encoded.payload).unwrap();
+            (
+                segment.session_id,
+                segment.send_time,
+                segment.messages.len(),
+                segment.first_message_sequence_no,
+                segment.stream_offset,
+            )
+        };
+        assert_eq!(decode(&first), (42, origin, 2, 1, 0));
+        assert_eq!(decode(&second), (42, origin, 1, 3, 2 * 40));
+        assert_eq!(decode(&third), (42, later, 1, 4, 3 * 40));
+        assert_eq!(first.message_count, 2);
+    }
+
+    #[test]
+    fn multicast_sink() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink = MulticastSink::new(
+            socket,
+            receiver.local_addr().unwrap(),
+            Pacer::unpaced(),
+            SegmentEncoder::new(message_protocol_ids::TOPS, 1, 42),
+        );
+
+        let quote = Tops1_6Message::<String>::try_from(&spec::QUOTE_UPDATE[..]).unwrap();
+        sink.send_tops(&quote).unwrap();
+        sink.send(&spec::TRADE_REPORT).unwrap();
+        let (segments, _) = sink.finish().unwrap();
+
+        let mut datagram = [0; 1500];
+        let mut received = Vec::new();
+        for _ in 0..segments {
+            let length = receiver.recv(&mut datagram).unwrap();
+            let (_, IexTpSegment::V1(segment)) = iex_tp_segment(&datagram[..length]).unwrap();
+            received.extend(segment.messages.iter().map(|message| message.to_vec()));
+        }
+        assert_eq!(
+            received,
+            [spec::QUOTE_UPDATE.to_vec(), spec::TRADE_REPORT.to_vec()]
+        );
+    }
+}
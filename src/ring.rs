@@ -0,0 +1,628 @@
+//! A shared-memory ring of fixed-size records, published by one process and read by any number of others
+//!
+//! The ring is a file (typically in `/dev/shm`) which every process maps in memory. Its layout, in native byte order:
+//!
+//! | Offset | Size | Field                                                                |
+//! |--------|------|----------------------------------------------------------------------|
+//! | 0      | 8    | magic, the bytes `IEXRING1`                                          |
+//! | 8      | 4    | version, 1                                                           |
+//! | 12     | 4    | record size, 64                                                      |
+//! | 16     | 8    | capacity, the number of records (a power of two)                     |
+//! | 24     | 8    | published, the number of records published so far (atomic)          |
+//! | 32     | 32   | reserved                                                             |
+//! | 64     | ...  | the records; record `n` (counting from 0) is in slot `n % capacity`  |
+//!
+//! The layout of a record is given by [`RingRecord`]. Its first field is its sequence number `n + 1`, which the
+//! publisher sets to 0 while writing the slot: a reader of record `n` loads the sequence number (with acquire
+//! ordering), copies the record, then loads the sequence number again (after an acquire fence), and only keeps the copy
+//! if both loads returned `n + 1`. Readers which fall more than `capacity` records behind are lapped, and miss
+//! records. `examples/ring_reader.c` is a reader in C.
+
+use std::{
+    ffi::c_void,
+    fs::{File, OpenOptions},
+    io::{self, ErrorKind},
+    os::fd::AsRawFd,
+    path::Path,
+    ptr,
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::Deep1_0Message,
+    tops::{
+        AuctionInformation, OfficialPrice, OperationalHaltStatus, RetailLiquidityIndicator,
+        SecurityDirectory, ShortSalePriceTestStatus, SystemEvent, Tops1_6Message, TradeReport,
+        TradingStatus,
+    },
+    utils::price_key,
+};
+
+pub const MAGIC: [u8; 8] = *b"IEXRING1";
+pub const VERSION: u32 = 1;
+pub const RECORD_SIZE: usize = 64;
+const HEADER_SIZE: usize = 64;
+const WORDS: usize = RECORD_SIZE / 8;
+const PUBLISHED_WORD: usize = 3;
+
+const PROT_READ: i32 = 1;
+const PROT_WRITE: i32 = 2;
+const MAP_SHARED: i32 = 1;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: i32,
+        flags: i32,
+        fd: i32,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+}
+
+/// A message normalized into 64 bytes, the fields used depending on its kind
+///
+/// | Kind                                | `code`             | `price`, `size`            | `price2`, `size2`          | `id`     |
+/// |-------------------------------------|--------------------|----------------------------|----------------------------|----------|
+/// | `S` system event                    | event type         |                            |                            |          |
+/// | `D` security directory              | LULD tier          | adjusted POC, round lot    |                            |          |
+/// | `H` trading status                  | status             |                            |                            |          |
+/// | `O` operational halt status         | status             |                            |                            |          |
+/// | `P` short sale price test status    | detail             | size 1 if in effect        |                            |          |
+/// | `E` security event                  | event type         |                            |                            |          |
+/// | `Q` quote update                    |                    | bid                        | ask                        |          |
+/// | `8`/`5` price level update          | 1 if complete      | price level                |                            |          |
+/// | `T` trade report, `B` trade break   |                    | trade                      |                            | trade id |
+/// | `X` official price                  | price type         | official price             |                            |          |
+/// | `A` auction information             | auction type       | reference, paired shares   | indicative, imbalance      |          |
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RingRecord {
+    /// The sequence number of the record in the ring, set by the publisher
+    pub sequence: u64,
+    /// In nanoseconds since the Unix epoch
+    pub timestamp: i64,
+    /// The character code of the kind of message
+    pub kind: u8,
+    pub code: u8,
+    pub reserved: [u8; 6],
+    /// NUL-padded
+    pub symbol: [u8; 8],
+    /// Fixed-point, with 4 decimal digits
+    pub price: i64,
+    pub price2: i64,
+    pub size: u32,
+    pub size2: u32,
+    pub id: i64,
+}
+
+impl RingRecord {
+    fn new(kind: u8, timestamp: DateTime<Utc>, symbol: &str) -> Self {
+        let mut padded = [0; 8];
+        let length = symbol.len().min(8);
+        padded[..length].copy_from_slice(&symbol.as_bytes()[..length]);
+        RingRecord {
+            kind,
+            timestamp: timestamp.timestamp_nanos_opt().unwrap_or_default(),
+            symbol: padded,
+            ..Default::default()
+        }
+    }
+
+    /// The symbol, without its padding
+    pub fn symbol(&self) -> &str {
+        let length = self.symbol.iter().position(|&byte| byte == 0).unwrap_or(8);
+        std::str::from_utf8(&self.symbol[..length]).unwrap_or_default()
+    }
+
+    fn to_words(self) -> [u64; WORDS] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.sequence.to_ne_bytes());
+        bytes[8..16].copy_from_slice(&self.timestamp.to_ne_bytes());
+        bytes[16] = self.kind;
+        bytes[17] = self.code;
+        bytes[18..24].copy_from_slice(&self.reserved);
+        bytes[24..32].copy_from_slice(&self.symbol);
+        bytes[32..40].copy_from_slice(&self.price.to_ne_bytes());
+        bytes[40..48].copy_from_slice(&self.price2.to_ne_bytes());
+        bytes[48..52].copy_from_slice(&self.size.to_ne_bytes());
+        bytes[52..56].copy_from_slice(&self.size2.to_ne_bytes());
+        bytes[56..64].copy_from_slice(&self.id.to_ne_bytes());
+        std::array::from_fn(|word| u64::from_ne_bytes(bytes[word * 8..][..8].try_into().unwrap()))
+    }
+
+    fn from_words(words: [u64; WORDS]) -> Self {
+        let mut bytes = [0; RECORD_SIZE];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_ne_bytes());
+        }
+        let field = |offset: usize| -> [u8; 8] { bytes[offset..offset + 8].try_into().unwrap() };
+        let half = |offset: usize| -> [u8; 4] { bytes[offset..offset + 4].try_into().unwrap() };
+        RingRecord {
+            sequence: u64::from_ne_bytes(field(0)),
+            timestamp: i64::from_ne_bytes(field(8)),
+            kind: bytes[16],
+            code: bytes[17],
+            reserved: bytes[18..24].try_into().unwrap(),
+            symbol: field(24),
+            price: i64::from_ne_bytes(field(32)),
+            price2: i64::from_ne_bytes(field(40)),
+            size: u32::from_ne_bytes(half(48)),
+            size2: u32::from_ne_bytes(half(52)),
+            id: i64::from_ne_bytes(field(56)),
+        }
+    }
+
+    fn system_event(message: &SystemEvent) -> Self {
+        RingRecord {
+            code: message.event_type.code(),
+            ..Self::new(b'S', message.timestamp, "")
+        }
+    }
+
+    fn security_directory<S: AsRef<str>>(message: &SecurityDirectory<S>) -> Self {
+        RingRecord {
+            code: message.luld_tier.into(),
+            price: price_key(message.adjusted_poc_price),
+            size: message.round_lot_size,
+            ..Self::new(b'D', message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn trading_status<S: AsRef<str>>(message: &TradingStatus<S>) -> Self {
+        RingRecord {
+            code: message.status.code(),
+            ..Self::new(b'H', message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn retail_liquidity_indicator<S: AsRef<str>>(message: &RetailLiquidityIndicator<S>) -> Self {
+        RingRecord {
+            code: message.indicator.code(),
+            ..Self::new(b'I', message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn operational_halt_status<S: AsRef<str>>(message: &OperationalHaltStatus<S>) -> Self {
+        RingRecord {
+            code: message.status.code(),
+            ..Self::new(b'O', message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn short_sale_price_test_status<S: AsRef<str>>(message: &ShortSalePriceTestStatus<S>) -> Self {
+        RingRecord {
+            code: message.detail.code(),
+            size: message.in_effect.into(),
+            ..Self::new(b'P', message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn trade<S: AsRef<str>>(kind: u8, message: &TradeReport<S>) -> Self {
+        RingRecord {
+            price: price_key(message.price),
+            size: message.size,
+            id: message.id,
+            ..Self::new(kind, message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn official_price<S: AsRef<str>>(message: &OfficialPrice<S>) -> Self {
+        RingRecord {
+            code: message.price_type.code(),
+            price: price_key(message.official_price),
+            ..Self::new(b'X', message.timestamp, message.symbol.as_ref())
+        }
+    }
+
+    fn auction_information<S: AsRef<str>>(message: &AuctionInformation<S>) -> Self {
+        RingRecord {
+            code: message.auction_type.code(),
+            price: price_key(message.reference_price),
+            size: message.paired_shares,
+            price2: price_key(message.indicative_clearing_price),
+            size2: message.imbalance_shares,
+            ..Self::new(b'A', message.timestamp, message.symbol.as_ref())
+        }
+    }
+}
+
+impl<S: AsRef<str>> From<&Tops1_6Message<S>> for RingRecord {
+    fn from(message: &Tops1_6Message<S>) -> Self {
+        match message {
+            Tops1_6Message::SystemEvent(message) => Self::system_event(message),
+            Tops1_6Message::SecurityDirectory(message) => Self::security_directory(message),
+            Tops1_6Message::TradingStatus(message) => Self::trading_status(message),
+            Tops1_6Message::RetailLiquidityIndicator(message) => {
+                Self::retail_liquidity_indicator(message)
+            }
+            Tops1_6Message::OperationalHaltStatus(message) => {
+                Self::operational_halt_status(message)
+            }
+            Tops1_6Message::ShortSalePriceTestStatus(message) => {
+                Self::short_sale_price_test_status(message)
+            }
+            Tops1_6Message::QuoteUpdate(message) => RingRecord {
+                price: price_key(message.bid_price),
+                size: message.bid_size,
+                price2: price_key(message.ask_price),
+                size2: message.ask_size,
+                ..Self::new(b'Q', message.timestamp, message.symbol.as_ref())
+            },
+            Tops1_6Message::TradeReport(message) => Self::trade(b'T', message),
+            Tops1_6Message::OfficialPrice(message) => Self::official_price(message),
+            Tops1_6Message::TradeBreak(message) => RingRecord {
+                price: price_key(message.price),
+                size: message.size,
+                id: message.id,
+                ..Self::new(b'B', message.timestamp, message.symbol.as_ref())
+            },
+            Tops1_6Message::AuctionInformation(message) => Self::auction_information(message),
+        }
+    }
+}
+
+impl<S: AsRef<str>> From<&Deep1_0Message<S>> for RingRecord {
+    fn from(message: &Deep1_0Message<S>) -> Self {
+        match message {
+            Deep1_0Message::SystemEvent(message) => Self::system_event(message),
+            Deep1_0Message::SecurityDirectory(message) => Self::security_directory(message),
+            Deep1_0Message::TradingStatus(message) => Self::trading_status(message),
+            Deep1_0Message::OperationalHaltStatus(message) => {
+                Self::operational_halt_status(message)
+            }
+            Deep1_0Message::ShortSalePriceTestStatus(message) => {
+                Self::short_sale_price_test_status(message)
+            }
+            Deep1_0Message::SecurityEvent(message) => RingRecord {
+                code: message.event_type.code(),
+                ..Self::new(b'E', message.timestamp, message.symbol.as_ref())
+            },
+            Deep1_0Message::PriceLevelUpdate(message) => RingRecord {
+                code: message.event_processing_complete.into(),
+                price: price_key(message.price),
+                size: message.size,
+                ..Self::new(
+                    message.side.code(),
+                    message.timestamp,
+                    message.symbol.as_ref(),
+                )
+            },
+            Deep1_0Message::TradeReport(message) => Self::trade(b'T', message),
+            Deep1_0Message::OfficialPrice(message) => Self::official_price(message),
+            Deep1_0Message::TradeBreak(message) => RingRecord {
+                price: price_key(message.price),
+                size: message.size,
+                id: message.id,
+                ..Self::new(b'B', message.timestamp, message.symbol.as_ref())
+            },
+            Deep1_0Message::AuctionInformation(message) => Self::auction_information(message),
+        }
+    }
+}
+
+/// A file mapped in memory, as words
+#[derive(Debug)]
+struct Mapping {
+    words: *mut AtomicU64,
+    length: usize,
+}
+
+// The mapping is only accessed through atomics
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, length: usize, writable: bool) -> io::Result<Self> {
+        let protection = if writable {
+            PROT_READ | PROT_WRITE
+        } else {
+            PROT_READ
+        };
+        let address = unsafe {
+            mmap(
+                ptr::null_mut(),
+                length,
+                protection,
+                MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            words: address.cast(),
+            length,
+        })
+    }
+
+    fn word(&self, index: usize) -> &AtomicU64 {
+        assert!(index < self.length / 8);
+        unsafe { &*self.words.add(index) }
+    }
+
+    /// The words of the slot of a record
+    fn slot(&self, capacity: u64, record: u64) -> usize {
+        (HEADER_SIZE + (record % capacity) as usize * RECORD_SIZE) / 8
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.words.cast(), self.length);
+        }
+    }
+}
+
+/// Publishes records to a shared-memory ring, see the [module](self) documentation
+#[derive(Debug)]
+pub struct RingPublisher {
+    mapping: Mapping,
+    capacity: u64,
+    published: u64,
+}
+
+impl RingPublisher {
+    /// Creates a ring of `capacity` records in a file, replacing any existing one
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity isn't a power of two.
+    pub fn create(path: impl AsRef<Path>, capacity: usize) -> io::Result<Self> {
+        assert!(
+            capacity.is_power_of_two(),
+            "the capacity must be a power of two"
+        );
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let length = HEADER_SIZE + capacity * RECORD_SIZE;
+        file.set_len(length as u64)?;
+        let mapping = Mapping::new(&file, length, true)?;
+
+        let mut header = [0; HEADER_SIZE];
+        header[0..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_ne_bytes());
+        header[12..16].copy_from_slice(&(RECORD_SIZE as u32).to_ne_bytes());
+        header[16..24].copy_from_slice(&(capacity as u64).to_ne_bytes());
+        for (index, word) in header.chunks_exact(8).enumerate() {
+            mapping.word(index).store(
+                u64::from_ne_bytes(word.try_into().unwrap()),
+                Ordering::Release,
+            );
+        }
+
+        Ok(RingPublisher {
+            mapping,
+            capacity: capacity as u64,
+            published: 0,
+        })
+    }
+
+    /// The number of records published so far
+    pub fn published(&self) -> u64 {
+        self.published
+    }
+
+    /// Publishes a record, overwriting the oldest one if the ring is full. The sequence number of the record is set
+    /// by the ring.
+    pub fn publish(&mut self, record: &RingRecord) {
+        let sequence = self.published + 1;
+        let words = RingRecord {
+            sequence,
+            ..*record
+        }
+        .to_words();
+        let slot = self.mapping.slot(self.capacity, self.published);
+
+        self.mapping.word(slot).store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        for (index, word) in words.iter().enumerate().skip(1) {
+            self.mapping
+                .word(slot + index)
+                .store(*word, Ordering::Relaxed);
+        }
+        self.mapping.word(slot).store(sequence, Ordering::Release);
+        self.mapping
+            .word(PUBLISHED_WORD)
+            .store(sequence, Ordering::Release);
+        self.published = sequence;
+    }
+}
+
+/// The records a [`RingReader`] missed, as it was lapped by the publisher
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lapped {
+    pub missed: u64,
+}
+
+/// Reads the records of a shared-memory ring, see the [module](self) documentation
+#[derive(Debug)]
+pub struct RingReader {
+    mapping: Mapping,
+    capacity: u64,
+    next: u64,
+}
+
+impl RingReader {
+    /// Opens a ring created by a [`RingPublisher`], to read the records published from now on
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_string());
+        if file.metadata()?.len() < HEADER_SIZE as u64 {
+            return Err(invalid("not a ring"));
+        }
+
+        let header = Mapping::new(&file, HEADER_SIZE, false)?;
+        let magic = header.word(0).load(Ordering::Acquire).to_ne_bytes();
+        let format = header.word(1).load(Ordering::Acquire).to_ne_bytes();
+        let capacity = header.word(2).load(Ordering::Acquire);
+        if magic != MAGIC
+            || format[..4] != VERSION.to_ne_bytes()
+            || format[4..] != (RECORD_SIZE as u32).to_ne_bytes()
+        {
+            return Err(invalid("not a ring, or of an unsupported version"));
+        }
+        let length = HEADER_SIZE as u64 + capacity * RECORD_SIZE as u64;
+        if !capacity.is_power_of_two() || file.metadata()?.len() < length {
+            return Err(invalid("truncated ring"));
+        }
+        drop(header);
+
+        let mapping = Mapping::new(&file, length as usize, false)?;
+        let next = mapping.word(PUBLISHED_WORD).load(Ordering::Acquire);
+        Ok(RingReader {
+            mapping,
+            capacity,
+            next,
+        })
+    }
+
+    /// Reads the oldest record in the ring rather than the next one published
+    pub fn rewind(&mut self) {
+        let published = self.mapping.word(PUBLISHED_WORD).load(Ordering::Acquire);
+        self.next = published.saturating_sub(self.capacity);
+    }
+
+    /// Reads the next record, if it has been published. When the reader was lapped, it moves on to the oldest record
+    /// in the ring, and the next call reads it.
+    pub fn next_record(&mut self) -> Result<Option<RingRecord>, Lapped> {
+        let published = self.mapping.word(PUBLISHED_WORD).load(Ordering::Acquire);
+        if self.next >= published {
+            return Ok(None);
+        }
+
+        let expected = self.next + 1;
+        let slot = self.mapping.slot(self.capacity, self.next);
+        let sequence = self.mapping.word(slot).load(Ordering::Acquire);
+        let mut words = [0; WORDS];
+        for (index, word) in words.iter_mut().enumerate() {
+            *word = self.mapping.word(slot + index).load(Ordering::Relaxed);
+        }
+        fence(Ordering::Acquire);
+        if sequence == expected && self.mapping.word(slot).load(Ordering::Relaxed) == expected {
+            self.next = expected;
+            return Ok(Some(RingRecord::from_words(words)));
+        }
+
+        // The slot was overwritten, or is being overwritten: skip to the oldest record in the ring (which may be
+        // overwritten in turn before being read, lapping the reader again)
+        let published = self.mapping.word(PUBLISHED_WORD).load(Ordering::Acquire);
+        let oldest = published.saturating_sub(self.capacity).max(expected);
+        let missed = oldest - self.next;
+        self.next = oldest;
+        Err(Lapped { missed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, thread};
+
+    use crate::{spec, tops::tops_1_6_message};
+
+    use super::*;
+
+    fn ring_path(name: &str) -> std::path::PathBuf {
+        env::temp_dir().join(format!("iex-parser-ring-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn records() {
+        let (_, quote) = tops_1_6_message::<&str>(&spec::QUOTE_UPDATE).unwrap();
+        let record = RingRecord::from(&quote);
+        assert_eq!(record.kind, b'Q');
+        assert_eq!(record.symbol(), "ZIEXT");
+        assert_eq!((record.price, record.size), (990500, 9700));
+        assert_eq!((record.price2, record.size2), (990700, 1000));
+        assert_eq!(RingRecord::from_words(record.to_words()), record);
+        assert_eq!(std::mem::size_of::<RingRecord>(), RECORD_SIZE);
+    }
+
+    #[test]
+    fn ring() {
+        let path = ring_path("ring");
+        let mut publisher = RingPublisher::create(&path, 4).unwrap();
+        let (_, trade) = tops_1_6_message::<&str>(&spec::TRADE_REPORT).unwrap();
+        let record = RingRecord::from(&trade);
+        publisher.publish(&record);
+
+        // Readers start at the next record, unless rewound
+        let mut reader = RingReader::open(&path).unwrap();
+        assert_eq!(reader.next_record(), Ok(None));
+        let mut rewound = RingReader::open(&path).unwrap();
+        rewound.rewind();
+        assert_eq!(rewound.next_record().unwrap().unwrap().sequence, 1);
+
+        for _ in 0..3 {
+            publisher.publish(&record);
+        }
+        let read = reader.next_record().unwrap().unwrap();
+        assert_eq!(
+            read,
+            RingRecord {
+                sequence: 2,
+                ..record
+            }
+        );
+
+        // Lapped by 4 records: records 3 and 4 were overwritten
+        for _ in 0..4 {
+            publisher.publish(&record);
+        }
+        assert_eq!(reader.next_record(), Err(Lapped { missed: 2 }));
+        let sequences = std::iter::from_fn(|| reader.next_record().unwrap())
+            .map(|record| record.sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, [5, 6, 7, 8]);
+
+        fs::write(ring_path("invalid"), [0; 128]).unwrap();
+        assert!(RingReader::open(ring_path("invalid")).is_err());
+        fs::remove_file(ring_path("invalid")).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn concurrent_reader() {
+        let path = ring_path("concurrent");
+        let mut publisher = RingPublisher::create(&path, 64).unwrap();
+        let mut reader = RingReader::open(&path).unwrap();
+
+        let reading = thread::spawn(move || {
+            let (mut read, mut missed, mut last) = (0u64, 0u64, 0u64);
+            while read + missed < 100_000 {
+                match reader.next_record() {
+                    Ok(Some(record)) => {
+                        // Every record read is consistent, and records are read in order
+                        assert_eq!(record.price, record.sequence as i64);
+                        assert_eq!(record.id, -(record.sequence as i64));
+                        assert!(record.sequence > last);
+                        last = record.sequence;
+                        read += 1;
+                    }
+                    Ok(None) => thread::yield_now(),
+                    Err(lapped) => missed += lapped.missed,
+                }
+            }
+            read
+        });
+
+        for sequence in 1..=100_000i64 {
+            publisher.publish(&RingRecord {
+                price: sequence,
+                id: -sequence,
+                ..Default::default()
+            });
+        }
+        assert!(reading.join().unwrap() > 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::iex_tp::IexTp1Segment;
+
+/// A stream of messages, whose sequence numbers are independent of other streams'
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StreamId {
+    pub message_protocol_id: u16,
+    pub channel_id: u32,
+    pub session_id: u32,
+}
+
+impl From<&IexTp1Segment<'_>> for StreamId {
+    fn from(segment: &IexTp1Segment<'_>) -> Self {
+        Self {
+            message_protocol_id: segment.message_protocol_id,
+            channel_id: segment.channel_id,
+            session_id: segment.session_id,
+        }
+    }
+}
+
+/// A range of sequence numbers of a stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SequenceRange {
+    pub stream: StreamId,
+    pub first: i64,
+    pub count: i64,
+    /// The send time of the segment which revealed the range
+    pub send_time: DateTime<Utc>,
+}
+
+impl SequenceRange {
+    /// The sequence number following the range
+    pub fn end(&self) -> i64 {
+        self.first + self.count
+    }
+}
+
+/// How a segment follows the previous segments of its stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sequencing {
+    /// The segment carries the next messages of the stream (or is its first segment)
+    InOrder,
+    /// Messages are missing before the segment
+    Gap(SequenceRange),
+    /// Messages of the segment were already seen (a retransmission, or a duplicate packet). The messages following them
+    /// in the segment, if any, are new.
+    Duplicate(SequenceRange),
+}
+
+/// Detects the gaps and duplicates in the sequence numbers of IEX-TP segments
+///
+/// Every stream (message protocol, channel and session) is tracked separately. Gaps are reported once: the missing
+/// messages aren't expected to arrive later, as HIST files carry a single copy of the feed.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceTracker {
+    /// The next sequence number expected in each stream
+    next: HashMap<StreamId, i64>,
+    missing: u64,
+    duplicates: u64,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, segment: &IexTp1Segment<'_>) -> Sequencing {
+        let stream = StreamId::from(segment);
+        let first = segment.first_message_sequence_no;
+        let end = first + segment.messages.len() as i64;
+
+        let Some(next) = self.next.get_mut(&stream) else {
+            self.next.insert(stream, end);
+            return Sequencing::InOrder;
+        };
+
+        let range = |first, count| SequenceRange {
+            stream,
+            first,
+            count,
+            send_time: segment.send_time,
+        };
+
+        if first > *next {
+            let gap = range(*next, first - *next);
+            self.missing += gap.count as u64;
+            *next = end;
+            Sequencing::Gap(gap)
+        } else if first < *next && end > first {
+            let duplicate = range(first, end.min(*next) - first);
+            self.duplicates += duplicate.count as u64;
+            *next = (*next).max(end);
+            Sequencing::Duplicate(duplicate)
+        } else {
+            *next = (*next).max(end);
+            Sequencing::InOrder
+        }
+    }
+
+    /// The next sequence number expected in a stream, if it was seen
+    pub fn next_sequence_number(&self, stream: &StreamId) -> Option<i64> {
+        self.next.get(stream).copied()
+    }
+
+    pub fn streams(&self) -> impl Iterator<Item = &StreamId> {
+        self.next.keys()
+    }
+
+    /// The total number of messages missing in the gaps seen
+    pub fn missing(&self) -> u64 {
+        self.missing
+    }
+
+    /// The total number of messages seen more than once
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_protocol_ids;
+
+    use super::*;
+
+    fn segment(first_message_sequence_no: i64, count: usize) -> IexTp1Segment<'static> {
+        IexTp1Segment {
+            message_protocol_id: message_protocol_ids::TOPS,
+            channel_id: 1,
+            session_id: 42,
+            send_time: DateTime::from_timestamp_nanos(0),
+            messages: vec![&[0x53]; count],
+            first_message_sequence_no,
+            stream_offset: 0,
+        }
+    }
+
+    #[test]
+    fn gaps_and_duplicates() {
+        let mut tracker = SequenceTracker::new();
+
+        assert_eq!(tracker.update(&segment(1, 2)), Sequencing::InOrder);
+        // Heartbeats carry the next sequence number
+        assert_eq!(tracker.update(&segment(3, 0)), Sequencing::InOrder);
+        assert_eq!(tracker.update(&segment(3, 1)), Sequencing::InOrder);
+        let Sequencing::Gap(gap) = tracker.update(&segment(7, 2)) else {
+            panic!("expected a gap");
+        };
+        assert_eq!((gap.first, gap.count, gap.end()), (4, 3, 7));
+        let Sequencing::Duplicate(duplicate) = tracker.update(&segment(8, 3)) else {
+            panic!("expected a duplicate");
+        };
+        assert_eq!((duplicate.first, duplicate.count), (8, 1));
+        assert_eq!(tracker.update(&segment(11, 1)), Sequencing::InOrder);
+
+        let mut other_session = segment(1, 1);
+        other_session.session_id = 43;
+        assert_eq!(tracker.update(&other_session), Sequencing::InOrder);
+
+        assert_eq!(tracker.missing(), 3);
+        assert_eq!(tracker.duplicates(), 1);
+        assert_eq!(
+            tracker.next_sequence_number(&StreamId::from(&segment(0, 0))),
+            Some(12)
+        );
+        assert_eq!(tracker.streams().count(), 2);
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::Read;
+
+use chrono::{DateTime, Utc};
+
+use crate::hist::{self, HistReader, Message, Messages};
+
+/// An item of a multi-day series
+#[derive(Clone, Debug)]
+pub enum SeriesItem<M> {
+    /// The data of a new day starts. Days are numbered from zero, in the order they were given.
+    SessionStart {
+        day: usize,
+    },
+    Message(M),
+    /// The data of a day ended
+    SessionEnd {
+        day: usize,
+        /// The number of messages of the symbol during the day. Zero if the symbol wasn't listed (or didn't have any
+        /// activity) that day, e.g. before its listing or after its delisting.
+        message_count: u64,
+        /// The timestamp of the last message of the day, of any symbol
+        last_timestamp: Option<DateTime<Utc>>,
+    },
+}
+
+struct Day<R, M> {
+    day: usize,
+    messages: Messages<R, M>,
+    message_count: u64,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+/// Stitches the daily HIST files of a symbol into a single continuous series
+///
+/// Only the messages of the symbol are kept, along with the messages without any symbol (e.g. system events), which
+/// describe the sessions themselves. Each day is delimited by a [`SeriesItem::SessionStart`] and a
+/// [`SeriesItem::SessionEnd`], even if the symbol didn't appear in it.
+pub struct ContinuousSeries<D, R, M>
+where
+    M: Message,
+{
+    days: D,
+    symbol: M::Symbol,
+    next_day: usize,
+    current: Option<Day<R, M>>,
+}
+
+impl<D, R, M> ContinuousSeries<D, R, M>
+where
+    D: Iterator<Item = Result<HistReader<R>, hist::Error>>,
+    R: Read,
+    M: Message,
+    M::Symbol: PartialEq,
+{
+    /// Creates a series over the given days, which should be in chronological order (e.g. an iterator opening the HIST
+    /// file of each day with [`HistReader::open`])
+    pub fn new(days: impl IntoIterator<IntoIter = D>, symbol: M::Symbol) -> Self {
+        Self {
+            days: days.into_iter(),
+            symbol,
+            next_day: 0,
+            current: None,
+        }
+    }
+}
+
+impl<D, R, M> Iterator for ContinuousSeries<D, R, M>
+where
+    D: Iterator<Item = Result<HistReader<R>, hist::Error>>,
+    R: Read,
+    M: Message,
+    M::Symbol: PartialEq,
+{
+    type Item = Result<SeriesItem<M>, hist::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(current) = &mut self.current else {
+                let reader = self.days.next()?;
+                let day = self.next_day;
+                self.next_day += 1;
+
+                return Some(reader.map(|reader| {
+                    self.current = Some(Day {
+                        day,
+                        messages: reader.messages(),
+                        message_count: 0,
+                        last_timestamp: None,
+                    });
+                    SeriesItem::SessionStart { day }
+                }));
+            };
+
+            match current.messages.next() {
+                None => {
+                    let current = self.current.take().expect("a day is in progress");
+                    return Some(Ok(SeriesItem::SessionEnd {
+                        day: current.day,
+                        message_count: current.message_count,
+                        last_timestamp: current.last_timestamp,
+                    }));
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(message)) => {
+                    if let Some(timestamp) = message.timestamp() {
+                        current.last_timestamp = Some(timestamp);
+                    }
+                    match message.symbol() {
+                        Some(symbol) if *symbol != self.symbol => continue,
+                        Some(_) => current.message_count += 1,
+                        None => {}
+                    }
+                    return Some(Ok(SeriesItem::Message(message)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::assert_matches;
+
+    use crate::{
+        hist::tests::{capture, segment},
+        message_protocol_ids,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    const SYSTEM_EVENT: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+    const ZIEXT_TRADE: [u8; 38] = [
+        0x54, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54,
+        0x20, 0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x96, 0x8F, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn stitched_days() {
+        let mut zvzzt_trade = ZIEXT_TRADE;
+        zvzzt_trade[10..15].copy_from_slice(b"ZVZZT");
+
+        let first = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&ZIEXT_TRADE, &zvzzt_trade],
+        )]);
+        let second = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&zvzzt_trade, &SYSTEM_EVENT],
+        )]);
+
+        let days = [&first[..], &second[..]].map(HistReader::new);
+        let items =
+            ContinuousSeries::<_, _, Tops1_6Message<String>>::new(days, "ZIEXT".to_string())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+
+        assert_eq!(items.len(), 6);
+        assert_matches!(items[0], SeriesItem::SessionStart { day: 0 });
+        assert_matches!(
+            items[1],
+            SeriesItem::Message(Tops1_6Message::TradeReport(_))
+        );
+        assert_matches!(
+            items[2],
+            SeriesItem::SessionEnd {
+                day: 0,
+                message_count: 1,
+                ..
+            }
+        );
+        assert_matches!(items[3], SeriesItem::SessionStart { day: 1 });
+        assert_matches!(
+            items[4],
+            SeriesItem::Message(Tops1_6Message::SystemEvent(_))
+        );
+        assert_matches!(
+            items[5],
+            SeriesItem::SessionEnd {
+                day: 1,
+                message_count: 0,
+                last_timestamp: Some(_),
+            }
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    deep::Deep1_0Message,
+    tops::{MarketSession, SystemEvent, SystemEventType, Tops1_6Message},
+};
+
+/// Keeps the messages of a single market session: the regular hours, or the extended hours around them
+///
+/// Quote updates and trades are assigned to a session by their own flags (the market session of the quote, the
+/// extended hours sale condition of the trade). Other messages are assigned by the system events seen so far, i.e.
+/// they belong to the regular session between the start and the end of the regular hours. Official prices belong to
+/// the regular session, even the closing price published after its end. System events are always kept, as they
+/// delimit the sessions.
+#[derive(Clone, Debug)]
+pub struct SessionFilter {
+    session: MarketSession,
+    regular_hours: bool,
+}
+
+impl SessionFilter {
+    pub fn new(session: MarketSession) -> Self {
+        Self {
+            session,
+            regular_hours: false,
+        }
+    }
+
+    /// Whether the regular hours are in progress according to the system events seen
+    pub fn is_regular_hours(&self) -> bool {
+        self.regular_hours
+    }
+
+    fn apply_system_event(&mut self, event: &SystemEvent) {
+        match event.event_type {
+            SystemEventType::StartOfRegularHours => self.regular_hours = true,
+            SystemEventType::EndOfRegularHours
+            | SystemEventType::EndOfSystemHours
+            | SystemEventType::EndOfMessages => self.regular_hours = false,
+            SystemEventType::StartOfMessages | SystemEventType::StartOfSystemHours => {}
+        }
+    }
+
+    fn clock_session(&self) -> MarketSession {
+        if self.regular_hours {
+            MarketSession::Regular
+        } else {
+            MarketSession::OutOfHours
+        }
+    }
+
+    fn trade_session(extended_hours: bool) -> MarketSession {
+        if extended_hours {
+            MarketSession::OutOfHours
+        } else {
+            MarketSession::Regular
+        }
+    }
+
+    /// Whether a TOPS message is kept
+    pub fn update<S>(&mut self, message: &Tops1_6Message<S>) -> bool {
+        let session = match message {
+            Tops1_6Message::SystemEvent(event) => {
+                self.apply_system_event(event);
+                return true;
+            }
+            Tops1_6Message::QuoteUpdate(quote) => quote.market_session,
+            Tops1_6Message::TradeReport(trade) => {
+                Self::trade_session(trade.sale_condition.extended_hours)
+            }
+            Tops1_6Message::TradeBreak(trade) => {
+                Self::trade_session(trade.sale_condition.extended_hours)
+            }
+            Tops1_6Message::OfficialPrice(_) => MarketSession::Regular,
+            _ => self.clock_session(),
+        };
+        session == self.session
+    }
+
+    /// Whether a DEEP message is kept
+    pub fn update_deep<S>(&mut self, message: &Deep1_0Message<S>) -> bool {
+        let session = match message {
+            Deep1_0Message::SystemEvent(event) => {
+                self.apply_system_event(event);
+                return true;
+            }
+            Deep1_0Message::TradeReport(trade) => {
+                Self::trade_session(trade.sale_condition.extended_hours)
+            }
+            Deep1_0Message::TradeBreak(trade) => {
+                Self::trade_session(trade.sale_condition.extended_hours)
+            }
+            Deep1_0Message::OfficialPrice(_) => MarketSession::Regular,
+            _ => self.clock_session(),
+        };
+        session == self.session
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::{
+        deep::{PriceLevelUpdate, Side},
+        fixtures,
+        tops::{QuoteUpdate, RetailLiquidityIndicator, RetailLiquidityIndicatorType},
+    };
+
+    use super::*;
+
+    fn system_event(event_type: SystemEventType) -> SystemEvent {
+        SystemEvent {
+            event_type,
+            timestamp: DateTime::from_timestamp_nanos(0),
+        }
+    }
+
+    #[test]
+    fn regular_hours() {
+        let quote = |market_session| {
+            Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                market_session,
+                ..fixtures::quote("ZIEXT", 0, 10.0, 10.05)
+            })
+        };
+        let indicator = Tops1_6Message::RetailLiquidityIndicator(RetailLiquidityIndicator {
+            indicator: RetailLiquidityIndicatorType::BuyInterest,
+            timestamp: DateTime::from_timestamp_nanos(0),
+            symbol: "ZIEXT",
+        });
+        let mut filter = SessionFilter::new(MarketSession::Regular);
+
+        assert!(
+            filter.update(&Tops1_6Message::<&str>::SystemEvent(system_event(
+                SystemEventType::StartOfSystemHours
+            )))
+        );
+        assert!(!filter.update(&quote(MarketSession::OutOfHours)));
+        assert!(!filter.update(&indicator));
+        filter.update(&Tops1_6Message::<&str>::SystemEvent(system_event(
+            SystemEventType::StartOfRegularHours,
+        )));
+        assert!(filter.is_regular_hours());
+        assert!(filter.update(&quote(MarketSession::Regular)));
+        assert!(filter.update(&indicator));
+        // The session flag of the quote prevails over the clock
+        assert!(!filter.update(&quote(MarketSession::OutOfHours)));
+    }
+
+    #[test]
+    fn extended_hours() {
+        let update = Deep1_0Message::PriceLevelUpdate(PriceLevelUpdate {
+            side: Side::Buy,
+            event_processing_complete: true,
+            timestamp: DateTime::from_timestamp_nanos(0),
+            symbol: "ZIEXT",
+            size: 100,
+            price: 10.0,
+        });
+        let mut filter = SessionFilter::new(MarketSession::OutOfHours);
+
+        assert!(filter.update_deep(&update));
+        filter.update_deep(&Deep1_0Message::<&str>::SystemEvent(system_event(
+            SystemEventType::StartOfRegularHours,
+        )));
+        assert!(!filter.update_deep(&update));
+        filter.update_deep(&Deep1_0Message::<&str>::SystemEvent(system_event(
+            SystemEventType::EndOfRegularHours,
+        )));
+        assert!(filter.update_deep(&update));
+    }
+}
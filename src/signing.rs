@@ -0,0 +1,192 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{
+    join::{AlignedTrade, TradeQuoteJoiner},
+    tops::{QuoteUpdate, Tops1_6Message, TradeReport},
+};
+
+/// The side which initiated a trade
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Initiator {
+    Buyer,
+    Seller,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SigningMethod {
+    /// Trades above the prevailing mid price are buyer-initiated, those below it are seller-initiated and those at the
+    /// mid are left unclassified
+    QuoteRule,
+    /// Trades at a higher price than the last different trade price (an uptick) are buyer-initiated, those at a lower
+    /// price are seller-initiated
+    TickRule,
+    /// The quote rule, falling back to the tick rule for trades at the mid price or without a usable quote
+    #[default]
+    LeeReady,
+}
+
+/// A trade annotated with the prevailing quote and the side which initiated it
+#[derive(Clone, Debug)]
+pub struct SignedTrade<S> {
+    pub aligned: AlignedTrade<S>,
+    /// `None` if the trade couldn't be classified
+    pub initiator: Option<Initiator>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TickState {
+    last_price: f64,
+    // The direction of the last price change, if the price ever changed
+    last_direction: Option<Initiator>,
+}
+
+/// Classifies trades as buyer- or seller-initiated
+///
+/// The prevailing quote is the IEX BBO in force immediately before the trade (in feed order), without any lag: TOPS
+/// timestamps quotes and trades with the same clock. Crossed or one-sided quotes aren't used by the quote rule.
+#[derive(Clone, Debug)]
+pub struct TradeSigner<S> {
+    method: SigningMethod,
+    joiner: TradeQuoteJoiner<S>,
+    ticks: HashMap<S, TickState>,
+}
+
+impl<S> Default for TradeSigner<S> {
+    fn default() -> Self {
+        Self {
+            method: SigningMethod::default(),
+            joiner: TradeQuoteJoiner::default(),
+            ticks: HashMap::new(),
+        }
+    }
+}
+
+impl<S> TradeSigner<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new(method: SigningMethod) -> Self {
+        Self {
+            method,
+            ..Self::default()
+        }
+    }
+
+    pub fn apply_quote(&mut self, quote: &QuoteUpdate<S>) {
+        self.joiner.apply_quote(quote);
+    }
+
+    fn quote_rule(aligned: &AlignedTrade<S>) -> Option<Initiator> {
+        let bbo = aligned.quote?.bbo;
+        if bbo.ask_price < bbo.bid_price {
+            return None;
+        }
+        let mid = bbo.mid()?;
+
+        if aligned.trade.price > mid {
+            Some(Initiator::Buyer)
+        } else if aligned.trade.price < mid {
+            Some(Initiator::Seller)
+        } else {
+            None
+        }
+    }
+
+    // Classifies the trade with the tick rule, updating the last trade price of the symbol
+    fn tick_rule(&mut self, trade: &TradeReport<S>) -> Option<Initiator> {
+        let Some(state) = self.ticks.get_mut(&trade.symbol) else {
+            self.ticks.insert(
+                trade.symbol.clone(),
+                TickState {
+                    last_price: trade.price,
+                    last_direction: None,
+                },
+            );
+            return None;
+        };
+
+        if trade.price > state.last_price {
+            state.last_direction = Some(Initiator::Buyer);
+        } else if trade.price < state.last_price {
+            state.last_direction = Some(Initiator::Seller);
+        }
+        state.last_price = trade.price;
+        state.last_direction
+    }
+
+    pub fn apply_trade(&mut self, trade: &TradeReport<S>) -> SignedTrade<S> {
+        let aligned = self.joiner.apply_trade(trade);
+        // The tick state must follow every trade, whichever rule ends up classifying it
+        let tick = self.tick_rule(trade);
+
+        let initiator = match self.method {
+            SigningMethod::QuoteRule => Self::quote_rule(&aligned),
+            SigningMethod::TickRule => tick,
+            SigningMethod::LeeReady => Self::quote_rule(&aligned).or(tick),
+        };
+
+        SignedTrade { aligned, initiator }
+    }
+
+    /// Feeds a TOPS message to the signer, returning the signed trade if the message is a trade report
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Option<SignedTrade<S>> {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => {
+                self.apply_quote(quote);
+                None
+            }
+            Tops1_6Message::TradeReport(trade) => Some(self.apply_trade(trade)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::*;
+
+    fn quote(bid_price: f64, ask_price: f64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::QuoteUpdate(fixtures::quote("ZIEXT", 0, bid_price, ask_price))
+    }
+
+    fn trade(price: f64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradeReport(fixtures::trade("ZIEXT", 0, price, 100))
+    }
+
+    fn sign(signer: &mut TradeSigner<&'static str>, price: f64) -> Option<Initiator> {
+        signer.update(&trade(price)).unwrap().initiator
+    }
+
+    #[test]
+    fn lee_ready() {
+        let mut signer = TradeSigner::new(SigningMethod::LeeReady);
+
+        // Neither a quote nor a previous trade
+        assert_eq!(sign(&mut signer, 10.00), None);
+        // Uptick without a quote
+        assert_eq!(sign(&mut signer, 10.01), Some(Initiator::Buyer));
+
+        signer.update(&quote(10.00, 10.04));
+        assert_eq!(sign(&mut signer, 10.03), Some(Initiator::Buyer));
+        assert_eq!(sign(&mut signer, 10.01), Some(Initiator::Seller));
+        // At the mid, after a downtick
+        assert_eq!(sign(&mut signer, 10.02), Some(Initiator::Buyer));
+        // At the mid, zero tick: the last price change was an uptick
+        assert_eq!(sign(&mut signer, 10.02), Some(Initiator::Buyer));
+
+        // Crossed quotes are ignored
+        signer.update(&quote(10.05, 10.03));
+        assert_eq!(sign(&mut signer, 10.01), Some(Initiator::Seller));
+    }
+
+    #[test]
+    fn quote_rule_leaves_mid_trades_unclassified() {
+        let mut signer = TradeSigner::new(SigningMethod::QuoteRule);
+
+        signer.update(&quote(10.00, 10.04));
+        assert_eq!(sign(&mut signer, 10.01), Some(Initiator::Seller));
+        assert_eq!(sign(&mut signer, 10.02), None);
+    }
+}
@@ -0,0 +1,960 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    io::{self, Write},
+    iter::Peekable,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    hist::HistWriter,
+    iex_tp::IexTp1Segment,
+    message_protocol_ids,
+    tops::{
+        AuctionInformation, AuctionType, ImbalanceSide, LuldTier, MarketSession, OfficialPrice,
+        OfficialPriceType, QuoteUpdate, SaleCondition, SecurityDirectory, SecurityDirectoryFlags,
+        SystemEvent, SystemEventType, Tops1_6Message, TradeBreak, TradeReport, TradingStatus,
+        TradingStatusReason, TradingStatusType,
+    },
+};
+
+/// A small deterministic pseudo-random generator (xorshift64*), so that simulations are reproducible across builds and
+/// platforms
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state must not be zero
+        Self {
+            state: (seed ^ 0x9E3779B97F4A7C15) | 1,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// A uniform number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A standard normal number (Box-Muller transform)
+    pub fn normal(&mut self) -> f64 {
+        let radius = (-2.0 * (1.0 - self.next_f64()).ln()).sqrt();
+        radius * (std::f64::consts::TAU * self.next_f64()).cos()
+    }
+
+    /// The time until the next event of a Poisson process with the given rate (per second)
+    pub fn exponential(&mut self, rate: f64) -> TimeDelta {
+        let seconds = -(1.0 - self.next_f64()).ln() / rate;
+        // Keep events strictly ordered in time
+        TimeDelta::nanoseconds((seconds * 1e9) as i64 + 1)
+    }
+}
+
+/// The simulated market of a single symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolConfig {
+    pub symbol: String,
+    /// The mid price at the start of the simulation
+    pub initial_price: f64,
+    /// The average spread, in dollars. Quoted prices are whole cents, with a spread of at least one cent.
+    pub spread: f64,
+    /// The standard deviation of the relative change of the mid price over one second
+    pub volatility: f64,
+    /// The average number of quote updates per second
+    pub quote_rate: f64,
+    /// The average number of trades per second
+    pub trade_rate: f64,
+    /// The probability of a trade being an odd lot
+    pub odd_lot_probability: f64,
+}
+
+impl SymbolConfig {
+    /// A symbol with a tight spread, a moderate volatility and a few quotes and trades per second
+    pub fn new(symbol: impl Into<String>, initial_price: f64) -> Self {
+        Self {
+            symbol: symbol.into(),
+            initial_price,
+            spread: 0.02,
+            volatility: 0.0005,
+            quote_rate: 10.0,
+            trade_rate: 1.0,
+            odd_lot_probability: 0.2,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    /// The time of the first message, which starts the regular market hours
+    pub start: DateTime<Utc>,
+    /// The length of the regular market hours
+    pub duration: TimeDelta,
+    pub symbols: Vec<SymbolConfig>,
+    pub seed: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            // 2017-04-17 09:30 ET
+            start: DateTime::from_timestamp_nanos(1492435800000000000),
+            duration: TimeDelta::minutes(1),
+            symbols: vec![
+                SymbolConfig::new("ZIEXT", 25.0),
+                SymbolConfig::new("ZVZZT", 50.0),
+                SymbolConfig::new("ZXIET", 75.0),
+            ],
+            seed: 1,
+        }
+    }
+}
+
+// Prices as decoded from the wire (fixed-point with 4 decimal digits), so that they survive encoding unchanged
+pub(crate) fn price(dollars: f64) -> f64 {
+    (dollars * 1e4).round() * 1e-4
+}
+
+pub(crate) fn dollars(cents: i64) -> f64 {
+    (cents * 100) as f64 * 1e-4
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Quote,
+    Trade,
+}
+
+#[derive(Clone, Debug)]
+struct SymbolState {
+    config: SymbolConfig,
+    mid: f64,
+    /// The time the mid price was last updated
+    updated: DateTime<Utc>,
+    /// The current quote, in cents
+    bid: i64,
+    ask: i64,
+}
+
+/// Generates a synthetic stream of TOPS messages: the system events of a trading day in order, the security directory
+/// and trading status of every symbol, then random walk quotes and trades arriving at random (Poisson) times
+///
+/// Mid prices follow a geometric random walk, and trades execute at the bid or the ask of the current quote. Messages
+/// are produced in chronological order, and the same configuration always produces the same messages.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    rng: Rng,
+    end: DateTime<Utc>,
+    symbols: Vec<SymbolState>,
+    /// The next events of each symbol, earliest first
+    events: BinaryHeap<Reverse<(DateTime<Utc>, usize, Event)>>,
+    /// Messages produced but not yet returned
+    pending: VecDeque<Tops1_6Message<String>>,
+    next_trade_id: i64,
+    finished: bool,
+}
+
+impl Simulator {
+    pub fn new(config: SimConfig) -> Self {
+        assert!(
+            config.duration >= TimeDelta::zero(),
+            "simulation duration must not be negative"
+        );
+        for symbol in &config.symbols {
+            assert!(
+                symbol.quote_rate > 0.0 && symbol.trade_rate > 0.0,
+                "quote and trade rates must be positive"
+            );
+        }
+
+        let start = config.start;
+        let mut rng = Rng::new(config.seed);
+        let mut pending = VecDeque::new();
+        let system_event = |event_type| {
+            Tops1_6Message::SystemEvent(SystemEvent {
+                event_type,
+                timestamp: start,
+            })
+        };
+        pending.push_back(system_event(SystemEventType::StartOfMessages));
+        pending.push_back(system_event(SystemEventType::StartOfSystemHours));
+
+        let mut symbols = Vec::new();
+        let mut events = BinaryHeap::new();
+        for (index, config) in config.symbols.into_iter().enumerate() {
+            pending.push_back(Tops1_6Message::SecurityDirectory(SecurityDirectory {
+                flags: SecurityDirectoryFlags {
+                    test_security: true,
+                    when_issued: false,
+                    etp: false,
+                },
+                timestamp: start,
+                symbol: config.symbol.clone(),
+                round_lot_size: 100,
+                adjusted_poc_price: price(config.initial_price),
+                luld_tier: LuldTier::Tier1,
+            }));
+            pending.push_back(Tops1_6Message::TradingStatus(TradingStatus {
+                status: TradingStatusType::Trading,
+                timestamp: start,
+                symbol: config.symbol.clone(),
+                reason: TradingStatusReason::Other(*b"    "),
+            }));
+
+            events.push(Reverse((
+                start + rng.exponential(config.quote_rate),
+                index,
+                Event::Quote,
+            )));
+            events.push(Reverse((
+                start + rng.exponential(config.trade_rate),
+                index,
+                Event::Trade,
+            )));
+            let mut state = SymbolState {
+                mid: config.initial_price,
+                updated: start,
+                bid: 0,
+                ask: 0,
+                config,
+            };
+            Self::requote(&mut state, &mut rng);
+            symbols.push(state);
+        }
+        pending.push_back(system_event(SystemEventType::StartOfRegularHours));
+        // Every symbol is quoted from the open
+        for state in &symbols {
+            pending.push_back(Self::quote(state, &mut rng, start));
+        }
+
+        Self {
+            rng,
+            end: start + config.duration,
+            symbols,
+            events,
+            pending,
+            next_trade_id: 1,
+            finished: false,
+        }
+    }
+
+    // Quotes whole cents around the mid price, with a random spread averaging the configured one
+    fn requote(state: &mut SymbolState, rng: &mut Rng) {
+        let spread = (state.config.spread * 100.0 * 2.0 * rng.next_f64())
+            .round()
+            .max(1.0) as i64;
+        let bid = ((state.mid * 100.0) - spread as f64 / 2.0).round().max(1.0) as i64;
+        state.bid = bid;
+        state.ask = bid + spread;
+    }
+
+    fn quote(
+        state: &SymbolState,
+        rng: &mut Rng,
+        timestamp: DateTime<Utc>,
+    ) -> Tops1_6Message<String> {
+        let mut size = || 100 * (1 + (rng.next_u64() % 10) as u32);
+        Tops1_6Message::QuoteUpdate(QuoteUpdate {
+            available: true,
+            market_session: MarketSession::Regular,
+            timestamp,
+            symbol: state.config.symbol.clone(),
+            bid_size: size(),
+            bid_price: dollars(state.bid),
+            ask_size: size(),
+            ask_price: dollars(state.ask),
+        })
+    }
+
+    fn step(&mut self, time: DateTime<Utc>, index: usize, event: Event) {
+        let rng = &mut self.rng;
+        let state = &mut self.symbols[index];
+
+        match event {
+            Event::Quote => {
+                let elapsed =
+                    (time - state.updated).num_nanoseconds().unwrap_or_default() as f64 * 1e-9;
+                state.mid *= (state.config.volatility * elapsed.sqrt() * rng.normal()).exp();
+                state.updated = time;
+                Self::requote(state, rng);
+                let next = time + rng.exponential(state.config.quote_rate);
+                self.events.push(Reverse((next, index, Event::Quote)));
+
+                self.pending.push_back(Self::quote(state, rng, time));
+            }
+            Event::Trade => {
+                let at_ask = rng.next_u64() & 1 == 1;
+                let odd_lot = rng.next_f64() < state.config.odd_lot_probability;
+                let size = if odd_lot {
+                    1 + (rng.next_u64() % 99) as u32
+                } else {
+                    100 * (1 + (rng.next_u64() % 5) as u32)
+                };
+                let price = if at_ask { state.ask } else { state.bid };
+                let next = time + rng.exponential(state.config.trade_rate);
+                self.events.push(Reverse((next, index, Event::Trade)));
+
+                self.pending
+                    .push_back(Tops1_6Message::TradeReport(TradeReport {
+                        sale_condition: SaleCondition {
+                            intermarket_sweep: false,
+                            extended_hours: false,
+                            odd_lot,
+                            trade_through_exempt: false,
+                            single_price: false,
+                        },
+                        timestamp: time,
+                        symbol: state.config.symbol.clone(),
+                        size,
+                        price: dollars(price),
+                        id: self.next_trade_id,
+                    }));
+                self.next_trade_id += 1;
+            }
+        }
+    }
+}
+
+impl Iterator for Simulator {
+    type Item = Tops1_6Message<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Some(message);
+            }
+            if self.finished {
+                return None;
+            }
+
+            match self.events.pop() {
+                Some(Reverse((time, index, event))) if time < self.end => {
+                    self.step(time, index, event)
+                }
+                _ => {
+                    let end = self.end;
+                    self.pending.extend(
+                        [
+                            SystemEventType::EndOfRegularHours,
+                            SystemEventType::EndOfSystemHours,
+                            SystemEventType::EndOfMessages,
+                        ]
+                        .map(|event_type| {
+                            Tops1_6Message::SystemEvent(SystemEvent {
+                                event_type,
+                                timestamp: end,
+                            })
+                        }),
+                    );
+                    self.finished = true;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Halt {
+    symbol: String,
+    at: TimeDelta,
+    duration: TimeDelta,
+}
+
+/// A simulated trading day with disruptions, for regression tests of stateful components: trading halts, trade breaks,
+/// a closing auction, and at the transport level, restarts of the session and segments lost by the network
+///
+/// Disruptions are scheduled at offsets from the start of the simulation, and those past its end are ignored. As the
+/// [`Simulator`], the same scenario always produces the same messages and segments.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub config: SimConfig,
+    halts: Vec<Halt>,
+    trade_breaks: Vec<TimeDelta>,
+    closing_auction: bool,
+    restarts: Vec<TimeDelta>,
+    gaps: Vec<TimeDelta>,
+}
+
+impl Scenario {
+    /// A scenario without any disruption
+    pub fn new(config: SimConfig) -> Self {
+        Self {
+            config,
+            halts: Vec::new(),
+            trade_breaks: Vec::new(),
+            closing_auction: false,
+            restarts: Vec::new(),
+            gaps: Vec::new(),
+        }
+    }
+
+    /// A scenario with one disruption of every kind, at times drawn from the seed of the configuration
+    pub fn random(config: SimConfig) -> Self {
+        let mut rng = Rng::new(config.seed.rotate_left(32));
+        let nanos = config.duration.num_nanoseconds().unwrap_or(i64::MAX).max(1);
+        let mut offset = |from: f64, to: f64| {
+            TimeDelta::nanoseconds((nanos as f64 * (from + (to - from) * rng.next_f64())) as i64)
+        };
+
+        let halt = offset(0.1, 0.4);
+        let halt_duration = offset(0.05, 0.2);
+        let (trade_break, restart, gap) = (offset(0.1, 0.9), offset(0.5, 0.8), offset(0.1, 0.9));
+        let mut scenario = Self::new(config).with_closing_auction();
+        if let Some(symbol) = scenario.config.symbols.first() {
+            let symbol = symbol.symbol.clone();
+            scenario = scenario.with_halt(symbol, halt, halt_duration);
+        }
+        scenario
+            .with_trade_break(trade_break)
+            .with_restart(restart)
+            .with_gap(gap)
+    }
+
+    /// Halts the trading of a symbol for a while: quotes and trades stop between a halted and a trading status
+    pub fn with_halt(
+        mut self,
+        symbol: impl Into<String>,
+        at: TimeDelta,
+        duration: TimeDelta,
+    ) -> Self {
+        self.halts.push(Halt {
+            symbol: symbol.into(),
+            at,
+            duration,
+        });
+        self
+    }
+
+    /// Breaks the last trade reported before some time
+    pub fn with_trade_break(mut self, at: TimeDelta) -> Self {
+        self.trade_breaks.push(at);
+        self
+    }
+
+    /// Ends the simulation with a closing auction: auction information about every symbol during the last 10
+    /// seconds, then their official closing prices
+    pub fn with_closing_auction(mut self) -> Self {
+        self.closing_auction = true;
+        self
+    }
+
+    /// Starts a new session at the first segment sent at or after some time, as after a restart of the feed
+    pub fn with_restart(mut self, at: TimeDelta) -> Self {
+        self.restarts.push(at);
+        self
+    }
+
+    /// Loses the first segment sent at or after some time, leaving a gap in the sequence numbers
+    pub fn with_gap(mut self, at: TimeDelta) -> Self {
+        self.gaps.push(at);
+        self
+    }
+
+    /// Every message sent by the exchange, including the ones lost in gaps
+    pub fn messages(&self) -> ScenarioMessages {
+        let config = &self.config;
+        let start = config.start;
+        let end = start + config.duration;
+        let mut injections = Vec::new();
+        for halt in &self.halts {
+            injections.push((start + halt.at, Injection::Halt(halt.symbol.clone())));
+            injections.push((
+                start + halt.at + halt.duration,
+                Injection::Resume(halt.symbol.clone()),
+            ));
+        }
+        for &at in &self.trade_breaks {
+            injections.push((start + at, Injection::TradeBreak));
+        }
+        if self.closing_auction {
+            let seconds = config.duration.num_seconds().min(10);
+            for before in (1..=seconds).rev() {
+                injections.push((
+                    end - TimeDelta::seconds(before),
+                    Injection::AuctionInformation,
+                ));
+            }
+            injections.push((end, Injection::ClosingPrices));
+        }
+        injections.retain(|&(time, _)| time > start && time <= end);
+        // Stable, so that simultaneous injections keep their order
+        injections.sort_by_key(|&(time, _)| time);
+
+        ScenarioMessages {
+            simulator: Simulator::new(config.clone()).peekable(),
+            rng: Rng::new(config.seed.rotate_left(16)),
+            end,
+            injections: injections.into(),
+            halted: HashSet::new(),
+            quotes: HashMap::new(),
+            last_trades: HashMap::new(),
+            last_trade: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The segments received from the exchange: messages with the same timestamp are sent in the same segment, and
+    /// lost segments are missing
+    pub fn segments(&self) -> ScenarioSegments {
+        let times = |offsets: &[TimeDelta]| {
+            let mut times = offsets
+                .iter()
+                .map(|&offset| self.config.start + offset)
+                .collect::<Vec<_>>();
+            times.sort();
+            VecDeque::from(times)
+        };
+
+        ScenarioSegments {
+            messages: self.messages().peekable(),
+            restarts: times(&self.restarts),
+            gaps: times(&self.gaps),
+            session_id: (self.config.seed as u32).wrapping_add(0x42870000),
+            next_sequence_number: 1,
+            stream_offset: 0,
+        }
+    }
+
+    /// Writes the segments received from the exchange to a HIST file, as captured 20 microseconds after being sent
+    pub fn write_hist<W: Write>(&self, writer: &mut HistWriter<W>) -> io::Result<()> {
+        for segment in self.segments() {
+            writer.write_payload(
+                segment.send_time + TimeDelta::microseconds(20),
+                &segment.encode()?,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Injection {
+    Halt(String),
+    Resume(String),
+    TradeBreak,
+    AuctionInformation,
+    ClosingPrices,
+}
+
+/// The messages of a [`Scenario`], see [`Scenario::messages`]
+#[derive(Clone, Debug)]
+pub struct ScenarioMessages {
+    simulator: Peekable<Simulator>,
+    rng: Rng,
+    end: DateTime<Utc>,
+    /// The disruptions yet to happen, earliest first
+    injections: VecDeque<(DateTime<Utc>, Injection)>,
+    halted: HashSet<String>,
+    /// The last quote of each symbol, as bid and ask prices
+    quotes: HashMap<String, (f64, f64)>,
+    last_trades: HashMap<String, f64>,
+    last_trade: Option<TradeReport<String>>,
+    pending: VecDeque<Tops1_6Message<String>>,
+}
+
+impl ScenarioMessages {
+    fn inject(&mut self, timestamp: DateTime<Utc>, injection: Injection) {
+        let trading_status = |status, symbol, reason| {
+            Tops1_6Message::TradingStatus(TradingStatus {
+                status,
+                timestamp,
+                symbol,
+                reason,
+            })
+        };
+
+        match injection {
+            Injection::Halt(symbol) => {
+                self.halted.insert(symbol.clone());
+                self.pending.push_back(trading_status(
+                    TradingStatusType::Halted,
+                    symbol,
+                    TradingStatusReason::HaltNewsPending,
+                ));
+            }
+            Injection::Resume(symbol) => {
+                self.halted.remove(&symbol);
+                self.pending.push_back(trading_status(
+                    TradingStatusType::Trading,
+                    symbol,
+                    TradingStatusReason::Other(*b"    "),
+                ));
+            }
+            Injection::TradeBreak => {
+                if let Some(trade) = self.last_trade.take() {
+                    self.pending
+                        .push_back(Tops1_6Message::TradeBreak(TradeBreak {
+                            sale_condition: trade.sale_condition,
+                            timestamp,
+                            symbol: trade.symbol,
+                            size: trade.size,
+                            price: trade.price,
+                            id: trade.id,
+                        }));
+                }
+            }
+            Injection::AuctionInformation => {
+                let mut symbols = self.quotes.iter().collect::<Vec<_>>();
+                symbols.sort_by(|a, b| a.0.cmp(b.0));
+                for (symbol, &(bid, ask)) in symbols {
+                    let reference_price = price((bid + ask) / 2.0);
+                    let imbalance_shares = 100 * (self.rng.next_u64() % 50) as u32;
+                    let imbalance_side = match imbalance_shares {
+                        0 => ImbalanceSide::NoImbalance,
+                        _ if self.rng.next_u64() & 1 == 0 => ImbalanceSide::Buy,
+                        _ => ImbalanceSide::Sell,
+                    };
+                    self.pending.push_back(Tops1_6Message::AuctionInformation(
+                        AuctionInformation {
+                            auction_type: AuctionType::Closing,
+                            timestamp,
+                            symbol: symbol.clone(),
+                            paired_shares: 100 * (1 + (self.rng.next_u64() % 1000) as u32),
+                            reference_price,
+                            indicative_clearing_price: reference_price,
+                            imbalance_shares,
+                            imbalance_side,
+                            extension_number: 0,
+                            scheduled_auction_time: self.end,
+                            auction_book_clearing_price: reference_price,
+                            collar_reference_price: reference_price,
+                            lower_auction_collar: price(reference_price * 0.9),
+                            upper_auction_collar: price(reference_price * 1.1),
+                        },
+                    ));
+                }
+            }
+            Injection::ClosingPrices => {
+                let mut symbols = self.quotes.iter().collect::<Vec<_>>();
+                symbols.sort_by(|a, b| a.0.cmp(b.0));
+                for (symbol, &(bid, ask)) in symbols {
+                    let official_price = self
+                        .last_trades
+                        .get(symbol)
+                        .copied()
+                        .unwrap_or(price((bid + ask) / 2.0));
+                    self.pending
+                        .push_back(Tops1_6Message::OfficialPrice(OfficialPrice {
+                            price_type: OfficialPriceType::ClosingPrice,
+                            timestamp,
+                            symbol: symbol.clone(),
+                            official_price,
+                        }));
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for ScenarioMessages {
+    type Item = Tops1_6Message<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Some(message);
+            }
+
+            // Disruptions happen before the messages simulated at the same time
+            let next_time = self.simulator.peek().and_then(Tops1_6Message::timestamp);
+            if let Some(&(time, _)) = self.injections.front() {
+                if next_time.is_none_or(|next_time| time <= next_time) {
+                    let (time, injection) = self.injections.pop_front()?;
+                    self.inject(time, injection);
+                    continue;
+                }
+            }
+
+            let message = self.simulator.next()?;
+            match &message {
+                Tops1_6Message::QuoteUpdate(quote) => {
+                    if self.halted.contains(&quote.symbol) {
+                        continue;
+                    }
+                    self.quotes
+                        .insert(quote.symbol.clone(), (quote.bid_price, quote.ask_price));
+                }
+                Tops1_6Message::TradeReport(trade) => {
+                    if self.halted.contains(&trade.symbol) {
+                        continue;
+                    }
+                    self.last_trades.insert(trade.symbol.clone(), trade.price);
+                    self.last_trade = Some(trade.clone());
+                }
+                _ => {}
+            }
+            return Some(message);
+        }
+    }
+}
+
+/// A segment of a [`Scenario`], with its messages decoded
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedSegment {
+    pub session_id: u32,
+    pub send_time: DateTime<Utc>,
+    pub first_message_sequence_no: i64,
+    pub stream_offset: i64,
+    pub messages: Vec<Tops1_6Message<String>>,
+}
+
+impl SimulatedSegment {
+    /// Encodes the segment as it's sent on the wire, on channel 1 of the TOPS feed
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        let messages = self
+            .messages
+            .iter()
+            .map(Tops1_6Message::encode)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(IexTp1Segment {
+            message_protocol_id: message_protocol_ids::TOPS,
+            channel_id: 1,
+            session_id: self.session_id,
+            send_time: self.send_time,
+            messages: messages.iter().map(Vec::as_slice).collect(),
+            first_message_sequence_no: self.first_message_sequence_no,
+            stream_offset: self.stream_offset,
+        }
+        .encode())
+    }
+}
+
+/// The segments of a [`Scenario`], see [`Scenario::segments`]
+#[derive(Clone, Debug)]
+pub struct ScenarioSegments {
+    messages: Peekable<ScenarioMessages>,
+    restarts: VecDeque<DateTime<Utc>>,
+    gaps: VecDeque<DateTime<Utc>>,
+    session_id: u32,
+    next_sequence_number: i64,
+    stream_offset: i64,
+}
+
+impl Iterator for ScenarioSegments {
+    type Item = SimulatedSegment;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let message = self.messages.next()?;
+            // Every simulated message has a timestamp
+            let send_time = message.timestamp()?;
+            let mut messages = vec![message];
+            while let Some(message) = self
+                .messages
+                .next_if(|message| message.timestamp() == Some(send_time))
+            {
+                messages.push(message);
+            }
+
+            if self.restarts.front().is_some_and(|&time| time <= send_time) {
+                self.restarts.pop_front();
+                self.session_id = self.session_id.wrapping_add(1);
+                self.next_sequence_number = 1;
+                self.stream_offset = 0;
+            }
+            let segment = SimulatedSegment {
+                session_id: self.session_id,
+                send_time,
+                first_message_sequence_no: self.next_sequence_number,
+                stream_offset: self.stream_offset,
+                messages,
+            };
+            self.next_sequence_number += segment.messages.len() as i64;
+            self.stream_offset += segment
+                .messages
+                .iter()
+                // Simulated messages can all be encoded
+                .map(|message| {
+                    message
+                        .encode()
+                        .map_or(0, |encoded| encoded.len() as i64 + 2)
+                })
+                .sum::<i64>();
+
+            if self.gaps.front().is_some_and(|&time| time <= send_time) {
+                self.gaps.pop_front();
+                continue;
+            }
+            return Some(segment);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bbo::BboTracker,
+        filter::MessageKind,
+        hist::HistReader,
+        sequence::{SequenceTracker, Sequencing},
+    };
+
+    use super::*;
+
+    #[test]
+    fn simulation() {
+        let config = SimConfig {
+            duration: TimeDelta::minutes(10),
+            ..SimConfig::default()
+        };
+        let messages = Simulator::new(config.clone()).collect::<Vec<_>>();
+
+        // Reproducible
+        assert_eq!(Simulator::new(config.clone()).collect::<Vec<_>>(), messages);
+
+        let kinds = |kind| {
+            messages
+                .iter()
+                .filter(|message| message.kind() == kind)
+                .count()
+        };
+        assert_eq!(kinds(MessageKind::SystemEvent), 6);
+        assert_eq!(kinds(MessageKind::TradingStatus), 3);
+        assert_eq!(kinds(MessageKind::SecurityDirectory), 3);
+        // About 10 quotes and 1 trade per second and symbol
+        let quotes = kinds(MessageKind::QuoteUpdate);
+        let trades = kinds(MessageKind::TradeReport);
+        assert!((16_000..20_000).contains(&quotes), "{quotes} quotes");
+        assert!((1_500..2_100).contains(&trades), "{trades} trades");
+
+        // In order, with sane quotes and trades at the quoted prices
+        assert!(messages
+            .windows(2)
+            .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+        let mut bbo = BboTracker::new();
+        for message in &messages {
+            match message {
+                Tops1_6Message::QuoteUpdate(quote) => {
+                    assert!(quote.bid_price > 0.0 && quote.bid_price < quote.ask_price);
+                }
+                Tops1_6Message::TradeReport(trade) => {
+                    let quote = bbo.bbo(&trade.symbol).unwrap();
+                    assert!(trade.price == quote.bid_price || trade.price == quote.ask_price);
+                    assert_eq!(trade.sale_condition.odd_lot, trade.size < 100);
+                }
+                _ => {}
+            }
+            bbo.update(message);
+        }
+
+        let Some(Tops1_6Message::SystemEvent(last)) = messages.last() else {
+            panic!("expected a system event");
+        };
+        assert_eq!(last.event_type, SystemEventType::EndOfMessages);
+        assert_eq!(last.timestamp, config.start + config.duration);
+    }
+
+    #[test]
+    fn scenario() {
+        let config = SimConfig::default();
+        let start = config.start;
+        let scenario = Scenario::new(config)
+            .with_halt("ZVZZT", TimeDelta::seconds(10), TimeDelta::seconds(5))
+            .with_trade_break(TimeDelta::seconds(30))
+            .with_closing_auction()
+            .with_restart(TimeDelta::seconds(40))
+            .with_gap(TimeDelta::seconds(20));
+        let messages = scenario.messages().collect::<Vec<_>>();
+        assert_eq!(scenario.messages().collect::<Vec<_>>(), messages);
+        assert!(messages
+            .windows(2)
+            .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+
+        // Nothing happens to the halted symbol during its halt
+        let halt = start + TimeDelta::seconds(10)..=start + TimeDelta::seconds(15);
+        let statuses = messages
+            .iter()
+            .filter_map(|message| match message {
+                Tops1_6Message::TradingStatus(status) if status.symbol == "ZVZZT" => {
+                    Some((status.status, status.timestamp))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                (TradingStatusType::Trading, start),
+                (TradingStatusType::Halted, *halt.start()),
+                (TradingStatusType::Trading, *halt.end()),
+            ]
+        );
+        assert!(!messages.iter().any(|message| {
+            message.symbol().is_some_and(|symbol| symbol == "ZVZZT")
+                && message.timestamp().is_some_and(|time| halt.contains(&time))
+                && message.kind() != MessageKind::TradingStatus
+        }));
+
+        // The last trade before the break is broken
+        let trade_break = messages
+            .iter()
+            .position(|message| message.kind() == MessageKind::TradeBreak)
+            .unwrap();
+        let Tops1_6Message::TradeBreak(broken) = &messages[trade_break] else {
+            unreachable!()
+        };
+        let Some(Tops1_6Message::TradeReport(trade)) = messages[..trade_break]
+            .iter()
+            .rfind(|message| message.kind() == MessageKind::TradeReport)
+        else {
+            panic!("expected a trade before the break");
+        };
+        assert_eq!((broken.id, broken.price), (trade.id, trade.price));
+
+        // 10 seconds of auction information about 3 symbols, then their closing prices before the end of the day
+        let count = |kind| {
+            messages
+                .iter()
+                .filter(|message| message.kind() == kind)
+                .count()
+        };
+        assert_eq!(count(MessageKind::AuctionInformation), 30);
+        assert_eq!(count(MessageKind::OfficialPrice), 3);
+        let end = messages.len() - 3;
+        assert!(messages[end - 3..end]
+            .iter()
+            .all(|message| message.kind() == MessageKind::OfficialPrice));
+
+        // Once written, the lost segment is a gap, and the restart starts a new stream
+        let mut writer = HistWriter::new(Vec::new()).unwrap();
+        scenario.write_hist(&mut writer).unwrap();
+        let capture = writer.into_inner();
+        let mut reader = HistReader::new(&capture[..]).unwrap();
+        let mut tracker = SequenceTracker::new();
+        let mut sessions = HashSet::new();
+        let mut gaps = Vec::new();
+        let mut received = 0;
+        while let Some(captured) = reader.next_segment().unwrap() {
+            sessions.insert(captured.segment.session_id);
+            received += captured.segment.messages.len();
+            if let Sequencing::Gap(range) = tracker.update(&captured.segment) {
+                gaps.push(range);
+            }
+        }
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(received + gaps[0].count as usize, messages.len());
+
+        let random = Scenario::random(SimConfig::default());
+        let messages = random.messages().collect::<Vec<_>>();
+        for kind in [MessageKind::TradeBreak, MessageKind::AuctionInformation] {
+            assert!(messages.iter().any(|message| message.kind() == kind));
+        }
+        assert_eq!(
+            random.segments().map(|segment| segment.session_id).max(),
+            random
+                .segments()
+                .next()
+                .map(|segment| segment.session_id + 1)
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    checkpoint::{CheckpointReader, CheckpointWriter},
+    tops::{
+        OfficialPrice, OfficialPriceType, OperationalHaltStatus, OperationalHaltStatusType,
+        QuoteUpdate, SystemEvent, Tops1_6Message, TradeReport, TradingStatus, TradingStatusType,
+    },
+};
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"IEXSNAP1";
+
+/// The latest state of a single symbol
+#[derive(Clone, Debug)]
+pub struct SymbolSnapshot<S> {
+    pub quote: Option<QuoteUpdate<S>>,
+    pub last_trade: Option<TradeReport<S>>,
+    pub official_open: Option<OfficialPrice<S>>,
+    pub official_close: Option<OfficialPrice<S>>,
+    pub trading_status: Option<TradingStatus<S>>,
+    pub operational_halt_status: Option<OperationalHaltStatus<S>>,
+}
+
+impl<S> Default for SymbolSnapshot<S> {
+    fn default() -> Self {
+        Self {
+            quote: None,
+            last_trade: None,
+            official_open: None,
+            official_close: None,
+            trading_status: None,
+            operational_halt_status: None,
+        }
+    }
+}
+
+impl<S> SymbolSnapshot<S> {
+    /// Whether the symbol is trading on IEX, i.e. its trading status is `Trading` and it isn't operationally halted.
+    /// Symbols without a trading status are assumed to be trading.
+    pub fn is_trading(&self) -> bool {
+        self.trading_status
+            .as_ref()
+            .is_none_or(|status| status.status == TradingStatusType::Trading)
+            && !self.is_operationally_halted()
+    }
+
+    pub fn is_operationally_halted(&self) -> bool {
+        self.operational_halt_status
+            .as_ref()
+            .is_some_and(|status| status.status == OperationalHaltStatusType::Halted)
+    }
+}
+
+/// Stores the latest state of every symbol as messages stream through
+#[derive(Clone, Debug)]
+pub struct MarketSnapshot<S> {
+    symbols: HashMap<S, SymbolSnapshot<S>>,
+    last_system_event: Option<SystemEvent>,
+    as_of: Option<DateTime<Utc>>,
+}
+
+impl<S> Default for MarketSnapshot<S> {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+            last_system_event: None,
+            as_of: None,
+        }
+    }
+}
+
+impl<S> MarketSnapshot<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn symbol_mut(&mut self, symbol: &S) -> &mut SymbolSnapshot<S> {
+        if !self.symbols.contains_key(symbol) {
+            self.symbols
+                .insert(symbol.clone(), SymbolSnapshot::default());
+        }
+        self.symbols
+            .get_mut(symbol)
+            .expect("the snapshot was inserted")
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        match message {
+            Tops1_6Message::SystemEvent(event) => self.last_system_event = Some(event.clone()),
+            Tops1_6Message::QuoteUpdate(quote) => {
+                self.symbol_mut(&quote.symbol).quote = Some(quote.clone())
+            }
+            Tops1_6Message::TradeReport(trade) => {
+                self.symbol_mut(&trade.symbol).last_trade = Some(trade.clone())
+            }
+            Tops1_6Message::OfficialPrice(price) => {
+                let snapshot = self.symbol_mut(&price.symbol);
+                match price.price_type {
+                    OfficialPriceType::OpeningPrice => snapshot.official_open = Some(price.clone()),
+                    OfficialPriceType::ClosingPrice => {
+                        snapshot.official_close = Some(price.clone())
+                    }
+                }
+            }
+            Tops1_6Message::TradingStatus(status) => {
+                self.symbol_mut(&status.symbol).trading_status = Some(status.clone())
+            }
+            Tops1_6Message::OperationalHaltStatus(status) => {
+                self.symbol_mut(&status.symbol).operational_halt_status = Some(status.clone())
+            }
+            _ => {}
+        }
+
+        if let Some(timestamp) = message.timestamp() {
+            self.as_of = Some(timestamp);
+        }
+    }
+
+    pub fn get<Q>(&self, symbol: &Q) -> Option<&SymbolSnapshot<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.symbols.get(symbol)
+    }
+
+    pub fn quote<Q>(&self, symbol: &Q) -> Option<&QuoteUpdate<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(symbol)?.quote.as_ref()
+    }
+
+    pub fn last_trade<Q>(&self, symbol: &Q) -> Option<&TradeReport<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(symbol)?.last_trade.as_ref()
+    }
+
+    /// Whether the symbol is trading, see [`SymbolSnapshot::is_trading`]
+    pub fn is_trading<Q>(&self, symbol: &Q) -> bool
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(symbol).is_none_or(SymbolSnapshot::is_trading)
+    }
+
+    pub fn last_system_event(&self) -> Option<&SystemEvent> {
+        self.last_system_event.as_ref()
+    }
+
+    /// The timestamp of the latest message seen, i.e. the instant the snapshot describes
+    pub fn as_of(&self) -> Option<DateTime<Utc>> {
+        self.as_of
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&S, &SymbolSnapshot<S>)> {
+        self.symbols.iter()
+    }
+
+    /// Writes the snapshot as a checkpoint, from which [`MarketSnapshot::read_from`] restores it, e.g. to reload the
+    /// end-of-day state the next morning. The latest messages of each symbol are written as they are on the wire.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let mut writer = CheckpointWriter::new(writer, CHECKPOINT_MAGIC)?;
+        writer.optional_timestamp(self.as_of)?;
+
+        let mut messages = Vec::new();
+        if let Some(event) = &self.last_system_event {
+            messages.push(event.encode()?);
+        }
+        let mut symbols = self.symbols.iter().collect::<Vec<_>>();
+        symbols.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        for (_, snapshot) in symbols {
+            if let Some(quote) = &snapshot.quote {
+                messages.push(quote.encode()?);
+            }
+            if let Some(trade) = &snapshot.last_trade {
+                messages.push(trade.encode()?);
+            }
+            for price in [&snapshot.official_open, &snapshot.official_close]
+                .into_iter()
+                .flatten()
+            {
+                messages.push(price.encode()?);
+            }
+            if let Some(status) = &snapshot.trading_status {
+                messages.push(status.encode()?);
+            }
+            if let Some(status) = &snapshot.operational_halt_status {
+                messages.push(status.encode()?);
+            }
+        }
+
+        writer.len(messages.len())?;
+        for message in &messages {
+            writer.message(message)?;
+        }
+        writer.finish()
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self>
+    where
+        S: for<'a> TryFrom<&'a str>,
+    {
+        let mut reader = CheckpointReader::new(reader, CHECKPOINT_MAGIC, "market snapshot")?;
+        let as_of = reader.optional_timestamp()?;
+
+        let mut snapshot = Self::new();
+        for _ in 0..reader.len()? {
+            let message = reader.message()?;
+            let message = Tops1_6Message::try_from(&message[..])
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid message"))?;
+            snapshot.update(&message);
+        }
+        snapshot.as_of = as_of;
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fixtures,
+        sim::{SimConfig, Simulator},
+        tops::TradingStatusReason,
+    };
+
+    use super::*;
+
+    #[test]
+    fn latest_values() {
+        let mut snapshot = MarketSnapshot::new();
+
+        for (timestamp, bid_size) in [(1, 100), (2, 200)] {
+            snapshot.update(&Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                bid_size,
+                ..fixtures::quote("ZIEXT", timestamp, 10.0, 10.01)
+            }));
+        }
+        snapshot.update(&Tops1_6Message::TradeReport(TradeReport {
+            id: 7,
+            ..fixtures::trade("ZIEXT", 3, 10.01, 100)
+        }));
+
+        assert_eq!(snapshot.quote("ZIEXT").unwrap().bid_size, 200);
+        assert_eq!(snapshot.last_trade("ZIEXT").unwrap().id, 7);
+        assert_eq!(snapshot.as_of(), Some(DateTime::from_timestamp_nanos(3)));
+        assert!(snapshot.quote("ZVZZT").is_none());
+    }
+
+    #[test]
+    fn halts() {
+        let mut snapshot = MarketSnapshot::new();
+        assert!(snapshot.is_trading("ZIEXT"));
+
+        snapshot.update(&Tops1_6Message::TradingStatus(TradingStatus {
+            status: TradingStatusType::Halted,
+            timestamp: DateTime::from_timestamp_nanos(1),
+            symbol: "ZIEXT",
+            reason: TradingStatusReason::HaltNewsPending,
+        }));
+        assert!(!snapshot.is_trading("ZIEXT"));
+
+        snapshot.update(&Tops1_6Message::TradingStatus(TradingStatus {
+            status: TradingStatusType::Trading,
+            timestamp: DateTime::from_timestamp_nanos(2),
+            symbol: "ZIEXT",
+            reason: TradingStatusReason::NotApplicable,
+        }));
+        assert!(snapshot.is_trading("ZIEXT"));
+
+        snapshot.update(&Tops1_6Message::OperationalHaltStatus(
+            OperationalHaltStatus {
+                status: OperationalHaltStatusType::Halted,
+                timestamp: DateTime::from_timestamp_nanos(3),
+                symbol: "ZIEXT",
+            },
+        ));
+        assert!(!snapshot.is_trading("ZIEXT"));
+        assert!(snapshot.get("ZIEXT").unwrap().is_operationally_halted());
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut snapshot = MarketSnapshot::new();
+        for message in Simulator::new(SimConfig::default()).take(1000) {
+            snapshot.update(&message);
+        }
+
+        let mut checkpoint = Vec::new();
+        snapshot.write_to(&mut checkpoint).unwrap();
+        let restored = MarketSnapshot::<String>::read_from(&checkpoint[..]).unwrap();
+        assert_eq!(restored.as_of(), snapshot.as_of());
+        assert_eq!(restored.iter().count(), snapshot.iter().count());
+        for (symbol, expected) in snapshot.iter() {
+            let actual = restored.get(symbol).unwrap();
+            let encode = |quote: &Option<QuoteUpdate<String>>| {
+                quote.as_ref().map(|quote| quote.encode().unwrap())
+            };
+            assert_eq!(encode(&actual.quote), encode(&expected.quote));
+            assert_eq!(
+                actual.last_trade.as_ref().map(|trade| trade.id),
+                expected.last_trade.as_ref().map(|trade| trade.id)
+            );
+            assert_eq!(actual.is_trading(), expected.is_trading());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! The example messages of the IEX TOPS 1.6 and DEEP 1.0 specifications, byte for byte, so that tests (including
+//! those of other crates) can check their decoding against authoritative bytes
+
+/// The end of system hours
+pub const SYSTEM_EVENT: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+
+/// ZIEXT is a test security, with a round lot of 100 shares, an adjusted POC price of $99.05 and LULD tier 1
+pub const SECURITY_DIRECTORY: [u8; 31] = [
+    0x44, 0x80, 0x00, 0x20, 0x89, 0x7B, 0x5A, 0x1F, 0xB6, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// ZIEXT is halted, news pending
+pub const TRADING_STATUS: [u8; 22] = [
+    0x48, 0x48, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0x54, 0x31, 0x20, 0x20,
+];
+
+/// ZIEXT is operationally halted on IEX
+pub const OPERATIONAL_HALT_STATUS: [u8; 18] = [
+    0x4f, 0x4f, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20,
+];
+
+/// The short sale price test of ZIEXT is activated
+pub const SHORT_SALE_PRICE_TEST_STATUS: [u8; 19] = [
+    0x50, 0x01, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0x41,
+];
+
+/// ZIEXT is quoted 9,700 shares bid at $99.05 and 1,000 shares offered at $99.07
+pub const QUOTE_UPDATE: [u8; 42] = [
+    0x51, 0x00, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0xE4, 0x25, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0xEC, 0x1D,
+    0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE8, 0x03, 0x00, 0x00,
+];
+
+/// 100 shares of ZIEXT traded at $99.05
+pub const TRADE_REPORT: [u8; 38] = [
+    0x54, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x96, 0x8F,
+    0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The IEX official opening price of ZIEXT is $99.05
+pub const OFFICIAL_PRICE: [u8; 26] = [
+    0x58, 0x51, 0x00, 0xF0, 0x30, 0x2A, 0x5B, 0x25, 0xB6, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The trade of [`TRADE_REPORT`] is broken
+pub const TRADE_BREAK: [u8; 38] = [
+    0x42, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x96, 0x8F,
+    0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The closing auction of ZIEXT pairs 100,000 shares, with an imbalance of 10,000 shares to buy
+pub const AUCTION_INFORMATION: [u8; 80] = [
+    0x41, 0x43, 0xDD, 0xBE, 0x20, 0xC6, 0x25, 0x33, 0x72, 0x15, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0xA0, 0x86, 0x01, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0xEC, 0x1D,
+    0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x27, 0x00, 0x00, 0x42, 0x00, 0xC0, 0xF9, 0x4F, 0x5F,
+    0x18, 0x1F, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC8, 0x5B, 0x0E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0xDE, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The buy side of the book of ZIEXT has 9,700 shares at $99.05
+pub const PRICE_LEVEL_UPDATE: [u8; 30] = [
+    0x38, 0x01, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20, 0xE4, 0x25, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// The opening process of ZIEXT is complete
+pub const SECURITY_EVENT: [u8; 18] = [
+    0x45, 0x4F, 0x00, 0xF0, 0x30, 0x2A, 0x5B, 0x25, 0xB6, 0x14, 0x5A, 0x49, 0x45, 0x58, 0x54, 0x20,
+    0x20, 0x20,
+];
+
+/// The examples of messages of the TOPS 1.6 specification, by name
+pub const TOPS_1_6: [(&str, &[u8]); 10] = [
+    ("SystemEvent", &SYSTEM_EVENT),
+    ("SecurityDirectory", &SECURITY_DIRECTORY),
+    ("TradingStatus", &TRADING_STATUS),
+    ("OperationalHaltStatus", &OPERATIONAL_HALT_STATUS),
+    ("ShortSalePriceTestStatus", &SHORT_SALE_PRICE_TEST_STATUS),
+    ("QuoteUpdate", &QUOTE_UPDATE),
+    ("TradeReport", &TRADE_REPORT),
+    ("OfficialPrice", &OFFICIAL_PRICE),
+    ("TradeBreak", &TRADE_BREAK),
+    ("AuctionInformation", &AUCTION_INFORMATION),
+];
+
+/// The examples of messages of the DEEP 1.0 specification, by name, including the messages shared with TOPS
+pub const DEEP_1_0: [(&str, &[u8]); 11] = [
+    ("SystemEvent", &SYSTEM_EVENT),
+    ("SecurityDirectory", &SECURITY_DIRECTORY),
+    ("TradingStatus", &TRADING_STATUS),
+    ("OperationalHaltStatus", &OPERATIONAL_HALT_STATUS),
+    ("ShortSalePriceTestStatus", &SHORT_SALE_PRICE_TEST_STATUS),
+    ("SecurityEvent", &SECURITY_EVENT),
+    ("PriceLevelUpdate", &PRICE_LEVEL_UPDATE),
+    ("TradeReport", &TRADE_REPORT),
+    ("OfficialPrice", &OFFICIAL_PRICE),
+    ("TradeBreak", &TRADE_BREAK),
+    ("AuctionInformation", &AUCTION_INFORMATION),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::{deep::deep_1_0_message, tops::tops_1_6_message};
+
+    use super::*;
+
+    #[test]
+    fn examples() {
+        for (name, example) in TOPS_1_6 {
+            let (rest, message) = tops_1_6_message::<String>(example).unwrap();
+            assert!(rest.is_empty(), "{name}");
+            assert_eq!(format!("{message:?}").split('(').next(), Some(name));
+            assert_eq!(message.encode().unwrap(), example, "{name}");
+        }
+        for (name, example) in DEEP_1_0 {
+            let (rest, message) = deep_1_0_message::<String>(example).unwrap();
+            assert!(rest.is_empty(), "{name}");
+            assert_eq!(format!("{message:?}").split('(').next(), Some(name));
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    io::{self, Write},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::tops::{
+    ShortSalePriceTestDetail, ShortSalePriceTestStatus, Tops1_6Message, TradeReport,
+};
+
+/// A period during which the short sale price test was in effect for a symbol
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SsrInterval {
+    pub start: DateTime<Utc>,
+    /// `None` if the restriction was still in effect at the end of the data
+    pub end: Option<DateTime<Utc>>,
+    /// Why the restriction was in effect, i.e. whether it was activated that day or continued from the previous one
+    pub detail: ShortSalePriceTestDetail,
+}
+
+/// Short sale restriction statistics of a single symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct SsrStats<S> {
+    pub symbol: S,
+    /// The periods the restriction was in effect, oldest first
+    pub intervals: Vec<SsrInterval>,
+    /// The number of shares traded
+    pub volume: u64,
+    /// The number of shares traded while the restriction was in effect
+    pub restricted_volume: u64,
+}
+
+impl<S> SsrStats<S> {
+    fn new(symbol: S) -> Self {
+        Self {
+            symbol,
+            intervals: Vec::new(),
+            volume: 0,
+            restricted_volume: 0,
+        }
+    }
+
+    /// Whether the restriction is currently in effect
+    pub fn is_active(&self) -> bool {
+        self.intervals
+            .last()
+            .is_some_and(|interval| interval.end.is_none())
+    }
+
+    /// The fraction of the volume traded under restriction, if anything traded
+    pub fn restricted_fraction(&self) -> Option<f64> {
+        (self.volume > 0).then(|| self.restricted_volume as f64 / self.volume as f64)
+    }
+}
+
+/// Aggregates short sale price test statuses and trades into per-symbol [`SsrStats`]
+#[derive(Clone, Debug)]
+pub struct SsrAggregator<S> {
+    symbols: HashMap<S, SsrStats<S>>,
+}
+
+impl<S> Default for SsrAggregator<S> {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+        }
+    }
+}
+
+impl<S> SsrAggregator<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn stats(&mut self, symbol: &S) -> &mut SsrStats<S> {
+        self.symbols
+            .entry(symbol.clone())
+            .or_insert_with(|| SsrStats::new(symbol.clone()))
+    }
+
+    pub fn apply_status(&mut self, status: &ShortSalePriceTestStatus<S>) {
+        let stats = self.stats(&status.symbol);
+        match (stats.is_active(), status.in_effect) {
+            (false, true) => stats.intervals.push(SsrInterval {
+                start: status.timestamp,
+                end: None,
+                detail: status.detail,
+            }),
+            (true, false) => {
+                if let Some(interval) = stats.intervals.last_mut() {
+                    interval.end = Some(status.timestamp);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn apply_trade(&mut self, trade: &TradeReport<S>) {
+        let stats = self.stats(&trade.symbol);
+        stats.volume += u64::from(trade.size);
+        if stats.is_active() {
+            stats.restricted_volume += u64::from(trade.size);
+        }
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        match message {
+            Tops1_6Message::ShortSalePriceTestStatus(status) => self.apply_status(status),
+            Tops1_6Message::TradeReport(trade) => self.apply_trade(trade),
+            _ => {}
+        }
+    }
+
+    pub fn get<Q>(&self, symbol: &Q) -> Option<&SsrStats<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.symbols.get(symbol)
+    }
+
+    /// Returns the statistics of all symbols seen. Restrictions still in effect are left open.
+    pub fn finish(self) -> Vec<SsrStats<S>> {
+        self.symbols.into_values().collect()
+    }
+}
+
+/// A short sale restriction across trading days: Rule 201 keeps it in effect until the end of the day following its
+/// activation, when IEX publishes it again as continued
+#[derive(Clone, Debug, PartialEq)]
+pub struct SsrRestriction<S> {
+    pub symbol: S,
+    /// When the restriction was activated, or `None` if it was already in effect on the first day of the data
+    pub activated: Option<DateTime<Utc>>,
+    /// When the restriction was first seen in effect
+    pub start: DateTime<Utc>,
+    /// When the restriction was deactivated, or `None` if it was in effect until the end of the last day of the data
+    /// it was seen in
+    pub end: Option<DateTime<Utc>>,
+    /// The days the restriction was in effect, oldest first
+    pub days: Vec<NaiveDate>,
+}
+
+/// Stitches the short sale restrictions of several days of data into a calendar of restrictions per symbol
+///
+/// Days must be added in chronological order. A restriction continued on a day is merged with the restriction of the
+/// same symbol in effect at the end of the previous day added.
+#[derive(Clone, Debug)]
+pub struct SsrCalendar<S> {
+    restrictions: Vec<SsrRestriction<S>>,
+    /// The index of the restriction of each symbol in effect at the end of the last day added
+    open: HashMap<S, usize>,
+}
+
+impl<S> Default for SsrCalendar<S> {
+    fn default() -> Self {
+        Self {
+            restrictions: Vec::new(),
+            open: HashMap::new(),
+        }
+    }
+}
+
+impl<S> SsrCalendar<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the statistics of a day, e.g. returned by [`SsrAggregator::finish`] after reading its HIST file
+    pub fn add_day(&mut self, day: NaiveDate, stats: impl IntoIterator<Item = SsrStats<S>>) {
+        let mut open = HashMap::new();
+        for stats in stats {
+            for interval in stats.intervals {
+                let continued = match self.open.remove(&stats.symbol) {
+                    Some(index) if interval.detail == ShortSalePriceTestDetail::Continued => {
+                        Some(index)
+                    }
+                    _ => None,
+                };
+                let index = match continued {
+                    Some(index) => {
+                        let restriction = &mut self.restrictions[index];
+                        restriction.end = interval.end;
+                        restriction.days.push(day);
+                        index
+                    }
+                    None => {
+                        self.restrictions.push(SsrRestriction {
+                            symbol: stats.symbol.clone(),
+                            activated: (interval.detail == ShortSalePriceTestDetail::Activated)
+                                .then_some(interval.start),
+                            start: interval.start,
+                            end: interval.end,
+                            days: vec![day],
+                        });
+                        self.restrictions.len() - 1
+                    }
+                };
+                if interval.end.is_none() {
+                    open.insert(stats.symbol.clone(), index);
+                }
+            }
+        }
+        self.open = open;
+    }
+
+    /// The restrictions, in the order they started (within a day, in no particular order)
+    pub fn restrictions(&self) -> &[SsrRestriction<S>] {
+        &self.restrictions
+    }
+
+    /// The restrictions of a symbol, oldest first
+    pub fn restrictions_of<'a, Q>(
+        &'a self,
+        symbol: &'a Q,
+    ) -> impl Iterator<Item = &'a SsrRestriction<S>>
+    where
+        S: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.restrictions
+            .iter()
+            .filter(move |restriction| restriction.symbol.borrow() == symbol)
+    }
+
+    /// Whether a restriction was in effect for a symbol on a day
+    pub fn is_restricted<Q>(&self, symbol: &Q, day: NaiveDate) -> bool
+    where
+        S: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.restrictions_of(symbol)
+            .any(|restriction| restriction.days.contains(&day))
+    }
+
+    /// Writes the calendar as CSV, sorted by symbol then start. Timestamps are in nanoseconds since the epoch, and left
+    /// empty when unknown.
+    pub fn write_csv<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+        S: Display,
+    {
+        let mut rows = self
+            .restrictions
+            .iter()
+            .map(|restriction| (restriction.symbol.to_string(), restriction))
+            .collect::<Vec<_>>();
+        rows.sort_by(|(a, a_restriction), (b, b_restriction)| {
+            a.cmp(b).then(a_restriction.start.cmp(&b_restriction.start))
+        });
+
+        let nanoseconds = |timestamp: Option<DateTime<Utc>>| {
+            timestamp
+                .and_then(|timestamp| timestamp.timestamp_nanos_opt())
+                .map_or(String::new(), |nanoseconds| nanoseconds.to_string())
+        };
+        writeln!(writer, "symbol,activated,start,end,first_day,last_day,days")?;
+        for (symbol, restriction) in rows {
+            writeln!(
+                writer,
+                "{symbol},{},{},{},{},{},{}",
+                nanoseconds(restriction.activated),
+                nanoseconds(Some(restriction.start)),
+                nanoseconds(restriction.end),
+                restriction
+                    .days
+                    .first()
+                    .map_or(String::new(), ToString::to_string),
+                restriction
+                    .days
+                    .last()
+                    .map_or(String::new(), ToString::to_string),
+                restriction.days.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+
+    use super::*;
+
+    fn status(
+        in_effect: bool,
+        detail: ShortSalePriceTestDetail,
+        timestamp: i64,
+    ) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::ShortSalePriceTestStatus(ShortSalePriceTestStatus {
+            in_effect,
+            timestamp: DateTime::from_timestamp_nanos(timestamp),
+            symbol: "ZIEXT",
+            detail,
+        })
+    }
+
+    fn trade(size: u32) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradeReport(fixtures::trade("ZIEXT", 0, 10.0, size))
+    }
+
+    #[test]
+    fn restricted_volume() {
+        let mut aggregator = SsrAggregator::new();
+
+        aggregator.update(&trade(100));
+        aggregator.update(&status(true, ShortSalePriceTestDetail::Activated, 10));
+        aggregator.update(&trade(300));
+        aggregator.update(&status(true, ShortSalePriceTestDetail::Continued, 20));
+        aggregator.update(&status(false, ShortSalePriceTestDetail::Deactivated, 30));
+        aggregator.update(&trade(400));
+
+        let stats = aggregator.get("ZIEXT").unwrap();
+        assert_eq!(
+            stats.intervals,
+            [SsrInterval {
+                start: DateTime::from_timestamp_nanos(10),
+                end: Some(DateTime::from_timestamp_nanos(30)),
+                detail: ShortSalePriceTestDetail::Activated,
+            }]
+        );
+        assert_eq!(stats.volume, 800);
+        assert_eq!(stats.restricted_fraction(), Some(0.375));
+        assert!(!stats.is_active());
+    }
+
+    #[test]
+    fn calendar() {
+        let day = |day| NaiveDate::from_ymd_opt(2017, 4, day).unwrap();
+        let stats = |statuses: &[Tops1_6Message<&'static str>]| {
+            let mut aggregator = SsrAggregator::new();
+            for status in statuses {
+                aggregator.update(status);
+            }
+            aggregator.finish()
+        };
+
+        let mut calendar = SsrCalendar::new();
+        // Already in effect on the first day
+        calendar.add_day(
+            day(17),
+            stats(&[
+                status(true, ShortSalePriceTestDetail::Continued, 10),
+                status(false, ShortSalePriceTestDetail::Deactivated, 20),
+                status(true, ShortSalePriceTestDetail::Activated, 30),
+            ]),
+        );
+        calendar.add_day(
+            day(18),
+            stats(&[status(true, ShortSalePriceTestDetail::Continued, 40)]),
+        );
+        calendar.add_day(
+            day(19),
+            stats(&[status(false, ShortSalePriceTestDetail::Deactivated, 50)]),
+        );
+
+        let restrictions = calendar.restrictions_of("ZIEXT").collect::<Vec<_>>();
+        assert_eq!(restrictions.len(), 2);
+        assert_eq!(restrictions[0].activated, None);
+        assert_eq!(
+            restrictions[0].end,
+            Some(DateTime::from_timestamp_nanos(20))
+        );
+        assert_eq!(
+            *restrictions[1],
+            SsrRestriction {
+                symbol: "ZIEXT",
+                activated: Some(DateTime::from_timestamp_nanos(30)),
+                start: DateTime::from_timestamp_nanos(30),
+                end: None,
+                days: vec![day(17), day(18)],
+            }
+        );
+        assert!(calendar.is_restricted("ZIEXT", day(18)));
+        assert!(!calendar.is_restricted("ZIEXT", day(19)));
+
+        let mut csv = Vec::new();
+        calendar.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "symbol,activated,start,end,first_day,last_day,days\n\
+             ZIEXT,,10,20,2017-04-17,2017-04-17,1\n\
+             ZIEXT,30,30,,2017-04-17,2017-04-18,2\n"
+        );
+    }
+}
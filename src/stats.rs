@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+use crate::{
+    export::{write_json_object, Value},
+    filter::{raw_symbol, MessageKind},
+    hist,
+    iex_tp::IexTp1Segment,
+};
+
+/// Counts of the segments and messages read, by kind and by symbol
+///
+/// The messages are counted undecoded, so a [`Stats`] can be fed by any reader: [`hist::Messages::with_stats`] keeps
+/// one up to date, and segments read otherwise (e.g. by a [`FeedReceiver`](crate::live::FeedReceiver)) are counted with
+/// [`Stats::add_segment`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub segments: u64,
+    /// The bytes of the segments, as on the wire
+    pub bytes: u64,
+    /// The messages, including the invalid ones
+    pub messages: u64,
+    pub invalid_segments: u64,
+    pub invalid_messages: u64,
+    pub kinds: HashMap<MessageKind, u64>,
+    /// The messages of each symbol, for the messages carrying one
+    pub symbols: HashMap<String, u64>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a segment and its messages
+    pub fn add_segment(&mut self, segment: &IexTp1Segment<'_>) {
+        self.add_segment_header(segment);
+        for message in &segment.messages {
+            self.add_message(message);
+        }
+    }
+
+    /// Counts a segment, but not its messages
+    pub(crate) fn add_segment_header(&mut self, segment: &IexTp1Segment<'_>) {
+        self.segments += 1;
+        self.bytes += segment.encoded_len() as u64;
+    }
+
+    /// Counts an undecoded message
+    pub fn add_message(&mut self, message: &[u8]) {
+        self.messages += 1;
+        if let Some(kind) = MessageKind::of(message) {
+            *self.kinds.entry(kind).or_default() += 1;
+        }
+        if let Some(symbol) = raw_symbol(message) {
+            let symbol = String::from_utf8_lossy(symbol);
+            let symbol = symbol.trim_end_matches(' ');
+            match self.symbols.get_mut(symbol) {
+                Some(count) => *count += 1,
+                None => {
+                    self.symbols.insert(symbol.to_string(), 1);
+                }
+            }
+        }
+    }
+
+    /// Counts the invalid segment or message of an error, ignoring I/O errors
+    pub fn add_error(&mut self, error: &hist::Error) {
+        match error {
+            hist::Error::Io(_) => {}
+            hist::Error::InvalidSegment { .. } => self.invalid_segments += 1,
+            hist::Error::InvalidMessage { .. } => self.invalid_messages += 1,
+        }
+    }
+
+    /// The number of invalid segments and messages
+    pub fn parse_errors(&self) -> u64 {
+        self.invalid_segments + self.invalid_messages
+    }
+
+    /// The number of messages of a kind
+    pub fn kind(&self, kind: MessageKind) -> u64 {
+        self.kinds.get(&kind).copied().unwrap_or_default()
+    }
+
+    /// The number of messages of a symbol
+    pub fn symbol(&self, symbol: &str) -> u64 {
+        self.symbols.get(symbol).copied().unwrap_or_default()
+    }
+
+    /// Adds the counts of other statistics, e.g. of another file or of another thread
+    pub fn merge(&mut self, other: &Stats) {
+        self.segments += other.segments;
+        self.bytes += other.bytes;
+        self.messages += other.messages;
+        self.invalid_segments += other.invalid_segments;
+        self.invalid_messages += other.invalid_messages;
+        for (kind, count) in &other.kinds {
+            *self.kinds.entry(*kind).or_default() += count;
+        }
+        for (symbol, count) in &other.symbols {
+            *self.symbols.entry(symbol.clone()).or_default() += count;
+        }
+    }
+
+    /// Writes the statistics as a JSON object, with the counts by kind (by name, in the order of [`MessageKind::ALL`])
+    /// and by symbol (in alphabetical order) as nested objects
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(
+            writer,
+            "{{\"segments\":{},\"bytes\":{},\"messages\":{},\"invalid_segments\":{},\"invalid_messages\":{},\"kinds\":",
+            self.segments, self.bytes, self.messages, self.invalid_segments, self.invalid_messages
+        )?;
+        let kinds = MessageKind::ALL
+            .into_iter()
+            .filter_map(|kind| Some((kind.name(), Value::Int(*self.kinds.get(&kind)? as i64))))
+            .collect::<Vec<_>>();
+        write_json_object(&mut writer, &kinds)?;
+
+        let mut symbols = self.symbols.iter().collect::<Vec<_>>();
+        symbols.sort();
+        write!(writer, ",\"symbols\":{{")?;
+        for (index, (symbol, count)) in symbols.into_iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            // Symbols are printable ASCII, but for quotes and backslashes
+            write!(writer, "{symbol:?}:{count}")?;
+        }
+        writeln!(writer, "}}}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        hist::{
+            tests::{capture, segment},
+            HistReader,
+        },
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[
+                &spec::SYSTEM_EVENT,
+                &spec::QUOTE_UPDATE,
+                &spec::TRADE_REPORT,
+            ],
+        );
+        let (_, IexTpSegment::V1(decoded)) = iex_tp_segment(&encoded).unwrap();
+        let mut stats = Stats::new();
+        stats.add_segment(&decoded);
+        stats.add_error(&hist::Error::InvalidSegment { position: 0 });
+        assert_eq!(stats.bytes, encoded.len() as u64);
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.kind(MessageKind::QuoteUpdate), 1);
+        assert_eq!(stats.kind(MessageKind::OfficialPrice), 0);
+        assert_eq!(stats.symbol("ZIEXT"), 2);
+        assert_eq!(stats.parse_errors(), 1);
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.segments, 2);
+        assert_eq!(merged.symbol("ZIEXT"), 4);
+
+        let mut json = Vec::new();
+        stats.write_json(&mut json).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            format!(
+                "{{\"segments\":1,\"bytes\":{},\"messages\":3,\"invalid_segments\":1,\"invalid_messages\":0,\
+                \"kinds\":{{\"system_event\":1,\"quote_update\":1,\"trade_report\":1}},\"symbols\":{{\"ZIEXT\":2}}}}\n",
+                encoded.len()
+            )
+        );
+    }
+
+    #[test]
+    fn reader_stats() {
+        let mut invalid = spec::TRADE_REPORT.to_vec();
+        invalid.truncate(20);
+        let file = capture(&[
+            segment(message_protocol_ids::TOPS, 1, &[&spec::QUOTE_UPDATE]),
+            segment(message_protocol_ids::DEEP_1_0, 1, &[&spec::SECURITY_EVENT]),
+            segment(
+                message_protocol_ids::TOPS,
+                2,
+                &[&spec::TRADE_REPORT, &invalid],
+            ),
+        ]);
+
+        let mut messages = HistReader::new(Cursor::new(file))
+            .unwrap()
+            .messages::<Tops1_6Message<String>>()
+            .with_stats();
+        assert_eq!(messages.by_ref().take_while(Result::is_ok).count(), 2);
+        assert!(messages.next().is_none());
+
+        // Segments of other protocols aren't counted
+        let stats = messages.stats().unwrap();
+        assert_eq!(stats.segments, 2);
+        assert_eq!(stats.messages, 3);
+        assert_eq!(stats.invalid_messages, 1);
+        assert_eq!(stats.kind(MessageKind::TradeReport), 2);
+        assert_eq!(stats.symbol("ZIEXT"), 3);
+    }
+}
@@ -0,0 +1,399 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, ops::Range};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    bbo::Bbo,
+    point_in_time::QuoteSnapshot,
+    tops::{QuoteUpdate, Tops1_6Message, TradeReport},
+};
+
+/// The quotes of a symbol, one column per field, sorted by timestamp
+#[derive(Clone, Debug, Default)]
+pub struct QuoteColumns {
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub bid_prices: Vec<f64>,
+    pub bid_sizes: Vec<u32>,
+    pub ask_prices: Vec<f64>,
+    pub ask_sizes: Vec<u32>,
+}
+
+impl QuoteColumns {
+    fn insert<S>(&mut self, quote: &QuoteUpdate<S>) {
+        let row = insertion_row(&self.timestamps, quote.timestamp);
+        self.timestamps.insert(row, quote.timestamp);
+        self.bid_prices.insert(row, quote.bid_price);
+        self.bid_sizes.insert(row, quote.bid_size);
+        self.ask_prices.insert(row, quote.ask_price);
+        self.ask_sizes.insert(row, quote.ask_size);
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// The BBO quoted by a row
+    pub fn bbo(&self, row: usize) -> Bbo {
+        Bbo {
+            bid_price: self.bid_prices[row],
+            bid_size: self.bid_sizes[row],
+            ask_price: self.ask_prices[row],
+            ask_size: self.ask_sizes[row],
+        }
+    }
+
+    /// The rows timestamped within a window
+    pub fn rows(&self, window: Range<DateTime<Utc>>) -> Range<usize> {
+        rows(&self.timestamps, window)
+    }
+
+    /// The last row timestamped at or before some time
+    pub fn row_at(&self, time: DateTime<Utc>) -> Option<usize> {
+        row_at(&self.timestamps, time)
+    }
+}
+
+/// The trades of a symbol, one column per field, sorted by timestamp
+#[derive(Clone, Debug, Default)]
+pub struct TradeColumns {
+    pub timestamps: Vec<DateTime<Utc>>,
+    pub prices: Vec<f64>,
+    pub sizes: Vec<u32>,
+    pub ids: Vec<i64>,
+}
+
+impl TradeColumns {
+    fn insert<S>(&mut self, trade: &TradeReport<S>) {
+        let row = insertion_row(&self.timestamps, trade.timestamp);
+        self.timestamps.insert(row, trade.timestamp);
+        self.prices.insert(row, trade.price);
+        self.sizes.insert(row, trade.size);
+        self.ids.insert(row, trade.id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// The rows timestamped within a window
+    pub fn rows(&self, window: Range<DateTime<Utc>>) -> Range<usize> {
+        rows(&self.timestamps, window)
+    }
+
+    /// The last row timestamped at or before some time
+    pub fn row_at(&self, time: DateTime<Utc>) -> Option<usize> {
+        row_at(&self.timestamps, time)
+    }
+}
+
+/// The row at which a message is inserted, after the rows timestamped at or before it, so that messages sharing a
+/// timestamp stay in the order they were ingested
+fn insertion_row(timestamps: &[DateTime<Utc>], timestamp: DateTime<Utc>) -> usize {
+    match timestamps.last() {
+        Some(&last) if last > timestamp => timestamps.partition_point(|&t| t <= timestamp),
+        _ => timestamps.len(),
+    }
+}
+
+/// The rows timestamped within a window, none if it ends before it starts
+fn rows(timestamps: &[DateTime<Utc>], window: Range<DateTime<Utc>>) -> Range<usize> {
+    let start = timestamps.partition_point(|&t| t < window.start);
+    let end = timestamps.partition_point(|&t| t < window.end);
+    start..end.max(start)
+}
+
+fn row_at(timestamps: &[DateTime<Utc>], time: DateTime<Utc>) -> Option<usize> {
+    timestamps.partition_point(|&t| t <= time).checked_sub(1)
+}
+
+/// The quotes and trades of a symbol
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTicks {
+    pub quotes: QuoteColumns,
+    pub trades: TradeColumns,
+}
+
+/// Holds the quotes and trades of a day in memory, by symbol and in columns sorted by timestamp, to answer many
+/// queries by symbol and time window (e.g. the BBO of a symbol at some time, or a snapshot of every quote) without
+/// reading the HIST file again
+///
+/// Messages may be ingested out of order, although it's much faster in order, as they're read from a HIST file.
+#[derive(Clone, Debug)]
+pub struct TickStore<S> {
+    symbols: HashMap<S, SymbolTicks>,
+}
+
+impl<S> Default for TickStore<S> {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+        }
+    }
+}
+
+impl<S> TickStore<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a TOPS message, if it's a quote update or a trade report
+    pub fn ingest(&mut self, message: &Tops1_6Message<S>) {
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => self.ticks(&quote.symbol).quotes.insert(quote),
+            Tops1_6Message::TradeReport(trade) => self.ticks(&trade.symbol).trades.insert(trade),
+            _ => {}
+        }
+    }
+
+    fn ticks(&mut self, symbol: &S) -> &mut SymbolTicks {
+        if !self.symbols.contains_key(symbol) {
+            self.symbols.insert(symbol.clone(), SymbolTicks::default());
+        }
+        self.symbols.get_mut(symbol).expect("inserted above")
+    }
+
+    /// The quotes and trades of a symbol
+    pub fn get<Q>(&self, symbol: &Q) -> Option<&SymbolTicks>
+    where
+        S: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.symbols.get(symbol)
+    }
+
+    /// The BBO of a symbol as of some time, with the timestamp of its quote
+    pub fn quote_at<Q>(&self, symbol: &Q, time: DateTime<Utc>) -> Option<(DateTime<Utc>, Bbo)>
+    where
+        S: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let quotes = &self.get(symbol)?.quotes;
+        let row = quotes.row_at(time)?;
+        Some((quotes.timestamps[row], quotes.bbo(row)))
+    }
+
+    /// The quotes of a symbol timestamped within a window, with their timestamps
+    pub fn quotes<'a, Q>(
+        &'a self,
+        symbol: &Q,
+        window: Range<DateTime<Utc>>,
+    ) -> impl Iterator<Item = (DateTime<Utc>, Bbo)> + 'a
+    where
+        S: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let quotes = self.get(symbol).map(|ticks| &ticks.quotes);
+        quotes.into_iter().flat_map(move |quotes| {
+            quotes
+                .rows(window.clone())
+                .map(|row| (quotes.timestamps[row], quotes.bbo(row)))
+        })
+    }
+
+    /// The trades of a symbol timestamped within a window, as their columns
+    pub fn trades<Q>(&self, symbol: &Q, window: Range<DateTime<Utc>>) -> TradeSlice<'_>
+    where
+        S: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.get(symbol) {
+            Some(ticks) => {
+                let trades = &ticks.trades;
+                let rows = trades.rows(window);
+                TradeSlice {
+                    timestamps: &trades.timestamps[rows.clone()],
+                    prices: &trades.prices[rows.clone()],
+                    sizes: &trades.sizes[rows.clone()],
+                    ids: &trades.ids[rows],
+                }
+            }
+            None => TradeSlice::default(),
+        }
+    }
+
+    /// The BBO of every symbol quoted as of some time
+    pub fn snapshot(&self, as_of: DateTime<Utc>) -> QuoteSnapshot<S> {
+        QuoteSnapshot {
+            as_of,
+            quotes: self
+                .symbols
+                .iter()
+                .filter_map(|(symbol, ticks)| {
+                    let row = ticks.quotes.row_at(as_of)?;
+                    Some((symbol.clone(), ticks.quotes.bbo(row)))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &S> {
+        self.symbols.keys()
+    }
+
+    /// The number of quotes and trades stored
+    pub fn len(&self) -> usize {
+        self.symbols
+            .values()
+            .map(|ticks| ticks.quotes.len() + ticks.trades.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a, S> Extend<&'a Tops1_6Message<S>> for TickStore<S>
+where
+    S: Eq + Hash + Clone + 'a,
+{
+    fn extend<I: IntoIterator<Item = &'a Tops1_6Message<S>>>(&mut self, messages: I) {
+        for message in messages {
+            self.ingest(message);
+        }
+    }
+}
+
+impl<S> Extend<Tops1_6Message<S>> for TickStore<S>
+where
+    S: Eq + Hash + Clone,
+{
+    fn extend<I: IntoIterator<Item = Tops1_6Message<S>>>(&mut self, messages: I) {
+        for message in messages {
+            self.ingest(&message);
+        }
+    }
+}
+
+impl<S> FromIterator<Tops1_6Message<S>> for TickStore<S>
+where
+    S: Eq + Hash + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = Tops1_6Message<S>>>(messages: I) -> Self {
+        let mut store = Self::new();
+        store.extend(messages);
+        store
+    }
+}
+
+/// The trades of a symbol within a window, see [`TickStore::trades`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TradeSlice<'a> {
+    pub timestamps: &'a [DateTime<Utc>],
+    pub prices: &'a [f64],
+    pub sizes: &'a [u32],
+    pub ids: &'a [i64],
+}
+
+impl TradeSlice<'_> {
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// The traded volume
+    pub fn volume(&self) -> u64 {
+        self.sizes.iter().map(|&size| u64::from(size)).sum()
+    }
+
+    /// The volume-weighted average price, if anything traded
+    pub fn vwap(&self) -> Option<f64> {
+        let volume = self.volume();
+        (volume > 0).then(|| {
+            self.prices
+                .iter()
+                .zip(self.sizes)
+                .map(|(&price, &size)| price * f64::from(size))
+                .sum::<f64>()
+                / volume as f64
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    fn quote(symbol: &str, seconds: i64, bid_price: f64) -> Tops1_6Message<String> {
+        Tops1_6Message::QuoteUpdate(QuoteUpdate {
+            timestamp: at(seconds),
+            ..fixtures::quote(symbol.to_string(), 0, bid_price, bid_price + 0.01)
+        })
+    }
+
+    fn trade(symbol: &str, seconds: i64, price: f64, size: u32) -> Tops1_6Message<String> {
+        Tops1_6Message::TradeReport(TradeReport {
+            timestamp: at(seconds),
+            id: seconds,
+            ..fixtures::trade(symbol.to_string(), 0, price, size)
+        })
+    }
+
+    #[test]
+    fn queries() {
+        let store = [
+            quote("SPY", 10, 400.0),
+            trade("SPY", 11, 400.01, 100),
+            quote("QQQ", 12, 300.0),
+            quote("SPY", 20, 401.0),
+            trade("SPY", 21, 401.01, 300),
+            // Out of order
+            quote("SPY", 15, 400.5),
+        ]
+        .into_iter()
+        .collect::<TickStore<String>>();
+        assert_eq!(store.len(), 6);
+
+        assert_eq!(store.quote_at("SPY", at(9)), None);
+        assert_eq!(
+            store
+                .quote_at("SPY", at(15))
+                .map(|(timestamp, bbo)| (timestamp, bbo.bid_price)),
+            Some((at(15), 400.5))
+        );
+        assert_eq!(
+            store
+                .quotes("SPY", at(10)..at(20))
+                .map(|(_, bbo)| bbo.bid_price)
+                .collect::<Vec<_>>(),
+            [400.0, 400.5]
+        );
+        assert_eq!(store.quotes("AAPL", at(0)..at(30)).count(), 0);
+
+        let trades = store.trades("SPY", at(0)..at(30));
+        assert_eq!(trades.ids, [11, 21]);
+        assert_eq!(trades.volume(), 400);
+        assert_float_eq!(trades.vwap().unwrap(), 400.76, abs <= 1e-9);
+        assert!(store.trades("SPY", at(12)..at(21)).is_empty());
+        // A window ending before it starts is empty
+        assert!(store.trades("SPY", at(25)..at(5)).is_empty());
+        assert_eq!(store.quotes("SPY", at(25)..at(5)).count(), 0);
+
+        let snapshot = store.snapshot(at(12));
+        assert_eq!(snapshot.quotes.len(), 2);
+        assert_eq!(snapshot.quotes["QQQ"].bid_price, 300.0);
+        assert_eq!(snapshot.quotes["SPY"].bid_price, 400.0);
+    }
+}
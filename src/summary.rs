@@ -0,0 +1,350 @@
+use std::{collections::HashMap, hash::Hash, io::Read};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    bbo::Bbo,
+    hist::{self, HistReader},
+    tops::{
+        OfficialPrice, OfficialPriceType, QuoteUpdate, Tops1_6Message, TradeBreak, TradeReport,
+    },
+};
+
+/// Summary statistics of a single symbol over a trading day
+#[derive(Clone, Debug, PartialEq)]
+pub struct DailySummary<S> {
+    pub symbol: S,
+    pub official_open: Option<f64>,
+    pub official_close: Option<f64>,
+    pub first_trade_price: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub last_trade_price: Option<f64>,
+    /// The number of shares traded
+    pub volume: u64,
+    /// The sum of price times size over all trades
+    pub notional: f64,
+    pub trade_count: u64,
+    /// The total time during which the symbol was quoted on both sides
+    pub quoted_time: TimeDelta,
+}
+
+impl<S> DailySummary<S> {
+    fn new(symbol: S) -> Self {
+        Self {
+            symbol,
+            official_open: None,
+            official_close: None,
+            first_trade_price: None,
+            high: None,
+            low: None,
+            last_trade_price: None,
+            volume: 0,
+            notional: 0.0,
+            trade_count: 0,
+            quoted_time: TimeDelta::zero(),
+        }
+    }
+
+    /// The official opening price, or the price of the first trade if there is none
+    pub fn open(&self) -> Option<f64> {
+        self.official_open.or(self.first_trade_price)
+    }
+
+    /// The official closing price, or the price of the last trade if there is none
+    pub fn close(&self) -> Option<f64> {
+        self.official_close.or(self.last_trade_price)
+    }
+
+    /// The volume weighted average price, if anything traded
+    pub fn vwap(&self) -> Option<f64> {
+        (self.volume > 0).then(|| self.notional / self.volume as f64)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SymbolState<S> {
+    summary: DailySummary<S>,
+    // When the symbol became quoted on both sides, if it currently is
+    two_sided_since: Option<DateTime<Utc>>,
+    // The price, size and ID of every trade, only kept if trade breaks are handled
+    trades: Vec<(f64, u32, i64)>,
+}
+
+impl<S> SymbolState<S> {
+    fn add_trade(&mut self, price: f64, size: u32) {
+        let summary = &mut self.summary;
+        summary.first_trade_price.get_or_insert(price);
+        summary.high = Some(summary.high.map_or(price, |high| high.max(price)));
+        summary.low = Some(summary.low.map_or(price, |low| low.min(price)));
+        summary.last_trade_price = Some(price);
+        summary.volume += u64::from(size);
+        summary.notional += price * f64::from(size);
+        summary.trade_count += 1;
+    }
+
+    // Removes a trade, recomputing the trade statistics from the remaining ones. Returns whether the trade was found.
+    fn remove_trade(&mut self, id: i64) -> bool {
+        let Some(index) = self
+            .trades
+            .iter()
+            .position(|&(_, _, trade_id)| trade_id == id)
+        else {
+            return false;
+        };
+        self.trades.remove(index);
+
+        let summary = &mut self.summary;
+        summary.first_trade_price = None;
+        summary.high = None;
+        summary.low = None;
+        summary.last_trade_price = None;
+        summary.volume = 0;
+        summary.notional = 0.0;
+        summary.trade_count = 0;
+        let trades = std::mem::take(&mut self.trades);
+        for &(price, size, _) in &trades {
+            self.add_trade(price, size);
+        }
+        self.trades = trades;
+        true
+    }
+}
+
+/// Aggregates the messages of a trading day into per-symbol [`DailySummary`]s
+#[derive(Clone, Debug)]
+pub struct SummaryBuilder<S> {
+    symbols: HashMap<S, SymbolState<S>>,
+    last_timestamp: Option<DateTime<Utc>>,
+    handle_trade_breaks: bool,
+}
+
+impl<S> Default for SummaryBuilder<S> {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+            last_timestamp: None,
+            handle_trade_breaks: false,
+        }
+    }
+}
+
+impl<S> SummaryBuilder<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a builder which keeps every trade, so that trade breaks can remove them from the summaries
+    pub fn with_trade_breaks() -> Self {
+        Self {
+            handle_trade_breaks: true,
+            ..Self::default()
+        }
+    }
+
+    fn state(&mut self, symbol: &S) -> &mut SymbolState<S> {
+        if !self.symbols.contains_key(symbol) {
+            self.symbols.insert(
+                symbol.clone(),
+                SymbolState {
+                    summary: DailySummary::new(symbol.clone()),
+                    two_sided_since: None,
+                    trades: Vec::new(),
+                },
+            );
+        }
+        self.symbols
+            .get_mut(symbol)
+            .expect("the state was inserted")
+    }
+
+    pub fn apply_trade(&mut self, trade: &TradeReport<S>) {
+        let handle_trade_breaks = self.handle_trade_breaks;
+        let state = self.state(&trade.symbol);
+        state.add_trade(trade.price, trade.size);
+        if handle_trade_breaks {
+            state.trades.push((trade.price, trade.size, trade.id));
+        }
+    }
+
+    /// Removes a broken trade from the summary of its symbol, returning whether the trade was found. Does nothing
+    /// unless the builder was created with [`SummaryBuilder::with_trade_breaks`].
+    pub fn apply_trade_break(&mut self, trade_break: &TradeBreak<S>) -> bool {
+        self.symbols
+            .get_mut(&trade_break.symbol)
+            .is_some_and(|state| state.remove_trade(trade_break.id))
+    }
+
+    pub fn apply_quote(&mut self, quote: &QuoteUpdate<S>) {
+        let state = self.state(&quote.symbol);
+        let two_sided = Bbo::from(quote).is_two_sided();
+
+        match (state.two_sided_since, two_sided) {
+            (Some(since), false) => {
+                state.summary.quoted_time += quote.timestamp - since;
+                state.two_sided_since = None;
+            }
+            (None, true) => state.two_sided_since = Some(quote.timestamp),
+            _ => {}
+        }
+    }
+
+    pub fn apply_official_price(&mut self, official_price: &OfficialPrice<S>) {
+        let summary = &mut self.state(&official_price.symbol).summary;
+        match official_price.price_type {
+            OfficialPriceType::OpeningPrice => {
+                summary.official_open = Some(official_price.official_price)
+            }
+            OfficialPriceType::ClosingPrice => {
+                summary.official_close = Some(official_price.official_price)
+            }
+        }
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        match message {
+            Tops1_6Message::TradeReport(trade) => self.apply_trade(trade),
+            Tops1_6Message::TradeBreak(trade_break) => {
+                self.apply_trade_break(trade_break);
+            }
+            Tops1_6Message::QuoteUpdate(quote) => self.apply_quote(quote),
+            Tops1_6Message::OfficialPrice(official_price) => {
+                self.apply_official_price(official_price)
+            }
+            _ => {}
+        }
+
+        if let Some(timestamp) = message.timestamp() {
+            self.last_timestamp = Some(timestamp);
+        }
+    }
+
+    /// Returns the summaries of all symbols seen. Quotes still in force are accounted up to the last message seen.
+    pub fn finish(self) -> Vec<DailySummary<S>> {
+        let end = self.last_timestamp;
+        self.symbols
+            .into_values()
+            .map(|mut state| {
+                if let (Some(since), Some(end)) = (state.two_sided_since, end) {
+                    state.summary.quoted_time += end - since;
+                }
+                state.summary
+            })
+            .collect()
+    }
+}
+
+/// Summarizes a TOPS HIST file in a single pass
+pub fn summarize<R, S>(reader: HistReader<R>) -> Result<Vec<DailySummary<S>>, hist::Error>
+where
+    R: Read,
+    S: for<'a> TryFrom<&'a str> + Eq + Hash + Clone,
+{
+    let mut builder = SummaryBuilder::new();
+    for message in reader.messages::<Tops1_6Message<S>>() {
+        builder.update(&message?);
+    }
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::{
+        fixtures,
+        hist::tests::{capture, segment},
+        message_protocol_ids,
+    };
+
+    use super::*;
+
+    #[test]
+    fn summary_from_hist_file() {
+        // Quote at 15:30:32.572715948, trade at 15:31:23.662974915, official opening price at 09:30
+        let quote: [u8; 42] = [
+            0x51, 0x00, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20, 0xE4, 0x25, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0xEC, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE8, 0x03, 0x00, 0x00,
+        ];
+        let trade: [u8; 38] = [
+            0x54, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x96, 0x8F, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let official_price: [u8; 26] = [
+            0x58, 0x51, 0x00, 0xF0, 0x30, 0x2A, 0x5B, 0x25, 0xB6, 0x14, 0x5A, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20, 0xEC, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let capture = capture(&[
+            segment(message_protocol_ids::TOPS, 1, &[&official_price, &quote]),
+            segment(message_protocol_ids::TOPS, 3, &[&trade]),
+        ]);
+        let summaries = summarize::<_, String>(HistReader::new(&capture[..]).unwrap()).unwrap();
+
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary.symbol, "ZIEXT");
+        assert_float_eq!(summary.open().unwrap(), 99.07, ulps <= 5);
+        assert_float_eq!(summary.close().unwrap(), 99.05, ulps <= 5);
+        assert_float_eq!(summary.high.unwrap(), 99.05, ulps <= 5);
+        assert_eq!(summary.volume, 100);
+        assert_eq!(summary.trade_count, 1);
+        assert_eq!(
+            summary.quoted_time,
+            TimeDelta::nanoseconds(1471980683662974915 - 1471980632572715948)
+        );
+    }
+
+    #[test]
+    fn quoted_time_ends_with_one_sided_quote() {
+        let quote = |timestamp, bid_size| QuoteUpdate {
+            bid_size,
+            ..fixtures::quote("ZIEXT", timestamp, 99.05, 99.07)
+        };
+
+        let mut builder = SummaryBuilder::new();
+        builder.update(&Tops1_6Message::QuoteUpdate(quote(10, 100)));
+        builder.update(&Tops1_6Message::QuoteUpdate(quote(15, 200)));
+        builder.update(&Tops1_6Message::QuoteUpdate(quote(30, 0)));
+        builder.update(&Tops1_6Message::QuoteUpdate(quote(50, 100)));
+        builder.update(&Tops1_6Message::QuoteUpdate(quote(55, 100)));
+
+        let summaries = builder.finish();
+        assert_eq!(summaries[0].quoted_time, TimeDelta::nanoseconds(25));
+        assert_eq!(summaries[0].vwap(), None);
+    }
+
+    #[test]
+    fn broken_trades_are_removed() {
+        let trade = |id, price| TradeReport {
+            id,
+            ..fixtures::trade("ZIEXT", id, price, 100)
+        };
+
+        let mut builder = SummaryBuilder::with_trade_breaks();
+        builder.update(&Tops1_6Message::TradeReport(trade(1, 10.0)));
+        builder.update(&Tops1_6Message::TradeReport(trade(2, 12.0)));
+        builder.update(&Tops1_6Message::TradeReport(trade(3, 11.0)));
+
+        let broken = trade(2, 12.0);
+        assert!(builder.apply_trade_break(&TradeBreak {
+            sale_condition: broken.sale_condition,
+            timestamp: DateTime::from_timestamp_nanos(4),
+            symbol: broken.symbol,
+            size: broken.size,
+            price: broken.price,
+            id: broken.id,
+        }));
+
+        let summary = &builder.finish()[0];
+        assert_eq!(summary.high, Some(11.0));
+        assert_eq!(summary.last_trade_price, Some(11.0));
+        assert_eq!(summary.volume, 200);
+        assert_eq!(summary.trade_count, 2);
+    }
+}
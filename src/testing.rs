@@ -0,0 +1,536 @@
+use std::{
+    io::{self, BufRead, ErrorKind, Read},
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    export::{ToRecord, Value},
+    hist::{self, HistReader, Message},
+    iex_tp::IexTp1Segment,
+    message_protocol_ids,
+};
+
+/// A step of the script of a [`MockExchange`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Sends a segment carrying the next messages of the session
+    Send(Vec<Vec<u8>>),
+    /// Sequences the next messages of the session, but doesn't send them, as if they were lost by the network
+    Drop(Vec<Vec<u8>>),
+    /// Sends a segment without any message
+    Heartbeat,
+    /// Sends a datagram as is, e.g. an invalid segment
+    Raw(Vec<u8>),
+    /// Moves the send time of the following segments forward
+    Advance(TimeDelta),
+    /// Waits before the next step, in real time
+    Sleep(Duration),
+    /// Starts a new session, as after a restart of the feed: the sequence numbers and stream offsets start over
+    Restart,
+}
+
+/// Serves a scripted IEX-TP feed over UDP, for integration tests of live consumers such as
+/// [`FeedReceiver`](crate::live::FeedReceiver)
+///
+/// Segments are sent to a single address (a multicast group, or the address of the receiver), with consecutive sequence
+/// numbers and stream offsets unless the script drops messages or restarts the session. Send times are simulated: they
+/// start at a fixed time and only move forward with [`Action::Advance`], so that tests are deterministic.
+#[derive(Debug)]
+pub struct MockExchange {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    message_protocol_id: u16,
+    channel_id: u32,
+    session_id: u32,
+    send_time: DateTime<Utc>,
+    next_sequence_number: i64,
+    stream_offset: i64,
+}
+
+impl MockExchange {
+    /// Creates an exchange sending a TOPS feed to an address, from an ephemeral port of the loopback interface
+    pub fn new(destination: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?,
+            destination,
+            message_protocol_id: message_protocol_ids::TOPS,
+            channel_id: 1,
+            session_id: 1,
+            // 2017-04-17 09:30 ET
+            send_time: DateTime::from_timestamp_nanos(1492435800000000000),
+            next_sequence_number: 1,
+            stream_offset: 0,
+        })
+    }
+
+    pub fn with_message_protocol_id(mut self, message_protocol_id: u16) -> Self {
+        self.message_protocol_id = message_protocol_id;
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: u32) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    pub fn with_send_time(mut self, send_time: DateTime<Utc>) -> Self {
+        self.send_time = send_time;
+        self
+    }
+
+    /// The session of the next segments
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+
+    /// The sequence number of the next message
+    pub fn next_sequence_number(&self) -> i64 {
+        self.next_sequence_number
+    }
+
+    pub fn send_time(&self) -> DateTime<Utc> {
+        self.send_time
+    }
+
+    pub fn run(&mut self, action: &Action) -> io::Result<()> {
+        match action {
+            Action::Send(messages) => self.send(messages),
+            Action::Drop(messages) => {
+                self.segment(messages);
+                Ok(())
+            }
+            Action::Heartbeat => self.send(&[]),
+            Action::Raw(payload) => self.socket.send_to(payload, self.destination).map(drop),
+            Action::Advance(delta) => {
+                self.send_time += *delta;
+                Ok(())
+            }
+            Action::Sleep(duration) => {
+                thread::sleep(*duration);
+                Ok(())
+            }
+            Action::Restart => {
+                self.session_id = self.session_id.wrapping_add(1);
+                self.next_sequence_number = 1;
+                self.stream_offset = 0;
+                Ok(())
+            }
+        }
+    }
+
+    /// Runs a whole script
+    pub fn run_script<'a>(
+        &mut self,
+        script: impl IntoIterator<Item = &'a Action>,
+    ) -> io::Result<()> {
+        script.into_iter().try_for_each(|action| self.run(action))
+    }
+
+    /// Runs a script on another thread, returning the exchange once it's done
+    pub fn spawn(mut self, script: Vec<Action>) -> JoinHandle<io::Result<Self>> {
+        thread::spawn(move || {
+            self.run_script(&script)?;
+            Ok(self)
+        })
+    }
+
+    /// Sends a segment carrying the next messages of the session
+    pub fn send(&mut self, messages: &[Vec<u8>]) -> io::Result<()> {
+        let payload = self.segment(messages);
+        self.socket.send_to(&payload, self.destination).map(drop)
+    }
+
+    // Encodes the segment carrying the next messages, and moves the session past them
+    fn segment(&mut self, messages: &[Vec<u8>]) -> Vec<u8> {
+        let segment = IexTp1Segment {
+            message_protocol_id: self.message_protocol_id,
+            channel_id: self.channel_id,
+            session_id: self.session_id,
+            send_time: self.send_time,
+            messages: messages.iter().map(Vec::as_slice).collect(),
+            first_message_sequence_no: self.next_sequence_number,
+            stream_offset: self.stream_offset,
+        };
+
+        self.next_sequence_number += messages.len() as i64;
+        self.stream_offset += messages
+            .iter()
+            .map(|message| message.len() as i64 + 2)
+            .sum::<i64>();
+        segment.encode()
+    }
+}
+
+/// A difference between the decoding of a message and its reference decoding, see [`compare_decodings`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// The index of the message among the messages of the capture
+    pub index: usize,
+    /// The field which differs, or `message` if a message is missing from either decoding
+    pub field: String,
+    /// The reference value (or the whole reference of a message missing from the capture), if any
+    pub expected: Option<String>,
+    /// The decoded value (or the kind of a message missing from the reference), if any
+    pub actual: Option<String>,
+}
+
+/// Compares the decoding of the messages of a capture with a reference decoding (e.g. produced by an independent
+/// implementation), field by field, to catch bugs such as swapped fields or wrong byte orders
+///
+/// The reference has a JSON object per line and per message, in order, with the field names and codes of the
+/// [`export`](crate::export) module (see [`columns`](crate::export::columns)). Only the fields of the reference are
+/// compared, so it can cover a subset of the fields. Prices are compared up to half a tick (0.00005), and timestamps
+/// can be either RFC 3339 strings or numbers of nanoseconds since the epoch.
+pub fn compare_decodings<M, R>(
+    reader: HistReader<R>,
+    reference: impl BufRead,
+) -> Result<Vec<Difference>, hist::Error>
+where
+    M: Message + ToRecord,
+    R: Read,
+{
+    let mut differences = Vec::new();
+    let mut messages = reader.messages::<M>();
+    let mut lines = reference
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()));
+
+    for index in 0.. {
+        let (message, line) = match (messages.next().transpose()?, lines.next().transpose()?) {
+            (None, None) => break,
+            (message, line) => (message, line),
+        };
+        let (Some(message), Some(line)) = (&message, &line) else {
+            differences.push(Difference {
+                index,
+                field: "message".to_string(),
+                expected: line,
+                actual: message.map(|message| message.kind().name().to_string()),
+            });
+            continue;
+        };
+
+        let record = message.to_record();
+        let fields = parse_json_object(line).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid reference of message {index}: {e}"),
+            )
+        })?;
+        for (field, expected) in fields {
+            let actual = record
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, value)| value);
+            if !actual.is_some_and(|actual| matches_json(actual, &expected)) {
+                differences.push(Difference {
+                    index,
+                    field,
+                    expected: Some(expected.text),
+                    actual: actual.map(Value::to_string),
+                });
+            }
+        }
+    }
+    Ok(differences)
+}
+
+/// A value of a flat JSON object: a string, or the token of a number, boolean or null
+#[derive(Clone, Debug, PartialEq)]
+struct JsonValue {
+    text: String,
+    string: bool,
+}
+
+fn matches_json(actual: &Value, expected: &JsonValue) -> bool {
+    let number = || expected.text.parse::<f64>().ok();
+    match actual {
+        Value::Bool(value) => !expected.string && expected.text == value.to_string(),
+        Value::Int(value) => expected.text.parse::<i64>() == Ok(*value),
+        Value::Price(value) => number().is_some_and(|number| (number - value).abs() < 5e-5),
+        Value::Time(value) if expected.string => {
+            DateTime::parse_from_rfc3339(&expected.text).is_ok_and(|expected| expected == *value)
+        }
+        Value::Time(value) => value
+            .timestamp_nanos_opt()
+            .is_some_and(|nanos| expected.text.parse::<i64>() == Ok(nanos)),
+        Value::Text(value) => *value == expected.text,
+    }
+}
+
+// Parses a JSON object whose values are all strings, numbers, booleans or null
+fn parse_json_object(line: &str) -> Result<Vec<(String, JsonValue)>, String> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = Vec::new();
+    let skip_whitespace = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    };
+
+    if chars.next() != Some('{') {
+        return Err("expected an object".to_string());
+    }
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(fields);
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.next() != Some('"') {
+            return Err("expected a field name".to_string());
+        }
+        let name = parse_json_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(format!("expected a value for {name}"));
+        }
+        skip_whitespace(&mut chars);
+        let value = if chars.next_if_eq(&'"').is_some() {
+            JsonValue {
+                text: parse_json_string(&mut chars)?,
+                string: true,
+            }
+        } else {
+            let mut text = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || "+-.".contains(*c)) {
+                text.push(c);
+            }
+            if text.is_empty() {
+                return Err(format!("unsupported value for {name}"));
+            }
+            JsonValue {
+                text,
+                string: false,
+            }
+        };
+        fields.push((name, value));
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') if chars.next().is_none() => return Ok(fields),
+            _ => return Err("expected a comma or the end of the object".to_string()),
+        }
+    }
+}
+
+// Parses the rest of a string, after its opening quote
+fn parse_json_string(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut string = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(string),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'u' => {
+                    let code = chars.take(4).collect::<String>();
+                    let c = u32::from_str_radix(&code, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or("invalid escape")?;
+                    string.push(c);
+                }
+                c => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::ErrorKind, net::SocketAddrV4};
+
+    use crate::{
+        deep::Deep1_0Message,
+        hist::tests::{capture, segment},
+        live::FeedReceiver,
+        sequence::{SequenceRange, SequenceTracker, Sequencing},
+        spec,
+        tops::Tops1_6Message,
+    };
+
+    use super::*;
+
+    #[test]
+    fn scripted_feed() {
+        let mut receiver = FeedReceiver::bind(
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0),
+            Ipv4Addr::UNSPECIFIED,
+        )
+        .unwrap();
+        receiver.set_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let message = spec::SYSTEM_EVENT.to_vec();
+        let exchange = MockExchange::new(receiver.local_addr().unwrap())
+            .unwrap()
+            .spawn(vec![
+                Action::Send(vec![message.clone(), message.clone()]),
+                Action::Drop(vec![message.clone()]),
+                Action::Advance(TimeDelta::seconds(1)),
+                Action::Heartbeat,
+                Action::Raw(b"garbage".to_vec()),
+                Action::Restart,
+                Action::Sleep(Duration::from_millis(1)),
+                Action::Send(vec![message.clone()]),
+            ]);
+
+        let mut tracker = SequenceTracker::new();
+        let mut received = Vec::new();
+        loop {
+            match receiver.recv_segment() {
+                Ok(segment) => {
+                    received.push((
+                        segment.session_id,
+                        segment.first_message_sequence_no,
+                        segment.stream_offset,
+                        segment.messages.len(),
+                        tracker.update(&segment),
+                    ));
+                    if received.len() == 3 {
+                        break;
+                    }
+                }
+                Err(e) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+            }
+        }
+        let exchange = exchange.join().unwrap().unwrap();
+
+        assert_eq!(received[0], (1, 1, 0, 2, Sequencing::InOrder));
+        let (_, _, _, _, Sequencing::Gap(SequenceRange { first, count, .. })) = received[1] else {
+            panic!("expected a gap, got {:?}", received[1]);
+        };
+        assert_eq!((received[1].1, received[1].2), (4, 36));
+        assert_eq!((first, count), (3, 1));
+        assert_eq!(received[2], (2, 1, 0, 1, Sequencing::InOrder));
+
+        assert_eq!(exchange.session_id(), 2);
+        assert_eq!(exchange.next_sequence_number(), 2);
+        assert_eq!(
+            exchange.send_time(),
+            DateTime::from_timestamp_nanos(1492435801000000000)
+        );
+    }
+
+    #[test]
+    fn differential() {
+        let capture = capture(&[segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[
+                &spec::SYSTEM_EVENT,
+                &spec::QUOTE_UPDATE,
+                &spec::TRADE_REPORT,
+            ],
+        )]);
+        // The values given by the specification
+        let reference = r#"
+            {"kind": "system_event", "timestamp": "2017-04-17T17:00:00Z", "event_type": "E"}
+            {"kind": "quote_update", "timestamp": 1471980632572715948, "symbol": "ZIEXT", "available": true, "bid_size": 9700, "bid_price": 99.05, "ask_size": 1000, "ask_price": 99.07}
+            {"kind": "trade_report", "timestamp": 1471980683662974915, "symbol": "ZIEXT", "size": 100, "price": 99.05, "id": 429974, "odd_lot": false}
+        "#;
+        let compare = |reference: &str| {
+            compare_decodings::<Tops1_6Message<String>, _>(
+                HistReader::new(&capture[..]).unwrap(),
+                reference.as_bytes(),
+            )
+            .unwrap()
+        };
+        assert_eq!(compare(reference), []);
+
+        // As if the bid and ask were swapped, and the last message missing
+        let swapped = reference
+            .replace("\"bid_size\": 9700", "\"bid_size\": 1000")
+            .replace("\"ask_price\": 99.07", "\"ask_price\": 99.05");
+        let (swapped, _) = swapped.rsplit_once("{\"kind\": \"trade_report\"").unwrap();
+        assert_eq!(
+            compare(swapped),
+            [
+                Difference {
+                    index: 1,
+                    field: "bid_size".to_string(),
+                    expected: Some("1000".to_string()),
+                    actual: Some("9700".to_string()),
+                },
+                Difference {
+                    index: 1,
+                    field: "ask_price".to_string(),
+                    expected: Some("99.05".to_string()),
+                    actual: Some("99.0700".to_string()),
+                },
+                Difference {
+                    index: 2,
+                    field: "message".to_string(),
+                    expected: None,
+                    actual: Some("trade_report".to_string()),
+                },
+            ]
+        );
+    }
+
+    // Compares the decodings of official sample captures (`<name>.pcap`) with their reference decodings
+    // (`<name>.jsonl`), found in the directory given by IEX_REFERENCE_DIR
+    #[test]
+    #[ignore = "needs official captures and reference decodings"]
+    fn official_samples() {
+        let directory = std::env::var_os("IEX_REFERENCE_DIR").expect("IEX_REFERENCE_DIR isn't set");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "pcap") {
+                continue;
+            }
+            let reference =
+                io::BufReader::new(std::fs::File::open(path.with_extension("jsonl")).unwrap());
+            let reader = HistReader::open(&path).unwrap();
+            // Named as the HIST files, e.g. 20170417_IEXTP1_DEEP1.0.pcap
+            let differences = if path.to_string_lossy().contains("DEEP") {
+                compare_decodings::<Deep1_0Message<String>, _>(reader, reference)
+            } else {
+                compare_decodings::<Tops1_6Message<String>, _>(reader, reference)
+            }
+            .unwrap();
+            assert_eq!(differences, [], "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn json_objects() {
+        let fields = parse_json_object(r#"{"a": "x\"A", "b":-1.5e3 ,"c":null}"#).unwrap();
+        assert_eq!(
+            fields,
+            [
+                (
+                    "a".to_string(),
+                    JsonValue {
+                        text: "x\"A".to_string(),
+                        string: true
+                    }
+                ),
+                (
+                    "b".to_string(),
+                    JsonValue {
+                        text: "-1.5e3".to_string(),
+                        string: false
+                    }
+                ),
+                (
+                    "c".to_string(),
+                    JsonValue {
+                        text: "null".to_string(),
+                        string: false
+                    }
+                ),
+            ]
+        );
+        assert_eq!(parse_json_object("{}"), Ok(Vec::new()));
+        assert!(parse_json_object(r#"{"a": [1]}"#).is_err());
+        assert!(parse_json_object(r#"{"a": 1} trailing"#).is_err());
+    }
+}
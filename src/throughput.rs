@@ -0,0 +1,195 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{hist::CapturedSegment, iex_tp::IexTp1Segment};
+
+const NANOSECONDS_PER_SECOND: i64 = 1_000_000_000;
+const NANOSECONDS_PER_MILLISECOND: i64 = 1_000_000;
+
+/// The traffic captured during one second
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Second {
+    pub start: DateTime<Utc>,
+    pub segments: u64,
+    pub messages: u64,
+    /// The bytes of the segments, as sent on the wire (without their UDP, IP and link-layer headers)
+    pub bytes: u64,
+    /// The most messages captured within a single millisecond of the second, i.e. the rate of its worst burst
+    pub peak_millisecond: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Bin {
+    segments: u64,
+    messages: u64,
+    bytes: u64,
+    peak_millisecond: u64,
+    /// The millisecond of the last segment, and the messages captured during it so far
+    millisecond: (i64, u64),
+}
+
+/// Bins the traffic of a capture by second of its capture times, for capacity planning: the bursts around the open
+/// and the close are typically many times the average rate
+///
+/// Segments are expected in the order of their capture times, as in HIST files. Out-of-order ones are counted in
+/// their second, but may understate its millisecond peak.
+#[derive(Clone, Debug, Default)]
+pub struct ThroughputProfile {
+    bins: BTreeMap<i64, Bin>,
+}
+
+impl ThroughputProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a segment captured at some time
+    pub fn add_segment(&mut self, time: DateTime<Utc>, segment: &IexTp1Segment<'_>) {
+        let nanoseconds = time.timestamp_nanos_opt().unwrap_or_default();
+        let bin = self
+            .bins
+            .entry(nanoseconds.div_euclid(NANOSECONDS_PER_SECOND))
+            .or_default();
+        let messages = segment.messages.len() as u64;
+        bin.segments += 1;
+        bin.messages += messages;
+        bin.bytes += segment.encoded_len() as u64;
+
+        let millisecond = nanoseconds.div_euclid(NANOSECONDS_PER_MILLISECOND);
+        if bin.millisecond.0 == millisecond {
+            bin.millisecond.1 += messages;
+        } else {
+            bin.millisecond = (millisecond, messages);
+        }
+        bin.peak_millisecond = bin.peak_millisecond.max(bin.millisecond.1);
+    }
+
+    /// Records a segment of a HIST file at the capture time of its packet, or its send time if the capture has no
+    /// timestamps
+    pub fn add_captured(&mut self, captured: &CapturedSegment<'_>) {
+        let time = captured.capture_time.unwrap_or(captured.segment.send_time);
+        self.add_segment(time, &captured.segment);
+    }
+
+    /// The seconds during which segments were captured, in chronological order
+    pub fn seconds(&self) -> impl Iterator<Item = Second> + '_ {
+        self.bins.iter().map(|(&second, bin)| Second {
+            start: DateTime::from_timestamp_nanos(second * NANOSECONDS_PER_SECOND),
+            segments: bin.segments,
+            messages: bin.messages,
+            bytes: bin.bytes,
+            peak_millisecond: bin.peak_millisecond,
+        })
+    }
+
+    /// The seconds with the most messages, busiest first (the earliest first in case of a tie)
+    pub fn busiest(&self, count: usize) -> Vec<Second> {
+        let mut seconds = self.seconds().collect::<Vec<_>>();
+        seconds.sort_by(|a, b| b.messages.cmp(&a.messages).then(a.start.cmp(&b.start)));
+        seconds.truncate(count);
+        seconds
+    }
+
+    /// The number of messages per second below which a share of the seconds fall (e.g. 0.99 for the 99th
+    /// percentile), among the seconds from the first one captured to the last one, idle ones included
+    pub fn messages_quantile(&self, quantile: f64) -> Option<u64> {
+        let first = *self.bins.keys().next()?;
+        let last = *self.bins.keys().next_back()?;
+        let idle = (last - first + 1) as usize - self.bins.len();
+
+        let mut counts = self
+            .bins
+            .values()
+            .map(|bin| bin.messages)
+            .collect::<Vec<_>>();
+        counts.sort_unstable();
+        let total = idle + counts.len();
+        let rank = ((quantile.clamp(0.0, 1.0) * total as f64).ceil() as usize).max(1);
+        Some(if rank <= idle {
+            0
+        } else {
+            counts[rank - idle - 1]
+        })
+    }
+
+    /// Writes the seconds as CSV, with the columns second, segments, messages, bytes and peak_millisecond
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "second,segments,messages,bytes,peak_millisecond")?;
+        for second in self.seconds() {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                second
+                    .start
+                    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                second.segments,
+                second.messages,
+                second.bytes,
+                second.peak_millisecond
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use crate::{
+        hist::tests::segment,
+        iex_tp::{iex_tp_segment, IexTpSegment},
+        message_protocol_ids, spec,
+    };
+
+    use super::*;
+
+    #[test]
+    fn profile() {
+        let encoded = segment(
+            message_protocol_ids::TOPS,
+            1,
+            &[&spec::QUOTE_UPDATE, &spec::TRADE_REPORT],
+        );
+        let (_, IexTpSegment::V1(decoded)) = iex_tp_segment(&encoded).unwrap();
+        let start = DateTime::from_timestamp_nanos(1492448400000000000);
+
+        let mut profile = ThroughputProfile::new();
+        // Three segments within a millisecond, then one in the next millisecond and one 3 seconds later
+        for offset in [0, 100, 200, 1_000, 3_000_000] {
+            profile.add_segment(start + TimeDelta::microseconds(offset), &decoded);
+        }
+
+        let seconds = profile.seconds().collect::<Vec<_>>();
+        assert_eq!(seconds.len(), 2);
+        assert_eq!(
+            seconds[0],
+            Second {
+                start,
+                segments: 4,
+                messages: 8,
+                bytes: 4 * encoded.len() as u64,
+                peak_millisecond: 6,
+            }
+        );
+        assert_eq!(seconds[1].start, start + TimeDelta::seconds(3));
+        assert_eq!(profile.busiest(1), [seconds[0]]);
+
+        // Two of the four seconds were idle
+        assert_eq!(profile.messages_quantile(0.5), Some(0));
+        assert_eq!(profile.messages_quantile(0.75), Some(2));
+        assert_eq!(profile.messages_quantile(1.0), Some(8));
+
+        let mut csv = Vec::new();
+        profile.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv.lines().nth(1),
+            Some(format!("2017-04-17T17:00:00Z,4,8,{},6", 4 * encoded.len()).as_str())
+        );
+    }
+}
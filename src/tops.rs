@@ -1,44 +1,46 @@
+use std::{
+    borrow::Cow,
+    io::{self, ErrorKind, Write},
+};
+
 use chrono::{DateTime, Utc};
 use nom::{
     bits,
     branch::alt,
-    bytes::complete::{tag, take},
-    combinator::{map, value},
+    bytes::complete::tag,
+    combinator::{all_consuming, map, map_res},
     error::Error,
-    number::complete::{le_i64, le_u32},
+    number::complete::{le_i64, le_u32, u8},
     sequence::{tuple, Tuple as _},
     IResult, Parser as _,
 };
 
-use crate::utils::{self, price};
+use crate::{
+    filter::MessageKind,
+    utils::{self, char_code_enum, price, InvalidCode, WithRaw},
+};
 
-#[derive(Clone, Debug)]
-pub enum SystemEventType {
-    StartOfMessages,
-    StartOfSystemHours,
-    StartOfRegularHours,
-    EndOfRegularHours,
-    EndOfSystemHours,
-    EndOfMessages,
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum SystemEventType {
+        StartOfMessages = b'O',
+        StartOfSystemHours = b'S',
+        StartOfRegularHours = b'R',
+        EndOfRegularHours = b'M',
+        EndOfSystemHours = b'E',
+        EndOfMessages = b'C',
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SystemEvent {
     pub event_type: SystemEventType,
     pub timestamp: DateTime<Utc>,
 }
 
-fn system_event(input: &[u8]) -> IResult<&[u8], SystemEvent> {
+pub(crate) fn system_event(input: &[u8]) -> IResult<&[u8], SystemEvent> {
     let (input, _) = tag([0x53]).parse(input)?;
-    let (input, event_type) = alt((
-        value(SystemEventType::StartOfMessages, tag([0x4f])),
-        value(SystemEventType::StartOfSystemHours, tag([0x53])),
-        value(SystemEventType::StartOfRegularHours, tag([0x52])),
-        value(SystemEventType::EndOfRegularHours, tag([0x4d])),
-        value(SystemEventType::EndOfSystemHours, tag([0x45])),
-        value(SystemEventType::EndOfMessages, tag([0x43])),
-    ))
-    .parse(input)?;
+    let (input, event_type) = map_res(u8, SystemEventType::try_from).parse(input)?;
     let (input, timestamp) = utils::timestamp.parse(input)?;
 
     Ok((
@@ -50,17 +52,42 @@ fn system_event(input: &[u8]) -> IResult<&[u8], SystemEvent> {
     ))
 }
 
-#[derive(Clone, Debug)]
+impl SystemEvent {
+    /// Parses a System Event message, returning the remaining input alongside it
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        system_event(input)
+    }
+
+    /// Writes the message as it's sent on the wire
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x53, self.event_type.code()])?;
+        utils::write_timestamp(writer, self.timestamp)
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`SystemEvent::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for SystemEvent {
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(system_event)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MarketSession {
     Regular,
     OutOfHours,
 }
 
-#[derive(Clone, Debug)]
-pub struct QuoteUpdate<S>
-where
-    S: for<'a> From<&'a str>,
-{
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuoteUpdate<S> {
     pub available: bool,
     pub market_session: MarketSession,
     pub timestamp: DateTime<Utc>,
@@ -71,9 +98,9 @@ where
     pub ask_price: f64,
 }
 
-fn quote_update<S>(input: &[u8]) -> IResult<&[u8], QuoteUpdate<S>>
+fn quote_update<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], QuoteUpdate<S>>
 where
-    S: for<'a> From<&'a str>,
+    S: TryFrom<&'a str>,
 {
     let (input, _) = tag([0x51]).parse(input)?;
     let (input, (availability, market_session, _)): (&[u8], (bool, bool, u8)) =
@@ -84,7 +111,7 @@ where
         )))
         .parse(input)?;
     let (input, timestamp) = utils::timestamp.parse(input)?;
-    let (input, symbol) = utils::iex_string(8).parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
     let (input, (bid_size, bid_price)) = (le_u32, price).parse(input)?;
     let (input, (ask_price, ask_size)) = (price, le_u32).parse(input)?;
 
@@ -98,7 +125,7 @@ where
                 MarketSession::Regular
             },
             timestamp,
-            symbol: symbol.into(),
+            symbol,
             bid_size,
             bid_price,
             ask_size,
@@ -107,7 +134,71 @@ where
     ))
 }
 
-#[derive(Clone, Debug)]
+impl<S> QuoteUpdate<S> {
+    /// Parses a Quote Update message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        quote_update(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> QuoteUpdate<T> {
+        QuoteUpdate {
+            available: self.available,
+            market_session: self.market_session,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            bid_size: self.bid_size,
+            bid_price: self.bid_price,
+            ask_size: self.ask_size,
+            ask_price: self.ask_price,
+        }
+    }
+}
+
+impl QuoteUpdate<Cow<'_, str>> {
+    /// Detaches the message from the buffer it was parsed from, allocating only if the symbol is still borrowed
+    pub fn into_owned(self) -> QuoteUpdate<Cow<'static, str>> {
+        self.map_symbol(|symbol| Cow::Owned(symbol.into_owned()))
+    }
+}
+
+impl<S: AsRef<str>> QuoteUpdate<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let flags = u8::from(!self.available) << 7
+            | u8::from(self.market_session == MarketSession::OutOfHours) << 6;
+        writer.write_all(&[0x51, flags])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        writer.write_all(&self.bid_size.to_le_bytes())?;
+        utils::write_price(writer, self.bid_price)?;
+        utils::write_price(writer, self.ask_price)?;
+        writer.write_all(&self.ask_size.to_le_bytes())
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`QuoteUpdate::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for QuoteUpdate<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(quote_update)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct SaleCondition {
     pub intermarket_sweep: bool,
     pub extended_hours: bool,
@@ -116,20 +207,32 @@ pub struct SaleCondition {
     pub single_price: bool,
 }
 
+impl SaleCondition {
+    // The flags as they're sent on the wire
+    fn bits(&self) -> u8 {
+        u8::from(self.intermarket_sweep) << 7
+            | u8::from(self.extended_hours) << 6
+            | u8::from(self.odd_lot) << 5
+            | u8::from(self.trade_through_exempt) << 4
+            | u8::from(self.single_price) << 3
+    }
+}
+
+type SaleConditionBits = (bool, bool, bool, bool, bool, u8);
+
 fn sale_condition(input: &[u8]) -> IResult<&[u8], SaleCondition> {
     let (
         input,
         (intermarket_sweep, extended_hours, odd_lot, trade_through_exempt, single_price, _),
-    ): (&[u8], (bool, bool, bool, bool, bool, u8)) =
-        bits::<_, _, Error<(&[u8], usize)>, _, _>(tuple((
-            nom::bits::complete::bool,
-            nom::bits::complete::bool,
-            nom::bits::complete::bool,
-            nom::bits::complete::bool,
-            nom::bits::complete::bool,
-            nom::bits::complete::tag(0u8, 3usize),
-        )))
-        .parse(input)?;
+    ): (&[u8], SaleConditionBits) = bits::<_, _, Error<(&[u8], usize)>, _, _>(tuple((
+        nom::bits::complete::bool,
+        nom::bits::complete::bool,
+        nom::bits::complete::bool,
+        nom::bits::complete::bool,
+        nom::bits::complete::bool,
+        nom::bits::complete::tag(0u8, 3usize),
+    )))
+    .parse(input)?;
 
     Ok((
         input,
@@ -143,11 +246,8 @@ fn sale_condition(input: &[u8]) -> IResult<&[u8], SaleCondition> {
     ))
 }
 
-#[derive(Clone, Debug)]
-pub struct TradeReport<S>
-where
-    S: for<'a> From<&'a str>,
-{
+#[derive(Clone, Debug, PartialEq)]
+pub struct TradeReport<S> {
     pub sale_condition: SaleCondition,
     pub timestamp: DateTime<Utc>,
     pub symbol: S,
@@ -156,14 +256,14 @@ where
     pub id: i64,
 }
 
-fn trade_report<S>(input: &[u8]) -> IResult<&[u8], TradeReport<S>>
+pub(crate) fn trade_report<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], TradeReport<S>>
 where
-    S: for<'a> From<&'a str>,
+    S: TryFrom<&'a str>,
 {
     let (input, _) = tag([0x54]).parse(input)?;
     let (input, sale_condition) = sale_condition.parse(input)?;
     let (input, timestamp) = utils::timestamp.parse(input)?;
-    let (input, symbol) = utils::iex_string(8).parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
     let (input, size) = le_u32.parse(input)?;
     let (input, price) = price.parse(input)?;
     let (input, id) = le_i64.parse(input)?;
@@ -173,7 +273,7 @@ where
         TradeReport {
             sale_condition,
             timestamp,
-            symbol: symbol.into(),
+            symbol,
             size,
             price,
             id,
@@ -181,184 +281,1640 @@ where
     ))
 }
 
-// Handle known yet unimplemented message types
-macro_rules! dummy_message_parser {
-    ($tag:expr, $len:expr, $msg_type:ident) => {
-        fn $msg_type(input: &[u8]) -> IResult<&[u8], ()> {
-            let (input, _) = tag($tag).parse(input)?;
-            let (input, _) = take($len).parse(input)?;
-            Ok((input, ()))
+impl<S> TradeReport<S> {
+    /// Parses a Trade Report message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        trade_report(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> TradeReport<T> {
+        TradeReport {
+            sale_condition: self.sale_condition,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            size: self.size,
+            price: self.price,
+            id: self.id,
         }
-    };
+    }
+}
+
+impl TradeReport<Cow<'_, str>> {
+    /// Detaches the message from the buffer it was parsed from, allocating only if the symbol is still borrowed
+    pub fn into_owned(self) -> TradeReport<Cow<'static, str>> {
+        self.map_symbol(|symbol| Cow::Owned(symbol.into_owned()))
+    }
 }
 
-dummy_message_parser!([0x44], 30usize, security_directory);
-dummy_message_parser!([0x48], 21usize, trading_status);
-dummy_message_parser!([0x49], 17usize, retail_liquidity_indicator);
-dummy_message_parser!([0x4f], 17usize, operational_halt_status);
-dummy_message_parser!([0x50], 18usize, short_sale_price_test_status);
-dummy_message_parser!([0x58], 25usize, official_price);
-dummy_message_parser!([0x42], 37usize, trade_break);
-dummy_message_parser!([0x41], 79usize, auction_information);
+impl<S: AsRef<str>> TradeReport<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x54, self.sale_condition.bits()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        utils::write_price(writer, self.price)?;
+        writer.write_all(&self.id.to_le_bytes())
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`TradeReport::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
 
-#[derive(Clone, Debug)]
-pub enum Tops1_6Message<S>
+impl<'a, S> TryFrom<&'a [u8]> for TradeReport<S>
 where
-    S: for<'a> From<&'a str>,
+    S: TryFrom<&'a str>,
 {
-    SystemEvent(SystemEvent),
-    SecurityDirectory,
-    TradingStatus,
-    RetailLiquidityIndicator,
-    OperationalHaltStatus,
-    ShortSalePriceTestStatus,
-    QuoteUpdate(QuoteUpdate<S>),
-    TradeReport(TradeReport<S>),
-    OfficialPrice,
-    TradeBreak,
-    AuctionInformation,
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(trade_report)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum OfficialPriceType {
+        OpeningPrice = b'Q',
+        ClosingPrice = b'M',
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfficialPrice<S> {
+    pub price_type: OfficialPriceType,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    pub official_price: f64,
 }
 
-pub fn tops_1_6_message<S>(input: &[u8]) -> IResult<&[u8], Tops1_6Message<S>>
+pub(crate) fn official_price<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], OfficialPrice<S>>
 where
-    S: for<'a> From<&'a str>,
+    S: TryFrom<&'a str>,
 {
-    alt((
-        map(system_event, Tops1_6Message::SystemEvent),
-        map(security_directory, |_| Tops1_6Message::SecurityDirectory),
-        map(trading_status, |_| Tops1_6Message::TradingStatus),
-        map(retail_liquidity_indicator, |_| {
-            Tops1_6Message::RetailLiquidityIndicator
-        }),
-        map(operational_halt_status, |_| {
-            Tops1_6Message::OperationalHaltStatus
-        }),
-        map(short_sale_price_test_status, |_| {
-            Tops1_6Message::ShortSalePriceTestStatus
-        }),
-        map(quote_update::<S>, Tops1_6Message::QuoteUpdate),
-        map(trade_report::<S>, Tops1_6Message::TradeReport),
-        map(official_price, |_| Tops1_6Message::OfficialPrice),
-        map(trade_break, |_| Tops1_6Message::TradeBreak),
-        map(auction_information, |_| Tops1_6Message::AuctionInformation),
+    let (input, _) = tag([0x58]).parse(input)?;
+    let (input, price_type) = map_res(u8, OfficialPriceType::try_from).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, official_price) = price.parse(input)?;
+
+    Ok((
+        input,
+        OfficialPrice {
+            price_type,
+            timestamp,
+            symbol,
+            official_price,
+        },
     ))
-    .parse(input)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::assert_matches::assert_matches;
+impl<S> OfficialPrice<S> {
+    /// Parses an Official Price message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        official_price(input)
+    }
 
-    use float_eq::assert_float_eq;
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> OfficialPrice<T> {
+        OfficialPrice {
+            price_type: self.price_type,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            official_price: self.official_price,
+        }
+    }
+}
 
-    use super::*;
+impl<S: AsRef<str>> OfficialPrice<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x58, self.price_type.code()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        utils::write_price(writer, self.official_price)
+    }
 
-    #[test]
-    fn quote_update_example() {
-        let input: [u8; 42] = [
-            0x51, 0x00, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
-            0x54, 0x20, 0x20, 0x20, 0xE4, 0x25, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0xEC, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE8, 0x03, 0x00, 0x00,
-        ];
-        let result = tops_1_6_message::<String>(&input).unwrap();
+    /// Encodes the message as it's sent on the wire, see [`OfficialPrice::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
 
-        assert_matches!(
-            result,
-            (
-                [],
-                Tops1_6Message::QuoteUpdate(QuoteUpdate {
-                    available: true,
-                    market_session: MarketSession::Regular,
-                    timestamp: _,
-                    symbol: _,
-                    bid_size: 9700,
-                    bid_price: _,
-                    ask_size: 1000,
-                    ask_price: _,
-                })
-            )
-        );
+impl<'a, S> TryFrom<&'a [u8]> for OfficialPrice<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
 
-        if let Tops1_6Message::QuoteUpdate(inner_result) = result.1 {
-            assert_eq!(inner_result.symbol, "ZIEXT");
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(official_price)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
 
-            assert_eq!(
-                inner_result.timestamp,
-                DateTime::from_timestamp_nanos(1471980632572715948)
-            );
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum TradingStatusType {
+        /// Trading halted across all US equity markets
+        Halted = b'H',
+        /// Halt released into an order acceptance period on IEX (IEX-listed securities only)
+        OrderAcceptancePeriod = b'O',
+        /// Trading paused and order acceptance period on IEX (IEX-listed securities only)
+        Paused = b'P',
+        /// Trading on IEX
+        Trading = b'T',
+    }
+}
 
-            assert_float_eq!(inner_result.bid_price, 99.05, ulps <= 5);
-            assert_float_eq!(inner_result.ask_price, 99.07, ulps <= 5);
-        } else {
-            unreachable!()
+/// The reason of a trading halt or pause
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TradingStatusReason {
+    HaltNewsPending,
+    IpoNotYetTrading,
+    IpoDeferred,
+    MarketWideCircuitBreakerLevel3,
+    NotApplicable,
+    HaltNewsDisseminated,
+    IpoOrderAcceptancePeriod,
+    IpoPreLaunchPeriod,
+    MarketWideCircuitBreakerLevel1,
+    MarketWideCircuitBreakerLevel2,
+    /// A reason code this version of the crate doesn't know, space-padded
+    Other([u8; 4]),
+}
+
+impl TradingStatusReason {
+    /// Returns the code identifying the reason in the specification
+    pub fn code(&self) -> &str {
+        match self {
+            Self::HaltNewsPending => "T1",
+            Self::IpoNotYetTrading => "IPO1",
+            Self::IpoDeferred => "IPOD",
+            Self::MarketWideCircuitBreakerLevel3 => "MCB3",
+            Self::NotApplicable => "NA",
+            Self::HaltNewsDisseminated => "T2",
+            Self::IpoOrderAcceptancePeriod => "IPO2",
+            Self::IpoPreLaunchPeriod => "IPO3",
+            Self::MarketWideCircuitBreakerLevel1 => "MCB1",
+            Self::MarketWideCircuitBreakerLevel2 => "MCB2",
+            Self::Other(code) => std::str::from_utf8(code).unwrap_or("").trim_end(),
         }
     }
+}
 
-    #[test]
-    fn trade_report_example() {
-        let input: [u8; 38] = [
-            0x54, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
-            0x54, 0x20, 0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x96, 0x8F, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
-        ];
-        let result = tops_1_6_message::<String>(&input).unwrap();
+impl std::str::FromStr for TradingStatusReason {
+    type Err = utils::InvalidCode;
 
-        assert_matches!(
-            result,
-            (
-                [],
-                Tops1_6Message::TradeReport(TradeReport {
-                    sale_condition: SaleCondition {
-                        intermarket_sweep: false,
-                        extended_hours: false,
-                        odd_lot: false,
-                        trade_through_exempt: false,
-                        single_price: false
-                    },
-                    timestamp: _,
-                    symbol: _,
-                    size: 100,
-                    price: _,
-                    id: 429_974,
-                })
-            )
-        );
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "T1" => Self::HaltNewsPending,
+            "IPO1" => Self::IpoNotYetTrading,
+            "IPOD" => Self::IpoDeferred,
+            "MCB3" => Self::MarketWideCircuitBreakerLevel3,
+            "NA" => Self::NotApplicable,
+            "T2" => Self::HaltNewsDisseminated,
+            "IPO2" => Self::IpoOrderAcceptancePeriod,
+            "IPO3" => Self::IpoPreLaunchPeriod,
+            "MCB1" => Self::MarketWideCircuitBreakerLevel1,
+            "MCB2" => Self::MarketWideCircuitBreakerLevel2,
+            _ if s.is_ascii() && s.len() <= 4 => {
+                let mut code = [b' '; 4];
+                code[..s.len()].copy_from_slice(s.as_bytes());
+                Self::Other(code)
+            }
+            _ => return Err(utils::InvalidCode(s.to_string())),
+        })
+    }
+}
 
-        if let Tops1_6Message::TradeReport(inner_result) = result.1 {
-            assert_eq!(
-                inner_result.timestamp,
-                DateTime::from_timestamp_nanos(1471980683662974915)
-            );
+impl std::fmt::Display for TradingStatusReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
 
-            assert_eq!(inner_result.symbol, "ZIEXT");
+#[derive(Clone, Debug, PartialEq)]
+pub struct TradingStatus<S> {
+    pub status: TradingStatusType,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    pub reason: TradingStatusReason,
+}
 
-            assert_float_eq!(inner_result.price, 99.05, ulps <= 5);
-        } else {
-            unreachable!()
+pub(crate) fn trading_status<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], TradingStatus<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x48]).parse(input)?;
+    let (input, status) = map_res(u8, TradingStatusType::try_from).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, reason) = map_res(utils::iex_string(4), str::parse).parse(input)?;
+
+    Ok((
+        input,
+        TradingStatus {
+            status,
+            timestamp,
+            symbol,
+            reason,
+        },
+    ))
+}
+
+impl<S> TradingStatus<S> {
+    /// Parses a Trading Status message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        trading_status(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> TradingStatus<T> {
+        TradingStatus {
+            status: self.status,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            reason: self.reason,
         }
     }
+}
 
-    #[test]
-    fn system_event_message() {
-        let input: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
-        let result = tops_1_6_message::<String>(&input).unwrap();
+impl<S: AsRef<str>> TradingStatus<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x48, self.status.code()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        utils::write_iex_string(writer, self.reason.code(), 4)
+    }
 
-        assert_matches!(
-            result,
-            (
-                [],
-                Tops1_6Message::SystemEvent(SystemEvent {
-                    event_type: SystemEventType::EndOfSystemHours,
-                    timestamp: _,
-                })
-            )
-        );
+    /// Encodes the message as it's sent on the wire, see [`TradingStatus::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
 
-        if let Tops1_6Message::SystemEvent(inner_result) = result.1 {
-            assert_eq!(
-                inner_result.timestamp,
-                DateTime::from_timestamp_nanos(1492448400000000000)
+impl<'a, S> TryFrom<&'a [u8]> for TradingStatus<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(trading_status)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum OperationalHaltStatusType {
+        /// IEX specific operational trading halt
+        Halted = b'O',
+        NotHalted = b'N',
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationalHaltStatus<S> {
+    pub status: OperationalHaltStatusType,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+}
+
+pub(crate) fn operational_halt_status<'a, S>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], OperationalHaltStatus<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x4f]).parse(input)?;
+    let (input, status) = map_res(u8, OperationalHaltStatusType::try_from).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+
+    Ok((
+        input,
+        OperationalHaltStatus {
+            status,
+            timestamp,
+            symbol,
+        },
+    ))
+}
+
+impl<S> OperationalHaltStatus<S> {
+    /// Parses an Operational Halt Status message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        operational_halt_status(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> OperationalHaltStatus<T> {
+        OperationalHaltStatus {
+            status: self.status,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+        }
+    }
+}
+
+impl<S: AsRef<str>> OperationalHaltStatus<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x4f, self.status.code()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`OperationalHaltStatus::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for OperationalHaltStatus<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(operational_halt_status)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum AuctionType {
+        Opening = b'O',
+        Closing = b'C',
+        Ipo = b'I',
+        Halt = b'H',
+        Volatility = b'V',
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum ImbalanceSide {
+        Buy = b'B',
+        Sell = b'S',
+        NoImbalance = b'N',
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuctionInformation<S> {
+    pub auction_type: AuctionType,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    pub paired_shares: u32,
+    pub reference_price: f64,
+    pub indicative_clearing_price: f64,
+    pub imbalance_shares: u32,
+    pub imbalance_side: ImbalanceSide,
+    pub extension_number: u8,
+    pub scheduled_auction_time: DateTime<Utc>,
+    pub auction_book_clearing_price: f64,
+    pub collar_reference_price: f64,
+    pub lower_auction_collar: f64,
+    pub upper_auction_collar: f64,
+}
+
+pub(crate) fn auction_information<'a, S>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], AuctionInformation<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x41]).parse(input)?;
+    let (input, auction_type) = map_res(u8, AuctionType::try_from).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, paired_shares) = le_u32.parse(input)?;
+    let (input, reference_price) = price.parse(input)?;
+    let (input, indicative_clearing_price) = price.parse(input)?;
+    let (input, imbalance_shares) = le_u32.parse(input)?;
+    let (input, imbalance_side) = map_res(u8, ImbalanceSide::try_from).parse(input)?;
+    let (input, extension_number) = u8.parse(input)?;
+    let (input, scheduled_auction_time) = le_u32.parse(input)?;
+    let (input, auction_book_clearing_price) = price.parse(input)?;
+    let (input, collar_reference_price) = price.parse(input)?;
+    let (input, lower_auction_collar) = price.parse(input)?;
+    let (input, upper_auction_collar) = price.parse(input)?;
+
+    Ok((
+        input,
+        AuctionInformation {
+            auction_type,
+            timestamp,
+            symbol,
+            paired_shares,
+            reference_price,
+            indicative_clearing_price,
+            imbalance_shares,
+            imbalance_side,
+            extension_number,
+            scheduled_auction_time: DateTime::from_timestamp(scheduled_auction_time.into(), 0)
+                .unwrap_or_default(),
+            auction_book_clearing_price,
+            collar_reference_price,
+            lower_auction_collar,
+            upper_auction_collar,
+        },
+    ))
+}
+
+impl<S> AuctionInformation<S> {
+    pub(crate) fn round_prices(&mut self) {
+        for price in [
+            &mut self.reference_price,
+            &mut self.indicative_clearing_price,
+            &mut self.auction_book_clearing_price,
+            &mut self.collar_reference_price,
+            &mut self.lower_auction_collar,
+            &mut self.upper_auction_collar,
+        ] {
+            utils::round_price(price);
+        }
+    }
+
+    /// Parses an Auction Information message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        auction_information(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> AuctionInformation<T> {
+        AuctionInformation {
+            auction_type: self.auction_type,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            paired_shares: self.paired_shares,
+            reference_price: self.reference_price,
+            indicative_clearing_price: self.indicative_clearing_price,
+            imbalance_shares: self.imbalance_shares,
+            imbalance_side: self.imbalance_side,
+            extension_number: self.extension_number,
+            scheduled_auction_time: self.scheduled_auction_time,
+            auction_book_clearing_price: self.auction_book_clearing_price,
+            collar_reference_price: self.collar_reference_price,
+            lower_auction_collar: self.lower_auction_collar,
+            upper_auction_collar: self.upper_auction_collar,
+        }
+    }
+}
+
+impl<S: AsRef<str>> AuctionInformation<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let scheduled_auction_time = u32::try_from(self.scheduled_auction_time.timestamp())
+            .map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "scheduled auction time out of range",
+                )
+            })?;
+
+        writer.write_all(&[0x41, self.auction_type.code()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        writer.write_all(&self.paired_shares.to_le_bytes())?;
+        utils::write_price(writer, self.reference_price)?;
+        utils::write_price(writer, self.indicative_clearing_price)?;
+        writer.write_all(&self.imbalance_shares.to_le_bytes())?;
+        writer.write_all(&[self.imbalance_side.code(), self.extension_number])?;
+        writer.write_all(&scheduled_auction_time.to_le_bytes())?;
+        utils::write_price(writer, self.auction_book_clearing_price)?;
+        utils::write_price(writer, self.collar_reference_price)?;
+        utils::write_price(writer, self.lower_auction_collar)?;
+        utils::write_price(writer, self.upper_auction_collar)
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`AuctionInformation::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for AuctionInformation<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(auction_information)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum ShortSalePriceTestDetail {
+        NoPriceTest = b' ',
+        /// Activated by a price drop of the security
+        Activated = b'A',
+        /// Continued from the previous trading day
+        Continued = b'C',
+        Deactivated = b'D',
+        NotAvailable = b'N',
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShortSalePriceTestStatus<S> {
+    /// Whether the short sale price test (Reg. SHO Rule 201) is in effect
+    pub in_effect: bool,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    pub detail: ShortSalePriceTestDetail,
+}
+
+pub(crate) fn short_sale_price_test_status<'a, S>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], ShortSalePriceTestStatus<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x50]).parse(input)?;
+    let (input, in_effect) = map_res(u8, |status| match status {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(()),
+    })
+    .parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, detail) = map_res(u8, ShortSalePriceTestDetail::try_from).parse(input)?;
+
+    Ok((
+        input,
+        ShortSalePriceTestStatus {
+            in_effect,
+            timestamp,
+            symbol,
+            detail,
+        },
+    ))
+}
+
+impl<S> ShortSalePriceTestStatus<S> {
+    /// Parses a Short Sale Price Test Status message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        short_sale_price_test_status(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> ShortSalePriceTestStatus<T> {
+        ShortSalePriceTestStatus {
+            in_effect: self.in_effect,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            detail: self.detail,
+        }
+    }
+}
+
+impl<S: AsRef<str>> ShortSalePriceTestStatus<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x50, u8::from(self.in_effect)])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        writer.write_all(&[self.detail.code()])
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`ShortSalePriceTestStatus::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for ShortSalePriceTestStatus<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(short_sale_price_test_status)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+/// Sent when an execution on IEX is broken on the same trading day, referencing the original Trade Report
+#[derive(Clone, Debug, PartialEq)]
+pub struct TradeBreak<S> {
+    pub sale_condition: SaleCondition,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    pub size: u32,
+    pub price: f64,
+    /// The ID of the broken trade
+    pub id: i64,
+}
+
+pub(crate) fn trade_break<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], TradeBreak<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x42]).parse(input)?;
+    let (input, sale_condition) = sale_condition.parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, size) = le_u32.parse(input)?;
+    let (input, price) = price.parse(input)?;
+    let (input, id) = le_i64.parse(input)?;
+
+    Ok((
+        input,
+        TradeBreak {
+            sale_condition,
+            timestamp,
+            symbol,
+            size,
+            price,
+            id,
+        },
+    ))
+}
+
+impl<S> TradeBreak<S> {
+    /// Parses a Trade Break message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        trade_break(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> TradeBreak<T> {
+        TradeBreak {
+            sale_condition: self.sale_condition,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            size: self.size,
+            price: self.price,
+            id: self.id,
+        }
+    }
+}
+
+impl<S: AsRef<str>> TradeBreak<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x42, self.sale_condition.bits()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        writer.write_all(&self.size.to_le_bytes())?;
+        utils::write_price(writer, self.price)?;
+        writer.write_all(&self.id.to_le_bytes())
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`TradeBreak::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for TradeBreak<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(trade_break)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SecurityDirectoryFlags {
+    pub test_security: bool,
+    /// When issued security
+    pub when_issued: bool,
+    /// Exchange traded product
+    pub etp: bool,
+}
+
+type SecurityDirectoryFlagBits = (bool, bool, bool, u8);
+
+fn security_directory_flags(input: &[u8]) -> IResult<&[u8], SecurityDirectoryFlags> {
+    let (input, (test_security, when_issued, etp, _)): (&[u8], SecurityDirectoryFlagBits) =
+        bits::<_, _, Error<(&[u8], usize)>, _, _>(tuple((
+            nom::bits::complete::bool,
+            nom::bits::complete::bool,
+            nom::bits::complete::bool,
+            nom::bits::complete::tag(0u8, 5usize),
+        )))
+        .parse(input)?;
+
+    Ok((
+        input,
+        SecurityDirectoryFlags {
+            test_security,
+            when_issued,
+            etp,
+        },
+    ))
+}
+
+/// The Limit Up-Limit Down tier of a security
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LuldTier {
+    NotApplicable = 0,
+    /// Tier 1 NMS Stock
+    Tier1 = 1,
+    /// Tier 2 NMS Stock
+    Tier2 = 2,
+}
+
+impl TryFrom<u8> for LuldTier {
+    type Error = InvalidCode;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        match code {
+            0 => Ok(LuldTier::NotApplicable),
+            1 => Ok(LuldTier::Tier1),
+            2 => Ok(LuldTier::Tier2),
+            _ => Err(InvalidCode(code.to_string())),
+        }
+    }
+}
+
+impl From<LuldTier> for u8 {
+    fn from(tier: LuldTier) -> Self {
+        tier as u8
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecurityDirectory<S> {
+    pub flags: SecurityDirectoryFlags,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+    /// The number of shares that represent a round lot
+    pub round_lot_size: u32,
+    /// The corporate action adjusted previous official closing price
+    pub adjusted_poc_price: f64,
+    pub luld_tier: LuldTier,
+}
+
+pub(crate) fn security_directory<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], SecurityDirectory<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x44]).parse(input)?;
+    let (input, flags) = security_directory_flags.parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+    let (input, round_lot_size) = le_u32.parse(input)?;
+    let (input, adjusted_poc_price) = price.parse(input)?;
+    let (input, luld_tier) = map_res(u8, LuldTier::try_from).parse(input)?;
+
+    Ok((
+        input,
+        SecurityDirectory {
+            flags,
+            timestamp,
+            symbol,
+            round_lot_size,
+            adjusted_poc_price,
+            luld_tier,
+        },
+    ))
+}
+
+impl<S> SecurityDirectory<S> {
+    /// Parses a Security Directory message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        security_directory(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> SecurityDirectory<T> {
+        SecurityDirectory {
+            flags: self.flags,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+            round_lot_size: self.round_lot_size,
+            adjusted_poc_price: self.adjusted_poc_price,
+            luld_tier: self.luld_tier,
+        }
+    }
+}
+
+impl<S: AsRef<str>> SecurityDirectory<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let flags = u8::from(self.flags.test_security) << 7
+            | u8::from(self.flags.when_issued) << 6
+            | u8::from(self.flags.etp) << 5;
+        writer.write_all(&[0x44, flags])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())?;
+        writer.write_all(&self.round_lot_size.to_le_bytes())?;
+        utils::write_price(writer, self.adjusted_poc_price)?;
+        writer.write_all(&[self.luld_tier as u8])
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`SecurityDirectory::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for SecurityDirectory<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(security_directory)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+char_code_enum! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum RetailLiquidityIndicatorType {
+        NotApplicable = b' ',
+        /// Buy interest for Retail Price Improvement orders
+        BuyInterest = b'A',
+        /// Sell interest for Retail Price Improvement orders
+        SellInterest = b'B',
+        /// Buy and sell interest for Retail Price Improvement orders
+        BuyAndSellInterest = b'C',
+    }
+}
+
+/// Sent when the retail liquidity resting on the book of a symbol changes
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetailLiquidityIndicator<S> {
+    pub indicator: RetailLiquidityIndicatorType,
+    pub timestamp: DateTime<Utc>,
+    pub symbol: S,
+}
+
+pub(crate) fn retail_liquidity_indicator<'a, S>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], RetailLiquidityIndicator<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    let (input, _) = tag([0x49]).parse(input)?;
+    let (input, indicator) = map_res(u8, RetailLiquidityIndicatorType::try_from).parse(input)?;
+    let (input, timestamp) = utils::timestamp.parse(input)?;
+    let (input, symbol) = utils::symbol.parse(input)?;
+
+    Ok((
+        input,
+        RetailLiquidityIndicator {
+            indicator,
+            timestamp,
+            symbol,
+        },
+    ))
+}
+
+impl<S> RetailLiquidityIndicator<S> {
+    /// Parses a Retail Liquidity Indicator message, returning the remaining input alongside it
+    pub fn parse<'a>(input: &'a [u8]) -> IResult<&'a [u8], Self>
+    where
+        S: TryFrom<&'a str>,
+    {
+        retail_liquidity_indicator(input)
+    }
+
+    /// Converts the symbol to another type, keeping all other fields
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> RetailLiquidityIndicator<T> {
+        RetailLiquidityIndicator {
+            indicator: self.indicator,
+            timestamp: self.timestamp,
+            symbol: f(self.symbol),
+        }
+    }
+}
+
+impl<S: AsRef<str>> RetailLiquidityIndicator<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[0x49, self.indicator.code()])?;
+        utils::write_timestamp(writer, self.timestamp)?;
+        utils::write_symbol(writer, self.symbol.as_ref())
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`RetailLiquidityIndicator::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for RetailLiquidityIndicator<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(retail_liquidity_indicator)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tops1_6Message<S> {
+    SystemEvent(SystemEvent),
+    SecurityDirectory(SecurityDirectory<S>),
+    TradingStatus(TradingStatus<S>),
+    RetailLiquidityIndicator(RetailLiquidityIndicator<S>),
+    OperationalHaltStatus(OperationalHaltStatus<S>),
+    ShortSalePriceTestStatus(ShortSalePriceTestStatus<S>),
+    QuoteUpdate(QuoteUpdate<S>),
+    TradeReport(TradeReport<S>),
+    OfficialPrice(OfficialPrice<S>),
+    TradeBreak(TradeBreak<S>),
+    AuctionInformation(AuctionInformation<S>),
+}
+
+pub fn tops_1_6_message<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], Tops1_6Message<S>>
+where
+    S: TryFrom<&'a str>,
+{
+    alt((
+        map(system_event, Tops1_6Message::SystemEvent),
+        map(security_directory::<S>, Tops1_6Message::SecurityDirectory),
+        map(trading_status::<S>, Tops1_6Message::TradingStatus),
+        map(
+            retail_liquidity_indicator::<S>,
+            Tops1_6Message::RetailLiquidityIndicator,
+        ),
+        map(
+            operational_halt_status::<S>,
+            Tops1_6Message::OperationalHaltStatus,
+        ),
+        map(
+            short_sale_price_test_status::<S>,
+            Tops1_6Message::ShortSalePriceTestStatus,
+        ),
+        map(quote_update::<S>, Tops1_6Message::QuoteUpdate),
+        map(trade_report::<S>, Tops1_6Message::TradeReport),
+        map(official_price::<S>, Tops1_6Message::OfficialPrice),
+        map(trade_break::<S>, Tops1_6Message::TradeBreak),
+        map(auction_information::<S>, Tops1_6Message::AuctionInformation),
+    ))
+    .parse(input)
+}
+
+/// Parses a TOPS 1.6 message like [`tops_1_6_message`], keeping a reference to the bytes it was decoded from (e.g.
+/// for re-emitting or archiving the original message)
+pub fn tops_1_6_message_with_raw<'a, S>(
+    input: &'a [u8],
+) -> IResult<&'a [u8], WithRaw<'a, Tops1_6Message<S>>>
+where
+    S: TryFrom<&'a str>,
+{
+    utils::with_raw(tops_1_6_message).parse(input)
+}
+
+impl<S> Tops1_6Message<S> {
+    /// The timestamp of the message, which all TOPS messages carry (unlike some DEEP messages, see
+    /// [`Deep1_0Message::timestamp`](crate::deep::Deep1_0Message::timestamp))
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Tops1_6Message::SystemEvent(message) => Some(message.timestamp),
+            Tops1_6Message::SecurityDirectory(message) => Some(message.timestamp),
+            Tops1_6Message::TradingStatus(message) => Some(message.timestamp),
+            Tops1_6Message::RetailLiquidityIndicator(message) => Some(message.timestamp),
+            Tops1_6Message::OperationalHaltStatus(message) => Some(message.timestamp),
+            Tops1_6Message::ShortSalePriceTestStatus(message) => Some(message.timestamp),
+            Tops1_6Message::QuoteUpdate(message) => Some(message.timestamp),
+            Tops1_6Message::TradeReport(message) => Some(message.timestamp),
+            Tops1_6Message::OfficialPrice(message) => Some(message.timestamp),
+            Tops1_6Message::TradeBreak(message) => Some(message.timestamp),
+            Tops1_6Message::AuctionInformation(message) => Some(message.timestamp),
+        }
+    }
+
+    /// The type of the message
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Tops1_6Message::SystemEvent(_) => MessageKind::SystemEvent,
+            Tops1_6Message::SecurityDirectory(_) => MessageKind::SecurityDirectory,
+            Tops1_6Message::TradingStatus(_) => MessageKind::TradingStatus,
+            Tops1_6Message::RetailLiquidityIndicator(_) => MessageKind::RetailLiquidityIndicator,
+            Tops1_6Message::OperationalHaltStatus(_) => MessageKind::OperationalHaltStatus,
+            Tops1_6Message::ShortSalePriceTestStatus(_) => MessageKind::ShortSalePriceTestStatus,
+            Tops1_6Message::QuoteUpdate(_) => MessageKind::QuoteUpdate,
+            Tops1_6Message::TradeReport(_) => MessageKind::TradeReport,
+            Tops1_6Message::OfficialPrice(_) => MessageKind::OfficialPrice,
+            Tops1_6Message::TradeBreak(_) => MessageKind::TradeBreak,
+            Tops1_6Message::AuctionInformation(_) => MessageKind::AuctionInformation,
+        }
+    }
+
+    /// The symbol of the message, if it carries one and has been decoded
+    pub fn symbol(&self) -> Option<&S> {
+        match self {
+            Tops1_6Message::SecurityDirectory(message) => Some(&message.symbol),
+            Tops1_6Message::TradingStatus(message) => Some(&message.symbol),
+            Tops1_6Message::RetailLiquidityIndicator(message) => Some(&message.symbol),
+            Tops1_6Message::OperationalHaltStatus(message) => Some(&message.symbol),
+            Tops1_6Message::ShortSalePriceTestStatus(message) => Some(&message.symbol),
+            Tops1_6Message::QuoteUpdate(message) => Some(&message.symbol),
+            Tops1_6Message::TradeReport(message) => Some(&message.symbol),
+            Tops1_6Message::OfficialPrice(message) => Some(&message.symbol),
+            Tops1_6Message::TradeBreak(message) => Some(&message.symbol),
+            Tops1_6Message::AuctionInformation(message) => Some(&message.symbol),
+            _ => None,
+        }
+    }
+
+    /// Rounds the prices of the message to the doubles nearest to their decimal values, see
+    /// [`PriceRepresentation`](crate::config::PriceRepresentation)
+    pub fn round_prices(&mut self) {
+        match self {
+            Tops1_6Message::SecurityDirectory(message) => {
+                utils::round_price(&mut message.adjusted_poc_price)
+            }
+            Tops1_6Message::QuoteUpdate(message) => {
+                utils::round_price(&mut message.bid_price);
+                utils::round_price(&mut message.ask_price);
+            }
+            Tops1_6Message::TradeReport(message) => utils::round_price(&mut message.price),
+            Tops1_6Message::OfficialPrice(message) => {
+                utils::round_price(&mut message.official_price)
+            }
+            Tops1_6Message::TradeBreak(message) => utils::round_price(&mut message.price),
+            Tops1_6Message::AuctionInformation(message) => message.round_prices(),
+            _ => {}
+        }
+    }
+
+    /// Converts the symbol (if the message carries one) to another type
+    pub fn map_symbol<T>(self, f: impl FnOnce(S) -> T) -> Tops1_6Message<T> {
+        match self {
+            Tops1_6Message::SystemEvent(message) => Tops1_6Message::SystemEvent(message),
+            Tops1_6Message::SecurityDirectory(message) => {
+                Tops1_6Message::SecurityDirectory(message.map_symbol(f))
+            }
+            Tops1_6Message::TradingStatus(message) => {
+                Tops1_6Message::TradingStatus(message.map_symbol(f))
+            }
+            Tops1_6Message::RetailLiquidityIndicator(message) => {
+                Tops1_6Message::RetailLiquidityIndicator(message.map_symbol(f))
+            }
+            Tops1_6Message::OperationalHaltStatus(message) => {
+                Tops1_6Message::OperationalHaltStatus(message.map_symbol(f))
+            }
+            Tops1_6Message::ShortSalePriceTestStatus(message) => {
+                Tops1_6Message::ShortSalePriceTestStatus(message.map_symbol(f))
+            }
+            Tops1_6Message::QuoteUpdate(message) => {
+                Tops1_6Message::QuoteUpdate(message.map_symbol(f))
+            }
+            Tops1_6Message::TradeReport(message) => {
+                Tops1_6Message::TradeReport(message.map_symbol(f))
+            }
+            Tops1_6Message::OfficialPrice(message) => {
+                Tops1_6Message::OfficialPrice(message.map_symbol(f))
+            }
+            Tops1_6Message::TradeBreak(message) => {
+                Tops1_6Message::TradeBreak(message.map_symbol(f))
+            }
+            Tops1_6Message::AuctionInformation(message) => {
+                Tops1_6Message::AuctionInformation(message.map_symbol(f))
+            }
+        }
+    }
+}
+
+impl Tops1_6Message<Cow<'_, str>> {
+    /// Detaches the message from the buffer it was parsed from, allocating only if the symbol is still borrowed
+    pub fn into_owned(self) -> Tops1_6Message<Cow<'static, str>> {
+        self.map_symbol(|symbol| Cow::Owned(symbol.into_owned()))
+    }
+}
+
+impl<S: AsRef<str>> Tops1_6Message<S> {
+    /// Writes the message as it's sent on the wire. Fails if the symbol isn't ASCII or is longer than 8 characters.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Tops1_6Message::SystemEvent(message) => message.write_to(writer),
+            Tops1_6Message::SecurityDirectory(message) => message.write_to(writer),
+            Tops1_6Message::TradingStatus(message) => message.write_to(writer),
+            Tops1_6Message::RetailLiquidityIndicator(message) => message.write_to(writer),
+            Tops1_6Message::OperationalHaltStatus(message) => message.write_to(writer),
+            Tops1_6Message::ShortSalePriceTestStatus(message) => message.write_to(writer),
+            Tops1_6Message::QuoteUpdate(message) => message.write_to(writer),
+            Tops1_6Message::TradeReport(message) => message.write_to(writer),
+            Tops1_6Message::OfficialPrice(message) => message.write_to(writer),
+            Tops1_6Message::TradeBreak(message) => message.write_to(writer),
+            Tops1_6Message::AuctionInformation(message) => message.write_to(writer),
+        }
+    }
+
+    /// Encodes the message as it's sent on the wire, see [`Tops1_6Message::write_to`]
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        utils::encode(|encoded| self.write_to(encoded))
+    }
+}
+
+impl<'a, S> TryFrom<&'a [u8]> for Tops1_6Message<S>
+where
+    S: TryFrom<&'a str>,
+{
+    type Error = nom::Err<Error<&'a [u8]>>;
+
+    fn try_from(input: &'a [u8]) -> Result<Self, Self::Error> {
+        all_consuming(tops_1_6_message)
+            .parse(input)
+            .map(|(_, message)| message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::assert_matches;
+
+    use float_eq::assert_float_eq;
+
+    use crate::{arbitrary::check, spec};
+
+    use super::*;
+
+    #[test]
+    fn encoding_round_trip() {
+        check(10_000, |message: Tops1_6Message<String>| {
+            let encoded = message.encode().unwrap();
+            assert_eq!(Tops1_6Message::try_from(&encoded[..]), Ok(message));
+        });
+    }
+
+    #[test]
+    fn encoding_rejects_long_symbols() {
+        let status = OperationalHaltStatus {
+            status: OperationalHaltStatusType::Halted,
+            timestamp: DateTime::from_timestamp_nanos(0),
+            symbol: "TOOLONGSYM",
+        };
+        assert_eq!(status.encode().unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn quote_update_example() {
+        let input = spec::QUOTE_UPDATE;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                    available: true,
+                    market_session: MarketSession::Regular,
+                    timestamp: _,
+                    symbol: _,
+                    bid_size: 9700,
+                    bid_price: _,
+                    ask_size: 1000,
+                    ask_price: _,
+                })
+            )
+        );
+
+        if let Tops1_6Message::QuoteUpdate(inner_result) = result.1 {
+            assert_eq!(inner_result.symbol, "ZIEXT");
+
+            assert_eq!(
+                inner_result.timestamp,
+                DateTime::from_timestamp_nanos(1471980632572715948)
+            );
+
+            assert_float_eq!(inner_result.bid_price, 99.05, ulps <= 5);
+            assert_float_eq!(inner_result.ask_price, 99.07, ulps <= 5);
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn trade_report_example() {
+        let input = spec::TRADE_REPORT;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::TradeReport(TradeReport {
+                    sale_condition: SaleCondition {
+                        intermarket_sweep: false,
+                        extended_hours: false,
+                        odd_lot: false,
+                        trade_through_exempt: false,
+                        single_price: false
+                    },
+                    timestamp: _,
+                    symbol: _,
+                    size: 100,
+                    price: _,
+                    id: 429_974,
+                })
+            )
+        );
+
+        if let Tops1_6Message::TradeReport(inner_result) = result.1 {
+            assert_eq!(
+                inner_result.timestamp,
+                DateTime::from_timestamp_nanos(1471980683662974915)
+            );
+
+            assert_eq!(inner_result.symbol, "ZIEXT");
+
+            assert_float_eq!(inner_result.price, 99.05, ulps <= 5);
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn system_event_message() {
+        let input = spec::SYSTEM_EVENT;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::SystemEvent(SystemEvent {
+                    event_type: SystemEventType::EndOfSystemHours,
+                    timestamp: _,
+                })
+            )
+        );
+
+        if let Tops1_6Message::SystemEvent(inner_result) = result.1 {
+            assert_eq!(
+                inner_result.timestamp,
+                DateTime::from_timestamp_nanos(1492448400000000000)
+            );
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn try_from_known_message_type() {
+        let input: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+        let result = SystemEvent::try_from(&input[..]).unwrap();
+        assert_eq!(result.encode().unwrap(), input);
+
+        assert_matches!(result.event_type, SystemEventType::EndOfSystemHours);
+        assert_eq!(
+            result.timestamp,
+            DateTime::from_timestamp_nanos(1492448400000000000)
+        );
+    }
+
+    #[test]
+    fn try_from_rejects_trailing_bytes() {
+        let input: [u8; 11] = [
+            0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14, 0x00,
+        ];
+        assert!(SystemEvent::try_from(&input[..]).is_err());
+
+        let (remaining, _) = SystemEvent::parse(&input).unwrap();
+        assert_eq!(remaining, [0x00]);
+    }
+
+    #[test]
+    fn try_from_rejects_other_message_type() {
+        let input: [u8; 10] = [0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14];
+        assert!(TradeReport::<String>::try_from(&input[..]).is_err());
+        assert!(QuoteUpdate::<String>::try_from(&input[..]).is_err());
+    }
+
+    #[test]
+    fn borrowed_symbols() {
+        let input: [u8; 38] = [
+            0x54, 0x00, 0xC3, 0xDF, 0xF7, 0x05, 0xA2, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20, 0x64, 0x00, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x96, 0x8F, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        let (_, result) = tops_1_6_message::<&str>(&input).unwrap();
+        assert_matches!(
+            result,
+            Tops1_6Message::TradeReport(TradeReport {
+                symbol: "ZIEXT",
+                ..
+            })
+        );
+
+        let (_, result) = tops_1_6_message::<Cow<str>>(&input).unwrap();
+        assert_matches!(
+            result,
+            Tops1_6Message::TradeReport(TradeReport {
+                symbol: Cow::Borrowed("ZIEXT"),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn cow_symbols_into_owned() {
+        let input = vec![
+            0x51, 0x00, 0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0x5A, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20, 0xE4, 0x25, 0x00, 0x00, 0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0xEC, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE8, 0x03, 0x00, 0x00,
+        ];
+
+        let retained: Tops1_6Message<Cow<'static, str>> = {
+            let (_, result) = tops_1_6_message::<Cow<str>>(&input).unwrap();
+            result.into_owned()
+        };
+        drop(input);
+
+        assert_matches!(
+            retained,
+            Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                symbol: Cow::Owned(_),
+                ..
+            })
+        );
+        if let Tops1_6Message::QuoteUpdate(inner_result) = retained {
+            assert_eq!(inner_result.symbol, "ZIEXT");
+        }
+    }
+
+    #[test]
+    fn system_event_type_codes() {
+        assert_eq!(SystemEventType::StartOfRegularHours.code(), b'R');
+        assert_eq!(
+            SystemEventType::try_from(b'C'),
+            Ok(SystemEventType::EndOfMessages)
+        );
+        assert!(SystemEventType::try_from(b'X').is_err());
+
+        for event_type in [
+            SystemEventType::StartOfMessages,
+            SystemEventType::StartOfSystemHours,
+            SystemEventType::StartOfRegularHours,
+            SystemEventType::EndOfRegularHours,
+            SystemEventType::EndOfSystemHours,
+            SystemEventType::EndOfMessages,
+        ] {
+            assert_eq!(event_type.to_string().parse(), Ok(event_type));
+        }
+
+        assert!("".parse::<SystemEventType>().is_err());
+        assert!("RM".parse::<SystemEventType>().is_err());
+    }
+
+    #[test]
+    fn message_with_raw() {
+        let input: [u8; 12] = [
+            0x53, 0x45, 0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14, 0x53, 0x43,
+        ];
+        let (remaining, result) = tops_1_6_message_with_raw::<String>(&input).unwrap();
+
+        assert_eq!(remaining, [0x53, 0x43]);
+        assert_eq!(result.raw, &input[..10]);
+        assert_matches!(
+            result.value,
+            Tops1_6Message::SystemEvent(SystemEvent {
+                event_type: SystemEventType::EndOfSystemHours,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn official_price_message() {
+        let input = spec::OFFICIAL_PRICE;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::OfficialPrice(OfficialPrice {
+                    price_type: OfficialPriceType::OpeningPrice,
+                    ..
+                })
+            )
+        );
+
+        if let Tops1_6Message::OfficialPrice(inner_result) = result.1 {
+            assert_eq!(inner_result.symbol, "ZIEXT");
+            assert_eq!(
+                inner_result.timestamp,
+                DateTime::from_timestamp_nanos(1492421400000000000)
+            );
+            assert_float_eq!(inner_result.official_price, 99.05, ulps <= 5);
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn trading_status_message() {
+        let input = spec::TRADING_STATUS;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::TradingStatus(TradingStatus {
+                    status: TradingStatusType::Halted,
+                    reason: TradingStatusReason::HaltNewsPending,
+                    ..
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn trading_status_reason_codes() {
+        assert_eq!(
+            "MCB1".parse(),
+            Ok(TradingStatusReason::MarketWideCircuitBreakerLevel1)
+        );
+        assert_eq!(TradingStatusReason::NotApplicable.to_string(), "NA");
+
+        let unknown: TradingStatusReason = "XY".parse().unwrap();
+        assert_eq!(unknown, TradingStatusReason::Other(*b"XY  "));
+        assert_eq!(unknown.to_string(), "XY");
+
+        assert!("TOOLONG".parse::<TradingStatusReason>().is_err());
+    }
+
+    #[test]
+    fn operational_halt_status_message() {
+        let input = spec::OPERATIONAL_HALT_STATUS;
+        let result = OperationalHaltStatus::<String>::try_from(&input[..]).unwrap();
+        assert_eq!(result.encode().unwrap(), input);
+
+        assert_eq!(result.status, OperationalHaltStatusType::Halted);
+        assert_eq!(result.symbol, "ZIEXT");
+    }
+
+    #[test]
+    fn retail_liquidity_indicator_message() {
+        let input = [
+            0x49, 0x41, 0x00, 0xf0, 0x30, 0x2a, 0x5b, 0x25, 0xb6, 0x14, 0x5a, 0x49, 0x45, 0x58,
+            0x54, 0x20, 0x20, 0x20,
+        ];
+        let result = RetailLiquidityIndicator::<String>::try_from(&input[..]).unwrap();
+        assert_eq!(result.encode().unwrap(), input);
+
+        assert_eq!(result.indicator, RetailLiquidityIndicatorType::BuyInterest);
+        assert_eq!(result.symbol, "ZIEXT");
+        assert!(RetailLiquidityIndicator::<String>::try_from(&input[..17]).is_err());
+    }
+
+    #[test]
+    fn security_directory_message() {
+        let input = spec::SECURITY_DIRECTORY;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::SecurityDirectory(SecurityDirectory {
+                    flags: SecurityDirectoryFlags {
+                        test_security: true,
+                        when_issued: false,
+                        etp: false,
+                    },
+                    round_lot_size: 100,
+                    luld_tier: LuldTier::Tier1,
+                    ..
+                })
+            )
+        );
+
+        if let Tops1_6Message::SecurityDirectory(inner_result) = result.1 {
+            assert_eq!(inner_result.symbol, "ZIEXT");
+            assert_float_eq!(inner_result.adjusted_poc_price, 99.05, ulps <= 5);
+        } else {
+            unreachable!()
+        }
+
+        let mut invalid = input;
+        invalid[30] = 0x03;
+        assert!(SecurityDirectory::<String>::try_from(&invalid[..]).is_err());
+    }
+
+    #[test]
+    fn trade_break_message() {
+        let input = spec::TRADE_BREAK;
+        let result = TradeBreak::<String>::try_from(&input[..]).unwrap();
+        assert_eq!(result.encode().unwrap(), input);
+
+        assert_eq!(result.symbol, "ZIEXT");
+        assert_eq!(result.size, 100);
+        assert_eq!(result.id, 429_974);
+        assert_float_eq!(result.price, 99.05, ulps <= 5);
+    }
+
+    #[test]
+    fn short_sale_price_test_status_message() {
+        let input = spec::SHORT_SALE_PRICE_TEST_STATUS;
+        let result = ShortSalePriceTestStatus::<String>::try_from(&input[..]).unwrap();
+        assert_eq!(result.encode().unwrap(), input);
+
+        assert!(result.in_effect);
+        assert_eq!(result.detail, ShortSalePriceTestDetail::Activated);
+        assert_eq!(result.symbol, "ZIEXT");
+
+        let mut invalid = input;
+        invalid[1] = 0x02;
+        assert!(ShortSalePriceTestStatus::<String>::try_from(&invalid[..]).is_err());
+    }
+
+    #[test]
+    fn auction_information_message() {
+        let input = spec::AUCTION_INFORMATION;
+        let result = tops_1_6_message::<String>(&input).unwrap();
+        assert_eq!(result.1.encode().unwrap(), input);
+
+        assert_matches!(
+            result,
+            (
+                [],
+                Tops1_6Message::AuctionInformation(AuctionInformation {
+                    auction_type: AuctionType::Closing,
+                    paired_shares: 100_000,
+                    imbalance_shares: 10_000,
+                    imbalance_side: ImbalanceSide::Buy,
+                    extension_number: 0,
+                    ..
+                })
+            )
+        );
+
+        if let Tops1_6Message::AuctionInformation(inner_result) = result.1 {
+            assert_eq!(inner_result.symbol, "ZIEXT");
+            assert_eq!(
+                inner_result.scheduled_auction_time,
+                DateTime::from_timestamp(1599076800, 0).unwrap()
             );
+            assert_float_eq!(inner_result.reference_price, 99.05, ulps <= 5);
+            assert_float_eq!(inner_result.indicative_clearing_price, 99.07, ulps <= 5);
+            assert_float_eq!(inner_result.auction_book_clearing_price, 99.10, ulps <= 5);
+            assert_float_eq!(inner_result.lower_auction_collar, 94.10, ulps <= 5);
+            assert_float_eq!(inner_result.upper_auction_collar, 104.00, ulps <= 5);
         } else {
             unreachable!()
         }
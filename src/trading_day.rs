@@ -0,0 +1,223 @@
+use std::io;
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc, Weekday};
+
+use crate::{hist::Message, tops::SystemEventType};
+
+/// The offset of US Eastern time from UTC at some time, by the daylight saving time rules in force since 2007, which
+/// cover every HIST file: daylight saving time starts on the second Sunday of March at 2:00 EST, and ends on the first
+/// Sunday of November at 2:00 EDT.
+pub fn eastern_offset(time: DateTime<Utc>) -> FixedOffset {
+    let year = time.year();
+    // The transitions, in UTC
+    let sunday = |month, n, hour| {
+        NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Sun, n)
+            .and_then(|day| day.and_hms_opt(hour, 0, 0))
+            .expect("every month has two Sundays")
+            .and_utc()
+    };
+    let dst_start = sunday(3, 2, 7);
+    let dst_end = sunday(11, 1, 6);
+    let hours = if (dst_start..dst_end).contains(&time) {
+        -4
+    } else {
+        -5
+    };
+    FixedOffset::east_opt(hours * 3600).expect("valid offset")
+}
+
+/// A time in US Eastern time, the time zone of the schedule of the market
+pub fn to_eastern(time: DateTime<Utc>) -> DateTime<FixedOffset> {
+    time.with_timezone(&eastern_offset(time))
+}
+
+/// The date of a time in US Eastern time. Unlike its UTC date, it doesn't change during the extended hours (e.g. at
+/// 20:00 EDT, already the next day in UTC).
+pub fn eastern_date(time: DateTime<Utc>) -> NaiveDate {
+    to_eastern(time).date_naive()
+}
+
+/// Assigns messages to trading days
+///
+/// A session, from its start of messages to its end of messages system events, belongs to the Eastern date of its
+/// start, whatever the timestamps of its messages. Messages outside of any session belong to their own Eastern date.
+/// Trading days never go back: a message timestamped before the start of the current day belongs to it.
+#[derive(Clone, Debug, Default)]
+pub struct TradingDays {
+    current: Option<NaiveDate>,
+    in_session: bool,
+}
+
+impl TradingDays {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The trading day of the next message, if it's timestamped or follows a message which was
+    pub fn update<M: Message>(&mut self, message: &M) -> Option<NaiveDate> {
+        let event_type = message.system_event().map(|event| event.event_type);
+        if event_type == Some(SystemEventType::StartOfMessages) {
+            self.in_session = false;
+        }
+
+        if !self.in_session {
+            if let Some(date) = message.timestamp().map(eastern_date) {
+                self.current = Some(self.current.map_or(date, |current| current.max(date)));
+            }
+        }
+
+        match event_type {
+            Some(SystemEventType::StartOfMessages) => self.in_session = true,
+            Some(SystemEventType::EndOfMessages) => self.in_session = false,
+            _ => {}
+        }
+        self.current
+    }
+
+    /// The trading day of the last message
+    pub fn current(&self) -> Option<NaiveDate> {
+        self.current
+    }
+}
+
+/// Splits an output by trading day, opening a writer for each day (e.g. a file named after it) when its first
+/// message is written, and closing the writer of the previous day
+///
+/// Days are expected in order, as assigned by [`TradingDays`]: going back to a day opens it again.
+pub struct DayPartitioned<W, O, C> {
+    open: O,
+    close: C,
+    current: Option<(NaiveDate, W)>,
+    days: Vec<NaiveDate>,
+}
+
+impl<W, O, C> DayPartitioned<W, O, C>
+where
+    O: FnMut(NaiveDate) -> io::Result<W>,
+    C: FnMut(W) -> io::Result<()>,
+{
+    pub fn new(open: O, close: C) -> Self {
+        Self {
+            open,
+            close,
+            current: None,
+            days: Vec::new(),
+        }
+    }
+
+    /// The writer of a day, closing the writer of the previous day if it's a new one
+    pub fn writer(&mut self, day: NaiveDate) -> io::Result<&mut W> {
+        if self
+            .current
+            .as_ref()
+            .is_none_or(|(current, _)| *current != day)
+        {
+            if let Some((_, writer)) = self.current.take() {
+                (self.close)(writer)?;
+            }
+            self.current = Some((day, (self.open)(day)?));
+            self.days.push(day);
+        }
+        Ok(&mut self.current.as_mut().expect("opened above").1)
+    }
+
+    /// Closes the writer of the last day, returning the days written, in order
+    pub fn finish(mut self) -> io::Result<Vec<NaiveDate>> {
+        if let Some((_, writer)) = self.current.take() {
+            (self.close)(writer)?;
+        }
+        Ok(self.days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tops::{SystemEvent, Tops1_6Message};
+
+    use super::*;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn event(event_type: SystemEventType, timestamp: &str) -> Tops1_6Message<String> {
+        Tops1_6Message::SystemEvent(SystemEvent {
+            event_type,
+            timestamp: time(timestamp),
+        })
+    }
+
+    #[test]
+    fn eastern_time() {
+        // Before and after the start of daylight saving time, on 2017-03-12
+        assert_eq!(
+            to_eastern(time("2017-03-12T06:59:59Z")).to_rfc3339(),
+            "2017-03-12T01:59:59-05:00"
+        );
+        assert_eq!(
+            to_eastern(time("2017-03-12T07:00:00Z")).to_rfc3339(),
+            "2017-03-12T03:00:00-04:00"
+        );
+        // And of its end, on 2017-11-05
+        assert_eq!(
+            eastern_offset(time("2017-11-05T05:59:59Z")).local_minus_utc(),
+            -4 * 3600
+        );
+        assert_eq!(
+            eastern_offset(time("2017-11-05T06:00:00Z")).local_minus_utc(),
+            -5 * 3600
+        );
+
+        // After hours, already the next day in UTC
+        assert_eq!(
+            eastern_date(time("2017-04-18T00:30:00Z")),
+            date("2017-04-17")
+        );
+    }
+
+    #[test]
+    fn trading_days() {
+        let mut days = TradingDays::new();
+        let messages = [
+            event(SystemEventType::StartOfMessages, "2017-04-17T11:00:00Z"),
+            event(SystemEventType::EndOfSystemHours, "2017-04-18T00:00:00Z"),
+            // Late, but still in the session
+            event(SystemEventType::EndOfMessages, "2017-04-18T04:05:00Z"),
+            event(SystemEventType::StartOfMessages, "2017-04-18T11:00:00Z"),
+        ];
+        let assigned = messages
+            .iter()
+            .map(|message| days.update(message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            assigned,
+            [
+                Some(date("2017-04-17")),
+                Some(date("2017-04-17")),
+                Some(date("2017-04-17")),
+                Some(date("2017-04-18")),
+            ]
+        );
+
+        let mut closed = Vec::new();
+        let mut partitioned = DayPartitioned::new(
+            |day| Ok(vec![day]),
+            |writer| {
+                closed.push(writer);
+                Ok(())
+            },
+        );
+        for day in assigned.into_iter().flatten() {
+            partitioned.writer(day).unwrap();
+        }
+        assert_eq!(
+            partitioned.finish().unwrap(),
+            [date("2017-04-17"), date("2017-04-18")]
+        );
+        assert_eq!(closed.len(), 2);
+    }
+}
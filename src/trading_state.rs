@@ -0,0 +1,549 @@
+use std::{borrow::Borrow, collections::HashMap, error, fmt, hash::Hash};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    deep::Deep1_0Message,
+    tops::{
+        OperationalHaltStatus, OperationalHaltStatusType, ShortSalePriceTestDetail,
+        ShortSalePriceTestStatus, Tops1_6Message, TradingStatus, TradingStatusType,
+    },
+};
+
+/// The trading state of a symbol
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TradingState {
+    /// `None` until a Trading Status message is received for the symbol
+    pub status: Option<TradingStatusType>,
+    pub operationally_halted: bool,
+    /// Whether the short sale price test (Reg. SHO Rule 201) is in effect
+    pub ssr_active: bool,
+}
+
+impl TradingState {
+    /// Whether the symbol is trading on IEX, i.e. its trading status is `Trading` and it isn't operationally halted.
+    /// Symbols without a trading status are assumed to be trading.
+    pub fn is_trading(&self) -> bool {
+        self.status
+            .is_none_or(|status| status == TradingStatusType::Trading)
+            && !self.operationally_halted
+    }
+}
+
+/// A change of the trading state of a symbol
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transition<S> {
+    pub symbol: S,
+    pub timestamp: DateTime<Utc>,
+    pub previous: TradingState,
+    pub current: TradingState,
+}
+
+/// Why a transition is illegal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IllegalTransition {
+    /// The trading status can't change directly from one status to the other
+    TradingStatus {
+        from: TradingStatusType,
+        to: TradingStatusType,
+    },
+    /// The short sale price test status contradicts its detail or the previous state
+    ShortSalePriceTest {
+        was_active: bool,
+        in_effect: bool,
+        detail: ShortSalePriceTestDetail,
+    },
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalTransition::TradingStatus { from, to } => {
+                write!(f, "illegal trading status transition from {from} to {to}")
+            }
+            IllegalTransition::ShortSalePriceTest {
+                was_active,
+                in_effect,
+                detail,
+            } => write!(
+                f,
+                "inconsistent short sale price test status (was active: {was_active}, in effect: {in_effect}, \
+                 detail: {detail:?})"
+            ),
+        }
+    }
+}
+
+/// An illegal transition. The state machine still applies it, since the feed is authoritative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransitionError<S> {
+    pub transition: Transition<S>,
+    pub kind: IllegalTransition,
+}
+
+impl<S> fmt::Display for TransitionError<S>
+where
+    S: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}: {}",
+            self.transition.symbol, self.transition.timestamp, self.kind
+        )
+    }
+}
+
+impl<S> error::Error for TransitionError<S> where S: fmt::Debug + fmt::Display {}
+
+/// Whether the trading status may change directly from `from` to `to`. Repeating a status (e.g. to change the reason
+/// of a halt) is legal.
+pub fn is_legal_transition(from: TradingStatusType, to: TradingStatusType) -> bool {
+    use TradingStatusType::*;
+
+    from == to
+        || matches!(
+            (from, to),
+            (Halted, OrderAcceptancePeriod)
+                | (Halted, Trading)
+                | (OrderAcceptancePeriod, Trading)
+                | (OrderAcceptancePeriod, Halted)
+                | (Paused, Trading)
+                | (Paused, Halted)
+                | (Trading, Halted)
+                | (Trading, Paused)
+        )
+}
+
+pub type TransitionResult<S> = Result<Option<Transition<S>>, TransitionError<S>>;
+
+/// Tracks the trading state of every symbol over time
+///
+/// Every update returns the resulting [`Transition`], if the state changed, or a [`TransitionError`] if the change is
+/// illegal. The history of each symbol is kept, so that the state can be queried at any point in time.
+#[derive(Clone, Debug)]
+pub struct TradingStateMachine<S> {
+    // The states of each symbol along with the time they came into force, oldest first
+    history: HashMap<S, Vec<(DateTime<Utc>, TradingState)>>,
+}
+
+impl<S> Default for TradingStateMachine<S> {
+    fn default() -> Self {
+        Self {
+            history: HashMap::new(),
+        }
+    }
+}
+
+impl<S> TradingStateMachine<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn transition(
+        &mut self,
+        symbol: &S,
+        timestamp: DateTime<Utc>,
+        f: impl FnOnce(&mut TradingState) -> Option<IllegalTransition>,
+    ) -> TransitionResult<S> {
+        let history = self.history.entry(symbol.clone()).or_default();
+        let previous = history.last().map(|(_, state)| *state).unwrap_or_default();
+        let mut current = previous;
+        let illegal = f(&mut current);
+
+        let transition = Transition {
+            symbol: symbol.clone(),
+            timestamp,
+            previous,
+            current,
+        };
+        if current != previous {
+            history.push((timestamp, current));
+        }
+
+        match illegal {
+            Some(kind) => Err(TransitionError { transition, kind }),
+            None if current != previous => Ok(Some(transition)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn apply_trading_status(&mut self, status: &TradingStatus<S>) -> TransitionResult<S> {
+        self.transition(&status.symbol, status.timestamp, |state| {
+            let illegal = state
+                .status
+                .filter(|&from| !is_legal_transition(from, status.status))
+                .map(|from| IllegalTransition::TradingStatus {
+                    from,
+                    to: status.status,
+                });
+            state.status = Some(status.status);
+            illegal
+        })
+    }
+
+    pub fn apply_operational_halt_status(
+        &mut self,
+        status: &OperationalHaltStatus<S>,
+    ) -> TransitionResult<S> {
+        self.transition(&status.symbol, status.timestamp, |state| {
+            state.operationally_halted = status.status == OperationalHaltStatusType::Halted;
+            None
+        })
+    }
+
+    pub fn apply_short_sale_price_test_status(
+        &mut self,
+        status: &ShortSalePriceTestStatus<S>,
+    ) -> TransitionResult<S> {
+        self.transition(&status.symbol, status.timestamp, |state| {
+            use ShortSalePriceTestDetail::*;
+
+            let consistent = match status.detail {
+                Activated => status.in_effect && !state.ssr_active,
+                Continued => status.in_effect,
+                Deactivated => !status.in_effect,
+                NoPriceTest | NotAvailable => true,
+            };
+            let illegal = (!consistent).then_some(IllegalTransition::ShortSalePriceTest {
+                was_active: state.ssr_active,
+                in_effect: status.in_effect,
+                detail: status.detail,
+            });
+            state.ssr_active = status.in_effect;
+            illegal
+        })
+    }
+
+    /// Feeds a TOPS message to the state machine. Messages which don't affect the trading state are ignored.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> TransitionResult<S> {
+        match message {
+            Tops1_6Message::TradingStatus(status) => self.apply_trading_status(status),
+            Tops1_6Message::OperationalHaltStatus(status) => {
+                self.apply_operational_halt_status(status)
+            }
+            Tops1_6Message::ShortSalePriceTestStatus(status) => {
+                self.apply_short_sale_price_test_status(status)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The current state of a symbol
+    pub fn state<Q>(&self, symbol: &Q) -> TradingState
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.history
+            .get(symbol)
+            .and_then(|history| history.last())
+            .map(|(_, state)| *state)
+            .unwrap_or_default()
+    }
+
+    /// The state of a symbol at some point in time, i.e. after all updates timestamped at or before it
+    pub fn state_at<Q>(&self, symbol: &Q, timestamp: DateTime<Utc>) -> TradingState
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(history) = self.history.get(symbol) else {
+            return TradingState::default();
+        };
+        let index = history.partition_point(|(since, _)| *since <= timestamp);
+        index
+            .checked_sub(1)
+            .map(|index| history[index].1)
+            .unwrap_or_default()
+    }
+
+    /// Whether the symbol was trading at some point in time, see [`TradingState::is_trading`]
+    pub fn is_trading<Q>(&self, symbol: &Q, timestamp: DateTime<Utc>) -> bool
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.state_at(symbol, timestamp).is_trading()
+    }
+
+    /// Whether the short sale price test was in effect for the symbol at some point in time
+    pub fn is_ssr_active<Q>(&self, symbol: &Q, timestamp: DateTime<Utc>) -> bool
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.state_at(symbol, timestamp).ssr_active
+    }
+}
+
+/// What likely explains an illegal transition
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AnomalyCause {
+    /// Messages were lost since the previous update of the symbol, and may have carried the intermediate states
+    MissedMessages,
+    /// The update is timestamped before the previous update of the symbol
+    OutOfOrder,
+    /// Nothing in the feed explains it: a genuinely unusual event, or an error of IEX
+    Unexplained,
+}
+
+impl AnomalyCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnomalyCause::MissedMessages => "missed_messages",
+            AnomalyCause::OutOfOrder => "out_of_order",
+            AnomalyCause::Unexplained => "unexplained",
+        }
+    }
+}
+
+/// An illegal transition, along with its likely cause
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Anomaly<S> {
+    pub error: TransitionError<S>,
+    pub cause: AnomalyCause,
+}
+
+/// Checks the transitions of the trading state of every symbol against the legal ones (see [`is_legal_transition`]),
+/// telling feed errors (lost or reordered messages) apart from unexplained transitions
+///
+/// The checker must be told about the messages lost, e.g. from the gaps found by a
+/// [`SequenceTracker`](crate::sequence::SequenceTracker).
+#[derive(Clone, Debug)]
+pub struct TransitionChecker<S> {
+    machine: TradingStateMachine<S>,
+    /// The timestamp of the last update of each symbol, and the number of gaps before it
+    last_updates: HashMap<S, (DateTime<Utc>, u64)>,
+    gaps: u64,
+}
+
+impl<S> Default for TransitionChecker<S> {
+    fn default() -> Self {
+        Self {
+            machine: TradingStateMachine::default(),
+            last_updates: HashMap::new(),
+            gaps: 0,
+        }
+    }
+}
+
+impl<S> TransitionChecker<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that messages of the feed were lost
+    pub fn note_gap(&mut self) {
+        self.gaps += 1;
+    }
+
+    fn check(
+        &mut self,
+        symbol: &S,
+        timestamp: DateTime<Utc>,
+        result: TransitionResult<S>,
+    ) -> Option<Anomaly<S>> {
+        let previous = self
+            .last_updates
+            .insert(symbol.clone(), (timestamp, self.gaps));
+        let error = result.err()?;
+        let cause = match previous {
+            Some((previous, _)) if timestamp < previous => AnomalyCause::OutOfOrder,
+            Some((_, gaps)) if gaps < self.gaps => AnomalyCause::MissedMessages,
+            _ => AnomalyCause::Unexplained,
+        };
+        Some(Anomaly { error, cause })
+    }
+
+    /// Feeds a TOPS message to the checker, returning the anomaly it reveals, if any. Messages which don't affect the
+    /// trading state are ignored.
+    pub fn update(&mut self, message: &Tops1_6Message<S>) -> Option<Anomaly<S>> {
+        match message {
+            Tops1_6Message::TradingStatus(status) => {
+                let result = self.machine.apply_trading_status(status);
+                self.check(&status.symbol, status.timestamp, result)
+            }
+            Tops1_6Message::OperationalHaltStatus(status) => {
+                let result = self.machine.apply_operational_halt_status(status);
+                self.check(&status.symbol, status.timestamp, result)
+            }
+            Tops1_6Message::ShortSalePriceTestStatus(status) => {
+                let result = self.machine.apply_short_sale_price_test_status(status);
+                self.check(&status.symbol, status.timestamp, result)
+            }
+            _ => None,
+        }
+    }
+
+    /// Feeds a DEEP message to the checker, see [`TransitionChecker::update`]
+    pub fn update_deep(&mut self, message: &Deep1_0Message<S>) -> Option<Anomaly<S>> {
+        match message {
+            Deep1_0Message::TradingStatus(status) => {
+                let result = self.machine.apply_trading_status(status);
+                self.check(&status.symbol, status.timestamp, result)
+            }
+            Deep1_0Message::OperationalHaltStatus(status) => {
+                let result = self.machine.apply_operational_halt_status(status);
+                self.check(&status.symbol, status.timestamp, result)
+            }
+            Deep1_0Message::ShortSalePriceTestStatus(status) => {
+                let result = self.machine.apply_short_sale_price_test_status(status);
+                self.check(&status.symbol, status.timestamp, result)
+            }
+            _ => None,
+        }
+    }
+
+    /// The trading states, as far as they were checked
+    pub fn machine(&self) -> &TradingStateMachine<S> {
+        &self.machine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tops::TradingStatusReason;
+
+    use super::*;
+
+    fn trading_status(status: TradingStatusType, timestamp: i64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradingStatus(TradingStatus {
+            status,
+            timestamp: DateTime::from_timestamp_nanos(timestamp),
+            symbol: "ZIEXT",
+            reason: TradingStatusReason::NotApplicable,
+        })
+    }
+
+    fn ssr(
+        in_effect: bool,
+        detail: ShortSalePriceTestDetail,
+        timestamp: i64,
+    ) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::ShortSalePriceTestStatus(ShortSalePriceTestStatus {
+            in_effect,
+            timestamp: DateTime::from_timestamp_nanos(timestamp),
+            symbol: "ZIEXT",
+            detail,
+        })
+    }
+
+    #[test]
+    fn point_in_time_queries() {
+        let mut machine = TradingStateMachine::new();
+
+        machine
+            .update(&trading_status(TradingStatusType::Trading, 10))
+            .unwrap();
+        let transition = machine
+            .update(&trading_status(TradingStatusType::Halted, 20))
+            .unwrap()
+            .unwrap();
+        assert!(transition.previous.is_trading());
+        assert!(!transition.current.is_trading());
+        machine
+            .update(&ssr(true, ShortSalePriceTestDetail::Activated, 25))
+            .unwrap();
+        machine
+            .update(&trading_status(TradingStatusType::Trading, 30))
+            .unwrap();
+
+        let at = DateTime::from_timestamp_nanos;
+        assert!(machine.is_trading("ZIEXT", at(5)));
+        assert!(machine.is_trading("ZIEXT", at(19)));
+        assert!(!machine.is_trading("ZIEXT", at(20)));
+        assert!(!machine.is_trading("ZIEXT", at(29)));
+        assert!(machine.is_trading("ZIEXT", at(30)));
+        assert!(!machine.is_ssr_active("ZIEXT", at(24)));
+        assert!(machine.is_ssr_active("ZIEXT", at(25)));
+        assert!(machine.is_trading("ZVZZT", at(25)));
+    }
+
+    #[test]
+    fn repeated_status_isnt_a_transition() {
+        let mut machine = TradingStateMachine::new();
+
+        machine
+            .update(&trading_status(TradingStatusType::Halted, 10))
+            .unwrap();
+        assert_eq!(
+            machine.update(&trading_status(TradingStatusType::Halted, 20)),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        let mut machine = TradingStateMachine::new();
+
+        machine
+            .update(&trading_status(TradingStatusType::Trading, 10))
+            .unwrap();
+        let error = machine
+            .update(&trading_status(
+                TradingStatusType::OrderAcceptancePeriod,
+                20,
+            ))
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            IllegalTransition::TradingStatus {
+                from: TradingStatusType::Trading,
+                to: TradingStatusType::OrderAcceptancePeriod
+            }
+        );
+        // The transition is applied regardless
+        assert_eq!(
+            machine.state("ZIEXT").status,
+            Some(TradingStatusType::OrderAcceptancePeriod)
+        );
+
+        machine
+            .update(&ssr(true, ShortSalePriceTestDetail::Activated, 30))
+            .unwrap();
+        assert!(machine
+            .update(&ssr(true, ShortSalePriceTestDetail::Activated, 40))
+            .is_err());
+        assert!(machine
+            .update(&ssr(true, ShortSalePriceTestDetail::Deactivated, 50))
+            .is_err());
+    }
+
+    #[test]
+    fn anomaly_causes() {
+        let mut checker = TransitionChecker::new();
+        let cause = |checker: &mut TransitionChecker<_>, status, timestamp| {
+            checker
+                .update(&trading_status(status, timestamp))
+                .map(|anomaly| anomaly.cause)
+        };
+
+        assert_eq!(cause(&mut checker, TradingStatusType::Trading, 10), None);
+        assert_eq!(
+            cause(&mut checker, TradingStatusType::OrderAcceptancePeriod, 20),
+            Some(AnomalyCause::Unexplained)
+        );
+        assert_eq!(cause(&mut checker, TradingStatusType::Trading, 30), None);
+        checker.note_gap();
+        assert_eq!(
+            cause(&mut checker, TradingStatusType::OrderAcceptancePeriod, 40),
+            Some(AnomalyCause::MissedMessages)
+        );
+        assert_eq!(cause(&mut checker, TradingStatusType::Halted, 50), None);
+        assert_eq!(
+            cause(&mut checker, TradingStatusType::Paused, 45),
+            Some(AnomalyCause::OutOfOrder)
+        );
+        assert_eq!(
+            checker.machine().state("ZIEXT").status,
+            Some(TradingStatusType::Paused)
+        );
+    }
+}
@@ -1,24 +1,67 @@
+use std::{
+    error, fmt,
+    io::{self, ErrorKind, Write},
+};
+
 use chrono::{DateTime, Utc};
 
 use nom::{
-    bytes::complete::take, combinator::map, error::ParseError, number::complete::le_i64, IResult,
-    Parser as _,
+    bytes::complete::take,
+    combinator::{consumed, map, map_res},
+    error::ParseError,
+    number::complete::le_i64,
+    IResult, Parser as _,
 };
 
+/// Parses an IEX Timestamp (8 bytes, signed integer containing the number of nanoseconds since the POSIX epoch)
+///
+/// # Example
+///
+/// ```
+/// use chrono::DateTime;
+/// use iex_parser::utils::timestamp;
+///
+/// let (_, result) = timestamp(&[0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14]).unwrap();
+/// assert_eq!(result, DateTime::from_timestamp_nanos(1492448400000000000));
+/// ```
 #[inline]
 pub fn timestamp(input: &[u8]) -> IResult<&[u8], DateTime<Utc>> {
     let (input, unix_time) = le_i64.parse(input)?;
     Ok((input, DateTime::from_timestamp_nanos(unix_time)))
 }
 
-// TODO document properly
-// Price: 8 bytes, signed integer containing a fixed-point number with 4 digits to the right of an implied decimal
-// point
+/// Parses an IEX Price (8 bytes, signed integer containing a fixed-point number with 4 digits to the right of an
+/// implied decimal point)
+///
+/// # Example
+///
+/// ```
+/// use iex_parser::utils::price;
+///
+/// let (_, result) = price(&[0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00]).unwrap();
+/// assert!((result - 99.05).abs() < 1e-9);
+/// ```
+#[inline]
 pub fn price(input: &[u8]) -> IResult<&[u8], f64> {
     let (input, int_price) = le_i64.parse(input)?;
     Ok((input, (int_price as f64) * 1e-4))
 }
 
+// Prices are kept in their wire representation (fixed-point with 4 decimal digits) wherever they must be ordered or
+// compared exactly
+pub(crate) fn price_key(price: f64) -> i64 {
+    (price * 1e4).round() as i64
+}
+
+pub(crate) fn key_price(key: i64) -> f64 {
+    (key as f64) * 1e-4
+}
+
+/// The double nearest to the decimal value of a price, e.g. 99.05 rather than the 99.05000000000001 of its scaling
+pub(crate) fn round_price(price: &mut f64) {
+    *price = price_key(*price) as f64 / 1e4;
+}
+
 /// Parses an IEX String (fixed-length ASCII byte sequence, left-justified and space-filled on the right)
 ///
 /// # Arguments
@@ -27,14 +70,14 @@ pub fn price(input: &[u8]) -> IResult<&[u8], f64> {
 ///
 /// # Example
 ///
-/// ```ignore
+/// ```
 /// use nom::error::Error;
-/// use crate::utils::iex_string;
+/// use iex_parser::utils::iex_string;
 ///
-/// let (_, result) = iex_string(8).unwrap().parse(b"HELLO   ").unwrap();
+/// let (_, result) = iex_string::<Error<&[u8]>>(8)(b"HELLO   ").unwrap();
 /// assert_eq!(result, "HELLO");
 ///
-/// let (_, result) = iex_string(8).unwrap().parse(b"        ").unwrap();
+/// let (_, result) = iex_string::<Error<&[u8]>>(8)(b"        ").unwrap();
 /// assert_eq!(result, "");
 /// ```
 #[inline]
@@ -48,11 +91,261 @@ pub fn iex_string<'a, E: ParseError<&'a [u8]>>(
     })
 }
 
+/// Parses an 8-byte IEX symbol into any type constructible from a `&str`
+///
+/// Conversion failures (e.g. a fixed-capacity string too short for the symbol) are surfaced as parse errors rather than
+/// panics. Since every `From<&str>` type is also `TryFrom<&str>`, infallible types such as `String` work as well.
+///
+/// # Example
+///
+/// ```
+/// use iex_parser::utils::symbol;
+///
+/// let (_, result) = symbol::<String>(b"ZIEXT   ").unwrap();
+/// assert_eq!(result, "ZIEXT");
+/// ```
+#[inline]
+pub fn symbol<'a, S>(input: &'a [u8]) -> IResult<&'a [u8], S>
+where
+    S: TryFrom<&'a str>,
+{
+    map_res(iex_string(8), S::try_from).parse(input)
+}
+
+/// Writes an IEX Timestamp, the inverse of [`timestamp`]. Fails for times which don't fit in 64 bits of nanoseconds.
+///
+/// # Example
+///
+/// ```
+/// use chrono::DateTime;
+/// use iex_parser::utils::write_timestamp;
+///
+/// let mut output = Vec::new();
+/// write_timestamp(&mut output, DateTime::from_timestamp_nanos(1492448400000000000)).unwrap();
+/// assert_eq!(output, [0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14]);
+/// ```
+pub fn write_timestamp<W: Write>(writer: &mut W, timestamp: DateTime<Utc>) -> io::Result<()> {
+    let nanos = timestamp
+        .timestamp_nanos_opt()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "timestamp out of range"))?;
+    writer.write_all(&nanos.to_le_bytes())
+}
+
+/// Writes an IEX Price, the inverse of [`price`], rounding to the nearest ten-thousandth
+///
+/// # Example
+///
+/// ```
+/// use iex_parser::utils::write_price;
+///
+/// let mut output = Vec::new();
+/// write_price(&mut output, 99.05).unwrap();
+/// assert_eq!(output, [0x24, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00]);
+/// ```
+pub fn write_price<W: Write>(writer: &mut W, price: f64) -> io::Result<()> {
+    writer.write_all(&price_key(price).to_le_bytes())
+}
+
+/// Writes an IEX String, the inverse of [`iex_string`]: the value space-filled on the right to the given length.
+/// Fails for values which aren't ASCII or are longer than the length.
+///
+/// # Example
+///
+/// ```
+/// use iex_parser::utils::write_iex_string;
+///
+/// let mut output = Vec::new();
+/// write_iex_string(&mut output, "ZIEXT", 8).unwrap();
+/// assert_eq!(output, b"ZIEXT   ");
+/// assert!(write_iex_string(&mut output, "TOO LONG!", 8).is_err());
+/// ```
+pub fn write_iex_string<W: Write>(writer: &mut W, value: &str, length: usize) -> io::Result<()> {
+    if !value.is_ascii() || value.len() > length {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{value:?} isn't an ASCII string of at most {length} characters"),
+        ));
+    }
+    writer.write_all(value.as_bytes())?;
+    writer.write_all(&b" ".repeat(length - value.len()))
+}
+
+/// Writes an 8-byte IEX symbol, the inverse of [`symbol`]
+pub fn write_symbol<W: Write>(writer: &mut W, symbol: &str) -> io::Result<()> {
+    write_iex_string(writer, symbol, 8)
+}
+
+// Encodes a message into a new buffer
+pub(crate) fn encode(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> io::Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    write(&mut encoded)?;
+    Ok(encoded)
+}
+
+/// A parsed value along with the exact bytes it was parsed from
+#[derive(Clone, Debug)]
+pub struct WithRaw<'a, T> {
+    pub raw: &'a [u8],
+    pub value: T,
+}
+
+impl<'a, T> WithRaw<'a, T> {
+    /// Converts the parsed value, keeping the raw bytes
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> WithRaw<'a, U> {
+        WithRaw {
+            raw: self.raw,
+            value: f(self.value),
+        }
+    }
+}
+
+/// Wraps a parser so that its output also references the bytes it consumed
+///
+/// # Example
+///
+/// ```
+/// use iex_parser::utils::{timestamp, with_raw};
+///
+/// let input = [0x00, 0xA0, 0x99, 0x97, 0xE9, 0x3D, 0xB6, 0x14, 0xFF];
+/// let (remaining, result) = with_raw(timestamp)(&input).unwrap();
+/// assert_eq!(result.raw, &input[..8]);
+/// assert_eq!(remaining, [0xFF]);
+/// ```
+pub fn with_raw<'a, O, E, F>(
+    parser: F,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], WithRaw<'a, O>, E>
+where
+    E: ParseError<&'a [u8]>,
+    F: nom::Parser<&'a [u8], O, E>,
+{
+    map(consumed(parser), |(raw, value)| WithRaw { raw, value })
+}
+
+/// Error returned when converting a character code which the specification doesn't define
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidCode(pub String);
+
+impl fmt::Display for InvalidCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid code {:?}", self.0)
+    }
+}
+
+impl error::Error for InvalidCode {}
+
+/// Defines an enum whose variants are identified by single-character codes in the specification, along with
+/// conversions to and from the code (as a byte, via `FromStr` and via `Display`)
+macro_rules! char_code_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $code:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant,)*
+        }
+
+        impl $name {
+            /// Returns the character code identifying the value in the specification
+            pub fn code(&self) -> u8 {
+                match self {
+                    $(Self::$variant => $code,)*
+                }
+            }
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = $crate::utils::InvalidCode;
+
+            fn try_from(code: u8) -> Result<Self, Self::Error> {
+                match code {
+                    $($code => Ok(Self::$variant),)*
+                    _ => Err($crate::utils::InvalidCode(char::from(code).to_string())),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> Self {
+                value.code()
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = $crate::utils::InvalidCode;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s.as_bytes() {
+                    [code] => Self::try_from(*code),
+                    _ => Err($crate::utils::InvalidCode(s.to_string())),
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", char::from(self.code()))
+            }
+        }
+    };
+}
+
+pub(crate) use char_code_enum;
+
+/// Asserts that an expression matches a pattern, as `std::assert_matches!`, which is only available on nightly
+#[cfg(test)]
+macro_rules! assert_matches {
+    ($expression:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
+        match $expression {
+            $pattern $(if $guard)? => {}
+            ref value => panic!(
+                "assertion failed: `{:?}` doesn't match `{}`",
+                value,
+                stringify!($pattern $(if $guard)?)
+            ),
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use assert_matches;
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use float_eq::assert_float_eq;
     use nom::error::Error;
 
+    #[test]
+    fn test_timestamp() {
+        let input = [0xAC, 0x63, 0xC0, 0x20, 0x96, 0x86, 0x6D, 0x14, 0xFF];
+        let (remaining, result) = timestamp(&input).unwrap();
+        assert_eq!(result, DateTime::from_timestamp_nanos(1471980632572715948));
+        assert_eq!(remaining, [0xFF]);
+    }
+
+    #[test]
+    fn test_timestamp_too_short() {
+        assert!(timestamp(&[0x00; 7]).is_err());
+    }
+
+    #[test]
+    fn test_price() {
+        let input = [0xEC, 0x1D, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let (remaining, result) = price(&input).unwrap();
+        assert_float_eq!(result, 99.07, ulps <= 5);
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn test_price_negative() {
+        let input = (-12_345i64).to_le_bytes();
+        let (_, result) = price(&input).unwrap();
+        assert_float_eq!(result, -1.2345, ulps <= 5);
+    }
+
     #[test]
     fn test_iex_string_valid() {
         let mut parser = iex_string::<Error<&[u8]>>(8);
@@ -101,4 +394,29 @@ mod tests {
         assert_eq!(result, "LONGSTRI");
         assert_eq!(remaining, b"NG");
     }
+
+    #[derive(Debug, PartialEq)]
+    struct ShortSymbol([u8; 4]);
+
+    impl TryFrom<&str> for ShortSymbol {
+        type Error = ();
+
+        fn try_from(value: &str) -> Result<Self, Self::Error> {
+            let mut bytes = [b' '; 4];
+            bytes
+                .get_mut(..value.len())
+                .ok_or(())?
+                .copy_from_slice(value.as_bytes());
+            Ok(Self(bytes))
+        }
+    }
+
+    #[test]
+    fn test_symbol_fallible_conversion() {
+        let (remaining, result) = symbol::<ShortSymbol>(b"SPY     ").unwrap();
+        assert_eq!(result, ShortSymbol(*b"SPY "));
+        assert!(remaining.is_empty());
+
+        assert!(symbol::<ShortSymbol>(b"ZIEXT   ").is_err());
+    }
 }
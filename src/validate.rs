@@ -0,0 +1,527 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read, Write},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    bbo::Bbo,
+    book::BookBuilder,
+    deep::{deep_1_0_message, Deep1_0Message},
+    export::{write_json_object, Record, Value},
+    filter::MessageKind,
+    hist::{self, HistReader},
+    iex_tp::IexTp1Segment,
+    locked_crossed::QuoteCondition,
+    message_protocol_ids,
+    sequence::{SequenceRange, SequenceTracker, Sequencing},
+    tops::{tops_1_6_message, Tops1_6Message, TradeBreak},
+    trading_state::{Anomaly, AnomalyCause, TransitionChecker},
+};
+
+/// A problem found in a HIST file
+#[derive(Clone, Debug, PartialEq)]
+pub enum Issue {
+    /// A UDP payload isn't a valid IEX-TP segment
+    InvalidSegment {
+        /// The offset (in the capture) following the packet
+        position: u64,
+    },
+    /// A message couldn't be decoded
+    InvalidMessage {
+        message_protocol_id: u16,
+        sequence_number: i64,
+        kind: Option<MessageKind>,
+        length: usize,
+    },
+    /// A message was decoded, but is longer than its kind of message
+    TrailingBytes {
+        message_protocol_id: u16,
+        sequence_number: i64,
+        kind: MessageKind,
+        length: usize,
+    },
+    /// Messages are missing from a stream
+    Gap(SequenceRange),
+    /// Messages of a stream were seen more than once
+    Duplicate(SequenceRange),
+    /// The quote (TOPS) or the book (DEEP) of a symbol became crossed
+    CrossedQuote {
+        timestamp: DateTime<Utc>,
+        symbol: String,
+        bid_price: f64,
+        ask_price: f64,
+    },
+    /// A trade break refers to a trade which wasn't seen
+    OrphanTradeBreak {
+        timestamp: DateTime<Utc>,
+        symbol: String,
+        trade_id: i64,
+    },
+    /// The trading state of a symbol changed in a way which isn't legal, e.g. from trading straight to an order
+    /// acceptance period
+    IllegalTransition {
+        timestamp: DateTime<Utc>,
+        symbol: String,
+        description: String,
+        cause: AnomalyCause,
+    },
+}
+
+impl From<Anomaly<String>> for Issue {
+    fn from(anomaly: Anomaly<String>) -> Self {
+        Issue::IllegalTransition {
+            timestamp: anomaly.error.transition.timestamp,
+            description: anomaly.error.kind.to_string(),
+            symbol: anomaly.error.transition.symbol,
+            cause: anomaly.cause,
+        }
+    }
+}
+
+impl Issue {
+    /// The name of the kind of issue
+    pub fn name(&self) -> &'static str {
+        match self {
+            Issue::InvalidSegment { .. } => "invalid_segment",
+            Issue::InvalidMessage { .. } => "invalid_message",
+            Issue::TrailingBytes { .. } => "trailing_bytes",
+            Issue::Gap(_) => "gap",
+            Issue::Duplicate(_) => "duplicate",
+            Issue::CrossedQuote { .. } => "crossed_quote",
+            Issue::OrphanTradeBreak { .. } => "orphan_trade_break",
+            Issue::IllegalTransition { .. } => "illegal_transition",
+        }
+    }
+
+    pub fn to_record(&self) -> Record {
+        let mut record = vec![("issue", Value::Text(self.name().to_string()))];
+        match self {
+            Issue::InvalidSegment { position } => {
+                record.push(("position", Value::Int(*position as i64)))
+            }
+            Issue::InvalidMessage {
+                message_protocol_id,
+                sequence_number,
+                kind,
+                length,
+            } => {
+                record.extend([
+                    (
+                        "message_protocol_id",
+                        Value::Int((*message_protocol_id).into()),
+                    ),
+                    ("sequence_number", Value::Int(*sequence_number)),
+                ]);
+                if let Some(kind) = kind {
+                    record.push(("kind", Value::Text(kind.name().to_string())));
+                }
+                record.push(("length", Value::Int(*length as i64)));
+            }
+            Issue::TrailingBytes {
+                message_protocol_id,
+                sequence_number,
+                kind,
+                length,
+            } => record.extend([
+                (
+                    "message_protocol_id",
+                    Value::Int((*message_protocol_id).into()),
+                ),
+                ("sequence_number", Value::Int(*sequence_number)),
+                ("kind", Value::Text(kind.name().to_string())),
+                ("length", Value::Int(*length as i64)),
+            ]),
+            Issue::Gap(range) | Issue::Duplicate(range) => record.extend([
+                (
+                    "message_protocol_id",
+                    Value::Int(range.stream.message_protocol_id.into()),
+                ),
+                ("channel_id", Value::Int(range.stream.channel_id.into())),
+                ("session_id", Value::Int(range.stream.session_id.into())),
+                ("first", Value::Int(range.first)),
+                ("count", Value::Int(range.count)),
+                ("send_time", Value::Time(range.send_time)),
+            ]),
+            Issue::CrossedQuote {
+                timestamp,
+                symbol,
+                bid_price,
+                ask_price,
+            } => record.extend([
+                ("timestamp", Value::Time(*timestamp)),
+                ("symbol", Value::Text(symbol.clone())),
+                ("bid_price", Value::Price(*bid_price)),
+                ("ask_price", Value::Price(*ask_price)),
+            ]),
+            Issue::OrphanTradeBreak {
+                timestamp,
+                symbol,
+                trade_id,
+            } => record.extend([
+                ("timestamp", Value::Time(*timestamp)),
+                ("symbol", Value::Text(symbol.clone())),
+                ("trade_id", Value::Int(*trade_id)),
+            ]),
+            Issue::IllegalTransition {
+                timestamp,
+                symbol,
+                description,
+                cause,
+            } => record.extend([
+                ("timestamp", Value::Time(*timestamp)),
+                ("symbol", Value::Text(symbol.clone())),
+                ("description", Value::Text(description.clone())),
+                ("cause", Value::Text(cause.name().to_string())),
+            ]),
+        }
+        record
+    }
+}
+
+/// The outcome of the validation of a HIST file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    pub segments: u64,
+    pub messages: u64,
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Writes the report as a JSON object, with the number of issues of each kind and the issues themselves
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut counts = Vec::<(&'static str, Value)>::new();
+        for issue in &self.issues {
+            match counts.iter_mut().find(|(name, _)| *name == issue.name()) {
+                Some((_, Value::Int(count))) => *count += 1,
+                _ => counts.push((issue.name(), Value::Int(1))),
+            }
+        }
+
+        write!(
+            writer,
+            "{{\"segments\":{},\"messages\":{},\"valid\":{},\"issue_counts\":",
+            self.segments,
+            self.messages,
+            self.is_valid()
+        )?;
+        write_json_object(&mut writer, &counts)?;
+        write!(writer, ",\"issues\":[")?;
+        for (index, issue) in self.issues.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write_json_object(&mut writer, &issue.to_record())?;
+        }
+        writeln!(writer, "]}}")
+    }
+}
+
+/// Checks the segments and messages of a HIST file: strict decoding, sequence gaps and duplicates, crossed quotes, trade
+/// breaks of unknown trades and illegal transitions of the trading states
+#[derive(Debug, Default)]
+pub struct Validator {
+    report: ValidationReport,
+    tracker: SequenceTracker,
+    quotes: HashMap<String, QuoteCondition>,
+    books: BookBuilder<String>,
+    crossed_books: HashSet<String>,
+    trade_ids: HashSet<i64>,
+    // TOPS and DEEP repeat the same transitions, which are checked separately
+    tops_transitions: TransitionChecker<String>,
+    deep_transitions: TransitionChecker<String>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a payload which isn't a valid IEX-TP segment
+    pub fn invalid_segment(&mut self, position: u64) {
+        self.report.issues.push(Issue::InvalidSegment { position });
+    }
+
+    pub fn validate_segment(&mut self, segment: &IexTp1Segment<'_>) {
+        self.report.segments += 1;
+        self.report.messages += segment.messages.len() as u64;
+        match self.tracker.update(segment) {
+            Sequencing::InOrder => {}
+            Sequencing::Gap(range) => {
+                match segment.message_protocol_id {
+                    message_protocol_ids::TOPS => self.tops_transitions.note_gap(),
+                    message_protocol_ids::DEEP_1_0 => self.deep_transitions.note_gap(),
+                    _ => {}
+                }
+                self.report.issues.push(Issue::Gap(range));
+            }
+            Sequencing::Duplicate(range) => self.report.issues.push(Issue::Duplicate(range)),
+        }
+
+        let protocol = segment.message_protocol_id;
+        for (sequence_number, message) in
+            (segment.first_message_sequence_no..).zip(&segment.messages)
+        {
+            let decoded = match protocol {
+                message_protocol_ids::TOPS => tops_1_6_message(message)
+                    .map(|(rest, decoded)| (rest.len(), Some(decoded), None)),
+                message_protocol_ids::DEEP_1_0 => deep_1_0_message(message)
+                    .map(|(rest, decoded)| (rest.len(), None, Some(decoded))),
+                _ => continue,
+            };
+
+            match decoded {
+                Err(_) => self.report.issues.push(Issue::InvalidMessage {
+                    message_protocol_id: protocol,
+                    sequence_number,
+                    kind: MessageKind::of(message),
+                    length: message.len(),
+                }),
+                Ok((rest, tops, deep)) => {
+                    if rest > 0 {
+                        self.report.issues.push(Issue::TrailingBytes {
+                            message_protocol_id: protocol,
+                            sequence_number,
+                            kind: MessageKind::of(message).expect("the message was decoded"),
+                            length: message.len(),
+                        });
+                    }
+                    if let Some(message) = tops {
+                        self.check_tops(message);
+                    }
+                    if let Some(message) = deep {
+                        self.check_deep(message);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_trade_break(&mut self, trade_break: TradeBreak<String>) {
+        if !self.trade_ids.contains(&trade_break.id) {
+            self.report.issues.push(Issue::OrphanTradeBreak {
+                timestamp: trade_break.timestamp,
+                symbol: trade_break.symbol,
+                trade_id: trade_break.id,
+            });
+        }
+    }
+
+    fn check_tops(&mut self, message: Tops1_6Message<String>) {
+        if let Some(anomaly) = self.tops_transitions.update(&message) {
+            self.report.issues.push(anomaly.into());
+        }
+        match message {
+            Tops1_6Message::QuoteUpdate(quote) => {
+                let condition = QuoteCondition::from(&Bbo::from(&quote));
+                let previous = self.quotes.insert(quote.symbol.clone(), condition);
+                if condition == QuoteCondition::Crossed && previous != Some(QuoteCondition::Crossed)
+                {
+                    self.report.issues.push(Issue::CrossedQuote {
+                        timestamp: quote.timestamp,
+                        symbol: quote.symbol,
+                        bid_price: quote.bid_price,
+                        ask_price: quote.ask_price,
+                    });
+                }
+            }
+            Tops1_6Message::TradeReport(trade) => {
+                self.trade_ids.insert(trade.id);
+            }
+            Tops1_6Message::TradeBreak(trade_break) => self.check_trade_break(trade_break),
+            _ => {}
+        }
+    }
+
+    fn check_deep(&mut self, message: Deep1_0Message<String>) {
+        if let Some(anomaly) = self.deep_transitions.update_deep(&message) {
+            self.report.issues.push(anomaly.into());
+        }
+        match message {
+            Deep1_0Message::PriceLevelUpdate(update) => {
+                self.books.apply(&update);
+                let book = self
+                    .books
+                    .book(&update.symbol)
+                    .expect("the book was just updated");
+                if book.is_in_transition() {
+                    return;
+                }
+
+                match (book.best_bid(), book.best_ask()) {
+                    (Some(bid), Some(ask)) if bid.price > ask.price => {
+                        if self.crossed_books.insert(update.symbol.clone()) {
+                            self.report.issues.push(Issue::CrossedQuote {
+                                timestamp: update.timestamp,
+                                symbol: update.symbol,
+                                bid_price: bid.price,
+                                ask_price: ask.price,
+                            });
+                        }
+                    }
+                    _ => {
+                        self.crossed_books.remove(&update.symbol);
+                    }
+                }
+            }
+            Deep1_0Message::TradeReport(trade) => {
+                self.trade_ids.insert(trade.id);
+            }
+            Deep1_0Message::TradeBreak(trade_break) => self.check_trade_break(trade_break),
+            _ => {}
+        }
+    }
+
+    pub fn finish(self) -> ValidationReport {
+        self.report
+    }
+}
+
+/// Validates a whole HIST file. Only I/O errors interrupt the validation.
+pub fn validate<R: Read>(mut reader: HistReader<R>) -> io::Result<ValidationReport> {
+    let mut validator = Validator::new();
+    loop {
+        match reader.next_segment() {
+            Ok(Some(captured)) => validator.validate_segment(&captured.segment),
+            Ok(None) => return Ok(validator.finish()),
+            Err(hist::Error::InvalidSegment { position }) => validator.invalid_segment(position),
+            Err(hist::Error::Io(e)) => return Err(e),
+            Err(hist::Error::InvalidMessage { .. }) => {
+                unreachable!("segments are read without decoding their messages")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hist::tests::{capture, segment},
+        tops::{TradingStatus, TradingStatusReason, TradingStatusType},
+    };
+
+    use super::*;
+
+    fn quote(bid_price: u8, ask_price: u8) -> Vec<u8> {
+        let mut quote = vec![0x51, 0x00];
+        quote.extend_from_slice(&1492448400000000000i64.to_le_bytes());
+        quote.extend_from_slice(b"ZIEXT   ");
+        quote.extend_from_slice(&100u32.to_le_bytes());
+        quote.extend_from_slice(&(i64::from(bid_price) * 10000).to_le_bytes());
+        quote.extend_from_slice(&(i64::from(ask_price) * 10000).to_le_bytes());
+        quote.extend_from_slice(&100u32.to_le_bytes());
+        quote
+    }
+
+    fn trade(message_type: u8, id: i64) -> Vec<u8> {
+        let mut trade = vec![message_type, 0x00];
+        trade.extend_from_slice(&1492448400000000000i64.to_le_bytes());
+        trade.extend_from_slice(b"ZIEXT   ");
+        trade.extend_from_slice(&100u32.to_le_bytes());
+        trade.extend_from_slice(&100_0000i64.to_le_bytes());
+        trade.extend_from_slice(&id.to_le_bytes());
+        trade
+    }
+
+    #[test]
+    fn issues() {
+        let mut trailing = trade(0x54, 2);
+        trailing.push(0);
+        let capture = capture(&[
+            segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[
+                    &quote(10, 11),
+                    &quote(12, 11),
+                    &quote(13, 11),
+                    &trade(0x54, 1),
+                ],
+            ),
+            vec![0xFF; 8],
+            segment(
+                message_protocol_ids::TOPS,
+                7,
+                &[&trade(0x42, 1), &trade(0x42, 2), &[0x54, 0x00], &trailing],
+            ),
+            segment(message_protocol_ids::TOPS, 9, &[&quote(10, 11)]),
+        ]);
+
+        let report = validate(HistReader::new(&capture[..]).unwrap()).unwrap();
+        assert_eq!((report.segments, report.messages), (3, 9));
+        let names = report.issues.iter().map(Issue::name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "crossed_quote",
+                "invalid_segment",
+                "gap",
+                "orphan_trade_break",
+                "invalid_message",
+                "trailing_bytes",
+                "duplicate",
+            ]
+        );
+        assert_eq!(
+            report.issues[3],
+            Issue::OrphanTradeBreak {
+                timestamp: DateTime::from_timestamp_nanos(1492448400000000000),
+                symbol: "ZIEXT".to_string(),
+                trade_id: 2,
+            }
+        );
+
+        let mut json = Vec::new();
+        report.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(
+            "{\"segments\":3,\"messages\":9,\"valid\":false,\"issue_counts\":{\"crossed_quote\":1,"
+        ));
+        assert!(json.contains(
+            "{\"issue\":\"gap\",\"message_protocol_id\":32771,\"channel_id\":1,\
+             \"session_id\":1116143616,\"first\":5,\"count\":2,"
+        ));
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        use TradingStatusType::*;
+
+        let status = |status, nanoseconds| {
+            TradingStatus {
+                status,
+                timestamp: DateTime::from_timestamp_nanos(nanoseconds),
+                symbol: "ZIEXT",
+                reason: TradingStatusReason::NotApplicable,
+            }
+            .encode()
+            .unwrap()
+        };
+        let capture = capture(&[
+            segment(
+                message_protocol_ids::TOPS,
+                1,
+                &[&status(Trading, 10), &status(OrderAcceptancePeriod, 20)],
+            ),
+            // The DEEP feed repeats the statuses, without an issue of its own
+            segment(message_protocol_ids::DEEP_1_0, 1, &[&status(Trading, 10)]),
+            segment(message_protocol_ids::TOPS, 5, &[&status(Paused, 30)]),
+        ]);
+
+        let report = validate(HistReader::new(&capture[..]).unwrap()).unwrap();
+        let issues = report
+            .issues
+            .iter()
+            .map(|issue| match issue {
+                Issue::IllegalTransition { cause, .. } => cause.name(),
+                issue => issue.name(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(issues, ["unexplained", "gap", "missed_messages"]);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{borrow::Borrow, collections::HashMap, f64::consts::FRAC_PI_2, hash::Hash};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    bbo::Bbo,
+    tops::{QuoteUpdate, Tops1_6Message, TradeReport},
+};
+
+/// The price series volatility is estimated from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PriceSource {
+    /// The prices of trade reports
+    #[default]
+    Trade,
+    /// The mid price of the IEX BBO, while quoted on both sides
+    MidQuote,
+}
+
+/// When the price series is sampled into returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    /// Samples every change of the price
+    Tick,
+    /// Samples the latest price at multiples of the interval since the POSIX epoch (previous-tick sampling)
+    Calendar(TimeDelta),
+}
+
+/// The realized variation of a symbol's log returns
+#[derive(Clone, Debug, PartialEq)]
+pub struct VolatilityEstimate<S> {
+    pub symbol: S,
+    /// The sum of squared log returns
+    pub realized_variance: f64,
+    /// The sum of products of adjacent absolute log returns, scaled by π/2. Unlike the realized variance, it isn't
+    /// affected by (rare) price jumps.
+    pub bipower_variation: f64,
+    /// The number of returns sampled
+    pub returns: u64,
+}
+
+impl<S> VolatilityEstimate<S> {
+    fn new(symbol: S) -> Self {
+        Self {
+            symbol,
+            realized_variance: 0.0,
+            bipower_variation: 0.0,
+            returns: 0,
+        }
+    }
+
+    /// The square root of the realized variance
+    pub fn realized_volatility(&self) -> f64 {
+        self.realized_variance.sqrt()
+    }
+
+    /// The part of the realized variance attributed to jumps
+    pub fn jump_variation(&self) -> f64 {
+        (self.realized_variance - self.bipower_variation).max(0.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SymbolState<S> {
+    estimate: VolatilityEstimate<S>,
+    /// The latest price observed
+    price: Option<f64>,
+    /// The price at the latest sample
+    sampled: Option<f64>,
+    next_sample: Option<DateTime<Utc>>,
+    previous_absolute_return: Option<f64>,
+}
+
+impl<S> SymbolState<S> {
+    fn sample(&mut self, price: f64) {
+        if let Some(sampled) = self.sampled {
+            let absolute_return = (price / sampled).ln().abs();
+            self.estimate.realized_variance += absolute_return * absolute_return;
+            if let Some(previous) = self.previous_absolute_return {
+                self.estimate.bipower_variation += FRAC_PI_2 * previous * absolute_return;
+            }
+            self.previous_absolute_return = Some(absolute_return);
+            self.estimate.returns += 1;
+        }
+        self.sampled = Some(price);
+    }
+
+    /// Takes the calendar samples due before `now`
+    fn advance(&mut self, interval: TimeDelta, now: DateTime<Utc>) {
+        let interval = interval.num_nanoseconds().unwrap_or(i64::MAX);
+        let nanos = now.timestamp_nanos_opt().unwrap_or_default();
+        // The first sample at or after `now`
+        let next = DateTime::from_timestamp_nanos(nanos + (-nanos).rem_euclid(interval));
+
+        if let (Some(due), Some(price)) = (self.next_sample, self.price) {
+            if due < now {
+                self.sample(price);
+
+                // The price didn't change during the later samples
+                let skipped = (next - due).num_nanoseconds().unwrap_or(i64::MAX) / interval - 1;
+                if skipped > 0 {
+                    self.estimate.returns += skipped as u64;
+                    self.previous_absolute_return = Some(0.0);
+                }
+            }
+        }
+        if self.next_sample.is_none_or(|due| due < now) {
+            self.next_sample = Some(next);
+        }
+    }
+}
+
+/// Estimates the realized variance and bipower variation of every symbol, streaming
+#[derive(Clone, Debug)]
+pub struct VolatilityEstimator<S> {
+    source: PriceSource,
+    sampling: Sampling,
+    symbols: HashMap<S, SymbolState<S>>,
+    last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl<S> VolatilityEstimator<S>
+where
+    S: Eq + Hash + Clone,
+{
+    pub fn new(source: PriceSource, sampling: Sampling) -> Self {
+        if let Sampling::Calendar(interval) = sampling {
+            assert!(
+                interval > TimeDelta::zero(),
+                "sampling interval must be positive"
+            );
+        }
+
+        Self {
+            source,
+            sampling,
+            symbols: HashMap::new(),
+            last_timestamp: None,
+        }
+    }
+
+    fn observe(&mut self, symbol: &S, timestamp: DateTime<Utc>, price: f64) {
+        let state = self
+            .symbols
+            .entry(symbol.clone())
+            .or_insert_with(|| SymbolState {
+                estimate: VolatilityEstimate::new(symbol.clone()),
+                price: None,
+                sampled: None,
+                next_sample: None,
+                previous_absolute_return: None,
+            });
+
+        match self.sampling {
+            Sampling::Tick => {
+                if state.sampled != Some(price) {
+                    state.sample(price);
+                }
+            }
+            Sampling::Calendar(interval) => state.advance(interval, timestamp),
+        }
+        state.price = Some(price);
+    }
+
+    /// Applies a trade report. Ignored unless the trade prices are the source.
+    pub fn apply_trade(&mut self, trade: &TradeReport<S>) {
+        if self.source == PriceSource::Trade {
+            self.observe(&trade.symbol, trade.timestamp, trade.price);
+        }
+    }
+
+    /// Applies a quote update. Ignored unless the mid prices are the source, or if the quote is one-sided.
+    pub fn apply_quote(&mut self, quote: &QuoteUpdate<S>) {
+        if self.source == PriceSource::MidQuote {
+            if let Some(mid) = Bbo::from(quote).mid() {
+                self.observe(&quote.symbol, quote.timestamp, mid);
+            }
+        }
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        match message {
+            Tops1_6Message::TradeReport(trade) => self.apply_trade(trade),
+            Tops1_6Message::QuoteUpdate(quote) => self.apply_quote(quote),
+            _ => {}
+        }
+        if let Some(timestamp) = message.timestamp() {
+            self.last_timestamp = Some(timestamp);
+        }
+    }
+
+    /// The estimate of a symbol so far. With calendar sampling, it only includes the samples taken before the
+    /// latest price of the symbol.
+    pub fn get<Q>(&self, symbol: &Q) -> Option<&VolatilityEstimate<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.symbols.get(symbol).map(|state| &state.estimate)
+    }
+
+    /// Returns the estimates of all symbols seen. With calendar sampling, the samples are taken up to the last message
+    /// seen.
+    pub fn finish(self) -> Vec<VolatilityEstimate<S>> {
+        let end = self.last_timestamp;
+        let sampling = self.sampling;
+        self.symbols
+            .into_values()
+            .map(|mut state| {
+                if let (Sampling::Calendar(interval), Some(end)) = (sampling, end) {
+                    state.advance(interval, end);
+                }
+                state.estimate
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::fixtures;
+
+    use super::*;
+
+    fn trade(timestamp: i64, price: f64) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradeReport(fixtures::trade("ZIEXT", timestamp, price, 100))
+    }
+
+    #[test]
+    fn tick_sampling() {
+        let mut estimator = VolatilityEstimator::new(PriceSource::Trade, Sampling::Tick);
+        for (timestamp, price) in [(0, 10.0), (1, 11.0), (2, 11.0), (3, 10.0)] {
+            estimator.update(&trade(timestamp, price));
+        }
+
+        let estimate = estimator.get("ZIEXT").unwrap();
+        let r = (1.1f64).ln();
+        assert_eq!(estimate.returns, 2);
+        assert_float_eq!(estimate.realized_variance, 2.0 * r * r, abs <= 1e-12);
+        assert_float_eq!(estimate.bipower_variation, FRAC_PI_2 * r * r, abs <= 1e-12);
+        assert_float_eq!(
+            estimate.realized_volatility(),
+            2f64.sqrt() * r,
+            abs <= 1e-12
+        );
+    }
+
+    #[test]
+    fn calendar_sampling() {
+        let mut estimator = VolatilityEstimator::new(
+            PriceSource::Trade,
+            Sampling::Calendar(TimeDelta::nanoseconds(10)),
+        );
+        // Sampled at 10, 20, 30 and 40 as 10, 11, 11 and 10
+        for (timestamp, price) in [(5, 10.0), (12, 10.5), (15, 11.0), (35, 12.0), (40, 10.0)] {
+            estimator.update(&trade(timestamp, price));
+        }
+        estimator.update(&Tops1_6Message::QuoteUpdate(fixtures::quote(
+            "ZVZZT", 45, 10.0, 10.1,
+        )));
+
+        let estimates = estimator.finish();
+        assert_eq!(estimates.len(), 1);
+        let r = (1.1f64).ln();
+        assert_eq!(estimates[0].returns, 3);
+        assert_float_eq!(estimates[0].realized_variance, 2.0 * r * r, abs <= 1e-12);
+        assert_float_eq!(estimates[0].bipower_variation, 0.0, abs <= 1e-12);
+        assert_float_eq!(estimates[0].jump_variation(), 2.0 * r * r, abs <= 1e-12);
+    }
+
+    #[test]
+    fn mid_quotes() {
+        let quote = |timestamp, bid_price, ask_size| {
+            Tops1_6Message::QuoteUpdate(QuoteUpdate {
+                ask_size,
+                ..fixtures::quote("ZIEXT", timestamp, bid_price, 10.10)
+            })
+        };
+
+        let mut estimator = VolatilityEstimator::new(PriceSource::MidQuote, Sampling::Tick);
+        estimator.update(&quote(0, 10.00, 100));
+        estimator.update(&quote(1, 9.00, 0));
+        estimator.update(&quote(2, 10.02, 100));
+        estimator.update(&trade(3, 20.0));
+
+        let estimate = estimator.get("ZIEXT").unwrap();
+        assert_eq!(estimate.returns, 1);
+        assert_float_eq!(
+            estimate.realized_volatility(),
+            (10.06f64 / 10.05).ln(),
+            abs <= 1e-12
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+use crate::{
+    tops::{MarketSession, Tops1_6Message, TradeReport},
+    utils::{key_price, price_key},
+};
+
+/// The volume traded within a price range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceBucket {
+    /// The lowest price of the bucket (inclusive)
+    pub low: f64,
+    /// The highest price of the bucket (exclusive)
+    pub high: f64,
+    pub volume: u64,
+    pub trade_count: u64,
+}
+
+/// The volume-by-price histogram of a symbol during a market session
+#[derive(Clone, Debug)]
+pub struct VolumeProfile<S> {
+    pub symbol: S,
+    pub session: MarketSession,
+    bucket_size: i64,
+    // Volume and trade count by bucket index
+    buckets: BTreeMap<i64, (u64, u64)>,
+}
+
+impl<S> VolumeProfile<S> {
+    fn bucket(&self, index: i64, (volume, trade_count): (u64, u64)) -> PriceBucket {
+        PriceBucket {
+            low: key_price(index * self.bucket_size),
+            high: key_price((index + 1) * self.bucket_size),
+            volume,
+            trade_count,
+        }
+    }
+
+    /// The non-empty buckets, from the lowest price to the highest
+    pub fn buckets(&self) -> impl Iterator<Item = PriceBucket> + '_ {
+        self.buckets
+            .iter()
+            .map(|(&index, &counts)| self.bucket(index, counts))
+    }
+
+    pub fn total_volume(&self) -> u64 {
+        self.buckets.values().map(|&(volume, _)| volume).sum()
+    }
+
+    /// The bucket with the highest volume (the lowest priced one in case of a tie)
+    pub fn point_of_control(&self) -> Option<PriceBucket> {
+        self.buckets
+            .iter()
+            .rev()
+            .max_by_key(|(_, &(volume, _))| volume)
+            .map(|(&index, &counts)| self.bucket(index, counts))
+    }
+}
+
+/// Aggregates trade reports into per-symbol, per-session volume profiles
+#[derive(Clone, Debug)]
+pub struct VolumeProfileBuilder<S> {
+    bucket_size: i64,
+    profiles: HashMap<S, HashMap<MarketSession, VolumeProfile<S>>>,
+}
+
+impl<S> VolumeProfileBuilder<S>
+where
+    S: Eq + Hash + Clone,
+{
+    /// Creates a builder with buckets of the given size (in dollars), aligned to multiples of it
+    pub fn new(bucket_size: f64) -> Self {
+        let bucket_size = price_key(bucket_size);
+        assert!(bucket_size > 0, "bucket size must be at least $0.0001");
+
+        Self {
+            bucket_size,
+            profiles: HashMap::new(),
+        }
+    }
+
+    pub fn apply(&mut self, trade: &TradeReport<S>) {
+        let session = if trade.sale_condition.extended_hours {
+            MarketSession::OutOfHours
+        } else {
+            MarketSession::Regular
+        };
+        let bucket_size = self.bucket_size;
+        let profile = self
+            .profiles
+            .entry(trade.symbol.clone())
+            .or_default()
+            .entry(session)
+            .or_insert_with(|| VolumeProfile {
+                symbol: trade.symbol.clone(),
+                session,
+                bucket_size,
+                buckets: BTreeMap::new(),
+            });
+
+        let (volume, trade_count) = profile
+            .buckets
+            .entry(price_key(trade.price).div_euclid(bucket_size))
+            .or_default();
+        *volume += u64::from(trade.size);
+        *trade_count += 1;
+    }
+
+    pub fn update(&mut self, message: &Tops1_6Message<S>) {
+        if let Tops1_6Message::TradeReport(trade) = message {
+            self.apply(trade);
+        }
+    }
+
+    pub fn profile<Q>(&self, symbol: &Q, session: MarketSession) -> Option<&VolumeProfile<S>>
+    where
+        S: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.profiles.get(symbol)?.get(&session)
+    }
+
+    pub fn finish(self) -> Vec<VolumeProfile<S>> {
+        self.profiles
+            .into_values()
+            .flat_map(HashMap::into_values)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use float_eq::assert_float_eq;
+
+    use crate::{fixtures, tops::SaleCondition};
+
+    use super::*;
+
+    fn trade(price: f64, size: u32, extended_hours: bool) -> Tops1_6Message<&'static str> {
+        Tops1_6Message::TradeReport(TradeReport {
+            sale_condition: SaleCondition {
+                extended_hours,
+                ..SaleCondition::default()
+            },
+            ..fixtures::trade("ZIEXT", 0, price, size)
+        })
+    }
+
+    #[test]
+    fn histogram() {
+        let mut builder = VolumeProfileBuilder::new(0.05);
+
+        builder.update(&trade(10.00, 100, false));
+        builder.update(&trade(10.04, 200, false));
+        builder.update(&trade(10.05, 500, false));
+        builder.update(&trade(10.21, 100, false));
+        builder.update(&trade(10.21, 100, true));
+
+        let profile = builder.profile("ZIEXT", MarketSession::Regular).unwrap();
+        let buckets = profile.buckets().collect::<Vec<_>>();
+        assert_eq!(buckets.len(), 3);
+        assert_float_eq!(buckets[0].low, 10.00, abs <= 1e-9);
+        assert_float_eq!(buckets[0].high, 10.05, abs <= 1e-9);
+        assert_eq!(buckets[0].volume, 300);
+        assert_eq!(buckets[0].trade_count, 2);
+        assert_float_eq!(buckets[2].low, 10.20, abs <= 1e-9);
+        assert_eq!(profile.total_volume(), 900);
+        assert_float_eq!(profile.point_of_control().unwrap().low, 10.05, abs <= 1e-9);
+
+        let extended = builder.profile("ZIEXT", MarketSession::OutOfHours).unwrap();
+        assert_eq!(extended.total_volume(), 100);
+    }
+}
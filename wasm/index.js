@@ -0,0 +1,167 @@
+// Reads the TOPS messages of HIST files with the C API of the crate built as a WebAssembly module, e.g. in the
+// browser, which has no file system: `cargo rustc --release --lib --target wasm32-unknown-unknown --features ffi
+// --crate-type cdylib` builds target/wasm32-unknown-unknown/release/iex_parser.wasm. Files are copied into the memory
+// of the module, and their messages are decoded from the IexMessage structures of include/iex_parser.h.
+
+const IEX_OK = 0;
+const IEX_END = 1;
+
+const KINDS = {
+  S: 'system_event',
+  D: 'security_directory',
+  H: 'trading_status',
+  I: 'retail_liquidity_indicator',
+  O: 'operational_halt_status',
+  P: 'short_sale_price_test_status',
+  Q: 'quote_update',
+  T: 'trade_report',
+  X: 'official_price',
+  B: 'trade_break',
+  A: 'auction_information',
+};
+
+// The offsets of the fields of IexMessage, and of the fields of each body within IexMessageBody
+const KIND = 0;
+const TIMESTAMP = 8;
+const SYMBOL = 16;
+const BODY = 32;
+
+const decoder = new TextDecoder();
+
+function string(view, offset, length) {
+  const bytes = new Uint8Array(view.buffer, view.byteOffset + offset, length);
+  const end = bytes.indexOf(0);
+  return decoder.decode(end === -1 ? bytes : bytes.subarray(0, end));
+}
+
+function code(view, offset) {
+  return String.fromCharCode(view.getUint8(offset));
+}
+
+function bool(view, offset) {
+  return view.getUint8(offset) !== 0;
+}
+
+function trade(view, body) {
+  return {
+    size: view.getUint32(body + 8, true),
+    price: view.getFloat64(body + 16, true),
+    id: view.getBigInt64(body + 24, true),
+    intermarket_sweep: bool(view, body),
+    extended_hours: bool(view, body + 1),
+    odd_lot: bool(view, body + 2),
+    trade_through_exempt: bool(view, body + 3),
+    single_price: bool(view, body + 4),
+  };
+}
+
+const BODIES = {
+  S: (view, body) => ({ event_type: code(view, body) }),
+  D: (view, body) => ({
+    test_security: bool(view, body),
+    when_issued: bool(view, body + 1),
+    etp: bool(view, body + 2),
+    round_lot_size: view.getUint32(body + 4, true),
+    adjusted_poc_price: view.getFloat64(body + 8, true),
+    luld_tier: view.getUint8(body + 3),
+  }),
+  H: (view, body) => ({ status: code(view, body), reason: string(view, body + 1, 5) }),
+  I: (view, body) => ({ indicator: code(view, body) }),
+  O: (view, body) => ({ status: code(view, body) }),
+  P: (view, body) => ({ in_effect: bool(view, body), detail: code(view, body + 1) }),
+  Q: (view, body) => ({
+    available: bool(view, body),
+    out_of_hours: bool(view, body + 1),
+    bid_size: view.getUint32(body + 4, true),
+    bid_price: view.getFloat64(body + 8, true),
+    ask_size: view.getUint32(body + 16, true),
+    ask_price: view.getFloat64(body + 24, true),
+  }),
+  T: trade,
+  X: (view, body) => ({ price_type: code(view, body), official_price: view.getFloat64(body + 8, true) }),
+  B: trade,
+  A: (view, body) => ({
+    auction_type: code(view, body),
+    paired_shares: view.getUint32(body + 4, true),
+    reference_price: view.getFloat64(body + 16, true),
+    indicative_clearing_price: view.getFloat64(body + 24, true),
+    imbalance_shares: view.getUint32(body + 8, true),
+    imbalance_side: code(view, body + 1),
+    extension_number: view.getUint8(body + 2),
+    scheduled_auction_time: view.getBigInt64(body + 32, true),
+    auction_book_clearing_price: view.getFloat64(body + 40, true),
+    collar_reference_price: view.getFloat64(body + 48, true),
+    lower_auction_collar: view.getFloat64(body + 56, true),
+    upper_auction_collar: view.getFloat64(body + 64, true),
+  }),
+};
+
+/**
+ * Decodes the IexMessage at `offset` in `view` into an object with a `kind` (e.g. `quote_update`) and the fields of
+ * the message, named as in the specification in snake case. Timestamps are BigInts of nanoseconds since the Unix
+ * epoch, codes are one-character strings, and prices are numbers.
+ */
+export function decodeMessage(view, offset = 0) {
+  const kind = code(view, offset + KIND);
+  const message = { kind: KINDS[kind], timestamp: view.getBigInt64(offset + TIMESTAMP, true) };
+  if (kind !== 'S') {
+    message.symbol = string(view, offset + SYMBOL, 9);
+  }
+  return Object.assign(message, BODIES[kind](view, offset + BODY));
+}
+
+export class TopsParser {
+  /** Instantiates the module, from its bytes or the response fetching them, or a promise of either */
+  static async load(source) {
+    const resolved = await source;
+    const { instance } = typeof Response !== 'undefined' && resolved instanceof Response
+      ? await WebAssembly.instantiateStreaming(resolved)
+      : await WebAssembly.instantiate(resolved);
+    return new TopsParser(instance.exports);
+  }
+
+  constructor(exports) {
+    this.api = exports;
+  }
+
+  /**
+   * Iterates over the TOPS messages of an uncompressed HIST file held in a Uint8Array, as objects decoded by
+   * `decodeMessage`. Throws if the file isn't a capture, or when reading an invalid segment.
+   */
+  *readMessages(file) {
+    const { api } = this;
+    const buffer = api.iex_buffer_alloc(file.length);
+    new Uint8Array(api.memory.buffer, buffer, file.length).set(file);
+    const reader = api.iex_reader_open_buffer(buffer, file.length);
+    api.iex_buffer_free(buffer, file.length);
+    if (reader === 0) {
+      throw new Error(this.lastError());
+    }
+
+    const size = api.iex_message_size();
+    const message = api.iex_buffer_alloc(size);
+    try {
+      for (;;) {
+        const status = api.iex_reader_next(reader, message);
+        if (status === IEX_END) {
+          return;
+        }
+        if (status !== IEX_OK) {
+          throw new Error(this.lastError());
+        }
+        // Growing the memory detaches its previous buffer, so the view is taken anew for every message
+        yield decodeMessage(new DataView(api.memory.buffer), message);
+      }
+    } finally {
+      api.iex_buffer_free(message, size);
+      api.iex_reader_close(reader);
+    }
+  }
+
+  /** Describes the last failure of the module */
+  lastError() {
+    const view = new DataView(this.api.memory.buffer);
+    const pointer = this.api.iex_last_error();
+    return string(view, pointer, view.byteLength - pointer);
+  }
+}
@@ -0,0 +1,41 @@
+// Run with `npm test` after `cargo build --features cli` and building the module (see index.js)
+
+import assert from 'node:assert';
+import { execFileSync } from 'node:child_process';
+import fs from 'node:fs';
+import os from 'node:os';
+import path from 'node:path';
+import { test } from 'node:test';
+import { fileURLToPath } from 'node:url';
+
+import { TopsParser } from './index.js';
+
+const repository = path.join(path.dirname(fileURLToPath(import.meta.url)), '..');
+const parser = await TopsParser.load(
+  fs.readFileSync(path.join(repository, 'target', 'wasm32-unknown-unknown', 'release', 'iex_parser.wasm')),
+);
+
+const directory = fs.mkdtempSync(path.join(os.tmpdir(), 'iex-parser-wasm-'));
+const capture = path.join(directory, 'corpus.pcap');
+execFileSync(path.join(repository, 'target', 'debug', 'iex-corpus'), [
+  '--size', '200K', '--symbols', '20', '--output', capture,
+], { stdio: 'ignore' });
+
+test('reads every message', () => {
+  const messages = [...parser.readMessages(fs.readFileSync(capture))];
+  assert.deepStrictEqual(messages[0], { kind: 'system_event', timestamp: 1492435800000000000n, event_type: 'O' });
+  assert.strictEqual(messages.at(-1).event_type, 'C');
+
+  const quotes = messages.filter((message) => message.kind === 'quote_update');
+  const trades = messages.filter((message) => message.kind === 'trade_report');
+  assert.ok(quotes.length > trades.length && trades.length > 0);
+  assert.ok(quotes.every((quote) => quote.symbol && quote.bid_price < quote.ask_price));
+  const timestamps = messages.map((message) => message.timestamp);
+  assert.deepStrictEqual(timestamps, [...timestamps].sort((a, b) => (a < b ? -1 : a > b ? 1 : 0)));
+});
+
+test('fails on invalid files', () => {
+  assert.throws(() => [...parser.readMessages(new Uint8Array(100))], /capture|pcap/i);
+});
+
+test.after(() => fs.rmSync(directory, { recursive: true }));